@@ -0,0 +1,277 @@
+use nagi_errors::*;
+use nagi_syntax_tree::ast::*;
+use nagi_syntax_tree::span::Span;
+use nagi_syntax_tree::token::*;
+
+use std::collections::HashSet;
+
+// 組み込みマクロの引数の数と、書式文字列が引数と合っているかを調べる
+// エラーはマクロの呼び出しの位置を示す
+pub fn check_builtin_macro(
+    builtin_macro: BuiltinMacro,
+    call_params: &[ASTNode],
+    span: Option<Span>,
+) -> Result<(), Error> {
+    let position = span.map(|span| span.start_position());
+    let name = builtin_macro.name();
+
+    let required = match builtin_macro {
+        BuiltinMacro::Println | BuiltinMacro::Eprintln | BuiltinMacro::Panic => 0,
+        BuiltinMacro::AssertEq | BuiltinMacro::AssertNe => 2,
+        _ => 1,
+    };
+    if call_params.len() < required {
+        return Err(macro_error(
+            position,
+            MacroError::InvalidBuiltinArguments,
+            format!("`{}!`には{}個以上の引数が必要です", name, required),
+        ));
+    }
+
+    let Some(index) = builtin_macro.format_string_index() else {
+        // include_str!とinclude_bytes!はファイル名だけを取る
+        if call_params.len() != 1 || string_literal(&call_params[0]).is_none() {
+            return Err(macro_error(
+                position,
+                MacroError::InvalidBuiltinArguments,
+                format!("`{}!`の引数はファイル名の文字列リテラル1つです", name),
+            ));
+        }
+        return Ok(());
+    };
+
+    // println!()やassert!(a)のように書式が無ければ調べることは無い
+    let Some(format_string) = call_params.get(index) else {
+        return Ok(());
+    };
+    let Some(format_string) = string_literal(format_string) else {
+        return Err(macro_error(
+            position,
+            MacroError::InvalidFormatString,
+            format!("`{}!`の書式は文字列リテラルでなければなりません", name),
+        ));
+    };
+
+    check_format(name, format_string, &call_params[index + 1..], position)
+}
+
+// 書式文字列のプレースホルダ
+enum Placeholder {
+    Next,          // {}
+    Index(usize),  // {0}
+    Named(String), // {name}
+}
+
+fn check_format(
+    name: &str,
+    format_string: &str,
+    arguments: &[ASTNode],
+    position: Option<SourcePosition>,
+) -> Result<(), Error> {
+    // 位置引数の後に名前付き引数(`name = expr`)を書く
+    let mut positional_count = 0;
+    let mut named_arguments = vec![];
+    for argument in arguments {
+        match named_argument(argument) {
+            Some(argument_name) => named_arguments.push(argument_name),
+            None if named_arguments.is_empty() => positional_count += 1,
+            None => {
+                return Err(macro_error(
+                    position,
+                    MacroError::FormatArgumentMismatch,
+                    format!("`{}!`で名前付き引数の後に位置引数は書けません", name),
+                ))
+            }
+        }
+    }
+
+    let mut next_index = 0;
+    let mut used_indexes = HashSet::new();
+    let mut used_names = HashSet::new();
+    for placeholder in parse_format_string(format_string).map_err(|text| {
+        macro_error(
+            position,
+            MacroError::InvalidFormatString,
+            format!("`{}!`の書式文字列が正しくありません: {}", name, text),
+        )
+    })? {
+        let index = match placeholder {
+            Placeholder::Next => {
+                next_index += 1;
+                next_index - 1
+            }
+            Placeholder::Index(index) => index,
+            // 名前付き引数が無ければスコープの変数を使う
+            Placeholder::Named(placeholder_name) => {
+                used_names.insert(placeholder_name);
+                continue;
+            }
+        };
+
+        if index >= positional_count {
+            return Err(macro_error(
+                position,
+                MacroError::FormatArgumentMismatch,
+                format!(
+                    "`{}!`の書式文字列には{}個の引数が必要ですが、{}個しかありません",
+                    name,
+                    index + 1,
+                    positional_count
+                ),
+            ));
+        }
+        used_indexes.insert(index);
+    }
+
+    if used_indexes.len() < positional_count {
+        return Err(macro_error(
+            position,
+            MacroError::FormatArgumentMismatch,
+            format!(
+                "`{}!`の引数が{}個ありますが、書式文字列で使われているのは{}個です",
+                name,
+                positional_count,
+                used_indexes.len()
+            ),
+        ));
+    }
+    if let Some(unused) = named_arguments
+        .iter()
+        .find(|argument_name| !used_names.contains(*argument_name))
+    {
+        return Err(macro_error(
+            position,
+            MacroError::FormatArgumentMismatch,
+            format!(
+                "`{}!`の名前付き引数`{}`は書式文字列で使われていません",
+                name, unused
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+// `{{`と`}}`は括弧そのもの
+// `{`から`}`の間は、引数(番号か名前)と`:`の後の書式指定
+fn parse_format_string(format_string: &str) -> Result<Vec<Placeholder>, String> {
+    let mut placeholders = vec![];
+    let mut chars = format_string.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+            }
+            '}' => return Err("対応する`{`が無い`}`があります".to_string()),
+            '{' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some('{') | None => return Err("`{`が閉じられていません".to_string()),
+                        Some(c) => inner.push(c),
+                    }
+                }
+
+                let argument = inner.split(':').next().unwrap().trim();
+                let placeholder = if argument.is_empty() {
+                    Placeholder::Next
+                } else if let Ok(index) = argument.parse() {
+                    Placeholder::Index(index)
+                } else if is_identifier(argument) {
+                    Placeholder::Named(argument.to_string())
+                } else {
+                    return Err(format!("`{{{}}}`は引数を指定できません", inner));
+                };
+                placeholders.push(placeholder);
+            }
+            _ => (),
+        }
+    }
+
+    Ok(placeholders)
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+        && text != "_"
+}
+
+// 式を包んでいるだけのノードを外す
+fn unwrap_expression(ast: &ASTNode) -> &ASTNode {
+    match &ast.node_kind {
+        ASTNodeKind::Expression { expression }
+        | ASTNodeKind::ExpressionWithoutBlock { expression, .. }
+        | ASTNodeKind::PathExpression { expression } => unwrap_expression(expression),
+        _ => ast,
+    }
+}
+
+// 引用符の中身
+fn string_literal(ast: &ASTNode) -> Option<&str> {
+    let ASTNodeKind::Literal { literal } = &unwrap_expression(ast).node_kind else {
+        return None;
+    };
+    if literal.literal_kind != LiteralKind::Str {
+        return None;
+    }
+
+    literal
+        .symbol
+        .strip_prefix('"')
+        .and_then(|symbol| symbol.strip_suffix('"'))
+}
+
+// `name = expr`の形の引数ならその名前
+fn named_argument(ast: &ASTNode) -> Option<String> {
+    let ASTNodeKind::BinaryOperator {
+        operator: BinaryOperator::Assign,
+        left,
+        ..
+    } = &unwrap_expression(ast).node_kind
+    else {
+        return None;
+    };
+    let ASTNodeKind::PathInExpression {
+        path_expr_segment,
+        repeat_path_expr_segment,
+    } = &unwrap_expression(left).node_kind
+    else {
+        return None;
+    };
+    let ASTNodeKind::PathExprSegment {
+        path_ident_segment,
+        generic_args: None,
+    } = &path_expr_segment.node_kind
+    else {
+        return None;
+    };
+    let ASTNodeKind::Factor {
+        token: Token::Identifier(identifier),
+    } = &path_ident_segment.node_kind
+    else {
+        return None;
+    };
+
+    repeat_path_expr_segment
+        .is_empty()
+        .then(|| identifier.clone())
+}
+
+fn macro_error(
+    position: Option<SourcePosition>,
+    macro_error: MacroError,
+    error_text: String,
+) -> Error {
+    Error {
+        error_kind: ErrorKind::Macro(macro_error),
+        error_text,
+        position,
+        notes: vec![],
+    }
+}
@@ -0,0 +1,129 @@
+use nagi_syntax_tree::token::*;
+
+// 演算子に対応するトレイト
+// 組み込みの型以外に演算子を使うと、このトレイトのimplからメソッドを探す
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LangItem {
+    pub trait_name: &'static str,
+    pub method_name: &'static str,
+}
+
+const fn lang_item(trait_name: &'static str, method_name: &'static str) -> LangItem {
+    LangItem {
+        trait_name,
+        method_name,
+    }
+}
+
+pub const ADD: LangItem = lang_item("Add", "add");
+pub const SUB: LangItem = lang_item("Sub", "sub");
+pub const MUL: LangItem = lang_item("Mul", "mul");
+pub const DIV: LangItem = lang_item("Div", "div");
+pub const REM: LangItem = lang_item("Rem", "rem");
+pub const BIT_AND: LangItem = lang_item("BitAnd", "bitand");
+pub const BIT_OR: LangItem = lang_item("BitOr", "bitor");
+pub const BIT_XOR: LangItem = lang_item("BitXor", "bitxor");
+pub const SHL: LangItem = lang_item("Shl", "shl");
+pub const SHR: LangItem = lang_item("Shr", "shr");
+
+pub const ADD_ASSIGN: LangItem = lang_item("AddAssign", "add_assign");
+pub const SUB_ASSIGN: LangItem = lang_item("SubAssign", "sub_assign");
+pub const MUL_ASSIGN: LangItem = lang_item("MulAssign", "mul_assign");
+pub const DIV_ASSIGN: LangItem = lang_item("DivAssign", "div_assign");
+pub const REM_ASSIGN: LangItem = lang_item("RemAssign", "rem_assign");
+pub const BIT_AND_ASSIGN: LangItem = lang_item("BitAndAssign", "bitand_assign");
+pub const BIT_OR_ASSIGN: LangItem = lang_item("BitOrAssign", "bitor_assign");
+pub const BIT_XOR_ASSIGN: LangItem = lang_item("BitXorAssign", "bitxor_assign");
+
+pub const NEG: LangItem = lang_item("Neg", "neg");
+pub const NOT: LangItem = lang_item("Not", "not");
+
+pub const INDEX: LangItem = lang_item("Index", "index");
+
+pub const PARTIAL_EQ: LangItem = lang_item("PartialEq", "eq");
+pub const PARTIAL_ORD: LangItem = lang_item("PartialOrd", "partial_cmp");
+
+const ALL: [LangItem; 23] = [
+    ADD,
+    SUB,
+    MUL,
+    DIV,
+    REM,
+    BIT_AND,
+    BIT_OR,
+    BIT_XOR,
+    SHL,
+    SHR,
+    ADD_ASSIGN,
+    SUB_ASSIGN,
+    MUL_ASSIGN,
+    DIV_ASSIGN,
+    REM_ASSIGN,
+    BIT_AND_ASSIGN,
+    BIT_OR_ASSIGN,
+    BIT_XOR_ASSIGN,
+    NEG,
+    NOT,
+    INDEX,
+    PARTIAL_EQ,
+    PARTIAL_ORD,
+];
+
+// `std::ops::Add`のようにパスで書いても最後の名前で探す
+pub fn from_trait_name(trait_name: &str) -> Option<LangItem> {
+    ALL.into_iter().find(|item| item.trait_name == trait_name)
+}
+
+// `&&`、`||`、`=`、範囲と`<-`はオーバーロードできない
+pub fn binary_operator(operator: &BinaryOperator) -> Option<LangItem> {
+    let item = match operator {
+        BinaryOperator::Add => ADD,
+        BinaryOperator::Sub => SUB,
+        BinaryOperator::Mul => MUL,
+        BinaryOperator::Div => DIV,
+        BinaryOperator::Mod => REM,
+        BinaryOperator::And => BIT_AND,
+        BinaryOperator::Or => BIT_OR,
+        BinaryOperator::Xor => BIT_XOR,
+        BinaryOperator::LeftShift => SHL,
+        BinaryOperator::RightShiht => SHR,
+        BinaryOperator::AddAssign => ADD_ASSIGN,
+        BinaryOperator::SubAssign => SUB_ASSIGN,
+        BinaryOperator::MulAssign => MUL_ASSIGN,
+        BinaryOperator::DivAssign => DIV_ASSIGN,
+        BinaryOperator::ModAssign => REM_ASSIGN,
+        BinaryOperator::AndAssign => BIT_AND_ASSIGN,
+        BinaryOperator::OrAssign => BIT_OR_ASSIGN,
+        BinaryOperator::XorAssign => BIT_XOR_ASSIGN,
+        BinaryOperator::Equal | BinaryOperator::NotEqual => PARTIAL_EQ,
+        BinaryOperator::GreaterThan
+        | BinaryOperator::LessThan
+        | BinaryOperator::GreaterThanOrEqual
+        | BinaryOperator::LessThanOrEqual => PARTIAL_ORD,
+        BinaryOperator::LazyAnd
+        | BinaryOperator::LazyOr
+        | BinaryOperator::Assign
+        | BinaryOperator::Range
+        | BinaryOperator::RangeInclusive
+        | BinaryOperator::LeftAllow => return None,
+    };
+
+    Some(item)
+}
+
+pub fn unary_operator(operator: &UnaryOperator) -> LangItem {
+    match operator {
+        UnaryOperator::Neg => NEG,
+        UnaryOperator::Not => NOT,
+    }
+}
+
+// 比較演算子の結果はメソッドの戻り値によらずbool
+pub fn is_comparison(item: LangItem) -> bool {
+    item == PARTIAL_EQ || item == PARTIAL_ORD
+}
+
+// 複合代入演算子は値を返さない
+pub fn is_assign(item: LangItem) -> bool {
+    item.trait_name.ends_with("Assign")
+}
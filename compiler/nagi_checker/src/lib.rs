@@ -1,50 +1,142 @@
 use nagi_errors::*;
 use nagi_syntax_tree::ast::ASTNode;
 use nagi_syntax_tree::cst::CSTNode;
+use nagi_syntax_tree::graph::Graph;
 use nagi_syntax_tree::token::*;
+use nagi_syntax_tree::walk::SyntaxNode;
 use semantic_analyzer::SemanticAnalyzer;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::{Rc, Weak};
+use type_checker::TypeChecker;
+use unsafe_checker::UnsafeChecker;
 
+pub use type_checker::{OperatorMethod, TypeTable};
+
+mod builtin_macro_checker;
+mod lang_items;
 mod semantic_analyzer;
 mod type_checker;
+mod unsafe_checker;
 
 pub fn check(cst: &CSTNode) -> Result<ASTNode, Error> {
+    check_with_symbol_tree(cst).map(|(ast, _)| ast)
+}
+
+// ASTParserで直接作ったASTのチェック
+pub fn check_ast(ast: &ASTNode) -> Result<(), Error> {
+    check_ast_with_symbol_tree(ast).map(|_| ())
+}
+
+// 束縛がどのスコープに入ったか確かめられるように記号表の木も返す
+pub fn check_with_symbol_tree(cst: &CSTNode) -> Result<(ASTNode, SymbolTreeNode), Error> {
+    check_tree_depth(cst)?;
     let mut analyzer = SemanticAnalyzer::new();
 
-    analyzer.semantic_analyze(cst)
+    let ast = analyzer.semantic_analyze(cst)?;
+    UnsafeChecker::new().check(&ast)?;
+    analyzer.type_check(&ast)?;
+
+    Ok((ast, analyzer.symbol_tree()))
 }
 
+pub fn check_ast_with_symbol_tree(ast: &ASTNode) -> Result<SymbolTreeNode, Error> {
+    check_tree_depth(ast)?;
+    let mut analyzer = SemanticAnalyzer::new();
+
+    analyzer.semantic_check(ast)?;
+    UnsafeChecker::new().check(ast)?;
+    analyzer.type_check(ast)?;
+
+    Ok(analyzer.symbol_tree())
+}
+
+// 演算子がどのトレイトのメソッドに解決されたか
+pub fn operator_methods(ast: &ASTNode) -> Result<Vec<OperatorMethod>, Error> {
+    check_tree_depth(ast)?;
+    let mut type_checker = TypeChecker::new();
+    type_checker.check(ast)?;
+
+    Ok(type_checker.operator_methods().to_vec())
+}
+
+// 式の型、演算子のメソッド、変数の束縛をASTのノードの番号ごとに調べる
+// 番号はnagi_syntax_tree::arena::Arena::from_tree(ast.clone())で付く前順の番号と同じ
+// Rcで共有して何度も現れるノードも、現れた場所ごとの番号で載る
+pub fn type_table(ast: &ASTNode) -> Result<TypeTable, Error> {
+    check_tree_depth(ast)?;
+    let mut type_checker = TypeChecker::new();
+    type_checker.check(ast)?;
+
+    Ok(type_checker.type_table())
+}
+
+// 検査はどの段階も木を再帰でたどり、入れ子1段ごとにスタックを使う
+// 構文解析の入れ子の制限では`1 + 1 + ...`のような左結合の演算子の連なりが止まらないので、
+// 検査を始める前に木の深さを確かめる
+// デバッグビルドでもメインスレッドの8MiBのスタックで溢れない深さ
+pub const MAX_TREE_DEPTH: usize = 1024;
+
+fn check_tree_depth<T: SyntaxNode>(tree: &T) -> Result<(), Error> {
+    // 深さを調べる方が溢れないよう、再帰せずにたどる
+    let mut stack = vec![(tree, 1)];
+    while let Some((node, depth)) = stack.pop() {
+        if depth > MAX_TREE_DEPTH {
+            return Err(Error {
+                error_kind: ErrorKind::Semantic(SemanticError::NestingTooDeep),
+                error_text: format!("入れ子が深すぎます(上限は{})", MAX_TREE_DEPTH),
+                position: node
+                    .start_position()
+                    .map(|(row, column)| SourcePosition { row, column }),
+                notes: vec![],
+            });
+        }
+        node.for_each_child(&mut |child| stack.push((child, depth + 1)));
+    }
+
+    Ok(())
+}
+
+// 記号表の木のノード
+// 中身は共有しているので、cloneしたものに追加しても木の同じノードに入る
 #[derive(Debug, Clone)]
 pub struct SymbolTreeNode {
-    parent: Option<RefCell<Weak<SymbolTreeNode>>>,
-    children: RefCell<Vec<Rc<SymbolTreeNode>>>,
-    symbol_table: HashMap<SymbolKey, SymbolRecord>,
+    scope: Rc<SymbolScope>,
+}
+
+#[derive(Debug)]
+struct SymbolScope {
+    parent: Option<Weak<SymbolScope>>,
+    children: RefCell<Vec<Rc<SymbolScope>>>,
+    symbol_table: RefCell<HashMap<SymbolKey, SymbolRecord>>,
 }
 
 impl SymbolTreeNode {
     pub fn new() -> Self {
         Self {
-            parent: None,
-            children: RefCell::new(vec![]),
-            symbol_table: HashMap::new(),
+            scope: Rc::new(SymbolScope {
+                parent: None,
+                children: RefCell::new(vec![]),
+                symbol_table: RefCell::new(HashMap::new()),
+            }),
         }
     }
 
-    pub fn add_child(&mut self) -> Self {
-        let child = Rc::new(Self {
-            parent: Some(RefCell::new(Rc::downgrade(&Rc::new(self.clone())))),
+    pub fn add_child(&self) -> Self {
+        let child = Rc::new(SymbolScope {
+            parent: Some(Rc::downgrade(&self.scope)),
             children: RefCell::new(vec![]),
-            symbol_table: HashMap::new(),
+            symbol_table: RefCell::new(HashMap::new()),
         });
-        self.children.borrow_mut().push(Rc::clone(&child));
+        self.scope.children.borrow_mut().push(Rc::clone(&child));
 
-        child.as_ref().clone()
+        Self { scope: child }
     }
 
     pub fn insert_function(&mut self, symbol_name: &str, return_type: Option<SymbolType>) -> bool {
-        self.symbol_table
+        self.scope
+            .symbol_table
+            .borrow_mut()
             .insert(
                 SymbolKey {
                     symbol_pattern: SymbolPattern::Function,
@@ -55,14 +147,15 @@ impl SymbolTreeNode {
             .is_none()
     }
 
+    // 同じスコープに同じ名前の変数があれば上書きする(シャドーイング)
     pub fn insert_variable(
         &mut self,
         symbol_name: &str,
         rarity: Rarity,
-        symbol_type: SymbolType,
+        symbol_type: Option<SymbolType>,
         size: u32,
     ) {
-        self.symbol_table.insert(
+        self.scope.symbol_table.borrow_mut().insert(
             SymbolKey {
                 symbol_pattern: SymbolPattern::Variable,
                 symbol_name: symbol_name.to_string(),
@@ -77,28 +170,65 @@ impl SymbolTreeNode {
 
     // ルートノードまで特定のシンボルが存在するか探す
     pub fn is_symbol_in_ancestors(&self, pattern: &SymbolPattern, symbol_name: &str) -> bool {
-        if self.has_symbol(pattern, symbol_name) {
-            return true;
+        let mut current_scope = Some(Rc::clone(&self.scope));
+        while let Some(scope) = current_scope {
+            if scope.has_symbol(pattern, symbol_name) {
+                return true;
+            }
+            current_scope = scope.parent.as_ref().and_then(Weak::upgrade);
         }
 
-        if self.parent.is_none() {
-            return false;
+        false
+    }
+
+    // 自身と子孫から特定のシンボルを持つノードを探す
+    pub fn find_scope(&self, pattern: &SymbolPattern, symbol_name: &str) -> Option<Self> {
+        if self.scope.has_symbol(pattern, symbol_name) {
+            return Some(self.clone());
         }
 
-        let mut currnt_node = self.parent.as_ref().unwrap().borrow().upgrade();
-        while let Some(node) = currnt_node {
-            if node.has_symbol(pattern, symbol_name) {
-                return true;
+        self.scope.children.borrow().iter().find_map(|child| {
+            Self {
+                scope: Rc::clone(child),
             }
-            currnt_node = node.parent.as_ref().unwrap().borrow().upgrade();
-        }
+            .find_scope(pattern, symbol_name)
+        })
+    }
+}
 
-        false
+impl SymbolTreeNode {
+    // スコープを1つのノードとし、そのスコープのシンボルを名前の順に書く
+    pub fn graph(&self) -> Graph {
+        let mut graph = Graph::new("scopes");
+        self.scope.add_to_graph(&mut graph, None);
+        graph
+    }
+}
+
+impl SymbolScope {
+    fn add_to_graph(&self, graph: &mut Graph, parent: Option<usize>) {
+        let mut symbols: Vec<(String, String)> = self
+            .symbol_table
+            .borrow()
+            .iter()
+            .map(|(key, record)| (key.symbol_name.clone(), record.describe(&key.symbol_name)))
+            .collect();
+        symbols.sort();
+
+        let mut lines = vec!["scope".to_string()];
+        lines.extend(symbols.into_iter().map(|(_, line)| line));
+        let index = graph.add_node(lines, None);
+        if let Some(parent) = parent {
+            graph.add_edge(parent, index);
+        }
+        for child in self.children.borrow().iter() {
+            child.add_to_graph(graph, Some(index));
+        }
     }
 
     // 自身のノードにシンボルが存在するか
     fn has_symbol(&self, pattern: &SymbolPattern, symbol_name: &str) -> bool {
-        self.symbol_table.contains_key(&SymbolKey {
+        self.symbol_table.borrow().contains_key(&SymbolKey {
             symbol_pattern: pattern.clone(),
             symbol_name: symbol_name.to_string(),
         })
@@ -123,10 +253,31 @@ pub enum SymbolRecord {
     Function(FunctionSymbolRecord),
 }
 
+impl SymbolRecord {
+    // 型が分からなければ`_`と書く
+    fn describe(&self, symbol_name: &str) -> String {
+        match self {
+            SymbolRecord::Variable(record) => format!(
+                "{:?} {}: {}",
+                record.rarity,
+                symbol_name,
+                record
+                    .symbol_type
+                    .as_ref()
+                    .map_or("_".to_string(), |symbol_type| format!("{:?}", symbol_type))
+            ),
+            SymbolRecord::Function(record) => match &record.return_type {
+                Some(return_type) => format!("fn {} -> {:?}", symbol_name, return_type),
+                None => format!("fn {}", symbol_name),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct VariableSymbolRecord {
     rarity: Rarity,
-    symbol_type: SymbolType,
+    symbol_type: Option<SymbolType>, // 型推論ができるまではNone
     size: u32,
 }
 
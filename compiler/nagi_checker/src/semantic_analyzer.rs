@@ -1,10 +1,13 @@
 use nagi_errors::*;
+use nagi_syntax_tree::ast::visit::{walk_children, Visitor};
 use nagi_syntax_tree::ast::*;
 use nagi_syntax_tree::cst::*;
 use nagi_syntax_tree::hst::*;
 use nagi_syntax_tree::keywords::Keyword;
+use nagi_syntax_tree::span::Span;
 use nagi_syntax_tree::token::*;
 
+use crate::builtin_macro_checker::check_builtin_macro;
 use crate::type_checker::TypeChecker;
 use crate::SymbolTreeNode;
 
@@ -13,7 +16,6 @@ use std::rc::Rc;
 #[derive()]
 pub struct SemanticAnalyzer {
     symbol_table: SymbolTreeNode,
-    type_checker: TypeChecker,
 }
 
 // 今のところは型チェックと変数や関数の重複チェックのみ
@@ -21,7 +23,6 @@ impl SemanticAnalyzer {
     pub fn new() -> Self {
         Self {
             symbol_table: SymbolTreeNode::new(),
-            type_checker: TypeChecker::new(),
         }
     }
 
@@ -39,6 +40,10 @@ impl SemanticAnalyzer {
                 }
 
                 for cst_item in items.iter() {
+                    // 構文エラーで読み飛ばした部分はチェックしない
+                    if matches!(cst_item.node_kind, CSTNodeKind::Error { .. }) {
+                        continue;
+                    }
                     item.push(analyze(cst_item, &mut self.symbol_table)?);
                 }
 
@@ -46,303 +51,1559 @@ impl SemanticAnalyzer {
                     inner_attribute,
                     item,
                 })
+                .with_span(cst.span())
             }
-            _ => {
-                return Err(Error {
-                    error_kind: ErrorKind::Semantic(SemanticError::TODO),
-                    error_text: format!(""),
-                })
-            }
+            _ => return Err(not_crate_error(cst.span())),
+        };
+
+        //println!("{:#?}", self.symbol_table);
+
+        Ok(ast)
+    }
+
+    // 構文解析器が直接作ったASTに対して、CSTから変換する時と同じ重複チェックをする
+    pub fn semantic_check(&mut self, ast: &ASTNode) -> Result<(), Error> {
+        let ASTNodeKind::Crate { .. } = &ast.node_kind else {
+            return Err(not_crate_error(ast.span));
         };
 
-        //println!("{:#?}", self.symbol_table);
+        for child in ast.children() {
+            check(child, &mut self.symbol_table)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn type_check(&mut self, ast: &ASTNode) -> Result<(), Error> {
+        TypeChecker::new().check(ast)
+    }
+
+    pub fn symbol_tree(&self) -> SymbolTreeNode {
+        self.symbol_table.clone()
+    }
+}
+
+// analyzeと同じ順番、同じスコープでたどる
+fn check(ast: &ASTNode, symbol_tree: &mut SymbolTreeNode) -> Result<(), Error> {
+    match &ast.node_kind {
+        ASTNodeKind::Function {
+            outer_attribute,
+            function_qualifiers,
+            identifier,
+            generic_params,
+            function_parameters,
+            function_return_type,
+            where_clause: _,
+            block_expression,
+        } => {
+            for attr in outer_attribute {
+                check(attr, symbol_tree)?;
+            }
+            check(function_qualifiers, symbol_tree)?;
+            for expr in [generic_params, function_parameters, function_return_type]
+                .into_iter()
+                .flatten()
+            {
+                check(expr, symbol_tree)?;
+            }
+
+            if !symbol_tree.insert_function(identifier, None) {
+                return Err(redefinition_function_error(identifier, ast.span));
+            }
+
+            if let Some(expr) = block_expression {
+                let mut scope = symbol_tree.add_child();
+                if let Some(expr) = function_parameters {
+                    bind_function_parameters(expr, &mut scope);
+                }
+                check(expr, &mut scope)?;
+            }
+        }
+
+        ASTNodeKind::Implementation {
+            outer_attribute,
+            trait_path,
+            type_expression,
+            associated_items,
+        } => {
+            for attr in outer_attribute {
+                check(attr, symbol_tree)?;
+            }
+            if let Some(expr) = trait_path {
+                check(expr, symbol_tree)?;
+            }
+            check(type_expression, symbol_tree)?;
+
+            let mut scope = symbol_tree.add_child();
+            for item in associated_items {
+                check(item, &mut scope)?;
+            }
+        }
+
+        ASTNodeKind::BlockExpression {
+            inner_attribute,
+            statements,
+        } => {
+            let mut child = symbol_tree.add_child();
+            for attr in inner_attribute {
+                check(attr, &mut child)?;
+            }
+
+            if let Some(expr) = statements {
+                check(expr, &mut symbol_tree.add_child())?;
+            }
+        }
+
+        // 条件の束縛はthenのブロックだけから見える
+        ASTNodeKind::IfExpression {
+            expression,
+            block_expression,
+            else_expression,
+        } => {
+            let mut scope = symbol_tree.add_child();
+            check(expression, &mut scope)?;
+            check(block_expression, &mut scope)?;
+
+            if let Some(expr) = else_expression {
+                check(expr, symbol_tree)?;
+            }
+        }
+
+        ASTNodeKind::IfLetExpression {
+            pattern,
+            scrutinee,
+            block_expression,
+            else_expression,
+        } => {
+            let mut scope = symbol_tree.add_child();
+            check(pattern, &mut scope)?;
+            check(scrutinee, symbol_tree)?;
+            bind_pattern(pattern, &Rarity::Let, &mut scope);
+            check(block_expression, &mut scope)?;
+
+            if let Some(expr) = else_expression {
+                check(expr, symbol_tree)?;
+            }
+        }
+
+        // LetChainの中では後の条件から前の束縛が見える
+        ASTNodeKind::LetChainCondition { pattern, scrutinee } => {
+            check(pattern, symbol_tree)?;
+            check(scrutinee, symbol_tree)?;
+            bind_pattern(pattern, &Rarity::Let, symbol_tree);
+        }
+
+        // let-elseのelseブロックからは束縛が見えない
+        ASTNodeKind::LetStatement {
+            rarity,
+            pattern_no_top_alt,
+            block_expression,
+            ..
+        } => {
+            for child in ast.children() {
+                check(child, symbol_tree)?;
+            }
+            if let Some(expr) = block_expression {
+                check_let_else_diverges(expr)?;
+            }
+
+            bind_pattern(pattern_no_top_alt, rarity, symbol_tree);
+        }
+
+        ASTNodeKind::BuiltinMacroInvocation {
+            builtin_macro,
+            call_params,
+        } => {
+            for child in call_params {
+                check(child, symbol_tree)?;
+            }
+            check_builtin_macro(*builtin_macro, call_params, ast.span)?;
+        }
+
+        _ => {
+            for child in ast.children() {
+                check(child, symbol_tree)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// パターンの中の識別子を変数として登録する
+fn bind_pattern(pattern: &ASTNode, rarity: &Rarity, symbol_tree: &mut SymbolTreeNode) {
+    PatternBinder {
+        rarity,
+        symbol_tree,
+    }
+    .visit_node(pattern);
+}
+
+struct PatternBinder<'a> {
+    rarity: &'a Rarity,
+    symbol_tree: &'a mut SymbolTreeNode,
+}
+
+impl Visitor<'_> for PatternBinder<'_> {
+    fn visit_identifier_pattern(&mut self, node: &ASTNode) {
+        if let ASTNodeKind::IdentifierPattern { identifier, .. } = &node.node_kind {
+            // 型推論ができるまで型とサイズは決まらない
+            self.symbol_tree
+                .insert_variable(identifier, self.rarity.clone(), None, 0);
+        }
+
+        walk_children(self, node);
+    }
+}
+
+// 引数のパターンの識別子と`self`を変数として登録する
+fn bind_function_parameters(function_parameters: &ASTNode, symbol_tree: &mut SymbolTreeNode) {
+    let ASTNodeKind::FunctionParameters {
+        self_param,
+        function_params,
+    } = &function_parameters.node_kind
+    else {
+        return;
+    };
+
+    if self_param.is_some() {
+        symbol_tree.insert_variable("self", Rarity::Let, None, 0);
+    }
+    for param in function_params {
+        if let ASTNodeKind::FunctionParam {
+            pattern_no_top_alt, ..
+        } = &param.node_kind
+        {
+            bind_pattern(pattern_no_top_alt, &Rarity::Let, symbol_tree);
+        }
+    }
+}
+
+// エラーはelseブロックの位置を示す
+fn check_let_else_diverges(block_expression: &ASTNode) -> Result<(), Error> {
+    if diverges(block_expression) {
+        return Ok(());
+    }
+
+    Err(Error {
+        error_kind: ErrorKind::Semantic(SemanticError::LetElseNotDiverging),
+        error_text: "let-elseのelseブロックは`return`などで発散しなければなりません".to_string(),
+        position: block_expression.span.map(|span| span.start_position()),
+        notes: vec![],
+    })
+}
+
+// 式の評価が終わらずに制御が外へ出るか
+// ブロックはどれかの文が発散すれば発散し、ifはすべての分岐が発散すれば発散する
+fn diverges(ast: &ASTNode) -> bool {
+    match &ast.node_kind {
+        ASTNodeKind::ReturnExpression { .. } => true,
+        ASTNodeKind::BuiltinMacroInvocation { builtin_macro, .. } => {
+            *builtin_macro == BuiltinMacro::Panic
+        }
+        ASTNodeKind::ExpressionWithoutBlock { expression, .. }
+        | ASTNodeKind::ExpressionWithBlock {
+            expression_with_block: expression,
+            ..
+        }
+        | ASTNodeKind::Expression { expression } => diverges(expression),
+        ASTNodeKind::BlockExpression { statements, .. } => {
+            statements.as_deref().is_some_and(diverges)
+        }
+        ASTNodeKind::Statements { statements } => statements.iter().any(diverges),
+        ASTNodeKind::Statement { statement } => statement.as_deref().is_some_and(diverges),
+        ASTNodeKind::LetStatement { expression, .. } => expression.as_deref().is_some_and(diverges),
+        ASTNodeKind::UnsafeBlockExpression { block_expression } => diverges(block_expression),
+        ASTNodeKind::IfExpression {
+            block_expression,
+            else_expression,
+            ..
+        }
+        | ASTNodeKind::IfLetExpression {
+            block_expression,
+            else_expression,
+            ..
+        } => diverges(block_expression) && else_expression.as_deref().is_some_and(diverges),
+        _ => false,
+    }
+}
+
+fn not_crate_error(span: Option<Span>) -> Error {
+    Error {
+        error_kind: ErrorKind::Semantic(SemanticError::NotCrate),
+        error_text: "クレートでない木は検査できません".to_string(),
+        position: span.map(|span| span.start_position()),
+        notes: vec![],
+    }
+}
+
+// 後から定義した関数の位置を示す
+fn redefinition_function_error(ident: &str, span: Option<Span>) -> Error {
+    Error {
+        error_kind: ErrorKind::Semantic(SemanticError::RedefinitionFunction),
+        error_text: format!("`{}`関数はすでに定義されています", ident),
+        position: span.map(|span| span.start_position()),
+        notes: vec![],
+    }
+}
+
+// 深い入れ子では節点ごとにこの関数を通るので、デバッグビルドでもスタックを使いすぎないよう
+// 各節点の変換は別の関数に分け、ここには呼び出しだけを置く
+fn analyze(cst: &CSTNode, symbol_tree: &mut SymbolTreeNode) -> Result<ASTNode, Error> {
+    let ast = match &cst.node_kind {
+        CSTNodeKind::Crate {
+            inner_attributes: _,
+            items: _,
+        } => {
+            panic!();
+        }
+        CSTNodeKind::Factor {
+            token,
+            row: _,
+            column: _,
+        } => analyze_factor(token),
+        CSTNodeKind::Literal {
+            literal,
+            row: _,
+            column: _,
+        } => analyze_literal(literal),
+        CSTNodeKind::Operator {
+            token,
+            row: _,
+            column: _,
+        } => analyze_operator(cst, symbol_tree, token),
+        CSTNodeKind::InnerAttribute {
+            pound: _,
+            exclamation: _,
+            left_brackets: _,
+            attribute,
+            right_brackets: _,
+        } => analyze_inner_attribute(symbol_tree, attribute),
+        CSTNodeKind::OuterAttribute {
+            pound: _,
+            left_brackets: _,
+            attribute,
+            right_brackets: _,
+        } => analyze_outer_attribute(symbol_tree, attribute),
+        CSTNodeKind::Attribute {
+            unsafe_keyword,
+            left_parenthesis: _,
+            simple_path,
+            attribute_input,
+            right_parenthesis: _,
+        } => analyze_attribute(
+            symbol_tree,
+            unsafe_keyword.is_some(),
+            simple_path,
+            attribute_input,
+        ),
+        // DelimTokenTreeか`=`の右辺のExpression
+        CSTNodeKind::AttributeInput {
+            equal: _,
+            expression,
+        } => analyze(expression, symbol_tree),
+        CSTNodeKind::SimplePath {
+            path_separater: _,
+            simple_path_segment,
+            repeat_simple_path_segment,
+        } => analyze_simple_path(symbol_tree, simple_path_segment, repeat_simple_path_segment),
+        CSTNodeKind::DelimTokenTree {
+            left_parenthesis,
+            token_tree,
+            right_parenthesis: _,
+        } => analyze_delim_token_tree(symbol_tree, left_parenthesis, token_tree),
+
+        // 展開されずに残ったマクロはそのまま変換する
+        CSTNodeKind::MacroInvocation {
+            simple_path,
+            not: _,
+            delim_token_tree,
+        }
+        | CSTNodeKind::MacroInvocationSemi {
+            simple_path,
+            not: _,
+            delim_token_tree,
+            semicolon: _,
+        } => analyze_macro_invocation(symbol_tree, simple_path, delim_token_tree),
+        CSTNodeKind::BuiltinMacroInvocation {
+            identifier,
+            not: _,
+            left_parenthesis: _,
+            call_params,
+            right_parenthesis: _,
+        } => analyze_builtin_macro_invocation(symbol_tree, identifier, call_params),
+        CSTNodeKind::MacroRulesDefinition {
+            macro_rules_keyword: _,
+            not: _,
+            identifier,
+            delim_token_tree,
+            semicolon: _,
+        } => analyze_macro_rules_definition(symbol_tree, identifier, delim_token_tree),
+
+        // Expression
+        CSTNodeKind::Expression { expression } => analyze_expression(symbol_tree, expression),
+
+        CSTNodeKind::ExpressionWithBlock {
+            outer_attribute,
+            expression_with_block,
+        } => analyze_expression_with_block(symbol_tree, outer_attribute, expression_with_block),
+
+        CSTNodeKind::ExpressionWithoutBlock {
+            outer_attribute,
+            expression,
+        } => analyze_expression_without_block(symbol_tree, outer_attribute, expression),
+
+        CSTNodeKind::LiteralExpression { literal } => analyze(literal, symbol_tree),
+
+        CSTNodeKind::BlockExpression {
+            left_brace: _,
+            inner_attribute,
+            statements,
+            right_brace: _,
+        } => analyze_block_expression(symbol_tree, inner_attribute, statements),
+
+        CSTNodeKind::ConstBlockExpression {
+            const_keyword: _,
+            block_expression,
+        } => analyze_const_block_expression(symbol_tree, block_expression),
+
+        CSTNodeKind::UnsafeBlockExpression {
+            unsafe_keyword: _,
+            block_expression,
+        } => analyze_unsafe_block_expression(symbol_tree, block_expression),
+
+        CSTNodeKind::AsyncBlockExpression {
+            async_keyword: _,
+            move_keyword,
+            block_expression,
+        } => analyze_async_block_expression(symbol_tree, move_keyword.is_some(), block_expression),
+
+        CSTNodeKind::PathExpression { path_in_expression } => {
+            analyze_path_expression(symbol_tree, path_in_expression)
+        }
+
+        CSTNodeKind::PathInExpression {
+            path_separater: _,
+            path_expr_segment,
+            repeat_path_expr_segment,
+        } => analyze_path_in_expression(symbol_tree, path_expr_segment, repeat_path_expr_segment),
+
+        CSTNodeKind::PathExprSegment {
+            path_ident_segment,
+            generic_args: _,
+        } => analyze_path_expr_segment(symbol_tree, path_ident_segment),
+
+        CSTNodeKind::CallExpression {
+            expression,
+            left_parenthesis: _,
+            call_params,
+            right_parenthesis: _,
+        } => analyze_call_expression(symbol_tree, expression, call_params),
+
+        CSTNodeKind::MethodCallExpression {
+            expression,
+            dot: _,
+            path_expr_segment,
+            left_parenthesis: _,
+            call_params,
+            right_parenthesis: _,
+        } => {
+            analyze_method_call_expression(symbol_tree, expression, path_expr_segment, call_params)
+        }
+
+        CSTNodeKind::FieldExpression {
+            expression,
+            dot: _,
+            identifier,
+        } => analyze_field_expression(symbol_tree, expression, identifier),
+
+        CSTNodeKind::AwaitExpression {
+            expression,
+            dot: _,
+            await_keyword: _,
+        } => analyze_await_expression(symbol_tree, expression),
+
+        CSTNodeKind::IndexExpression {
+            expression,
+            left_brackets: _,
+            index,
+            right_brackets: _,
+        } => analyze_index_expression(symbol_tree, expression, index),
+
+        // Function
+        CSTNodeKind::Function { .. } => analyze_function(symbol_tree, cst),
+
+        CSTNodeKind::FunctionParam1 {
+            self_param,
+            comma: _,
+        } => analyze_function_parameters(symbol_tree, Some(self_param), None),
+        CSTNodeKind::FunctionParam2 {
+            self_param,
+            function_param,
+            function_param_repeat,
+            comma: _,
+        } => analyze_function_parameters(
+            symbol_tree,
+            self_param.as_ref().map(|(expr, _)| expr),
+            Some((function_param, function_param_repeat)),
+        ),
+        CSTNodeKind::ShorthandSelf {
+            outer_attribute,
+            and,
+            mut_keyword,
+            self_keyword: _,
+        } => analyze_self_param(
+            symbol_tree,
+            outer_attribute,
+            and.is_some(),
+            mut_keyword.is_some(),
+            None,
+        ),
+        CSTNodeKind::TypedSelf {
+            outer_attribute,
+            mut_keyword,
+            self_keyword: _,
+            colon: _,
+            type_expression,
+        } => analyze_self_param(
+            symbol_tree,
+            outer_attribute,
+            false,
+            mut_keyword.is_some(),
+            Some(type_expression),
+        ),
+        CSTNodeKind::FunctionParam {
+            outer_attribute,
+            pattern_no_top_alt,
+            colon: _,
+            type_expression,
+        } => analyze_function_param(
+            symbol_tree,
+            outer_attribute,
+            pattern_no_top_alt,
+            type_expression,
+        ),
+        // GenericParams
+        // ASTでは今は`>`だけを持つ
+        CSTNodeKind::GenericParams {
+            less_than: _,
+            greater_than,
+        } => analyze(greater_than, symbol_tree),
+
+        // ASTには型だけを残す
+        CSTNodeKind::FunctionReturnType {
+            right_allow: _,
+            type_expression,
+        } => analyze(type_expression, symbol_tree),
+
+        // TypeAlias
+        CSTNodeKind::TypeAlias {
+            outer_attribute,
+            visibility: _,
+            type_keyword: _,
+            identifier,
+            equal: _,
+            type_expression,
+            semicolon: _,
+        } => analyze_type_alias(symbol_tree, outer_attribute, identifier, type_expression),
+
+        // Struct
+        CSTNodeKind::Struct {
+            outer_attribute,
+            visibility: _,
+            struct_keyword: _,
+            identifier,
+            left_brace: _,
+            struct_fields,
+            right_brace_or_semicolon: _,
+        } => analyze_struct(symbol_tree, outer_attribute, identifier, struct_fields),
+        CSTNodeKind::StructField {
+            outer_attribute,
+            visibility: _,
+            identifier,
+            colon: _,
+            type_expression,
+        } => analyze_struct_field(symbol_tree, outer_attribute, identifier, type_expression),
+
+        // Implementation
+        CSTNodeKind::Implementation {
+            outer_attribute,
+            visibility: _,
+            impl_keyword: _,
+            trait_path,
+            type_expression,
+            left_brace: _,
+            associated_items,
+            right_brace: _,
+        } => analyze_implementation(
+            symbol_tree,
+            outer_attribute,
+            trait_path,
+            type_expression,
+            associated_items,
+        ),
+
+        // Type
+        CSTNodeKind::TypePath {
+            path_separater: _,
+            path_ident_segment,
+            repeat_path_ident_segment,
+        } => analyze_type_path(symbol_tree, path_ident_segment, repeat_path_ident_segment),
+
+        CSTNodeKind::FunctionQualifiers {
+            const_keyword,
+            async_keyword,
+            item_safety,
+            extern_keyword,
+            abi,
+        } => analyze_function_qualifiers(
+            symbol_tree,
+            const_keyword.is_some(),
+            async_keyword.is_some(),
+            item_safety,
+            extern_keyword.is_some(),
+            abi,
+        ),
+
+        // 括弧は木の形で表せるので残さない
+        CSTNodeKind::GroupedExpression {
+            left_parenthesis: _,
+            expression,
+            right_parenthesis: _,
+        } => analyze(expression, symbol_tree),
+
+        CSTNodeKind::ReturnExpression {
+            return_keyword: _,
+            expression,
+        } => analyze_return_expression(symbol_tree, expression),
+
+        CSTNodeKind::IfExpression {
+            if_keyword: _,
+            expression,
+            block_expression,
+            else_keyword: _,
+            else_expression,
+        } => analyze_if_expression(symbol_tree, expression, block_expression, else_expression),
+
+        CSTNodeKind::IfLetExpression {
+            if_keyword: _,
+            let_keyword: _,
+            pattern,
+            equal: _,
+            scrutinee,
+            block_expression,
+            else_keyword: _,
+            else_expression,
+        } => analyze_if_let_expression(
+            symbol_tree,
+            pattern,
+            scrutinee,
+            block_expression,
+            else_expression,
+        ),
+
+        CSTNodeKind::LetChain {
+            let_chain_condition,
+            repeat_let_chain_condition,
+        } => analyze_let_chain(symbol_tree, let_chain_condition, repeat_let_chain_condition),
+
+        CSTNodeKind::LetChainCondition {
+            let_keyword: _,
+            pattern,
+            equal: _,
+            scrutinee,
+        } => analyze_let_chain_condition(symbol_tree, pattern, scrutinee),
+
+        // Struct
+        CSTNodeKind::StructExpression { expression } => analyze(expression, symbol_tree),
+
+        CSTNodeKind::StructExprStruct {
+            path_in_expression,
+            left_brace: _,
+            expression,
+            right_brace: _,
+        } => analyze_struct_expr_struct(symbol_tree, path_in_expression, expression),
+
+        CSTNodeKind::StructExprField1 {
+            outer_attribute,
+            identifier,
+        } => analyze_struct_expr_field(symbol_tree, outer_attribute, identifier, None),
+
+        CSTNodeKind::StructExprField2 {
+            outer_attribute,
+            identifier_or_tuple,
+            colon: _,
+            expression,
+        } => analyze_struct_expr_field(
+            symbol_tree,
+            outer_attribute,
+            identifier_or_tuple,
+            Some(expression),
+        ),
+
+        // Pattern
+        CSTNodeKind::Pattern {
+            or_token: _,
+            pattern_no_top_alt,
+            repeat_pattern_no_top_alt,
+        } => analyze_pattern(symbol_tree, pattern_no_top_alt, repeat_pattern_no_top_alt),
+
+        CSTNodeKind::LiteralPattern { literal, .. } => analyze_literal_pattern(literal),
+
+        CSTNodeKind::WildcardPattern { wildcard } => {
+            analyze_wildcard_pattern(symbol_tree, wildcard)
+        }
+
+        CSTNodeKind::RestPattern { rest } => analyze_rest_pattern(symbol_tree, rest),
+
+        CSTNodeKind::IdentifierPattern {
+            ref_keyword,
+            mut_keyword,
+            identifier,
+            at_symbol: _,
+            pattern_no_top_alt,
+        } => analyze_identifier_pattern(
+            symbol_tree,
+            ref_keyword.is_some(),
+            mut_keyword.is_some(),
+            &identifier.node_kind,
+            pattern_no_top_alt,
+        ),
+
+        // Statements
+        CSTNodeKind::Statements => analyze_statements(cst, symbol_tree),
+
+        CSTNodeKind::Statement { statement } => analyze_statement(symbol_tree, statement),
+
+        CSTNodeKind::LetStatement {
+            outer_attribute,
+            rarity,
+            pattern_no_top_alt,
+            colon: _,
+            type_expression,
+            equal: _,
+            expression,
+            else_keyword: _,
+            block_expression,
+            semicolon: _,
+        } => analyze_let_statement(
+            symbol_tree,
+            outer_attribute,
+            rarity,
+            pattern_no_top_alt,
+            type_expression,
+            expression,
+            block_expression,
+        ),
+
+        _ => panic!("{:?}", cst.node_kind),
+    }?;
+
+    Ok(ast.with_span(cst.span()))
+}
+
+fn analyze_factor(token: &Token) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::Factor {
+        token: token.clone(),
+    }))
+}
+
+fn analyze_literal(literal: &Literal) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::Literal {
+        literal: literal.clone(),
+    }))
+}
+
+fn analyze_outer_attributes(
+    symbol_tree: &mut SymbolTreeNode,
+    outer_attribute: &[CSTNode],
+) -> Result<Vec<ASTNode>, Error> {
+    let mut ast_outer_attribute = vec![];
+    for attr in outer_attribute {
+        ast_outer_attribute.push(analyze(attr, symbol_tree)?);
+    }
+
+    Ok(ast_outer_attribute)
+}
+
+// `A (sep A)*`の形をしたものを並べる
+fn analyze_repeat(
+    symbol_tree: &mut SymbolTreeNode,
+    first: &CSTNode,
+    repeat: &[(CSTNode, CSTNode)],
+) -> Result<Vec<ASTNode>, Error> {
+    let mut ast_nodes = vec![analyze(first, symbol_tree)?];
+    for (_, expr) in repeat {
+        ast_nodes.push(analyze(expr, symbol_tree)?);
+    }
+
+    Ok(ast_nodes)
+}
+
+fn analyze_optional(
+    symbol_tree: &mut SymbolTreeNode,
+    cst: &Option<Rc<CSTNode>>,
+) -> Result<Option<Rc<ASTNode>>, Error> {
+    let mut ast = None;
+    if let Some(expr) = cst {
+        ast = Some(Rc::new(analyze(expr, symbol_tree)?));
+    }
+
+    Ok(ast)
+}
+
+fn analyze_inner_attribute(
+    symbol_tree: &mut SymbolTreeNode,
+    attribute: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::InnerAttribute {
+        attribute: Rc::new(analyze(attribute, symbol_tree)?),
+    }))
+}
+
+fn analyze_outer_attribute(
+    symbol_tree: &mut SymbolTreeNode,
+    attribute: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::OuterAttribute {
+        attribute: Rc::new(analyze(attribute, symbol_tree)?),
+    }))
+}
+
+fn analyze_attribute(
+    symbol_tree: &mut SymbolTreeNode,
+    is_unsafe: bool,
+    simple_path: &CSTNode,
+    attribute_input: &Option<Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    let ast_simple_path = Rc::new(analyze(simple_path, symbol_tree)?);
+    let ast_attribute_input = analyze_optional(symbol_tree, attribute_input)?;
+
+    Ok(ASTNode::new(ASTNodeKind::Attribute {
+        is_unsafe,
+        simple_path: ast_simple_path,
+        attribute_input: ast_attribute_input,
+    }))
+}
+
+fn analyze_simple_path(
+    symbol_tree: &mut SymbolTreeNode,
+    simple_path_segment: &CSTNode,
+    repeat_simple_path_segment: &[(CSTNode, CSTNode)],
+) -> Result<ASTNode, Error> {
+    let ast_simple_path_segment = Rc::new(analyze(simple_path_segment, symbol_tree)?);
+    let mut ast_repeat_simple_path_segment = vec![];
+    for (_, expr) in repeat_simple_path_segment {
+        ast_repeat_simple_path_segment.push(analyze(expr, symbol_tree)?);
+    }
+
+    Ok(ASTNode::new(ASTNodeKind::SimplePath {
+        simple_path_segment: ast_simple_path_segment,
+        repeat_simple_path_segment: ast_repeat_simple_path_segment,
+    }))
+}
+
+fn analyze_delim_token_tree(
+    symbol_tree: &mut SymbolTreeNode,
+    left_parenthesis: &CSTNode,
+    token_tree: &[CSTNode],
+) -> Result<ASTNode, Error> {
+    let CSTNodeKind::Factor {
+        token: Token::LeftParenthesis(delimiter),
+        ..
+    } = &left_parenthesis.node_kind
+    else {
+        panic!();
+    };
+
+    let mut ast_token_tree = vec![];
+    for tree in token_tree {
+        ast_token_tree.push(analyze(tree, symbol_tree)?);
+    }
+
+    Ok(ASTNode::new(ASTNodeKind::DelimTokenTree {
+        delimiter: delimiter.clone(),
+        token_tree: ast_token_tree,
+    }))
+}
+
+fn analyze_macro_invocation(
+    symbol_tree: &mut SymbolTreeNode,
+    simple_path: &CSTNode,
+    delim_token_tree: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::MacroInvocation {
+        simple_path: Rc::new(analyze(simple_path, symbol_tree)?),
+        delim_token_tree: Rc::new(analyze(delim_token_tree, symbol_tree)?),
+    }))
+}
+
+fn analyze_builtin_macro_invocation(
+    symbol_tree: &mut SymbolTreeNode,
+    identifier: &CSTNode,
+    call_params: &Option<Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    let CSTNodeKind::Factor {
+        token: Token::Identifier(ident),
+        ..
+    } = &identifier.node_kind
+    else {
+        panic!();
+    };
+
+    let builtin_macro = BuiltinMacro::from_name(ident).unwrap();
+    let call_params = analyze_call_params(call_params, symbol_tree)?;
+    check_builtin_macro(builtin_macro, &call_params, identifier.span())?;
+
+    Ok(ASTNode::new(ASTNodeKind::BuiltinMacroInvocation {
+        builtin_macro,
+        call_params,
+    }))
+}
+
+fn analyze_macro_rules_definition(
+    symbol_tree: &mut SymbolTreeNode,
+    identifier: &CSTNode,
+    delim_token_tree: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::MacroRulesDefinition {
+        identifier: identifier_name(identifier),
+        delim_token_tree: Rc::new(analyze(delim_token_tree, symbol_tree)?),
+    }))
+}
+
+fn analyze_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    expression: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::Expression {
+        expression: Rc::new(analyze(expression, symbol_tree)?),
+    }))
+}
+
+fn analyze_expression_with_block(
+    symbol_tree: &mut SymbolTreeNode,
+    outer_attribute: &[CSTNode],
+    expression_with_block: &CSTNode,
+) -> Result<ASTNode, Error> {
+    let ast_outer_attribute = analyze_outer_attributes(symbol_tree, outer_attribute)?;
+    let ast_expression_with_block = Rc::new(analyze(expression_with_block, symbol_tree)?);
+
+    Ok(ASTNode::new(ASTNodeKind::ExpressionWithBlock {
+        outer_attribute: ast_outer_attribute,
+        expression_with_block: ast_expression_with_block,
+    }))
+}
+
+fn analyze_expression_without_block(
+    symbol_tree: &mut SymbolTreeNode,
+    outer_attribute: &[CSTNode],
+    expression: &CSTNode,
+) -> Result<ASTNode, Error> {
+    let ast_outer_attribute = analyze_outer_attributes(symbol_tree, outer_attribute)?;
+    let ast_expression = Rc::new(analyze(expression, symbol_tree)?);
+
+    // 式文の`;`はchildrenにあるので式の範囲に含めない
+    let first = outer_attribute.first().unwrap_or(expression);
+    let span = first
+        .span
+        .zip(expression.span)
+        .map(|(first, last)| first.to(last));
+    Ok(ASTNode::new(ASTNodeKind::ExpressionWithoutBlock {
+        outer_attribute: ast_outer_attribute,
+        expression: ast_expression,
+    })
+    .with_span(span))
+}
+
+fn analyze_block_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    inner_attribute: &[CSTNode],
+    statements: &Option<Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    // ブロックの場合はネストしてから
+    // InnerAttribute
+    let mut child = symbol_tree.add_child();
+    let mut inner_attri = vec![];
+    for attr in inner_attribute {
+        inner_attri.push(analyze(attr, &mut child)?);
+    }
+
+    // Statement
+    let ast_statements = analyze_optional(&mut symbol_tree.add_child(), statements)?;
+
+    Ok(ASTNode::new(ASTNodeKind::BlockExpression {
+        inner_attribute: inner_attri,
+        statements: ast_statements,
+    }))
+}
+
+fn analyze_const_block_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    block_expression: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::ConstBlockExpression {
+        block_expression: Rc::new(analyze(block_expression, symbol_tree)?),
+    }))
+}
+
+fn analyze_unsafe_block_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    block_expression: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::UnsafeBlockExpression {
+        block_expression: Rc::new(analyze(block_expression, symbol_tree)?),
+    }))
+}
+
+fn analyze_async_block_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    move_keyword: bool,
+    block_expression: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::AsyncBlockExpression {
+        move_keyword,
+        block_expression: Rc::new(analyze(block_expression, symbol_tree)?),
+    }))
+}
+
+fn analyze_path_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    path_in_expression: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::PathExpression {
+        expression: Rc::new(analyze(path_in_expression, symbol_tree)?),
+    }))
+}
+
+fn analyze_path_in_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    path_expr_segment: &CSTNode,
+    repeat_path_expr_segment: &[(CSTNode, CSTNode)],
+) -> Result<ASTNode, Error> {
+    let ast_path_expr_segment = Rc::new(analyze(path_expr_segment, symbol_tree)?);
+    let mut ast_repeat_path_expr_segment = vec![];
+    for (_, expr) in repeat_path_expr_segment {
+        ast_repeat_path_expr_segment.push(analyze(expr, symbol_tree)?);
+    }
+
+    Ok(ASTNode::new(ASTNodeKind::PathInExpression {
+        path_expr_segment: ast_path_expr_segment,
+        repeat_path_expr_segment: ast_repeat_path_expr_segment,
+    }))
+}
+
+fn analyze_path_expr_segment(
+    symbol_tree: &mut SymbolTreeNode,
+    path_ident_segment: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::PathExprSegment {
+        path_ident_segment: Rc::new(analyze(path_ident_segment, symbol_tree)?),
+        generic_args: None, // TODO
+    }))
+}
+
+fn analyze_call_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    expression: &CSTNode,
+    call_params: &Option<Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::CallExpression {
+        expression: Rc::new(analyze(expression, symbol_tree)?),
+        call_params: analyze_call_params(call_params, symbol_tree)?,
+    }))
+}
+
+fn analyze_method_call_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    expression: &CSTNode,
+    path_expr_segment: &CSTNode,
+    call_params: &Option<Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::MethodCallExpression {
+        expression: Rc::new(analyze(expression, symbol_tree)?),
+        path_expr_segment: Rc::new(analyze(path_expr_segment, symbol_tree)?),
+        call_params: analyze_call_params(call_params, symbol_tree)?,
+    }))
+}
+
+fn analyze_field_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    expression: &CSTNode,
+    identifier: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::FieldExpression {
+        expression: Rc::new(analyze(expression, symbol_tree)?),
+        identifier: identifier_name(identifier),
+    }))
+}
+
+fn analyze_await_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    expression: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::AwaitExpression {
+        expression: Rc::new(analyze(expression, symbol_tree)?),
+    }))
+}
+
+fn analyze_index_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    expression: &CSTNode,
+    index: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::IndexExpression {
+        expression: Rc::new(analyze(expression, symbol_tree)?),
+        index: Rc::new(analyze(index, symbol_tree)?),
+    }))
+}
+
+fn analyze_function(symbol_tree: &mut SymbolTreeNode, cst: &CSTNode) -> Result<ASTNode, Error> {
+    let CSTNodeKind::Function {
+        outer_attribute,
+        visibility: _,
+        function_qualifiers,
+        fn_keyword: _,
+        identifier,
+        generic_params,
+        left_parenthesis: _,
+        function_parameters,
+        right_parenthesis: _,
+        function_return_type,
+        where_clause: _,
+        block_expression_or_semicolon,
+    } = &cst.node_kind
+    else {
+        panic!();
+    };
+
+    let ast_outer_attribute = analyze_outer_attributes(symbol_tree, outer_attribute)?;
+    let ast_function_qualifiers = Rc::new(analyze(function_qualifiers, symbol_tree)?);
+    let ident = identifier_name(identifier);
+    let ast_generic_params = analyze_optional(symbol_tree, generic_params)?;
+    let ast_function_parameters = analyze_optional(symbol_tree, function_parameters)?;
+    let ast_return_type = analyze_optional(symbol_tree, function_return_type)?;
+
+    // TODO 戻り値の型
+    if !symbol_tree.insert_function(&ident, None) {
+        //panic!("`{}`関数はすでに定義されています", ident);
+        return Err(redefinition_function_error(&ident, cst.span()));
+    }
+
+    // BlockExpression内の定義をみていく
+    // `;`の場合はNone
+    // 引数はBlockExpressionと同じスコープに入れる
+    let mut ast_block_expression = None;
+    if !matches!(
+        block_expression_or_semicolon.node_kind,
+        CSTNodeKind::Factor {
+            token: Token::Semicolon,
+            ..
+        }
+    ) {
+        let mut scope = symbol_tree.add_child();
+        if let Some(expr) = &ast_function_parameters {
+            bind_function_parameters(expr, &mut scope);
+        }
+        ast_block_expression = Some(Rc::new(analyze(block_expression_or_semicolon, &mut scope)?));
+    }
+
+    Ok(ASTNode::new(ASTNodeKind::Function {
+        outer_attribute: ast_outer_attribute,
+        function_qualifiers: ast_function_qualifiers,
+        identifier: ident,
+        generic_params: ast_generic_params,
+        function_parameters: ast_function_parameters,
+        function_return_type: ast_return_type,
+        where_clause: None,
+        block_expression: ast_block_expression,
+    }))
+}
+
+// (最初のFunctionParam, 残りの`,` FunctionParam)
+type FunctionParams<'a> = (&'a Rc<CSTNode>, &'a Vec<(CSTNode, CSTNode)>);
 
-        Ok(ast)
+// FunctionParam1とFunctionParam2
+fn analyze_function_parameters(
+    symbol_tree: &mut SymbolTreeNode,
+    self_param: Option<&Rc<CSTNode>>,
+    function_param: Option<FunctionParams>,
+) -> Result<ASTNode, Error> {
+    let mut ast_self_param = None;
+    if let Some(expr) = self_param {
+        ast_self_param = Some(Rc::new(analyze(expr, symbol_tree)?));
     }
 
-    pub fn type_check(&mut self) {}
+    let mut function_params = vec![];
+    if let Some((first, repeat)) = function_param {
+        function_params = analyze_repeat(symbol_tree, first, repeat)?;
+    }
+
+    Ok(ASTNode::new(ASTNodeKind::FunctionParameters {
+        self_param: ast_self_param,
+        function_params,
+    }))
 }
 
-fn analyze(cst: &CSTNode, symbol_tree: &mut SymbolTreeNode) -> Result<ASTNode, Error> {
-    let ast = match &cst.node_kind {
-        CSTNodeKind::Crate {
-            inner_attributes:_,
-            items:_,
-        } => {
-            panic!();
-        }
-        CSTNodeKind::Factor {
-            token,
-            row: _,
-            column: _,
-        } => ASTNode::new(ASTNodeKind::Factor {
-            token: token.clone(),
-        }),
-        CSTNodeKind::Literal {
-            literal,
-            row: _,
-            column: _,
-        } => ASTNode::new(ASTNodeKind::Literal {
-            literal: literal.clone(),
-        }),
-        CSTNodeKind::Operator {
-            token,
-            row: _,
-            column: _,
-        } => analyze_operator(cst, symbol_tree, token)?
-                  ,
-        CSTNodeKind::InnerAttribute {
-            pound: _,
-            exclamation: _,
-            left_brackets: _,
-            attribute,
-            right_brackets: _,
-        } => ASTNode::new(ASTNodeKind::InnerAttribute {
-            attribute: Box::new(analyze(attribute, symbol_tree)?),
-        }),
-        CSTNodeKind::OuterAttribute {
-            pound: _,
-            left_brackets: _,
-            attribute,
-            right_brackets: _,
-        } => ASTNode::new(ASTNodeKind::InnerAttribute {
-            attribute: Box::new(analyze(attribute, symbol_tree)?),
-        }),
+// ShorthandSelfとTypedSelf
+fn analyze_self_param(
+    symbol_tree: &mut SymbolTreeNode,
+    outer_attribute: &[CSTNode],
+    reference: bool,
+    mut_keyword: bool,
+    type_expression: Option<&Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    let ast_outer_attribute = analyze_outer_attributes(symbol_tree, outer_attribute)?;
 
-        // Expression
-        CSTNodeKind::Expression { expression } => ASTNode::new(ASTNodeKind::Expression {
-            expression: Box::new(analyze(expression, symbol_tree)?),
-        }),
+    let mut ast_type_expression = None;
+    if let Some(expr) = type_expression {
+        ast_type_expression = Some(Rc::new(analyze(expr, symbol_tree)?));
+    }
 
-        CSTNodeKind::ExpressionWithBlock { outer_attribute, expression_with_block } => {
+    Ok(ASTNode::new(ASTNodeKind::SelfParam {
+        outer_attribute: ast_outer_attribute,
+        reference,
+        mut_keyword,
+        type_expression: ast_type_expression,
+    }))
+}
 
-            let mut ast_outer_attribute = vec![];
-            for expr in outer_attribute.iter() {
-                ast_outer_attribute.push(analyze(expr, symbol_tree)?);
-            }
-            let ast_expression_with_block = Box::new(analyze(expression_with_block, symbol_tree)?);
+fn analyze_function_param(
+    symbol_tree: &mut SymbolTreeNode,
+    outer_attribute: &[CSTNode],
+    pattern_no_top_alt: &CSTNode,
+    type_expression: &CSTNode,
+) -> Result<ASTNode, Error> {
+    let ast_outer_attribute = analyze_outer_attributes(symbol_tree, outer_attribute)?;
 
-            ASTNode::new(ASTNodeKind::ExpressionWithBlock{
-outer_attribute: ast_outer_attribute,
-    expression_with_block: ast_expression_with_block,
+    Ok(ASTNode::new(ASTNodeKind::FunctionParam {
+        outer_attribute: ast_outer_attribute,
+        pattern_no_top_alt: Rc::new(analyze(pattern_no_top_alt, symbol_tree)?),
+        type_expression: Rc::new(analyze(type_expression, symbol_tree)?),
+    }))
+}
 
-            })
-        }
+fn analyze_type_alias(
+    symbol_tree: &mut SymbolTreeNode,
+    outer_attribute: &[CSTNode],
+    identifier: &CSTNode,
+    type_expression: &CSTNode,
+) -> Result<ASTNode, Error> {
+    let ast_outer_attribute = analyze_outer_attributes(symbol_tree, outer_attribute)?;
 
-        CSTNodeKind::ExpressionWithoutBlock { outer_attribute, expression } => {
-           let mut ast_outer_attribute = vec![];
-            for expr in outer_attribute.iter() {
-                ast_outer_attribute.push(analyze(expr, symbol_tree)?);
-            }
-            let ast_expression = Box::new(analyze(expression, symbol_tree)?);
+    Ok(ASTNode::new(ASTNodeKind::TypeAlias {
+        outer_attribute: ast_outer_attribute,
+        identifier: identifier_name(identifier),
+        type_expression: Rc::new(analyze(type_expression, symbol_tree)?),
+    }))
+}
 
-            ASTNode::new(ASTNodeKind::ExpressionWithoutBlock{
-outer_attribute: ast_outer_attribute,
-    expression: ast_expression,
+fn analyze_struct(
+    symbol_tree: &mut SymbolTreeNode,
+    outer_attribute: &[CSTNode],
+    identifier: &CSTNode,
+    struct_fields: &Option<Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    let ast_outer_attribute = analyze_outer_attributes(symbol_tree, outer_attribute)?;
 
-            })
+    let mut ast_struct_fields = vec![];
+    if let Some(fields) = struct_fields {
+        let CSTNodeKind::StructFields {
+            struct_field,
+            repeat_struct_field,
+            comma: _,
+        } = &fields.node_kind
+        else {
+            panic!();
+        };
 
+        ast_struct_fields = analyze_repeat(symbol_tree, struct_field, repeat_struct_field)?;
+    }
 
-        }
+    Ok(ASTNode::new(ASTNodeKind::Struct {
+        outer_attribute: ast_outer_attribute,
+        identifier: identifier_name(identifier),
+        struct_fields: ast_struct_fields,
+    }))
+}
 
-        CSTNodeKind::LiteralExpression { literal } => analyze(literal, symbol_tree)?,
+fn analyze_struct_field(
+    symbol_tree: &mut SymbolTreeNode,
+    outer_attribute: &[CSTNode],
+    identifier: &CSTNode,
+    type_expression: &CSTNode,
+) -> Result<ASTNode, Error> {
+    let ast_outer_attribute = analyze_outer_attributes(symbol_tree, outer_attribute)?;
 
-        CSTNodeKind::BlockExpression {
-            left_brace: _,
-            inner_attribute,
-            statements,
-            right_brace: _,
-        } => {
-            // ブロックの場合はネストしてから
-            let Some(expr) = statements else {
-                panic!();
-            };
-            // InnerAttribute
-            let mut child = symbol_tree.add_child();
-            let mut inner_attri = vec![];
-            for attr in inner_attribute {
-                inner_attri.push(analyze(attr, &mut child)?);
-            }
+    Ok(ASTNode::new(ASTNodeKind::StructField {
+        outer_attribute: ast_outer_attribute,
+        identifier: identifier_name(identifier),
+        type_expression: Rc::new(analyze(type_expression, symbol_tree)?),
+    }))
+}
 
-            // Statement
-            let mut statements = None;
-            if let Ok(statement) = analyze(expr, &mut symbol_tree.add_child()) {
-                statements = Some(Box::new(statement));
-            }
+// implごとにスコープを分け、別のimplにある同じ名前の関数と衝突しないようにする
+fn analyze_implementation(
+    symbol_tree: &mut SymbolTreeNode,
+    outer_attribute: &[CSTNode],
+    trait_path: &Option<(Rc<CSTNode>, Rc<CSTNode>)>,
+    type_expression: &CSTNode,
+    associated_items: &[CSTNode],
+) -> Result<ASTNode, Error> {
+    let ast_outer_attribute = analyze_outer_attributes(symbol_tree, outer_attribute)?;
 
-            ASTNode::new(ASTNodeKind::BlockExpression {
-                inner_attribute: inner_attri,
-                statements,
-            })
-        }
+    let mut ast_trait_path = None;
+    if let Some((expr, _)) = trait_path {
+        ast_trait_path = Some(Rc::new(analyze(expr, symbol_tree)?));
+    }
+    let ast_type_expression = Rc::new(analyze(type_expression, symbol_tree)?);
 
-        CSTNodeKind::PathExpression { path_in_expression } => {
-            ASTNode::new(ASTNodeKind::PathExpression {
-                expression: Box::new(analyze(path_in_expression, symbol_tree)?),
-            })
-        }
+    let mut scope = symbol_tree.add_child();
+    let mut ast_associated_items = vec![];
+    for item in associated_items {
+        ast_associated_items.push(analyze(item, &mut scope)?);
+    }
 
-        CSTNodeKind::PathInExpression {
-            path_separater: _,
-            path_expr_segment,
-            repeat_path_expr_segment,
-        } => {
-            let ast_path_expr_segment = Box::new(analyze(path_expr_segment, symbol_tree)?);
-            let mut ast_repeat_path_expr_segment = vec![];
-            for (_, expr) in repeat_path_expr_segment {
-                ast_repeat_path_expr_segment.push(analyze(expr, symbol_tree)?);
-            }
+    Ok(ASTNode::new(ASTNodeKind::Implementation {
+        outer_attribute: ast_outer_attribute,
+        trait_path: ast_trait_path,
+        type_expression: ast_type_expression,
+        associated_items: ast_associated_items,
+    }))
+}
 
-            ASTNode::new(ASTNodeKind::PathInExpression {
-                path_expr_segment: ast_path_expr_segment,
-                repeat_path_expr_segment: ast_repeat_path_expr_segment,
-            })
-        }
+fn analyze_type_path(
+    symbol_tree: &mut SymbolTreeNode,
+    path_ident_segment: &CSTNode,
+    repeat_path_ident_segment: &[(CSTNode, CSTNode)],
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::TypePath {
+        path_ident_segments: analyze_repeat(
+            symbol_tree,
+            path_ident_segment,
+            repeat_path_ident_segment,
+        )?,
+    }))
+}
 
-        // Function
-        CSTNodeKind::Function {
-            function_qualifiers,
-            fn_keyword: _,
-            identifier,
-            generic_params,
-            left_parenthesis: _,
-            function_parameters,
-            right_parenthesis: _,
-            function_return_type,
-            where_clause,
-            block_expression_or_semicolon,
-        } => {
-            let ast_function_qualifiers = Box::new(analyze(function_qualifiers, symbol_tree)?);
-
-            let CSTNodeKind::Factor {
-                token,
-                row: _,
-                column: _,
-            } = &identifier.node_kind
-            else {
-                panic!();
-            };
-            let Token::Identifier(ident) = token else {
-                panic!();
-            };
-
-            let mut ast_generic_params = None;
-            if let Some(generic_param) = generic_params {
-                ast_generic_params = Some(Box::new(analyze(generic_param, symbol_tree)?));
-            }
+fn analyze_function_qualifiers(
+    symbol_tree: &mut SymbolTreeNode,
+    const_keyword: bool,
+    async_keyword: bool,
+    item_safety: &Option<Rc<CSTNode>>,
+    extern_keyword: bool,
+    abi: &Option<Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::FunctionQualifiers {
+        const_keyword,
+        async_keyword,
+        item_safety: analyze_optional(symbol_tree, item_safety)?,
+        extern_keyword,
+        abi: analyze_optional(symbol_tree, abi)?,
+    }))
+}
 
-            let mut ast_function_parameters = None;
-            if let Some(expr) = function_parameters {
-                ast_function_parameters = Some(Box::new(analyze(expr, symbol_tree)?));
-            }
+fn analyze_return_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    expression: &Option<Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::ReturnExpression {
+        expression: analyze_optional(symbol_tree, expression)?,
+    }))
+}
 
-            let mut ast_return_type = None;
-            if let Some(return_type) = function_return_type {
-                ast_return_type = Some(Box::new(analyze(return_type, symbol_tree)?));
-            }
+fn analyze_if_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    expression: &CSTNode,
+    block_expression: &CSTNode,
+    else_expression: &Option<Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    // 条件の束縛はthenのブロックだけから見える
+    let mut scope = symbol_tree.add_child();
+    let ast_expression = Rc::new(analyze(expression, &mut scope)?);
+    let ast_block_expression = Rc::new(analyze(block_expression, &mut scope)?);
+    let ast_else_expression = analyze_optional(symbol_tree, else_expression)?;
 
-            // TODO 戻り値の型
-            if !symbol_tree.insert_function(ident, None) {
-                //panic!("`{}`関数はすでに定義されています", ident);
-                return Err(Error{
-error_kind: ErrorKind::Semantic(SemanticError::RedefinitionFunction),
-error_text: format!("`{}`関数はすでに定義されています", ident)
-                })
-            }
+    Ok(ASTNode::new(ASTNodeKind::IfExpression {
+        expression: ast_expression,
+        block_expression: ast_block_expression,
+        else_expression: ast_else_expression,
+    }))
+}
 
-            // BlockExpression内の定義をみていく
-            let mut ast_block_expression = None;
-            if let Ok(expr) = analyze(block_expression_or_semicolon, &mut symbol_tree.add_child()) {
-                ast_block_expression = Some(Box::new(expr));
-            }
+fn analyze_if_let_expression(
+    symbol_tree: &mut SymbolTreeNode,
+    pattern: &CSTNode,
+    scrutinee: &CSTNode,
+    block_expression: &CSTNode,
+    else_expression: &Option<Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    let mut scope = symbol_tree.add_child();
+    let ast_pattern = Rc::new(analyze(pattern, &mut scope)?);
+    let ast_scrutinee = Rc::new(analyze(scrutinee, symbol_tree)?);
+    bind_pattern(&ast_pattern, &Rarity::Let, &mut scope);
+    let ast_block_expression = Rc::new(analyze(block_expression, &mut scope)?);
+    let ast_else_expression = analyze_optional(symbol_tree, else_expression)?;
 
-            ASTNode::new(ASTNodeKind::Function {
-                function_qualifiers: ast_function_qualifiers,
-                identifier: ident.to_string(),
-                generic_params: ast_generic_params,
-                function_parameters: ast_function_parameters,
-                function_return_type: ast_return_type,
-                where_clause: None,
-                block_expression: ast_block_expression,
-            })
-        }
+    Ok(ASTNode::new(ASTNodeKind::IfLetExpression {
+        pattern: ast_pattern,
+        scrutinee: ast_scrutinee,
+        block_expression: ast_block_expression,
+        else_expression: ast_else_expression,
+    }))
+}
 
-        CSTNodeKind::FunctionQualifiers {
-            const_keyword,
-            async_keyword,
-            item_safety,
-            extern_keyword,
-            abi,
-        } => {
-            let mut ast_item_safety = None;
-            if let Some(expr) = item_safety {
-                //ast_item_safety = Some();
-            }
+fn analyze_let_chain(
+    symbol_tree: &mut SymbolTreeNode,
+    let_chain_condition: &CSTNode,
+    repeat_let_chain_condition: &[(CSTNode, CSTNode)],
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::LetChain {
+        let_chain_condition: analyze_repeat(
+            symbol_tree,
+            let_chain_condition,
+            repeat_let_chain_condition,
+        )?,
+    }))
+}
 
-            let mut ast_abi = None;
-            if let Some(expr) = abi {
-                //ast_abi = Some();
-            }
+// LetChainの中では後の条件から前の束縛が見える
+fn analyze_let_chain_condition(
+    symbol_tree: &mut SymbolTreeNode,
+    pattern: &CSTNode,
+    scrutinee: &CSTNode,
+) -> Result<ASTNode, Error> {
+    let ast_pattern = Rc::new(analyze(pattern, symbol_tree)?);
+    let ast_scrutinee = Rc::new(analyze(scrutinee, symbol_tree)?);
+    bind_pattern(&ast_pattern, &Rarity::Let, symbol_tree);
 
-            ASTNode::new(ASTNodeKind::FunctionQualifiers {
-                const_keyword: const_keyword.is_some(),
-                async_keyword: async_keyword.is_some(),
-                item_safety: ast_item_safety,
-                extern_keyword: extern_keyword.is_some(),
-                abi: ast_abi,
-            })
-        }
+    Ok(ASTNode::new(ASTNodeKind::LetChainCondition {
+        pattern: ast_pattern,
+        scrutinee: ast_scrutinee,
+    }))
+}
 
-        // Struct
-        CSTNodeKind::StructExpression { expression } => {
-            panic!();
+fn analyze_struct_expr_struct(
+    symbol_tree: &mut SymbolTreeNode,
+    path_in_expression: &CSTNode,
+    expression: &Option<Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    let ast_path_in_expression = Rc::new(analyze(path_in_expression, symbol_tree)?);
+    let mut struct_expr_fields = vec![];
+    let mut struct_base = None;
+    match expression.as_ref().map(|expr| &expr.node_kind) {
+        Some(CSTNodeKind::StructExprFields {
+            struct_expr_filed,
+            struct_expr_filed_repeat,
+            comma: _,
+            struct_base: base,
+        }) => {
+            struct_expr_fields =
+                analyze_repeat(symbol_tree, struct_expr_filed, struct_expr_filed_repeat)?;
+
+            if let Some(base) = base {
+                let CSTNodeKind::StructBase {
+                    dotdot: _,
+                    expression,
+                } = &base.node_kind
+                else {
+                    panic!();
+                };
+                struct_base = Some(Rc::new(analyze(expression, symbol_tree)?));
+            }
         }
+        Some(_) => struct_expr_fields.push(analyze(expression.as_ref().unwrap(), symbol_tree)?),
+        None => (),
+    }
 
-        // Pattern
-        CSTNodeKind::IdentifierPattern {
-            ref_keyword,
-            mut_keyword,
-            identifier,
-            at_symbol:_,
+    Ok(ASTNode::new(ASTNodeKind::StructExpression {
+        path_in_expression: ast_path_in_expression,
+        struct_expr_fields,
+        struct_base,
+    }))
+}
+
+fn analyze_pattern(
+    symbol_tree: &mut SymbolTreeNode,
+    pattern_no_top_alt: &CSTNode,
+    repeat_pattern_no_top_alt: &[(CSTNode, CSTNode)],
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::Pattern {
+        pattern_no_top_alt: analyze_repeat(
+            symbol_tree,
             pattern_no_top_alt,
-        } => analyze_identifier_pattern(symbol_tree, ref_keyword.is_some(), mut_keyword.is_some(), &identifier.node_kind,pattern_no_top_alt)?
-        ,
+            repeat_pattern_no_top_alt,
+        )?,
+    }))
+}
 
-        // Statements
-        CSTNodeKind::Statements => {
-            let mut statements = vec![];
-            for child in cst.children.iter() {
-                statements.push(analyze(child, symbol_tree)?);
-            }
+fn analyze_literal_pattern(literal: &Literal) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::LiteralPattern {
+        literal: literal.clone(),
+    }))
+}
+
+fn analyze_wildcard_pattern(
+    symbol_tree: &mut SymbolTreeNode,
+    wildcard: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::WildcardPattern {
+        wildcard: Rc::new(analyze(wildcard, symbol_tree)?),
+    }))
+}
+
+fn analyze_rest_pattern(
+    symbol_tree: &mut SymbolTreeNode,
+    rest: &CSTNode,
+) -> Result<ASTNode, Error> {
+    Ok(ASTNode::new(ASTNodeKind::RestPattern {
+        rest: Rc::new(analyze(rest, symbol_tree)?),
+    }))
+}
 
-            ASTNode::new(ASTNodeKind::Statements { statements })
+fn analyze_statements(cst: &CSTNode, symbol_tree: &mut SymbolTreeNode) -> Result<ASTNode, Error> {
+    let mut statements = vec![];
+    for child in cst.children.iter() {
+        if matches!(child.node_kind, CSTNodeKind::Error { .. }) {
+            continue;
         }
+        statements.push(analyze(child, symbol_tree)?);
+    }
 
-        CSTNodeKind::Statement { statement } => ASTNode::new(ASTNodeKind::Statement {
-            statement: Some(Box::new(analyze(statement, symbol_tree)?)),
-        }),
+    Ok(ASTNode::new(ASTNodeKind::Statements { statements }))
+}
 
-        CSTNodeKind::LetStatement {
-            outer_attribute,
-            rarity,
-            pattern_no_top_alt,
-            colon: _,
-            type_expression,
-            equal: _,
-            expression,
-            else_keyword: _,
-            block_expression,
-            semicolon: _,
-        } => {
-            analyze_let_statement(symbol_tree, outer_attribute,
-                rarity,pattern_no_top_alt,type_expression,
-                expression,block_expression)?
+// `;`だけの文はNone
+fn analyze_statement(
+    symbol_tree: &mut SymbolTreeNode,
+    statement: &CSTNode,
+) -> Result<ASTNode, Error> {
+    let mut ast_statement = None;
+    if !matches!(
+        statement.node_kind,
+        CSTNodeKind::Factor {
+            token: Token::Semicolon,
+            ..
         }
+    ) {
+        ast_statement = Some(Rc::new(analyze(statement, symbol_tree)?));
+    }
 
-        _ => panic!("{:?}", cst.node_kind),
+    Ok(ASTNode::new(ASTNodeKind::Statement {
+        statement: ast_statement,
+    }))
+}
+
+fn identifier_name(identifier: &CSTNode) -> String {
+    let CSTNodeKind::Factor {
+        token: Token::Identifier(ident),
+        ..
+    } = &identifier.node_kind
+    else {
+        panic!();
     };
 
-    Ok(ast)
+    ident.to_string()
 }
 
 fn analyze_operator(
@@ -350,32 +1611,77 @@ fn analyze_operator(
     symbol_tree: &mut SymbolTreeNode,
     token: &Token,
 ) -> Result<ASTNode, Error> {
-    let Some(left_child) = cst.children.first() else {
-        panic!();
+    let mut operands = vec![];
+    for child in cst.children.iter() {
+        operands.push(analyze(child, symbol_tree)?);
+    }
+
+    let Some(ast) = ASTNode::operator(token, operands) else {
+        return Err(Error {
+            error_kind: ErrorKind::Semantic(SemanticError::UnknownOperator),
+            error_text: format!("`{}`は演算子として使えません", token),
+            position: cst.span().map(|span| span.start_position()),
+            notes: vec![],
+        });
     };
-    let left = Box::new(analyze(left_child, symbol_tree)?);
 
-    let Some(right_child) = cst.children.first() else {
+    Ok(ast)
+}
+
+fn analyze_call_params(
+    call_params: &Option<Rc<CSTNode>>,
+    symbol_tree: &mut SymbolTreeNode,
+) -> Result<Vec<ASTNode>, Error> {
+    let Some(call_params) = call_params else {
+        return Ok(vec![]);
+    };
+    let CSTNodeKind::CallParams {
+        expression,
+        comma_and_expression,
+        comma: _,
+    } = &call_params.node_kind
+    else {
         panic!();
     };
-    let right = Box::new(analyze(right_child, symbol_tree)?);
-
-    let operator = match token {
-        Token::Plus => BinaryOperator::Add,
-        Token::Minus => BinaryOperator::Sub,
-        Token::Star => BinaryOperator::Mul,
-        Token::Slash => BinaryOperator::Div,
-        Token::Percent => BinaryOperator::Mod,
-        Token::Caret => BinaryOperator::Xor,
-        Token::LeftShift => BinaryOperator::LeftShift,
-        Token::RightShift => BinaryOperator::RightShiht,
-        _ => panic!(),
+
+    let mut params = vec![analyze(expression, symbol_tree)?];
+    for (_, expr) in comma_and_expression {
+        params.push(analyze(expr, symbol_tree)?);
+    }
+
+    Ok(params)
+}
+
+fn analyze_struct_expr_field(
+    symbol_tree: &mut SymbolTreeNode,
+    outer_attribute: &Vec<CSTNode>,
+    identifier: &CSTNode,
+    expression: Option<&Rc<CSTNode>>,
+) -> Result<ASTNode, Error> {
+    let mut ast_outer_attribute = vec![];
+    for expr in outer_attribute {
+        ast_outer_attribute.push(analyze(expr, symbol_tree)?);
+    }
+
+    // Identifierかタプルのインデックス
+    let CSTNodeKind::Factor {
+        token,
+        row: _,
+        column: _,
+    } = &identifier.node_kind
+    else {
+        panic!();
     };
 
-    Ok(ASTNode::new(ASTNodeKind::BinaryOperator {
-        operator,
-        left,
-        right,
+    let mut ast_expression = None;
+    if let Some(expr) = expression {
+        ast_expression = Some(Rc::new(analyze(expr, symbol_tree)?));
+    }
+
+    Ok(ASTNode::new(ASTNodeKind::StructExprField {
+        outer_attribute: ast_outer_attribute,
+        identifier: token.to_string(),
+        expression: ast_expression,
     }))
 }
 
@@ -384,7 +1690,7 @@ fn analyze_identifier_pattern(
     ref_keyword: bool,
     mut_keyword: bool,
     node_kind: &CSTNodeKind,
-    pattern_no_top_alt: &Option<Box<CSTNode>>,
+    pattern_no_top_alt: &Option<Rc<CSTNode>>,
 ) -> Result<ASTNode, Error> {
     let CSTNodeKind::Factor {
         token,
@@ -400,7 +1706,7 @@ fn analyze_identifier_pattern(
 
     let mut ast_pattern_no_top_alt = None;
     if let Some(expr) = pattern_no_top_alt {
-        ast_pattern_no_top_alt = Some(Box::new(analyze(expr, symbol_tree)?));
+        ast_pattern_no_top_alt = Some(Rc::new(analyze(expr, symbol_tree)?));
     }
 
     Ok(ASTNode::new(ASTNodeKind::IdentifierPattern {
@@ -416,9 +1722,9 @@ fn analyze_let_statement(
     outer_attribute: &Vec<CSTNode>,
     rarity: &CSTNode,
     pattern_no_top_alt: &CSTNode,
-    type_expression: &Option<Box<CSTNode>>,
-    expression: &Option<Box<CSTNode>>,
-    block_expression: &Option<Box<CSTNode>>,
+    type_expression: &Option<Rc<CSTNode>>,
+    expression: &Option<Rc<CSTNode>>,
+    block_expression: &Option<Rc<CSTNode>>,
 ) -> Result<ASTNode, Error> {
     let mut ast_outer_attribute = vec![];
     for expr in outer_attribute {
@@ -441,23 +1747,28 @@ fn analyze_let_statement(
         _ => panic!("Unknown Rarity"),
     };
 
-    let ast_pattern_no_top_alt = Box::new(analyze(pattern_no_top_alt, symbol_tree)?);
+    let ast_pattern_no_top_alt = Rc::new(analyze(pattern_no_top_alt, symbol_tree)?);
 
     let mut ast_type_expression = None;
     if let Some(expr) = type_expression {
-        ast_type_expression = Some(Box::new(analyze(expr, symbol_tree)?));
+        ast_type_expression = Some(Rc::new(analyze(expr, symbol_tree)?));
     }
 
     let mut ast_expression = None;
     if let Some(expr) = expression {
-        ast_expression = Some(Box::new(analyze(expr, symbol_tree)?));
+        ast_expression = Some(Rc::new(analyze(expr, symbol_tree)?));
     }
 
+    // let-elseのelseブロックからは束縛が見えない
     let mut ast_block_expression = None;
     if let Some(expr) = block_expression {
-        ast_block_expression = Some(Box::new(analyze(expr, symbol_tree)?));
+        let ast = analyze(expr, symbol_tree)?;
+        check_let_else_diverges(&ast)?;
+        ast_block_expression = Some(Rc::new(ast));
     }
 
+    bind_pattern(&ast_pattern_no_top_alt, &ast_rarity, symbol_tree);
+
     Ok(ASTNode::new(ASTNodeKind::LetStatement {
         outer_attribute: ast_outer_attribute,
         rarity: ast_rarity,
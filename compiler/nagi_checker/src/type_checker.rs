@@ -1,8 +1,1032 @@
-// TODO
-pub struct TypeChecker {}
+use nagi_errors::*;
+use nagi_syntax_tree::arena::{Arena, NodeId};
+use nagi_syntax_tree::ast::*;
+use nagi_syntax_tree::span::Span;
+use nagi_syntax_tree::token::*;
 
-impl TypeChecker {
-    pub fn new() -> TypeChecker {
-        Self {}
+use crate::lang_items::{self, LangItem};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ptr;
+use std::rc::Rc;
+
+// 今のところは演算子のオーバーロードを解決するための型推論のみ
+// 推論できない型はUnknownとし、エラーにはしない
+// 推論した型や解決した演算子は、ASTのノードの番号ごとに記録する
+pub struct TypeChecker<'a> {
+    structs: HashMap<String, HashMap<String, Type>>, // 構造体の名前とフィールドの型
+    type_aliases: HashMap<String, Type>,
+    functions: HashMap<String, Type>,         // 関数の戻り値の型
+    methods: HashMap<(String, String), Type>, // (型の名前, メソッドの名前)と戻り値の型
+    trait_impls: Vec<TraitImpl>,
+    scopes: Vec<HashMap<String, (Type, Option<NodeId>)>>, // 変数の型と束縛したパターンのノード
+    impl_context: Option<ImplContext>,
+    operator_methods: Vec<OperatorMethod>,
+    arena: Option<Arena<ASTNode>>, // ノードの番号を引くための、検査している木の配列
+    current_node: Option<(NodeId, &'a ASTNode)>, // 型を推論しているノードとその番号
+    node_types: HashMap<NodeId, Type>,
+    node_operator_methods: HashMap<NodeId, OperatorMethod>,
+    bindings: HashMap<NodeId, NodeId>, // 変数を使ったノードと、その変数を束縛したパターンのノード
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Type {
+    Primitive(String),
+    Named(String), // 構造体
+    Integer,       // 接尾辞の無い整数リテラル
+    Float,         // 接尾辞の無い浮動小数点数リテラル
+    Unit,
+    Unknown,
+}
+
+const PRIMITIVES: [&str; 17] = [
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32",
+    "f64", "bool", "char", "str",
+];
+
+impl Type {
+    fn bool() -> Self {
+        Type::Primitive("bool".to_string())
+    }
+
+    // メソッドを探すときの型の名前
+    fn name(&self) -> Option<&str> {
+        match self {
+            Type::Primitive(name) | Type::Named(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    // 接尾辞の無いリテラルはどの整数型、浮動小数点数型にもなれる
+    fn accepts(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Unknown, _) | (_, Type::Unknown) => true,
+            (Type::Primitive(name), Type::Integer) | (Type::Integer, Type::Primitive(name)) => {
+                name.starts_with('i') || name.starts_with('u')
+            }
+            (Type::Primitive(name), Type::Float) | (Type::Float, Type::Primitive(name)) => {
+                name.starts_with('f')
+            }
+            _ => self == other,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Primitive(name) | Type::Named(name) => write!(f, "{}", name),
+            Type::Integer => write!(f, "{{integer}}"),
+            Type::Float => write!(f, "{{float}}"),
+            Type::Unit => write!(f, "()"),
+            Type::Unknown => write!(f, "_"),
+        }
+    }
+}
+
+// 演算子のトレイトのimpl
+// 右辺の型はメソッドの2番目の引数、結果の型はメソッドの戻り値
+struct TraitImpl {
+    lang_item: LangItem,
+    self_type: Type,
+    rhs_type: Type,
+    output: Type,
+}
+
+// implの中では`Self`と`Self::Output`のような関連型が使える
+struct ImplContext {
+    self_type: Type,
+    type_aliases: HashMap<String, Type>,
+}
+
+// 演算子を解決したメソッド
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperatorMethod {
+    pub operator: &'static str,
+    pub self_type: String,
+    pub trait_name: &'static str,
+    pub method_name: &'static str,
+}
+
+impl fmt::Display for OperatorMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<{} as {}>::{}",
+            self.self_type, self.trait_name, self.method_name
+        )
+    }
+}
+
+// ノードの番号ごとの型チェックの結果
+// 型は表示した文字列で持つ
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeTable {
+    pub node_types: HashMap<NodeId, String>,
+    pub operator_methods: HashMap<NodeId, OperatorMethod>,
+    pub bindings: HashMap<NodeId, NodeId>,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new() -> Self {
+        Self {
+            structs: HashMap::new(),
+            type_aliases: HashMap::new(),
+            functions: HashMap::new(),
+            methods: HashMap::new(),
+            trait_impls: vec![],
+            scopes: vec![],
+            impl_context: None,
+            operator_methods: vec![],
+            arena: None,
+            current_node: None,
+            node_types: HashMap::new(),
+            node_operator_methods: HashMap::new(),
+            bindings: HashMap::new(),
+        }
+    }
+
+    pub fn check(&mut self, ast: &'a ASTNode) -> Result<(), Error> {
+        let ASTNodeKind::Crate { item, .. } = &ast.node_kind else {
+            return Ok(());
+        };
+        // ノードの番号は木の配列と同じ前順の番号にする
+        let arena = Arena::from_tree(ast.clone());
+        self.current_node = Some((arena.root(), ast));
+        self.arena = Some(arena);
+
+        self.collect(item)?;
+        for expr in item {
+            self.infer(expr)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn operator_methods(&self) -> &[OperatorMethod] {
+        &self.operator_methods
+    }
+
+    pub fn type_table(&self) -> TypeTable {
+        TypeTable {
+            node_types: self
+                .node_types
+                .iter()
+                .map(|(id, node_type)| (*id, node_type.to_string()))
+                .collect(),
+            operator_methods: self.node_operator_methods.clone(),
+            bindings: self.bindings.clone(),
+        }
+    }
+
+    // 推論しているノードから子をたどってastの番号を探す
+    // ほとんどは推論しているノードの子なので、孫より先に子を調べる
+    fn node_id(&self, ast: &ASTNode) -> Option<NodeId> {
+        let arena = self.arena.as_ref()?;
+        let mut stack = vec![self.current_node?];
+        while let Some((id, node)) = stack.pop() {
+            if ptr::eq(node, ast) {
+                return Some(id);
+            }
+            for (child_id, child) in arena.children(id).iter().zip(node.children()) {
+                if ptr::eq(child, ast) {
+                    return Some(*child_id);
+                }
+                stack.push((*child_id, child));
+            }
+        }
+        None
+    }
+
+    fn child_id(&self, id: NodeId, index: usize) -> Option<NodeId> {
+        let arena = self.arena.as_ref()?;
+        arena.children(id).get(index).copied()
+    }
+
+    //
+    // 宣言の収集
+    //
+
+    fn collect(&mut self, items: &[ASTNode]) -> Result<(), Error> {
+        // フィールドや引数の型から参照できるように、構造体の名前を先に集める
+        for item in items {
+            if let ASTNodeKind::Struct { identifier, .. } = &item.node_kind {
+                self.structs.insert(identifier.clone(), HashMap::new());
+            }
+        }
+        for item in items {
+            if let ASTNodeKind::TypeAlias {
+                identifier,
+                type_expression,
+                ..
+            } = &item.node_kind
+            {
+                let alias = self.resolve_type(type_expression);
+                self.type_aliases.insert(identifier.clone(), alias);
+            }
+        }
+
+        for item in items {
+            match &item.node_kind {
+                ASTNodeKind::Struct {
+                    identifier,
+                    struct_fields,
+                    ..
+                } => {
+                    let mut fields = HashMap::new();
+                    for field in struct_fields {
+                        if let ASTNodeKind::StructField {
+                            identifier,
+                            type_expression,
+                            ..
+                        } = &field.node_kind
+                        {
+                            fields.insert(identifier.clone(), self.resolve_type(type_expression));
+                        }
+                    }
+                    self.structs.insert(identifier.clone(), fields);
+                }
+                ASTNodeKind::Function {
+                    identifier,
+                    function_return_type,
+                    ..
+                } => {
+                    let return_type = self.return_type(function_return_type);
+                    self.functions.insert(identifier.clone(), return_type);
+                }
+                ASTNodeKind::Implementation {
+                    trait_path,
+                    type_expression,
+                    associated_items,
+                    ..
+                } => self.collect_implementation(
+                    trait_path,
+                    type_expression,
+                    associated_items,
+                    item.span,
+                )?,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn collect_implementation(
+        &mut self,
+        trait_path: &Option<Rc<ASTNode>>,
+        type_expression: &ASTNode,
+        associated_items: &[ASTNode],
+        span: Option<Span>, // implの範囲
+    ) -> Result<(), Error> {
+        let self_type = self.resolve_type(type_expression);
+        let previous = self.enter_impl(self_type.clone(), associated_items);
+
+        let mut functions = HashMap::new();
+        for item in associated_items {
+            if let ASTNodeKind::Function {
+                identifier,
+                function_parameters,
+                function_return_type,
+                ..
+            } = &item.node_kind
+            {
+                let return_type = self.return_type(function_return_type);
+                if let Some(name) = self_type.name() {
+                    self.methods
+                        .insert((name.to_string(), identifier.clone()), return_type.clone());
+                }
+                functions.insert(identifier.as_str(), (function_parameters, return_type));
+            }
+        }
+
+        let lang_item = trait_path
+            .as_deref()
+            .and_then(last_segment_name)
+            .and_then(|name| lang_items::from_trait_name(&name));
+        if let Some(lang_item) = lang_item {
+            let Some((function_parameters, output)) = functions.get(lang_item.method_name) else {
+                return Err(missing_trait_item_error(lang_item, &self_type, span));
+            };
+
+            let rhs_type = match function_parameters
+                .as_deref()
+                .map(|params| &params.node_kind)
+            {
+                Some(ASTNodeKind::FunctionParameters {
+                    self_param,
+                    function_params,
+                }) => {
+                    // selfを除いた最初の引数が右辺
+                    let index = if self_param.is_some() { 0 } else { 1 };
+                    match function_params.get(index).map(|param| &param.node_kind) {
+                        Some(ASTNodeKind::FunctionParam {
+                            type_expression, ..
+                        }) => self.resolve_type(type_expression),
+                        _ => Type::Unknown,
+                    }
+                }
+                _ => Type::Unknown,
+            };
+
+            self.trait_impls.push(TraitImpl {
+                lang_item,
+                self_type,
+                rhs_type,
+                output: output.clone(),
+            });
+        }
+
+        self.impl_context = previous;
+        Ok(())
+    }
+
+    fn enter_impl(&mut self, self_type: Type, associated_items: &[ASTNode]) -> Option<ImplContext> {
+        let previous = self.impl_context.replace(ImplContext {
+            self_type,
+            type_aliases: HashMap::new(),
+        });
+
+        for item in associated_items {
+            if let ASTNodeKind::TypeAlias {
+                identifier,
+                type_expression,
+                ..
+            } = &item.node_kind
+            {
+                let alias = self.resolve_type(type_expression);
+                if let Some(context) = &mut self.impl_context {
+                    context.type_aliases.insert(identifier.clone(), alias);
+                }
+            }
+        }
+
+        previous
+    }
+
+    //
+    // 型の解決
+    //
+
+    fn resolve_type(&self, type_expression: &ASTNode) -> Type {
+        let ASTNodeKind::TypePath {
+            path_ident_segments,
+        } = &type_expression.node_kind
+        else {
+            return Type::Unknown;
+        };
+
+        let names: Vec<String> = path_ident_segments.iter().map(segment_name).collect();
+        match names.as_slice() {
+            [self_type, alias] if self_type == "Self" => self
+                .impl_context
+                .as_ref()
+                .and_then(|context| context.type_aliases.get(alias))
+                .cloned()
+                .unwrap_or(Type::Unknown),
+            [.., name] => self.named_type(name),
+            [] => Type::Unknown,
+        }
+    }
+
+    fn named_type(&self, name: &str) -> Type {
+        if name == "Self" {
+            return self
+                .impl_context
+                .as_ref()
+                .map_or(Type::Unknown, |context| context.self_type.clone());
+        }
+        if PRIMITIVES.contains(&name) {
+            return Type::Primitive(name.to_string());
+        }
+        if self.structs.contains_key(name) {
+            return Type::Named(name.to_string());
+        }
+
+        self.type_aliases
+            .get(name)
+            .cloned()
+            .unwrap_or(Type::Unknown)
+    }
+
+    fn return_type(&self, function_return_type: &Option<Rc<ASTNode>>) -> Type {
+        match function_return_type {
+            Some(expr) => self.resolve_type(expr),
+            None => Type::Unit,
+        }
+    }
+
+    //
+    // 型推論
+    //
+
+    fn infer(&mut self, ast: &'a ASTNode) -> Result<Type, Error> {
+        let node_id = self.node_id(ast);
+        self.infer_with_id(ast, node_id)
+    }
+
+    fn infer_with_id(&mut self, ast: &'a ASTNode, node_id: Option<NodeId>) -> Result<Type, Error> {
+        let parent_node = std::mem::replace(&mut self.current_node, node_id.map(|id| (id, ast)));
+        let ast_type = self.infer_node(ast);
+        self.current_node = parent_node;
+
+        let ast_type = ast_type?;
+        if let Some(id) = node_id {
+            self.node_types.insert(id, ast_type.clone());
+        }
+        Ok(ast_type)
+    }
+
+    fn infer_node(&mut self, ast: &'a ASTNode) -> Result<Type, Error> {
+        let ast_type = match &ast.node_kind {
+            ASTNodeKind::Function {
+                function_parameters,
+                block_expression,
+                ..
+            } => {
+                self.scopes.push(HashMap::new());
+                if let Some(params) = function_parameters {
+                    self.bind_function_parameters(params);
+                }
+                if let Some(expr) = block_expression {
+                    self.infer(expr)?;
+                }
+                self.scopes.pop();
+
+                Type::Unit
+            }
+
+            ASTNodeKind::Implementation {
+                type_expression,
+                associated_items,
+                ..
+            } => {
+                let self_type = self.resolve_type(type_expression);
+                let previous = self.enter_impl(self_type, associated_items);
+                for item in associated_items {
+                    self.infer(item)?;
+                }
+                self.impl_context = previous;
+
+                Type::Unit
+            }
+
+            ASTNodeKind::Struct { .. } | ASTNodeKind::TypeAlias { .. } => Type::Unit,
+
+            ASTNodeKind::Literal { literal } => literal_type(literal),
+
+            ASTNodeKind::Expression { expression }
+            | ASTNodeKind::ExpressionWithoutBlock { expression, .. }
+            | ASTNodeKind::PathExpression { expression }
+            | ASTNodeKind::ExpressionWithBlock {
+                expression_with_block: expression,
+                ..
+            }
+            | ASTNodeKind::UnsafeBlockExpression {
+                block_expression: expression,
+            }
+            | ASTNodeKind::ConstBlockExpression {
+                block_expression: expression,
+            } => self.infer(expression)?,
+
+            ASTNodeKind::PathInExpression { .. } => match path_names(ast).as_slice() {
+                [name] => self.variable_type(ast, name),
+                _ => Type::Unknown,
+            },
+
+            ASTNodeKind::BlockExpression { statements, .. } => {
+                self.scopes.push(HashMap::new());
+                let block_type = match statements {
+                    Some(expr) => self.infer(expr),
+                    None => Ok(Type::Unit),
+                };
+                self.scopes.pop();
+
+                block_type?
+            }
+
+            // 最後のStatementで包まれていない式がブロックの値
+            ASTNodeKind::Statements { statements } => {
+                let mut block_type = Type::Unit;
+                for statement in statements {
+                    block_type = self.infer(statement)?;
+                }
+
+                match statements.last().map(|statement| &statement.node_kind) {
+                    Some(ASTNodeKind::Statement { .. }) => Type::Unit,
+                    _ => block_type,
+                }
+            }
+
+            ASTNodeKind::LetStatement {
+                pattern_no_top_alt,
+                type_expression,
+                expression,
+                block_expression,
+                ..
+            } => {
+                let mut value_type = Type::Unknown;
+                if let Some(expr) = expression {
+                    value_type = self.infer(expr)?;
+                }
+                if let Some(expr) = block_expression {
+                    self.infer(expr)?;
+                }
+                if let Some(expr) = type_expression {
+                    value_type = self.resolve_type(expr);
+                }
+
+                self.bind_pattern(pattern_no_top_alt, value_type);
+                Type::Unit
+            }
+
+            ASTNodeKind::IfExpression {
+                expression,
+                block_expression,
+                else_expression,
+            } => {
+                self.scopes.push(HashMap::new());
+                let block_type = self
+                    .infer(expression)
+                    .and_then(|_| self.infer(block_expression));
+                self.scopes.pop();
+
+                let block_type = block_type?;
+                match else_expression {
+                    Some(expr) => {
+                        self.infer(expr)?;
+                        block_type
+                    }
+                    None => Type::Unit,
+                }
+            }
+
+            ASTNodeKind::IfLetExpression {
+                pattern,
+                scrutinee,
+                block_expression,
+                else_expression,
+            } => {
+                self.infer(scrutinee)?;
+
+                self.scopes.push(HashMap::new());
+                self.bind_pattern(pattern, Type::Unknown);
+                let block_type = self.infer(block_expression);
+                self.scopes.pop();
+
+                let block_type = block_type?;
+                match else_expression {
+                    Some(expr) => {
+                        self.infer(expr)?;
+                        block_type
+                    }
+                    None => Type::Unit,
+                }
+            }
+
+            ASTNodeKind::LetChainCondition { pattern, scrutinee } => {
+                self.infer(scrutinee)?;
+                self.bind_pattern(pattern, Type::Unknown);
+                Type::bool()
+            }
+
+            ASTNodeKind::BinaryOperator { .. } => self.infer_binary_operator(ast)?,
+
+            ASTNodeKind::UnaryOperator {
+                operator,
+                expression,
+            } => {
+                let expression_type = self.infer(expression)?;
+                match (&expression_type, operator) {
+                    (Type::Named(_), _) => self.resolve_operator(
+                        lang_items::unary_operator(operator),
+                        operator.as_str(),
+                        expression_type,
+                        None,
+                    )?,
+                    _ => expression_type,
+                }
+            }
+
+            ASTNodeKind::IndexExpression { expression, index } => {
+                let expression_type = self.infer(expression)?;
+                let index_type = self.infer(index)?;
+                match expression_type {
+                    Type::Named(_) => self.resolve_operator(
+                        lang_items::INDEX,
+                        "[]",
+                        expression_type,
+                        Some(index_type),
+                    )?,
+                    _ => Type::Unknown,
+                }
+            }
+
+            ASTNodeKind::CallExpression {
+                expression,
+                call_params,
+            } => {
+                for param in call_params {
+                    self.infer(param)?;
+                }
+
+                // `f(..)`か`Type::f(..)`
+                match path_names(unwrap_expression(expression)).as_slice() {
+                    [name] => self.functions.get(name).cloned().unwrap_or(Type::Unknown),
+                    [.., type_name, method] => {
+                        self.method_type(&self.named_type(type_name), method)
+                    }
+                    [] => {
+                        self.infer(expression)?;
+                        Type::Unknown
+                    }
+                }
+            }
+
+            ASTNodeKind::MethodCallExpression {
+                expression,
+                path_expr_segment,
+                call_params,
+            } => {
+                let receiver_type = self.infer(expression)?;
+                for param in call_params {
+                    self.infer(param)?;
+                }
+
+                match &path_expr_segment.node_kind {
+                    ASTNodeKind::PathExprSegment {
+                        path_ident_segment, ..
+                    } => self.method_type(&receiver_type, &segment_name(path_ident_segment)),
+                    _ => Type::Unknown,
+                }
+            }
+
+            ASTNodeKind::FieldExpression {
+                expression,
+                identifier,
+            } => {
+                let expression_type = self.infer(expression)?;
+                match &expression_type {
+                    Type::Named(name) => self
+                        .structs
+                        .get(name)
+                        .and_then(|fields| fields.get(identifier))
+                        .cloned()
+                        .unwrap_or(Type::Unknown),
+                    _ => Type::Unknown,
+                }
+            }
+
+            ASTNodeKind::StructExpression {
+                path_in_expression, ..
+            } => {
+                for child in ast.children() {
+                    self.infer(child)?;
+                }
+
+                match path_names(path_in_expression).last() {
+                    Some(name) => self.named_type(name),
+                    None => Type::Unknown,
+                }
+            }
+
+            _ => {
+                for child in ast.children() {
+                    self.infer(child)?;
+                }
+                Type::Unknown
+            }
+        };
+
+        Ok(ast_type)
+    }
+
+    // `a + b + c`のような左結合の連なりは左の枝が深くなるので、再帰せずに左の枝をたどり
+    // 内側の演算子から順に解決する
+    fn infer_binary_operator(&mut self, ast: &'a ASTNode) -> Result<Type, Error> {
+        // 左の枝と右の枝の番号は、演算子のノードの番号から引く
+        let mut chain = vec![];
+        let mut node = ast;
+        let mut node_id = self.current_node.map(|(id, _)| id);
+        while let ASTNodeKind::BinaryOperator {
+            operator,
+            left,
+            right,
+        } = &node.node_kind
+        {
+            let right_id = node_id.and_then(|id| self.child_id(id, 1));
+            chain.push((node, node_id, operator, right, right_id));
+            node_id = node_id.and_then(|id| self.child_id(id, 0));
+            node = left;
+        }
+
+        let mut left_type = self.infer_with_id(node, node_id)?;
+        for (node, node_id, operator, right, right_id) in chain.into_iter().rev() {
+            let right_type = self.infer_with_id(right, right_id)?;
+
+            let parent_node =
+                std::mem::replace(&mut self.current_node, node_id.map(|id| (id, node)));
+            let operator_type = self.binary_operator_type(operator, left_type, right_type);
+            self.current_node = parent_node;
+
+            left_type = operator_type?;
+            if let Some(id) = node_id {
+                self.node_types.insert(id, left_type.clone());
+            }
+        }
+
+        Ok(left_type)
+    }
+
+    fn binary_operator_type(
+        &mut self,
+        operator: &BinaryOperator,
+        left_type: Type,
+        right_type: Type,
+    ) -> Result<Type, Error> {
+        let Some(lang_item) = lang_items::binary_operator(operator) else {
+            let operator_type = match operator {
+                BinaryOperator::LazyAnd | BinaryOperator::LazyOr => Type::bool(),
+                BinaryOperator::Assign => Type::Unit,
+                _ => Type::Unknown,
+            };
+            return Ok(operator_type);
+        };
+
+        // どちらかが構造体ならimplを探す
+        if matches!(left_type, Type::Named(_)) || matches!(right_type, Type::Named(_)) {
+            return self.resolve_operator(
+                lang_item,
+                operator.as_str(),
+                left_type,
+                Some(right_type),
+            );
+        }
+
+        let operator_type = if lang_items::is_comparison(lang_item) {
+            Type::bool()
+        } else if lang_items::is_assign(lang_item) {
+            Type::Unit
+        } else if matches!(left_type, Type::Integer | Type::Float | Type::Unknown) {
+            right_type
+        } else {
+            left_type
+        };
+
+        Ok(operator_type)
+    }
+
+    // 演算子をトレイトのメソッドに解決し、その結果の型を返す
+    fn resolve_operator(
+        &mut self,
+        lang_item: LangItem,
+        operator: &'static str,
+        self_type: Type,
+        rhs_type: Option<Type>,
+    ) -> Result<Type, Error> {
+        // 型が分からなければどのimplか決められないので調べない
+        if self_type == Type::Unknown || rhs_type == Some(Type::Unknown) {
+            return Ok(Type::Unknown);
+        }
+
+        let Some(trait_impl) = self.trait_impls.iter().find(|trait_impl| {
+            trait_impl.lang_item == lang_item
+                && trait_impl.self_type.accepts(&self_type)
+                && rhs_type
+                    .as_ref()
+                    .is_none_or(|rhs_type| trait_impl.rhs_type.accepts(rhs_type))
+        }) else {
+            // 右辺の型が違うimplしか無ければ右辺の型も示す
+            let rhs_type = rhs_type.filter(|rhs_type| {
+                rhs_type != &self_type
+                    || self.trait_impls.iter().any(|trait_impl| {
+                        trait_impl.lang_item == lang_item
+                            && trait_impl.self_type.accepts(&self_type)
+                    })
+            });
+            // 演算子のノードの範囲を示す
+            let span = self.current_node.and_then(|(_, node)| node.span);
+            return Err(no_operator_implementation_error(
+                operator,
+                &self_type,
+                rhs_type.as_ref(),
+                span,
+            ));
+        };
+
+        let operator_method = OperatorMethod {
+            operator,
+            self_type: trait_impl.self_type.to_string(),
+            trait_name: lang_item.trait_name,
+            method_name: lang_item.method_name,
+        };
+        if let Some((id, _)) = self.current_node {
+            self.node_operator_methods
+                .insert(id, operator_method.clone());
+        }
+        self.operator_methods.push(operator_method);
+
+        let output = if lang_items::is_comparison(lang_item) {
+            Type::bool()
+        } else if lang_items::is_assign(lang_item) {
+            Type::Unit
+        } else {
+            trait_impl.output.clone()
+        };
+
+        Ok(output)
+    }
+
+    fn method_type(&self, receiver_type: &Type, method: &str) -> Type {
+        receiver_type
+            .name()
+            .and_then(|name| self.methods.get(&(name.to_string(), method.to_string())))
+            .cloned()
+            .unwrap_or(Type::Unknown)
+    }
+
+    //
+    // 変数
+    //
+
+    // 変数を使ったノードと束縛したノードを結び付ける
+    fn variable_type(&mut self, ast: &ASTNode, name: &str) -> Type {
+        let Some((variable_type, binding)) = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+        else {
+            return Type::Unknown;
+        };
+
+        if let (Some(id), Some(binding)) = (self.node_id(ast), binding) {
+            self.bindings.insert(id, binding);
+        }
+        variable_type
+    }
+
+    fn bind_function_parameters(&mut self, function_parameters: &ASTNode) {
+        let ASTNodeKind::FunctionParameters {
+            self_param,
+            function_params,
+        } = &function_parameters.node_kind
+        else {
+            return;
+        };
+
+        if let Some(ASTNodeKind::SelfParam {
+            type_expression, ..
+        }) = self_param.as_deref().map(|param| &param.node_kind)
+        {
+            let self_type = match type_expression {
+                Some(expr) => self.resolve_type(expr),
+                None => self.named_type("Self"),
+            };
+            let binding = self_param.as_deref().and_then(|param| self.node_id(param));
+            self.bind_variable("self", self_type, binding);
+        }
+
+        for param in function_params {
+            if let ASTNodeKind::FunctionParam {
+                pattern_no_top_alt,
+                type_expression,
+                ..
+            } = &param.node_kind
+            {
+                let param_type = self.resolve_type(type_expression);
+                self.bind_pattern(pattern_no_top_alt, param_type);
+            }
+        }
+    }
+
+    // 識別子だけのパターンには型を付け、それ以外のパターンの束縛は型をUnknownとする
+    fn bind_pattern(&mut self, pattern: &ASTNode, pattern_type: Type) {
+        match &pattern.node_kind {
+            ASTNodeKind::IdentifierPattern {
+                identifier,
+                pattern_no_top_alt: None,
+                ..
+            } => self.bind_variable(identifier, pattern_type, self.node_id(pattern)),
+            ASTNodeKind::IdentifierPattern {
+                identifier,
+                pattern_no_top_alt: Some(expr),
+                ..
+            } => {
+                self.bind_variable(identifier, Type::Unknown, self.node_id(pattern));
+                self.bind_pattern(expr, Type::Unknown);
+            }
+            _ => {
+                for child in pattern.children() {
+                    self.bind_pattern(child, Type::Unknown);
+                }
+            }
+        }
+    }
+
+    fn bind_variable(&mut self, name: &str, variable_type: Type, binding: Option<NodeId>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), (variable_type, binding));
+        }
+    }
+}
+
+fn literal_type(literal: &Literal) -> Type {
+    match literal.literal_kind {
+        LiteralKind::Bool(_) => Type::bool(),
+        LiteralKind::Char => Type::Primitive("char".to_string()),
+        LiteralKind::Byte => Type::Primitive("u8".to_string()),
+        LiteralKind::Str | LiteralKind::StrRaw => Type::Primitive("str".to_string()),
+        LiteralKind::Integer | LiteralKind::Float if !literal.suffix.is_empty() => {
+            Type::Primitive(literal.suffix.clone())
+        }
+        LiteralKind::Integer => Type::Integer,
+        LiteralKind::Float => Type::Float,
+        _ => Type::Unknown,
+    }
+}
+
+// 式を包んでいるだけのノードを外す
+fn unwrap_expression(ast: &ASTNode) -> &ASTNode {
+    match &ast.node_kind {
+        ASTNodeKind::Expression { expression }
+        | ASTNodeKind::ExpressionWithoutBlock { expression, .. }
+        | ASTNodeKind::PathExpression { expression } => unwrap_expression(expression),
+        _ => ast,
+    }
+}
+
+// PathInExpressionの各セグメントの名前
+fn path_names(ast: &ASTNode) -> Vec<String> {
+    let ASTNodeKind::PathInExpression {
+        path_expr_segment,
+        repeat_path_expr_segment,
+    } = &ast.node_kind
+    else {
+        return vec![];
+    };
+
+    std::iter::once(path_expr_segment.as_ref())
+        .chain(repeat_path_expr_segment)
+        .map(|segment| match &segment.node_kind {
+            ASTNodeKind::PathExprSegment {
+                path_ident_segment, ..
+            } => segment_name(path_ident_segment),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+fn segment_name(segment: &ASTNode) -> String {
+    match &segment.node_kind {
+        ASTNodeKind::Factor { token } => token.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn last_segment_name(type_path: &ASTNode) -> Option<String> {
+    let ASTNodeKind::TypePath {
+        path_ident_segments,
+    } = &type_path.node_kind
+    else {
+        return None;
+    };
+
+    path_ident_segments.last().map(segment_name)
+}
+
+fn no_operator_implementation_error(
+    operator: &str,
+    self_type: &Type,
+    rhs_type: Option<&Type>,
+    span: Option<Span>,
+) -> Error {
+    let error_text = match rhs_type {
+        Some(rhs_type) => format!(
+            "`{}`には右辺が`{}`の`{}`の実装がありません",
+            self_type, rhs_type, operator
+        ),
+        _ => format!("`{}`には`{}`の実装がありません", self_type, operator),
+    };
+
+    Error {
+        error_kind: ErrorKind::Semantic(SemanticError::NoOperatorImplementation),
+        error_text,
+        position: span.map(|span| span.start_position()),
+        notes: vec![],
+    }
+}
+
+fn missing_trait_item_error(lang_item: LangItem, self_type: &Type, span: Option<Span>) -> Error {
+    Error {
+        error_kind: ErrorKind::Semantic(SemanticError::MissingTraitItem),
+        error_text: format!(
+            "`{}`の`{}`の実装に`{}`関数がありません",
+            self_type, lang_item.trait_name, lang_item.method_name
+        ),
+        position: span.map(|span| span.start_position()),
+        notes: vec![],
     }
 }
@@ -0,0 +1,174 @@
+use nagi_errors::*;
+use nagi_syntax_tree::ast::*;
+use nagi_syntax_tree::keywords::Keyword;
+use nagi_syntax_tree::span::Span;
+use nagi_syntax_tree::token::Token;
+
+use std::collections::HashMap;
+
+// unsafeな操作がunsafeな文脈(unsafe fnの本体かunsafeブロック)の中にあるか調べる
+// 今のところunsafeな操作はunsafe fnの呼び出しのみ
+pub struct UnsafeChecker {
+    // スコープごとに、見えている関数がunsafe fnかどうか
+    scopes: Vec<HashMap<String, bool>>,
+    is_unsafe_context: bool,
+}
+
+impl UnsafeChecker {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![],
+            is_unsafe_context: false,
+        }
+    }
+
+    pub fn check(&mut self, ast: &ASTNode) -> Result<(), Error> {
+        match &ast.node_kind {
+            // アイテムはスコープ内のどこからでも見えるので先に登録する
+            ASTNodeKind::Crate { item, .. } => {
+                self.scopes.push(declare_functions(item.iter()));
+                self.check_children(ast)?;
+                self.scopes.pop();
+            }
+            ASTNodeKind::BlockExpression { statements, .. } => {
+                let mut scope = HashMap::new();
+                if let Some(statements) = statements {
+                    scope = declare_functions(statements.children().into_iter().filter_map(
+                        |statement| match &statement.node_kind {
+                            ASTNodeKind::Statement {
+                                statement: Some(statement),
+                            } => Some(statement.as_ref()),
+                            _ => None,
+                        },
+                    ));
+                }
+
+                self.scopes.push(scope);
+                self.check_children(ast)?;
+                self.scopes.pop();
+            }
+
+            // 関数の中はその関数の安全性で決まり、外側のunsafeブロックは引き継がない
+            ASTNodeKind::Function {
+                function_qualifiers,
+                ..
+            } => {
+                let is_unsafe_context = self.is_unsafe_context;
+                self.is_unsafe_context = is_unsafe_function(function_qualifiers);
+                self.check_children(ast)?;
+                self.is_unsafe_context = is_unsafe_context;
+            }
+            ASTNodeKind::UnsafeBlockExpression { .. } => {
+                let is_unsafe_context = self.is_unsafe_context;
+                self.is_unsafe_context = true;
+                self.check_children(ast)?;
+                self.is_unsafe_context = is_unsafe_context;
+            }
+
+            ASTNodeKind::CallExpression { expression, .. } => {
+                if let Some(identifier) = callee_identifier(expression) {
+                    if !self.is_unsafe_context && self.is_unsafe_function_name(identifier) {
+                        return Err(unsafe_call_error(identifier, ast.span));
+                    }
+                }
+                self.check_children(ast)?;
+            }
+
+            _ => self.check_children(ast)?,
+        }
+
+        Ok(())
+    }
+
+    fn check_children(&mut self, ast: &ASTNode) -> Result<(), Error> {
+        for child in ast.children() {
+            self.check(child)?;
+        }
+
+        Ok(())
+    }
+
+    // 内側のスコープから探す
+    fn is_unsafe_function_name(&self, identifier: &str) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(identifier))
+            .copied()
+            .unwrap_or(false)
+    }
+}
+
+fn declare_functions<'a>(items: impl Iterator<Item = &'a ASTNode>) -> HashMap<String, bool> {
+    let mut scope = HashMap::new();
+    for item in items {
+        if let ASTNodeKind::Function {
+            function_qualifiers,
+            identifier,
+            ..
+        } = &item.node_kind
+        {
+            scope.insert(identifier.clone(), is_unsafe_function(function_qualifiers));
+        }
+    }
+
+    scope
+}
+
+fn is_unsafe_function(function_qualifiers: &ASTNode) -> bool {
+    let ASTNodeKind::FunctionQualifiers {
+        item_safety: Some(item_safety),
+        ..
+    } = &function_qualifiers.node_kind
+    else {
+        return false;
+    };
+
+    matches!(
+        item_safety.node_kind,
+        ASTNodeKind::Factor {
+            token: Token::Keyword(Keyword::Unsafe)
+        }
+    )
+}
+
+// 呼び出し先が`g`のような一つの識別子だけのパスなら、その名前を返す
+fn callee_identifier(expression: &ASTNode) -> Option<&str> {
+    match &expression.node_kind {
+        ASTNodeKind::ExpressionWithoutBlock { expression, .. }
+        | ASTNodeKind::PathExpression { expression } => callee_identifier(expression),
+        ASTNodeKind::PathInExpression {
+            path_expr_segment,
+            repeat_path_expr_segment,
+        } if repeat_path_expr_segment.is_empty() => {
+            let ASTNodeKind::PathExprSegment {
+                path_ident_segment, ..
+            } = &path_expr_segment.node_kind
+            else {
+                return None;
+            };
+            let ASTNodeKind::Factor {
+                token: Token::Identifier(identifier),
+            } = &path_ident_segment.node_kind
+            else {
+                return None;
+            };
+
+            Some(identifier)
+        }
+        _ => None,
+    }
+}
+
+// 呼び出しの式の位置を示す
+fn unsafe_call_error(ident: &str, span: Option<Span>) -> Error {
+    Error {
+        error_kind: ErrorKind::Semantic(SemanticError::UnsafeOperation),
+        error_text: format!(
+            "`{}`はunsafe関数なので、unsafe関数かunsafeブロックの中でしか呼び出せません",
+            ident
+        ),
+        position: span.map(|span| span.start_position()),
+        notes: vec![],
+    }
+}
@@ -1,53 +1,118 @@
+use serde::Deserialize;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// 構文木のクレートが使う設定は、そちらで定義したものをそのまま使う
+pub use nagi_syntax_tree::graph::GraphFormat;
+pub use nagi_syntax_tree::pretty::{BraceStyle, FormatStyle, TrailingComma};
+pub use nagi_syntax_tree::serialize::TreeFormat;
 
 const NAGI_EXTENSION: &str = "nag";
-const NAGI_AST_EXTENSION: &str = "ast";
+const NAGI_CONFIG_FILE: &str = "nagi.toml";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ParseTraceFormat {
+    Json,
+    Html,
+}
+
+// 構文解析器
+// Cstは具象構文木を作ってからチェッカーでASTに変換する
+// Astはトークンから直接ASTを作る
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ParseFrontEnd {
+    Cst,
+    Ast,
+}
+
+// 構文解析の制限
+// エディタやビルドサーバーの中で異常な入力を解析してもプロセスが落ちないようにする
+#[derive(Debug, Clone)]
+pub struct ParseLimits {
+    pub max_depth: usize,        // 規則の入れ子の深さ
+    pub max_tokens: usize,       // トークン数
+    pub max_memo_entries: usize, // メモ表の要素数
+    pub cancellation: CancellationToken,
+}
+
+impl Default for ParseLimits {
+    // max_depthはデバッグビルドでもメインスレッドの8MiBのスタックで溢れない深さ
+    fn default() -> Self {
+        Self {
+            max_depth: 512,
+            max_tokens: 1_000_000,
+            max_memo_entries: 10_000_000,
+            cancellation: CancellationToken::default(),
+        }
+    }
+}
+
+// 呼び出し側から解析を中断させる
+// cloneしたものは同じ状態を共有するので、別のスレッドからcancelできる
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    is_cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.is_cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled.load(Ordering::Relaxed)
+    }
+}
 
 #[derive(Debug)]
 pub struct CompileCommandOption {
     pub is_debug: bool,
     pub is_compiler_debug: bool,
-    pub is_output_ast: bool,
+    pub is_output_ast: bool, // チェックしたASTを対象ファイルの隣に書き出す
+    pub tree_format: TreeFormat,
+    pub parse_trace: Option<ParseTraceFormat>,
+    pub graph: Option<GraphFormat>, // CST、AST、スコープの図を対象ファイルの隣に書き出す
+    pub parser: ParseFrontEnd,
+    pub parse_limits: ParseLimits,
     pub target_list: Vec<String>,
 }
 
 impl CompileCommandOption {
-    pub fn new(args: &Vec<String>) -> Result<Self, String> {
+    pub fn new(args: &[String]) -> Result<Self, String> {
         let mut is_debug = false;
         let mut is_compiler_debug = false;
         let mut is_output_ast = false;
+        let mut tree_format = TreeFormat::Json;
+        let mut parse_trace = None;
+        let mut graph = None;
+        let mut parser = ParseFrontEnd::Cst;
+        let mut parse_limits = ParseLimits::default();
         let mut target_list = vec![];
 
-        if args.first().is_none() {
+        if args.is_empty() {
             return Ok(Self {
                 is_debug,
                 is_compiler_debug,
                 is_output_ast,
-                target_list: get_file(&PathBuf::from("./"), true).unwrap(),
+                tree_format,
+                parse_trace,
+                graph,
+                parser,
+                parse_limits,
+                target_list: current_dir_files()?,
             });
         };
 
         let mut iter = args.iter();
         while let Some(option) = iter.next() {
             match option.as_str() {
-                "--path" => {
-                    let path_list = extract_path(&iter.as_slice().to_vec());
-                    for _ in 0..path_list.len() {
-                        iter.next(); // advance_byみたいなことやりたい
-                    }
-
-                    for path in path_list {
-                        target_list.append(&mut get_file(&path, false)?);
-                    }
-                }
-                "--path-recursive" => {
-                    let path_list = extract_path(&iter.as_slice().to_vec());
-                    for _ in 0..path_list.len() {
-                        iter.next();
-                    }
-                    for path in path_list {
-                        target_list.append(&mut get_file(&path, true)?);
-                    }
+                "--path" | "--path-recursive" => {
+                    target_list.append(&mut path_option(option, &mut iter)?);
                 }
                 "--debug" => {
                     is_debug = true;
@@ -58,6 +123,59 @@ impl CompileCommandOption {
                 "--ast" => {
                     is_output_ast = true;
                 }
+                "--tree-format" => {
+                    tree_format = match iter.next().map(String::as_str) {
+                        Some("json") => TreeFormat::Json,
+                        Some("binary") => TreeFormat::Binary,
+                        _ => {
+                            let text = "'--tree-format' expects 'json' or 'binary'".to_string();
+                            println!("{}", text);
+                            return Err(text);
+                        }
+                    };
+                }
+                "--parse-trace" => {
+                    parse_trace = match iter.next().map(String::as_str) {
+                        Some("json") => Some(ParseTraceFormat::Json),
+                        Some("html") => Some(ParseTraceFormat::Html),
+                        _ => {
+                            let text = "'--parse-trace' expects 'json' or 'html'".to_string();
+                            println!("{}", text);
+                            return Err(text);
+                        }
+                    };
+                }
+                "--graph" => {
+                    graph = match iter.next().map(String::as_str) {
+                        Some("dot") => Some(GraphFormat::Dot),
+                        Some("mermaid") => Some(GraphFormat::Mermaid),
+                        _ => {
+                            let text = "'--graph' expects 'dot' or 'mermaid'".to_string();
+                            println!("{}", text);
+                            return Err(text);
+                        }
+                    };
+                }
+                "--parser" => {
+                    parser = match iter.next().map(String::as_str) {
+                        Some("cst") => ParseFrontEnd::Cst,
+                        Some("ast") => ParseFrontEnd::Ast,
+                        _ => {
+                            let text = "'--parser' expects 'cst' or 'ast'".to_string();
+                            println!("{}", text);
+                            return Err(text);
+                        }
+                    };
+                }
+                "--max-parse-depth" => {
+                    parse_limits.max_depth = parse_limit(option, iter.next())?;
+                }
+                "--max-tokens" => {
+                    parse_limits.max_tokens = parse_limit(option, iter.next())?;
+                }
+                "--max-memo-entries" => {
+                    parse_limits.max_memo_entries = parse_limit(option, iter.next())?;
+                }
 
                 _ => {
                     let text = format!("unknown option '{}'", option);
@@ -67,16 +185,345 @@ impl CompileCommandOption {
             };
         }
 
+        // --debug-compiler だけならJSONのトレースを出力する
+        if is_compiler_debug && parse_trace.is_none() {
+            parse_trace = Some(ParseTraceFormat::Json);
+        }
+
         Ok(Self {
             is_debug,
             is_compiler_debug,
             is_output_ast,
+            tree_format,
+            parse_trace,
+            graph,
+            parser,
+            parse_limits,
+            target_list,
+        })
+    }
+}
+
+// nagi.tomlのうち整形で読む部分
+// [fmt]以外の表は読み飛ばす
+#[derive(Deserialize)]
+struct NagiConfig {
+    #[serde(default)]
+    fmt: FormatStyle,
+}
+
+// nagi.tomlの中身から整形の設定を読む
+pub fn read_format_style(text: &str) -> Result<FormatStyle, String> {
+    toml::from_str::<NagiConfig>(text)
+        .map(|config| config.fmt)
+        .map_err(|error| format!("{}: {}", NAGI_CONFIG_FILE, error))
+}
+
+#[derive(Debug)]
+pub struct FormatCommandOption {
+    pub is_check: bool, // 書き換えずに、整形済みでないファイルがあれば失敗する
+    pub is_diff: bool,  // 書き換えずに差分を表示する
+    pub style: FormatStyle,
+    pub target_list: Vec<String>,
+}
+
+impl FormatCommandOption {
+    pub fn new(args: &[String]) -> Result<Self, String> {
+        let mut is_check = false;
+        let mut is_diff = false;
+        let mut config = None;
+        let mut target_list = vec![];
+        let mut has_path = false;
+
+        let mut iter = args.iter();
+        while let Some(option) = iter.next() {
+            match option.as_str() {
+                "--path" | "--path-recursive" => {
+                    target_list.append(&mut path_option(option, &mut iter)?);
+                    has_path = true;
+                }
+                "--check" => {
+                    is_check = true;
+                }
+                "--diff" => {
+                    is_diff = true;
+                }
+                "--config" => {
+                    let Some(path) = iter.next() else {
+                        let text = "'--config' expects a path".to_string();
+                        println!("{}", text);
+                        return Err(text);
+                    };
+                    config = Some(PathBuf::from(path));
+                }
+
+                _ => {
+                    let text = format!("unknown option '{}'", option);
+                    println!("{}", text);
+                    return Err(text);
+                }
+            };
+        }
+
+        if !has_path {
+            target_list = current_dir_files()?;
+        }
+
+        // 指定がなければ作業ディレクトリのnagi.tomlを使い、それもなければ既定の設定にする
+        let config = config.or_else(|| {
+            let path = PathBuf::from(NAGI_CONFIG_FILE);
+            path.exists().then_some(path)
+        });
+        let style = match config {
+            Some(path) => {
+                let style = std::fs::read_to_string(&path)
+                    .map_err(|_| format!("cannot read '{}'", path.display()))
+                    .and_then(|text| read_format_style(&text));
+                if let Err(text) = &style {
+                    println!("{}", text);
+                }
+                style?
+            }
+            None => FormatStyle::default(),
+        };
+
+        Ok(Self {
+            is_check,
+            is_diff,
+            style,
+            target_list,
+        })
+    }
+}
+
+// nagi diff <old> <new>
+// どちらも.nagならソースコードを解析し、.astか.ast.jsonなら--astで書き出した木を読む
+#[derive(Debug)]
+pub struct DiffCommandOption {
+    pub is_cst: bool,       // チェック後のASTではなく、マクロ展開後のCSTを比べる
+    pub ignore_spans: bool, // ノードの範囲とトークンの位置を比べない
+    pub parser: ParseFrontEnd,
+    pub old: String,
+    pub new: String,
+}
+
+impl DiffCommandOption {
+    pub fn new(args: &[String]) -> Result<Self, String> {
+        let mut is_cst = false;
+        let mut ignore_spans = false;
+        let mut parser = ParseFrontEnd::Cst;
+        let mut files = vec![];
+
+        let mut iter = args.iter();
+        while let Some(option) = iter.next() {
+            match option.as_str() {
+                "--cst" => {
+                    is_cst = true;
+                }
+                "--ignore-spans" => {
+                    ignore_spans = true;
+                }
+                "--parser" => {
+                    parser = match iter.next().map(String::as_str) {
+                        Some("cst") => ParseFrontEnd::Cst,
+                        Some("ast") => ParseFrontEnd::Ast,
+                        _ => {
+                            let text = "'--parser' expects 'cst' or 'ast'".to_string();
+                            println!("{}", text);
+                            return Err(text);
+                        }
+                    };
+                }
+                file if !file.starts_with("--") => files.push(file.to_string()),
+
+                _ => {
+                    let text = format!("unknown option '{}'", option);
+                    println!("{}", text);
+                    return Err(text);
+                }
+            };
+        }
+
+        let [old, new] = <[String; 2]>::try_from(files).map_err(|_| {
+            let text = "'diff' expects two files".to_string();
+            println!("{}", text);
+            text
+        })?;
+        if is_cst
+            && (TreeFormat::from_file_name(&old).is_some()
+                || TreeFormat::from_file_name(&new).is_some())
+        {
+            let text = "'--cst' compares only source files".to_string();
+            println!("{}", text);
+            return Err(text);
+        }
+
+        Ok(Self {
+            is_cst,
+            ignore_spans,
+            parser: if is_cst { ParseFrontEnd::Cst } else { parser },
+            old,
+            new,
+        })
+    }
+
+    // ソースコードの方を解析するときの設定
+    pub fn compile_option(&self) -> CompileCommandOption {
+        CompileCommandOption {
+            is_debug: false,
+            is_compiler_debug: false,
+            is_output_ast: false,
+            tree_format: TreeFormat::Json,
+            parse_trace: None,
+            graph: None,
+            parser: self.parser,
+            parse_limits: ParseLimits::default(),
+            target_list: vec![self.old.clone(), self.new.clone()],
+        }
+    }
+}
+
+// nagi query <selector> [files]
+// ファイルを指定しなければ作業ディレクトリ以下の.nagを探す
+#[derive(Debug)]
+pub struct QueryCommandOption {
+    pub is_cst: bool, // チェック後のASTではなく、マクロ展開後のCSTを探す
+    pub parser: ParseFrontEnd,
+    pub query: String,
+    pub target_list: Vec<String>,
+}
+
+impl QueryCommandOption {
+    pub fn new(args: &[String]) -> Result<Self, String> {
+        let mut is_cst = false;
+        let mut parser = ParseFrontEnd::Cst;
+        let mut query = None;
+        let mut target_list = vec![];
+        let mut has_path = false;
+
+        let mut iter = args.iter();
+        while let Some(option) = iter.next() {
+            match option.as_str() {
+                "--path" | "--path-recursive" => {
+                    target_list.append(&mut path_option(option, &mut iter)?);
+                    has_path = true;
+                }
+                "--cst" => {
+                    is_cst = true;
+                }
+                "--parser" => {
+                    parser = match iter.next().map(String::as_str) {
+                        Some("cst") => ParseFrontEnd::Cst,
+                        Some("ast") => ParseFrontEnd::Ast,
+                        _ => {
+                            let text = "'--parser' expects 'cst' or 'ast'".to_string();
+                            println!("{}", text);
+                            return Err(text);
+                        }
+                    };
+                }
+                // 最初の引数がセレクタで、残りは探すファイル
+                text if !text.starts_with("--") => {
+                    if query.is_none() {
+                        query = Some(text.to_string());
+                    } else {
+                        target_list.push(text.to_string());
+                        has_path = true;
+                    }
+                }
+
+                _ => {
+                    let text = format!("unknown option '{}'", option);
+                    println!("{}", text);
+                    return Err(text);
+                }
+            };
+        }
+
+        let Some(query) = query else {
+            let text = "'query' expects a selector".to_string();
+            println!("{}", text);
+            return Err(text);
+        };
+        if !has_path {
+            target_list = current_dir_files()?;
+        }
+        if is_cst
+            && target_list
+                .iter()
+                .any(|target| TreeFormat::from_file_name(target).is_some())
+        {
+            let text = "'--cst' searches only source files".to_string();
+            println!("{}", text);
+            return Err(text);
+        }
+
+        Ok(Self {
+            is_cst,
+            parser: if is_cst { ParseFrontEnd::Cst } else { parser },
+            query,
             target_list,
         })
     }
+
+    pub fn compile_option(&self) -> CompileCommandOption {
+        CompileCommandOption {
+            is_debug: false,
+            is_compiler_debug: false,
+            is_output_ast: false,
+            tree_format: TreeFormat::Json,
+            parse_trace: None,
+            graph: None,
+            parser: self.parser,
+            parse_limits: ParseLimits::default(),
+            target_list: self.target_list.clone(),
+        }
+    }
+}
+
+fn parse_limit(option: &str, value: Option<&String>) -> Result<usize, String> {
+    match value.map(|value| value.parse::<usize>()) {
+        Some(Ok(limit)) => Ok(limit),
+        _ => {
+            let text = format!("'{}' expects a number", option);
+            println!("{}", text);
+            Err(text)
+        }
+    }
 }
 
-fn extract_path(args: &Vec<String>) -> Vec<PathBuf> {
+// --pathと--path-recursiveの後ろに続くパスから.nagのファイルを集める
+// 存在するパスが続く間だけ読み進め、1つも無ければ失敗する
+fn path_option(option: &str, iter: &mut std::slice::Iter<String>) -> Result<Vec<String>, String> {
+    let path_list = extract_path(iter.as_slice());
+    if path_list.is_empty() {
+        let text = match iter.as_slice().first() {
+            Some(path) => format!("'{}' cannot find '{}'", option, path),
+            None => format!("'{}' expects a path", option),
+        };
+        println!("{}", text);
+        return Err(text);
+    }
+    iter.nth(path_list.len() - 1);
+
+    let mut target_list = vec![];
+    for path in path_list {
+        let mut files = get_file(&path, option == "--path-recursive").inspect_err(|text| {
+            println!("{}", text);
+        })?;
+        target_list.append(&mut files);
+    }
+    Ok(target_list)
+}
+
+// パスの指定がないときは作業ディレクトリ以下の.nagを探す
+fn current_dir_files() -> Result<Vec<String>, String> {
+    get_file(Path::new("./"), true).inspect_err(|text| {
+        println!("{}", text);
+    })
+}
+
+fn extract_path(args: &[String]) -> Vec<PathBuf> {
     let mut paths = vec![];
     for arg in args.iter() {
         let Ok(path) = Path::new(arg).canonicalize() else {
@@ -91,28 +538,27 @@ fn extract_path(args: &Vec<String>) -> Vec<PathBuf> {
     paths
 }
 
-fn get_file(target: &PathBuf, recursive: bool) -> Result<Vec<String>, String> {
+fn get_file(target: &Path, recursive: bool) -> Result<Vec<String>, String> {
     println!("{:?}", target.as_os_str());
     let mut target_list = Vec::<String>::new();
     if let Some(extension) = target.extension() {
-        let Ok(target_str) = target.clone().into_os_string().into_string() else {
-            return Err(format!(""));
+        let Some(target_str) = target.to_str() else {
+            return Err(format!("'{}' is not a UTF-8 path", target.display()));
         };
 
         if let Some(ext) = extension.to_str() {
             if ext == NAGI_EXTENSION {
-                target_list.push(target_str);
+                target_list.push(target_str.to_string());
             }
         }
     } else {
-        let Ok(files) = target.read_dir() else {
-            return Err(format!(""));
-        };
+        let files = target
+            .read_dir()
+            .map_err(|error| format!("cannot read '{}': {}", target.display(), error))?;
 
         for file in files {
-            let Ok(dir_entry) = file else {
-                return Err(format!(""));
-            };
+            let dir_entry =
+                file.map_err(|error| format!("cannot read '{}': {}", target.display(), error))?;
             let path = dir_entry.path();
 
             if recursive {
@@ -1,6 +1,18 @@
-use std::{env, fs, process, time::Instant};
+use std::{env, fs, path::Path, process, time::Instant};
 
-use nagi_command_option::CompileCommandOption;
+use nagi_command_option::{
+    CompileCommandOption, DiffCommandOption, FormatCommandOption, GraphFormat, ParseTraceFormat,
+    QueryCommandOption, TreeFormat,
+};
+use nagi_extender::{export_ast, import_ast};
+use nagi_parse::{ParseTrace, SyntaxTree};
+use nagi_syntax_tree::ast::ASTNode;
+use nagi_syntax_tree::cst::CSTNode;
+use nagi_syntax_tree::diff::{diff_trees, DiffOptions};
+use nagi_syntax_tree::graph::{tree_graph, Graph};
+use nagi_syntax_tree::query::{Query, QueryMatch};
+use nagi_syntax_tree::serialize::source_hash;
+use nagi_syntax_tree::walk::SyntaxNode;
 
 #[derive(Debug)]
 pub enum ExitStatus {
@@ -8,6 +20,9 @@ pub enum ExitStatus {
     CompileFailure = -1,
     UnknownCommand = -2,
     InvalidArgs = -3,
+    NotFormatted = -4,
+    TreesDiffer = -5,
+    NoMatches = -6,
 }
 
 pub fn driver() {
@@ -20,6 +35,9 @@ pub fn driver() {
 
     let result = match command.as_str() {
         "compile" => run_compiler(&args),
+        "fmt" => run_formatter(&args),
+        "diff" => run_tree_diff(&args),
+        "query" => run_query(&args),
         _ => ExitStatus::UnknownCommand,
     };
 
@@ -32,38 +50,342 @@ pub fn driver() {
     process::exit(result as i32);
 }
 
-fn run_compiler(args: &Vec<String>) -> ExitStatus {
+fn run_compiler(args: &[String]) -> ExitStatus {
     println!("workdir : {}", env::current_dir().unwrap().display());
     let Ok(compile_option) = CompileCommandOption::new(args) else {
         return ExitStatus::InvalidArgs;
     };
 
-    let mut cst_list = vec![];
+    let mut has_syntax_error = false;
+    let mut tree_list = vec![];
     for target in compile_option.target_list.iter() {
         let Ok(code) = open_file(target) else {
             return ExitStatus::CompileFailure;
         };
 
-        let Ok(cst) = nagi_parse::parse(&code, &compile_option) else {
-            return ExitStatus::CompileFailure;
-        };
+        let parse_result = nagi_parse::parse_file(&code, Path::new(target), &compile_option);
+        for error in parse_result.errors.iter() {
+            println!("{}:{}", target, error);
+        }
+        if !parse_result.is_ok() {
+            has_syntax_error = true;
+        }
+        if let (Some(trace), Some(format)) = (&parse_result.trace, compile_option.parse_trace) {
+            write_parse_trace(target, trace, format);
+        }
 
-        cst_list.push(cst);
+        tree_list.push((target, code, parse_result.tree));
     }
 
     let mut ast_list = vec![];
-    for cst in cst_list.iter() {
-        let Ok(ast) = nagi_checker::check(cst) else {
-            return ExitStatus::CompileFailure;
+    for (target, code, tree) in tree_list {
+        let checked = match tree {
+            SyntaxTree::Cst(cst) => {
+                if let Some(format) = compile_option.graph {
+                    write_graph(target, "cst", &tree_graph("cst", &cst), format);
+                }
+                nagi_checker::check_with_symbol_tree(&cst)
+            }
+            SyntaxTree::Ast(ast) => {
+                nagi_checker::check_ast_with_symbol_tree(&ast).map(|symbol_tree| (ast, symbol_tree))
+            }
+        };
+        let (ast, symbol_tree) = match checked {
+            Ok(checked) => checked,
+            Err(error) => {
+                println!("{}:{}", target, error);
+                return ExitStatus::CompileFailure;
+            }
         };
 
         ast.write_ast("a.json"); // test
+        if let Some(format) = compile_option.graph {
+            write_graph(target, "ast", &tree_graph("ast", &ast), format);
+            write_graph(target, "scopes", &symbol_tree.graph(), format);
+        }
+        if compile_option.is_output_ast {
+            let file_name = format!("{}.{}", target, compile_option.tree_format.ast_extension());
+            let source_hash = source_hash(&code);
+            if let Err(error) =
+                export_ast(&file_name, &ast, compile_option.tree_format, source_hash)
+            {
+                println!("{}", error);
+                return ExitStatus::CompileFailure;
+            }
+        }
         ast_list.push(ast);
     }
 
+    // 構文エラーがあっても残りの部分はチェックしてから失敗とする
+    if has_syntax_error {
+        return ExitStatus::CompileFailure;
+    }
+
     ExitStatus::Success
 }
 
+// --checkも--diffも無ければファイルを書き換える
+fn run_formatter(args: &[String]) -> ExitStatus {
+    println!("workdir : {}", env::current_dir().unwrap().display());
+    let Ok(format_option) = FormatCommandOption::new(args) else {
+        return ExitStatus::InvalidArgs;
+    };
+
+    let mut has_syntax_error = false;
+    let mut is_not_formatted = false;
+    for target in format_option.target_list.iter() {
+        let Ok(code) = open_file(target) else {
+            return ExitStatus::CompileFailure;
+        };
+
+        let formatted = match nagi_parse::format_source(&code, &format_option.style) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                for error in errors.iter() {
+                    println!("{}:{}", target, error);
+                }
+                has_syntax_error = true;
+                continue;
+            }
+        };
+        if formatted == code {
+            continue;
+        }
+
+        is_not_formatted = true;
+        if format_option.is_diff {
+            print_diff(target, &code, &formatted);
+        } else if format_option.is_check {
+            println!("{}: not formatted", target);
+        }
+        if !format_option.is_check
+            && !format_option.is_diff
+            && fs::write(target, formatted).is_err()
+        {
+            println!("write error! {}", target);
+            return ExitStatus::CompileFailure;
+        }
+    }
+
+    if has_syntax_error {
+        ExitStatus::CompileFailure
+    } else if format_option.is_check && is_not_formatted {
+        ExitStatus::NotFormatted
+    } else {
+        ExitStatus::Success
+    }
+}
+
+// 2つのファイルの構文木の差分を表示し、差分があれば失敗とする
+fn run_tree_diff(args: &[String]) -> ExitStatus {
+    let Ok(diff_option) = DiffCommandOption::new(args) else {
+        return ExitStatus::InvalidArgs;
+    };
+    let options = DiffOptions {
+        ignore_spans: diff_option.ignore_spans,
+    };
+    let compile_option = diff_option.compile_option();
+
+    let diff = if diff_option.is_cst {
+        let (Ok(old), Ok(new)) = (
+            read_cst(&diff_option.old, &compile_option),
+            read_cst(&diff_option.new, &compile_option),
+        ) else {
+            return ExitStatus::CompileFailure;
+        };
+        diff_trees(&old, &new, &options)
+    } else {
+        let (Ok(old), Ok(new)) = (
+            read_ast(&diff_option.old, &compile_option),
+            read_ast(&diff_option.new, &compile_option),
+        ) else {
+            return ExitStatus::CompileFailure;
+        };
+        diff_trees(&old, &new, &options)
+    };
+
+    println!("--- {}", diff_option.old);
+    println!("+++ {}", diff_option.new);
+    print!("{}", diff.report());
+    if diff.is_empty() {
+        ExitStatus::Success
+    } else {
+        ExitStatus::TreesDiffer
+    }
+}
+
+// 構文木の条件に当てはまるノードを、grepのように1行ずつ表示する
+fn run_query(args: &[String]) -> ExitStatus {
+    let Ok(query_option) = QueryCommandOption::new(args) else {
+        return ExitStatus::InvalidArgs;
+    };
+    let query = match Query::parse(&query_option.query) {
+        Ok(query) => query,
+        Err(error) => {
+            println!("invalid selector: {}", error);
+            return ExitStatus::InvalidArgs;
+        }
+    };
+    let compile_option = query_option.compile_option();
+
+    // 読めないファイルがあっても残りのファイルは探し、最後に失敗を返す
+    let mut has_error = false;
+    let mut match_count = 0;
+    for target in query_option.target_list.iter() {
+        if query_option.is_cst {
+            let Ok(cst) = read_cst(target, &compile_option) else {
+                has_error = true;
+                continue;
+            };
+            match_count += print_matches(target, &query.find(&cst));
+        } else {
+            let Ok(ast) = read_ast(target, &compile_option) else {
+                has_error = true;
+                continue;
+            };
+            match_count += print_matches(target, &query.find(&ast));
+        }
+    }
+
+    if has_error {
+        ExitStatus::CompileFailure
+    } else if match_count == 0 {
+        ExitStatus::NoMatches
+    } else {
+        ExitStatus::Success
+    }
+}
+
+fn print_matches<T: SyntaxNode>(target: &str, matches: &[QueryMatch<'_, T>]) -> usize {
+    for found in matches.iter() {
+        let location = match (&found.span, found.position) {
+            (Some(span), _) => span.clone(),
+            (None, Some((row, column))) => format!("{}:{}", row, column),
+            (None, None) => "-".to_string(),
+        };
+        println!("{}:{}: {}", target, location, found.describe());
+    }
+    matches.len()
+}
+
+fn parse_target(target: &str, compile_option: &CompileCommandOption) -> Result<SyntaxTree, ()> {
+    let code = open_file(target)?;
+    let parse_result = nagi_parse::parse_file(&code, Path::new(target), compile_option);
+    for error in parse_result.errors.iter() {
+        println!("{}:{}", target, error);
+    }
+    if !parse_result.is_ok() {
+        return Err(());
+    }
+
+    Ok(parse_result.tree)
+}
+
+fn read_cst(target: &str, compile_option: &CompileCommandOption) -> Result<CSTNode, ()> {
+    match parse_target(target, compile_option)? {
+        SyntaxTree::Cst(cst) => Ok(cst),
+        SyntaxTree::Ast(_) => Err(()),
+    }
+}
+
+// 書き出したASTはそのまま読み、ソースコードはコンパイルするときと同じようにチェックしたASTにする
+fn read_ast(target: &str, compile_option: &CompileCommandOption) -> Result<ASTNode, ()> {
+    if let Some(format) = TreeFormat::from_file_name(target) {
+        return import_ast(target, format).map_err(|error| println!("{}: {}", target, error));
+    }
+
+    let ast = match parse_target(target, compile_option)? {
+        SyntaxTree::Cst(cst) => nagi_checker::check(&cst),
+        SyntaxTree::Ast(ast) => nagi_checker::check_ast(&ast).map(|_| ast),
+    };
+    ast.map_err(|error| println!("{}: {}", target, error))
+}
+
+// 行ごとの差分をunified形式で表示する
+fn print_diff(target: &str, old: &str, new: &str) {
+    const CONTEXT: usize = 3;
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j]はold[i..]とnew[j..]の最長共通部分列の長さ
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // (記号, oldの行番号, newの行番号, 行)
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', i, j, old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', i, j, old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', i, j, new[j]));
+            j += 1;
+        }
+    }
+
+    // 変更のある行の前後CONTEXT行をまとめて1つの塊にする
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for (index, _) in lines.iter().enumerate().filter(|(_, line)| line.0 != ' ') {
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    println!("--- {}", target);
+    println!("+++ {}", target);
+    for (start, end) in hunks {
+        let hunk = &lines[start..end];
+        let old_count = hunk.iter().filter(|line| line.0 != '+').count();
+        let new_count = hunk.iter().filter(|line| line.0 != '-').count();
+        println!(
+            "@@ -{},{} +{},{} @@",
+            hunk[0].1 + 1,
+            old_count,
+            hunk[0].2 + 1,
+            new_count
+        );
+        for (sign, _, _, line) in hunk {
+            println!("{}{}", sign, line);
+        }
+    }
+}
+
+// 対象ファイルの隣に<target>.trace.jsonか<target>.trace.htmlを出力する
+fn write_parse_trace(target: &str, trace: &ParseTrace, format: ParseTraceFormat) {
+    let (file_name, text) = match format {
+        ParseTraceFormat::Json => (format!("{}.trace.json", target), trace.to_json()),
+        ParseTraceFormat::Html => (format!("{}.trace.html", target), trace.to_html()),
+    };
+
+    if fs::write(&file_name, text).is_err() {
+        println!("trace output error! {}", file_name);
+    }
+}
+
+// 対象ファイルの隣に<target>.<name>.dotか<target>.<name>.mmdを出力する
+fn write_graph(target: &str, name: &str, graph: &Graph, format: GraphFormat) {
+    let file_name = format!("{}.{}.{}", target, name, format.extension());
+    if fs::write(&file_name, graph.render(format)).is_err() {
+        println!("graph output error! {}", file_name);
+    }
+}
+
 fn open_file(file_path: &str) -> Result<String, ()> {
     let Ok(sorce_code) = fs::read_to_string(file_path) else {
         return Err(()); //TODO
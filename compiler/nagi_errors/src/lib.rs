@@ -1,7 +1,44 @@
+use std::fmt;
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct Error {
     pub error_kind: ErrorKind,
     pub error_text: String,
+    pub position: Option<SourcePosition>,
+    pub notes: Vec<ErrorNote>, // 開き括弧の位置など関連する箇所
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(position) = &self.position {
+            write!(f, "{}: ", position)?;
+        }
+        write!(f, "{}", self.error_text)?;
+
+        for note in self.notes.iter() {
+            write!(f, "\n    {}: note: {}", note.position, note.note_text)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SourcePosition {
+    pub row: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for SourcePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.row, self.column)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ErrorNote {
+    pub position: SourcePosition,
+    pub note_text: String,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -9,6 +46,8 @@ pub enum ErrorKind {
     Lexcal(LexicalError),
     Syntax(SyntaxError),
     Semantic(SemanticError),
+    Macro(MacroError),
+    TreeFile(TreeFileError),
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -18,6 +57,7 @@ pub enum LexicalError {
     InvalidEscapeSequence, // 無効なエスケープシーケンス
     InvalidNumberFormat,   // 無効な数値フォーマット
     InvalidCommentFormat,  // 無効なコメントフォーマット
+    UnterminatedLiteral,   // 閉じられていないリテラル
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -27,6 +67,8 @@ pub enum SyntaxError {
     ExpectedToken,        // 期待したトークンがない
     MissingSemicolon,     // セミコロン忘れ
     ParenthesesNotClosed, // 括弧が閉じられていない
+    LimitExceeded,        // 入れ子の深さなどの制限を超えた
+    Cancelled,            // 呼び出し側が解析を中断した
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -40,4 +82,35 @@ pub enum SemanticError {
     DivisionByZero,        // 0除算をしようとした
     TooFewArguments,       // 引数が少ない
     TooManyArguments,      // 引数が多い
+    UnsafeOperation,       // unsafeな文脈の外でのunsafeな操作
+    LetElseNotDiverging,   // let-elseのelseブロックが発散しない
+
+    NoOperatorImplementation, // 演算子の実装が無い
+    MissingTraitItem,         // トレイトの実装に必要な関数が無い
+    NestingTooDeep,           // 木の入れ子が検査できないほど深い
+    UnknownOperator,          // 演算子として扱えないトークン
+    NotCrate,                 // クレートでない木を検査しようとした
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MacroError {
+    UndefinedMacro,     // 未定義のマクロ
+    InvalidDefinition,  // macro_rules!の書き方が正しくない
+    NoRuleMatched,      // どの規則にもマッチしなかった
+    InvalidTranscriber, // 展開先でメタ変数の使い方が正しくない
+    RecursionLimit,     // 展開の再帰が深すぎる
+
+    InvalidBuiltinArguments, // 組み込みマクロの引数が正しくない
+    InvalidFormatString,     // 書式文字列が正しくない
+    FormatArgumentMismatch,  // 書式文字列と引数が合わない
+    IncludeFailed,           // include_str!などでファイルを読めない
+}
+
+// 構文木のファイルの読み書き
+#[derive(Debug, PartialEq, Eq)]
+pub enum TreeFileError {
+    Io,              // ファイルを読み書きできない
+    InvalidFormat,   // 構文木のファイルではないか、種類の違う木
+    VersionMismatch, // 形式のバージョンかコンパイラのバージョンが違う
+    Corrupted,       // 中身が壊れている
 }
@@ -1,9 +1,10 @@
+use nagi_command_option::TreeFormat;
+use nagi_errors::Error;
 use nagi_syntax_tree::ast::ASTNode;
 use nagi_syntax_tree::cst::CSTNode;
 use nagi_syntax_tree::hst::HSTNode;
+use nagi_syntax_tree::serialize::{read_tree, write_tree};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::{BufReader, Write};
 
 // CSTとASTをまぜまぜする
 
@@ -13,25 +14,18 @@ pub struct Extender {
 
 impl Extender {}
 
-pub fn import_ast(file_path: &str) -> Result<ASTNode, ()> {
-    let file = File::open(file_path).unwrap();
-    let reader = BufReader::new(file);
-    let ast: ASTNode = serde_json::from_reader(reader).unwrap();
-
-    Ok(ast)
+pub fn import_ast(file_path: &str, format: TreeFormat) -> Result<ASTNode, Error> {
+    read_tree(file_path, format)
 }
 
-pub fn export_ast(file_name: &str, ast: &ASTNode) -> Result<(), ()> {
-    let Ok(mut file) = File::create(file_name) else {
-        return Err(());
-    };
-    let Ok(data) = serde_json::to_string(ast) else {
-        return Err(());
-    };
-
-    file.write_all(data.as_bytes()).unwrap();
-
-    Ok(())
+// source_hashはバイナリ形式のヘッダーに書く
+pub fn export_ast(
+    file_name: &str,
+    ast: &ASTNode,
+    format: TreeFormat,
+    source_hash: u64,
+) -> Result<(), Error> {
+    write_tree(file_name, ast, format, source_hash)
 }
 
 //pub fn add_syntax_tree(cst: ASTNode, ast: ASTNode) -> HSTNode {}
@@ -50,31 +50,12 @@ impl Lexer {
                     _ => TokenKind::Unkown,
                 }
             }
-            '/' => match self.get_next()? {
-                '/' => self.line_comment(),
-                '*' => self.block_comment(),
+            '"' => self.string_literal(),
+            '/' => match self.get_next() {
+                Some('/') => self.line_comment(),
+                Some('*') => self.block_comment(),
                 _ => TokenKind::Slash,
             },
-            '+' => TokenKind::Plus,
-            '-' => TokenKind::Minus,
-            '*' => TokenKind::Star,
-            '%' => TokenKind::Percent,
-            '=' => TokenKind::Equal,
-            '^' => TokenKind::Caret,
-            '!' => TokenKind::Not,
-            '&' => TokenKind::And,
-            '|' => TokenKind::Or,
-            '>' => TokenKind::GreaterThan,
-            '<' => TokenKind::LessThan,
-            '@' => TokenKind::At,
-            '.' => TokenKind::Dot,
-            ',' => TokenKind::Comma,
-            ':' => TokenKind::Colon,
-            ';' => TokenKind::Semicolon,
-            '#' => TokenKind::Pound,
-            '$' => TokenKind::Dollar,
-            '?' => TokenKind::Question,
-            '~' => TokenKind::Tilde,
             '(' => TokenKind::LeftParenthesis,
             ')' => TokenKind::RightParenthesis,
             '[' => TokenKind::LeftBrackets,
@@ -85,7 +66,9 @@ impl Lexer {
             _ if self.is_identifier_start() => self.identifier_or_unknown(),
             _ if self.is_white_space() => self.white_space(),
 
-            _ => TokenKind::Unkown,
+            // `_`は識別子の始まりとして上で切り出す
+            c => TokenKind::from_punctuation_str(c.encode_utf8(&mut [0; 4]))
+                .unwrap_or(TokenKind::Unkown),
         };
         if !matches!(
             token_kind,
@@ -229,7 +212,10 @@ impl Lexer {
                     '0'..='9' | '_' => LiteralKind::DecLiteral,
                     '.' => LiteralKind::DecLiteral, // 後続で処理するためにこの時点では10進数として扱う
                     _ if is_identifier_start(second) => LiteralKind::DecLiteral,
-                    _ => return LiteralKind::Unknown,
+                    _ => {
+                        self.push_char(); // push 0
+                        return LiteralKind::DecLiteral; // 0の後に区切り文字
+                    }
                 }
             }
             _ => LiteralKind::DecLiteral,
@@ -503,6 +489,68 @@ impl Lexer {
 
     // StringLiteral  ::= " (~[" \ IsolatedCR] | QuoteEscape | AsciiEscape | UnicodeEscape | StringContinue)* " Suffix?
     // StringContinue ::= \ followed by \n
+    fn string_literal(&mut self) -> TokenKind {
+        if !self.eat_string_literal() {
+            return TokenKind::Unkown;
+        }
+
+        TokenKind::Literal(LiteralKind::StringLiteral)
+    }
+
+    fn eat_string_literal(&mut self) -> bool {
+        // "
+        if !self.is_same('"') {
+            return false;
+        }
+        self.push_char(); // push "
+
+        loop {
+            let Some(c) = self.get() else {
+                return false;
+            };
+
+            match c {
+                '"' => break,
+                '\\' => {
+                    let Some(escape_prefix) = self.get_next() else {
+                        return false;
+                    };
+
+                    let result = match escape_prefix {
+                        '\'' | '\"' => self.eat_quote_escape(), // QuoteEscape ( \' | \" )
+                        'n' | 'r' | 't' | '\\' | '0' | 'x' => self.eat_ascii_escape(), // ( \x OctDigit HexDigit | \n | \r | \t | \\ | \0 )
+                        'u' => self.eat_unicode_escape(), // UnicodeEscape ::= \u{ (HexDigit _*)1..6 }
+                        '\n' => self.eat_string_continue(), // StringContinue ::= \ followed by \n
+                        _ => false,
+                    };
+                    if !result {
+                        return false;
+                    }
+                }
+                // IsolatedCR
+                '\r' if self.get_next() != Some('\n') => return false,
+                _ => self.push_char(), // push any char
+            }
+        }
+        self.push_char(); // push "
+
+        self.eat_literal_suffix();
+        true
+    }
+
+    // StringContinue ::= \ followed by \n
+    fn eat_string_continue(&mut self) -> bool {
+        if !self.eat_escape() {
+            return false;
+        }
+
+        if !self.is_same('\n') {
+            return false;
+        }
+        self.push_char(); // push \n
+
+        true
+    }
 
     // RawStringLiteral ::= r RawStringContent Suffix?
     // RawStringContent ::= " (~ IsolatedCR)* (non-greedy) " | # RawStringContent #
@@ -599,7 +647,7 @@ impl Lexer {
         let Some(expect_u) = self.get() else {
             return false;
         };
-        if expect_u == 'u' {
+        if expect_u != 'u' {
             return false;
         }
         self.push_char(); // push u
@@ -637,7 +685,7 @@ impl Lexer {
         let Some(expect_right_parentheses) = self.get() else {
             return false;
         };
-        if expect_right_parentheses != '{' {
+        if expect_right_parentheses != '}' {
             return false;
         }
         self.push_char(); // push }
@@ -678,11 +726,38 @@ impl Lexer {
 
     // Comment
 
+    // `//`から行末まで 改行は含めない
     fn line_comment(&mut self) -> TokenKind {
+        while !self.is_same('\n') && self.get().is_some() {
+            self.push_char();
+        }
+
         TokenKind::Comment
     }
 
+    // `/*`から対応する`*/`まで 入れ子にできる
+    // 閉じていなければファイルの終わりまで
     fn block_comment(&mut self) -> TokenKind {
+        self.push_char();
+        self.push_char();
+
+        let mut depth = 1;
+        while depth > 0 {
+            match (self.get(), self.get_next()) {
+                (None, _) => break,
+                (Some('/'), Some('*')) => {
+                    self.push_char();
+                    depth += 1;
+                }
+                (Some('*'), Some('/')) => {
+                    self.push_char();
+                    depth -= 1;
+                }
+                _ => {}
+            }
+            self.push_char();
+        }
+
         TokenKind::Comment
     }
 
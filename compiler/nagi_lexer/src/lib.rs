@@ -22,46 +22,26 @@ pub enum LiteralKind {
     RawCStringLiteral,
 }
 
-#[derive(Debug, Eq, PartialEq)]
-pub enum TokenKind {
-    Unkown,
-    Identifier(String),
-    Prefix,
-    Literal(LiteralKind),
-    Comment,
-    WhiteSpace,
-
-    LeftParenthesis,  // (
-    RightParenthesis, // )
-    LeftBrackets,     // [
-    RightBrackets,    // ]
-    LeftBrace,        // {
-    RightBrace,       // }
-
-    Plus,        // +
-    Minus,       // -
-    Star,        // *
-    Slash,       // /
-    Percent,     // %
-    Equal,       // =
-    Caret,       // ^
-    Not,         // !
-    And,         // &
-    Or,          // |
-    GreaterThan, //  >
-    LessThan,    // <
-    At,          // @
-    Dot,         // .
-    Comma,       // ,
-    Colon,       // :
-    Semicolon,   // ;
-    Underscore,  // _
-    Pound,       // #
-    Dollar,      // $
-    Question,    // ?
-    Tilde,       // ~
-
-    Eof,
+// 1文字の記号はnagi_macrosの表から付け足す
+nagi_macros::define_token_kind! {
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum TokenKind {
+        Unkown,
+        Identifier(String),
+        Prefix,
+        Literal(LiteralKind),
+        Comment,
+        WhiteSpace,
+
+        LeftParenthesis,  // (
+        RightParenthesis, // )
+        LeftBrackets,     // [
+        RightBrackets,    // ]
+        LeftBrace,        // {
+        RightBrace,       // }
+
+        Eof,
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -70,6 +70,15 @@ mod test {
         check_equal("1234567890", TokenKind::Literal(LiteralKind::DecLiteral));
         check_equal("0_9_", TokenKind::Literal(LiteralKind::DecLiteral));
         check_equal("0", TokenKind::Literal(LiteralKind::DecLiteral));
+        check_equal("0;", TokenKind::Literal(LiteralKind::DecLiteral));
+        check_token_order(
+            "0 }",
+            vec![
+                TokenKind::Literal(LiteralKind::DecLiteral),
+                TokenKind::WhiteSpace,
+                TokenKind::RightBrace,
+            ],
+        );
         check_equal("100u64", TokenKind::Literal(LiteralKind::DecLiteral));
 
         check_equal("02468ACE", TokenKind::Literal(LiteralKind::DecLiteral)); // この時点では 10進数 + 有効なsuffix かは判定しない
@@ -117,4 +126,49 @@ mod test {
         );
         check_not_equal("0", TokenKind::Literal(LiteralKind::FloatLiteral(false)));
     }
+
+    #[test]
+    fn literal_string() {
+        check_equal("\"C\"", TokenKind::Literal(LiteralKind::StringLiteral));
+        check_equal("\"\"", TokenKind::Literal(LiteralKind::StringLiteral));
+        check_equal(
+            "\"a\\\"b\\n\\u{1F600}\"",
+            TokenKind::Literal(LiteralKind::StringLiteral),
+        );
+        check_equal(
+            "\"a\\\n  b\"",
+            TokenKind::Literal(LiteralKind::StringLiteral),
+        );
+
+        check_not_equal("\"abc", TokenKind::Literal(LiteralKind::StringLiteral));
+        check_not_equal("\"\\q\"", TokenKind::Literal(LiteralKind::StringLiteral));
+        check_not_equal("\"a\rb\"", TokenKind::Literal(LiteralKind::StringLiteral));
+    }
+
+    #[test]
+    fn comment() {
+        check_equal("// hoge", TokenKind::Comment);
+        check_equal("/* hoge */", TokenKind::Comment);
+        check_equal("/* a /* b */ c */", TokenKind::Comment);
+        check_token_order(
+            "a // b\nc",
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::WhiteSpace,
+                TokenKind::Comment,
+                TokenKind::WhiteSpace,
+                TokenKind::Identifier("c".to_string()),
+            ],
+        );
+        check_token_order(
+            "a/*\n*/b",
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Comment,
+                TokenKind::Identifier("b".to_string()),
+            ],
+        );
+
+        check_not_equal("/ hoge", TokenKind::Comment);
+    }
 }
@@ -1,27 +1,48 @@
-macro_rules! define_separator {
-    ( $(($name:ident, $symbol:pat)),* ) => {
-        #[derive(Debug, PartialEq, Eq, Clone)]
-            pub enum Keyword {
-                $($name),*
-            }
+// 表のkeywordとreservedの行から予約語の型を作る
+//
+// nagi_macros::define_keywords! {
+//     #[derive(Debug)]
+//     pub enum Keyword {}
+// }
+#[macro_export]
+macro_rules! define_keywords {
+    ($(#[$meta:meta])* $vis:vis enum $keyword:ident {}) => {
+        $crate::__with_token_table!(keyword, __keyword_enum, [$(#[$meta])* $vis enum $keyword]);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __keyword_enum {
+    ([$(#[$meta:meta])* $vis:vis enum $keyword:ident]
+        $(($name:ident, $spelling:literal, $category:ident, $precedence:tt),)*) => {
+        $(#[$meta])*
+        $vis enum $keyword {
+            $($name,)*
+        }
+
+        impl $keyword {
+            pub const ALL: &'static [Self] = &[$(Self::$name,)*];
 
-            impl Keyword {
-                pub fn from_str(keyword: &str) -> Option<Self> {
-                    match sep {
-                        $($symbol => Some(Separator::$name),)*
-                        _ => None
-                    }
+            pub fn from_str(keyword: &str) -> Option<Self> {
+                match keyword {
+                    $($spelling => Some(Self::$name),)*
+                    _ => None,
                 }
-                
-                pub fn is_keyword(keyword: &str) -> Option<Self> {
-                    match sep {
-                        $($symbol => Some(Separator::$name),)*
-                        _ => None
-                    }
+            }
+
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(Self::$name => $spelling,)*
                 }
-                
             }
 
+            // 将来のために取っておくだけの予約語か
+            pub fn is_reserved(&self) -> bool {
+                match self {
+                    $(Self::$name => $crate::__is!(reserved, $category),)*
+                }
+            }
+        }
     };
-
 }
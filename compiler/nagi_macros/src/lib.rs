@@ -1,3 +1,8 @@
+// 予約語と記号の表(table.rs)から、字句解析器と構文解析器で使う型と関数を作る
+mod keyword;
+mod table;
+mod token;
+
 pub fn add(left: usize, right: usize) -> usize {
     left + right
 }
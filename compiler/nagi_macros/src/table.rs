@@ -0,0 +1,190 @@
+// 予約語と記号の表
+// 予約語や演算子を増やすときはここに1行足す
+//
+// (名前, 表記, 分類, 優先順位)
+//
+// 分類
+//   keyword  : 予約語
+//   reserved : 今は使っていないが、将来のために取っておく予約語
+//   single   : 字句解析器が1文字で切り出す記号
+//   joint    : 構文解析器が続けて並んだ1文字の記号をつなげて作る記号
+//   unjoined : つなげない記号 `a<-1`と`a < -1`を区別できないため
+//
+// 優先順位
+//   prefix: 単項演算子(束縛力)
+//   infix: 二項演算子(左の束縛力, 右の束縛力)
+//   左結合は(l, l + 1)、右結合は(l, l - 1)とし、優先順位の異なる演算子同士で値が重ならないようにする
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __with_token_table {
+    ($select:ident, $callback:ident, $extra:tt) => {
+        $crate::__select!($select, $callback, $extra, []
+            (Const, "const", keyword, {}),
+            (Continue, "continue", keyword, {}),
+            (Crate, "crate", keyword, {}),
+            (Else, "else", keyword, {}),
+            (Enum, "enum", keyword, {}),
+            (Extern, "extern", keyword, {}),
+            (False, "false", keyword, {}),
+            (Fn, "fn", keyword, {}),
+            (For, "for", keyword, {}),
+            (If, "if", keyword, {}),
+            (Impl, "impl", keyword, {}),
+            (In, "in", keyword, {}),
+            (Let, "let", keyword, {}),
+            (Loop, "loop", keyword, {}),
+            (Match, "match", keyword, {}),
+            (Mod, "mod", keyword, {}),
+            (Move, "move", keyword, {}),
+            (Mut, "mut", keyword, {}),
+            (Pub, "pub", keyword, {}),
+            (Ref, "ref", keyword, {}),
+            (Return, "return", keyword, {}),
+            (SelfValue, "self", keyword, {}),
+            (SelfType, "Self", keyword, {}),
+            (Static, "static", keyword, {}),
+            (Struct, "struct", keyword, {}),
+            (Super, "super", keyword, {}),
+            (Trait, "trait", keyword, {}),
+            (True, "true", keyword, {}),
+            (Type, "type", keyword, {}),
+            (Unsafe, "unsafe", keyword, {}),
+            (Use, "use", keyword, {}),
+            (Where, "where", keyword, {}),
+            (While, "while", keyword, {}),
+            (Async, "async", keyword, {}),
+            (Await, "await", keyword, {}),
+            (Dyn, "dyn", keyword, {}),
+            (Abstract, "abstract", reserved, {}),
+            (Become, "become", reserved, {}),
+            (Box, "box", reserved, {}),
+            (Do, "do", reserved, {}),
+            (Final, "final", reserved, {}),
+            (Macro, "macro", reserved, {}),
+            (Override, "override", reserved, {}),
+            (Priv, "priv", reserved, {}),
+            (Typeof, "typeof", reserved, {}),
+            (Unsized, "unsized", reserved, {}),
+            (Virtual, "virtual", reserved, {}),
+            (Yield, "yield", reserved, {}),
+            (Try, "try", reserved, {}),
+            (MacroRules, "macro_rules", keyword, {}),
+            (Union, "union", keyword, {}),
+            (StaticLifetime, "'static", keyword, {}),
+            (Ur, "ur", keyword, {}),
+            (Sr, "sr", keyword, {}),
+            (Nr, "nr", keyword, {}),
+
+            (Plus, "+", single, { infix: Add(21, 22) }),
+            (Minus, "-", single, { prefix: Neg(25), infix: Sub(21, 22) }),
+            (Star, "*", single, { infix: Mul(23, 24) }),
+            (Slash, "/", single, { infix: Div(23, 24) }),
+            (Percent, "%", single, { infix: Mod(23, 24) }),
+            (Caret, "^", single, { infix: Xor(15, 16) }),
+            (Not, "!", single, { prefix: Not(25) }),
+            (And, "&", single, { infix: And(17, 18) }),
+            (Or, "|", single, { infix: Or(13, 14) }),
+            (AndAnd, "&&", joint, { infix: LazyAnd(9, 10) }),
+            (OrOr, "||", joint, { infix: LazyOr(7, 8) }),
+            (LeftShift, "<<", joint, { infix: LeftShift(19, 20) }),
+            (RightShift, ">>", joint, { infix: RightShiht(19, 20) }),
+            (PlusEqual, "+=", joint, { infix: AddAssign(2, 1) }),
+            (MinusEqual, "-=", joint, { infix: SubAssign(2, 1) }),
+            (StarEqual, "*=", joint, { infix: MulAssign(2, 1) }),
+            (SlashEqual, "/=", joint, { infix: DivAssign(2, 1) }),
+            (PercentEqual, "%=", joint, { infix: ModAssign(2, 1) }),
+            (CaretEqual, "^=", joint, { infix: XorAssign(2, 1) }),
+            (AndEqual, "&=", joint, { infix: AndAssign(2, 1) }),
+            (OrEqual, "|=", joint, { infix: OrAssign(2, 1) }),
+            (LeftShiftEqual, "<<=", joint, {}),
+            (RightShiftEqual, ">>=", joint, {}),
+            (Equal, "=", single, { infix: Assign(2, 1) }),
+            (EqualEqual, "==", joint, { infix: Equal(11, 12) }),
+            (NotEqual, "!=", joint, { infix: NotEqual(11, 12) }),
+            (GreaterThan, ">", single, { infix: GreaterThan(11, 12) }),
+            (LessThan, "<", single, { infix: LessThan(11, 12) }),
+            (GreaterThanOrEqual, ">=", joint, { infix: GreaterThanOrEqual(11, 12) }),
+            (LessThanOrEqual, "<=", joint, { infix: LessThanOrEqual(11, 12) }),
+            (At, "@", single, {}),
+            (Underscore, "_", single, {}),
+            (Dot, ".", single, {}),
+            (DotDot, "..", joint, { infix: Range(5, 6) }),
+            (DotDotDot, "...", joint, {}),
+            (DotDotEqual, "..=", joint, { infix: RangeInclusive(5, 6) }),
+            (Comma, ",", single, {}),
+            (Semicolon, ";", single, {}),
+            (Colon, ":", single, {}),
+            (PathSeparater, "::", joint, {}),
+            (RightAllow, "->", joint, {}),
+            (FatAllow, "=>", joint, {}),
+            (LeftAllow, "<-", unjoined, { infix: LeftAllow(3, 2) }),
+            (Pound, "#", single, {}),
+            (Dollar, "$", single, {}),
+            (Question, "?", single, {}),
+            (Tilde, "~", single, {}),
+        );
+    };
+}
+
+// 表から分類に合う行だけを選び、$callbackに渡す
+//   keyword     : keywordとreserved
+//   punctuation : single、joint、unjoined
+//   single      : single
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __select {
+    ($select:ident, $callback:ident, $extra:tt, [$($selected:tt)*]) => {
+        $crate::$callback!($extra $($selected)*);
+    };
+
+    (keyword, $callback:ident, $extra:tt, [$($selected:tt)*]
+        ($name:ident, $spelling:literal, keyword, $precedence:tt), $($rest:tt)*) => {
+        $crate::__select!(keyword, $callback, $extra,
+            [$($selected)* ($name, $spelling, keyword, $precedence),] $($rest)*);
+    };
+    (keyword, $callback:ident, $extra:tt, [$($selected:tt)*]
+        ($name:ident, $spelling:literal, reserved, $precedence:tt), $($rest:tt)*) => {
+        $crate::__select!(keyword, $callback, $extra,
+            [$($selected)* ($name, $spelling, reserved, $precedence),] $($rest)*);
+    };
+    (punctuation, $callback:ident, $extra:tt, [$($selected:tt)*]
+        ($name:ident, $spelling:literal, single, $precedence:tt), $($rest:tt)*) => {
+        $crate::__select!(punctuation, $callback, $extra,
+            [$($selected)* ($name, $spelling, single, $precedence),] $($rest)*);
+    };
+    (punctuation, $callback:ident, $extra:tt, [$($selected:tt)*]
+        ($name:ident, $spelling:literal, joint, $precedence:tt), $($rest:tt)*) => {
+        $crate::__select!(punctuation, $callback, $extra,
+            [$($selected)* ($name, $spelling, joint, $precedence),] $($rest)*);
+    };
+    (punctuation, $callback:ident, $extra:tt, [$($selected:tt)*]
+        ($name:ident, $spelling:literal, unjoined, $precedence:tt), $($rest:tt)*) => {
+        $crate::__select!(punctuation, $callback, $extra,
+            [$($selected)* ($name, $spelling, unjoined, $precedence),] $($rest)*);
+    };
+    (single, $callback:ident, $extra:tt, [$($selected:tt)*]
+        ($name:ident, $spelling:literal, single, $precedence:tt), $($rest:tt)*) => {
+        $crate::__select!(single, $callback, $extra,
+            [$($selected)* ($name, $spelling, single, $precedence),] $($rest)*);
+    };
+
+    // 分類が合わない行は飛ばす
+    ($select:ident, $callback:ident, $extra:tt, [$($selected:tt)*] $row:tt, $($rest:tt)*) => {
+        $crate::__select!($select, $callback, $extra, [$($selected)*] $($rest)*);
+    };
+}
+
+// 行の分類が$expectedならtrue
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __is {
+    (reserved, reserved) => {
+        true
+    };
+    (joint, joint) => {
+        true
+    };
+    ($expected:ident, $category:ident) => {
+        false
+    };
+}
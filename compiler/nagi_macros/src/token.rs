@@ -0,0 +1,193 @@
+// 表の記号の行を、トークンの型の後ろに付け足す
+// 構文解析器のトークンは全部の記号を、字句解析器のトークンは1文字の記号だけを持つ
+//
+// nagi_macros::define_tokens! {
+//     #[derive(Debug)]
+//     pub enum Token {
+//         Identifier(String),
+//         Eof,
+//     }
+// }
+#[macro_export]
+macro_rules! define_tokens {
+    ($(#[$meta:meta])* $vis:vis enum $token:ident { $($variants:tt)* }) => {
+        $crate::__with_token_table!(
+            punctuation,
+            __punctuation_enum,
+            [$(#[$meta])* $vis enum $token { $($variants)* }]
+        );
+    };
+}
+
+#[macro_export]
+macro_rules! define_token_kind {
+    ($(#[$meta:meta])* $vis:vis enum $token:ident { $($variants:tt)* }) => {
+        $crate::__with_token_table!(
+            single,
+            __punctuation_enum,
+            [$(#[$meta])* $vis enum $token { $($variants)* }]
+        );
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __punctuation_enum {
+    ([$(#[$meta:meta])* $vis:vis enum $token:ident { $($variants:tt)* }]
+        $(($name:ident, $spelling:literal, $category:ident, $precedence:tt),)*) => {
+        $(#[$meta])*
+        $vis enum $token {
+            $($variants)*
+            $($name,)*
+        }
+
+        impl $token {
+            pub const PUNCTUATIONS: &'static [Self] = &[$(Self::$name,)*];
+
+            // 記号の表記
+            pub fn punctuation_str(&self) -> Option<&'static str> {
+                match self {
+                    $(Self::$name => Some($spelling),)*
+                    _ => None,
+                }
+            }
+
+            pub fn from_punctuation_str(punctuation: &str) -> Option<Self> {
+                match punctuation {
+                    $($spelling => Some(Self::$name),)*
+                    _ => None,
+                }
+            }
+
+            // 1文字の記号をつなげて作る記号か
+            pub fn is_joint_punctuation(&self) -> bool {
+                match self {
+                    $(Self::$name => $crate::__is!(joint, $category),)*
+                    _ => false,
+                }
+            }
+        }
+    };
+}
+
+// 表の優先順位から単項演算子と二項演算子の型と、表記を返すas_str、束縛力を返すbinding_powerを作る
+//
+// nagi_macros::define_operators! {
+//     Token;
+//     #[derive(Debug)]
+//     pub enum UnaryOperator {}
+//     #[derive(Debug)]
+//     pub enum BinaryOperator {}
+// }
+#[macro_export]
+macro_rules! define_operators {
+    ($token:ident;
+        $(#[$unary_meta:meta])* $unary_vis:vis enum $unary:ident {}
+        $(#[$binary_meta:meta])* $binary_vis:vis enum $binary:ident {}) => {
+        $crate::__with_token_table!(
+            punctuation,
+            __operator_enums,
+            [$token;
+                $(#[$unary_meta])* $unary_vis enum $unary
+                $(#[$binary_meta])* $binary_vis enum $binary]
+        );
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __operator_enums {
+    ([$token:ident;
+        $(#[$unary_meta:meta])* $unary_vis:vis enum $unary:ident
+        $(#[$binary_meta:meta])* $binary_vis:vis enum $binary:ident]
+        $(($name:ident, $spelling:literal, $category:ident, {
+            $(prefix: $unary_operator:ident($prefix:literal))? $(,)?
+            $(infix: $binary_operator:ident($left:literal, $right:literal))?
+        }),)*) => {
+        $(#[$unary_meta])*
+        $unary_vis enum $unary {
+            $($($unary_operator,)?)*
+        }
+
+        impl $unary {
+            pub fn from_token(token: &$token) -> Option<Self> {
+                match token {
+                    $($($token::$name => Some(Self::$unary_operator),)?)*
+                    _ => None,
+                }
+            }
+
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($(Self::$unary_operator => $spelling,)?)*
+                }
+            }
+
+            // 右の束縛力
+            pub fn binding_power(&self) -> u16 {
+                match self {
+                    $($(Self::$unary_operator => $prefix,)?)*
+                }
+            }
+        }
+
+        $(#[$binary_meta])*
+        $binary_vis enum $binary {
+            $($($binary_operator,)?)*
+        }
+
+        impl $binary {
+            pub fn from_token(token: &$token) -> Option<Self> {
+                match token {
+                    $($($token::$name => Some(Self::$binary_operator),)?)*
+                    _ => None,
+                }
+            }
+
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($(Self::$binary_operator => $spelling,)?)*
+                }
+            }
+
+            // (左の束縛力, 右の束縛力)
+            pub fn binding_power(&self) -> (u16, u16) {
+                match self {
+                    $($(Self::$binary_operator => ($left, $right),)?)*
+                }
+            }
+        }
+    };
+}
+
+// 表の優先順位からPratt parserの束縛力を返す関数を作る
+#[macro_export]
+macro_rules! define_binding_powers {
+    ($token:ident) => {
+        $crate::__with_token_table!(punctuation, __binding_powers, [$token]);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __binding_powers {
+    ([$token:ident]
+        $(($name:ident, $spelling:literal, $category:ident, {
+            $(prefix: $unary_operator:ident($prefix:literal))? $(,)?
+            $(infix: $binary_operator:ident($left:literal, $right:literal))?
+        }),)*) => {
+        pub fn prefix_binding_power(op: &$token) -> Option<((), u16)> {
+            match op {
+                $($($token::$name => Some(((), $prefix)),)?)*
+                _ => None,
+            }
+        }
+
+        pub fn infix_binding_power(op: &$token) -> Option<(u16, u16)> {
+            match op {
+                $($($token::$name => Some(($left, $right)),)?)*
+                _ => None,
+            }
+        }
+    };
+}
@@ -0,0 +1,88 @@
+// cargo bench -p nagi_parse
+// 結果は benches/README.md に記録する
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use nagi_command_option::{CompileCommandOption, ParseFrontEnd, ParseLimits, TreeFormat};
+
+// 1関数あたり12行
+fn make_source(function_count: usize) -> String {
+    let mut code = String::new();
+    for i in 0..function_count {
+        code.push_str(&format!(
+            "fn function{i}() {{
+    let x = {n} + 2 * 3;
+    ur y = f(x, 1).b[2];
+    sr z = (x - y) * 3;
+    if x {{ let w = 1; }} else {{ nr v = 2; }}
+    x.y.z(1, 2);
+    let q = -x + y * z - w / 4 % 5;
+    {{
+        let inner = q.r;
+    }}
+    q
+}}
+
+",
+            n = i + 1 // `0`単体はまだ字句解析できない
+        ));
+    }
+    code
+}
+
+fn bench(name: &str, lines: usize, f: impl Fn()) {
+    // 最低でも1秒、3回は計測する
+    let mut times = Vec::new();
+    let start = Instant::now();
+    while times.len() < 3 || start.elapsed() < Duration::from_secs(1) {
+        let time = Instant::now();
+        f();
+        times.push(time.elapsed());
+    }
+    times.sort();
+
+    let median = times[times.len() / 2];
+    println!(
+        "{:<16} {:>6} lines  median {:>10.3?}  min {:>10.3?}  ({} runs)",
+        name,
+        lines,
+        median,
+        times[0],
+        times.len()
+    );
+}
+
+fn main() {
+    let mut option = CompileCommandOption {
+        is_debug: false,
+        is_compiler_debug: false,
+        is_output_ast: false,
+        tree_format: TreeFormat::Json,
+        parse_trace: None,
+        graph: None,
+        parser: ParseFrontEnd::Cst,
+        parse_limits: ParseLimits::default(),
+        target_list: vec![],
+    };
+
+    for function_count in [50, 420] {
+        let code = make_source(function_count);
+        let lines = code.lines().count();
+
+        let result = nagi_parse::parse(&code, &option);
+        assert!(result.is_ok(), "{:?}", result.errors);
+
+        bench("lex", lines, || {
+            let mut lexer = nagi_lexer::lexer::Lexer::new(&code);
+            black_box(lexer.tokenize());
+        });
+        option.parser = ParseFrontEnd::Cst;
+        bench("lex + parse", lines, || {
+            black_box(nagi_parse::parse(&code, &option));
+        });
+        option.parser = ParseFrontEnd::Ast;
+        bench("lex + parse (ast)", lines, || {
+            black_box(nagi_parse::parse(&code, &option));
+        });
+    }
+}
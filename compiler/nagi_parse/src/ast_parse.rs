@@ -1 +1,4 @@
 pub mod ast_parser;
+
+#[cfg(test)]
+mod tests;
@@ -1,32 +1,3094 @@
+use crate::expression::*;
 use crate::lexer::Lexer;
+use crate::packrat::*;
 use crate::parser::*;
 
+use nagi_command_option::ParseLimits;
+use nagi_errors::{Error, SyntaxError};
 use nagi_syntax_tree::ast::*;
 use nagi_syntax_tree::keywords::Keyword;
 use nagi_syntax_tree::token::*;
+use std::rc::Rc;
 
-use std::collections::HashMap;
-use std::fs::OpenOptions;
-use std::io::{BufWriter, Write};
+// CSTを作らずにトークンから直接ASTを作る
+// 規則はCSTParserと同じ順番で試し、同じエラーを報告する
+// 作るASTはCSTをnagi_checker::checkで変換したものと同じ
+
+// 規則の本体 左再帰の種を成長させるために何度か呼び出す
+type Rule = fn(&mut ASTParser) -> Result<ASTNode, Error>;
 
 pub struct ASTParser {
     lexer: Lexer,
-    memo: HashMap<ParseMemoKey, Option<ASTNode>>,
-    min_bp: u16,
-    last_write_memo: ParseMemoKey,
+    state: PackratState<ASTNode>,
+}
+
+impl PackratParser for ASTParser {
+    type Node = ASTNode;
+
+    fn lexer(&self) -> &Lexer {
+        &self.lexer
+    }
+
+    fn lexer_mut(&mut self) -> &mut Lexer {
+        &mut self.lexer
+    }
+
+    fn state(&mut self) -> &mut PackratState<ASTNode> {
+        &mut self.state
+    }
+
+    fn set_span(&self, node: ASTNode, start: usize, end: usize) -> ASTNode {
+        node.with_span(self.lexer.span(start, end))
+    }
 }
 
 // TODO 機能ごとの分割
 impl ASTParser {
-    pub fn new(token_list: &Vec<nagi_lexer::Token>) -> Self {
+    pub(crate) fn from_lexer(lexer: Lexer) -> Self {
+        let token_count = lexer.token_count();
         Self {
-            lexer: Lexer::new(token_list),
-            memo: HashMap::new(),
-            min_bp: 0,
-            last_write_memo: ParseMemoKey {
-                position: 0,
-                rule: "".to_string(),
-            },
+            lexer,
+            state: PackratState::new(token_count),
+        }
+    }
+
+    pub fn enable_trace(&mut self) {
+        self.start_trace();
+    }
+
+    pub fn set_limits(&mut self, limits: ParseLimits) {
+        self.state.limits = limits;
+    }
+
+    // 構文エラーがあっても途中までのASTと全てのエラーを返す
+    pub fn parse(&mut self) -> ParseResult<ASTNode> {
+        let tree = self.crates_and_source_files();
+        let (errors, trace) = self.finish();
+
+        ParseResult {
+            tree,
+            errors,
+            trace,
+        }
+    }
+
+    fn crates_and_source_files(&mut self) -> ASTNode {
+        let mut inner_attribute = Vec::<ASTNode>::new();
+        let mut item = Vec::<ASTNode>::new();
+
+        // InnerAttribute*
+        while let Ok(expr) = self.inner_attribute() {
+            inner_attribute.push(expr);
+        }
+
+        // Item*
+        loop {
+            if let Ok(expr) = self.item() {
+                item.push(expr);
+                continue;
+            }
+
+            if matches!(self.lexer.peek(), Token::Eof) {
+                break;
+            }
+
+            // アイテムとして解析できなければ次のアイテムまで読み飛ばす
+            // 読み飛ばした部分はASTに残さない
+            self.skip_to_sync(false);
+        }
+
+        let end = self.lexer.get_token_position();
+        ASTNode::new(ASTNodeKind::Crate {
+            inner_attribute,
+            item,
+        })
+        .with_span(self.lexer.span(0, end))
+    }
+
+    //
+    // Attributes
+    //
+
+    // InnerAttribute ::= `#` `!` `[` Attribute `]`
+    fn inner_attribute(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::InnerAttribute);
+        match self.get_memo(&key, Self::inner_attribute) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `#`
+        if !self.is_token(Token::Pound) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // `!`
+        if !self.is_token(Token::Not) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // `[`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brackets)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_brackets = self.next_opener();
+
+        // Attribute
+        let attribute = Rc::new(self.attribute()?);
+
+        // `]`
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Brackets),
+            left_brackets,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::InnerAttribute { attribute }))
+    }
+
+    // OuterAttribute ::= `#` `[` Attribute `]`
+    fn outer_attribute(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::OuterAttribute);
+        match self.get_memo(&key, Self::outer_attribute) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `#`
+        if !self.is_token(Token::Pound) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // `[`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brackets)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_brackets = self.next_opener();
+
+        let attribute = Rc::new(self.attribute()?);
+
+        // `]`
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Brackets),
+            left_brackets,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::OuterAttribute { attribute }))
+    }
+
+    // Attribute ::= SimplePath AttributeInput?  | `unsafe` `(` SimplePath AttributeInput? `)`
+    fn attribute(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Attribute);
+        match self.get_memo(&key, Self::attribute) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `unsafe` `(`
+        let mut left_parenthesis = None;
+        if let Token::Keyword(Keyword::Unsafe) = self.lexer.peek() {
+            self.lexer.next();
+
+            if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Parenthesis)) {
+                return self.error(SyntaxError::ExpectedToken, &key);
+            }
+            left_parenthesis = Some(self.next_opener());
+        }
+
+        // SimplePath
+        let simple_path = Rc::new(self.simple_path()?);
+
+        // AttributeInput?
+        let attribute_input = self.attribute_input().ok().map(Rc::new);
+
+        // `)`
+        if let Some(opener_position) = left_parenthesis {
+            if !self.is_closing_token(
+                Token::RightParenthesis(RightParenthesis::Parenthesis),
+                opener_position,
+            ) {
+                return self.error(SyntaxError::ExpectedToken, &key);
+            }
+            self.lexer.next();
+        }
+
+        Ok(ASTNode::new(ASTNodeKind::Attribute {
+            is_unsafe: left_parenthesis.is_some(),
+            simple_path,
+            attribute_input,
+        }))
+    }
+
+    // AttributeInput ::= DelimTokenTree | `=` Expression
+    fn attribute_input(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::AttributeInput);
+        match self.get_memo(&key, Self::attribute_input) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // DelimTokenTree
+        if let Token::LeftParenthesis(_) = self.lexer.peek() {
+            return self.delim_token_tree();
+        }
+
+        // `=` Expression
+        let Token::Equal = self.lexer.peek() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        self.lexer.next();
+
+        self.expression()
+    }
+
+    // SimplePath ::= `::`? SimplePathSegment (`::` SimplePathSegment)*
+    fn simple_path(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::SimplePath);
+        match self.get_memo(&key, Self::simple_path) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `::`?
+        if let Token::PathSeparater = self.lexer.peek_glue() {
+            self.lexer.next_glue();
+        }
+
+        // SimplePathSegment
+        if !is_simple_path_segment(&self.lexer.peek()) {
+            self.expected("path");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let simple_path_segment = Rc::new(self.make_factor_and_next());
+
+        // (`::` SimplePathSegment)*
+        let mut repeat_simple_path_segment = Vec::<ASTNode>::new();
+        while let Token::PathSeparater = self.lexer.peek_glue() {
+            self.lexer.next_glue();
+
+            if !is_simple_path_segment(&self.lexer.peek()) {
+                self.expected("identifier");
+                return self.error(SyntaxError::ExpectedToken, &key);
+            }
+            repeat_simple_path_segment.push(self.make_factor_and_next());
+        }
+
+        Ok(ASTNode::new(ASTNodeKind::SimplePath {
+            simple_path_segment,
+            repeat_simple_path_segment,
+        }))
+    }
+
+    // DelimTokenTree ::= `(` TokenTree* `)` | `[` TokenTree* `]` | `{` TokenTree* `}`
+    // TokenTree ::= 括弧以外のトークン | DelimTokenTree
+    fn delim_token_tree(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::DelimTokenTree);
+        match self.get_memo(&key, Self::delim_token_tree) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let Token::LeftParenthesis(delimiter) = self.lexer.peek() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        let left_parenthesis = self.next_opener();
+
+        // TokenTree*
+        let mut token_tree = Vec::<ASTNode>::new();
+        loop {
+            match self.lexer.peek() {
+                Token::LeftParenthesis(_) => token_tree.push(self.delim_token_tree()?),
+                Token::RightParenthesis(_) | Token::Eof => break,
+                _ => token_tree.push(self.make_factor_and_next()),
+            }
+        }
+
+        // 対応する閉じ括弧
+        if !self.is_closing_token(
+            Token::RightParenthesis(delimiter.closing()),
+            left_parenthesis,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::DelimTokenTree {
+            delimiter,
+            token_tree,
+        }))
+    }
+
+    // Visibility ::= `pub`
+    //              | `pub` `(` `crate` `)`
+    //              | `pub` `(` `self` `)`
+    //              | `pub` `(` `super` `)`
+    //              | `pub` `(` `in` SimplePath `)`
+    fn visibility(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Visibility);
+        match self.get_memo(&key, Self::visibility) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `pub`
+        // TODO `pub` `(` ... `)`
+        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::Pub)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::Visibility {}))
+    }
+
+    //
+    // Items
+    //
+
+    // Item ::= OuterAttribute* VisItem | MacroItem
+    fn item(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Item);
+        match self.get_memo(&key, Self::item) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // OuterAttribute*
+        let mut outer_attribute = Vec::<ASTNode>::new();
+        while let Ok(expr) = self.outer_attribute() {
+            outer_attribute.push(expr);
+        }
+
+        // MacroItem
+        if outer_attribute.is_empty() {
+            if let Ok(node) = self.macro_item() {
+                return Ok(node);
+            }
+        }
+
+        let Ok(mut node) = self.vis_item() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        set_outer_attribute(&mut node, outer_attribute);
+
+        Ok(self.rule_span(node, &key))
+    }
+
+    // MacroItem ::= MacroInvocationSemi | MacroRulesDefinition
+    fn macro_item(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::MacroItem);
+        match self.get_memo(&key, Self::macro_item) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        if let Ok(node) = self.macro_rules_definition() {
+            return Ok(node);
+        }
+        if let Ok(node) = self.macro_invocation_semi() {
+            return Ok(node);
+        }
+
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    // MacroInvocation ::= SimplePath `!` DelimTokenTree
+    fn macro_invocation(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::MacroInvocation);
+        match self.get_memo(&key, Self::macro_invocation) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // 組み込みマクロは引数を式として解析する
+        if let Ok(node) = self.builtin_macro_invocation() {
+            return Ok(node);
+        }
+
+        // SimplePath
+        let simple_path = Rc::new(self.simple_path()?);
+
+        // `!`
+        // `!=`と区別するため一文字だけ見る
+        if !matches!(self.lexer.peek(), Token::Not)
+            || !matches!(self.lexer.peek_ahead(1), Token::LeftParenthesis(_))
+        {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        // DelimTokenTree
+        let delim_token_tree = Rc::new(self.delim_token_tree()?);
+
+        Ok(ASTNode::new(ASTNodeKind::MacroInvocation {
+            simple_path,
+            delim_token_tree,
+        }))
+    }
+
+    // BuiltinMacroInvocation ::= BuiltinMacroName `!` `(` CallParams? `)`
+    // 引数が式でなければユーザー定義のマクロとして解析し直す
+    fn builtin_macro_invocation(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::BuiltinMacroInvocation);
+        match self.get_memo(&key, Self::builtin_macro_invocation) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // BuiltinMacroName
+        let Token::Identifier(name) = self.lexer.peek() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        let Some(builtin_macro) = BuiltinMacro::from_name(&name) else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        if !matches!(self.lexer.peek_ahead(1), Token::Not) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+        self.lexer.next();
+
+        // `(`
+        if !matches!(
+            self.lexer.peek(),
+            Token::LeftParenthesis(LeftParenthesis::Parenthesis)
+        ) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        // CallParams?
+        let call_params = self.call_params().unwrap_or_default();
+
+        // `)`
+        if !matches!(
+            self.lexer.peek(),
+            Token::RightParenthesis(RightParenthesis::Parenthesis)
+        ) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::BuiltinMacroInvocation {
+            builtin_macro,
+            call_params,
+        }))
+    }
+
+    // MacroInvocationSemi ::= SimplePath `!` `(` TokenTree* `)` `;`
+    //                       | SimplePath `!` `[` TokenTree* `]` `;`
+    //                       | SimplePath `!` `{` TokenTree* `}`
+    fn macro_invocation_semi(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::MacroInvocationSemi);
+        match self.get_memo(&key, Self::macro_invocation_semi) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // 組み込みマクロは式文になる
+        let node = self.macro_invocation()?;
+        if !matches!(node.node_kind, ASTNodeKind::MacroInvocation { .. }) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+
+        // `{}`で囲んだものは`;`が無くてもよい
+        if matches!(self.lexer.peek(), Token::Semicolon) {
+            self.lexer.next();
+        } else if !is_brace_token_tree(&node) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+
+        Ok(self.rule_span(node, &key))
+    }
+
+    // MacroRulesDefinition ::= `macro_rules` `!` Identifier MacroRulesDef
+    // MacroRulesDef ::= `(` MacroRules `)` `;` | `[` MacroRules `]` `;` | `{` MacroRules `}`
+    fn macro_rules_definition(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::MacroRulesDefinition);
+        match self.get_memo(&key, Self::macro_rules_definition) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `macro_rules`
+        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::MacroRules)) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        // `!`
+        if !self.is_token(Token::Not) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // Identifier
+        let Token::Identifier(identifier) = self.lexer.peek() else {
+            self.expected("identifier");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        };
+        self.lexer.next();
+
+        // MacroRulesDef
+        let delim_token_tree = self.delim_token_tree()?;
+        if !matches!(
+            delim_token_tree.node_kind,
+            ASTNodeKind::DelimTokenTree {
+                delimiter: LeftParenthesis::Brace,
+                ..
+            }
+        ) {
+            if !self.is_token(Token::Semicolon) {
+                return self.error(SyntaxError::MissingSemicolon, &key);
+            }
+            self.lexer.next();
+        }
+
+        Ok(ASTNode::new(ASTNodeKind::MacroRulesDefinition {
+            identifier,
+            delim_token_tree: Rc::new(delim_token_tree),
+        }))
+    }
+
+    // VisItem ::= Visibility? ( Module | ExternCrate | UseDeclaration | Function | ... )
+    fn vis_item(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::VisItem);
+        match self.get_memo(&key, Self::vis_item) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Visibility?
+        // TODO ASTに残す
+        let _ = self.visibility();
+
+        // Function
+        if let Ok(expr) = self.function() {
+            return Ok(self.rule_span(expr, &key));
+        }
+
+        // TypeAlias
+        if let Ok(expr) = self.type_alias() {
+            return Ok(self.rule_span(expr, &key));
+        }
+
+        // Struct
+        if let Ok(expr) = self.struct_item() {
+            return Ok(self.rule_span(expr, &key));
+        }
+
+        // Implementation
+        if let Ok(expr) = self.implementation() {
+            return Ok(self.rule_span(expr, &key));
+        }
+
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    //
+    // Functions
+    //
+
+    // Function ::= FunctionQualifiers `fn` Identifier GenericParams?
+    //             `(` FunctionParameters? `)`
+    //             FunctionReturnType? WhereClause?
+    //             ( BlockExpression | `;` )
+    fn function(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Function);
+        match self.get_memo(&key, Self::function) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // FunctionQualifiers
+        let function_qualifiers = Rc::new(self.function_qualifiers()?);
+
+        // `fn`
+        if !self.is_token(Token::Keyword(Keyword::Fn)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // Identifier
+        let Token::Identifier(identifier) = self.lexer.peek() else {
+            self.expected("identifier");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        };
+        self.lexer.next();
+
+        // GenericParams?
+        let generic_params = self.generic_params().ok().map(Rc::new);
+
+        // `(`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Parenthesis)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_parenthesis = self.next_opener();
+
+        // FunctionParameters?
+        let function_parameters = self.function_parameters().ok().map(Rc::new);
+
+        // `)`
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Parenthesis),
+            left_parenthesis,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // FunctionReturnType?
+        let function_return_type = self.function_return_type().ok().map(Rc::new);
+
+        // ( BlockExpression | `;` )
+        let block_expression = if let Token::Semicolon = self.lexer.peek() {
+            self.lexer.next();
+            None
+        } else if let Ok(expr) = self.block_expression() {
+            Some(Rc::new(expr))
+        } else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        Ok(ASTNode::new(ASTNodeKind::Function {
+            outer_attribute: vec![],
+            function_qualifiers,
+            identifier,
+            generic_params,
+            function_parameters,
+            function_return_type,
+            where_clause: None,
+            block_expression,
+        }))
+    }
+
+    // FunctionQualifiers ::= `const`? `async`? ItemSafety? (`extern` Abi?)?
+    fn function_qualifiers(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::FunctionQualifiers);
+        match self.get_memo(&key, Self::function_qualifiers) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let mut const_keyword = false;
+        let mut async_keyword = false;
+        let mut extern_keyword = false;
+
+        // `const`?
+        if matches!(self.lexer.peek(), Token::Keyword(Keyword::Const)) {
+            const_keyword = true;
+            self.lexer.next();
+        }
+
+        // `async`?
+        if matches!(self.lexer.peek(), Token::Keyword(Keyword::Async)) {
+            async_keyword = true;
+            self.lexer.next();
+        }
+
+        // ItemSafety?
+        let item_safety = self.item_safety().ok().map(Rc::new);
+
+        // (`extern` `Abi`?)?
+        let mut abi = None;
+        if matches!(self.lexer.peek(), Token::Keyword(Keyword::Extern)) {
+            extern_keyword = true;
+            self.lexer.next();
+
+            abi = self.abi().ok().map(Rc::new);
+        }
+
+        Ok(ASTNode::new(ASTNodeKind::FunctionQualifiers {
+            const_keyword,
+            async_keyword,
+            item_safety,
+            extern_keyword,
+            abi,
+        }))
+    }
+
+    // ItemSafety ::= `safe` | `unsafe`
+    fn item_safety(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::ItemSafety);
+        match self.get_memo(&key, Self::item_safety) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        match self.lexer.peek() {
+            Token::Keyword(Keyword::Unsafe) => Ok(self.make_factor_and_next()),
+            Token::Identifier(identifier) => {
+                if identifier != "safe" {
+                    return self.error(SyntaxError::ExpectedToken, &key);
+                }
+                Ok(self.make_factor_and_next())
+            }
+            _ => self.error(SyntaxError::ExpectedToken, &key),
+        }
+    }
+
+    // Abi ::= STRING_LITERAL | RAW_STRING_LITERAL
+    fn abi(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Abi);
+        match self.get_memo(&key, Self::abi) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        match self.lexer.peek() {
+            Token::Literal(literal) => {
+                if !matches!(literal.literal_kind, LiteralKind::Str | LiteralKind::StrRaw) {
+                    return self.error(SyntaxError::ExpectedToken, &key);
+                }
+
+                Ok(self.make_factor_and_next())
+            }
+            _ => self.error(SyntaxError::ExpectedToken, &key),
         }
     }
+
+    // GenericParams ::= `<` `>` | `<` (GenericParam `,`)* GenericParam `,`? `>`
+    fn generic_params(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::GenericParams);
+        match self.get_memo(&key, Self::generic_params) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `<` `>`
+        if !self.is_token(Token::LessThan) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+        if matches!(self.lexer.peek(), Token::GreaterThan) {
+            return Ok(self.make_factor_and_next());
+        }
+
+        // (GenericParam `,`)* GenericParam `,`?
+        // TODO
+
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    // FunctionParameters ::= SelfParam `,`? | (SelfParam `,`)? FunctionParam (`,` FunctionParam)* `,`?
+    fn function_parameters(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::FunctionParameters);
+        match self.get_memo(&key, Self::function_parameters) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // SelfParam
+        let self_param = self.self_param().ok().map(Rc::new);
+
+        // `,`
+        let comma = matches!(self.lexer.peek(), Token::Comma);
+
+        // ,のみはエラー
+        if self_param.is_none() && comma {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+
+        // SelfParamのみ
+        if self_param.is_some() && !comma {
+            return Ok(ASTNode::new(ASTNodeKind::FunctionParameters {
+                self_param,
+                function_params: vec![],
+            }));
+        }
+        if comma {
+            self.lexer.next();
+        }
+
+        // FunctionParam
+        let mut function_params = vec![];
+        match self.function_param() {
+            Ok(param) => function_params.push(param),
+            // SelfParam `,`
+            Err(_) if self_param.is_some() => (),
+            Err(_) => return self.error(SyntaxError::NotMatch, &key),
+        }
+
+        // (`,` FunctionParam)* `,`?
+        if !function_params.is_empty() {
+            while let Token::Comma = self.lexer.peek() {
+                self.lexer.next();
+                let Ok(param) = self.function_param() else {
+                    break;
+                };
+                function_params.push(param);
+            }
+        }
+
+        Ok(ASTNode::new(ASTNodeKind::FunctionParameters {
+            self_param,
+            function_params,
+        }))
+    }
+
+    // SelfParam ::= OuterAttribute* ( ShorthandSelf | TypedSelf )
+    fn self_param(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::SelfParam);
+        match self.get_memo(&key, Self::self_param) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // OuterAttribute*
+        let mut outer_attribute = Vec::<ASTNode>::new();
+        while let Ok(expr) = self.outer_attribute() {
+            outer_attribute.push(expr);
+        }
+
+        // ( TypedSelf | ShorthandSelf )
+        // `self`だけではTypedSelfの途中と区別できないので、TypedSelfから試す
+        let Ok(mut node) = self.typed_self().or_else(|_| self.shorthand_self()) else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        if let ASTNodeKind::SelfParam {
+            outer_attribute: attribute,
+            ..
+        } = &mut node.node_kind
+        {
+            *attribute = outer_attribute;
+        }
+
+        Ok(node)
+    }
+
+    // ShorthandSelf ::= (`&` | `&` Lifetime)? `mut`? `self`
+    fn shorthand_self(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::ShorthandSelf);
+        match self.get_memo(&key, Self::shorthand_self) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // (`&` | `&` Lifetime)?
+        // TODO Lifetime
+        let reference = matches!(self.lexer.peek(), Token::And);
+        if reference {
+            self.lexer.next();
+        }
+
+        // `mut`?
+        let mut_keyword = matches!(self.lexer.peek(), Token::Keyword(Keyword::Mut));
+        if mut_keyword {
+            self.lexer.next();
+        }
+
+        // `self`
+        if !self.is_token(Token::Keyword(Keyword::SelfValue)) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::SelfParam {
+            outer_attribute: vec![],
+            reference,
+            mut_keyword,
+            type_expression: None,
+        }))
+    }
+
+    // TypedSelf ::= `mut`? `self` `:` Type
+    fn typed_self(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::TypedSelf);
+        match self.get_memo(&key, Self::typed_self) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `mut`?
+        let mut_keyword = matches!(self.lexer.peek(), Token::Keyword(Keyword::Mut));
+        if mut_keyword {
+            self.lexer.next();
+        }
+
+        // `self`
+        if !self.is_token(Token::Keyword(Keyword::SelfValue)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // `:`
+        if !self.is_token(Token::Colon) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // Type
+        let type_expression = Rc::new(self.type_expression()?);
+
+        Ok(ASTNode::new(ASTNodeKind::SelfParam {
+            outer_attribute: vec![],
+            reference: false,
+            mut_keyword,
+            type_expression: Some(type_expression),
+        }))
+    }
+
+    // FunctionParam ::= OuterAttribute* ( FunctionParamPattern | `...` | Type )
+    // TODO `...`とTypeだけの引数
+    fn function_param(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::FunctionParam);
+        match self.get_memo(&key, Self::function_param) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // OuterAttribute*
+        let mut outer_attribute = Vec::<ASTNode>::new();
+        while let Ok(expr) = self.outer_attribute() {
+            outer_attribute.push(expr);
+        }
+
+        // FunctionParamPattern
+        let Ok(mut node) = self.function_param_pattern() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        if let ASTNodeKind::FunctionParam {
+            outer_attribute: attribute,
+            ..
+        } = &mut node.node_kind
+        {
+            *attribute = outer_attribute;
+        }
+
+        Ok(node)
+    }
+
+    // FunctionParamPattern ::= PatternNoTopAlt `:` ( Type | `...` )
+    fn function_param_pattern(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::FunctionParamPattern);
+        match self.get_memo(&key, Self::function_param_pattern) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // PatternNoTopAlt
+        let pattern_no_top_alt = Rc::new(self.pattern_no_top_alt()?);
+
+        // `:`
+        if !self.is_token(Token::Colon) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        // Type
+        let type_expression = Rc::new(self.type_expression()?);
+
+        Ok(ASTNode::new(ASTNodeKind::FunctionParam {
+            outer_attribute: vec![],
+            pattern_no_top_alt,
+            type_expression,
+        }))
+    }
+
+    // FunctionReturnType ::= `->` Type
+    // ASTには型だけを残す
+    fn function_return_type(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::FunctionReturnType);
+        match self.get_memo(&key, Self::function_return_type) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `->`
+        if !matches!(self.lexer.peek_glue(), Token::RightAllow) {
+            self.expected("`->`");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next_glue();
+
+        // Type
+        let Ok(type_expression) = self.type_expression() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        Ok(type_expression)
+    }
+
+    //
+    // Type Aliases
+    //
+
+    // TypeAlias ::= `type` Identifier `=` Type `;`
+    // TODO GenericParams、TypeParamBounds、WhereClause
+    fn type_alias(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::TypeAlias);
+        match self.get_memo(&key, Self::type_alias) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `type`
+        if !self.is_token(Token::Keyword(Keyword::Type)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // Identifier
+        let Token::Identifier(identifier) = self.lexer.peek() else {
+            self.expected("identifier");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        };
+        self.lexer.next();
+
+        // `=`
+        if !self.is_token(Token::Equal) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // Type
+        let type_expression = Rc::new(self.type_expression()?);
+
+        // `;`
+        if !self.is_token(Token::Semicolon) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::TypeAlias {
+            outer_attribute: vec![],
+            identifier,
+            type_expression,
+        }))
+    }
+
+    //
+    // Structs
+    //
+
+    // Struct ::= `struct` Identifier ( `{` StructFields? `}` | `;` )
+    // TODO TupleStruct、GenericParams、WhereClause
+    fn struct_item(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Struct);
+        match self.get_memo(&key, Self::struct_item) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `struct`
+        if !self.is_token(Token::Keyword(Keyword::Struct)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // Identifier
+        let Token::Identifier(identifier) = self.lexer.peek() else {
+            self.expected("identifier");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        };
+        self.lexer.next();
+
+        // `;`
+        if matches!(self.lexer.peek(), Token::Semicolon) {
+            self.lexer.next();
+            return Ok(ASTNode::new(ASTNodeKind::Struct {
+                outer_attribute: vec![],
+                identifier,
+                struct_fields: vec![],
+            }));
+        }
+
+        // `{`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brace)) {
+            self.expected("`;`");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_brace = self.next_opener();
+
+        // StructFields?
+        let struct_fields = self.struct_fields().unwrap_or_default();
+
+        // `}`
+        if !self.is_closing_token(Token::RightParenthesis(RightParenthesis::Brace), left_brace) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::Struct {
+            outer_attribute: vec![],
+            identifier,
+            struct_fields,
+        }))
+    }
+
+    // StructFields ::= StructField (`,` StructField)* `,`?
+    fn struct_fields(&mut self) -> Result<Vec<ASTNode>, Error> {
+        // StructField
+        let mut struct_fields = vec![self.struct_field()?];
+
+        // (`,` StructField)* `,`?
+        while let Token::Comma = self.lexer.peek() {
+            self.lexer.next();
+            let Ok(field) = self.struct_field() else {
+                break;
+            };
+            struct_fields.push(field);
+        }
+
+        Ok(struct_fields)
+    }
+
+    // StructField ::= OuterAttribute* Visibility? Identifier `:` Type
+    fn struct_field(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::StructField);
+        match self.get_memo(&key, Self::struct_field) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // OuterAttribute*
+        let mut outer_attribute = Vec::<ASTNode>::new();
+        while let Ok(expr) = self.outer_attribute() {
+            outer_attribute.push(expr);
+        }
+
+        // Visibility?
+        // TODO ASTに残す
+        let _ = self.visibility();
+
+        // Identifier
+        let Token::Identifier(identifier) = self.lexer.peek() else {
+            self.expected("identifier");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        };
+        self.lexer.next();
+
+        // `:`
+        if !self.is_token(Token::Colon) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // Type
+        let type_expression = Rc::new(self.type_expression()?);
+
+        Ok(ASTNode::new(ASTNodeKind::StructField {
+            outer_attribute,
+            identifier,
+            type_expression,
+        }))
+    }
+
+    //
+    // Implementations
+    //
+
+    // Implementation ::= InherentImpl | TraitImpl
+    // InherentImpl   ::= `impl` Type `{` AssociatedItem* `}`
+    // TraitImpl      ::= `impl` TypePath `for` Type `{` AssociatedItem* `}`
+    // TODO GenericParams、WhereClause、InnerAttribute
+    fn implementation(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Implementation);
+        match self.get_memo(&key, Self::implementation) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `impl`
+        if !self.is_token(Token::Keyword(Keyword::Impl)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // Type
+        let mut type_expression = Rc::new(self.type_expression()?);
+
+        // (`for` Type)?
+        // `for`があれば先に読んだ型はトレイト
+        let mut trait_path = None;
+        if matches!(self.lexer.peek(), Token::Keyword(Keyword::For)) {
+            self.lexer.next();
+            trait_path = Some(type_expression);
+            type_expression = Rc::new(self.type_expression()?);
+        }
+
+        // `{`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brace)) {
+            self.expected("`for`");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_brace = self.next_opener();
+
+        // AssociatedItem*
+        let mut associated_items = vec![];
+        while let Ok(item) = self.associated_item() {
+            associated_items.push(item);
+        }
+
+        // `}`
+        if !self.is_closing_token(Token::RightParenthesis(RightParenthesis::Brace), left_brace) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::Implementation {
+            outer_attribute: vec![],
+            trait_path,
+            type_expression,
+            associated_items,
+        }))
+    }
+
+    // AssociatedItem ::= OuterAttribute* ( TypeAlias | Visibility? Function )
+    // TODO MacroInvocationSemi、ConstantItem
+    fn associated_item(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::AssociatedItem);
+        match self.get_memo(&key, Self::associated_item) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // OuterAttribute*
+        let mut outer_attribute = Vec::<ASTNode>::new();
+        while let Ok(expr) = self.outer_attribute() {
+            outer_attribute.push(expr);
+        }
+
+        // TypeAlias | Visibility? Function
+        let Ok(mut node) = self.type_alias().or_else(|_| {
+            let _ = self.visibility();
+            self.function()
+        }) else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        set_outer_attribute(&mut node, outer_attribute);
+
+        Ok(self.rule_span(node, &key))
+    }
+
+    //
+    // Type
+    //
+
+    // Type ::= TypeNoBounds | ImplTraitType | TraitObjectType
+    // TODO TypePath以外の型
+    fn type_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Type);
+        match self.get_memo(&key, Self::type_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let Ok(node) = self.type_path() else {
+            self.expected("type");
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        Ok(node)
+    }
+
+    // TypePath ::= `::`? TypePathSegment (`::` TypePathSegment)*
+    // TypePathSegment ::= PathIdentSegment
+    // TODO GenericArgs
+    fn type_path(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::TypePath);
+        match self.get_memo(&key, Self::type_path) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `::`?
+        if matches!(self.lexer.peek_glue(), Token::PathSeparater) {
+            self.lexer.next_glue();
+        }
+
+        // TypePathSegment
+        let mut path_ident_segments = vec![self.path_ident_segment()?];
+
+        // (`::` TypePathSegment)*
+        while matches!(self.lexer.peek_glue(), Token::PathSeparater) {
+            let position = self.lexer.get_token_position();
+            self.lexer.next_glue();
+
+            let Ok(segment) = self.path_ident_segment() else {
+                self.backtrack(position);
+                break;
+            };
+            path_ident_segments.push(segment);
+        }
+
+        Ok(ASTNode::new(ASTNodeKind::TypePath {
+            path_ident_segments,
+        }))
+    }
+
+    //
+    // Expressions
+    //
+
+    // Expression ::= ExpressionWithoutBlock | ExpressionWithBlock
+    fn expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Expression);
+        match self.get_memo(&key, Self::expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+        let checkpoint = self.state.furthest.checkpoint();
+
+        if let Ok(expr) = self.expression_without_block() {
+            return Ok(expr);
+        }
+
+        if let Ok(expr) = self.expression_with_block() {
+            return Ok(expr);
+        }
+
+        self.state
+            .furthest
+            .summarize(checkpoint, key.position, "expression");
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    // ExpressionWithoutBlock ::= OuterAttribute* ( LiteralExpression | PathExpression | OperatorExpression | ... )
+    fn expression_without_block(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::ExpressionWithoutBlock);
+        match self.get_memo(&key, Self::expression_without_block) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+        let checkpoint = self.state.furthest.checkpoint();
+
+        // OuterAttribute*
+        let mut outer_attribute = Vec::<ASTNode>::new();
+        while let Ok(expr) = self.outer_attribute() {
+            outer_attribute.push(expr);
+        }
+
+        // OperatorExpression
+        if let Ok(expr) = self.operator_expression() {
+            return Ok(ASTNode::new(ASTNodeKind::ExpressionWithoutBlock {
+                outer_attribute,
+                expression: Rc::new(expr),
+            }));
+        }
+
+        // 演算子を含まない式
+        if let Ok(operand) = self.operand() {
+            if let ASTNodeKind::ExpressionWithoutBlock { expression, .. } = operand.node_kind {
+                return Ok(ASTNode::new(ASTNodeKind::ExpressionWithoutBlock {
+                    outer_attribute,
+                    expression,
+                }));
+            }
+        }
+
+        self.state
+            .furthest
+            .summarize(checkpoint, key.position, "expression");
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    // 演算子式の被演算子
+    // Expressionから演算子式を除いたもの
+    // CallExpressionなどの左再帰の起点になる
+    fn operand(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Operand);
+        match self.get_memo(&key, Self::operand) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+        let checkpoint = self.state.furthest.checkpoint();
+
+        // ExpressionWithoutBlock
+        // 左再帰する規則を先に試す
+        let rules: [Rule; 12] = [
+            Self::call_expression,
+            Self::method_call_expression,
+            Self::field_expression,
+            Self::await_expression,
+            Self::index_expression,
+            Self::literal_expression,
+            Self::macro_invocation,
+            Self::path_expression,
+            Self::grouped_expression,
+            Self::struct_expression,
+            Self::return_expression,
+            Self::async_block_expression,
+        ];
+        for rule in rules {
+            if let Ok(expr) = rule(self) {
+                return Ok(ASTNode::new(ASTNodeKind::ExpressionWithoutBlock {
+                    outer_attribute: vec![],
+                    expression: Rc::new(expr),
+                }));
+            }
+        }
+
+        // ExpressionWithBlock
+        if let Ok(expr) = self.expression_with_block() {
+            return Ok(expr);
+        }
+
+        self.state
+            .furthest
+            .summarize(checkpoint, key.position, "expression");
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    fn literal_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::LiteralExpression);
+        match self.get_memo(&key, Self::literal_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let literal = match self.lexer.next() {
+            Token::Literal(literal) => literal,
+            Token::Keyword(Keyword::True) => Literal::new(LiteralKind::Bool(true), ""),
+            Token::Keyword(Keyword::False) => Literal::new(LiteralKind::Bool(false), ""),
+            _ => return self.error(SyntaxError::ExpectedToken, &key),
+        };
+
+        Ok(ASTNode::new(ASTNodeKind::Literal { literal }))
+    }
+
+    // PathExpression ::= PathInExpression | QualifiedPathInExpression
+    fn path_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::PathExpression);
+        match self.get_memo(&key, Self::path_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // PathInExpression
+        if let Ok(expr) = self.path_in_expression() {
+            return Ok(expr);
+        }
+
+        // QualifiedPathInExpression
+        if let Ok(expr) = self.qualified_path_in_expression() {
+            return Ok(expr);
+        }
+
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    // PathInExpression ::= `::`? PathExprSegment (`::` PathExprSegment)*
+    fn path_in_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::PathInExpression);
+        match self.get_memo(&key, Self::path_in_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        //  `::`?
+        if matches!(self.lexer.peek_glue(), Token::PathSeparater) {
+            self.lexer.next_glue();
+        }
+
+        // PathExprSegment
+        let path_expr_segment = Rc::new(self.path_expr_segment()?);
+
+        // (`::` PathExprSegment)*
+        let mut repeat_path_expr_segment = Vec::<ASTNode>::new();
+        loop {
+            // `::`
+            if !matches!(self.lexer.peek_glue(), Token::PathSeparater) {
+                break;
+            }
+            self.lexer.next_glue();
+
+            // PathExprSegment
+            let Ok(expr) = self.path_expr_segment() else {
+                break;
+            };
+
+            repeat_path_expr_segment.push(expr);
+        }
+
+        Ok(ASTNode::new(ASTNodeKind::PathInExpression {
+            path_expr_segment,
+            repeat_path_expr_segment,
+        }))
+    }
+
+    // PathExprSegment ::= PathIdentSegment (`::` GenericArgs)?
+    fn path_expr_segment(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::PathExprSegment);
+        match self.get_memo(&key, Self::path_expr_segment) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // PathIdentSegment
+        let path_ident_segment = Rc::new(self.path_ident_segment()?);
+
+        // TODO (`::` GenericArgs)?
+
+        Ok(ASTNode::new(ASTNodeKind::PathExprSegment {
+            path_ident_segment,
+            generic_args: None,
+        }))
+    }
+
+    // PathIdentSegment   ::= Identifier | `super` | `self` | `Self` | `crate` | `$crate`
+    fn path_ident_segment(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::PathIdentSegment);
+        match self.get_memo(&key, Self::path_ident_segment) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        match self.lexer.peek() {
+            Token::Identifier(_)
+            | Token::Keyword(
+                Keyword::Super | Keyword::SelfValue | Keyword::SelfType | Keyword::Crate,
+            ) => Ok(self.make_factor_and_next()),
+
+            _ => self.error(SyntaxError::NotMatch, &key),
+        }
+    }
+
+    // QualifiedPathInExpression ::= QualifiedPathType (`::` PathExprSegment)+
+    fn qualified_path_in_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::QualifiedPathInExpression);
+        match self.get_memo(&key, Self::qualified_path_in_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // TODO
+
+        // QualifiedPathType
+        self.qualified_path_type()?;
+
+        // (`::` PathExprSegment)+
+
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    // QualifiedPathType ::= `<` Type (`as` TypePath)? `>`
+    fn qualified_path_type(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::QualifiedPathType);
+        match self.get_memo(&key, Self::qualified_path_type) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    // OperatorExpression
+    // 演算子を一つも含まない場合は失敗する
+    fn operator_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::OperatorExpression);
+        match self.get_memo(&key, Self::operator_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let expr = self.pratt_expression(0)?;
+        if !matches!(
+            expr.node_kind,
+            ASTNodeKind::UnaryOperator { .. } | ASTNodeKind::BinaryOperator { .. }
+        ) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+
+        Ok(expr)
+    }
+
+    // Pratt parsing
+    // 被演算子はoperandで解析するのでメモ化しない
+    fn pratt_expression(&mut self, min_bp: u16) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::PrattExpression);
+        if !self.check_limits() {
+            return self.error(SyntaxError::LimitExceeded, &key);
+        }
+
+        // 前置演算子
+        let mut lhs = if is_operator(&self.lexer.peek_glue()) {
+            let op = self.lexer.peek_glue();
+            let Some(((), right_bp)) = prefix_binding_power(&op) else {
+                self.expected("expression");
+                return self.error(SyntaxError::ExpectedToken, &key);
+            };
+            self.lexer.next_glue();
+
+            let rhs = self.nested(|parser| parser.pratt_expression(right_bp))?;
+            self.make_operator(&op, vec![rhs], &key)?
+        } else {
+            self.operand()?
+        };
+
+        loop {
+            let op = self.lexer.peek_glue();
+
+            if !is_operator(&op) {
+                break;
+            }
+
+            // 後置演算子
+            if let Some((left_bp, ())) = postfix_binding_power(&op) {
+                if left_bp < min_bp {
+                    break;
+                }
+                self.lexer.next_glue();
+
+                lhs = self.make_operator(&op, vec![lhs], &key)?;
+                continue;
+            }
+
+            // 中置演算子
+            if let Some((left_bp, right_bp)) = infix_binding_power(&op) {
+                if left_bp < min_bp {
+                    break;
+                }
+                self.lexer.next_glue();
+
+                let rhs = self.nested(|parser| parser.pratt_expression(right_bp))?;
+                lhs = self.make_operator(&op, vec![lhs, rhs], &key)?;
+                continue;
+            }
+
+            break;
+        }
+
+        Ok(lhs)
+    }
+
+    // GroupedExpression ::= `(` Expression `)`
+    // 括弧は木の形で表せるので中の式をそのまま返す
+    fn grouped_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::GroupedExpression);
+        match self.get_memo(&key, Self::grouped_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `(`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Parenthesis)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_parenthesis = self.next_opener();
+
+        // Expression
+        let expression = self.expression()?;
+
+        // `)`
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Parenthesis),
+            left_parenthesis,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(expression)
+    }
+
+    // StructExpression ::= StructExprStruct | StructExprTuple | StructExprUnit
+    fn struct_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::StructExpression);
+        match self.get_memo(&key, Self::struct_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        if let Ok(expr) = self.struct_expr_struct() {
+            return Ok(expr);
+        }
+
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    // StructExprStruct ::= PathInExpression `{` (StructExprFields | StructBase)? `}`
+    fn struct_expr_struct(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::StructExprStruct);
+        match self.get_memo(&key, Self::struct_expr_struct) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let path_in_expression = Rc::new(self.path_in_expression()?);
+
+        // `{`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brace)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_brace = self.next_opener();
+
+        // (StructExprFields | StructBase)?
+        let mut struct_expr_fields = vec![];
+        let mut struct_base = None;
+        if let Ok((fields, base)) = self.struct_expr_fileds() {
+            struct_expr_fields = fields;
+            struct_base = base.map(Rc::new);
+        } else if let Ok(expr) = self.struct_expr_filed() {
+            struct_expr_fields.push(expr);
+        }
+
+        // `}`
+        if !self.is_closing_token(Token::RightParenthesis(RightParenthesis::Brace), left_brace) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::StructExpression {
+            path_in_expression,
+            struct_expr_fields,
+            struct_base,
+        }))
+    }
+
+    // StructExprFields ::= StructExprField (, StructExprField)* (, StructBase | ,?)
+    // フィールドの列はASTのノードにならないのでメモ化しない
+    fn struct_expr_fileds(&mut self) -> Result<(Vec<ASTNode>, Option<ASTNode>), Error> {
+        // StructExprField
+        let mut struct_expr_fields = vec![self.struct_expr_filed()?];
+
+        // (, StructExprField)*
+        loop {
+            // `,`
+            if !matches!(self.lexer.peek(), Token::Comma) {
+                break;
+            }
+            self.lexer.next();
+
+            let Ok(expr) = self.struct_expr_filed() else {
+                break;
+            };
+
+            struct_expr_fields.push(expr);
+        }
+
+        // (, StructBase | ,?)
+        let mut struct_base = None;
+        if matches!(self.lexer.peek(), Token::Comma) {
+            self.lexer.next();
+            if let Ok(expr) = self.struct_base() {
+                struct_base = Some(expr);
+            }
+        }
+
+        Ok((struct_expr_fields, struct_base))
+    }
+
+    // StructExprField  ::= OuterAttribute* ( Identifier | (Identifier |TUPLE_INDEX) `:` Expression )
+    fn struct_expr_filed(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::StructExprField);
+        match self.get_memo(&key, Self::struct_expr_filed) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // OuterAttribute*
+        let mut outer_attribute = Vec::<ASTNode>::new();
+        while let Ok(expr) = self.outer_attribute() {
+            outer_attribute.push(expr);
+        }
+
+        // Identifierかタプルのインデックス
+        let token = self.lexer.peek();
+        let is_identifier = match &token {
+            Token::Identifier(_) => true,
+            Token::Literal(literal) => match literal.literal_kind {
+                LiteralKind::Integer => false,
+                _ => return self.error(SyntaxError::NotMatch, &key),
+            },
+            _ => return self.error(SyntaxError::ExpectedToken, &key),
+        };
+        self.lexer.next();
+        let identifier = token.to_string();
+
+        // `:`
+        if !self.is_token(Token::Colon) {
+            if !is_identifier {
+                return self.error(SyntaxError::NotMatch, &key);
+            }
+
+            return Ok(ASTNode::new(ASTNodeKind::StructExprField {
+                outer_attribute,
+                identifier,
+                expression: None,
+            }));
+        }
+        self.lexer.next();
+
+        // Expression
+        let expression = Some(Rc::new(self.expression()?));
+
+        Ok(ASTNode::new(ASTNodeKind::StructExprField {
+            outer_attribute,
+            identifier,
+            expression,
+        }))
+    }
+
+    // StructBase ::= `..` Expression
+    fn struct_base(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::StructBase);
+        match self.get_memo(&key, Self::struct_base) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `..`
+        if !self.is_token(Token::DotDot) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // Expression
+        self.expression()
+    }
+
+    // CallExpression ::= Expression `(` CallParams? `)`
+    fn call_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::CallExpression);
+        match self.get_memo(&key, Self::call_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Expression
+        // 演算子式は優先順位の関係で含めない
+        let expression = Rc::new(self.operand()?);
+
+        // `(`
+        if !matches!(
+            self.lexer.peek(),
+            Token::LeftParenthesis(LeftParenthesis::Parenthesis)
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_parenthesis = self.next_opener();
+
+        // CallParams?
+        let call_params = self.call_params().unwrap_or_default();
+
+        // `)`
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Parenthesis),
+            left_parenthesis,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::CallExpression {
+            expression,
+            call_params,
+        }))
+    }
+
+    // CallParams ::= Expression ( `,` Expression )* `,`?
+    fn call_params(&mut self) -> Result<Vec<ASTNode>, Error> {
+        let mut call_params = vec![self.expression()?];
+
+        // ( `,` Expression )*
+        loop {
+            if !matches!(self.lexer.peek(), Token::Comma) {
+                break;
+            }
+            self.lexer.next();
+
+            let Ok(expr) = self.expression() else {
+                break;
+            };
+
+            call_params.push(expr);
+        }
+
+        if matches!(self.lexer.peek(), Token::Comma) {
+            self.lexer.next();
+        }
+
+        Ok(call_params)
+    }
+
+    // MethodCallExpression ::= Expression `.` PathExprSegment `(` CallParams? `)`
+    fn method_call_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::MethodCallExpression);
+        match self.get_memo(&key, Self::method_call_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Expression
+        let expression = Rc::new(self.operand()?);
+
+        // `.`
+        if !matches!(self.lexer.peek(), Token::Dot) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // PathExprSegment
+        let path_expr_segment = Rc::new(self.path_expr_segment()?);
+
+        // `(`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Parenthesis)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_parenthesis = self.next_opener();
+
+        // CallParams?
+        let call_params = self.call_params().unwrap_or_default();
+
+        // `)`
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Parenthesis),
+            left_parenthesis,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::MethodCallExpression {
+            expression,
+            path_expr_segment,
+            call_params,
+        }))
+    }
+
+    // FieldExpression ::= Expression `.` Identifier
+    fn field_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::FieldExpression);
+        match self.get_memo(&key, Self::field_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Expression
+        let expression = Rc::new(self.operand()?);
+
+        // `.`
+        if !matches!(self.lexer.peek(), Token::Dot) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // Identifier
+        let Token::Identifier(identifier) = self.lexer.peek() else {
+            self.expected("identifier");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        };
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::FieldExpression {
+            expression,
+            identifier,
+        }))
+    }
+
+    // AwaitExpression ::= Expression `.` `await`
+    fn await_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::AwaitExpression);
+        match self.get_memo(&key, Self::await_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Expression
+        let expression = Rc::new(self.operand()?);
+
+        // `.`
+        if !matches!(self.lexer.peek(), Token::Dot) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // `await`
+        if !self.is_token(Token::Keyword(Keyword::Await)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::AwaitExpression { expression }))
+    }
+
+    // IndexExpression ::= Expression `[` Expression `]`
+    fn index_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::IndexExpression);
+        match self.get_memo(&key, Self::index_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Expression
+        let expression = Rc::new(self.operand()?);
+
+        // `[`
+        if !matches!(
+            self.lexer.peek(),
+            Token::LeftParenthesis(LeftParenthesis::Brackets)
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_brackets = self.next_opener();
+
+        // Expression
+        let Ok(index) = self.expression() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        let index = Rc::new(index);
+
+        // `]`
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Brackets),
+            left_brackets,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::IndexExpression {
+            expression,
+            index,
+        }))
+    }
+
+    // ReturnExpression ::= `return` Expression?
+    fn return_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::ReturnExpression);
+        match self.get_memo(&key, Self::return_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `return`
+        if !self.is_token(Token::Keyword(Keyword::Return)) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        // Expression?
+        let expression = self.expression().ok().map(Rc::new);
+
+        Ok(ASTNode::new(ASTNodeKind::ReturnExpression { expression }))
+    }
+
+    // ExpressionWithBlock ::= OuterAttribute*
+    //                        (
+    //                          BlockExpression | ConstBlockExpression | UnsafeBlockExpression | LoopExpression
+    //                        | IfExpression | IfLetExpression | MatchExpression
+    //                        )
+    fn expression_with_block(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::ExpressionWithBlock);
+        match self.get_memo(&key, Self::expression_with_block) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+        let checkpoint = self.state.furthest.checkpoint();
+
+        // OuterAttribute*
+        let mut outer_attribute = Vec::<ASTNode>::new();
+        while let Ok(expr) = self.outer_attribute() {
+            outer_attribute.push(expr);
+        }
+
+        // BlockExpression
+        if let Ok(expr) = self.block_expression() {
+            return Ok(wrap_outer_attribute(expr, outer_attribute));
+        }
+
+        // ConstBlockExpression
+        if let Ok(expr) = self.const_block_expression() {
+            return Ok(wrap_outer_attribute(expr, outer_attribute));
+        }
+
+        // UnsafeBlockExpression
+        if let Ok(expr) = self.unsafe_block_expression() {
+            return Ok(wrap_outer_attribute(expr, outer_attribute));
+        }
+
+        // IfExpression
+        if let Ok(expr) = self.if_expression() {
+            return Ok(wrap_outer_attribute(expr, outer_attribute));
+        }
+
+        // IfLetExpression
+        if let Ok(expr) = self.if_let_expression() {
+            return Ok(wrap_outer_attribute(expr, outer_attribute));
+        }
+
+        self.state
+            .furthest
+            .summarize(checkpoint, key.position, "expression");
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    // BlockExpression ::=  `{` InnerAttribute* Statements? `}`
+    fn block_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::BlockExpression);
+        match self.get_memo(&key, Self::block_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `{`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brace)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_brace = self.next_opener();
+
+        // InnerAttribute*
+        let mut inner_attribute = Vec::<ASTNode>::new();
+        while let Ok(expr) = self.inner_attribute() {
+            inner_attribute.push(expr);
+        }
+
+        // Statements?
+        let statements = self.statements().ok().map(Rc::new);
+
+        // `}`
+        if !self.is_closing_token(Token::RightParenthesis(RightParenthesis::Brace), left_brace) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::BlockExpression {
+            inner_attribute,
+            statements,
+        }))
+    }
+
+    // Statements ::= Statement+ | Statement+ ExpressionWithoutBlock | ExpressionWithoutBlock
+    fn statements(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Statements);
+        match self.get_memo(&key, Self::statements) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let mut statements = vec![];
+        let mut recovered = false;
+        loop {
+            // Statement
+            if let Ok(expr) = self.statement() {
+                statements.push(expr);
+                continue;
+            }
+
+            // ExpressionWithoutBlock
+            // ブロックの最後の式のみ
+            let position = self.lexer.get_token_position();
+            if let Ok(expr) = self.expression_without_block() {
+                if matches!(
+                    self.lexer.peek(),
+                    Token::RightParenthesis(RightParenthesis::Brace)
+                ) {
+                    statements.push(expr);
+                    break;
+                }
+                self.backtrack(position);
+            }
+
+            if matches!(
+                self.lexer.peek(),
+                Token::RightParenthesis(RightParenthesis::Brace) | Token::Eof
+            ) {
+                break;
+            }
+
+            // 文として解析できなければ次の文まで読み飛ばす
+            // 読み飛ばした部分はASTに残さない
+            self.skip_to_sync(true);
+            recovered = true;
+        }
+
+        if statements.is_empty() && !recovered {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+
+        Ok(ASTNode::new(ASTNodeKind::Statements { statements }))
+    }
+
+    // ConstBlockExpression ::= `const` BlockExpression
+    fn const_block_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::ConstBlockExpression);
+        match self.get_memo(&key, Self::const_block_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `const`
+        if !self.is_token(Token::Keyword(Keyword::Const)) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        // BlockExpression
+        let block_expression = Rc::new(self.block_expression()?);
+
+        Ok(ASTNode::new(ASTNodeKind::ConstBlockExpression {
+            block_expression,
+        }))
+    }
+
+    // UnsafeBlockExpression ::= `unsafe` BlockExpression
+    fn unsafe_block_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::UnsafeBlockExpression);
+        match self.get_memo(&key, Self::unsafe_block_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `unsafe`
+        if !self.is_token(Token::Keyword(Keyword::Unsafe)) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        // BlockExpression
+        let block_expression = Rc::new(self.block_expression()?);
+
+        Ok(ASTNode::new(ASTNodeKind::UnsafeBlockExpression {
+            block_expression,
+        }))
+    }
+
+    // AsyncBlockExpression ::= `async` `move`? BlockExpression
+    fn async_block_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::AsyncBlockExpression);
+        match self.get_memo(&key, Self::async_block_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `async`
+        if !self.is_token(Token::Keyword(Keyword::Async)) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        // `move`?
+        let mut move_keyword = false;
+        if matches!(self.lexer.peek(), Token::Keyword(Keyword::Move)) {
+            move_keyword = true;
+            self.lexer.next();
+        }
+
+        // BlockExpression
+        let block_expression = Rc::new(self.block_expression()?);
+
+        Ok(ASTNode::new(ASTNodeKind::AsyncBlockExpression {
+            move_keyword,
+            block_expression,
+        }))
+    }
+
+    // IfExpression ::= `if` Expression BlockExpression (`else` ( BlockExpression | IfExpression | IfLetExpression ) )?
+    fn if_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::IfExpression);
+        match self.get_memo(&key, Self::if_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `if`
+        if !self.is_token(Token::Keyword(Keyword::If)) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        // LetChain | Expression
+        let expression = match self.let_chain() {
+            Ok(expr) => Rc::new(expr),
+            Err(_) => Rc::new(self.expression()?),
+        };
+
+        // BlockExpression
+        let block_expression = Rc::new(self.block_expression()?);
+
+        // ( `else` ( BlockExpression | IfExpression | IfLetExpression ) )?
+        let Ok(else_expression) = self.else_expression() else {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        };
+
+        Ok(ASTNode::new(ASTNodeKind::IfExpression {
+            expression,
+            block_expression,
+            else_expression,
+        }))
+    }
+
+    // IfLetExpression ::= `if` `let` Pattern `=` Scrutinee BlockExpression
+    //                   ( else ( BlockExpression | IfExpression | IfLetExpression ) )?
+    fn if_let_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::IfLetExpression);
+        match self.get_memo(&key, Self::if_let_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `if`
+        if !self.is_token(Token::Keyword(Keyword::If)) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        // `let`
+        if !self.is_token(Token::Keyword(Keyword::Let)) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        // Pattern
+        let pattern = Rc::new(self.pattern()?);
+
+        // =
+        if !self.is_token(Token::Equal) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        self.lexer.next();
+
+        // Scrutinee
+        // `&&`は次のLetChainConditionとの区切りなので含めない
+        let scrutinee = Rc::new(self.condition_expression()?);
+
+        // BlockExpression
+        let block_expression = Rc::new(self.block_expression()?);
+
+        // ( else ( BlockExpression | IfExpression | IfLetExpression ) )?
+        let Ok(else_expression) = self.else_expression() else {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        };
+
+        Ok(ASTNode::new(ASTNodeKind::IfLetExpression {
+            pattern,
+            scrutinee,
+            block_expression,
+            else_expression,
+        }))
+    }
+
+    // LetChain ::= LetChainCondition ( `&&` LetChainCondition )*
+    // `let`を含まないものは普通の`&&`の式なので、LetChainとしては失敗する
+    fn let_chain(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::LetChain);
+        match self.get_memo(&key, Self::let_chain) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // LetChainCondition ( `&&` LetChainCondition )*
+        let mut let_chain_condition = vec![self.let_chain_condition()?];
+        while matches!(self.lexer.peek_glue(), Token::AndAnd) {
+            self.lexer.next_glue();
+            let_chain_condition.push(self.let_chain_condition()?);
+        }
+
+        // `if let`だけのものはIfLetExpression
+        let has_let = let_chain_condition
+            .iter()
+            .any(|expr| matches!(expr.node_kind, ASTNodeKind::LetChainCondition { .. }));
+        if !has_let || let_chain_condition.len() < 2 {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+
+        Ok(ASTNode::new(ASTNodeKind::LetChain {
+            let_chain_condition,
+        }))
+    }
+
+    // LetChainCondition ::= Expression | `let` Pattern `=` Scrutinee
+    fn let_chain_condition(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::LetChainCondition);
+        match self.get_memo(&key, Self::let_chain_condition) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Expression
+        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::Let)) {
+            return self.condition_expression();
+        }
+
+        // `let`
+        self.lexer.next();
+
+        // Pattern
+        let pattern = Rc::new(self.pattern()?);
+
+        // `=`
+        if !self.is_token(Token::Equal) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        // Scrutinee
+        let scrutinee = Rc::new(self.condition_expression()?);
+
+        Ok(ASTNode::new(ASTNodeKind::LetChainCondition {
+            pattern,
+            scrutinee,
+        }))
+    }
+
+    // `&&`や`||`、それより優先順位の低い演算子を含まない式
+    // LetChainの条件とif letのScrutineeに使う
+    fn condition_expression(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::ConditionExpression);
+        match self.get_memo(&key, Self::condition_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let Some((left_bp, _)) = infix_binding_power(&Token::AndAnd) else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        let Ok(expr) = self.nested(|parser| parser.pratt_expression(left_bp + 1)) else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        // 演算子式はexpressionと同じくExpressionWithoutBlockで包む
+        if !matches!(
+            expr.node_kind,
+            ASTNodeKind::UnaryOperator { .. } | ASTNodeKind::BinaryOperator { .. }
+        ) {
+            return Ok(expr);
+        }
+        Ok(ASTNode::new(ASTNodeKind::ExpressionWithoutBlock {
+            outer_attribute: vec![],
+            expression: Rc::new(expr),
+        }))
+    }
+
+    // ( `else` ( BlockExpression | IfExpression | IfLetExpression ) )?
+    // `else`の後に続かなければ失敗する
+    fn else_expression(&mut self) -> Result<Option<Rc<ASTNode>>, ()> {
+        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::Else)) {
+            return Ok(None);
+        }
+        self.lexer.next();
+
+        // ( BlockExpression | IfExpression | IfLetExpression )
+        if let Ok(expr) = self.block_expression() {
+            Ok(Some(Rc::new(expr)))
+        } else if let Ok(expr) = self.if_expression() {
+            Ok(Some(Rc::new(expr)))
+        } else if let Ok(expr) = self.if_let_expression() {
+            Ok(Some(Rc::new(expr)))
+        } else {
+            Err(())
+        }
+    }
+
+    //
+    // Statement
+    //
+
+    // Statement ::= `;` | Item | LetStatement | ExpressionStatement | MacroInvocationSemi
+    fn statement(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Statement);
+        match self.get_memo(&key, Self::statement) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // ;
+        if matches!(self.lexer.peek(), Token::Semicolon) {
+            self.lexer.next();
+            return Ok(ASTNode::new(ASTNodeKind::Statement { statement: None }));
+        }
+
+        // Item | LetStatement | ExpressionStatement
+        let rules: [Rule; 3] = [Self::item, Self::let_statement, Self::expression_statement];
+        for rule in rules {
+            if let Ok(expr) = rule(self) {
+                return Ok(ASTNode::new(ASTNodeKind::Statement {
+                    statement: Some(Rc::new(expr)),
+                }));
+            }
+        }
+
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    // LetStatement ::= OuterAttribute* (`ur` | `sr` | `nr` | `let`)
+    //                  PatternNoTopAlt ( `:` Type )?
+    //                  (`=` Expression ( `else` BlockExpression)? )? `;`
+    fn let_statement(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::LetStatement);
+        match self.get_memo(&key, Self::let_statement) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let mut type_expression = None;
+        let mut expression = None;
+        let mut block_expression = None;
+
+        // OuterAttribute*
+        let mut outer_attribute = Vec::<ASTNode>::new();
+        while let Ok(expr) = self.outer_attribute() {
+            outer_attribute.push(expr);
+        }
+
+        // (`ur` | `sr` | `nr` | `let`)
+        let rarity = match self.lexer.peek() {
+            Token::Keyword(Keyword::Let) => Rarity::Let,
+            Token::Keyword(Keyword::Ur) => Rarity::Ur,
+            Token::Keyword(Keyword::Sr) => Rarity::Sr,
+            Token::Keyword(Keyword::Nr) => Rarity::Nr,
+            _ => {
+                for keyword in [Keyword::Let, Keyword::Ur, Keyword::Sr, Keyword::Nr] {
+                    self.expected(&format!("`{}`", keyword.as_str()));
+                }
+                return self.error(SyntaxError::ExpectedToken, &key);
+            }
+        };
+        self.lexer.next();
+
+        // PatternNoTopAlt
+        let Ok(pattern_no_top_alt) = self.pattern_no_top_alt() else {
+            self.expected("pattern");
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        let pattern_no_top_alt = Rc::new(pattern_no_top_alt);
+
+        // ( `:` Type )?
+        if matches!(self.lexer.peek(), Token::Colon) {
+            self.lexer.next();
+
+            // Type
+            type_expression = Some(Rc::new(self.type_expression()?));
+        }
+
+        //  (`=` Expression ( `else` BlockExpression)? )? `;`
+        if matches!(self.lexer.peek(), Token::Equal) {
+            // `=`
+            self.lexer.next();
+
+            // Expression
+            expression = Some(Rc::new(self.expression()?));
+
+            // `else`
+            if matches!(self.lexer.peek(), Token::Keyword(Keyword::Else)) {
+                self.lexer.next();
+
+                // BlockExpression
+                block_expression = Some(Rc::new(self.block_expression()?))
+            }
+        }
+
+        // ;
+        if !self.is_token(Token::Semicolon) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        self.lexer.next();
+
+        Ok(ASTNode::new(ASTNodeKind::LetStatement {
+            outer_attribute,
+            rarity,
+            pattern_no_top_alt,
+            type_expression,
+            expression,
+            block_expression,
+        }))
+    }
+
+    // ExpressionStatement ::= ExpressionWithoutBlock `;` | ExpressionWithBlock `;`?
+    fn expression_statement(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::ExpressionStatement);
+        match self.get_memo(&key, Self::expression_statement) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // ExpressionWithoutBlock `;`
+        if let Ok(expr) = self.expression_without_block() {
+            if self.is_token(Token::Semicolon) {
+                self.lexer.next();
+                return Ok(expr);
+            }
+            self.backtrack(key.position);
+        }
+
+        // ExpressionWithBlock `;`?
+        if let Ok(expr) = self.expression_with_block() {
+            return Ok(expr);
+        }
+
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    //
+    // 以下Pattern
+    //
+
+    // Pattern ::= `|`? PatternNoTopAlt ( `|` PatternNoTopAlt )*
+    fn pattern(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::Pattern);
+        match self.get_memo(&key, Self::pattern) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `|`?
+        if let Token::Or = self.lexer.peek() {
+            self.lexer.next();
+        }
+
+        // PatternNoTopAlt ( `|` PatternNoTopAlt )*
+        let mut pattern_no_top_alt = vec![self.pattern_no_top_alt()?];
+        while matches!(self.lexer.peek_glue(), Token::Or) {
+            self.lexer.next();
+            pattern_no_top_alt.push(self.pattern_no_top_alt()?);
+        }
+
+        Ok(ASTNode::new(ASTNodeKind::Pattern { pattern_no_top_alt }))
+    }
+
+    // PatternNoTopAlt ::= PatternWithoutRange | RangePattern
+    fn pattern_no_top_alt(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::PatternNoTopAlt);
+        match self.get_memo(&key, Self::pattern_no_top_alt) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        if let Ok(expr) = self.pattern_without_range() {
+            return Ok(expr);
+        }
+
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    // PatternWithoutRange ::= LiteralPattern | IdentifierPattern | WildcardPattern | RestPattern |
+    //                         ReferencePattern | StructPattern | TupleStructPattern | TuplePattern | GroupedPattern |
+    //                         SlicePattern | PathPattern | MacroInvocation
+    fn pattern_without_range(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::PatternWithoutRange);
+        match self.get_memo(&key, Self::pattern_without_range) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let rules: [Rule; 5] = [
+            Self::literal_pattern,
+            Self::identifier_pattern,
+            Self::wildcard_pattern,
+            Self::rest_pattern,
+            Self::reference_pattern,
+        ];
+        for rule in rules {
+            if let Ok(expr) = rule(self) {
+                return Ok(expr);
+            }
+        }
+
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    // LiteralPattern ::= `true` | `false` | CHAR_LITERAL | ... | `-`? INTEGER_LITERAL | `-`? FLOAT_LITERAL
+    fn literal_pattern(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::LiteralPattern);
+        match self.get_memo(&key, Self::literal_pattern) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let literal = match self.lexer.next() {
+            Token::Keyword(Keyword::True) => Literal::new(LiteralKind::Bool(true), ""),
+            Token::Keyword(Keyword::False) => Literal::new(LiteralKind::Bool(false), ""),
+            Token::Keyword(_) => return self.error(SyntaxError::ExpectedToken, &key),
+            Token::Literal(literal) => literal,
+            // TODO 符号をASTに残す
+            Token::Minus => {
+                let Token::Literal(literal) = self.lexer.next() else {
+                    return self.error(SyntaxError::NotMatch, &key);
+                };
+
+                if !matches!(
+                    literal.literal_kind,
+                    LiteralKind::Integer | LiteralKind::Float
+                ) {
+                    return self.error(SyntaxError::NotMatch, &key);
+                }
+                literal
+            }
+
+            _ => return self.error(SyntaxError::NotMatch, &key),
+        };
+
+        Ok(ASTNode::new(ASTNodeKind::LiteralPattern { literal }))
+    }
+
+    // IdentifierPattern ::= `ref`? `mut`? Identifier (`@` PatternNoTopAlt )?
+    fn identifier_pattern(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::IdentifierPattern);
+        match self.get_memo(&key, Self::identifier_pattern) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let mut pattern_no_top_alt = None;
+
+        // `ref`?
+        let ref_keyword = matches!(self.lexer.peek(), Token::Keyword(Keyword::Ref));
+        if ref_keyword {
+            self.lexer.next();
+        }
+
+        // `mut`?
+        let mut_keyword = matches!(self.lexer.peek(), Token::Keyword(Keyword::Mut));
+        if mut_keyword {
+            self.lexer.next();
+        }
+
+        // Identifier
+        let Token::Identifier(identifier) = self.lexer.peek() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        self.lexer.next();
+
+        // (`@` PatternNoTopAlt )?
+        if matches!(self.lexer.peek(), Token::At) {
+            self.lexer.next();
+            pattern_no_top_alt = Some(Rc::new(self.pattern_no_top_alt()?));
+        }
+
+        Ok(ASTNode::new(ASTNodeKind::IdentifierPattern {
+            ref_keyword,
+            mut_keyword,
+            identifier,
+            pattern_no_top_alt,
+        }))
+    }
+
+    // WildcardPattern ::= `_`
+    fn wildcard_pattern(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::WildcardPattern);
+        match self.get_memo(&key, Self::wildcard_pattern) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        match self.lexer.peek() {
+            Token::Underscore => Ok(ASTNode::new(ASTNodeKind::WildcardPattern {
+                wildcard: Rc::new(self.make_factor_and_next()),
+            })),
+            _ => self.error(SyntaxError::ExpectedToken, &key),
+        }
+    }
+
+    // RestPattern ::= `..`
+    fn rest_pattern(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::RestPattern);
+        match self.get_memo(&key, Self::rest_pattern) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        match self.lexer.peek_glue() {
+            Token::DotDot => Ok(ASTNode::new(ASTNodeKind::RestPattern {
+                rest: Rc::new(self.make_glued_factor_and_next()),
+            })),
+            _ => self.error(SyntaxError::ExpectedToken, &key),
+        }
+    }
+
+    // ReferencePattern ::= (`&`|`&&`) mut? PatternWithoutRange
+    fn reference_pattern(&mut self) -> Result<ASTNode, Error> {
+        let key = self.make_key(ParseRule::ReferencePattern);
+        match self.get_memo(&key, Self::reference_pattern) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // (`&`|`&&`)
+        if !matches!(self.lexer.peek_glue(), Token::And | Token::AndAnd) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+
+        // mut?
+        if !self.is_token(Token::Keyword(Keyword::Mut)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+
+        // PatternWithoutRange
+        self.pattern_without_range()
+    }
+
+    //
+    // Diagnostics
+    //
+
+    // 閉じ括弧用
+    // 対応する開き括弧の位置も記録する
+    fn is_closing_token(&mut self, token: Token, opener_position: usize) -> bool {
+        if self.lexer.peek() == token {
+            return true;
+        }
+
+        let opener = self.lexer.get_token_at(opener_position);
+        let position = self.lexer.get_sorce_position_at(opener_position);
+        self.expected_closing(token, opener, position);
+        false
+    }
+
+    //
+    //
+    //
+
+    // 開き括弧を読み進め、そのトークン位置を返す
+    fn next_opener(&mut self) -> usize {
+        let position = self.lexer.get_token_position();
+        self.lexer.next();
+
+        position
+    }
+
+    // 子の規則が作ったノードの範囲を、属性や`;`などを含めたこの規則の範囲にする
+    fn rule_span(&self, mut node: ASTNode, key: &ParseMemoKey) -> ASTNode {
+        node.span = self
+            .lexer
+            .span(key.position, self.lexer.get_token_position());
+        node
+    }
+
+    fn make_factor_and_next(&mut self) -> ASTNode {
+        let start = self.lexer.get_token_position();
+        ASTNode::new(ASTNodeKind::Factor {
+            token: self.lexer.next(),
+        })
+        .with_span(self.lexer.span(start, start + 1))
+    }
+
+    fn make_glued_factor_and_next(&mut self) -> ASTNode {
+        let start = self.lexer.get_token_position();
+        let token = self.lexer.next_glue();
+        let end = self.lexer.get_token_position();
+        ASTNode::new(ASTNodeKind::Factor { token }).with_span(self.lexer.span(start, end))
+    }
+
+    fn make_operator(
+        &mut self,
+        op: &Token,
+        operands: Vec<ASTNode>,
+        key: &ParseMemoKey,
+    ) -> Result<ASTNode, Error> {
+        // 演算子式は規則の先頭から始まる
+        let end = self.lexer.get_token_position();
+        match ASTNode::operator(op, operands) {
+            Some(node) => Ok(node.with_span(self.lexer.span(key.position, end))),
+            None => self.error(SyntaxError::NotMatch, key),
+        }
+    }
+}
+
+// `{`で囲んだマクロ呼び出しか
+fn is_brace_token_tree(node: &ASTNode) -> bool {
+    let ASTNodeKind::MacroInvocation {
+        delim_token_tree, ..
+    } = &node.node_kind
+    else {
+        return false;
+    };
+
+    matches!(
+        delim_token_tree.node_kind,
+        ASTNodeKind::DelimTokenTree {
+            delimiter: LeftParenthesis::Brace,
+            ..
+        }
+    )
+}
+
+// OuterAttributeはアイテム自身に持たせる
+fn set_outer_attribute(node: &mut ASTNode, outer_attribute: Vec<ASTNode>) {
+    if let ASTNodeKind::Function {
+        outer_attribute: attribute,
+        ..
+    }
+    | ASTNodeKind::TypeAlias {
+        outer_attribute: attribute,
+        ..
+    }
+    | ASTNodeKind::Struct {
+        outer_attribute: attribute,
+        ..
+    }
+    | ASTNodeKind::Implementation {
+        outer_attribute: attribute,
+        ..
+    } = &mut node.node_kind
+    {
+        *attribute = outer_attribute;
+    }
+}
+
+// OuterAttributeがあればExpressionWithBlockで包む
+fn wrap_outer_attribute(expression: ASTNode, outer_attribute: Vec<ASTNode>) -> ASTNode {
+    if outer_attribute.is_empty() {
+        return expression;
+    }
+
+    ASTNode::new(ASTNodeKind::ExpressionWithBlock {
+        outer_attribute,
+        expression_with_block: Rc::new(expression),
+    })
 }
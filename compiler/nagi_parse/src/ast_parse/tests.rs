@@ -0,0 +1,1544 @@
+#[cfg(test)]
+mod test {
+    use crate::ast_parse::ast_parser::ASTParser;
+    use crate::cst_parse::cst_parser::CSTParser;
+    use crate::lexer;
+    use crate::SyntaxTree;
+    use nagi_checker::{SymbolPattern, SymbolTreeNode};
+    use nagi_command_option::{
+        CancellationToken, CompileCommandOption, GraphFormat, ParseFrontEnd, ParseLimits,
+        TreeFormat,
+    };
+    use nagi_errors::{
+        Error, ErrorKind, MacroError, SemanticError, SourcePosition, SyntaxError, TreeFileError,
+    };
+    use nagi_lexer::lexer::Lexer;
+    use nagi_syntax_tree::arena::{self, Arena};
+    use nagi_syntax_tree::ast::visit::{
+        walk_children, walk_children_fold, Folder, Visitor, VisitorMut,
+    };
+    use nagi_syntax_tree::ast::{ASTNode, ASTNodeKind};
+    use nagi_syntax_tree::cst::{self, CSTNode, CSTNodeKind};
+    use nagi_syntax_tree::diff::{diff_trees, DiffOptions, EditKind};
+    use nagi_syntax_tree::graph::tree_graph;
+    use nagi_syntax_tree::pretty::{print_ast, print_cst};
+    use nagi_syntax_tree::query::Query;
+    use nagi_syntax_tree::serialize::{decode_tree, encode_tree, source_hash};
+    use nagi_syntax_tree::span::Span;
+    use nagi_syntax_tree::token::{BinaryOperator, Token};
+
+    // nagi_parse::testsとベンチで使っているプログラム、それ以外の構文、構文エラーを含むもの
+    const CORPUS: &[&str] = &[
+        "",
+        "fn a() {}",
+        "fn a();",
+        "fn a<>() {}",
+        "fn a() {} fn b() {} fn c() { let x = 1; }",
+        "fn add() { let hoge = 100 * 10; sr a = 1; {ur b = 2;} {nr c = 3;}  } fn sub() { let a = 100; }",
+        "fn f() { 1 + 2 }",
+        "fn f() { 3 - 1 }",
+        "fn f() { 6 / 2 }",
+        "fn f() { 5 * 6 }",
+        "fn f() { 12 * 23 - 32 / 16 + 90 }",
+        "fn f() { f() }",
+        "fn f() { f(1, a) }",
+        "fn f() { f(1,) }",
+        "fn f() { f(1)(2) }",
+        "fn f() { f(1 + 2) }",
+        "fn f() { a.b() }",
+        "fn f() { a.b(1).c(2, 3) }",
+        "fn f() { f().b() }",
+        "fn f() { a.b }",
+        "fn f() { a.b.c }",
+        "fn f() { a.b(1).c }",
+        "fn f() { (a).b }",
+        "fn f() { a[1] }",
+        "fn f() { a[1][b.c] }",
+        "fn f() { a.b[2](1) }",
+        "fn f() { a + b(1) }",
+        "fn f() { f(1) * g(2) + 3 }",
+        "fn f() { -a.b }",
+        "fn f() { 1 + 2 * 3 - 4 }",
+        "fn f() { (a + b).c }",
+        "fn f() { !a }",
+        "fn f() { -(1 + 2) * -3 }",
+        "fn f() { a % b | d & e }",
+        "fn f() { let a = 1 < 2; let b = 3 > 4; }",
+        "fn f() { a = b; }",
+        "fn f() { a::b::c(1); ::d; }",
+        "fn f() { self.a; super::b; crate::c; }",
+        "fn f() { let a = true; let b = false; let c = 1.5; }",
+        "fn f() { return; }",
+        "fn f() { return 1 + 2; }",
+        "fn f() { return }",
+        "fn f() { if a { b; } }",
+        "fn f() { if a { b } else { c } }",
+        "fn f() { if a { 1 } else if b { 2 } else { 3 } }",
+        "fn f() { let x = if a { 1 } else { 2 }; }",
+        "fn f() { if let a = b { c } }",
+        "fn f() { if let a | b = c { d } else if let e = f { g } else { h } }",
+        "fn f() { if let a = b && c == d && let e = a { e } else { f } }",
+        "fn f() { if a && let b = c { b } }",
+        "fn f() { if a && b || c { 1 } let d = e || f; }",
+        "fn f() { if let a = b || c { 1 } }",
+        "fn f() { if a || let b = c { 1 } }",
+        "fn f() { let a = b else { return; }; a }",
+        "fn f() { let a = b else { if c { return; } else { return } }; }",
+        "fn f() { let a = b else { unsafe { return 1; } }; }",
+        "fn f() { let a = b else { }; }",
+        "fn f() { let a = b else { if c { return; } }; }",
+        "fn f() { { { 1 } } }",
+        "fn f() { ; ; }",
+        "fn f() { let x = A { a, b: 1, 2: c }; }",
+        "fn f() { let x = A { a }; let y = A {}; }",
+        "fn f() { let x = A { a: 1, }; }",
+        "fn f() { let x = A { a: 1, ..b }; }",
+        "fn f() { let _ = 1; let ref mut a @ b = 2; }",
+        "fn f() { let true = a; let 1 = b; let -1 = c; let - 1.5 = d; }",
+        "fn f() { let a: = 1; }",
+        "fn f() { let a = 1 else { b }; }",
+        "fn f() { fn g() {} g() }",
+        "fn f() { fn g() { fn h() {} } fn h() {} }",
+        "fn a() {} fn a() {}",
+        "fn f() { fn g() {} fn g() {} }",
+        "fn f() { { fn g() {} } { fn g() {} } }",
+        "fn f() { if a { fn g() {} fn g() {} } }",
+        "fn a() {} fn b() { fn a() {} }",
+        "fn a() { fn b() {} } fn a() {}",
+        "fn a() { let x = 1; } = = ; fn b() { let y = 2; }",
+        "fn a() { let x = 1; let = ; let y = 2; } fn b() { }",
+        "fn a( { { let x = 1; } } fn b() { let y = 2; }",
+        "fn a() { let = 1; } ) fn b() { ur = 2; }",
+        "fn a() { let x = 1 let y = 2; }",
+        "fn 3() {}",
+        "fn a() { sr b = (1 + 2; }",
+        "fn a() { let = 3; }",
+        "fn a() {\n    let x = 1;\n",
+        "fn a() { b(1)(2); }",
+        "fn a(self) {}",
+        "fn a(x: i32) {}",
+        "pub fn a() {}",
+        "const fn a() {} async fn b() {} unsafe fn c() {} extern fn d() {}",
+        "unsafe extern \"C\" fn a() {} safe fn b() {} extern \"C\" fn c() {}",
+        "fn f() { unsafe { 1 } }",
+        "fn f() { const { 1 } }",
+        "fn f() { let a = async { 1 }; let b = async move { 2 }; a.await; }",
+        "fn f() { a.b().await.c; async {}.await(1); }",
+        "fn f() { const fn g() {} unsafe fn h() {} async fn i() {} }",
+        "unsafe fn g() {} fn f() { unsafe { g() } }",
+        "unsafe fn g() {} fn f() { g() }",
+        "unsafe fn g() { g(); fn h() { g() } }",
+        "unsafe fn g() {} fn f() { unsafe { async { g() }; fn h() { g() } } }",
+        "unsafe fn g() {} fn f() { fn g() {} g() }",
+        "fn f() { unsafe 1; const; async move; a.; }",
+        "#[a] fn b() {}",
+        "#![a] fn b() {}",
+        "#[test] fn a() {}",
+        "#![no_std] #![a::b] fn a() {}",
+        "#[inline(always)] #[cfg(all(unix, target = 1))] fn a() {}",
+        "#[a = 1] #[b::c = true] #[d = 1 + 2] fn e() {}",
+        "#[unsafe(no_mangle)] #[unsafe(export_name = 1)] fn a() {}",
+        "#[::a::self::b [1, {2}, (3 (4))]] fn c() {}",
+        "#[a {}] #[b()] #[c[]] fn d() {}",
+        "fn a() { #![allow(x)] #[rare] let x = 1; #[b] { 2 } }",
+        "fn a() { #[b] fn c() {} }",
+        "macro_rules! m { ($a:expr) => { $a }; } m!(1); m! { x } m![];",
+        "fn a() { m!(1) * 2; let x = b::m![c]; m! { d } macro_rules! n ( () => {} ); }",
+        "macro_rules! m { () => {} } m!() fn a() {}",
+        "fn a() { m!(; }",
+        "fn a() { println!(\"{} {x}\", 1, x = 2); let s = format!(\"a\"); panic!() }",
+        "fn a() { println!(a b); assert!(x,); }",
+        "macro_rules! m ();",
+        "#[a(b] fn c() {}",
+        "#[] fn a() {}",
+        "#[a::] fn b() {}",
+        "#[unsafe a] fn b() {}",
+        "#[unsafe(a] fn b() {}",
+        "#[a = ] fn b() {}",
+        "#[a b] fn c() {}",
+        "#[a(b)) fn c() {}",
+        "#[a fn b() {}",
+        "#![a] #![a(] fn b() {}",
+        "fn a() { a[; }",
+        "fn a() { f(1 2); g(; }",
+        "fn a() { A { a: }; }",
+        "fn a() { if { } }",
+        "fn a() { if a { } else }",
+        "fn a() { fn a() {} let = ; fn a() {} }",
+        "fn a(self,) {}",
+        "fn a(&mut self, b: i32) -> i32 { b }",
+        "fn a(self: Box, mut b: A) {}",
+        "struct A;",
+        "struct A { x: f32, pub y: f32, }",
+        "pub type A = B;",
+        "impl A { fn new() -> A { a } pub fn b(&self) {} }",
+        "impl std::ops::Add for A { type Output = A; fn add(self, rhs: A) -> A { rhs } }",
+        "struct A { x: }",
+        "impl A for { }",
+        "} fn a() {}",
+        "fn",
+        "let x = 1;",
+    ];
+
+    // CSTを作ってからnagi_checker::checkで変換した結果と比べる
+    fn assert_same_as_cst(code: &str) {
+        let mut lexer = Lexer::new(code);
+        let token_list = lexer.tokenize();
+
+        let cst_result = CSTParser::new(&token_list).parse();
+        let ast_result = ASTParser::from_lexer(lexer::Lexer::new(&token_list)).parse();
+        assert_eq!(ast_result.errors, cst_result.errors, "{}", code);
+
+        match nagi_checker::check(&cst_result.tree) {
+            Ok(ast) => {
+                assert_eq!(ast_result.tree, ast, "{}", code);
+                assert_eq!(spans(&ast_result.tree), spans(&ast), "{}", code);
+                assert_eq!(
+                    nagi_checker::check_ast(&ast_result.tree),
+                    Ok(()),
+                    "{}",
+                    code
+                );
+            }
+            Err(error) => {
+                assert_eq!(
+                    nagi_checker::check_ast(&ast_result.tree),
+                    Err(error),
+                    "{}",
+                    code
+                );
+            }
+        }
+    }
+
+    // 全てのノードの範囲を行きがけ順に並べる
+    fn spans(ast: &ASTNode) -> Vec<Option<Span>> {
+        let mut result = vec![ast.span];
+        for child in ast.children() {
+            result.extend(spans(child));
+        }
+        result
+    }
+
+    fn parse_ast(code: &str) -> ASTNode {
+        let mut lexer = Lexer::new(code);
+        ASTParser::from_lexer(lexer::Lexer::new(&lexer.tokenize()))
+            .parse()
+            .tree
+    }
+
+    fn make_option(parser: ParseFrontEnd) -> CompileCommandOption {
+        CompileCommandOption {
+            is_debug: false,
+            is_compiler_debug: false,
+            is_output_ast: false,
+            tree_format: TreeFormat::Json,
+            parse_trace: None,
+            graph: None,
+            parser,
+            parse_limits: ParseLimits::default(),
+            target_list: vec![],
+        }
+    }
+
+    // benches/parse.rsと同じ形の関数
+    fn make_source(function_count: usize) -> String {
+        let mut code = String::new();
+        for i in 1..=function_count {
+            code.push_str(&format!(
+                "fn function{i}() {{
+    let x = {i} + 2 * 3;
+    ur y = f(x, 1).b[2];
+    sr z = (x - y) * 3;
+    if x {{ let w = 1; }} else {{ nr v = 2; }}
+    x.y.z(1, 2);
+    let q = -x + y * z - w / 4 % 5;
+    {{
+        let inner = q.r;
+    }}
+    q
+}}
+"
+            ));
+        }
+        code
+    }
+
+    #[test]
+    fn same_as_cst_and_check() {
+        for code in CORPUS {
+            assert_same_as_cst(code);
+        }
+
+        assert_same_as_cst(&make_source(20));
+    }
+
+    // 優先順位と結合性はnagi_parse::testsで調べ、ASTの構文解析器も同じ木を作るかを確かめる
+    #[test]
+    fn operators_bind_same_as_cst() {
+        let operators: Vec<&str> = Token::PUNCTUATIONS
+            .iter()
+            .filter(|token| BinaryOperator::from_token(token).is_some())
+            .map(|token| token.punctuation_str().unwrap())
+            .collect();
+        for x in &operators {
+            for y in &operators {
+                assert_same_as_cst(&format!("fn f() {{ a {} b {} c; -a {} b; }}", x, y, x));
+            }
+        }
+    }
+
+    // ASTNodeの比較は範囲を見ないので、JSONにして範囲まで比べる
+    #[test]
+    fn binary_tree_round_trip() {
+        let source = make_source(20);
+        for code in CORPUS.iter().copied().chain([source.as_str()]) {
+            let ast = parse_ast(code);
+            let (header, decoded) =
+                decode_tree::<ASTNode>(&encode_tree(&ast, source_hash(code))).unwrap();
+            assert_eq!(header.source_hash, source_hash(code));
+            assert_eq!(
+                serde_json::to_string(&decoded).unwrap(),
+                serde_json::to_string(&ast).unwrap()
+            );
+
+            let mut lexer = Lexer::new(code);
+            let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+            let (_, decoded) = decode_tree::<CSTNode>(&encode_tree(&cst, 0)).unwrap();
+            assert_eq!(decoded, cst);
+        }
+
+        let ast = parse_ast(&source);
+        let bytes = encode_tree(&ast, source_hash(&source));
+        assert!(bytes.len() * 4 < serde_json::to_vec(&ast).unwrap().len());
+
+        // 壊れたファイルや違うバージョンのファイルは読まずにエラーにする
+        let error = |bytes: &[u8]| decode_tree::<ASTNode>(bytes).unwrap_err().error_kind;
+        let mut other_version = bytes.clone();
+        other_version[4] += 1;
+        let mut flipped = bytes.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        let cst = parse_cst("fn a() {}");
+        assert_eq!(
+            error(b"{}"),
+            ErrorKind::TreeFile(TreeFileError::InvalidFormat)
+        );
+        assert_eq!(
+            error(&encode_tree(&cst, 0)),
+            ErrorKind::TreeFile(TreeFileError::InvalidFormat)
+        );
+        assert_eq!(
+            error(&other_version),
+            ErrorKind::TreeFile(TreeFileError::VersionMismatch)
+        );
+        assert_eq!(
+            error(&bytes[..bytes.len() - 1]),
+            ErrorKind::TreeFile(TreeFileError::Corrupted)
+        );
+        assert_eq!(
+            error(&flipped),
+            ErrorKind::TreeFile(TreeFileError::Corrupted)
+        );
+    }
+
+    #[test]
+    fn redefinition_function() {
+        let code = "fn a() {} fn b() { fn a() {} } fn b() {}";
+        let error = nagi_checker::check_ast(&parse_ast(code)).unwrap_err();
+        assert_eq!(
+            error.error_kind,
+            ErrorKind::Semantic(SemanticError::RedefinitionFunction)
+        );
+        assert!(error.error_text.contains("`b`"), "{}", error.error_text);
+
+        // 後から定義した関数の位置を示す
+        assert_eq!(error.position, Some(SourcePosition { row: 1, column: 32 }));
+        let mut lexer = Lexer::new(code);
+        let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+        assert_eq!(nagi_checker::check(&cst), Err(error));
+    }
+
+    #[test]
+    fn unsafe_operation_needs_unsafe_context() {
+        let check_both = |code: &str| {
+            let mut lexer = Lexer::new(code);
+            let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+            let result = nagi_checker::check(&cst).map(|_| ());
+            assert_eq!(
+                nagi_checker::check_ast(&parse_ast(code)),
+                result,
+                "{}",
+                code
+            );
+            result
+        };
+
+        for code in [
+            "unsafe fn g() {} fn f() { unsafe { g() } }",
+            "unsafe fn g() { g() }",
+            "unsafe fn g() {} fn f() { unsafe { async { g() }; } }",
+            // 内側で定義した安全な関数が外側のunsafe fnを隠す
+            "unsafe fn g() {} fn f() { fn g() {} g() }",
+            "fn f() { g() } fn g() {}",
+        ] {
+            assert_eq!(check_both(code), Ok(()), "{}", code);
+        }
+
+        // 呼び出しの式の位置を示す
+        for (code, column) in [
+            ("unsafe fn g() {} fn f() { g() }", 27),
+            ("fn f() { g(); } unsafe fn g() {}", 10),
+            // unsafeブロックの中で定義した関数の中はunsafeな文脈ではない
+            ("unsafe fn g() {} fn f() { unsafe { fn h() { g() } } }", 45),
+            ("fn f() { unsafe fn g() {} { g() } }", 29),
+        ] {
+            let error = check_both(code).unwrap_err();
+            assert_eq!(
+                error.error_kind,
+                ErrorKind::Semantic(SemanticError::UnsafeOperation),
+                "{}",
+                code
+            );
+            assert!(error.error_text.contains("`g`"), "{}", error.error_text);
+            assert_eq!(
+                error.position,
+                Some(SourcePosition { row: 1, column }),
+                "{}",
+                code
+            );
+        }
+    }
+
+    // CSTから変換した場合とASTを直接チェックした場合の記号表の木
+    fn symbol_trees(code: &str) -> [SymbolTreeNode; 2] {
+        let mut lexer = Lexer::new(code);
+        let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+
+        [
+            nagi_checker::check_with_symbol_tree(&cst).unwrap().1,
+            nagi_checker::check_ast_with_symbol_tree(&parse_ast(code)).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn bindings_are_scoped() {
+        let code = "fn f() {
+            if let a = x && let b = a { let c = 1; } else { let d = 2; }
+            if let e = x { let g = 3; } else { let h = 4; }
+            let i = x else { let j = 5; return; };
+            let k = 6;
+        }";
+
+        for tree in symbol_trees(code) {
+            // innerを束縛したスコープからouterが見えるか
+            let sees = |inner: &str, outer: &str| {
+                tree.find_scope(&SymbolPattern::Variable, inner)
+                    .unwrap()
+                    .is_symbol_in_ancestors(&SymbolPattern::Variable, outer)
+            };
+
+            assert!(sees("c", "a") && sees("c", "b"));
+            assert!(!sees("d", "a") && !sees("d", "b"));
+            assert!(sees("g", "e"));
+            assert!(!sees("h", "e"));
+            // elseブロックの束縛は外に漏れない
+            assert!(!sees("i", "j"));
+            assert!(sees("k", "i"));
+            assert!(!sees("k", "a") && !sees("k", "e"));
+        }
+    }
+
+    #[test]
+    fn let_else_must_diverge() {
+        for code in [
+            "fn f() { let a = b else { return; }; }",
+            "fn f() { let a = b else { if c { return; } else { return } }; }",
+            "fn f() { let a = b else { let c = return; }; }",
+            "fn f() { let a = b else { unsafe { return 1; } }; }",
+        ] {
+            let mut lexer = Lexer::new(code);
+            let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+            assert!(nagi_checker::check(&cst).is_ok(), "{}", code);
+        }
+
+        for code in [
+            "fn f() { let a = b else { }; }",
+            "fn f() { let a = b else { 1 }; }",
+            "fn f() { let a = b else { if c { return; } }; }",
+            "fn f() { let a = b else { { } }; }",
+        ] {
+            let mut lexer = Lexer::new(code);
+            let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+            let error = nagi_checker::check(&cst).unwrap_err();
+            assert_eq!(
+                error.error_kind,
+                ErrorKind::Semantic(SemanticError::LetElseNotDiverging),
+                "{}",
+                code
+            );
+            assert_eq!(error.position, Some(SourcePosition { row: 1, column: 25 }));
+            assert_eq!(nagi_checker::check_ast(&parse_ast(code)), Err(error));
+        }
+    }
+
+    #[test]
+    fn builtin_macro_format_strings() {
+        for code in [
+            "fn f() { println!(); print!(\"a {{}} b\"); }",
+            "fn f() { println!(\"{} {}\", a, b + 1); eprintln!(\"{1} {0} {1}\", a, b); }",
+            "fn f() { let s = format!(\"{x} {y:?} {:>5}\", z, y = 1); }",
+            "fn f() { panic!(\"{}\", a) }",
+            "fn f() { assert!(a, \"{}\", b); assert_eq!(a, b); assert_ne!(a, b, \"c\"); }",
+            "fn f() { let s = include_str!(\"a.txt\"); }",
+            "fn f() { let a = b else { panic!() }; }",
+        ] {
+            let mut lexer = Lexer::new(code);
+            let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+            assert!(nagi_checker::check(&cst).is_ok(), "{}", code);
+            assert_eq!(
+                nagi_checker::check_ast(&parse_ast(code)),
+                Ok(()),
+                "{}",
+                code
+            );
+        }
+
+        for (code, macro_error) in [
+            (
+                "fn f() { println!(\"{} {}\", a); }",
+                MacroError::FormatArgumentMismatch,
+            ),
+            (
+                "fn f() { println!(\"{1}\", a); }",
+                MacroError::FormatArgumentMismatch,
+            ),
+            (
+                "fn f() { println!(\"{}\", a, b); }",
+                MacroError::FormatArgumentMismatch,
+            ),
+            (
+                "fn f() { println!(\"{a}\", b = 1); }",
+                MacroError::FormatArgumentMismatch,
+            ),
+            (
+                "fn f() { println!(\"{b} {}\", b = 1, c); }",
+                MacroError::FormatArgumentMismatch,
+            ),
+            (
+                "fn f() { println!(\"{\"); }",
+                MacroError::InvalidFormatString,
+            ),
+            (
+                "fn f() { println!(\"}\"); }",
+                MacroError::InvalidFormatString,
+            ),
+            (
+                "fn f() { println!(\"{a b}\"); }",
+                MacroError::InvalidFormatString,
+            ),
+            ("fn f() { println!(a); }", MacroError::InvalidFormatString),
+            ("fn f() { format!(); }", MacroError::InvalidBuiltinArguments),
+            (
+                "fn f() { assert_eq!(a); }",
+                MacroError::InvalidBuiltinArguments,
+            ),
+            (
+                "fn f() { include_str!(a); }",
+                MacroError::InvalidBuiltinArguments,
+            ),
+        ] {
+            let mut lexer = Lexer::new(code);
+            let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+            let error = nagi_checker::check(&cst).unwrap_err();
+            assert_eq!(error.error_kind, ErrorKind::Macro(macro_error), "{}", code);
+            // マクロの呼び出しの位置を示す
+            assert_eq!(
+                error.position,
+                Some(SourcePosition { row: 1, column: 10 }),
+                "{}",
+                code
+            );
+            assert_eq!(nagi_checker::check_ast(&parse_ast(code)), Err(error));
+        }
+    }
+
+    // 解析した木の図
+    #[test]
+    fn graph_export() {
+        let ast = parse_ast("fn a() { ur x = 1 + 2; }");
+        let dot = tree_graph("ast", &ast).render(GraphFormat::Dot);
+        assert!(dot.starts_with("digraph \"ast\" {\n"));
+        assert!(dot.contains(
+            "[label=\"LetStatement\\nrarity: Ur\", style=filled, fillcolor=\"#f9d976\"];"
+        ));
+        assert!(dot.contains("[label=\"BinaryOperator\\noperator: Add\"];"));
+        assert_eq!(
+            dot.matches(" -> ").count(),
+            Arena::from_tree(ast.clone()).len() - 1
+        );
+
+        let mermaid = tree_graph("ast", &ast).render(GraphFormat::Mermaid);
+        assert!(mermaid.starts_with("%% ast\nflowchart TD\n    n0[\"Crate\"]\n"));
+        assert!(mermaid.contains("[\"IdentifierPattern<br/>identifier: #quot;x#quot;\"]"));
+        assert!(mermaid.ends_with("    classDef ur fill:#f9d976\n    class n6 ur\n"));
+
+        let cst = parse_cst("fn a() { ur x = 1 + 2; }");
+        let dot = tree_graph("cst", &cst).render(GraphFormat::Dot);
+        assert!(dot.contains("[label=\"Operator\\ntoken: +\"];"));
+    }
+
+    // 記号表のスコープの図
+    #[test]
+    fn symbol_tree_graph() {
+        let (_, symbol_tree) =
+            nagi_checker::check_with_symbol_tree(&parse_cst("fn a() { ur x = 1; let b = 2; }"))
+                .unwrap();
+        assert_eq!(
+            symbol_tree.graph().render(GraphFormat::Mermaid),
+            "%% scopes\n\
+             flowchart TD\n    \
+             n0[\"scope<br/>fn a\"]\n    \
+             n1[\"scope\"]\n    \
+             n2[\"scope\"]\n    \
+             n3[\"scope<br/>Let b: _<br/>Ur x: _\"]\n    \
+             n0 --> n1\n    \
+             n1 --> n2\n    \
+             n1 --> n3\n"
+        );
+    }
+
+    // ノードの番号は前順に付き、子から親をたどれる
+    #[test]
+    fn arena_node_ids() {
+        let source = make_source(5);
+        for code in CORPUS.iter().copied().chain([source.as_str()]) {
+            let ast = parse_ast(code);
+            let arena = Arena::from_tree(ast.clone());
+            assert_eq!(arena.parent(arena.root()), None);
+            assert_eq!(arena.len(), arena::preorder(&ast).len());
+
+            for (id, node) in arena.iter() {
+                assert_eq!(
+                    node.kind_name(),
+                    arena::preorder(&ast)[id.index()].1.kind_name()
+                );
+                for child in arena.children(id) {
+                    assert_eq!(arena.parent(*child), Some(id));
+                }
+                for descendant in arena.descendants(id) {
+                    assert!(arena.ancestors(descendant).any(|ancestor| ancestor == id));
+                    assert!(arena.is_ancestor(id, descendant));
+                }
+            }
+
+            // 範囲も含めて元の木に戻る
+            let tree = arena.to_tree();
+            assert_eq!(tree, ast);
+            assert_eq!(
+                serde_json::to_string(&tree).unwrap(),
+                serde_json::to_string(&ast).unwrap()
+            );
+        }
+
+        let cst = parse_cst("fn a() { let x = (1 + 2) * 3; }");
+        let arena = Arena::from_tree(cst.clone());
+        assert_eq!(
+            serde_json::to_string(&arena.to_tree()).unwrap(),
+            serde_json::to_string(&cst).unwrap()
+        );
+
+        // Rcで共有した部分木も、現れた場所ごとに別の番号になる
+        let ast = parse_ast("fn a() { 1 + 2 }");
+        let Some(ASTNodeKind::BinaryOperator { operator, left, .. }) = arena::preorder(&ast)
+            .into_iter()
+            .map(|(_, node)| node.node_kind.clone())
+            .find(|kind| matches!(kind, ASTNodeKind::BinaryOperator { .. }))
+        else {
+            panic!("{:?}", ast);
+        };
+        let ast = ASTNode::new(ASTNodeKind::BinaryOperator {
+            operator,
+            left: left.clone(),
+            right: left,
+        });
+        let arena = Arena::from_tree(ast.clone());
+        let [left, right] = arena.children(arena.root()) else {
+            panic!("{:?}", arena.children(arena.root()));
+        };
+        assert_ne!(left, right);
+        assert_eq!(
+            arena.descendants(*left).count(),
+            arena.descendants(*right).count()
+        );
+        assert_eq!(arena.to_tree(), ast);
+    }
+
+    // 解析した木どうしの差分と、その位置
+    #[test]
+    fn tree_diff() {
+        let ignore_spans = DiffOptions { ignore_spans: true };
+        let report = |old: &str, new: &str, options: &DiffOptions| {
+            diff_trees(&parse_ast(old), &parse_ast(new), options).report()
+        };
+
+        for code in CORPUS {
+            let ast = parse_ast(code);
+            assert!(diff_trees(&ast, &ast.clone(), &DiffOptions::default()).is_empty());
+        }
+
+        assert_eq!(
+            report(
+                "fn a() { let x = 1; }",
+                "fn a() { let x = 2; }",
+                &ignore_spans
+            ),
+            "update 1:18: Literal(literal=1) -> Literal(literal=2)\n\
+             1 changes: 0 inserted, 0 deleted, 1 updated, 0 moved\n"
+        );
+        assert_eq!(
+            report(
+                "fn a() { 1 } fn b() { 2 }",
+                "fn b() { 2 } fn a() { 1 }",
+                &ignore_spans
+            ),
+            "move 1:1 -> 1:14: Function(identifier=\"a\") (6 nodes)\n\
+             1 changes: 0 inserted, 0 deleted, 0 updated, 1 moved\n"
+        );
+        assert_eq!(
+            report("fn a() { f(); }", "fn a() { g(); f(); }", &ignore_spans),
+            "insert 1:10: Statement (7 nodes)\n\
+             1 changes: 1 inserted, 0 deleted, 0 updated, 0 moved\n"
+        );
+        assert_eq!(
+            report("fn a() { f(); g(); }", "fn a() { g(); }", &ignore_spans),
+            "delete 1:10: Statement (7 nodes)\n\
+             1 changes: 0 inserted, 1 deleted, 0 updated, 0 moved\n"
+        );
+
+        // 範囲を比べれば、ずれたノードは更新になる
+        assert_eq!(
+            report("fn a() {}", "fn  a() {}", &DiffOptions::default())
+                .lines()
+                .next(),
+            Some("update 1:1: Crate(span=1:1-1:10) -> Crate(span=1:1-1:11)")
+        );
+        assert!(diff_trees(
+            &parse_ast("fn a() {}"),
+            &parse_ast("fn  a() {}"),
+            &ignore_spans
+        )
+        .is_empty());
+
+        // 番号はArenaの番号と同じ
+        let parse_cst = |code: &str| {
+            let mut lexer = Lexer::new(code);
+            CSTParser::new(&lexer.tokenize()).parse().tree
+        };
+        let old = parse_cst("fn a() { f(); }");
+        let new = parse_cst("fn a() {\n    f();\n    g();\n}");
+        let diff = diff_trees(&old, &new, &ignore_spans);
+        let [edit] = &diff.edits[..] else {
+            panic!("{}", diff.report());
+        };
+        assert_eq!(edit.kind, EditKind::Insert);
+        let arena = Arena::from_tree(new.clone());
+        let inserted = arena.node(edit.new.unwrap());
+        assert_eq!(diff.new_label(edit.new.unwrap()), inserted.kind_name());
+        assert_eq!(inserted.span().map(|span| span.start), Some((3, 5)));
+    }
+
+    // 解析した木の実際の位置で、セレクタの結合子と条件を確かめる
+    #[test]
+    fn tree_query() {
+        let ast = parse_ast("fn f() { ur a = f(1); sr b = f(2); ur c = 3; ur d = { f(4) }; }");
+        let find = |selector: &str| -> Vec<String> {
+            Query::parse(selector)
+                .unwrap()
+                .find(&ast)
+                .iter()
+                .map(|found| {
+                    format!(
+                        "{} {}",
+                        found.span.clone().unwrap_or_default(),
+                        found.describe()
+                    )
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            find("LetStatement[rarity=Ur] > ExpressionWithoutBlock > CallExpression"),
+            ["1:17-1:21 CallExpression"]
+        );
+        assert_eq!(
+            find("LetStatement[rarity=Ur] CallExpression"),
+            ["1:17-1:21 CallExpression", "1:55-1:59 CallExpression"]
+        );
+        assert_eq!(
+            find("LetStatement[rarity!=Ur] IdentifierPattern, Literal[literal=3]"),
+            [
+                "1:26-1:27 IdentifierPattern identifier=\"b\"",
+                "1:43-1:44 Literal literal=3"
+            ]
+        );
+        assert_eq!(
+            find("IdentifierPattern[identifier*=c]"),
+            ["1:39-1:40 IdentifierPattern identifier=\"c\""]
+        );
+        assert_eq!(find("* > *").len(), Arena::from_tree(ast.clone()).len() - 1);
+
+        // CSTのノードも最初のトークンから最後のトークンまでの範囲を持つ
+        let cst = CSTParser::new(&Lexer::new("fn f() {\n    ur a = f(1);\n}").tokenize())
+            .parse()
+            .tree;
+        let matches = Query::parse("Statement > LetStatement").unwrap().find(&cst);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            (matches[0].position, &matches[0].span),
+            (Some((2, 5)), &Some("2:5-2:17".to_string()))
+        );
+    }
+
+    #[test]
+    fn type_table_is_keyed_by_node_id() {
+        let code = "struct Vec3 { x: f32 }
+            impl Add for Vec3 { type Output = Vec3; fn add(self, rhs: Vec3) -> Vec3 { rhs } }
+            fn f(a: Vec3, b: Vec3) -> Vec3 { let c = a + b; let d = 1.0f32; c }";
+        let ast = parse_ast(code);
+        let arena = Arena::from_tree(ast.clone());
+        let table = nagi_checker::type_table(&ast).unwrap();
+
+        let (add, _) = arena
+            .iter()
+            .find(|(_, node)| matches!(node.node_kind, ASTNodeKind::BinaryOperator { .. }))
+            .unwrap();
+        assert_eq!(table.node_types[&add], "Vec3");
+        assert_eq!(
+            table.operator_methods[&add].to_string(),
+            "<Vec3 as Add>::add"
+        );
+        assert_eq!(table.operator_methods.len(), 1);
+
+        // 変数を使ったノードから、束縛したパターンのノードを引ける
+        let binding = |name: &str| {
+            arena
+                .iter()
+                .find(|(_, node)| {
+                    matches!(&node.node_kind, ASTNodeKind::IdentifierPattern { identifier, .. } if identifier == name)
+                })
+                .unwrap()
+                .0
+        };
+        let uses = |name: &str| {
+            let binding = binding(name);
+            let mut uses: Vec<_> = table
+                .bindings
+                .iter()
+                .filter(|(_, id)| **id == binding)
+                .map(|(id, _)| *id)
+                .collect();
+            uses.sort();
+            uses
+        };
+        assert_eq!(uses("a").len(), 1);
+        assert_eq!(uses("d").len(), 0);
+        let [c] = uses("c")[..] else {
+            panic!("`c`は1回だけ使っている");
+        };
+        assert!(matches!(
+            arena.node(c).node_kind,
+            ASTNodeKind::PathInExpression { .. }
+        ));
+        assert_eq!(table.node_types[&c], "Vec3");
+        assert!(arena.is_ancestor(arena.parent(binding("c")).unwrap(), binding("c")));
+
+        // Rcで共有した部分木も、現れた場所ごとの番号で引ける
+        let ast = OperandSharer.fold_node(ast);
+        let arena = Arena::from_tree(ast.clone());
+        let table = nagi_checker::type_table(&ast).unwrap();
+        let (add, _) = arena
+            .iter()
+            .find(|(_, node)| matches!(node.node_kind, ASTNodeKind::BinaryOperator { .. }))
+            .unwrap();
+        let [left, right] = arena.children(add) else {
+            panic!("{:?}", arena.children(add));
+        };
+        assert_eq!(table.node_types[left], "Vec3");
+        assert_eq!(table.node_types[right], "Vec3");
+        assert_eq!(
+            table
+                .bindings
+                .values()
+                .filter(|id| **id == binding("a"))
+                .count(),
+            2
+        );
+    }
+
+    // 二項演算子の右辺を左辺と同じRcにする
+    struct OperandSharer;
+
+    impl Folder for OperandSharer {
+        fn fold_binary_operator(&mut self, node: ASTNode) -> ASTNode {
+            let mut node = walk_children_fold(self, node);
+            if let ASTNodeKind::BinaryOperator { left, right, .. } = &mut node.node_kind {
+                *right = left.clone();
+            }
+            node
+        }
+    }
+
+    #[test]
+    fn operators_resolve_to_trait_methods() {
+        let code = "struct Vec3 { x: f32 }
+            impl std::ops::Add for Vec3 { type Output = Self; fn add(self, rhs: Vec3) -> Self::Output { rhs } }
+            impl Neg for Vec3 { type Output = Vec3; fn neg(self) -> Vec3 { self } }
+            impl Index for Vec3 { type Output = f32; fn index(&self, i: usize) -> f32 { self.x } }
+            impl PartialEq for Vec3 { fn eq(&self, other: Vec3) -> bool { true } }
+            impl AddAssign for Vec3 { fn add_assign(&mut self, rhs: Vec3) {} }
+            fn f(a: Vec3, b: Vec3, i: usize) { let c = -(a + b); let x = c[i] * 2.0; c == a; c += b; }";
+
+        let methods = nagi_checker::operator_methods(&parse_ast(code)).unwrap();
+        let methods: Vec<String> = methods.iter().map(|method| method.to_string()).collect();
+        assert_eq!(
+            methods,
+            [
+                "<Vec3 as Add>::add",
+                "<Vec3 as Neg>::neg",
+                "<Vec3 as Index>::index",
+                "<Vec3 as PartialEq>::eq",
+                "<Vec3 as AddAssign>::add_assign",
+            ]
+        );
+
+        // 演算子の式かimplの位置を示す
+        for (code, semantic_error, text, (row, column)) in [
+            (
+                "struct Foo; fn f(a: Foo, b: Foo) { let c = a + b; }",
+                SemanticError::NoOperatorImplementation,
+                "`Foo`には`+`の実装がありません",
+                (1, 44),
+            ),
+            (
+                "struct Vec3 { x: f32 }
+                impl Mul for Vec3 { type Output = Vec3; fn mul(self, rhs: f32) -> Vec3 { self } }
+                fn f(a: Vec3) { let b = a * 2.0; let c = a * a; }",
+                SemanticError::NoOperatorImplementation,
+                "`Vec3`には右辺が`Vec3`の`*`の実装がありません",
+                (3, 58),
+            ),
+            (
+                "struct Vec3 { x: f32 }
+                impl Add for Vec3 { type Output = Self; fn sub(self, rhs: Vec3) -> Self::Output { rhs } }",
+                SemanticError::MissingTraitItem,
+                "`Vec3`の`Add`の実装に`add`関数がありません",
+                (2, 17),
+            ),
+        ] {
+            let mut lexer = Lexer::new(code);
+            let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+            let error = nagi_checker::check(&cst).unwrap_err();
+            assert_eq!(
+                error.error_kind,
+                ErrorKind::Semantic(semantic_error),
+                "{}",
+                code
+            );
+            assert!(error.error_text.contains(text), "{}", error.error_text);
+            assert_eq!(error.position, Some(SourcePosition { row, column }));
+            assert_eq!(nagi_checker::check_ast(&parse_ast(code)), Err(error));
+        }
+    }
+
+    #[test]
+    fn grouped_expression_is_removed() {
+        let ast = parse_ast("fn f() { (1) }");
+        let ASTNodeKind::Crate { item, .. } = &ast.node_kind else {
+            panic!("{:?}", ast.node_kind);
+        };
+        let ASTNodeKind::Function {
+            block_expression: Some(block_expression),
+            ..
+        } = &item[0].node_kind
+        else {
+            panic!("{:?}", item[0].node_kind);
+        };
+
+        // ExpressionWithoutBlock(Grouped(ExpressionWithoutBlock(Literal)))の括弧だけが消える
+        let mut node = block_expression.children()[0].children()[0];
+        for _ in 0..2 {
+            let ASTNodeKind::ExpressionWithoutBlock { expression, .. } = &node.node_kind else {
+                panic!("{:?}", node.node_kind);
+            };
+            node = expression;
+        }
+        assert!(matches!(node.node_kind, ASTNodeKind::Literal { .. }));
+    }
+
+    // メインスレッドと同じ大きさのスタックで動かす
+    fn run_on_main_stack(f: impl FnOnce() + Send + 'static) {
+        std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(f)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    fn check_code(code: &str) -> Result<ASTNode, Error> {
+        let mut lexer = Lexer::new(code);
+        let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+        nagi_checker::check(&cst)
+    }
+
+    // 検査器の入れ子ごとのスタックが構文解析器より大きくならない
+    #[test]
+    fn deeply_nested_blocks_are_checked() {
+        run_on_main_stack(|| {
+            let nesting = 60;
+            let code = format!(
+                "fn f() {{ {}1{} }}",
+                "{ ".repeat(nesting),
+                " }".repeat(nesting)
+            );
+            assert!(check_code(&code).is_ok());
+        });
+    }
+
+    #[test]
+    fn deeply_nested_operators_are_checked() {
+        run_on_main_stack(|| {
+            for code in [
+                format!("fn f() {{ {}1{} }}", "(".repeat(80), ")".repeat(80)),
+                format!("fn f() {{ {}1{} }}", "1 + (".repeat(60), ")".repeat(60)),
+                format!("fn f() {{ {} }}", vec!["1"; 800].join(" + ")),
+            ] {
+                assert!(check_code(&code).is_ok(), "{}", code);
+            }
+
+            // 左結合の連なりも内側の演算子から順に解決する
+            let code = format!(
+                "struct Vec3 {{ x: f32 }}
+                impl Add for Vec3 {{ type Output = Vec3; fn add(self, rhs: Vec3) -> Vec3 {{ rhs }} }}
+                fn f(a: Vec3) -> Vec3 {{ {} }}",
+                vec!["a"; 800].join(" + ")
+            );
+            let ast = parse_ast(&code);
+            let table = nagi_checker::type_table(&ast).unwrap();
+            assert_eq!(table.operator_methods.len(), 799);
+            let arena = Arena::from_tree(ast.clone());
+            assert!(arena
+                .iter()
+                .filter(|(_, node)| matches!(node.node_kind, ASTNodeKind::BinaryOperator { .. }))
+                .all(|(id, _)| table.node_types[&id] == "Vec3"));
+        });
+    }
+
+    #[test]
+    fn attributes_stay_distinct() {
+        let code = "#![a] #[test] fn b() { #![c] }";
+
+        let mut lexer = Lexer::new(code);
+        let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+        for ast in [parse_ast(code), nagi_checker::check(&cst).unwrap()] {
+            let ASTNodeKind::Crate {
+                inner_attribute,
+                item,
+            } = &ast.node_kind
+            else {
+                panic!("{:?}", ast.node_kind);
+            };
+            assert!(matches!(
+                inner_attribute[0].node_kind,
+                ASTNodeKind::InnerAttribute { .. }
+            ));
+
+            let ASTNodeKind::Function {
+                outer_attribute,
+                block_expression: Some(block_expression),
+                ..
+            } = &item[0].node_kind
+            else {
+                panic!("{:?}", item[0].node_kind);
+            };
+            let ASTNodeKind::OuterAttribute { attribute } = &outer_attribute[0].node_kind else {
+                panic!("{:?}", outer_attribute[0].node_kind);
+            };
+            let ASTNodeKind::Attribute {
+                is_unsafe: false,
+                simple_path,
+                attribute_input: None,
+            } = &attribute.node_kind
+            else {
+                panic!("{:?}", attribute.node_kind);
+            };
+            assert_eq!(
+                simple_path.children()[0].node_kind,
+                ASTNodeKind::Factor {
+                    token: Token::Identifier("test".to_string())
+                }
+            );
+
+            let ASTNodeKind::BlockExpression {
+                inner_attribute, ..
+            } = &block_expression.node_kind
+            else {
+                panic!("{:?}", block_expression.node_kind);
+            };
+            assert!(matches!(
+                inner_attribute[0].node_kind,
+                ASTNodeKind::InnerAttribute { .. }
+            ));
+        }
+    }
+
+    #[test]
+    fn select_front_end() {
+        let code = "fn a() { let x = 1 + 2; } fn b() { a(); }";
+
+        let cst = crate::parse(code, &make_option(ParseFrontEnd::Cst));
+        let ast = crate::parse(code, &make_option(ParseFrontEnd::Ast));
+        assert!(cst.is_ok() && ast.is_ok());
+
+        let SyntaxTree::Cst(cst) = cst.tree else {
+            panic!("{:?}", cst.tree);
+        };
+        assert_eq!(
+            ast.tree,
+            SyntaxTree::Ast(nagi_checker::check(&cst).unwrap())
+        );
+
+        let mut option = make_option(ParseFrontEnd::Ast);
+        option.parse_trace = Some(nagi_command_option::ParseTraceFormat::Json);
+        let trace = crate::parse(code, &option).trace.unwrap();
+        assert!(trace.root.find(&|node| node.rule == "Function").is_some());
+    }
+
+    // 両方の構文解析器で制限を付けて解析し、同じエラーになることを確かめる
+    fn parse_with_limits(code: &str, limits: ParseLimits) -> Vec<Error> {
+        let mut results = [ParseFrontEnd::Cst, ParseFrontEnd::Ast].map(|parser| {
+            let mut option = make_option(parser);
+            option.parse_limits = limits.clone();
+            crate::parse(code, &option).errors
+        });
+        assert_eq!(results[0], results[1], "{}", code);
+        std::mem::take(&mut results[0])
+    }
+
+    fn assert_limit_exceeded(code: &str, limits: ParseLimits, error_text: &str) {
+        let errors = parse_with_limits(code, limits);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(
+            errors[0].error_kind,
+            ErrorKind::Syntax(SyntaxError::LimitExceeded)
+        );
+        assert!(errors[0].error_text.contains(error_text), "{:?}", errors);
+    }
+
+    // ドライバと同じく、構文エラーが無ければ検査まで行う
+    fn compile(code: &str, parser: ParseFrontEnd) -> Vec<Error> {
+        let result = crate::parse(code, &make_option(parser));
+        if !result.is_ok() {
+            return result.errors;
+        }
+
+        let checked = match result.tree {
+            SyntaxTree::Cst(cst) => nagi_checker::check(&cst).map(|_| ()),
+            SyntaxTree::Ast(ast) => nagi_checker::check_ast(&ast),
+        };
+        checked.err().into_iter().collect()
+    }
+
+    #[test]
+    fn compile_limits() {
+        // 既定の制限はメインスレッドと同じ大きさのスタックを前提にしている
+        run_on_main_stack(compile_limits_on_main_stack);
+    }
+
+    fn compile_limits_on_main_stack() {
+        // スタックが溢れる深さの入れ子
+        let nesting = 10000;
+        for code in [
+            format!(
+                "fn f() {{ {}1{} }}",
+                "(".repeat(nesting),
+                ")".repeat(nesting)
+            ),
+            format!("fn f() {{ {}1 }}", "-".repeat(nesting)),
+            format!("fn f() {{ {}1 }}", "return ".repeat(nesting)),
+            format!(
+                "fn f() {{ {}{} }}",
+                "{ ".repeat(nesting),
+                "}".repeat(nesting)
+            ),
+            format!("fn f() {{ a{} }}", ".b()".repeat(nesting)),
+        ] {
+            assert_limit_exceeded(&code, ParseLimits::default(), "nesting is too deep");
+        }
+
+        // 制限を超えない入れ子は解析して検査できる
+        for code in [
+            format!("fn f() {{ {}1{} }}", "(".repeat(80), ")".repeat(80)),
+            format!("fn f() {{ {}1{} }}", "{ ".repeat(60), " }".repeat(60)),
+            format!("fn f() {{ {} }}", vec!["1"; 100].join(" + ")),
+        ] {
+            for parser in [ParseFrontEnd::Cst, ParseFrontEnd::Ast] {
+                assert_eq!(compile(&code, parser), vec![], "{}", code);
+            }
+        }
+
+        // 構文解析の制限にかからない深い木は検査の前に止める
+        let code = format!(
+            "fn f() {{ {} }}",
+            vec!["1"; nagi_checker::MAX_TREE_DEPTH * 2].join(" + ")
+        );
+        for parser in [ParseFrontEnd::Cst, ParseFrontEnd::Ast] {
+            let errors = compile(&code, parser);
+            assert_eq!(errors.len(), 1, "{:?}", errors);
+            assert_eq!(
+                errors[0].error_kind,
+                ErrorKind::Semantic(SemanticError::NestingTooDeep)
+            );
+        }
+
+        let code = "fn a() { let x = 1 + 2; } fn b() { a(); }";
+        let limits = ParseLimits {
+            max_tokens: 10,
+            ..ParseLimits::default()
+        };
+        assert_limit_exceeded(code, limits, "too many tokens");
+
+        let limits = ParseLimits {
+            max_memo_entries: 10,
+            ..ParseLimits::default()
+        };
+        assert_limit_exceeded(code, limits, "too many memo entries");
+
+        let cancellation = CancellationToken::new();
+        let limits = ParseLimits {
+            cancellation: cancellation.clone(),
+            ..ParseLimits::default()
+        };
+        cancellation.cancel();
+        let errors = parse_with_limits(code, limits);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(
+            errors[0].error_kind,
+            ErrorKind::Syntax(SyntaxError::Cancelled)
+        );
+    }
+
+    // 行きがけ順で最初に見つかったノード
+    fn find<'a>(ast: &'a ASTNode, is_target: &dyn Fn(&ASTNodeKind) -> bool) -> Option<&'a ASTNode> {
+        if is_target(&ast.node_kind) {
+            return Some(ast);
+        }
+        ast.children()
+            .into_iter()
+            .find_map(|child| find(child, is_target))
+    }
+
+    // 識別子パターンの名前と、たどったノードの数
+    #[derive(Default)]
+    struct PatternCollector {
+        identifiers: Vec<String>,
+        node_count: usize,
+    }
+
+    impl Visitor<'_> for PatternCollector {
+        fn visit_node(&mut self, node: &ASTNode) {
+            self.node_count += 1;
+            nagi_syntax_tree::ast::visit::walk_node(self, node);
+        }
+
+        fn visit_identifier_pattern(&mut self, node: &ASTNode) {
+            if let ASTNodeKind::IdentifierPattern { identifier, .. } = &node.node_kind {
+                self.identifiers.push(identifier.clone());
+            }
+            walk_children(self, node);
+        }
+    }
+
+    struct PatternRenamer;
+
+    impl VisitorMut for PatternRenamer {
+        fn visit_identifier_pattern(&mut self, node: &mut ASTNode) {
+            if let ASTNodeKind::IdentifierPattern { identifier, .. } = &mut node.node_kind {
+                identifier.push('_');
+            }
+        }
+    }
+
+    struct LiteralReplacer(ASTNode);
+
+    impl Folder for LiteralReplacer {
+        fn fold_literal(&mut self, _: ASTNode) -> ASTNode {
+            self.0.clone()
+        }
+    }
+
+    #[derive(Default)]
+    struct TokenCounter(usize);
+
+    impl cst::visit::Visitor<'_> for TokenCounter {
+        fn visit_factor(&mut self, _: &CSTNode) {
+            self.0 += 1;
+        }
+
+        fn visit_literal(&mut self, _: &CSTNode) {
+            self.0 += 1;
+        }
+
+        fn visit_operator(&mut self, node: &CSTNode) {
+            self.0 += 1;
+            cst::visit::walk_children(self, node);
+        }
+    }
+
+    #[test]
+    fn visitors_walk_every_node() {
+        let code = "fn f(x: i32) { let a = 1 + x; if let b = a { let c = b * 2; } }";
+        let mut ast = parse_ast(code);
+
+        let mut collector = PatternCollector::default();
+        collector.visit_node(&ast);
+        assert_eq!(collector.identifiers, ["x", "a", "b", "c"]);
+        assert_eq!(collector.node_count, spans(&ast).len());
+
+        PatternRenamer.visit_node(&mut ast);
+        let mut collector = PatternCollector::default();
+        collector.visit_node(&ast);
+        assert_eq!(collector.identifiers, ["x_", "a_", "b_", "c_"]);
+
+        // 全てのリテラルを0に置き換える
+        let zero = find(&parse_ast("fn f() { 0 }"), &|kind| {
+            matches!(kind, ASTNodeKind::Literal { .. })
+        })
+        .unwrap()
+        .clone();
+        let folded = LiteralReplacer(zero).fold_node(parse_ast(code));
+        assert_eq!(
+            folded,
+            parse_ast("fn f(x: i32) { let a = 0 + x; if let b = a { let c = b * 0; } }")
+        );
+
+        // CSTはnode_kindの中のノードとchildrenの両方をたどる
+        let mut lexer = Lexer::new(code);
+        let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+        let mut counter = TokenCounter::default();
+        cst::visit::Visitor::visit_node(&mut counter, &cst);
+        assert_eq!(counter.0, cst.tokens().len());
+    }
+
+    #[test]
+    fn spans_cover_source() {
+        let code = "#[a] pub fn f() {\n    let a = 1 + -x;\n    a;\n}";
+        let mut lexer = Lexer::new(code);
+        let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+
+        for ast in [parse_ast(code), nagi_checker::check(&cst).unwrap()] {
+            let span = |is_target: &dyn Fn(&ASTNodeKind) -> bool| {
+                let span = find(&ast, is_target).unwrap().span.unwrap();
+                (span.start, span.end)
+            };
+
+            assert_eq!(
+                span(&|kind| matches!(kind, ASTNodeKind::Crate { .. })),
+                ((1, 1), (4, 2))
+            );
+            assert_eq!(
+                span(&|kind| matches!(kind, ASTNodeKind::Function { .. })),
+                ((1, 1), (4, 2))
+            );
+            assert_eq!(
+                span(&|kind| matches!(kind, ASTNodeKind::LetStatement { .. })),
+                ((2, 5), (2, 20))
+            );
+            assert_eq!(
+                span(&|kind| matches!(kind, ASTNodeKind::BinaryOperator { .. })),
+                ((2, 13), (2, 19))
+            );
+            assert_eq!(
+                span(&|kind| matches!(kind, ASTNodeKind::UnaryOperator { .. })),
+                ((2, 17), (2, 19))
+            );
+
+            // 式文の`;`は文に含め、式には含めない
+            let is_expression_statement = |kind: &ASTNodeKind| {
+                matches!(kind, ASTNodeKind::Statement { statement: Some(statement) }
+                    if matches!(statement.node_kind, ASTNodeKind::ExpressionWithoutBlock { .. }))
+            };
+            let statement = find(&ast, &is_expression_statement).unwrap();
+            let expression = statement.children()[0];
+            assert_eq!(
+                statement.span.map(|span| (span.start, span.end)),
+                Some(((3, 5), (3, 7)))
+            );
+            assert_eq!(
+                expression.span.map(|span| (span.start, span.end)),
+                Some(((3, 5), (3, 6)))
+            );
+
+            // JSONに書き出しても範囲が残る
+            let file_name = std::env::temp_dir().join("nagi_spans_cover_source.json");
+            let file_name = file_name.to_str().unwrap();
+            ast.write_ast(file_name);
+            let json = std::fs::read_to_string(file_name).unwrap();
+            let read: ASTNode = serde_json::from_str(&json).unwrap();
+            assert_eq!(spans(&read), spans(&ast));
+        }
+    }
+
+    #[test]
+    fn spans_end_where_literals_end() {
+        // 文字列の中の改行とエスケープはソースコードに書かれたとおりに数える
+        let code = "fn f() {\n    let s = \"a\n\\\"b\";\n    s;\n}";
+        let mut lexer = Lexer::new(code);
+        let cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+
+        for ast in [parse_ast(code), nagi_checker::check(&cst).unwrap()] {
+            let span = |is_target: &dyn Fn(&ASTNodeKind) -> bool| {
+                let span = find(&ast, is_target).unwrap().span.unwrap();
+                (span.start, span.end)
+            };
+            assert_eq!(
+                span(&|kind| matches!(kind, ASTNodeKind::Literal { .. })),
+                ((2, 13), (3, 5))
+            );
+            assert_eq!(
+                span(&|kind| matches!(kind, ASTNodeKind::LetStatement { .. })),
+                ((2, 5), (3, 6))
+            );
+        }
+    }
+
+    // 括弧で囲んだ式を外し、括弧を束縛力から書き直させる
+    struct Ungrouper;
+
+    impl Folder for Ungrouper {
+        fn fold_expression_without_block(&mut self, node: ASTNode) -> ASTNode {
+            let mut node = walk_children_fold(self, node);
+            if let ASTNodeKind::ExpressionWithoutBlock { expression, .. } = &mut node.node_kind {
+                if let ASTNodeKind::ExpressionWithoutBlock {
+                    outer_attribute,
+                    expression: inner,
+                } = &expression.node_kind
+                {
+                    if outer_attribute.is_empty() {
+                        *expression = inner.clone();
+                    }
+                }
+            }
+            node
+        }
+    }
+
+    // 行と列を0にして、位置以外を比べられるようにする
+    struct PositionClearer;
+
+    impl cst::visit::VisitorMut for PositionClearer {
+        fn visit_factor(&mut self, node: &mut CSTNode) {
+            clear_position(node);
+        }
+
+        fn visit_literal(&mut self, node: &mut CSTNode) {
+            clear_position(node);
+        }
+
+        fn visit_literal_pattern(&mut self, node: &mut CSTNode) {
+            clear_position(node);
+            cst::visit::walk_children_mut(self, node);
+        }
+
+        fn visit_operator(&mut self, node: &mut CSTNode) {
+            clear_position(node);
+            cst::visit::walk_children_mut(self, node);
+        }
+    }
+
+    fn clear_position(node: &mut CSTNode) {
+        if let CSTNodeKind::Factor { row, column, .. }
+        | CSTNodeKind::Literal { row, column, .. }
+        | CSTNodeKind::LiteralPattern { row, column, .. }
+        | CSTNodeKind::Operator { row, column, .. } = &mut node.node_kind
+        {
+            *row = 0;
+            *column = 0;
+        }
+    }
+
+    fn parse_cst(code: &str) -> CSTNode {
+        let mut lexer = Lexer::new(code);
+        let mut cst = CSTParser::new(&lexer.tokenize()).parse().tree;
+        cst::visit::VisitorMut::visit_node(&mut PositionClearer, &mut cst);
+        cst
+    }
+
+    fn parse_ast_without_errors(code: &str) -> Option<ASTNode> {
+        let mut lexer = Lexer::new(code);
+        let result = ASTParser::from_lexer(lexer::Lexer::new(&lexer.tokenize())).parse();
+        result.errors.is_empty().then_some(result.tree)
+    }
+
+    #[test]
+    fn printed_source_parses_to_same_tree() {
+        let source = make_source(3);
+        for code in CORPUS.iter().copied().chain([source.as_str()]) {
+            // 構文エラーを含むCSTもトークンはそのまま書き出す
+            let cst = parse_cst(code);
+            let printed = print_cst(&cst);
+            assert_eq!(parse_cst(&printed), cst, "{}\n{}", code, printed);
+            assert_eq!(print_cst(&parse_cst(&printed)), printed, "{}", code);
+
+            let Some(ast) = parse_ast_without_errors(code) else {
+                continue;
+            };
+            let printed = print_ast(&ast);
+            assert_eq!(parse_ast(&printed), ast, "{}\n{}", code, printed);
+            assert_eq!(print_ast(&parse_ast(&printed)), printed, "{}", code);
+
+            // 括弧を外した木は、束縛力から括弧を付け直せば同じ木になる
+            let ungrouped = Ungrouper.fold_node(ast);
+            let printed = print_ast(&ungrouped);
+            let reparsed = Ungrouper.fold_node(parse_ast(&printed));
+            assert_eq!(reparsed, ungrouped, "{}\n{}", code, printed);
+        }
+    }
+
+    #[test]
+    fn print_canonical_source() {
+        let code = "#![a] fn  add(x:i32)->i32{let a=-(x+1)*2;ur b=(a-(x-1)).c(1)[2];if a{sr c=!(a==b);}else{nr d=a;}a}
+            struct A{x:f32,pub y:f32} impl A{fn new()->A{a::b(1.0,y)} fn b(&self){}}";
+        let expected = "#![a]
+
+fn add(x: i32) -> i32 {
+    let a = -(x + 1) * 2;
+    ur b = (a - (x - 1)).c(1)[2];
+    if a {
+        sr c = !(a == b);
+    } else {
+        nr d = a;
+    }
+    a
+}
+
+struct A {
+    x: f32,
+    y: f32,
+}
+
+impl A {
+    fn new() -> A {
+        a::b(1.0, y)
+    }
+
+    fn b(&self) {}
+}
+";
+        assert_eq!(print_ast(&parse_ast(code)), expected);
+        assert_eq!(
+            print_cst(&parse_cst(code)),
+            expected.replace("    y: f32,", "    pub y: f32")
+        );
+    }
+}
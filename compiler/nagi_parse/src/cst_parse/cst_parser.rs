@@ -1,39 +1,93 @@
-use std::collections::HashMap;
-use std::fs::OpenOptions;
-use std::io::Write;
+use std::rc::Rc;
 
 use crate::expression::*;
 use crate::lexer::Lexer;
+use crate::macro_expand::macro_expander::FragmentSpecifier;
+use crate::packrat::*;
 use crate::parser::*;
 
-use nagi_errors::{Error, ErrorKind, SyntaxError};
+use nagi_command_option::ParseLimits;
+use nagi_errors::{Error, SyntaxError};
 use nagi_syntax_tree::cst::*;
 use nagi_syntax_tree::keywords::Keyword;
+use nagi_syntax_tree::span::Span;
 use nagi_syntax_tree::token::*;
 
+// 規則の本体 左再帰の種を成長させるために何度か呼び出す
+type Rule = fn(&mut CSTParser) -> Result<CSTNode, Error>;
+
 pub struct CSTParser {
-    log: String,
     lexer: Lexer,
-    memo: HashMap<ParseMemoKey, Option<ParseMemoValue<CSTNode>>>,
-    min_bp: u16,
+    state: PackratState<CSTNode>,
+}
+
+impl PackratParser for CSTParser {
+    type Node = CSTNode;
+
+    fn lexer(&self) -> &Lexer {
+        &self.lexer
+    }
+
+    fn lexer_mut(&mut self) -> &mut Lexer {
+        &mut self.lexer
+    }
+
+    fn state(&mut self) -> &mut PackratState<CSTNode> {
+        &mut self.state
+    }
 }
 
 // TODO 機能ごとの分割
 impl CSTParser {
     pub fn new(token_list: &Vec<nagi_lexer::Token>) -> Self {
         Self {
-            log: "".to_string(),
             lexer: Lexer::new(token_list),
-            memo: HashMap::new(),
-            min_bp: 0,
+            state: PackratState::new(token_list.len()),
+        }
+    }
+
+    pub(crate) fn from_lexer(lexer: Lexer) -> Self {
+        let token_count = lexer.token_count();
+        Self {
+            lexer,
+            state: PackratState::new(token_count),
+        }
+    }
+
+    pub fn enable_trace(&mut self) {
+        self.start_trace();
+    }
+
+    pub fn set_limits(&mut self, limits: ParseLimits) {
+        self.state.limits = limits;
+    }
+
+    // 構文エラーがあっても途中までのCSTと全てのエラーを返す
+    pub fn parse(&mut self) -> ParseResult<CSTNode> {
+        let tree = self.crates_and_source_files();
+        let (errors, trace) = self.finish();
+
+        ParseResult {
+            tree,
+            errors,
+            trace,
         }
     }
 
-    pub fn parse(&mut self) -> Result<CSTNode, Error> {
-        self.crates_and_source_files()
+    // マクロのフラグメントとして先頭から解析できたトークン数
+    pub(crate) fn fragment_length(&mut self, fragment: FragmentSpecifier) -> Option<usize> {
+        let result = match fragment {
+            FragmentSpecifier::Expr => self.expression(),
+            FragmentSpecifier::Ty => self.type_expression(),
+            FragmentSpecifier::Pat => self.pattern(),
+            FragmentSpecifier::Block => self.block_expression(),
+            FragmentSpecifier::Ident | FragmentSpecifier::Tt => return None,
+        };
+
+        result.ok().map(|_| self.lexer.get_token_position())
     }
 
-    fn crates_and_source_files(&mut self) -> Result<CSTNode, Error> {
+    fn crates_and_source_files(&mut self) -> CSTNode {
         let mut inner_attributes = Vec::<CSTNode>::new();
         let mut items = Vec::<CSTNode>::new();
 
@@ -41,30 +95,58 @@ impl CSTParser {
         while let Ok(inner_attribute) = self.inner_attribute() {
             inner_attributes.push(inner_attribute);
         }
+
         // Item*
-        while let Ok(item) = self.item() {
-            items.push(item);
+        loop {
+            if let Ok(item) = self.item() {
+                items.push(item);
+                continue;
+            }
+
+            if matches!(self.lexer.peek(), Token::Eof) {
+                break;
+            }
+
+            // アイテムとして解析できなければ次のアイテムまで読み飛ばす
+            items.push(self.recover(false));
         }
 
-        //println!("{:#?}", self.memo);
+        CSTNode::new(
+            CSTNodeKind::Crate {
+                inner_attributes,
+                items,
+            },
+            vec![],
+        )
+    }
+
+    //
+    // Error recovery
+    //
 
-        if matches!(self.lexer.peek(), Token::Eof) {
-            self.log.push_str("Parse success\n");
+    // パニックモードでの復帰
+    // `;` `}` アイテムのキーワードまで読み飛ばし、読み飛ばしたトークンをErrorノードにする
+    // ブロック内(in_block)の場合、ブロックを閉じる`}`は読み飛ばさない
+    fn recover(&mut self, in_block: bool) -> CSTNode {
+        let start = self.skip_to_sync(in_block);
+        let end = self.lexer.get_token_position();
 
-            Ok(CSTNode::new(
-                CSTNodeKind::Crate {
-                    inner_attributes,
-                    items,
-                },
-                vec![],
-            ))
-        } else {
-            self.log.push_str("Parse error\n");
-            Err(Error {
-                error_kind: ErrorKind::Syntax(SyntaxError::NotMatch),
-                error_text: "".to_string(),
+        let tokens = (start..end)
+            .map(|position| {
+                let (row, column) = self.lexer.get_sorce_position_at(position);
+                CSTNode::new(
+                    CSTNodeKind::Factor {
+                        token: self.lexer.get_token_at(position),
+                        row,
+                        column,
+                    },
+                    vec![],
+                )
+                .with_span(self.lexer.span(position, position + 1))
             })
-        }
+            .collect();
+
+        CSTNode::new(CSTNodeKind::Error { tokens }, vec![])
     }
 
     //
@@ -73,45 +155,43 @@ impl CSTParser {
 
     // InnerAttribute ::= `#` `!` `[` Attribute `]`
     fn inner_attribute(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("InnerAttribute");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::InnerAttribute);
+        match self.get_memo(&key, Self::inner_attribute) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // `#`
-        if !matches!(self.lexer.peek(), Token::Pound) {
+        if !self.is_token(Token::Pound) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let pound = Box::new(self.make_factor_and_next());
+        let pound = Rc::new(self.make_factor_and_next());
 
         // `!`
-        if !matches!(self.lexer.peek(), Token::Not) {
+        if !self.is_token(Token::Not) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let exclamation = Box::new(self.make_factor_and_next());
+        let exclamation = Rc::new(self.make_factor_and_next());
 
         // `[`
-        if !matches!(
-            self.lexer.peek(),
-            Token::LeftParenthesis(LeftParenthesis::Brackets)
-        ) {
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brackets)) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let left_brackets = Box::new(self.make_factor_and_next());
+        let left_brackets = Rc::new(self.make_factor_and_next());
 
         // Attribute
-        let attribute = Box::new(self.attribute()?);
+        let attribute = Rc::new(self.attribute()?);
 
         // `]`
-        if !matches!(
-            self.lexer.peek(),
-            Token::RightParenthesis(RightParenthesis::Brackets)
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Brackets),
+            &left_brackets,
         ) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let right_brackets = Box::new(self.make_factor_and_next());
+        let right_brackets = Rc::new(self.make_factor_and_next());
 
         let node = CSTNode::new(
             CSTNodeKind::InnerAttribute {
@@ -123,45 +203,42 @@ impl CSTParser {
             },
             vec![],
         );
-        self.write_memo(&key, Some(&node));
 
         Ok(node)
     }
 
     // OuterAttribute ::= `#` `[` Attribute `]`
     fn outer_attribute(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("OuterAttribute");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::OuterAttribute);
+        match self.get_memo(&key, Self::outer_attribute) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // `#`
-        if !matches!(self.lexer.peek(), Token::Pound) {
+        if !self.is_token(Token::Pound) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let pound = Box::new(self.make_factor_and_next());
+        let pound = Rc::new(self.make_factor_and_next());
 
         // `[`
-        if !matches!(
-            self.lexer.peek(),
-            Token::LeftParenthesis(LeftParenthesis::Brackets)
-        ) {
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brackets)) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let left_brackets = Box::new(self.make_factor_and_next());
+        let left_brackets = Rc::new(self.make_factor_and_next());
 
-        let attribute = Box::new(self.attribute()?);
+        let attribute = Rc::new(self.attribute()?);
 
         // `]`
-        if !matches!(
-            self.lexer.peek(),
-            Token::RightParenthesis(RightParenthesis::Brackets)
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Brackets),
+            &left_brackets,
         ) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let right_brackets = Box::new(self.make_factor_and_next());
+        let right_brackets = Rc::new(self.make_factor_and_next());
 
         let node = CSTNode::new(
             CSTNodeKind::OuterAttribute {
@@ -172,70 +249,219 @@ impl CSTParser {
             },
             vec![],
         );
-        self.write_memo(&key, Some(&node));
 
         Ok(node)
     }
 
     // Attribute ::= SimplePath AttributeInput?  | `unsafe` `(` SimplePath AttributeInput? `)`
     fn attribute(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("Attribute");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::Attribute);
+        match self.get_memo(&key, Self::attribute) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
-        self.error(SyntaxError::NotMatch, &key)
+        // `unsafe` `(`
+        let mut unsafe_keyword = None;
+        let mut left_parenthesis = None;
+        if let Token::Keyword(Keyword::Unsafe) = self.lexer.peek() {
+            unsafe_keyword = Some(Rc::new(self.make_factor_and_next()));
+
+            if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Parenthesis)) {
+                return self.error(SyntaxError::ExpectedToken, &key);
+            }
+            left_parenthesis = Some(Rc::new(self.make_factor_and_next()));
+        }
+
+        // SimplePath
+        let simple_path = Rc::new(self.simple_path()?);
+
+        // AttributeInput?
+        let attribute_input = self.attribute_input().ok().map(Rc::new);
+
+        // `)`
+        let mut right_parenthesis = None;
+        if let Some(opener) = &left_parenthesis {
+            if !self.is_closing_token(
+                Token::RightParenthesis(RightParenthesis::Parenthesis),
+                opener,
+            ) {
+                return self.error(SyntaxError::ExpectedToken, &key);
+            }
+            right_parenthesis = Some(Rc::new(self.make_factor_and_next()));
+        }
+
+        let node = CSTNode::new(
+            CSTNodeKind::Attribute {
+                unsafe_keyword,
+                left_parenthesis,
+                simple_path,
+                attribute_input,
+                right_parenthesis,
+            },
+            vec![],
+        );
+
+        Ok(node)
     }
 
     // AttributeInput ::= DelimTokenTree | `=` Expression
     fn attribute_input(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("AttributeInput");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::AttributeInput);
+        match self.get_memo(&key, Self::attribute_input) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
-        self.error(SyntaxError::NotMatch, &key)
+        // DelimTokenTree
+        if let Token::LeftParenthesis(_) = self.lexer.peek() {
+            let delim_token_tree = Rc::new(self.delim_token_tree()?);
+
+            return Ok(CSTNode::new(
+                CSTNodeKind::AttributeInput {
+                    equal: None,
+                    expression: delim_token_tree,
+                },
+                vec![],
+            ));
+        }
+
+        // `=` Expression
+        let Token::Equal = self.lexer.peek() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        let equal = Some(Rc::new(self.make_factor_and_next()));
+        let expression = Rc::new(self.expression()?);
+
+        let node = CSTNode::new(CSTNodeKind::AttributeInput { equal, expression }, vec![]);
+
+        Ok(node)
     }
 
-    // Visibility ::= `pub`
-    //              | `pub` `(` `crate` `)`
-    //              | `pub` `(` `self` `)`
-    //              | `pub` `(` `super` `)`
-    //              | `pub` `(` `in` SimplePath `)`
-    fn visibility(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("Visibility");
-        match self.get_memo(&key) {
+    // SimplePath ::= `::`? SimplePathSegment (`::` SimplePathSegment)*
+    fn simple_path(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::SimplePath);
+        match self.get_memo(&key, Self::simple_path) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
-        // TODO
+        // `::`?
+        let mut path_separater = None;
+        if let Token::PathSeparater = self.lexer.peek_glue() {
+            path_separater = Some(Rc::new(self.make_glued_factor_and_next()));
+        }
 
-        // `pub`
-        let mut pub_keyword = CSTNode::new(
-            CSTNodeKind::Visibility {
-                pub_keyword: Box::new(self.make_factor()),
+        // SimplePathSegment
+        if !is_simple_path_segment(&self.lexer.peek()) {
+            self.expected("path");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let simple_path_segment = Rc::new(self.make_factor_and_next());
+
+        // (`::` SimplePathSegment)*
+        let mut repeat_simple_path_segment = Vec::<(CSTNode, CSTNode)>::new();
+        while let Token::PathSeparater = self.lexer.peek_glue() {
+            let separater = self.make_glued_factor_and_next();
+
+            if !is_simple_path_segment(&self.lexer.peek()) {
+                self.expected("identifier");
+                return self.error(SyntaxError::ExpectedToken, &key);
+            }
+            repeat_simple_path_segment.push((separater, self.make_factor_and_next()));
+        }
+
+        let node = CSTNode::new(
+            CSTNodeKind::SimplePath {
+                path_separater,
+                simple_path_segment,
+                repeat_simple_path_segment,
             },
             vec![],
         );
 
-        if matches!(self.lexer.peek(), Token::Keyword(Keyword::Pub)) {
-            return self.error(SyntaxError::ExpectedToken, &key);
+        Ok(node)
+    }
+
+    // DelimTokenTree ::= `(` TokenTree* `)` | `[` TokenTree* `]` | `{` TokenTree* `}`
+    // TokenTree ::= 括弧以外のトークン | DelimTokenTree
+    fn delim_token_tree(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::DelimTokenTree);
+        match self.get_memo(&key, Self::delim_token_tree) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let Token::LeftParenthesis(delimiter) = self.lexer.peek() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        let left_parenthesis = Rc::new(self.make_factor_and_next());
+
+        // TokenTree*
+        let mut token_tree = Vec::<CSTNode>::new();
+        loop {
+            match self.lexer.peek() {
+                Token::LeftParenthesis(_) => token_tree.push(self.delim_token_tree()?),
+                Token::RightParenthesis(_) | Token::Eof => break,
+                _ => token_tree.push(self.make_factor_and_next()),
+            }
         }
 
-        if matches!(
-            self.lexer.peek(),
-            Token::LeftParenthesis(LeftParenthesis::Parenthesis)
+        // 対応する閉じ括弧
+        if !self.is_closing_token(
+            Token::RightParenthesis(delimiter.closing()),
+            &left_parenthesis,
         ) {
-            return Ok(pub_keyword);
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let right_parenthesis = Rc::new(self.make_factor_and_next());
+
+        let node = CSTNode::new(
+            CSTNodeKind::DelimTokenTree {
+                left_parenthesis,
+                token_tree,
+                right_parenthesis,
+            },
+            vec![],
+        );
+
+        Ok(node)
+    }
+
+    // Visibility ::= `pub`
+    //              | `pub` `(` `crate` `)`
+    //              | `pub` `(` `self` `)`
+    //              | `pub` `(` `super` `)`
+    //              | `pub` `(` `in` SimplePath `)`
+    fn visibility(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::Visibility);
+        match self.get_memo(&key, Self::visibility) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `pub`
+        // TODO `pub` `(` ... `)`
+        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::Pub)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
         }
 
-        Ok(pub_keyword)
+        Ok(CSTNode::new(
+            CSTNodeKind::Visibility {
+                pub_keyword: Rc::new(self.make_factor_and_next()),
+            },
+            vec![],
+        ))
     }
 
     //
@@ -244,11 +470,12 @@ impl CSTParser {
 
     // Item ::= OuterAttribute* VisItem | MacroItem
     fn item(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("Item");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::Item);
+        match self.get_memo(&key, Self::item) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // OuterAttribute*
@@ -257,114 +484,118 @@ impl CSTParser {
             outer_attribute.push(expr);
         }
 
-        self.vis_item()
+        // MacroItem
+        if outer_attribute.is_empty() {
+            if let Ok(node) = self.macro_item() {
+                return Ok(node);
+            }
+        }
 
-        // TODO
+        let Ok(mut node) = self.vis_item() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        set_outer_attribute(&mut node, outer_attribute);
+
+        Ok(node)
     }
 
-    // VisItem ::= Visibility?
-    //           (
-    //             Module
-    //           | ExternCrate
-    //           | UseDeclaration
-    //           | Function
-    //           | TypeAlias
-    //           | Struct
-    //           | Enumeration
-    //           | Union
-    //           | ConstantItem
-    //           | StaticItem
-    //           | Trait
-    //           | Implementation
-    //           | ExternBlock
-    //           )
-    fn vis_item(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("VisItem");
-        match self.get_memo(&key) {
+    // MacroItem ::= MacroInvocationSemi | MacroRulesDefinition
+    fn macro_item(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::MacroItem);
+        match self.get_memo(&key, Self::macro_item) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
-        // Visibility?
-        let mut visibility = None;
-        if let Ok(expr) = self.visibility() {
-            visibility = Some(Box::new(expr));
+        if let Ok(node) = self.macro_rules_definition() {
+            return Ok(node);
         }
-
-        // Module
-        // ExternCrate
-        // UseDeclaration
-
-        // Function
-        if let Ok(expr) = self.function() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(expr);
+        if let Ok(node) = self.macro_invocation_semi() {
+            return Ok(node);
         }
 
-        // TypeAlias
-        // Struct
-        // Enumeration
-        // Union
-        // ConstantItem
-        // StaticItem
-        // Trait
-        // Implementation
-        // ExternBlock
-
         self.error(SyntaxError::NotMatch, &key)
     }
 
-    //
-    // Functions
-    //
-
-    // Function ::= FunctionQualifiers `fn` Identifier GenericParams?
-    //             `(` FunctionParameters? `)`
-    //             FunctionReturnType? WhereClause?
-    //             ( BlockExpression | `;` )
-    fn function(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("Function");
-        match self.get_memo(&key) {
+    // MacroInvocation ::= SimplePath `!` DelimTokenTree
+    fn macro_invocation(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::MacroInvocation);
+        match self.get_memo(&key, Self::macro_invocation) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
-        // FunctionQualifiers
-        let function_qualifiers = Box::new(self.function_qualifiers()?);
-
-        // `fn`
-        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::Fn)) {
-            return self.error(SyntaxError::ExpectedToken, &key);
+        // 組み込みマクロは引数を式として解析する
+        if let Ok(node) = self.builtin_macro_invocation() {
+            return Ok(node);
         }
-        let fn_keyword = Box::new(self.make_factor_and_next());
 
-        // Identifier
-        if !matches!(self.lexer.peek(), Token::Identifier(_)) {
-            return self.error(SyntaxError::ExpectedToken, &key);
+        // SimplePath
+        let simple_path = Rc::new(self.simple_path()?);
+
+        // `!`
+        // `!=`と区別するため一文字だけ見る
+        if !matches!(self.lexer.peek(), Token::Not)
+            || !matches!(self.lexer.peek_ahead(1), Token::LeftParenthesis(_))
+        {
+            return self.error(SyntaxError::NotMatch, &key);
         }
-        let identifier = Box::new(self.make_factor_and_next());
+        let not = Rc::new(self.make_factor_and_next());
 
-        // GenericParams?
-        let mut generic_params = None;
-        if let Ok(expr) = self.generic_params() {
-            generic_params = Some(Box::new(expr));
+        // DelimTokenTree
+        let delim_token_tree = Rc::new(self.delim_token_tree()?);
+
+        Ok(CSTNode::new(
+            CSTNodeKind::MacroInvocation {
+                simple_path,
+                not,
+                delim_token_tree,
+            },
+            vec![],
+        ))
+    }
+
+    // BuiltinMacroInvocation ::= BuiltinMacroName `!` `(` CallParams? `)`
+    // 引数が式でなければユーザー定義のマクロとして解析し直す
+    fn builtin_macro_invocation(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::BuiltinMacroInvocation);
+        match self.get_memo(&key, Self::builtin_macro_invocation) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // BuiltinMacroName
+        let Token::Identifier(name) = self.lexer.peek() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        if BuiltinMacro::from_name(&name).is_none()
+            || !matches!(self.lexer.peek_ahead(1), Token::Not)
+        {
+            return self.error(SyntaxError::NotMatch, &key);
         }
+        let identifier = Rc::new(self.make_factor_and_next());
+        let not = Rc::new(self.make_factor_and_next());
 
         // `(`
         if !matches!(
             self.lexer.peek(),
             Token::LeftParenthesis(LeftParenthesis::Parenthesis)
         ) {
-            return self.error(SyntaxError::ExpectedToken, &key);
+            return self.error(SyntaxError::NotMatch, &key);
         }
-        let left_parenthesis = Box::new(self.make_factor_and_next());
+        let left_parenthesis = Rc::new(self.make_factor_and_next());
 
-        // FunctionParameters?
-        let mut function_parameters = None;
-        if let Ok(param) = self.function_parameters() {
-            function_parameters = Some(Box::new(param));
+        // CallParams?
+        let mut call_params = None;
+        if let Ok(res) = self.call_params() {
+            call_params = Some(Rc::new(res));
         }
 
         // `)`
@@ -372,14 +603,249 @@ impl CSTParser {
             self.lexer.peek(),
             Token::RightParenthesis(RightParenthesis::Parenthesis)
         ) {
-            return self.error(SyntaxError::ExpectedToken, &key);
+            return self.error(SyntaxError::NotMatch, &key);
         }
-        let right_parenthesis = Box::new(self.make_factor_and_next());
+        let right_parenthesis = Rc::new(self.make_factor_and_next());
 
-        // FunctionReturnType?
-        let mut function_return_type = None;
+        Ok(CSTNode::new(
+            CSTNodeKind::BuiltinMacroInvocation {
+                identifier,
+                not,
+                left_parenthesis,
+                call_params,
+                right_parenthesis,
+            },
+            vec![],
+        ))
+    }
+
+    // MacroInvocationSemi ::= SimplePath `!` `(` TokenTree* `)` `;`
+    //                       | SimplePath `!` `[` TokenTree* `]` `;`
+    //                       | SimplePath `!` `{` TokenTree* `}`
+    fn macro_invocation_semi(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::MacroInvocationSemi);
+        match self.get_memo(&key, Self::macro_invocation_semi) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let CSTNodeKind::MacroInvocation {
+            simple_path,
+            not,
+            delim_token_tree,
+        } = self.macro_invocation()?.node_kind
+        else {
+            // 組み込みマクロは式文になる
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        // `{}`で囲んだものは`;`が無くてもよい
+        let mut semicolon = None;
+        if matches!(self.lexer.peek(), Token::Semicolon) {
+            semicolon = Some(Rc::new(self.make_factor_and_next()));
+        } else if !is_brace_token_tree(&delim_token_tree) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+
+        Ok(CSTNode::new(
+            CSTNodeKind::MacroInvocationSemi {
+                simple_path,
+                not,
+                delim_token_tree,
+                semicolon,
+            },
+            vec![],
+        ))
+    }
+
+    // MacroRulesDefinition ::= `macro_rules` `!` Identifier MacroRulesDef
+    // MacroRulesDef ::= `(` MacroRules `)` `;` | `[` MacroRules `]` `;` | `{` MacroRules `}`
+    fn macro_rules_definition(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::MacroRulesDefinition);
+        match self.get_memo(&key, Self::macro_rules_definition) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `macro_rules`
+        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::MacroRules)) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        let macro_rules_keyword = Rc::new(self.make_factor_and_next());
+
+        // `!`
+        if !self.is_token(Token::Not) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let not = Rc::new(self.make_factor_and_next());
+
+        // Identifier
+        let Token::Identifier(_) = self.lexer.peek() else {
+            self.expected("identifier");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        };
+        let identifier = Rc::new(self.make_factor_and_next());
+
+        // MacroRulesDef
+        let delim_token_tree = Rc::new(self.delim_token_tree()?);
+        let mut semicolon = None;
+        if !is_brace_token_tree(&delim_token_tree) {
+            if !self.is_token(Token::Semicolon) {
+                return self.error(SyntaxError::MissingSemicolon, &key);
+            }
+            semicolon = Some(Rc::new(self.make_factor_and_next()));
+        }
+
+        Ok(CSTNode::new(
+            CSTNodeKind::MacroRulesDefinition {
+                macro_rules_keyword,
+                not,
+                identifier,
+                delim_token_tree,
+                semicolon,
+            },
+            vec![],
+        ))
+    }
+
+    // VisItem ::= Visibility?
+    //           (
+    //             Module
+    //           | ExternCrate
+    //           | UseDeclaration
+    //           | Function
+    //           | TypeAlias
+    //           | Struct
+    //           | Enumeration
+    //           | Union
+    //           | ConstantItem
+    //           | StaticItem
+    //           | Trait
+    //           | Implementation
+    //           | ExternBlock
+    //           )
+    fn vis_item(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::VisItem);
+        match self.get_memo(&key, Self::vis_item) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Visibility?
+        let mut visibility = None;
+        if let Ok(expr) = self.visibility() {
+            visibility = Some(Rc::new(expr));
+        }
+
+        // Module
+        // ExternCrate
+        // UseDeclaration
+
+        // Function
+        if let Ok(mut expr) = self.function() {
+            set_visibility(&mut expr, visibility);
+            return Ok(expr);
+        }
+
+        // TypeAlias
+        if let Ok(mut expr) = self.type_alias() {
+            set_visibility(&mut expr, visibility);
+            return Ok(expr);
+        }
+
+        // Struct
+        if let Ok(mut expr) = self.struct_item() {
+            set_visibility(&mut expr, visibility);
+            return Ok(expr);
+        }
+
+        // Enumeration
+        // Union
+        // ConstantItem
+        // StaticItem
+        // Trait
+
+        // Implementation
+        if let Ok(mut expr) = self.implementation() {
+            set_visibility(&mut expr, visibility);
+            return Ok(expr);
+        }
+
+        // ExternBlock
+
+        self.error(SyntaxError::NotMatch, &key)
+    }
+
+    //
+    // Functions
+    //
+
+    // Function ::= FunctionQualifiers `fn` Identifier GenericParams?
+    //             `(` FunctionParameters? `)`
+    //             FunctionReturnType? WhereClause?
+    //             ( BlockExpression | `;` )
+    fn function(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::Function);
+        match self.get_memo(&key, Self::function) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // FunctionQualifiers
+        let function_qualifiers = Rc::new(self.function_qualifiers()?);
+
+        // `fn`
+        if !self.is_token(Token::Keyword(Keyword::Fn)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let fn_keyword = Rc::new(self.make_factor_and_next());
+
+        // Identifier
+        if !matches!(self.lexer.peek(), Token::Identifier(_)) {
+            self.expected("identifier");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let identifier = Rc::new(self.make_factor_and_next());
+
+        // GenericParams?
+        let mut generic_params = None;
+        if let Ok(expr) = self.generic_params() {
+            generic_params = Some(Rc::new(expr));
+        }
+
+        // `(`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Parenthesis)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_parenthesis = Rc::new(self.make_factor_and_next());
+
+        // FunctionParameters?
+        let mut function_parameters = None;
+        if let Ok(param) = self.function_parameters() {
+            function_parameters = Some(Rc::new(param));
+        }
+
+        // `)`
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Parenthesis),
+            &left_parenthesis,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let right_parenthesis = Rc::new(self.make_factor_and_next());
+
+        // FunctionReturnType?
+        let mut function_return_type = None;
         if let Ok(expr) = self.function_return_type() {
-            function_return_type = Some(Box::new(expr));
+            function_return_type = Some(Rc::new(expr));
         }
 
         // WhereClause?
@@ -389,6 +855,8 @@ impl CSTParser {
         if let Token::Semicolon = self.lexer.peek() {
             let node = CSTNode::new(
                 CSTNodeKind::Function {
+                    outer_attribute: vec![],
+                    visibility: None,
                     function_qualifiers,
                     fn_keyword,
                     identifier,
@@ -398,18 +866,19 @@ impl CSTParser {
                     right_parenthesis,
                     function_return_type,
                     where_clause,
-                    block_expression_or_semicolon: Box::new(self.make_factor_and_next()),
+                    block_expression_or_semicolon: Rc::new(self.make_factor_and_next()),
                 },
                 vec![],
             );
 
-            self.write_memo(&key, Some(&node));
             return Ok(node);
         }
 
         if let Ok(expr) = self.block_expression() {
             let node = CSTNode::new(
                 CSTNodeKind::Function {
+                    outer_attribute: vec![],
+                    visibility: None,
                     function_qualifiers,
                     fn_keyword,
                     identifier,
@@ -419,12 +888,11 @@ impl CSTParser {
                     right_parenthesis,
                     function_return_type,
                     where_clause,
-                    block_expression_or_semicolon: Box::new(expr),
+                    block_expression_or_semicolon: Rc::new(expr),
                 },
                 vec![],
             );
 
-            self.write_memo(&key, Some(&node));
             return Ok(node);
         }
 
@@ -433,11 +901,12 @@ impl CSTParser {
 
     // FunctionQualifiers ::= `const`? `async`? ItemSafety? (`extern` Abi?)?
     fn function_qualifiers(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("FunctionQualifiers");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::FunctionQualifiers);
+        match self.get_memo(&key, Self::function_qualifiers) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         let mut const_keyword = None;
@@ -448,25 +917,25 @@ impl CSTParser {
 
         // `const`?
         if matches!(self.lexer.peek(), Token::Keyword(Keyword::Const)) {
-            const_keyword = Some(Box::new(self.make_factor_and_next()));
+            const_keyword = Some(Rc::new(self.make_factor_and_next()));
         }
 
         // `async`?
         if matches!(self.lexer.peek(), Token::Keyword(Keyword::Async)) {
-            async_keyword = Some(Box::new(self.make_factor_and_next()));
+            async_keyword = Some(Rc::new(self.make_factor_and_next()));
         }
 
         // ItemSafety?
         if let Ok(expr) = self.item_safety() {
-            item_safety = Some(Box::new(expr));
+            item_safety = Some(Rc::new(expr));
         }
 
         // (`extern` `Abi`?)?
         if matches!(self.lexer.peek(), Token::Keyword(Keyword::Extern)) {
-            extern_keyword = Some(Box::new(self.make_factor_and_next()));
+            extern_keyword = Some(Rc::new(self.make_factor_and_next()));
 
             if let Ok(expr) = self.abi() {
-                abi = Some(Box::new(expr));
+                abi = Some(Rc::new(expr));
             }
         }
 
@@ -480,18 +949,18 @@ impl CSTParser {
             },
             vec![],
         );
-        self.write_memo(&key, Some(&node));
 
         Ok(node)
     }
 
     // ItemSafety ::= `safe` | `unsafe`
     fn item_safety(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("ItemSafety");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::ItemSafety);
+        match self.get_memo(&key, Self::item_safety) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         match self.lexer.peek() {
@@ -508,11 +977,12 @@ impl CSTParser {
 
     // Abi ::= STRING_LITERAL | RAW_STRING_LITERAL
     fn abi(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("Abi");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::Abi);
+        match self.get_memo(&key, Self::abi) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         match self.lexer.peek() {
@@ -529,20 +999,27 @@ impl CSTParser {
 
     // GenericParams ::= `<` `>` | `<` (GenericParam `,`)* GenericParam `,`? `>`
     fn generic_params(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("GenericParams");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::GenericParams);
+        match self.get_memo(&key, Self::generic_params) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // `<` `>`
-        if !matches!(self.lexer.peek(), Token::LessThan) {
+        if !self.is_token(Token::LessThan) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        self.make_factor_and_next();
+        let less_than = self.make_factor_and_next();
         if matches!(self.lexer.peek(), Token::GreaterThan) {
-            return Ok(self.make_factor_and_next());
+            return Ok(CSTNode::new(
+                CSTNodeKind::GenericParams {
+                    less_than: Rc::new(less_than),
+                    greater_than: Rc::new(self.make_factor_and_next()),
+                },
+                vec![],
+            ));
         }
 
         // (GenericParam `,`)* GenericParam `,`?
@@ -553,11 +1030,12 @@ impl CSTParser {
 
     // FunctionParameters ::= SelfParam `,`? | (SelfParam `,`)? FunctionParam (`,` FunctionParam)* `,`?
     fn function_parameters(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("FunctionParameters");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::FunctionParameters);
+        match self.get_memo(&key, Self::function_parameters) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // SelfParam
@@ -575,12 +1053,11 @@ impl CSTParser {
             // SelfParamのみ
             let node = CSTNode::new(
                 CSTNodeKind::FunctionParam1 {
-                    self_param: Box::new(first_self_param.unwrap()),
+                    self_param: Rc::new(first_self_param.unwrap()),
                     comma: None,
                 },
                 vec![],
             );
-            self.write_memo(&key, Some(&node));
 
             return Ok(node);
         } else if first_self_param.is_err() && commna.is_some() {
@@ -588,38 +1065,43 @@ impl CSTParser {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
         let self_param = if first_self_param.is_ok() && commna.is_some() {
-            Some((
-                Box::new(first_self_param.unwrap()),
-                Box::new(commna.unwrap()),
-            ))
+            Some((Rc::new(first_self_param.unwrap()), Rc::new(commna.unwrap())))
         } else {
             None
         };
 
         // FunctionParam
-        let function_param = Box::new(self.function_param()?);
+        let Ok(function_param) = self.function_param() else {
+            // SelfParam `,`
+            if let Some((self_param, comma)) = self_param {
+                return Ok(CSTNode::new(
+                    CSTNodeKind::FunctionParam1 {
+                        self_param,
+                        comma: Some(comma),
+                    },
+                    vec![],
+                ));
+            }
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        let function_param = Rc::new(function_param);
 
         // (`,` FunctionParam)* `,`?
         let mut function_param_repeat = Vec::<(CSTNode, CSTNode)>::new();
+        let mut last_comma = None;
         while let Token::Comma = self.lexer.peek() {
             // `,`
             let comma = self.make_factor_and_next();
 
             // FunctionParam
             let Ok(param) = self.function_param() else {
+                last_comma = Some(Rc::new(comma));
                 break;
             };
 
             function_param_repeat.push((comma, param));
         }
 
-        // `,`
-        let last_comma = if matches!(self.lexer.peek(), Token::Comma) {
-            Some(Box::new(self.make_factor_and_next()))
-        } else {
-            None
-        };
-
         let node = CSTNode::new(
             CSTNodeKind::FunctionParam2 {
                 self_param,
@@ -630,17 +1112,17 @@ impl CSTParser {
             vec![],
         );
 
-        self.write_memo(&key, Some(&node));
         Ok(node)
     }
 
     // SelfParam ::= OuterAttribute* ( ShorthandSelf | TypedSelf )
     fn self_param(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("Selfparam");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::SelfParam);
+        match self.get_memo(&key, Self::self_param) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // OuterAttribute*
@@ -649,84 +1131,121 @@ impl CSTParser {
             outer_attribute.push(expr);
         }
 
-        // ( ShorthandSelf | TypedSelf )
-        if let Ok(expr) = self.shorthand_self() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(expr);
-        } else if let Ok(expr) = self.typed_self() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(expr);
+        // ( TypedSelf | ShorthandSelf )
+        // `self`だけではTypedSelfの途中と区別できないので、TypedSelfから試す
+        let Ok(mut node) = self.typed_self().or_else(|_| self.shorthand_self()) else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        // OuterAttributeはSelfParam自身に持たせる
+        if let CSTNodeKind::ShorthandSelf {
+            outer_attribute: attribute,
+            ..
+        }
+        | CSTNodeKind::TypedSelf {
+            outer_attribute: attribute,
+            ..
+        } = &mut node.node_kind
+        {
+            *attribute = outer_attribute;
+            node.update_span();
         }
 
-        self.error(SyntaxError::NotMatch, &key)
+        Ok(node)
     }
 
     // ShorthandSelf ::= (`&` | `&` Lifetime)? `mut`? `self`
     fn shorthand_self(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("ShorthandSelf");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::ShorthandSelf);
+        match self.get_memo(&key, Self::shorthand_self) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // (`&` | `&` Lifetime)?
+        // TODO Lifetime
+        let mut and = None;
         if matches!(self.lexer.peek(), Token::And) {
-            // TODO
+            and = Some(Rc::new(self.make_factor_and_next()));
         }
 
         // `mut`?
+        let mut mut_keyword = None;
         if matches!(self.lexer.peek(), Token::Keyword(Keyword::Mut)) {
-            // TODO
+            mut_keyword = Some(Rc::new(self.make_factor_and_next()));
         }
 
         // `self`
-        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::SelfValue)) {
+        if !self.is_token(Token::Keyword(Keyword::SelfValue)) {
             return self.error(SyntaxError::NotMatch, &key);
         }
+        let self_keyword = Rc::new(self.make_factor_and_next());
 
-        self.error(SyntaxError::NotMatch, &key)
+        Ok(CSTNode::new(
+            CSTNodeKind::ShorthandSelf {
+                outer_attribute: vec![],
+                and,
+                mut_keyword,
+                self_keyword,
+            },
+            vec![],
+        ))
     }
 
     // TypedSelf ::= `mut`? `self` `:` Type
     fn typed_self(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("TypedSelf");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::TypedSelf);
+        match self.get_memo(&key, Self::typed_self) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
-        let mut node = self.make_factor();
-
         // `mut`?
+        let mut mut_keyword = None;
         if matches!(self.lexer.peek(), Token::Keyword(Keyword::Mut)) {
-            // TODO
+            mut_keyword = Some(Rc::new(self.make_factor_and_next()));
         }
 
         // `self`
-        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::SelfValue)) {
+        if !self.is_token(Token::Keyword(Keyword::SelfValue)) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
+        let self_keyword = Rc::new(self.make_factor_and_next());
 
         // `:`
-        if !matches!(self.lexer.peek(), Token::Colon) {
+        if !self.is_token(Token::Colon) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
+        let colon = Rc::new(self.make_factor_and_next());
 
         // Type
-        self.type_expression()?;
+        let type_expression = Rc::new(self.type_expression()?);
 
-        Ok(node)
+        Ok(CSTNode::new(
+            CSTNodeKind::TypedSelf {
+                outer_attribute: vec![],
+                mut_keyword,
+                self_keyword,
+                colon,
+                type_expression,
+            },
+            vec![],
+        ))
     }
 
     // FunctionParam ::= OuterAttribute* ( FunctionParamPattern | `...` | Type )
+    // TODO `...`とTypeだけの引数
     fn function_param(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("FunctionParam");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::FunctionParam);
+        match self.get_memo(&key, Self::function_param) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // OuterAttribute*
@@ -735,77 +1254,479 @@ impl CSTParser {
             outer_attribute.push(expr);
         }
 
-        // `...`
-        if let Token::DotDotDot = self.lexer.peek() {
-            self.lexer.next();
-            // TODO
+        // FunctionParamPattern
+        let Ok(mut node) = self.function_param_pattern() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        if let CSTNodeKind::FunctionParam {
+            outer_attribute: attribute,
+            ..
+        } = &mut node.node_kind
+        {
+            *attribute = outer_attribute;
+            node.update_span();
         }
 
-        // FunctionParamPattern
-        if let Ok(expr) = self.function_param_pattern() {
-            return Ok(expr);
+        Ok(node)
+    }
+
+    // FunctionParamPattern ::= PatternNoTopAlt `:` ( Type | `...` )
+    fn function_param_pattern(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::FunctionParamPattern);
+        match self.get_memo(&key, Self::function_param_pattern) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // PatternNoTopAlt
+        let pattern_no_top_alt = Rc::new(self.pattern_no_top_alt()?);
+
+        // `:`
+        if !self.is_token(Token::Colon) {
+            return self.error(SyntaxError::NotMatch, &key);
         }
+        let colon = Rc::new(self.make_factor_and_next());
 
         // Type
+        let type_expression = Rc::new(self.type_expression()?);
 
-        self.error(SyntaxError::NotMatch, &key)
+        Ok(CSTNode::new(
+            CSTNodeKind::FunctionParam {
+                outer_attribute: vec![],
+                pattern_no_top_alt,
+                colon,
+                type_expression,
+            },
+            vec![],
+        ))
     }
 
-    // FunctionParamPattern ::= PatternNoTopAlt `:` ( Type | `...` )
-    fn function_param_pattern(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("FunctionParamPattern");
-        match self.get_memo(&key) {
+    // FunctionReturnType ::= `->` Type
+    fn function_return_type(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::FunctionReturnType);
+        match self.get_memo(&key, Self::function_return_type) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `->`
+        if !matches!(self.lexer.peek_glue(), Token::RightAllow) {
+            self.expected("`->`");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let right_allow = Rc::new(self.make_glued_factor_and_next());
+
+        // Type
+        let type_expression = Rc::new(self.type_expression()?);
+
+        Ok(CSTNode::new(
+            CSTNodeKind::FunctionReturnType {
+                right_allow,
+                type_expression,
+            },
+            vec![],
+        ))
+    }
+
+    //
+    // Type Aliases
+    //
+
+    // TypeAlias ::= `type` Identifier `=` Type `;`
+    // TODO GenericParams、TypeParamBounds、WhereClause
+    fn type_alias(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::TypeAlias);
+        match self.get_memo(&key, Self::type_alias) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `type`
+        if !self.is_token(Token::Keyword(Keyword::Type)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let type_keyword = Rc::new(self.make_factor_and_next());
+
+        // Identifier
+        if !matches!(self.lexer.peek(), Token::Identifier(_)) {
+            self.expected("identifier");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let identifier = Rc::new(self.make_factor_and_next());
+
+        // `=`
+        if !self.is_token(Token::Equal) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let equal = Rc::new(self.make_factor_and_next());
+
+        // Type
+        let type_expression = Rc::new(self.type_expression()?);
+
+        // `;`
+        if !self.is_token(Token::Semicolon) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let semicolon = Rc::new(self.make_factor_and_next());
+
+        Ok(CSTNode::new(
+            CSTNodeKind::TypeAlias {
+                outer_attribute: vec![],
+                visibility: None,
+                type_keyword,
+                identifier,
+                equal,
+                type_expression,
+                semicolon,
+            },
+            vec![],
+        ))
+    }
+
+    //
+    // Structs
+    //
+
+    // Struct ::= `struct` Identifier ( `{` StructFields? `}` | `;` )
+    // TODO TupleStruct、GenericParams、WhereClause
+    fn struct_item(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::Struct);
+        match self.get_memo(&key, Self::struct_item) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `struct`
+        if !self.is_token(Token::Keyword(Keyword::Struct)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let struct_keyword = Rc::new(self.make_factor_and_next());
+
+        // Identifier
+        if !matches!(self.lexer.peek(), Token::Identifier(_)) {
+            self.expected("identifier");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let identifier = Rc::new(self.make_factor_and_next());
+
+        // `;`
+        if matches!(self.lexer.peek(), Token::Semicolon) {
+            return Ok(CSTNode::new(
+                CSTNodeKind::Struct {
+                    outer_attribute: vec![],
+                    visibility: None,
+                    struct_keyword,
+                    identifier,
+                    left_brace: None,
+                    struct_fields: None,
+                    right_brace_or_semicolon: Rc::new(self.make_factor_and_next()),
+                },
+                vec![],
+            ));
+        }
+
+        // `{`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brace)) {
+            self.expected("`;`");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_brace = Rc::new(self.make_factor_and_next());
+
+        // StructFields?
+        let struct_fields = self.struct_fields().ok().map(Rc::new);
+
+        // `}`
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Brace),
+            &left_brace,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let right_brace = Rc::new(self.make_factor_and_next());
+
+        Ok(CSTNode::new(
+            CSTNodeKind::Struct {
+                outer_attribute: vec![],
+                visibility: None,
+                struct_keyword,
+                identifier,
+                left_brace: Some(left_brace),
+                struct_fields,
+                right_brace_or_semicolon: right_brace,
+            },
+            vec![],
+        ))
+    }
+
+    // StructFields ::= StructField (`,` StructField)* `,`?
+    fn struct_fields(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::StructFields);
+        match self.get_memo(&key, Self::struct_fields) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // StructField
+        let struct_field = Rc::new(self.struct_field()?);
+
+        // (`,` StructField)*
+        let mut repeat_struct_field = Vec::<(CSTNode, CSTNode)>::new();
+        let mut comma = None;
+        while let Token::Comma = self.lexer.peek() {
+            // `,`
+            let separator = self.make_factor_and_next();
+
+            // StructField
+            let Ok(field) = self.struct_field() else {
+                // `,`?
+                comma = Some(Rc::new(separator));
+                break;
+            };
+
+            repeat_struct_field.push((separator, field));
+        }
+
+        Ok(CSTNode::new(
+            CSTNodeKind::StructFields {
+                struct_field,
+                repeat_struct_field,
+                comma,
+            },
+            vec![],
+        ))
+    }
+
+    // StructField ::= OuterAttribute* Visibility? Identifier `:` Type
+    fn struct_field(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::StructField);
+        match self.get_memo(&key, Self::struct_field) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // OuterAttribute*
+        let mut outer_attribute = Vec::<CSTNode>::new();
+        while let Ok(expr) = self.outer_attribute() {
+            outer_attribute.push(expr);
+        }
+
+        // Visibility?
+        let visibility = self.visibility().ok().map(Rc::new);
+
+        // Identifier
+        if !matches!(self.lexer.peek(), Token::Identifier(_)) {
+            self.expected("identifier");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let identifier = Rc::new(self.make_factor_and_next());
+
+        // `:`
+        if !self.is_token(Token::Colon) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let colon = Rc::new(self.make_factor_and_next());
+
+        // Type
+        let type_expression = Rc::new(self.type_expression()?);
+
+        Ok(CSTNode::new(
+            CSTNodeKind::StructField {
+                outer_attribute,
+                visibility,
+                identifier,
+                colon,
+                type_expression,
+            },
+            vec![],
+        ))
+    }
+
+    //
+    // Implementations
+    //
+
+    // Implementation ::= InherentImpl | TraitImpl
+    // InherentImpl   ::= `impl` Type `{` AssociatedItem* `}`
+    // TraitImpl      ::= `impl` TypePath `for` Type `{` AssociatedItem* `}`
+    // TODO GenericParams、WhereClause、InnerAttribute
+    fn implementation(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::Implementation);
+        match self.get_memo(&key, Self::implementation) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `impl`
+        if !self.is_token(Token::Keyword(Keyword::Impl)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let impl_keyword = Rc::new(self.make_factor_and_next());
+
+        // Type
+        let mut type_expression = Rc::new(self.type_expression()?);
+
+        // (`for` Type)?
+        // `for`があれば先に読んだ型はトレイト
+        let mut trait_path = None;
+        if matches!(self.lexer.peek(), Token::Keyword(Keyword::For)) {
+            let for_keyword = Rc::new(self.make_factor_and_next());
+            trait_path = Some((type_expression, for_keyword));
+            type_expression = Rc::new(self.type_expression()?);
+        }
+
+        // `{`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brace)) {
+            self.expected("`for`");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_brace = Rc::new(self.make_factor_and_next());
+
+        // AssociatedItem*
+        let mut associated_items = vec![];
+        while let Ok(item) = self.associated_item() {
+            associated_items.push(item);
+        }
+
+        // `}`
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Brace),
+            &left_brace,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let right_brace = Rc::new(self.make_factor_and_next());
+
+        Ok(CSTNode::new(
+            CSTNodeKind::Implementation {
+                outer_attribute: vec![],
+                visibility: None,
+                impl_keyword,
+                trait_path,
+                type_expression,
+                left_brace,
+                associated_items,
+                right_brace,
+            },
+            vec![],
+        ))
+    }
+
+    // AssociatedItem ::= OuterAttribute* ( TypeAlias | Visibility? Function )
+    // TODO MacroInvocationSemi、ConstantItem
+    fn associated_item(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::AssociatedItem);
+        match self.get_memo(&key, Self::associated_item) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // OuterAttribute*
+        let mut outer_attribute = Vec::<CSTNode>::new();
+        while let Ok(expr) = self.outer_attribute() {
+            outer_attribute.push(expr);
+        }
+
+        // TypeAlias | Visibility? Function
+        let Ok(mut node) = self.type_alias().or_else(|_| -> Result<CSTNode, Error> {
+            let visibility = self.visibility().ok().map(Rc::new);
+            let mut node = self.function()?;
+            set_visibility(&mut node, visibility);
+            Ok(node)
+        }) else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        set_outer_attribute(&mut node, outer_attribute);
+
+        Ok(node)
+    }
+
+    //
+    // Type
+    //
+
+    // Type ::= TypeNoBounds | ImplTraitType | TraitObjectType
+    // TODO TypePath以外の型
+    fn type_expression(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::Type);
+        match self.get_memo(&key, Self::type_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
-        // PatternNoTopAlt
-        self.pattern_no_top_alt()?;
-
-        // `:`
-        if !matches!(self.lexer.peek(), Token::Colon) {
+        let Ok(node) = self.type_path() else {
+            self.expected("type");
             return self.error(SyntaxError::NotMatch, &key);
-        }
-
-        // ( Type | `...` )
-        match self.lexer.peek_glue() {
-            Token::DotDotDot => (),
-            _ => (),
         };
 
-        // TODO
-        self.error(SyntaxError::NotMatch, &key)
+        Ok(node)
     }
 
-    // FunctionReturnType ::= `->` Type
-    fn function_return_type(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("FunctionReturnType");
-        match self.get_memo(&key) {
+    // TypePath ::= `::`? TypePathSegment (`::` TypePathSegment)*
+    // TypePathSegment ::= PathIdentSegment
+    // TODO GenericArgs
+    fn type_path(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::TypePath);
+        match self.get_memo(&key, Self::type_path) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
-        self.error(SyntaxError::NotMatch, &key)
-    }
+        // `::`?
+        let mut path_separater = None;
+        if matches!(self.lexer.peek_glue(), Token::PathSeparater) {
+            path_separater = Some(Rc::new(self.make_glued_factor_and_next()));
+        }
 
-    // Structs
+        // TypePathSegment
+        let path_ident_segment = Rc::new(self.path_ident_segment()?);
 
-    //
-    // Type
-    //
+        // (`::` TypePathSegment)*
+        let mut repeat_path_ident_segment = Vec::<(CSTNode, CSTNode)>::new();
+        while matches!(self.lexer.peek_glue(), Token::PathSeparater) {
+            let position = self.lexer.get_token_position();
 
-    // Type ::= TypeNoBounds | ImplTraitType | TraitObjectType
-    fn type_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("FunctionReturnType");
-        match self.get_memo(&key) {
-            MemoResult::Some(res) => return Ok(res),
-            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
-        };
+            // `::`
+            let separator = self.make_glued_factor_and_next();
 
-        self.error(SyntaxError::NotMatch, &key)
+            // TypePathSegment
+            let Ok(segment) = self.path_ident_segment() else {
+                self.backtrack(position);
+                break;
+            };
+
+            repeat_path_ident_segment.push((separator, segment));
+        }
+
+        Ok(CSTNode::new(
+            CSTNodeKind::TypePath {
+                path_separater,
+                path_ident_segment,
+                repeat_path_ident_segment,
+            },
+            vec![],
+        ))
     }
 
     //
@@ -814,23 +1735,26 @@ impl CSTParser {
 
     // Expression ::= ExpressionWithoutBlock | ExpressionWithBlock
     fn expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("Expression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::Expression);
+        match self.get_memo(&key, Self::expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
+        let checkpoint = self.state.furthest.checkpoint();
 
         if let Ok(expr) = self.expression_without_block() {
-            self.write_memo(&key, Some(&expr));
             return Ok(expr);
         }
 
         if let Ok(expr) = self.expression_with_block() {
-            self.write_memo(&key, Some(&expr));
             return Ok(expr);
         }
 
+        self.state
+            .furthest
+            .summarize(checkpoint, key.position, "expression");
         self.error(SyntaxError::NotMatch, &key)
     }
 
@@ -842,12 +1766,14 @@ impl CSTParser {
     //                              | ContinueExpression | BreakExpression | RangeExpression | ReturnExpression | UnderscoreExpression | MacroInvocation
     //                           )
     fn expression_without_block(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("ExpressionWithoutBlock");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::ExpressionWithoutBlock);
+        match self.get_memo(&key, Self::expression_without_block) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
+        let checkpoint = self.state.furthest.checkpoint();
 
         // OuterAttribute*
         let mut outer_attribute = Vec::<CSTNode>::new();
@@ -857,111 +1783,96 @@ impl CSTParser {
 
         // OperatorExpression
         if let Ok(expr) = self.operator_expression() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(CSTNode::new(
-                CSTNodeKind::ExpressionWithoutBlock {
-                    outer_attribute,
-                    expression: Box::new(expr),
-                },
-                vec![],
-            ));
-        }
-
-        // LiteralExpression
-        if let Ok(expr) = self.literal_expression() {
-            self.write_memo(&key, Some(&expr));
             return Ok(CSTNode::new(
                 CSTNodeKind::ExpressionWithoutBlock {
                     outer_attribute,
-                    expression: Box::new(expr),
+                    expression: Rc::new(expr),
                 },
                 vec![],
             ));
         }
 
-        // PathExpression
-        if let Ok(expr) = self.path_expression() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(CSTNode::new(
-                CSTNodeKind::ExpressionWithoutBlock {
-                    outer_attribute,
-                    expression: Box::new(expr),
-                },
-                vec![],
-            ));
-        }
-
-        // GroupedExpression
-        if let Ok(expr) = self.grouped_expression() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(CSTNode::new(
-                CSTNodeKind::ExpressionWithoutBlock {
-                    outer_attribute,
-                    expression: Box::new(expr),
-                },
-                vec![],
-            ));
+        // 演算子を含まない式
+        if let Ok(operand) = self.operand() {
+            if let CSTNodeKind::ExpressionWithoutBlock { expression, .. } = operand.node_kind {
+                return Ok(CSTNode::new(
+                    CSTNodeKind::ExpressionWithoutBlock {
+                        outer_attribute,
+                        expression,
+                    },
+                    vec![],
+                ));
+            }
         }
 
-        // StructExpression
-        if let Ok(expr) = self.struct_expression() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(CSTNode::new(
-                CSTNodeKind::ExpressionWithoutBlock {
-                    outer_attribute,
-                    expression: Box::new(expr),
-                },
-                vec![],
-            ));
-        }
+        self.state
+            .furthest
+            .summarize(checkpoint, key.position, "expression");
+        self.error(SyntaxError::NotMatch, &key)
+    }
 
-        // CallExpression
-        if let Ok(expr) = self.call_expression() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(CSTNode::new(
-                CSTNodeKind::ExpressionWithoutBlock {
-                    outer_attribute,
-                    expression: Box::new(expr),
-                },
-                vec![],
-            ));
-        }
+    // 演算子式の被演算子
+    // Expressionから演算子式を除いたもの
+    // CallExpressionなどの左再帰の起点になる
+    fn operand(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::Operand);
+        match self.get_memo(&key, Self::operand) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+        let checkpoint = self.state.furthest.checkpoint();
 
-        // MethodCallExpression
-        if let Ok(expr) = self.method_call_expression() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(CSTNode::new(
-                CSTNodeKind::ExpressionWithoutBlock {
-                    outer_attribute,
-                    expression: Box::new(expr),
-                },
-                vec![],
-            ));
+        // ExpressionWithoutBlock
+        // 左再帰する規則を先に試す
+        let rules: [Rule; 12] = [
+            Self::call_expression,
+            Self::method_call_expression,
+            Self::field_expression,
+            Self::await_expression,
+            Self::index_expression,
+            Self::literal_expression,
+            Self::macro_invocation,
+            Self::path_expression,
+            Self::grouped_expression,
+            Self::struct_expression,
+            Self::return_expression,
+            Self::async_block_expression,
+        ];
+        for rule in rules {
+            if let Ok(expr) = rule(self) {
+                return Ok(CSTNode::new(
+                    CSTNodeKind::ExpressionWithoutBlock {
+                        outer_attribute: vec![],
+                        expression: Rc::new(expr),
+                    },
+                    vec![],
+                ));
+            }
         }
 
-        // ReturnExpression
-        if let Ok(expr) = self.return_expression() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(CSTNode::new(
-                CSTNodeKind::ExpressionWithoutBlock {
-                    outer_attribute,
-                    expression: Box::new(expr),
-                },
-                vec![],
-            ));
+        // ExpressionWithBlock
+        if let Ok(expr) = self.expression_with_block() {
+            return Ok(expr);
         }
 
+        self.state
+            .furthest
+            .summarize(checkpoint, key.position, "expression");
         self.error(SyntaxError::NotMatch, &key)
     }
 
     fn literal_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("LiteralExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::LiteralExpression);
+        match self.get_memo(&key, Self::literal_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
+        let start = self.lexer.get_token_position();
         let pos = self.lexer.get_sorce_position();
         let literal = match self.lexer.next() {
             Token::Literal(literal) => CSTNode::new(
@@ -993,28 +1904,26 @@ impl CSTParser {
             _ => return self.error(SyntaxError::ExpectedToken, &key),
         };
 
-        self.write_memo(&key, Some(&literal));
-        Ok(literal)
+        Ok(literal.with_span(self.span_from(start)))
     }
 
     // PathExpression ::= PathInExpression | QualifiedPathInExpression
     fn path_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("PathExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::PathExpression);
+        match self.get_memo(&key, Self::path_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // PathInExpression
         if let Ok(expr) = self.path_in_expression() {
-            self.write_memo(&key, Some(&expr));
             return Ok(expr);
         }
 
         // QualifiedPathInExpression
         if let Ok(expr) = self.qualified_path_in_expression() {
-            self.write_memo(&key, Some(&expr));
             return Ok(expr);
         }
 
@@ -1023,57 +1932,39 @@ impl CSTParser {
 
     // PathInExpression ::= `::`? PathExprSegment (`::` PathExprSegment)*
     fn path_in_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("PathInExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::PathInExpression);
+        match self.get_memo(&key, Self::path_in_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         let mut path_separater = None;
 
         //  `::`?
         if matches!(self.lexer.peek_glue(), Token::PathSeparater) {
-            let pos = self.lexer.get_sorce_position();
-            path_separater = Some(Box::new(CSTNode::new(
-                CSTNodeKind::Factor {
-                    token: self.lexer.next_glue(),
-                    row: pos.0,
-                    column: pos.1,
-                },
-                vec![],
-            )));
+            path_separater = Some(Rc::new(self.make_glued_factor_and_next()));
         }
 
         // PathExprSegment
-        let path_expr_segment = Box::new(self.path_expr_segment()?);
+        let path_expr_segment = Rc::new(self.path_expr_segment()?);
 
         // (`::` PathExprSegment)*
         let mut repeat_path_expr_segment = Vec::<(CSTNode, CSTNode)>::new();
         loop {
             // `::`
-            let pos = self.lexer.get_sorce_position();
             if !matches!(self.lexer.peek_glue(), Token::PathSeparater) {
                 break;
             }
-            self.lexer.next_glue();
+            let path_separater = self.make_glued_factor_and_next();
 
             // PathExprSegment
             let Ok(expr) = self.path_expr_segment() else {
                 break;
             };
 
-            repeat_path_expr_segment.push((
-                CSTNode::new(
-                    CSTNodeKind::Factor {
-                        token: Token::PathSeparater,
-                        row: pos.0,
-                        column: pos.1,
-                    },
-                    vec![],
-                ),
-                expr,
-            ));
+            repeat_path_expr_segment.push((path_separater, expr));
         }
 
         let node = CSTNode::new(
@@ -1084,22 +1975,22 @@ impl CSTParser {
             },
             vec![],
         );
-        self.write_memo(&key, Some(&node));
 
         Ok(node)
     }
 
     // PathExprSegment ::= PathIdentSegment (`::` GenericArgs)?
     fn path_expr_segment(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("PathExprSegment");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::PathExprSegment);
+        match self.get_memo(&key, Self::path_expr_segment) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // PathIdentSegment
-        let path_ident_segment = Box::new(self.path_ident_segment()?);
+        let path_ident_segment = Rc::new(self.path_ident_segment()?);
 
         //  (`::` GenericArgs)?
         let mut generic_args = None;
@@ -1119,24 +2010,23 @@ impl CSTParser {
 
     // PathIdentSegment   ::= Identifier | `super` | `self` | `Self` | `crate` | `$crate`
     fn path_ident_segment(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("PathIdentSegment");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::PathIdentSegment);
+        match self.get_memo(&key, Self::path_ident_segment) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         let node = self.make_factor();
         match self.lexer.peek() {
             Token::Identifier(_) => {
                 self.lexer.next();
-                self.write_memo(&key, Some(&node));
                 Ok(node)
             }
             Token::Keyword(keyword) => match keyword {
                 Keyword::Super | Keyword::SelfValue | Keyword::SelfType | Keyword::Crate => {
                     self.lexer.next();
-                    self.write_memo(&key, Some(&node));
                     Ok(node)
                 }
                 _ => self.error(SyntaxError::NotMatch, &key),
@@ -1148,11 +2038,12 @@ impl CSTParser {
 
     // QualifiedPathInExpression ::= QualifiedPathType (`::` PathExprSegment)+
     fn qualified_path_in_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("QualifiedPathInExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::QualifiedPathInExpression);
+        match self.get_memo(&key, Self::qualified_path_in_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // TODO
@@ -1167,11 +2058,12 @@ impl CSTParser {
 
     // QualifiedPathType ::= `<` Type (`as` TypePath)? `>`
     fn qualified_path_type(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("QualifiedPathType");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::QualifiedPathType);
+        match self.get_memo(&key, Self::qualified_path_type) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         self.error(SyntaxError::NotMatch, &key)
@@ -1179,57 +2071,61 @@ impl CSTParser {
 
     // QualifiedPathInType ::= QualifiedPathType (`::` TypePathSegment)+
     fn qualified_path_in_type(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("QualifiedPathInType");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::QualifiedPathInType);
+        match self.get_memo(&key, Self::qualified_path_in_type) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
         self.qualified_path_type()?;
 
         self.error(SyntaxError::NotMatch, &key)
     }
 
-    // Pratt parsing
     // OperatorExpression
+    // 演算子を一つも含まない場合は失敗する
     fn operator_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("OperatorExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::OperatorExpression);
+        match self.get_memo(&key, Self::operator_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
-        let min_bp = self.min_bp;
+        let expr = self.pratt_expression(0)?;
+        if !matches!(expr.node_kind, CSTNodeKind::Operator { .. }) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+
+        Ok(expr)
+    }
+
+    // Pratt parsing
+    // 被演算子はoperandで解析するのでメモ化しない
+    fn pratt_expression(&mut self, min_bp: u16) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::PrattExpression);
+        if !self.check_limits() {
+            return self.error(SyntaxError::LimitExceeded, &key);
+        }
 
         // 前置演算子
         let mut lhs: CSTNode = if is_operator(&self.lexer.peek_glue()) {
             let op = self.lexer.peek_glue();
             let Some(((), right_bp)) = prefix_binding_power(&op) else {
+                self.expected("expression");
                 return self.error(SyntaxError::ExpectedToken, &key);
             };
 
-            self.min_bp = right_bp; // 次の再帰のために保存
             let mut node = self.make_operator_and_next();
-            node.children.push(self.operator_expression()?);
+            node.children
+                .push(self.nested(|parser| parser.pratt_expression(right_bp))?);
+            node.update_span();
 
             node
         } else {
-            // TODO Expressionの最初に呼び出す
-            // Expressionの再帰用に呼び出し元だけを削除
-            self.memo.remove(&self.make_key("Expression"));
-            self.memo.remove(&self.make_key("ExpressionWithoutBlock"));
-            self.expression()?
-
-            /*
-            match self.expression() {
-                Ok(expr) => expr,
-                Err(error) => {
-                    self.min_bp = 0;
-                    return Err(error);
-                }
-            }
-             * */
+            self.operand()?
         };
 
         loop {
@@ -1245,8 +2141,14 @@ impl CSTParser {
                 if left_bp < min_bp {
                     break;
                 }
+                let op_start = self.lexer.get_token_position();
                 self.lexer.next_glue();
 
+                // 演算子が被演算子の後ろにあるので範囲を付け直す
+                let span = lhs
+                    .span
+                    .zip(self.span_from(op_start))
+                    .map(|(operand, operator)| operand.to(operator));
                 lhs = CSTNode::new(
                     CSTNodeKind::Operator {
                         token: op,
@@ -1254,7 +2156,8 @@ impl CSTParser {
                         column: op_pos.1,
                     },
                     vec![lhs],
-                );
+                )
+                .with_span(span);
 
                 continue;
             }
@@ -1266,8 +2169,7 @@ impl CSTParser {
                 }
                 self.lexer.next_glue();
 
-                self.min_bp = right_bp; // 次の再帰のために保存
-                let rhs = self.operator_expression()?;
+                let rhs = self.nested(|parser| parser.pratt_expression(right_bp))?;
                 let node = CSTNode::new(
                     CSTNodeKind::Operator {
                         token: op,
@@ -1284,7 +2186,6 @@ impl CSTParser {
             break;
         }
 
-        self.write_memo(&key, Some(&lhs));
         Ok(lhs)
     }
 
@@ -1293,11 +2194,12 @@ impl CSTParser {
     //                    | (`&`|`&&`) `raw` `const` Expression
     //                    | (`&`|`&&`) `raw` `mut` Expression
     fn borrow_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("BrrowExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::BorrowExpression);
+        match self.get_memo(&key, Self::borrow_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // (`&`|`&&`)
@@ -1325,35 +2227,33 @@ impl CSTParser {
 
     // GroupedExpression ::= `(` Expression `)`
     fn grouped_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("GroupedExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::GroupedExpression);
+        match self.get_memo(&key, Self::grouped_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // TODO
 
         // `(`
-        if !matches!(
-            self.lexer.peek(),
-            Token::LeftParenthesis(LeftParenthesis::Parenthesis)
-        ) {
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Parenthesis)) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let left_parenthesis = Box::new(self.make_factor_and_next());
+        let left_parenthesis = Rc::new(self.make_factor_and_next());
 
         // Expression
-        let expression = Box::new(self.expression()?);
+        let expression = Rc::new(self.expression()?);
 
         // `)`
-        if !matches!(
-            self.lexer.peek(),
-            Token::RightParenthesis(RightParenthesis::Parenthesis)
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Parenthesis),
+            &left_parenthesis,
         ) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let right_parenthesis = Box::new(self.make_factor_and_next());
+        let right_parenthesis = Rc::new(self.make_factor_and_next());
 
         Ok(CSTNode::new(
             CSTNodeKind::GroupedExpression {
@@ -1367,18 +2267,18 @@ impl CSTParser {
 
     // StructExpression ::= StructExprStruct | StructExprTuple | StructExprUnit
     fn struct_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("StructExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::StructExpression);
+        match self.get_memo(&key, Self::struct_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         if let Ok(expr) = self.struct_expr_struct() {
-            self.write_memo(&key, Some(&expr));
             return Ok(CSTNode::new(
                 CSTNodeKind::StructExpression {
-                    expression: Box::new(expr),
+                    expression: Rc::new(expr),
                 },
                 vec![],
             ));
@@ -1389,40 +2289,38 @@ impl CSTParser {
 
     // StructExprStruct ::= PathInExpression `{` (StructExprFields | StructBase)? `}`
     fn struct_expr_struct(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("StructExprStruct");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::StructExprStruct);
+        match self.get_memo(&key, Self::struct_expr_struct) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
-        let path_in_expression = Box::new(self.path_in_expression()?);
+        let path_in_expression = Rc::new(self.path_in_expression()?);
 
         // `{`
-        if !matches!(
-            self.lexer.peek(),
-            Token::LeftParenthesis(LeftParenthesis::Brace)
-        ) {
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brace)) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let left_brace = Box::new(self.make_factor_and_next());
+        let left_brace = Rc::new(self.make_factor_and_next());
 
         // (StructExprFields | StructBase)?
         let mut expression = None;
         if let Ok(expr) = self.struct_expr_fileds() {
-            expression = Some(Box::new(expr));
+            expression = Some(Rc::new(expr));
         } else if let Ok(expr) = self.struct_expr_filed() {
-            expression = Some(Box::new(expr));
+            expression = Some(Rc::new(expr));
         }
 
         // `}`
-        if !matches!(
-            self.lexer.peek(),
-            Token::RightParenthesis(RightParenthesis::Brace)
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Brace),
+            &left_brace,
         ) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let right_brace = Box::new(self.make_factor_and_next());
+        let right_brace = Rc::new(self.make_factor_and_next());
 
         let node = CSTNode::new(
             CSTNodeKind::StructExprStruct {
@@ -1433,22 +2331,22 @@ impl CSTParser {
             },
             vec![],
         );
-        self.write_memo(&key, Some(&node));
 
         Ok(node)
     }
 
     // StructExprFields ::= StructExprField (, StructExprField)* (, StructBase | ,?)
     fn struct_expr_fileds(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("StructExprFields");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::StructExprFields);
+        match self.get_memo(&key, Self::struct_expr_fileds) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // StructExprField
-        let struct_expr_filed = Box::new(self.struct_expr_filed()?);
+        let struct_expr_filed = Rc::new(self.struct_expr_filed()?);
 
         // (, StructExprField)*
         let mut struct_expr_filed_repeat = Vec::<(CSTNode, CSTNode)>::new();
@@ -1470,9 +2368,9 @@ impl CSTParser {
         let mut comma = None;
         let mut struct_base = None;
         if matches!(self.lexer.peek(), Token::Comma) {
-            comma = Some(Box::new(self.make_factor_and_next()));
+            comma = Some(Rc::new(self.make_factor_and_next()));
             if let Ok(expr) = self.struct_base() {
-                struct_base = Some(Box::new(expr));
+                struct_base = Some(Rc::new(expr));
             }
         }
 
@@ -1485,18 +2383,18 @@ impl CSTParser {
             },
             vec![],
         );
-        self.write_memo(&key, Some(&node));
 
         Ok(node)
     }
 
     // StructExprField  ::= OuterAttribute* ( Identifier | (Identifier |TUPLE_INDEX) `:` Expression )
     fn struct_expr_filed(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("StructExprField");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::StructExprField);
+        match self.get_memo(&key, Self::struct_expr_filed) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // OuterAttribute*
@@ -1510,10 +2408,10 @@ impl CSTParser {
         let identifier_or_tuple = match self.lexer.peek() {
             Token::Identifier(_) => {
                 is_identifier = true;
-                Box::new(self.make_factor_and_next())
+                Rc::new(self.make_factor_and_next())
             }
             Token::Literal(literal) => match literal.literal_kind {
-                LiteralKind::Integer => Box::new(self.make_factor_and_next()),
+                LiteralKind::Integer => Rc::new(self.make_factor_and_next()),
                 _ => return self.error(SyntaxError::NotMatch, &key),
             },
             _ => return self.error(SyntaxError::ExpectedToken, &key),
@@ -1522,7 +2420,7 @@ impl CSTParser {
         Token::Literal(Literal::new(LiteralKind::Integer, "0"));
 
         // `:`
-        if !matches!(self.lexer.peek(), Token::Colon) {
+        if !self.is_token(Token::Colon) {
             if !is_identifier {
                 return self.error(SyntaxError::NotMatch, &key);
             }
@@ -1535,13 +2433,12 @@ impl CSTParser {
                 vec![],
             );
 
-            self.write_memo(&key, Some(&node));
             return Ok(node);
         }
-        let colon = Box::new(self.make_factor_and_next());
+        let colon = Rc::new(self.make_factor_and_next());
 
         // Expression
-        let expression = Box::new(self.expression()?);
+        let expression = Rc::new(self.expression()?);
 
         let node = CSTNode::new(
             CSTNodeKind::StructExprField2 {
@@ -1553,30 +2450,29 @@ impl CSTParser {
             vec![],
         );
 
-        self.write_memo(&key, Some(&node));
         return Ok(node);
     }
 
     // StructBase ::= `..` Expression
     fn struct_base(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("StructBase");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::StructBase);
+        match self.get_memo(&key, Self::struct_base) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // `..`
-        if !matches!(self.lexer.peek(), Token::DotDot) {
+        if !self.is_token(Token::DotDot) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let dotdot = Box::new(self.make_factor_and_next());
+        let dotdot = Rc::new(self.make_factor_and_next());
 
         // Expression
-        let expression = Box::new(self.expression()?);
+        let expression = Rc::new(self.expression()?);
 
         let node = CSTNode::new(CSTNodeKind::StructBase { dotdot, expression }, vec![]);
-        self.write_memo(&key, Some(&node));
         Ok(node)
     }
 
@@ -1586,15 +2482,17 @@ impl CSTParser {
 
     // CallExpression ::= Expression `(` CallParams? `)`
     fn call_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("CallExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::CallExpression);
+        match self.get_memo(&key, Self::call_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // Expression
-        let expression = Box::new(self.expression()?);
+        // 演算子式は優先順位の関係で含めない
+        let expression = Rc::new(self.operand()?);
 
         // `(`
         if !matches!(
@@ -1603,22 +2501,22 @@ impl CSTParser {
         ) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let left_parenthesis = Box::new(self.make_factor_and_next());
+        let left_parenthesis = Rc::new(self.make_factor_and_next());
 
         // CallParams?
         let mut call_params = None;
         if let Ok(res) = self.call_params() {
-            call_params = Some(Box::new(res));
+            call_params = Some(Rc::new(res));
         }
 
         // `)`
-        if !matches!(
-            self.lexer.peek(),
-            Token::LeftParenthesis(LeftParenthesis::Parenthesis)
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Parenthesis),
+            &left_parenthesis,
         ) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let right_parenthesis = Box::new(self.make_factor_and_next());
+        let right_parenthesis = Rc::new(self.make_factor_and_next());
 
         Ok(CSTNode::new(
             CSTNodeKind::CallExpression {
@@ -1633,73 +2531,227 @@ impl CSTParser {
 
     // CallParams ::= Expression ( `,` Expression )* `,`?
     fn call_params(&mut self) -> Result<CSTNode, Error> {
-        let expression = Box::new(self.expression()?);
+        let expression = Rc::new(self.expression()?);
 
-        // ( `,` Expression )*
+        // ( `,` Expression )* `,`?
         let mut comma_and_expression = Vec::<(CSTNode, CSTNode)>::new();
-        loop {
-            if !matches!(self.lexer.peek(), Token::Comma) {
-                break;
-            }
-            let comma = self.make_factor_and_next();
+        let mut comma = None;
+        while matches!(self.lexer.peek(), Token::Comma) {
+            let factor = self.make_factor_and_next();
 
+            // 後ろに式が無ければ最後の`,`
             let Ok(expr) = self.expression() else {
+                comma = Some(Rc::new(factor));
                 break;
             };
 
-            comma_and_expression.push((comma, expr));
+            comma_and_expression.push((factor, expr));
+        }
+
+        Ok(CSTNode::new(
+            CSTNodeKind::CallParams {
+                expression,
+                comma_and_expression,
+                comma,
+            },
+            vec![],
+        ))
+    }
+
+    // MethodCallExpression ::= Expression `.` PathExprSegment `(` CallParams? `)`
+    fn method_call_expression(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::MethodCallExpression);
+        match self.get_memo(&key, Self::method_call_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Expression
+        let expression = Rc::new(self.operand()?);
+
+        // `.`
+        if !matches!(self.lexer.peek(), Token::Dot) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let dot = Rc::new(self.make_factor_and_next());
+
+        // PathExprSegment
+        let path_expr_segment = Rc::new(self.path_expr_segment()?);
+
+        // `(`
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Parenthesis)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_parenthesis = Rc::new(self.make_factor_and_next());
+
+        // CallParams?
+        let mut call_params = None;
+        if let Ok(res) = self.call_params() {
+            call_params = Some(Rc::new(res));
+        }
+
+        // `)`
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Parenthesis),
+            &left_parenthesis,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let right_parenthesis = Rc::new(self.make_factor_and_next());
+
+        Ok(CSTNode::new(
+            CSTNodeKind::MethodCallExpression {
+                expression,
+                dot,
+                path_expr_segment,
+                left_parenthesis,
+                call_params,
+                right_parenthesis,
+            },
+            vec![],
+        ))
+    }
+
+    // FieldExpression ::= Expression `.` Identifier
+    fn field_expression(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::FieldExpression);
+        match self.get_memo(&key, Self::field_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Expression
+        let expression = Rc::new(self.operand()?);
+
+        // `.`
+        if !matches!(self.lexer.peek(), Token::Dot) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let dot = Rc::new(self.make_factor_and_next());
+
+        // Identifier
+        if !matches!(self.lexer.peek(), Token::Identifier(_)) {
+            self.expected("identifier");
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let identifier = Rc::new(self.make_factor_and_next());
+
+        Ok(CSTNode::new(
+            CSTNodeKind::FieldExpression {
+                expression,
+                dot,
+                identifier,
+            },
+            vec![],
+        ))
+    }
+
+    // AwaitExpression ::= Expression `.` `await`
+    fn await_expression(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::AwaitExpression);
+        match self.get_memo(&key, Self::await_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Expression
+        let expression = Rc::new(self.operand()?);
+
+        // `.`
+        if !matches!(self.lexer.peek(), Token::Dot) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let dot = Rc::new(self.make_factor_and_next());
+
+        // `await`
+        if !self.is_token(Token::Keyword(Keyword::Await)) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let await_keyword = Rc::new(self.make_factor_and_next());
+
+        Ok(CSTNode::new(
+            CSTNodeKind::AwaitExpression {
+                expression,
+                dot,
+                await_keyword,
+            },
+            vec![],
+        ))
+    }
+
+    // IndexExpression ::= Expression `[` Expression `]`
+    fn index_expression(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::IndexExpression);
+        match self.get_memo(&key, Self::index_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Expression
+        let expression = Rc::new(self.operand()?);
+
+        // `[`
+        if !matches!(
+            self.lexer.peek(),
+            Token::LeftParenthesis(LeftParenthesis::Brackets)
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let left_brackets = Rc::new(self.make_factor_and_next());
+
+        // Expression
+        let Ok(index) = self.expression() else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        let index = Rc::new(index);
+
+        // `]`
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Brackets),
+            &left_brackets,
+        ) {
+            return self.error(SyntaxError::ExpectedToken, &key);
         }
-
-        let comma = if matches!(self.lexer.peek(), Token::Comma) {
-            Some(Box::new(self.make_factor()))
-        } else {
-            None
-        };
+        let right_brackets = Rc::new(self.make_factor_and_next());
 
         Ok(CSTNode::new(
-            CSTNodeKind::CallParams {
+            CSTNodeKind::IndexExpression {
                 expression,
-                comma_and_expression,
-                comma,
+                left_brackets,
+                index,
+                right_brackets,
             },
             vec![],
         ))
     }
 
-    // MethodCallExpression ::= Expression `.` PathExprSegment `(` CallParams? `)`
-    fn method_call_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("MethodCallExpression");
-        match self.get_memo(&key) {
-            MemoResult::Some(res) => return Ok(res),
-            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
-        };
-
-        self.expression()?;
-
-        self.call_params();
-
-        self.error(SyntaxError::NotMatch, &key)
-    }
-
     // ReturnExpression ::= `return` Expression?
     fn return_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("ReturnExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::ReturnExpression);
+        match self.get_memo(&key, Self::return_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
         // `return`
-        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::Return)) {
+        if !self.is_token(Token::Keyword(Keyword::Return)) {
             return self.error(SyntaxError::NotMatch, &key);
         }
-        let return_keyword = Box::new(self.make_factor_and_next());
+        let return_keyword = Rc::new(self.make_factor_and_next());
 
         // Expression?
         let mut expression = None;
         if let Ok(expr) = self.expression() {
-            expression = Some(Box::new(expr));
+            expression = Some(Rc::new(expr));
         }
 
         Ok(CSTNode::new(
@@ -1714,11 +2766,12 @@ impl CSTParser {
     // Scrutinee ::= Expression
     // ** except struct expression **
     fn scrutinee(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("Scrutinee");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::Scrutinee);
+        match self.get_memo(&key, Self::scrutinee) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         let Ok(expression) = self.expression() else {
@@ -1739,12 +2792,14 @@ impl CSTParser {
     //                        | IfExpression | IfLetExpression | MatchExpression
     //                        )
     fn expression_with_block(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("ExpressionWithBlock");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::ExpressionWithBlock);
+        match self.get_memo(&key, Self::expression_with_block) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
+        let checkpoint = self.state.furthest.checkpoint();
         // OuterAttribute*
         let mut outer_attribute = Vec::<CSTNode>::new();
         while let Ok(expr) = self.outer_attribute() {
@@ -1753,164 +2808,264 @@ impl CSTParser {
 
         // BlockExpression
         if let Ok(expr) = self.block_expression() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(expr);
+            return Ok(wrap_outer_attribute(expr, outer_attribute));
+        }
+
+        // ConstBlockExpression
+        if let Ok(expr) = self.const_block_expression() {
+            return Ok(wrap_outer_attribute(expr, outer_attribute));
+        }
+
+        // UnsafeBlockExpression
+        if let Ok(expr) = self.unsafe_block_expression() {
+            return Ok(wrap_outer_attribute(expr, outer_attribute));
         }
 
         // IfExpression
         if let Ok(expr) = self.if_expression() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(expr);
+            return Ok(wrap_outer_attribute(expr, outer_attribute));
         }
 
         // IfLetExpression
         if let Ok(expr) = self.if_let_expression() {
-            self.write_memo(&key, Some(&expr));
-            return Ok(expr);
+            return Ok(wrap_outer_attribute(expr, outer_attribute));
         }
 
+        self.state
+            .furthest
+            .summarize(checkpoint, key.position, "expression");
         self.error(SyntaxError::NotMatch, &key)
     }
 
     // BlockExpression ::=  `{` InnerAttribute* Statements? `}`
     fn block_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("BlockExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::BlockExpression);
+        match self.get_memo(&key, Self::block_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // `{`
-        if !matches!(
-            self.lexer.peek(),
-            Token::LeftParenthesis(LeftParenthesis::Brace)
-        ) {
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brace)) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let left_brace = Box::new(self.make_factor_and_next());
+        let left_brace = Rc::new(self.make_factor_and_next());
+
+        // InnerAttribute*
+        let mut inner_attribute = Vec::<CSTNode>::new();
+        while let Ok(expr) = self.inner_attribute() {
+            inner_attribute.push(expr);
+        }
 
         // Statements?
         let mut statements = None;
         if let Ok(expr) = self.statements() {
-            statements = Some(Box::new(expr));
+            statements = Some(Rc::new(expr));
         }
 
         // `}`
-        if !matches!(
-            self.lexer.peek(),
-            Token::RightParenthesis(RightParenthesis::Brace)
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Brace),
+            &left_brace,
         ) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let right_brace = Box::new(self.make_factor_and_next());
+        let right_brace = Rc::new(self.make_factor_and_next());
 
         let expr = CSTNode::new(
             CSTNodeKind::BlockExpression {
                 left_brace,
-                inner_attribute: Vec::new(),
+                inner_attribute,
                 statements,
                 right_brace,
             },
             vec![],
         );
-        self.write_memo(&key, Some(&expr));
 
         Ok(expr)
     }
 
     // Statements ::= Statement+ | Statement+ ExpressionWithoutBlock | ExpressionWithoutBlock
     fn statements(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("Statements");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::Statements);
+        match self.get_memo(&key, Self::statements) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
-        // Statement+ | Statement+ ExpressionWithoutBlock
         let mut node = CSTNode::new(CSTNodeKind::Statements, vec![]);
-        if let Ok(expr1) = self.statement() {
-            node.children.push(expr1);
-
-            while let Ok(expr2) = self.statement() {
-                node.children.push(expr2);
+        loop {
+            // Statement
+            if let Ok(expr) = self.statement() {
+                node.children.push(expr);
+                continue;
             }
 
             // ExpressionWithoutBlock
-            if let Ok(expr3) = self.expression_without_block() {
-                node.children.push(expr3);
+            // ブロックの最後の式のみ
+            let position = self.lexer.get_token_position();
+            if let Ok(expr) = self.expression_without_block() {
+                if matches!(
+                    self.lexer.peek(),
+                    Token::RightParenthesis(RightParenthesis::Brace)
+                ) {
+                    node.children.push(expr);
+                    break;
+                }
+                self.backtrack(position);
             }
 
-            self.write_memo(&key, Some(&node));
-            return Ok(node);
+            if matches!(
+                self.lexer.peek(),
+                Token::RightParenthesis(RightParenthesis::Brace) | Token::Eof
+            ) {
+                break;
+            }
+
+            // 文として解析できなければ次の文まで読み飛ばす
+            node.children.push(self.recover(true));
         }
 
-        // ExpressionWithoutBlock
-        if let Ok(expr) = self.expression_without_block() {
-            node.children.push(expr);
-            self.write_memo(&key, Some(&node));
-            return Ok(node);
+        if node.children.is_empty() {
+            return self.error(SyntaxError::NotMatch, &key);
         }
 
-        self.error(SyntaxError::NotMatch, &key)
+        node.update_span();
+        Ok(node)
     }
 
     // ConstBlockExpression ::= `const` BlockExpression
     fn const_block_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("ConstBlockExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::ConstBlockExpression);
+        match self.get_memo(&key, Self::const_block_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // `const`
-        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::Const)) {
+        if !self.is_token(Token::Keyword(Keyword::Const)) {
             return self.error(SyntaxError::NotMatch, &key);
         }
-        let const_keyword = Box::new(self.make_factor_and_next());
+        let const_keyword = Rc::new(self.make_factor_and_next());
 
-        // TODO
+        // BlockExpression
+        let block_expression = Rc::new(self.block_expression()?);
 
-        self.error(SyntaxError::NotMatch, &key)
+        Ok(CSTNode::new(
+            CSTNodeKind::ConstBlockExpression {
+                const_keyword,
+                block_expression,
+            },
+            vec![],
+        ))
     }
 
     // UnsafeBlockExpression ::= `unsafe` BlockExpression
+    fn unsafe_block_expression(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::UnsafeBlockExpression);
+        match self.get_memo(&key, Self::unsafe_block_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `unsafe`
+        if !self.is_token(Token::Keyword(Keyword::Unsafe)) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        let unsafe_keyword = Rc::new(self.make_factor_and_next());
+
+        // BlockExpression
+        let block_expression = Rc::new(self.block_expression()?);
+
+        Ok(CSTNode::new(
+            CSTNodeKind::UnsafeBlockExpression {
+                unsafe_keyword,
+                block_expression,
+            },
+            vec![],
+        ))
+    }
+
+    // AsyncBlockExpression ::= `async` `move`? BlockExpression
+    fn async_block_expression(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::AsyncBlockExpression);
+        match self.get_memo(&key, Self::async_block_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // `async`
+        if !self.is_token(Token::Keyword(Keyword::Async)) {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+        let async_keyword = Rc::new(self.make_factor_and_next());
+
+        // `move`?
+        let mut move_keyword = None;
+        if matches!(self.lexer.peek(), Token::Keyword(Keyword::Move)) {
+            move_keyword = Some(Rc::new(self.make_factor_and_next()));
+        }
+
+        // BlockExpression
+        let block_expression = Rc::new(self.block_expression()?);
+
+        Ok(CSTNode::new(
+            CSTNodeKind::AsyncBlockExpression {
+                async_keyword,
+                move_keyword,
+                block_expression,
+            },
+            vec![],
+        ))
+    }
 
     //IfExpression ::= `if` Expression BlockExpression (`else` ( BlockExpression | IfExpression | IfLetExpression ) )?
     fn if_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("IfExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::IfExpression);
+        match self.get_memo(&key, Self::if_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
         // `if`
-        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::If)) {
+        if !self.is_token(Token::Keyword(Keyword::If)) {
             return self.error(SyntaxError::NotMatch, &key);
         }
-        let if_keyword = Box::new(self.make_factor_and_next());
+        let if_keyword = Rc::new(self.make_factor_and_next());
 
-        // Expression
-        let expression = Box::new(self.expression()?);
+        // LetChain | Expression
+        let expression = match self.let_chain() {
+            Ok(expr) => Rc::new(expr),
+            Err(_) => Rc::new(self.expression()?),
+        };
 
         // BlockExpression
-        let block_expression = Box::new(self.block_expression()?);
+        let block_expression = Rc::new(self.block_expression()?);
 
         // ( `else` ( BlockExpression | IfExpression | IfLetExpression ) )?
         let mut else_keyword = None;
         let mut else_expression = None;
         if matches!(self.lexer.peek(), Token::Keyword(Keyword::Else)) {
-            else_keyword = Some(Box::new(self.make_factor_and_next()));
+            else_keyword = Some(Rc::new(self.make_factor_and_next()));
 
             // ( BlockExpression | IfExpression | IfLetExpression )
             if let Ok(expr) = self.block_expression() {
-                else_expression = Some(Box::new(expr));
+                else_expression = Some(Rc::new(expr));
             } else if let Ok(expr) = self.if_expression() {
-                else_expression = Some(Box::new(expr));
+                else_expression = Some(Rc::new(expr));
             } else if let Ok(expr) = self.if_let_expression() {
-                else_expression = Some(Box::new(expr));
+                else_expression = Some(Rc::new(expr));
             } else {
                 return self.error(SyntaxError::ExpectedToken, &key);
             }
@@ -1926,7 +3081,6 @@ impl CSTParser {
             },
             vec![],
         );
-        self.write_memo(&key, Some(&node));
 
         Ok(node)
     }
@@ -1934,54 +3088,55 @@ impl CSTParser {
     // IfLetExpression ::= `if` `let` Pattern `=` Scrutinee BlockExpression
     //                   ( else ( BlockExpression | IfExpression | IfLetExpression ) )?
     fn if_let_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("IfLetExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::IfLetExpression);
+        match self.get_memo(&key, Self::if_let_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // `if`
-        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::If)) {
+        if !self.is_token(Token::Keyword(Keyword::If)) {
             return self.error(SyntaxError::NotMatch, &key);
         }
-        let if_keyword = Box::new(self.make_factor_and_next());
+        let if_keyword = Rc::new(self.make_factor_and_next());
 
         // `let`
-        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::Let)) {
+        if !self.is_token(Token::Keyword(Keyword::Let)) {
             return self.error(SyntaxError::NotMatch, &key);
         }
-        let let_keyword = Box::new(self.make_factor_and_next());
+        let let_keyword = Rc::new(self.make_factor_and_next());
 
         // Pattern
-        let pattern = Box::new(self.pattern()?);
+        let pattern = Rc::new(self.pattern()?);
 
         // =
-        if !matches!(self.lexer.peek(), Token::Equal) {
+        if !self.is_token(Token::Equal) {
             return self.error(SyntaxError::NotMatch, &key);
         }
-        let equal = Box::new(self.make_factor_and_next());
+        let equal = Rc::new(self.make_factor_and_next());
 
         // Scrutinee
-        // ** except lazy boolean operator expression **
-        let scrutinee = Box::new(self.scrutinee()?);
+        // `&&`は次のLetChainConditionとの区切りなので含めない
+        let scrutinee = Rc::new(self.condition_expression()?);
 
         // BlockExpression
-        let block_expression = Box::new(self.block_expression()?);
+        let block_expression = Rc::new(self.block_expression()?);
 
         // ( else ( BlockExpression | IfExpression | IfLetExpression ) )?
         let mut else_keyword = None;
         let mut else_expression = None;
         if matches!(self.lexer.peek(), Token::Keyword(Keyword::Else)) {
-            else_keyword = Some(Box::new(self.make_factor_and_next()));
+            else_keyword = Some(Rc::new(self.make_factor_and_next()));
 
             // ( BlockExpression | IfExpression | IfLetExpression )
             if let Ok(expr) = self.block_expression() {
-                else_expression = Some(Box::new(expr));
+                else_expression = Some(Rc::new(expr));
             } else if let Ok(expr) = self.if_expression() {
-                else_expression = Some(Box::new(expr));
+                else_expression = Some(Rc::new(expr));
             } else if let Ok(expr) = self.if_let_expression() {
-                else_expression = Some(Box::new(expr));
+                else_expression = Some(Rc::new(expr));
             } else {
                 return self.error(SyntaxError::ExpectedToken, &key);
             }
@@ -2000,21 +3155,134 @@ impl CSTParser {
             },
             vec![],
         );
-        self.write_memo(&key, Some(&node));
 
         Ok(node)
     }
 
+    // LetChain ::= LetChainCondition ( `&&` LetChainCondition )*
+    // `let`を含まないものは普通の`&&`の式なので、LetChainとしては失敗する
+    fn let_chain(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::LetChain);
+        match self.get_memo(&key, Self::let_chain) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // LetChainCondition
+        let let_chain_condition = self.let_chain_condition()?;
+        let mut has_let = matches!(
+            let_chain_condition.node_kind,
+            CSTNodeKind::LetChainCondition { .. }
+        );
+
+        // ( `&&` LetChainCondition )*
+        let mut repeat_let_chain_condition = vec![];
+        while matches!(self.lexer.peek_glue(), Token::AndAnd) {
+            let and_and = self.make_glued_factor_and_next();
+            let expr = self.let_chain_condition()?;
+            has_let |= matches!(expr.node_kind, CSTNodeKind::LetChainCondition { .. });
+            repeat_let_chain_condition.push((and_and, expr));
+        }
+
+        // `if let`だけのものはIfLetExpression
+        if !has_let || repeat_let_chain_condition.is_empty() {
+            return self.error(SyntaxError::NotMatch, &key);
+        }
+
+        Ok(CSTNode::new(
+            CSTNodeKind::LetChain {
+                let_chain_condition: Rc::new(let_chain_condition),
+                repeat_let_chain_condition,
+            },
+            vec![],
+        ))
+    }
+
+    // LetChainCondition ::= Expression | `let` Pattern `=` Scrutinee
+    fn let_chain_condition(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::LetChainCondition);
+        match self.get_memo(&key, Self::let_chain_condition) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        // Expression
+        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::Let)) {
+            return self.condition_expression();
+        }
+
+        // `let`
+        let let_keyword = Rc::new(self.make_factor_and_next());
+
+        // Pattern
+        let pattern = Rc::new(self.pattern()?);
+
+        // `=`
+        if !self.is_token(Token::Equal) {
+            return self.error(SyntaxError::ExpectedToken, &key);
+        }
+        let equal = Rc::new(self.make_factor_and_next());
+
+        // Scrutinee
+        let scrutinee = Rc::new(self.condition_expression()?);
+
+        Ok(CSTNode::new(
+            CSTNodeKind::LetChainCondition {
+                let_keyword,
+                pattern,
+                equal,
+                scrutinee,
+            },
+            vec![],
+        ))
+    }
+
+    // `&&`や`||`、それより優先順位の低い演算子を含まない式
+    // LetChainの条件とif letのScrutineeに使う
+    fn condition_expression(&mut self) -> Result<CSTNode, Error> {
+        let key = self.make_key(ParseRule::ConditionExpression);
+        match self.get_memo(&key, Self::condition_expression) {
+            MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
+            MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
+            MemoResult::None => (),
+        };
+
+        let Some((left_bp, _)) = infix_binding_power(&Token::AndAnd) else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        let Ok(expr) = self.nested(|parser| parser.pratt_expression(left_bp + 1)) else {
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+
+        // 演算子式はexpressionと同じくExpressionWithoutBlockで包む
+        if !matches!(expr.node_kind, CSTNodeKind::Operator { .. }) {
+            return Ok(expr);
+        }
+        Ok(CSTNode::new(
+            CSTNodeKind::ExpressionWithoutBlock {
+                outer_attribute: vec![],
+                expression: Rc::new(expr),
+            },
+            vec![],
+        ))
+    }
+
     // MatchExpression ::= `match` Scrutinee `{` InnerAttribute* MatchArms? `}`
     fn match_expression(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("MatchExpression");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::MatchExpression);
+        match self.get_memo(&key, Self::match_expression) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
         // `match`
-        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::Match)) {
+        if !self.is_token(Token::Keyword(Keyword::Match)) {
             return self.error(SyntaxError::NotMatch, &key);
         }
         self.make_factor_and_next();
@@ -2023,13 +3291,10 @@ impl CSTParser {
         let scrutinee = self.scrutinee()?;
 
         // `{`
-        if !matches!(
-            self.lexer.peek(),
-            Token::LeftParenthesis(LeftParenthesis::Brace)
-        ) {
+        if !self.is_token(Token::LeftParenthesis(LeftParenthesis::Brace)) {
             return self.error(SyntaxError::NotMatch, &key);
         }
-        self.make_factor_and_next();
+        let left_brace = self.make_factor_and_next();
 
         // InnerAttribute*
         loop {
@@ -2041,9 +3306,9 @@ impl CSTParser {
         // MatchArms?
 
         // `}`
-        if !matches!(
-            self.lexer.peek(),
-            Token::RightParenthesis(RightParenthesis::Brace)
+        if !self.is_closing_token(
+            Token::RightParenthesis(RightParenthesis::Brace),
+            &left_brace,
         ) {
             return self.error(SyntaxError::NotMatch, &key);
         }
@@ -2058,31 +3323,30 @@ impl CSTParser {
 
     // Statement ::= `;` | Item | LetStatement | ExpressionStatement | MacroInvocationSemi
     fn statement(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("Statement");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::Statement);
+        match self.get_memo(&key, Self::statement) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // ;
         if matches!(self.lexer.peek(), Token::Semicolon) {
             let expr = CSTNode::new(
                 CSTNodeKind::Statement {
-                    statement: Box::new(self.make_factor_and_next()),
+                    statement: Rc::new(self.make_factor_and_next()),
                 },
                 vec![],
             );
-            self.write_memo(&key, Some(&expr));
             return Ok(expr);
         }
 
         // Item
         if let Ok(expr) = self.item() {
-            self.write_memo(&key, Some(&expr));
             return Ok(CSTNode::new(
                 CSTNodeKind::Statement {
-                    statement: Box::new(expr),
+                    statement: Rc::new(expr),
                 },
                 vec![],
             ));
@@ -2090,10 +3354,9 @@ impl CSTParser {
 
         // LetStatement
         if let Ok(expr) = self.let_statement() {
-            self.write_memo(&key, Some(&expr));
             return Ok(CSTNode::new(
                 CSTNodeKind::Statement {
-                    statement: Box::new(expr),
+                    statement: Rc::new(expr),
                 },
                 vec![],
             ));
@@ -2101,10 +3364,9 @@ impl CSTParser {
 
         // ExpressionStatement
         if let Ok(expr) = self.expression_statement() {
-            self.write_memo(&key, Some(&expr));
             return Ok(CSTNode::new(
                 CSTNodeKind::Statement {
-                    statement: Box::new(expr),
+                    statement: Rc::new(expr),
                 },
                 vec![],
             ));
@@ -2117,11 +3379,12 @@ impl CSTParser {
     //                  PatternNoTopAlt ( `:` Type )?
     //                  (`=` Expression ( `else` BlockExpression)? )? `;`
     fn let_statement(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("LetStatement");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::LetStatement);
+        match self.get_memo(&key, Self::let_statement) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         let mut colon = None;
@@ -2139,52 +3402,54 @@ impl CSTParser {
 
         // (`ur` | `sr` | `nr` | `let`)
         let rarity = match self.lexer.peek() {
-            Token::Keyword(keyword) => match keyword {
-                Keyword::Ur | Keyword::Sr | Keyword::Nr | Keyword::Let => {
-                    Box::new(self.make_factor_and_next())
+            Token::Keyword(Keyword::Ur | Keyword::Sr | Keyword::Nr | Keyword::Let) => {
+                Rc::new(self.make_factor_and_next())
+            }
+            _ => {
+                for keyword in [Keyword::Let, Keyword::Ur, Keyword::Sr, Keyword::Nr] {
+                    self.expected(&format!("`{}`", keyword.as_str()));
                 }
-                _ => return self.error(SyntaxError::ExpectedToken, &key),
-            },
-            _ => return self.error(SyntaxError::ExpectedToken, &key),
+                return self.error(SyntaxError::ExpectedToken, &key);
+            }
         };
 
         // PatternNoTopAlt
-        let pattern_no_top_alt = Box::new(self.pattern_no_top_alt()?);
+        let Ok(pattern_no_top_alt) = self.pattern_no_top_alt() else {
+            self.expected("pattern");
+            return self.error(SyntaxError::NotMatch, &key);
+        };
+        let pattern_no_top_alt = Rc::new(pattern_no_top_alt);
 
         // ( `:` Type )?
         if matches!(self.lexer.peek(), Token::Colon) {
-            colon = Some(Box::new(self.make_factor_and_next()));
+            colon = Some(Rc::new(self.make_factor_and_next()));
 
             // Type
-            type_expression = Some(Box::new(self.type_expression()?));
+            type_expression = Some(Rc::new(self.type_expression()?));
         }
 
         //  (`=` Expression ( `else` BlockExpression)? )? `;`
         if matches!(self.lexer.peek(), Token::Equal) {
             // `=`
-            equal = Some(Box::new(self.make_factor_and_next()));
+            equal = Some(Rc::new(self.make_factor_and_next()));
 
             // Expression
-            expression = Some(Box::new(self.expression()?));
-            //self.min_bp = 0;
+            expression = Some(Rc::new(self.expression()?));
 
             // `else`
-            if let Token::Keyword(keyword) = self.lexer.peek() {
-                if !matches!(keyword, Keyword::Else) {
-                    return self.error(SyntaxError::ExpectedToken, &key);
-                }
-                else_keyword = Some(Box::new(self.make_factor_and_next()));
+            if matches!(self.lexer.peek(), Token::Keyword(Keyword::Else)) {
+                else_keyword = Some(Rc::new(self.make_factor_and_next()));
 
                 // BlockExpression
-                block_expression = Some(Box::new(self.block_expression()?))
+                block_expression = Some(Rc::new(self.block_expression()?))
             }
         }
 
         // ;
-        if !matches!(self.lexer.peek(), Token::Semicolon) {
+        if !self.is_token(Token::Semicolon) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
-        let semicolon = Box::new(self.make_factor_and_next());
+        let semicolon = Rc::new(self.make_factor_and_next());
 
         let expr = CSTNode::new(
             CSTNodeKind::LetStatement {
@@ -2201,32 +3466,32 @@ impl CSTParser {
             },
             vec![],
         );
-        self.write_memo(&key, Some(&expr));
 
         Ok(expr)
     }
 
     // ExpressionStatement ::= ExpressionWithoutBlock `;` | ExpressionWithBlock `;`?
     fn expression_statement(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("ExpressionStatement");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::ExpressionStatement);
+        match self.get_memo(&key, Self::expression_statement) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // ExpressionWithoutBlock `;`
         if let Ok(mut expr) = self.expression_without_block() {
-            if matches!(self.lexer.peek(), Token::Semicolon) {
+            if self.is_token(Token::Semicolon) {
                 expr.children.push(self.make_factor_and_next());
-                self.write_memo(&key, Some(&expr));
+                expr.update_span();
                 return Ok(expr);
             }
+            self.backtrack(key.position);
         }
 
         // ExpressionWithBlock `;`?
         if let Ok(expr) = self.expression_with_block() {
-            self.write_memo(&key, Some(&expr));
             return Ok(expr);
         }
 
@@ -2239,39 +3504,51 @@ impl CSTParser {
 
     // Pattern ::= `|`? PatternNoTopAlt ( `|` PatternNoTopAlt )*
     fn pattern(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("Pattern");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::Pattern);
+        match self.get_memo(&key, Self::pattern) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
         let mut or_token = None;
 
         // `|`?
         if let Token::Or = self.lexer.peek() {
-            or_token = Some(Box::new(self.make_factor_and_next()));
+            or_token = Some(Rc::new(self.make_factor_and_next()));
         }
 
-        self.pattern_no_top_alt()?;
+        // PatternNoTopAlt
+        let pattern_no_top_alt = Rc::new(self.pattern_no_top_alt()?);
 
-        while let Ok(expr_pattern_no_top_alt) = self.pattern_no_top_alt() {
-            //
+        // ( `|` PatternNoTopAlt )*
+        let mut repeat_pattern_no_top_alt = vec![];
+        while matches!(self.lexer.peek_glue(), Token::Or) {
+            let or = self.make_factor_and_next();
+            repeat_pattern_no_top_alt.push((or, self.pattern_no_top_alt()?));
         }
 
-        self.error(SyntaxError::NotMatch, &key)
+        Ok(CSTNode::new(
+            CSTNodeKind::Pattern {
+                or_token,
+                pattern_no_top_alt,
+                repeat_pattern_no_top_alt,
+            },
+            vec![],
+        ))
     }
 
     // PatternNoTopAlt ::= PatternWithoutRange | RangePattern
     fn pattern_no_top_alt(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("PatternNoTopAlt");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::PatternNoTopAlt);
+        match self.get_memo(&key, Self::pattern_no_top_alt) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         if let Ok(expr) = self.pattern_without_range() {
-            self.write_memo(&key, Some(&expr));
             return Ok(expr);
         }
 
@@ -2282,40 +3559,36 @@ impl CSTParser {
     //                         ReferencePattern | StructPattern | TupleStructPattern | TuplePattern | GroupedPattern |
     //                         SlicePattern | PathPattern | MacroInvocation
     fn pattern_without_range(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("PatternWithoutRange");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::PatternWithoutRange);
+        match self.get_memo(&key, Self::pattern_without_range) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // LiteralPattern
         if let Ok(expr) = self.literal_pattern() {
-            self.write_memo(&key, Some(&expr));
             return Ok(expr);
         }
 
         // IdentifierPattern
         if let Ok(expr) = self.identifier_pattern() {
-            self.write_memo(&key, Some(&expr));
             return Ok(expr);
         }
 
         // WildcardPattern
         if let Ok(expr) = self.wildcard_pattern() {
-            self.write_memo(&key, Some(&expr));
             return Ok(expr);
         }
 
         // RestPattern
         if let Ok(expr) = self.rest_pattern() {
-            self.write_memo(&key, Some(&expr));
             return Ok(expr);
         }
 
         // ReferencePattern
         if let Ok(expr) = self.reference_pattern() {
-            self.write_memo(&key, Some(&expr));
             return Ok(expr);
         }
 
@@ -2334,70 +3607,59 @@ impl CSTParser {
     //                  | `-`? INTEGER_LITERAL
     //                  | `-`? FLOAT_LITERAL
     fn literal_pattern(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("LiteralPattern");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::LiteralPattern);
+        match self.get_memo(&key, Self::literal_pattern) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
-        match self.lexer.next() {
-            Token::Keyword(keyword) => match keyword {
-                Keyword::True => {
-                    let node = CSTNode::new(
-                        CSTNodeKind::LiteralPattern {
-                            literal: Literal::new(LiteralKind::Bool(true), ""),
-                        },
-                        vec![],
-                    );
-                    self.write_memo(&key, Some(&node));
-                    Ok(node)
-                }
+        let start = self.lexer.get_token_position();
+        let minus = if self.lexer.peek() == Token::Minus {
+            Some(Rc::new(self.make_factor_and_next()))
+        } else {
+            None
+        };
 
-                Keyword::False => {
-                    let node = CSTNode::new(
-                        CSTNodeKind::LiteralPattern {
-                            literal: Literal::new(LiteralKind::Bool(false), ""),
-                        },
-                        vec![],
-                    );
-                    self.write_memo(&key, Some(&node));
-                    Ok(node)
-                }
-                _ => self.error(SyntaxError::ExpectedToken, &key),
-            },
-            Token::Literal(literal) => {
-                let node = CSTNode::new(CSTNodeKind::LiteralPattern { literal }, vec![]);
-                self.write_memo(&key, Some(&node));
-                Ok(node)
-            }
-            //
-            Token::Minus => {
-                let Token::Literal(literal) = self.lexer.next() else {
-                    return self.error(SyntaxError::NotMatch, &key);
-                };
-
-                match literal.literal_kind {
-                    LiteralKind::Integer | LiteralKind::Float => {
-                        let node = CSTNode::new(CSTNodeKind::LiteralPattern { literal }, vec![]);
-                        self.write_memo(&key, Some(&node));
-                        Ok(node)
-                    }
-                    _ => self.error(SyntaxError::NotMatch, &key),
-                }
+        let (row, column) = self.lexer.get_sorce_position();
+        let literal = match (self.lexer.next(), &minus) {
+            (Token::Keyword(Keyword::True), None) => Literal::new(LiteralKind::Bool(true), ""),
+            (Token::Keyword(Keyword::False), None) => Literal::new(LiteralKind::Bool(false), ""),
+            (Token::Keyword(_), None) => return self.error(SyntaxError::ExpectedToken, &key),
+            (Token::Literal(literal), None) => literal,
+            (Token::Literal(literal), Some(_))
+                if matches!(
+                    literal.literal_kind,
+                    LiteralKind::Integer | LiteralKind::Float
+                ) =>
+            {
+                literal
             }
+            _ => return self.error(SyntaxError::NotMatch, &key),
+        };
 
-            _ => self.error(SyntaxError::NotMatch, &key),
-        }
+        let node = CSTNode::new(
+            CSTNodeKind::LiteralPattern {
+                minus,
+                literal,
+                row,
+                column,
+            },
+            vec![],
+        )
+        .with_span(self.span_from(start));
+        Ok(node)
     }
 
     // IdentifierPattern ::= `ref`? `mut`? Identifier (`@` PatternNoTopAlt )?
     fn identifier_pattern(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("IdentifierPattern");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::IdentifierPattern);
+        match self.get_memo(&key, Self::identifier_pattern) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         let mut ref_keyword = None;
@@ -2408,27 +3670,27 @@ impl CSTParser {
         // `ref`?
         if let Token::Keyword(keyword) = self.lexer.peek() {
             if matches!(keyword, Keyword::Ref) {
-                ref_keyword = Some(Box::new(self.make_factor_and_next()));
+                ref_keyword = Some(Rc::new(self.make_factor_and_next()));
             }
         }
 
         // `mut`?
         if let Token::Keyword(keyword) = self.lexer.peek() {
             if matches!(keyword, Keyword::Mut) {
-                mut_keyword = Some(Box::new(self.make_factor_and_next()));
+                mut_keyword = Some(Rc::new(self.make_factor_and_next()));
             }
         }
 
         // Identifier
         let identifier = match self.lexer.peek() {
-            Token::Identifier(_) => Box::new(self.make_factor_and_next()),
+            Token::Identifier(_) => Rc::new(self.make_factor_and_next()),
             _ => return self.error(SyntaxError::NotMatch, &key),
         };
 
         // (`@` PatternNoTopAlt )?
         if matches!(self.lexer.peek(), Token::At) {
-            at_symbol = Some(Box::new(self.make_factor_and_next()));
-            pattern_no_top_alt = Some(Box::new(self.pattern_no_top_alt()?));
+            at_symbol = Some(Rc::new(self.make_factor_and_next()));
+            pattern_no_top_alt = Some(Rc::new(self.pattern_no_top_alt()?));
         }
 
         let node = CSTNode::new(
@@ -2441,29 +3703,28 @@ impl CSTParser {
             },
             vec![],
         );
-        self.write_memo(&key, Some(&node));
 
         Ok(node)
     }
 
     // WildcardPattern ::= `_`
     fn wildcard_pattern(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("WildcardPattern");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::WildcardPattern);
+        match self.get_memo(&key, Self::wildcard_pattern) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         match self.lexer.peek() {
             Token::Underscore => {
                 let node = CSTNode::new(
                     CSTNodeKind::WildcardPattern {
-                        wildcard: Box::new(self.make_factor_and_next()),
+                        wildcard: Rc::new(self.make_factor_and_next()),
                     },
                     vec![],
                 );
-                self.write_memo(&key, Some(&node));
                 Ok(node)
             }
             _ => self.error(SyntaxError::ExpectedToken, &key),
@@ -2472,30 +3733,22 @@ impl CSTParser {
 
     // RestPattern ::= `..`
     fn rest_pattern(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("RestPattern");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::RestPattern);
+        match self.get_memo(&key, Self::rest_pattern) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         match self.lexer.peek_glue() {
             Token::DotDot => {
-                let pos = self.lexer.get_sorce_position();
                 let node = CSTNode::new(
                     CSTNodeKind::RestPattern {
-                        rest: Box::new(CSTNode::new(
-                            CSTNodeKind::Factor {
-                                token: self.lexer.next_glue(),
-                                row: pos.0,
-                                column: pos.1,
-                            },
-                            vec![],
-                        )),
+                        rest: Rc::new(self.make_glued_factor_and_next()),
                     },
                     vec![],
                 );
-                self.write_memo(&key, Some(&node));
                 Ok(node)
             }
             _ => self.error(SyntaxError::ExpectedToken, &key),
@@ -2504,11 +3757,12 @@ impl CSTParser {
 
     // ReferencePattern ::= (`&`|`&&`) mut? PatternWithoutRange
     fn reference_pattern(&mut self) -> Result<CSTNode, Error> {
-        let key = self.make_key("ReferencePattern");
-        match self.get_memo(&key) {
+        let key = self.make_key(ParseRule::ReferencePattern);
+        match self.get_memo(&key, Self::reference_pattern) {
             MemoResult::Some(res) => return Ok(res),
+            MemoResult::Fail => return self.error(SyntaxError::NotMatch, &key),
             MemoResult::Recursive => return self.error(SyntaxError::Recursed, &key),
-            MemoResult::None => self.write_memo(&key, None),
+            MemoResult::None => (),
         };
 
         // (`&`|`&&`)
@@ -2517,7 +3771,7 @@ impl CSTParser {
         }
 
         // mut?
-        if !matches!(self.lexer.peek(), Token::Keyword(Keyword::Mut)) {
+        if !self.is_token(Token::Keyword(Keyword::Mut)) {
             return self.error(SyntaxError::ExpectedToken, &key);
         }
 
@@ -2525,11 +3779,42 @@ impl CSTParser {
         self.pattern_without_range()
     }
 
+    //
+    // Diagnostics
+    //
+
+    // 閉じ括弧用
+    // 対応する開き括弧の位置も記録する
+    fn is_closing_token(&mut self, token: Token, opener: &CSTNode) -> bool {
+        if self.lexer.peek() == token {
+            return true;
+        }
+
+        let CSTNodeKind::Factor {
+            token: opener_token,
+            row,
+            column,
+        } = &opener.node_kind
+        else {
+            self.expected(&format!("`{}`", token));
+            return false;
+        };
+
+        self.expected_closing(token, opener_token.clone(), (*row, *column));
+        false
+    }
+
     //
     //
     //
 
+    // start番目のトークンから今の位置の手前までの範囲
+    fn span_from(&self, start: usize) -> Option<Span> {
+        self.lexer.span(start, self.lexer.get_token_position())
+    }
+
     fn make_factor(&self) -> CSTNode {
+        let start = self.lexer.get_token_position();
         let pos = self.lexer.get_sorce_position();
         let token = self.lexer.peek();
         CSTNode::new(
@@ -2540,9 +3825,11 @@ impl CSTParser {
             },
             vec![],
         )
+        .with_span(self.lexer.span(start, start + 1))
     }
 
     fn make_factor_and_next(&mut self) -> CSTNode {
+        let start = self.lexer.get_token_position();
         let pos = self.lexer.get_sorce_position();
         let token = self.lexer.next();
         CSTNode::new(
@@ -2553,120 +3840,117 @@ impl CSTParser {
             },
             vec![],
         )
+        .with_span(self.span_from(start))
     }
 
-    fn make_operator_and_next(&mut self) -> CSTNode {
+    fn make_glued_factor_and_next(&mut self) -> CSTNode {
+        let start = self.lexer.get_token_position();
         let pos = self.lexer.get_sorce_position();
         let token = self.lexer.next_glue();
         CSTNode::new(
-            CSTNodeKind::Operator {
+            CSTNodeKind::Factor {
                 token,
                 row: pos.0,
                 column: pos.1,
             },
             vec![],
         )
+        .with_span(self.span_from(start))
     }
 
-    fn make_key(&self, rule: &str) -> ParseMemoKey {
-        ParseMemoKey {
-            position: self.lexer.get_token_position(),
-            rule: rule.to_string(),
-        }
+    fn make_operator_and_next(&mut self) -> CSTNode {
+        let start = self.lexer.get_token_position();
+        let pos = self.lexer.get_sorce_position();
+        let token = self.lexer.next_glue();
+        CSTNode::new(
+            CSTNodeKind::Operator {
+                token,
+                row: pos.0,
+                column: pos.1,
+            },
+            vec![],
+        )
+        .with_span(self.span_from(start))
     }
+}
 
-    fn write_memo(&mut self, key: &ParseMemoKey, memo: Option<&CSTNode>) {
-        self.log.push_str(&format!(
-            "WriteMemo {} pos: {:?} token: {:?} \n",
-            key.rule,
-            key.position,
-            self.lexer.peek()
-        ));
-
-        if let Some(node) = memo {
-            match key.rule.as_str() {
-                "Statement" => self.min_bp = 0,
-                _ => (),
-            };
+// `{`で始まるDelimTokenTreeか
+fn is_brace_token_tree(node: &CSTNode) -> bool {
+    let CSTNodeKind::DelimTokenTree {
+        left_parenthesis, ..
+    } = &node.node_kind
+    else {
+        return false;
+    };
+
+    matches!(
+        left_parenthesis.node_kind,
+        CSTNodeKind::Factor {
+            token: Token::LeftParenthesis(LeftParenthesis::Brace),
+            ..
+        }
+    )
+}
 
-            self.memo.insert(
-                key.clone(),
-                Some(ParseMemoValue {
-                    node: node.clone(),
-                    next_position: self.lexer.get_token_position(),
-                }),
-            );
-        } else {
-            self.memo.insert(key.clone(), None);
-        }
+// OuterAttributeはアイテム自身に持たせる
+fn set_outer_attribute(node: &mut CSTNode, outer_attribute: Vec<CSTNode>) {
+    if let CSTNodeKind::Function {
+        outer_attribute: attribute,
+        ..
     }
-
-    fn get_memo(&mut self, key: &ParseMemoKey) -> MemoResult<CSTNode> {
-        let Some(node) = self.memo.get(key) else {
-            self.log.push_str(&format!(
-                "First call to {} pos: {:?} token: {:?} \n",
-                key.rule,
-                key.position,
-                self.lexer.peek()
-            ));
-
-            return MemoResult::None;
-        };
-
-        let Some(value) = node else {
-            self.log.push_str(&format!(
-                "Recursed {} pos: {:?} token: {:?}\n",
-                key.rule,
-                key.position,
-                self.lexer.peek()
-            ));
-
-            return MemoResult::Recursive;
-        };
-
-        self.log.push_str(&format!(
-            "Use memo {} pos: {:?} token: {:?}\n",
-            key.rule,
-            key.position,
-            self.lexer.peek()
-        ));
-
-        // メモがあった場合解析が進んだ場所まで移動
-        self.lexer.set_postion(value.next_position);
-        MemoResult::Some(value.node.clone())
+    | CSTNodeKind::TypeAlias {
+        outer_attribute: attribute,
+        ..
     }
-
-    fn backtrack(&mut self, position: usize) {
-        self.lexer.set_postion(position);
+    | CSTNodeKind::Struct {
+        outer_attribute: attribute,
+        ..
     }
+    | CSTNodeKind::Implementation {
+        outer_attribute: attribute,
+        ..
+    } = &mut node.node_kind
+    {
+        *attribute = outer_attribute;
+        node.update_span();
+    }
+}
 
-    // まともなエラー出力用のプロジェクトができるまで仮で
-    fn error(&mut self, error_type: SyntaxError, key: &ParseMemoKey) -> Result<CSTNode, Error> {
-        self.log.push_str(&format!(
-            "Error({:?}) {} pos: {:?} token: {:?}\n",
-            error_type,
-            key.rule,
-            key.position,
-            self.lexer.peek()
-        ));
-
-        self.backtrack(key.position);
-
-        Err(Error {
-            error_kind: ErrorKind::Syntax(error_type),
-            error_text: "".to_string(),
-        })
+// Visibilityもアイテム自身に持たせる
+fn set_visibility(node: &mut CSTNode, visibility: Option<Rc<CSTNode>>) {
+    if let CSTNodeKind::Function {
+        visibility: item_visibility,
+        ..
+    }
+    | CSTNodeKind::TypeAlias {
+        visibility: item_visibility,
+        ..
+    }
+    | CSTNodeKind::Struct {
+        visibility: item_visibility,
+        ..
     }
+    | CSTNodeKind::Implementation {
+        visibility: item_visibility,
+        ..
+    } = &mut node.node_kind
+    {
+        *item_visibility = visibility;
+        node.update_span();
+    }
+}
 
-    pub fn output_log_file(&self, file_name: &str) {
-        let mut log_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(file_name)
-            .unwrap();
-        let Err(_) = write!(log_file, "{}", self.log) else {
-            println!("log output error!");
-            return;
-        };
+// OuterAttributeがあればExpressionWithBlockで包む
+fn wrap_outer_attribute(expression: CSTNode, outer_attribute: Vec<CSTNode>) -> CSTNode {
+    if outer_attribute.is_empty() {
+        return expression;
     }
+
+    CSTNode::new(
+        CSTNodeKind::ExpressionWithBlock {
+            outer_attribute,
+            expression_with_block: Rc::new(expression),
+        },
+        vec![],
+    )
 }
@@ -0,0 +1,4 @@
+pub mod program_generator;
+
+#[cfg(test)]
+mod tests;
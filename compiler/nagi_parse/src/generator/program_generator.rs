@@ -0,0 +1,772 @@
+use crate::expression::{infix_binding_power, prefix_binding_power};
+use nagi_syntax_tree::token::Token;
+
+// cst.rsの文法から、構文エラーの無いプログラムをランダムに作る
+// 同じseedとmax_depthからは同じプログラムができる
+//
+// 構文解析器がまだ解析できない部分は作らない
+//   StructExpression、RestPattern、ジェネリクス引数、文字リテラル
+pub struct ProgramGenerator {
+    random: Random,
+    max_depth: usize,
+    binary_operators: Vec<&'static str>,
+    unary_operators: Vec<&'static str>,
+    source: String,
+}
+
+const IDENTIFIERS: &[&str] = &["a", "b", "c", "foo", "bar", "value", "x1", "_y"];
+const TYPES: &[&str] = &["i32", "u8", "f32", "bool", "A", "Vec3", "Self"];
+
+impl ProgramGenerator {
+    pub fn new(seed: u64, max_depth: usize) -> Self {
+        // 演算子は優先順位の表から選ぶ
+        // `<-`のようにつなげない記号は`<` `-`と区別できないので除く
+        let spellings = |has_binding_power: fn(&Token) -> bool| {
+            Token::PUNCTUATIONS
+                .iter()
+                .filter(|token| has_binding_power(token))
+                .map(|token| token.punctuation_str().unwrap())
+                .filter(|spelling| {
+                    spelling.len() == 1
+                        || Token::from_punctuation_str(spelling)
+                            .is_some_and(|token| token.is_joint_punctuation())
+                })
+                .collect()
+        };
+
+        Self {
+            random: Random::new(seed),
+            max_depth,
+            binary_operators: spellings(|token| infix_binding_power(token).is_some()),
+            unary_operators: spellings(|token| prefix_binding_power(token).is_some()),
+            source: String::new(),
+        }
+    }
+
+    pub fn generate(&mut self) -> String {
+        self.source.clear();
+
+        // Crate ::= InnerAttribute* Item*
+        if self.random.chance(4) {
+            self.inner_attribute();
+        }
+        for _ in 0..self.random.range(1, 3) {
+            self.item(self.max_depth);
+        }
+
+        std::mem::take(&mut self.source)
+    }
+
+    //
+    // 出力
+    //
+
+    // トークンは空白で区切る
+    fn token(&mut self, token: &str) {
+        self.source.push_str(token);
+        self.source.push(' ');
+    }
+
+    fn newline(&mut self) {
+        self.source.push('\n');
+    }
+
+    fn choose(&mut self, tokens: &[&'static str]) -> &'static str {
+        tokens[self.random.below(tokens.len())]
+    }
+
+    fn identifier(&mut self) {
+        let identifier = self.choose(IDENTIFIERS);
+        self.token(identifier);
+    }
+
+    //
+    // 属性とマクロ
+    //
+
+    fn inner_attribute(&mut self) {
+        self.token("#");
+        self.token("!");
+        self.token("[");
+        self.attribute();
+        self.token("]");
+        self.newline();
+    }
+
+    fn outer_attributes(&mut self) {
+        while self.random.chance(5) {
+            self.token("#");
+            self.token("[");
+            self.attribute();
+            self.token("]");
+        }
+    }
+
+    // Attribute ::= SimplePath AttributeInput? | `unsafe` `(` SimplePath AttributeInput? `)`
+    fn attribute(&mut self) {
+        let is_unsafe = self.random.chance(6);
+        if is_unsafe {
+            self.token("unsafe");
+            self.token("(");
+        }
+
+        self.simple_path();
+        match self.random.below(3) {
+            0 => self.delim_token_tree(1),
+            1 => {
+                self.token("=");
+                self.literal();
+            }
+            _ => (),
+        }
+
+        if is_unsafe {
+            self.token(")");
+        }
+    }
+
+    // SimplePath ::= `::`? SimplePathSegment (`::` SimplePathSegment)*
+    fn simple_path(&mut self) {
+        if self.random.chance(8) {
+            self.token("::");
+        }
+        self.identifier();
+        while self.random.chance(4) {
+            self.token("::");
+            self.identifier();
+        }
+    }
+
+    // DelimTokenTree ::= `(` TokenTree* `)` | `[` TokenTree* `]` | `{` TokenTree* `}`
+    fn delim_token_tree(&mut self, depth: usize) {
+        self.delim_token_tree_in(depth, 3);
+    }
+
+    // delimiter_countが2なら`{`を使わない
+    fn delim_token_tree_in(&mut self, depth: usize, delimiter_count: usize) {
+        let (left, right) = match self.random.below(delimiter_count) {
+            0 => ("(", ")"),
+            1 => ("[", "]"),
+            _ => ("{", "}"),
+        };
+
+        self.token(left);
+        for _ in 0..self.random.below(5) {
+            match self.random.below(4) {
+                0 if depth > 0 => self.delim_token_tree(depth - 1),
+                1 => self.literal(),
+                2 => {
+                    let punctuation =
+                        self.choose(&["+", "-", "*", ",", ";", ":", ".", "=", "$", "#"]);
+                    self.token(punctuation);
+                }
+                _ => self.identifier(),
+            }
+        }
+        self.token(right);
+    }
+
+    // MacroInvocationSemi ::= SimplePath `!` `(` TokenTree* `)` `;` | ... | SimplePath `!` `{` TokenTree* `}`
+    fn macro_invocation_semi(&mut self) {
+        self.simple_path();
+        self.token("!");
+        let start = self.source.len();
+        self.delim_token_tree(2);
+        if !self.source[start..].starts_with('{') {
+            self.token(";");
+        }
+        self.newline();
+    }
+
+    // MacroRulesDefinition ::= `macro_rules` `!` Identifier MacroRulesDef
+    fn macro_rules_definition(&mut self) {
+        self.token("macro_rules");
+        self.token("!");
+        self.identifier();
+        self.token("{");
+        self.token("(");
+        self.token("$");
+        self.identifier();
+        self.token(":");
+        self.token("expr");
+        self.token(")");
+        self.token("=>");
+        self.delim_token_tree(2);
+        self.token(";");
+        self.token("}");
+        self.newline();
+    }
+
+    //
+    // アイテム
+    //
+
+    // Item ::= OuterAttribute* VisItem | MacroItem
+    fn item(&mut self, depth: usize) {
+        match self.random.below(7) {
+            0 => self.macro_invocation_semi(),
+            1 => self.macro_rules_definition(),
+            choice => {
+                self.outer_attributes();
+                self.visibility();
+                match choice {
+                    2 => self.type_alias(),
+                    3 => self.struct_item(),
+                    4 => self.implementation(depth),
+                    _ => self.function(depth),
+                }
+            }
+        }
+    }
+
+    // Visibility ::= `pub`
+    fn visibility(&mut self) {
+        if self.random.chance(4) {
+            self.token("pub");
+        }
+    }
+
+    // Function ::= FunctionQualifiers `fn` Identifier GenericParams?
+    //             `(` FunctionParameters? `)` FunctionReturnType? ( BlockExpression | `;` )
+    fn function(&mut self, depth: usize) {
+        // FunctionQualifiers ::= `const`? `async`? ItemSafety? (`extern` Abi?)?
+        if self.random.chance(6) {
+            self.token("const");
+        }
+        if self.random.chance(6) {
+            self.token("async");
+        }
+        if self.random.chance(5) {
+            let item_safety = self.choose(&["unsafe", "safe"]);
+            self.token(item_safety);
+        }
+        if self.random.chance(8) {
+            self.token("extern");
+            if self.random.chance(2) {
+                self.token("\"C\"");
+            }
+        }
+
+        self.token("fn");
+        self.identifier();
+        if self.random.chance(8) {
+            self.token("<");
+            self.token(">");
+        }
+
+        self.token("(");
+        self.function_parameters();
+        self.token(")");
+
+        if self.random.chance(2) {
+            self.token("->");
+            self.type_path();
+        }
+
+        if self.random.chance(8) {
+            self.token(";");
+        } else {
+            self.block_expression(depth);
+        }
+        self.newline();
+    }
+
+    // FunctionParameters ::= SelfParam `,`? | (SelfParam `,`)? FunctionParam (`,` FunctionParam)* `,`?
+    fn function_parameters(&mut self) {
+        let has_self_param = self.random.chance(3);
+        let param_count = self.random.below(4);
+
+        if has_self_param {
+            self.self_param();
+            if param_count > 0 || self.random.chance(3) {
+                self.token(",");
+            }
+        }
+
+        for i in 0..param_count {
+            if i > 0 {
+                self.token(",");
+            }
+            // FunctionParam ::= OuterAttribute* PatternNoTopAlt `:` Type
+            self.outer_attributes();
+            self.pattern_no_top_alt(0);
+            self.token(":");
+            self.type_path();
+        }
+        if param_count > 0 && self.random.chance(4) {
+            self.token(",");
+        }
+    }
+
+    // SelfParam ::= OuterAttribute* ( ShorthandSelf | TypedSelf )
+    fn self_param(&mut self) {
+        self.outer_attributes();
+        if self.random.chance(3) {
+            // TypedSelf ::= `mut`? `self` `:` Type
+            if self.random.chance(2) {
+                self.token("mut");
+            }
+            self.token("self");
+            self.token(":");
+            self.type_path();
+        } else {
+            // ShorthandSelf ::= `&`? `mut`? `self`
+            if self.random.chance(2) {
+                self.token("&");
+            }
+            if self.random.chance(3) {
+                self.token("mut");
+            }
+            self.token("self");
+        }
+    }
+
+    // TypeAlias ::= `type` Identifier `=` Type `;`
+    fn type_alias(&mut self) {
+        self.token("type");
+        self.identifier();
+        self.token("=");
+        self.type_path();
+        self.token(";");
+        self.newline();
+    }
+
+    // Struct ::= `struct` Identifier ( `{` StructFields? `}` | `;` )
+    fn struct_item(&mut self) {
+        self.token("struct");
+        self.identifier();
+        if self.random.chance(4) {
+            self.token(";");
+            self.newline();
+            return;
+        }
+
+        self.token("{");
+        let field_count = self.random.below(4);
+        for i in 0..field_count {
+            if i > 0 {
+                self.token(",");
+            }
+            // StructField ::= OuterAttribute* Visibility? Identifier `:` Type
+            self.outer_attributes();
+            if self.random.chance(2) {
+                self.token("pub");
+            }
+            self.identifier();
+            self.token(":");
+            self.type_path();
+        }
+        if field_count > 0 && self.random.chance(3) {
+            self.token(",");
+        }
+        self.token("}");
+        self.newline();
+    }
+
+    // Implementation ::= `impl` Type `{` AssociatedItem* `}` | `impl` TypePath `for` Type `{` AssociatedItem* `}`
+    fn implementation(&mut self, depth: usize) {
+        self.token("impl");
+        if self.random.chance(2) {
+            self.type_path();
+            self.token("for");
+        }
+        self.type_path();
+        self.token("{");
+        self.newline();
+
+        // AssociatedItem ::= OuterAttribute* ( TypeAlias | Visibility? Function )
+        for _ in 0..self.random.below(3) {
+            self.outer_attributes();
+            if self.random.chance(3) {
+                self.type_alias();
+            } else {
+                self.visibility();
+                self.function(depth.saturating_sub(1));
+            }
+        }
+        self.token("}");
+        self.newline();
+    }
+
+    // TypePath ::= `::`? PathIdentSegment (`::` PathIdentSegment)*
+    fn type_path(&mut self) {
+        if self.random.chance(8) {
+            self.token("::");
+        }
+        let type_name = self.choose(TYPES);
+        self.token(type_name);
+        if self.random.chance(6) {
+            self.token("::");
+            self.identifier();
+        }
+    }
+
+    //
+    // 文とブロック
+    //
+
+    // BlockExpression ::= `{` InnerAttribute* Statements? `}`
+    fn block_expression(&mut self, depth: usize) {
+        self.token("{");
+        self.newline();
+        if self.random.chance(8) {
+            self.inner_attribute();
+        }
+
+        if depth > 0 {
+            for _ in 0..self.random.below(4) {
+                self.statement(depth - 1);
+            }
+            if self.random.chance(2) {
+                self.expression(depth - 1, false);
+                self.newline();
+            }
+        }
+        self.token("}");
+    }
+
+    // Statement ::= `;` | Item | LetStatement | ExpressionStatement | MacroInvocationSemi
+    fn statement(&mut self, depth: usize) {
+        match self.random.below(12) {
+            0 => {
+                self.token(";");
+                self.newline();
+            }
+            1 => self.item(depth),
+            2 => self.macro_invocation_semi(),
+            3..=6 => self.let_statement(depth),
+            7 => {
+                // ExpressionWithBlock `;`?
+                self.outer_attributes();
+                self.expression_with_block(depth);
+                if self.random.chance(3) {
+                    self.token(";");
+                }
+                self.newline();
+            }
+            8 => {
+                self.token("return");
+                if self.random.chance(2) {
+                    self.expression(depth, true);
+                }
+                self.token(";");
+                self.newline();
+            }
+            _ => {
+                // ExpressionWithoutBlock `;`
+                // 文の先頭がブロックだとそこで文が終わるので、ブロックを含む式は作らない
+                self.expression(depth, false);
+                self.token(";");
+                self.newline();
+            }
+        }
+    }
+
+    // LetStatement ::= OuterAttribute* (`ur` | `sr` | `nr` | `let`)
+    //                  PatternNoTopAlt ( `:` Type )? (`=` Expression ( `else` BlockExpression)? )? `;`
+    fn let_statement(&mut self, depth: usize) {
+        self.outer_attributes();
+        let rarity = self.choose(&["let", "let", "ur", "sr", "nr"]);
+        self.token(rarity);
+        self.pattern_no_top_alt(depth);
+
+        if self.random.chance(3) {
+            self.token(":");
+            self.type_path();
+        }
+
+        if self.random.chance(5) {
+            self.token("=");
+            // `else`の前の式がブロックで終わると区別できない
+            if self.random.chance(4) {
+                self.expression(depth, false);
+                self.token("else");
+                self.block_expression(depth);
+            } else {
+                self.expression(depth, true);
+            }
+        }
+        self.token(";");
+        self.newline();
+    }
+
+    //
+    // 式
+    //
+
+    // 演算子式
+    // allow_blockがfalseならブロックを含む式を作らない
+    fn expression(&mut self, depth: usize, allow_block: bool) {
+        if depth == 0 || self.random.chance(3) {
+            self.operand(depth, allow_block);
+            return;
+        }
+
+        if self.random.chance(4) {
+            let operator = self.unary_operators[self.random.below(self.unary_operators.len())];
+            self.token(operator);
+            self.expression(depth - 1, allow_block);
+        } else {
+            self.expression(depth - 1, allow_block);
+            let operator = self.binary_operators[self.random.below(self.binary_operators.len())];
+            self.token(operator);
+            self.expression(depth - 1, allow_block);
+        }
+    }
+
+    fn operand(&mut self, depth: usize, allow_block: bool) {
+        if depth == 0 {
+            if self.random.chance(2) {
+                self.literal();
+            } else {
+                self.path_expression();
+            }
+            return;
+        }
+
+        match self.random.below(10) {
+            0 => self.literal(),
+            1 => {
+                // GroupedExpression ::= `(` Expression `)`
+                self.token("(");
+                self.expression(depth - 1, true);
+                self.token(")");
+            }
+            2 => {
+                // AsyncBlockExpression ::= `async` `move`? BlockExpression
+                self.token("async");
+                if self.random.chance(2) {
+                    self.token("move");
+                }
+                self.block_expression(depth - 1);
+            }
+            3 => self.macro_invocation(depth),
+            4 if allow_block => self.expression_with_block(depth - 1),
+            _ => self.postfix_expression(depth),
+        }
+    }
+
+    // 後置の式は被演算子だけにかかる
+    // CallExpression、MethodCallExpression、FieldExpression、AwaitExpression、IndexExpression
+    fn postfix_expression(&mut self, depth: usize) {
+        self.path_expression();
+
+        for _ in 0..self.random.below(3) {
+            match self.random.below(5) {
+                0 => {
+                    self.token("(");
+                    self.call_params(depth - 1);
+                    self.token(")");
+                }
+                1 => {
+                    self.token(".");
+                    self.identifier();
+                    self.token("(");
+                    self.call_params(depth - 1);
+                    self.token(")");
+                }
+                2 => {
+                    self.token(".");
+                    self.identifier();
+                }
+                3 => {
+                    self.token(".");
+                    self.token("await");
+                }
+                _ => {
+                    self.token("[");
+                    self.expression(depth - 1, true);
+                    self.token("]");
+                }
+            }
+        }
+    }
+
+    // CallParams ::= Expression ( `,` Expression )* `,`?
+    fn call_params(&mut self, depth: usize) {
+        let count = self.random.below(4);
+        for i in 0..count {
+            if i > 0 {
+                self.token(",");
+            }
+            self.expression(depth, true);
+        }
+        if count > 0 && self.random.chance(4) {
+            self.token(",");
+        }
+    }
+
+    // MacroInvocation ::= SimplePath `!` DelimTokenTree
+    // BuiltinMacroInvocation ::= BuiltinMacroName `!` `(` CallParams? `)`
+    fn macro_invocation(&mut self, depth: usize) {
+        if self.random.chance(2) {
+            let name = self.choose(&["println", "format", "assert_eq", "panic"]);
+            self.token(name);
+            self.token("!");
+            self.token("(");
+            self.call_params(depth - 1);
+            self.token(")");
+        } else {
+            // `m!{}`は後ろに演算子が続いても文の終わりになるので使わない
+            self.simple_path();
+            self.token("!");
+            self.delim_token_tree_in(depth, 2);
+        }
+    }
+
+    // PathInExpression ::= `::`? PathExprSegment (`::` PathExprSegment)*
+    fn path_expression(&mut self) {
+        if self.random.chance(10) {
+            self.token("::");
+        }
+        if self.random.chance(10) {
+            let keyword = self.choose(&["self", "Self", "super", "crate"]);
+            self.token(keyword);
+        } else {
+            self.identifier();
+        }
+        while self.random.chance(5) {
+            self.token("::");
+            self.identifier();
+        }
+    }
+
+    fn literal(&mut self) {
+        let literal = self.choose(&[
+            "0", "1", "42", "7u8", "1_000", "0x1f", "1.5", "2.0f32", "\"str\"", "true", "false",
+        ]);
+        self.token(literal);
+    }
+
+    // ExpressionWithBlock ::= BlockExpression | ConstBlockExpression | UnsafeBlockExpression
+    //                       | IfExpression | IfLetExpression
+    fn expression_with_block(&mut self, depth: usize) {
+        match self.random.below(5) {
+            0 => self.block_expression(depth),
+            1 => {
+                self.token("const");
+                self.block_expression(depth);
+            }
+            2 => {
+                self.token("unsafe");
+                self.block_expression(depth);
+            }
+            _ => self.if_expression(depth),
+        }
+    }
+
+    // IfExpression ::= `if` (Expression | LetChain) BlockExpression (`else` ( BlockExpression | IfExpression | IfLetExpression ) )?
+    // IfLetExpression ::= `if` `let` Pattern `=` Scrutinee BlockExpression (`else` ...)?
+    fn if_expression(&mut self, depth: usize) {
+        self.token("if");
+
+        // 条件の式がブロックを含むと、本体のブロックと区別できない
+        let condition_count = self.random.range(1, 3);
+        for i in 0..condition_count {
+            if i > 0 {
+                self.token("&&");
+            }
+            if self.random.chance(2) {
+                // `let` Pattern `=` Scrutinee
+                // Scrutineeの中の`&&`は次の条件との区切りになるので演算子を使わない
+                self.token("let");
+                self.pattern(depth);
+                self.token("=");
+                self.postfix_expression(depth.max(1));
+            } else if condition_count == 1 {
+                self.expression(depth, false);
+            } else {
+                self.postfix_expression(depth.max(1));
+            }
+        }
+
+        self.block_expression(depth);
+
+        if self.random.chance(2) {
+            self.token("else");
+            if depth > 0 && self.random.chance(3) {
+                self.if_expression(depth - 1);
+            } else {
+                self.block_expression(depth);
+            }
+        }
+    }
+
+    //
+    // パターン
+    //
+
+    // Pattern ::= `|`? PatternNoTopAlt ( `|` PatternNoTopAlt )*
+    fn pattern(&mut self, depth: usize) {
+        if self.random.chance(8) {
+            self.token("|");
+        }
+        self.pattern_no_top_alt(depth);
+        while self.random.chance(5) {
+            self.token("|");
+            self.pattern_no_top_alt(depth);
+        }
+    }
+
+    // PatternNoTopAlt ::= LiteralPattern | IdentifierPattern | WildcardPattern
+    fn pattern_no_top_alt(&mut self, depth: usize) {
+        match self.random.below(6) {
+            0 => self.token("_"),
+            1 => {
+                let literal = self.choose(&["true", "false", "1", "-1", "-1.5", "\"str\""]);
+                self.token(literal);
+            }
+            _ => {
+                // IdentifierPattern ::= `ref`? `mut`? Identifier (`@` PatternNoTopAlt )?
+                if self.random.chance(4) {
+                    self.token("ref");
+                }
+                if self.random.chance(3) {
+                    self.token("mut");
+                }
+                self.identifier();
+                if depth > 0 && self.random.chance(6) {
+                    self.token("@");
+                    self.pattern_no_top_alt(depth - 1);
+                }
+            }
+        }
+    }
+}
+
+// xorshift64*
+// テストを再現できるように外部のクレートを使わず、seedだけで決まる乱数にする
+pub(crate) struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub(crate) fn new(seed: u64) -> Self {
+        // 0のままだとずっと0になる
+        Self {
+            state: seed ^ 0x9e37_79b9_7f4a_7c15,
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // 0..n
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    // min..=max
+    fn range(&mut self, min: usize, max: usize) -> usize {
+        min + self.below(max - min + 1)
+    }
+
+    // 1/nの確率でtrue
+    fn chance(&mut self, n: usize) -> bool {
+        self.below(n) == 0
+    }
+}
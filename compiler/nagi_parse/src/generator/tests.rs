@@ -0,0 +1,209 @@
+#[cfg(test)]
+mod test {
+    use crate::ast_parse::ast_parser::ASTParser;
+    use crate::cst_parse::cst_parser::CSTParser;
+    use crate::generator::program_generator::{ProgramGenerator, Random};
+    use crate::lexer;
+    use nagi_command_option::{FormatStyle, TrailingComma};
+    use nagi_errors::{ErrorKind, LexicalError, SourcePosition};
+    use nagi_lexer::lexer::Lexer;
+    use nagi_syntax_tree::ast::ASTNode;
+    use nagi_syntax_tree::cst::CSTNode;
+    use nagi_syntax_tree::pretty::{print_ast, print_cst};
+    use nagi_syntax_tree::token::Token;
+
+    const SEED_COUNT: u64 = 200;
+    const MAX_DEPTH: usize = 4;
+
+    fn token_lexer(code: &str) -> lexer::Lexer {
+        let mut lexer = Lexer::new(code);
+        lexer::Lexer::new(&lexer.tokenize())
+    }
+
+    // 空白とコメントを除いたトークン
+    fn source_tokens(code: &str) -> Vec<Token> {
+        token_lexer(code)
+            .tokens()
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    fn parse(code: &str) -> CSTNode {
+        let result = CSTParser::from_lexer(token_lexer(code)).parse();
+        assert!(result.errors.is_empty(), "{:?}\n{}", result.errors, code);
+        result.tree
+    }
+
+    fn parse_ast(code: &str) -> ASTNode {
+        let result = ASTParser::from_lexer(token_lexer(code)).parse();
+        assert!(result.errors.is_empty(), "{:?}\n{}", result.errors, code);
+        result.tree
+    }
+
+    // CSTのトークンを空白区切りで並べる
+    fn print(cst: &CSTNode) -> String {
+        cst.tokens()
+            .iter()
+            .map(|token| token.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn same_seed_same_program() {
+        for depth in 0..=MAX_DEPTH {
+            assert_eq!(
+                ProgramGenerator::new(1, depth).generate(),
+                ProgramGenerator::new(1, depth).generate()
+            );
+        }
+        assert_ne!(
+            ProgramGenerator::new(1, MAX_DEPTH).generate(),
+            ProgramGenerator::new(2, MAX_DEPTH).generate()
+        );
+    }
+
+    #[test]
+    fn generated_programs_parse() {
+        for depth in 0..=MAX_DEPTH {
+            let mut generator = ProgramGenerator::new(depth as u64, depth);
+            for _ in 0..SEED_COUNT {
+                parse(&generator.generate());
+            }
+        }
+    }
+
+    #[test]
+    fn cst_prints_back_to_source() {
+        for seed in 0..SEED_COUNT {
+            let code = ProgramGenerator::new(seed, MAX_DEPTH).generate();
+            let cst = parse(&code);
+
+            // 元のソースコードと同じトークン列になり、解析し直しても同じになる
+            let printed = print(&cst);
+            assert_eq!(source_tokens(&printed), source_tokens(&code), "{}", code);
+            assert_eq!(print(&parse(&printed)), printed, "{}", code);
+        }
+    }
+
+    #[test]
+    fn pretty_printed_programs_parse_back() {
+        for seed in 0..SEED_COUNT {
+            let code = ProgramGenerator::new(seed, MAX_DEPTH).generate();
+
+            let printed = print_cst(&parse(&code));
+            assert_eq!(source_tokens(&printed), source_tokens(&code), "{}", code);
+            assert_eq!(print_cst(&parse(&printed)), printed, "{}", code);
+
+            let ast = parse_ast(&code);
+            let printed = print_ast(&ast);
+            assert_eq!(parse_ast(&printed), ast, "{}\n{}", code, printed);
+        }
+    }
+
+    // 整形しても`,`以外のトークンは変わらず、もう一度整形しても変わらない
+    #[test]
+    fn formatted_programs_keep_tokens() {
+        let without_comma = |code: &str| {
+            let mut tokens = source_tokens(code);
+            tokens.retain(|token| *token != Token::Comma);
+            tokens
+        };
+        let narrow = FormatStyle {
+            max_width: 20,
+            trailing_comma: TrailingComma::Always,
+            ..FormatStyle::default()
+        };
+
+        for seed in 0..SEED_COUNT {
+            let code = ProgramGenerator::new(seed, MAX_DEPTH).generate();
+
+            for style in [FormatStyle::default(), narrow.clone()] {
+                let formatted = crate::format_source(&code, &style).unwrap();
+                assert_eq!(without_comma(&formatted), without_comma(&code), "{}", code);
+                assert_eq!(
+                    crate::format_source(&formatted, &style).unwrap(),
+                    formatted,
+                    "{}",
+                    code
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn broken_programs_do_not_panic() {
+        for seed in 0..SEED_COUNT {
+            let code = ProgramGenerator::new(seed, MAX_DEPTH).generate();
+            let lexer = token_lexer(&code);
+            let tokens = lexer.tokens();
+            let eof_position = lexer.eof_position();
+
+            // トークンを一つ消す、複製する、隣と入れ替える、途中で切る
+            let index = seed as usize % tokens.len();
+            let mut mutations = vec![tokens.clone(); 4];
+            mutations[0].remove(index);
+            mutations[1].insert(index, tokens[index].clone());
+            mutations[2].swap(index, (index + 1) % tokens.len());
+            mutations[3].truncate(index);
+
+            for mutation in mutations {
+                let lexer = lexer::Lexer::from_tokens(mutation, eof_position);
+                CSTParser::from_lexer(lexer).parse();
+            }
+        }
+    }
+
+    // 字句として読めない文字列も、パニックせずにエラーになる
+    #[test]
+    fn junk_source_does_not_panic() {
+        let parse_errors = |code: &str| {
+            let result = CSTParser::from_lexer(token_lexer(code)).parse();
+            assert!(!result.errors.is_empty(), "{:?}", code);
+            result.errors
+        };
+
+        for seed in 0..SEED_COUNT {
+            let code = ProgramGenerator::new(seed, MAX_DEPTH).generate();
+            let mut random = Random::new(seed);
+
+            // 行の始めに読めない文字を入れる、閉じていないリテラルで終わらせる
+            let line_starts = [0]
+                .into_iter()
+                .chain(code.match_indices('\n').map(|(index, _)| index + 1))
+                .collect::<Vec<_>>();
+            let index = line_starts[random.below(line_starts.len())];
+            for junk in ["`", "\"", "\"a\\q\" ", "\0", "0b2 ", "'"] {
+                let mut broken = code.clone();
+                broken.insert_str(index, junk);
+                parse_errors(&broken);
+            }
+
+            // でたらめなバイト列
+            let bytes = (0..64).map(|_| random.below(256) as u8).collect::<Vec<_>>();
+            CSTParser::from_lexer(token_lexer(&String::from_utf8_lossy(&bytes))).parse();
+        }
+
+        let errors = parse_errors("fn a() {\n    let b = `;\n}");
+        assert_eq!(
+            errors[0].error_kind,
+            ErrorKind::Lexcal(LexicalError::IllegalCharacter)
+        );
+        assert_eq!(errors[0].error_text, "unknown character '`'");
+        assert_eq!(
+            errors[0].position,
+            Some(SourcePosition { row: 2, column: 13 })
+        );
+
+        let errors = parse_errors("fn a() {\n    let b = \"c;\n}");
+        assert_eq!(
+            errors[0].error_kind,
+            ErrorKind::Lexcal(LexicalError::UnterminatedLiteral)
+        );
+        assert_eq!(
+            errors[0].position,
+            Some(SourcePosition { row: 2, column: 13 })
+        );
+    }
+}
@@ -1,41 +1,115 @@
+use nagi_errors::{Error, ErrorKind, LexicalError, SourcePosition};
 use nagi_syntax_tree::keywords::Keyword;
+use nagi_syntax_tree::span::{advance, Span};
 use nagi_syntax_tree::token::*;
+use nagi_syntax_tree::trivia::Comment;
 
 #[derive(Debug)]
 pub struct Lexer {
     token_list: Vec<Token>,
     position: usize,
     token_sorce_postion: Vec<(usize, usize)>,
+    token_sorce_end: Vec<(usize, usize)>, // トークンの直後の位置
+    comments: Vec<Comment>,
+    errors: Vec<Error>, // トークンにできなかった文字列
 }
 
 impl Lexer {
     pub fn new(tokenized_token_list: &Vec<nagi_lexer::Token>) -> Self {
         let mut token_list = Vec::<Token>::new();
         let mut token_sorce_postion = Vec::<(usize, usize)>::new();
-        let mut row = 1;
-        let mut column = 1;
+        let mut token_sorce_end = Vec::<(usize, usize)>::new();
+        let mut comments = Vec::<Comment>::new();
+        let mut errors = Vec::<Error>::new();
+        let mut position = (1, 1);
         for tokenized_token in tokenized_token_list.iter() {
-            if let Some(token) = convert_token(tokenized_token) {
-                token_list.push(token);
-                token_sorce_postion.push((row, column));
-            };
-
-            for c in tokenized_token.token.chars() {
-                column += 1;
-                if c == '\n' {
-                    row += 1;
-                    column = 1;
+            // 文字列の中の改行やエスケープもソースコードに書かれたとおりに数える
+            let end = advance(position, &tokenized_token.token);
+            match convert_token(tokenized_token) {
+                Ok(Some(token)) => {
+                    token_list.push(token);
+                    token_sorce_postion.push(position);
+                    token_sorce_end.push(end);
+                }
+                Ok(None) if tokenized_token.token_kind == nagi_lexer::TokenKind::Comment => {
+                    comments.push(Comment::new(&tokenized_token.token, position));
+                }
+                Ok(None) => (),
+                // 構文解析はこのトークンを読み飛ばして続ける
+                Err((error_kind, error_text)) => {
+                    let (row, column) = position;
+                    errors.push(Error {
+                        error_kind: ErrorKind::Lexcal(error_kind),
+                        error_text,
+                        position: Some(SourcePosition { row, column }),
+                        notes: vec![],
+                    });
                 }
             }
+            position = end;
+        }
+        // Eofの位置
+        token_sorce_postion.push(position);
+        token_sorce_end.push(position);
+
+        Self {
+            token_list,
+            position: 0,
+            token_sorce_postion,
+            token_sorce_end,
+            comments,
+            errors,
         }
+    }
+
+    // マクロ展開後のトークン列から作る
+    // 展開で作ったトークンはソースコードに無いので、表記の長さを範囲とする
+    pub fn from_tokens(tokens: Vec<(Token, (usize, usize))>, eof_position: (usize, usize)) -> Self {
+        let token_sorce_end = tokens
+            .iter()
+            .map(|(token, position)| advance(*position, &token.to_string()))
+            .chain([eof_position])
+            .collect();
+        let (token_list, mut token_sorce_postion): (Vec<_>, Vec<_>) = tokens.into_iter().unzip();
+        token_sorce_postion.push(eof_position);
 
         Self {
             token_list,
             position: 0,
             token_sorce_postion,
+            token_sorce_end,
+            comments: Vec::new(),
+            errors: Vec::new(),
         }
     }
 
+    // Eofを除いたトークンとその位置
+    pub fn tokens(&self) -> Vec<(Token, (usize, usize))> {
+        self.token_list
+            .iter()
+            .cloned()
+            .zip(self.token_sorce_postion.iter().cloned())
+            .collect()
+    }
+
+    // 読み飛ばしたコメントとその位置
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    // 字句のエラーを取り出す
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.errors)
+    }
+
+    pub fn eof_position(&self) -> (usize, usize) {
+        self.get_sorce_position_at(self.token_list.len())
+    }
+
+    pub fn token_count(&self) -> usize {
+        self.token_list.len()
+    }
+
     pub fn peek(&self) -> Token {
         self.token_list
             .get(self.position)
@@ -78,87 +152,42 @@ impl Lexer {
         op
     }
 
+    // 続けて並んだ1文字の記号を、表にある一番長い記号につなげる
+    // つながる記号の先頭でなければNone
     pub fn glue(&self) -> Option<(Token, usize)> {
-        let first = self.peek();
-        let res = match first {
-            Token::Plus => match self.peek_ahead(1) {
-                Token::Equal => Token::PlusEqual,
-                _ => first,
-            },
-            Token::Minus => match self.peek_ahead(1) {
-                Token::Equal => Token::MinusEqual,
-                _ => first,
-            },
-            Token::Star => match self.peek_ahead(1) {
-                Token::Equal => Token::StarEqual,
-                _ => first,
-            },
-            Token::Slash => match self.peek_ahead(1) {
-                Token::Equal => Token::SlashEqual,
-                _ => first,
-            },
-            Token::Percent => match self.peek_ahead(1) {
-                Token::Equal => Token::PercentEqual,
-                _ => first,
-            },
-            Token::Caret => match self.peek_ahead(1) {
-                Token::Equal => Token::CaretEqual,
-                _ => first,
-            },
-
-            Token::Not => match self.peek_ahead(1) {
-                Token::Equal => Token::NotEqual,
-                _ => first,
-            },
-            Token::And => match self.peek_ahead(1) {
-                Token::And => Token::AndAnd,
-                Token::Equal => Token::AndEqual,
-                _ => first,
-            },
-            Token::Or => match self.peek_ahead(1) {
-                Token::Or => Token::Or,
-                Token::Equal => Token::OrEqual,
-                _ => first,
-            },
-            Token::GreaterThan => match self.peek_ahead(1) {
-                Token::GreaterThan => match self.peek_ahead(2) {
-                    Token::Equal => Token::RightShiftEqual,
-                    _ => Token::RightShift,
-                },
-                Token::Equal => Token::GreaterThanOrEqual,
-                _ => first,
-            },
-            Token::LessThan => match self.peek_ahead(1) {
-                Token::LessThan => match self.peek_ahead(2) {
-                    Token::Equal => Token::LeftShiftEqual,
-                    _ => Token::LeftShift,
-                },
-                Token::Equal => Token::LessThanOrEqual,
-                _ => first,
-            },
-
-            Token::Dot => match self.peek_ahead(1) {
-                Token::Dot => match self.peek_ahead(2) {
-                    Token::Dot => Token::DotDotDot,
-                    Token::Equal => Token::DotDotEqual,
-                    _ => Token::DotDot,
-                },
-                _ => first,
-            },
-
-            _ => return None,
-        };
+        let mut spelling = String::new();
+        let mut res = None;
+        for ahead in 0.. {
+            let token = self.peek_ahead(ahead);
+            if token.is_joint_punctuation() {
+                break;
+            }
+            let Some(symbol) = token.punctuation_str() else {
+                break;
+            };
+            spelling.push_str(symbol);
 
-        if matches!(&res, first) {
-            Some((res, 1))
-        } else if matches!(
-            &res,
-            Token::LeftShiftEqual | Token::RightShiftEqual | Token::DotDotDot | Token::DotDotEqual
-        ) {
-            Some((res, 3))
-        } else {
-            Some((res, 2))
+            let is_joint_prefix = Token::PUNCTUATIONS.iter().any(|punctuation| {
+                punctuation.is_joint_punctuation()
+                    && punctuation
+                        .punctuation_str()
+                        .unwrap()
+                        .starts_with(&spelling)
+            });
+            if !is_joint_prefix {
+                break;
+            }
+
+            if ahead == 0 {
+                res = Some((token, 1));
+            } else if let Some(joint) = Token::from_punctuation_str(&spelling)
+                .filter(|punctuation| punctuation.is_joint_punctuation())
+            {
+                res = Some((joint, ahead + 1));
+            }
         }
+
+        res
     }
 
     pub fn get_token_position(&self) -> usize {
@@ -166,14 +195,41 @@ impl Lexer {
     }
 
     pub fn get_sorce_position(&self) -> (usize, usize) {
+        self.get_sorce_position_at(self.position)
+    }
+
+    pub fn get_token_at(&self, position: usize) -> Token {
+        self.token_list.get(position).cloned().unwrap_or(Token::Eof)
+    }
+
+    // start番目からend番目の手前までのトークンの範囲
+    pub fn span(&self, start: usize, end: usize) -> Option<Span> {
+        let last = end.checked_sub(1).filter(|last| start <= *last)?;
+        Some(Span::new(
+            self.get_sorce_position_at(start),
+            self.get_sorce_end_at(last),
+        ))
+    }
+
+    pub fn get_sorce_end_at(&self, position: usize) -> (usize, usize) {
+        let position = position.min(self.token_list.len());
+        self.token_sorce_end
+            .get(position)
+            .cloned()
+            .unwrap_or((0, 0))
+    }
+
+    pub fn get_sorce_position_at(&self, position: usize) -> (usize, usize) {
+        let position = position.min(self.token_list.len());
         self.token_sorce_postion
-            .get(self.position)
+            .get(position)
             .cloned()
             .unwrap_or((0, 0))
     }
 }
 
-fn convert_token(token: &nagi_lexer::Token) -> Option<Token> {
+// 空白とコメントはNoneにする
+fn convert_token(token: &nagi_lexer::Token) -> Result<Option<Token>, (LexicalError, String)> {
     let res = match &token.token_kind {
         nagi_lexer::TokenKind::Identifier(identifier) => {
             if let Some(keyword) = Keyword::from_str(identifier) {
@@ -183,18 +239,25 @@ fn convert_token(token: &nagi_lexer::Token) -> Option<Token> {
             }
         }
 
-        nagi_lexer::TokenKind::Literal(literal_kind) => match literal_kind {
-            nagi_lexer::LiteralKind::BinLiteral
-            | nagi_lexer::LiteralKind::OctLiteral
-            | nagi_lexer::LiteralKind::DecLiteral
-            | nagi_lexer::LiteralKind::HexLiteral => {
-                Token::Literal(Literal::new(LiteralKind::Integer, &token.token))
-            }
-            nagi_lexer::LiteralKind::FloatLiteral(_) => {
-                Token::Literal(Literal::new(LiteralKind::Float, &token.token))
-            }
-            _ => return None,
-        },
+        nagi_lexer::TokenKind::Literal(literal_kind) => {
+            let literal_kind = match literal_kind {
+                nagi_lexer::LiteralKind::BinLiteral
+                | nagi_lexer::LiteralKind::OctLiteral
+                | nagi_lexer::LiteralKind::DecLiteral
+                | nagi_lexer::LiteralKind::HexLiteral => LiteralKind::Integer,
+                nagi_lexer::LiteralKind::FloatLiteral(_) => LiteralKind::Float,
+                nagi_lexer::LiteralKind::CharacterLiteral => LiteralKind::Char,
+                nagi_lexer::LiteralKind::StringLiteral => LiteralKind::Str,
+                nagi_lexer::LiteralKind::RawStringLiteral => LiteralKind::StrRaw,
+                nagi_lexer::LiteralKind::ByteLiteral => LiteralKind::Byte,
+                nagi_lexer::LiteralKind::ByteStringLiteral => LiteralKind::ByteStr,
+                nagi_lexer::LiteralKind::RawByteStringLiteral => LiteralKind::ByteStrRaw,
+                nagi_lexer::LiteralKind::CStringLiteral => LiteralKind::CStr,
+                nagi_lexer::LiteralKind::RawCStringLiteral => LiteralKind::CStrRaw,
+                nagi_lexer::LiteralKind::Unknown => return Err(lexical_error(&token.token)),
+            };
+            Token::Literal(Literal::new(literal_kind, &token.token))
+        }
 
         nagi_lexer::TokenKind::LeftParenthesis => {
             Token::LeftParenthesis(LeftParenthesis::Parenthesis)
@@ -207,33 +270,44 @@ fn convert_token(token: &nagi_lexer::Token) -> Option<Token> {
         nagi_lexer::TokenKind::RightBrackets => Token::RightParenthesis(RightParenthesis::Brackets),
         nagi_lexer::TokenKind::RightBrace => Token::RightParenthesis(RightParenthesis::Brace),
 
-        nagi_lexer::TokenKind::Equal => Token::Equal,
-        nagi_lexer::TokenKind::Plus => Token::Plus,
-        nagi_lexer::TokenKind::Minus => Token::Minus,
-        nagi_lexer::TokenKind::Star => Token::Star,
-        nagi_lexer::TokenKind::Slash => Token::Slash,
-        nagi_lexer::TokenKind::Percent => Token::Percent,
-        nagi_lexer::TokenKind::Not => Token::Not,
-        nagi_lexer::TokenKind::And => Token::And,
-        nagi_lexer::TokenKind::Or => Token::Or,
-        nagi_lexer::TokenKind::GreaterThan => Token::GreaterThan,
-        nagi_lexer::TokenKind::LessThan => Token::LessThan,
-
-        nagi_lexer::TokenKind::At => Token::At,
-        nagi_lexer::TokenKind::Dot => Token::Dot,
-        nagi_lexer::TokenKind::Pound => Token::Pound,
-        nagi_lexer::TokenKind::Comma => Token::Colon,
-        nagi_lexer::TokenKind::Semicolon => Token::Semicolon,
-        nagi_lexer::TokenKind::Dollar => Token::Dollar,
-        nagi_lexer::TokenKind::Question => Token::Question,
-        nagi_lexer::TokenKind::Tilde => Token::Tilde,
-        nagi_lexer::TokenKind::Underscore => Token::Underscore,
-
         nagi_lexer::TokenKind::Eof => Token::Eof,
 
-        nagi_lexer::TokenKind::WhiteSpace => return None,
-        _ => panic!("{:?}", token),
+        nagi_lexer::TokenKind::WhiteSpace | nagi_lexer::TokenKind::Comment => return Ok(None),
+
+        // 1文字の記号は表記が同じトークンにする
+        token_kind => match token_kind
+            .punctuation_str()
+            .and_then(Token::from_punctuation_str)
+        {
+            Some(punctuation) => punctuation,
+            None => return Err(lexical_error(&token.token)),
+        },
     };
 
-    Some(res)
+    Ok(Some(res))
+}
+
+// nagi_lexerが読めなかった文字列は、閉じていない文字列リテラル、壊れた数値、知らない文字のどれか
+// 文字列リテラルは不正なエスケープの手前か、ファイルの終わりまでが一つのトークンになる
+fn lexical_error(token: &str) -> (LexicalError, String) {
+    let mut chars = token.chars();
+    match chars.next() {
+        Some('"') if token.contains('\\') => (
+            LexicalError::InvalidEscapeSequence,
+            "invalid escape sequence in string literal".to_string(),
+        ),
+        Some('"') => (
+            LexicalError::UnterminatedLiteral,
+            "unterminated string literal".to_string(),
+        ),
+        Some('0'..='9') => (
+            LexicalError::InvalidNumberFormat,
+            format!("invalid number literal `{}`", token),
+        ),
+        Some(c) => (
+            LexicalError::IllegalCharacter,
+            format!("unknown character {:?}", c),
+        ),
+        None => (LexicalError::IllegalCharacter, "empty token".to_string()),
+    }
 }
@@ -1,27 +1,114 @@
 mod ast_parse;
 mod cst_parse;
 mod expression;
+#[cfg(test)]
+mod generator;
 mod lexer;
+mod macro_expand;
+mod packrat;
 mod parser;
+#[cfg(test)]
+mod snapshot;
+mod trace;
 
 #[cfg(test)]
 mod tests;
 
+use ast_parse::ast_parser::ASTParser;
 use cst_parse::cst_parser::CSTParser;
-use nagi_command_option::CompileCommandOption;
-use nagi_errors::Error;
+use macro_expand::macro_expander;
+use nagi_command_option::{CompileCommandOption, FormatStyle, ParseFrontEnd};
 use nagi_lexer::lexer::Lexer;
+use nagi_syntax_tree::ast::ASTNode;
 use nagi_syntax_tree::cst::CSTNode;
+use nagi_syntax_tree::pretty::format_cst;
+use std::path::Path;
+
+pub use parser::ParseResult;
+pub use trace::{ParseTrace, TraceNode, TraceResult};
+
+// option.parserで選んだ構文解析器が作った木
+#[derive(Debug, PartialEq, Eq)]
+pub enum SyntaxTree {
+    Cst(CSTNode),
+    Ast(ASTNode),
+}
 
-pub fn parse(sorce_code: &str, option: &CompileCommandOption) -> Result<CSTNode, Error> {
+pub fn parse(sorce_code: &str, option: &CompileCommandOption) -> ParseResult<SyntaxTree> {
+    parse_source(sorce_code, None, option)
+}
+
+// include_str!などのパスはsorce_pathのファイルからの相対パスになる
+pub fn parse_file(
+    sorce_code: &str,
+    sorce_path: &Path,
+    option: &CompileCommandOption,
+) -> ParseResult<SyntaxTree> {
+    parse_source(sorce_code, Some(sorce_path), option)
+}
+
+fn parse_source(
+    sorce_code: &str,
+    sorce_path: Option<&Path>,
+    option: &CompileCommandOption,
+) -> ParseResult<SyntaxTree> {
     let mut lexer = Lexer::new(sorce_code);
-    let mut parser = CSTParser::new(&lexer.tokenize());
+    let token_list = lexer.tokenize();
+
+    let result = parse_tokens(lexer::Lexer::new(&token_list), option);
+    if !result.is_ok() {
+        return result;
+    }
 
-    let parse_result = parser.parse();
+    // 構文エラーが無ければマクロを展開して解析し直す
+    match macro_expander::expand(&lexer::Lexer::new(&token_list), sorce_path) {
+        Ok(None) => result,
+        Ok(Some(expanded)) => parse_tokens(expanded, option),
+        Err(error) => ParseResult {
+            errors: vec![error],
+            ..result
+        },
+    }
+}
+
+// マクロを展開する前のCSTとコメントから整形する
+// 構文エラーがあるファイルは、読み飛ばした部分を壊さないように整形しない
+pub fn format_source(
+    sorce_code: &str,
+    format: &FormatStyle,
+) -> Result<String, Vec<nagi_errors::Error>> {
+    let mut lexer = Lexer::new(sorce_code);
+    let token_list = lexer.tokenize();
 
-    if option.is_compiler_debug {
-        parser.output_log_file("log.txt");
+    let lexer = lexer::Lexer::new(&token_list);
+    let comments = lexer.comments().to_vec();
+    let result = CSTParser::from_lexer(lexer).parse();
+    if !result.is_ok() {
+        return Err(result.errors);
     }
 
-    parse_result
+    Ok(format_cst(&result.tree, &comments, format))
+}
+
+fn parse_tokens(lexer: lexer::Lexer, option: &CompileCommandOption) -> ParseResult<SyntaxTree> {
+    match option.parser {
+        ParseFrontEnd::Cst => {
+            let mut parser = CSTParser::from_lexer(lexer);
+            parser.set_limits(option.parse_limits.clone());
+            if option.parse_trace.is_some() {
+                parser.enable_trace();
+            }
+
+            parser.parse().map(SyntaxTree::Cst)
+        }
+        ParseFrontEnd::Ast => {
+            let mut parser = ASTParser::from_lexer(lexer);
+            parser.set_limits(option.parse_limits.clone());
+            if option.parse_trace.is_some() {
+                parser.enable_trace();
+            }
+
+            parser.parse().map(SyntaxTree::Ast)
+        }
+    }
 }
@@ -0,0 +1,4 @@
+pub mod macro_expander;
+
+#[cfg(test)]
+mod tests;
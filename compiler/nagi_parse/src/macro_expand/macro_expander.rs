@@ -0,0 +1,1269 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::cst_parse::cst_parser::CSTParser;
+use crate::lexer::Lexer;
+
+use nagi_errors::{Error, ErrorKind, ErrorNote, MacroError, SourcePosition};
+use nagi_syntax_tree::keywords::Keyword;
+use nagi_syntax_tree::token::*;
+
+// 展開の再帰の深さの上限
+const RECURSION_LIMIT: usize = 64;
+
+type Position = (usize, usize);
+
+// 位置付きのトークン
+// マクロの本体から作ったトークンは呼び出した位置を持つ
+#[derive(Debug, Clone)]
+struct MacroToken {
+    token: Token,
+    position: Position,
+}
+
+impl MacroToken {
+    fn new(token: Token, position: Position) -> Self {
+        Self { token, position }
+    }
+}
+
+// TokenTree ::= 括弧以外のトークン | DelimTokenTree
+#[derive(Debug, Clone)]
+enum TokenTree {
+    Token(MacroToken),
+    Delimited {
+        open: MacroToken,
+        trees: Vec<TokenTree>,
+        close: MacroToken,
+    },
+}
+
+impl TokenTree {
+    fn token(&self) -> Option<&Token> {
+        match self {
+            TokenTree::Token(token) => Some(&token.token),
+            TokenTree::Delimited { .. } => None,
+        }
+    }
+
+    fn delimiter(&self) -> Option<&LeftParenthesis> {
+        match self {
+            TokenTree::Delimited {
+                open:
+                    MacroToken {
+                        token: Token::LeftParenthesis(delimiter),
+                        ..
+                    },
+                ..
+            } => Some(delimiter),
+            _ => None,
+        }
+    }
+
+    fn flatten(&self, tokens: &mut Vec<MacroToken>) {
+        match self {
+            TokenTree::Token(token) => tokens.push(token.clone()),
+            TokenTree::Delimited { open, trees, close } => {
+                tokens.push(open.clone());
+                for tree in trees {
+                    tree.flatten(tokens);
+                }
+                tokens.push(close.clone());
+            }
+        }
+    }
+
+    fn token_count(&self) -> usize {
+        match self {
+            TokenTree::Token(_) => 1,
+            TokenTree::Delimited { trees, .. } => {
+                trees.iter().map(TokenTree::token_count).sum::<usize>() + 2
+            }
+        }
+    }
+}
+
+// `$name:fragment`のfragment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FragmentSpecifier {
+    Expr,
+    Ident,
+    Ty,
+    Pat,
+    Tt,
+    Block,
+}
+
+impl FragmentSpecifier {
+    fn from_str(fragment: &str) -> Option<Self> {
+        let res = match fragment {
+            "expr" => Self::Expr,
+            "ident" => Self::Ident,
+            "ty" => Self::Ty,
+            "pat" => Self::Pat,
+            "tt" => Self::Tt,
+            "block" => Self::Block,
+            _ => return None,
+        };
+
+        Some(res)
+    }
+}
+
+// `*` `+` `?`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RepeatKind {
+    ZeroOrMore,
+    OneOrMore,
+    ZeroOrOne,
+}
+
+// MacroMatch ::= Token | MacroMatcher | `$` Identifier `:` MacroFragSpec
+//              | `$` `(` MacroMatch+ `)` MacroRepSep? MacroRepOp
+#[derive(Debug)]
+enum Matcher {
+    Token(Token),
+    Delimited(LeftParenthesis, Vec<Matcher>),
+    Fragment(String, FragmentSpecifier),
+    Repeat {
+        matchers: Vec<Matcher>,
+        separator: Option<Token>,
+        repeat_kind: RepeatKind,
+    },
+}
+
+// MacroTranscriberの中身
+#[derive(Debug)]
+enum Transcriber {
+    Token(Token),
+    Delimited(LeftParenthesis, Vec<Transcriber>),
+    Variable(String),
+    Repeat {
+        transcribers: Vec<Transcriber>,
+        separator: Option<Token>,
+    },
+}
+
+// MacroRule ::= MacroMatcher `=>` MacroTranscriber
+#[derive(Debug)]
+struct MacroRule {
+    matchers: Vec<Matcher>,
+    transcribers: Vec<Transcriber>,
+    bound_names: HashSet<String>, // 本体の`let`で束縛する名前 衛生的にするため展開ごとに名前を変える
+}
+
+#[derive(Debug)]
+struct MacroRules {
+    position: Position,
+    rules: Vec<MacroRule>,
+}
+
+// メタ変数にマッチしたもの
+#[derive(Debug, Clone)]
+enum Binding {
+    Fragment(Vec<TokenTree>, FragmentSpecifier),
+    Repeat(Vec<Binding>),
+}
+
+type Bindings = HashMap<String, Binding>;
+
+// macro_rules!で定義したマクロをトークン列の上で展開する
+// 構文解析が成功した後に呼び、展開したトークン列をもう一度構文解析する
+#[derive(Debug, Default)]
+pub struct MacroExpander {
+    macros: HashMap<String, Rc<MacroRules>>,
+    definition_count: usize,
+    expansion_count: usize,
+    source_directory: Option<PathBuf>, // include_str!などのパスの基準 無ければカレントディレクトリ
+}
+
+// マクロの定義も呼び出しも無ければNone
+// source_pathは解析しているファイルのパス
+pub fn expand(lexer: &Lexer, source_path: Option<&Path>) -> Result<Option<Lexer>, Error> {
+    let mut expander = MacroExpander {
+        source_directory: source_path.and_then(Path::parent).map(Path::to_path_buf),
+        ..Default::default()
+    };
+    let trees = make_token_trees(lexer.tokens());
+    let trees = expander.expand_trees(trees, true, 0)?;
+    if expander.definition_count == 0 && expander.expansion_count == 0 {
+        return Ok(None);
+    }
+
+    let mut tokens = vec![];
+    for tree in trees.iter() {
+        tree.flatten(&mut tokens);
+    }
+    let tokens = tokens
+        .into_iter()
+        .map(|token| (token.token, token.position))
+        .collect();
+
+    Ok(Some(Lexer::from_tokens(tokens, lexer.eof_position())))
+}
+
+impl MacroExpander {
+    // in_braceはブロックかファイルの直下 文やアイテムの位置になりうる
+    fn expand_trees(
+        &mut self,
+        trees: Vec<TokenTree>,
+        in_brace: bool,
+        depth: usize,
+    ) -> Result<Vec<TokenTree>, Error> {
+        let mut output = Vec::<TokenTree>::new();
+        let mut index = 0;
+        while index < trees.len() {
+            // `macro_rules` `!` Identifier MacroRulesDef
+            if let Some(length) = self.define_macro(&trees[index..])? {
+                index += length;
+                continue;
+            }
+
+            // Identifier `!` DelimTokenTree
+            if let [TokenTree::Token(name), TokenTree::Token(not), TokenTree::Delimited { trees: input, .. }, ..] =
+                &trees[index..]
+            {
+                if let (Token::Identifier(identifier), Token::Not) = (&name.token, &not.token) {
+                    // 後ろに式が続く呼び出しは文ではない
+                    let is_statement = in_brace
+                        && is_statement_position(&output)
+                        && trees
+                            .get(index + 3)
+                            .is_none_or(|tree| tree.token() == Some(&Token::Semicolon));
+
+                    // ユーザー定義のマクロは同じ名前の組み込みマクロを隠す
+                    let builtin_macro = BuiltinMacro::from_name(identifier)
+                        .filter(|_| !self.macros.contains_key(identifier));
+                    let expanded = match builtin_macro {
+                        None => self.expand_macro(identifier, name.position, input, depth)?,
+                        Some(builtin_macro) => {
+                            match self.expand_builtin_macro(builtin_macro, name.position, input)? {
+                                Some(expanded) => expanded,
+                                // print!などは構文解析器が引数を式として解析する
+                                None => {
+                                    output.extend(trees[index..index + 2].iter().cloned());
+                                    index += 2;
+                                    continue;
+                                }
+                            }
+                        }
+                    };
+                    let expanded = self.expand_trees(expanded, is_statement, depth + 1)?;
+
+                    index += 3;
+
+                    // 式の位置では優先順位が変わらないように括弧で囲む
+                    if !is_statement {
+                        output.push(parenthesize(expanded, name.position));
+                        continue;
+                    }
+
+                    // 文やアイテムで終わる展開なら呼び出しの`;`は要らない
+                    let is_terminated = match expanded.last() {
+                        None => true,
+                        Some(tree) => {
+                            tree.token() == Some(&Token::Semicolon)
+                                || tree.delimiter() == Some(&LeftParenthesis::Brace)
+                        }
+                    };
+                    if is_terminated
+                        && matches!(
+                            trees.get(index).and_then(TokenTree::token),
+                            Some(Token::Semicolon)
+                        )
+                    {
+                        index += 1;
+                    }
+                    output.extend(expanded);
+                    continue;
+                }
+            }
+
+            match trees[index].clone() {
+                TokenTree::Delimited { open, trees, close } => {
+                    // ブロックの中で定義したマクロはブロックの外から見えない
+                    let macros = self.macros.clone();
+                    let is_brace =
+                        matches!(open.token, Token::LeftParenthesis(LeftParenthesis::Brace));
+                    let trees = self.expand_trees(trees, is_brace, depth)?;
+                    self.macros = macros;
+
+                    output.push(TokenTree::Delimited { open, trees, close });
+                }
+                tree => output.push(tree),
+            }
+            index += 1;
+        }
+
+        Ok(output)
+    }
+
+    // 定義を登録し、定義が占めていた木の数を返す
+    fn define_macro(&mut self, trees: &[TokenTree]) -> Result<Option<usize>, Error> {
+        let [TokenTree::Token(keyword), TokenTree::Token(not), TokenTree::Token(name), definition, ..] =
+            trees
+        else {
+            return Ok(None);
+        };
+        let (Token::Keyword(Keyword::MacroRules), Token::Not, Token::Identifier(identifier)) =
+            (&keyword.token, &not.token, &name.token)
+        else {
+            return Ok(None);
+        };
+        let TokenTree::Delimited { trees: rules, .. } = definition else {
+            return Ok(None);
+        };
+
+        let rules = parse_macro_rules(rules).map_err(|text| {
+            macro_error(
+                MacroError::InvalidDefinition,
+                format!("invalid definition of macro `{}!`: {}", identifier, text),
+                name.position,
+                vec![],
+            )
+        })?;
+        self.definition_count += 1;
+        self.macros.insert(
+            identifier.clone(),
+            Rc::new(MacroRules {
+                position: name.position,
+                rules,
+            }),
+        );
+
+        // `(` MacroRules `)` `;` | `[` MacroRules `]` `;`
+        let mut length = 4;
+        if definition.delimiter() != Some(&LeftParenthesis::Brace)
+            && matches!(
+                trees.get(4).and_then(TokenTree::token),
+                Some(Token::Semicolon)
+            )
+        {
+            length += 1;
+        }
+
+        Ok(Some(length))
+    }
+
+    fn expand_macro(
+        &mut self,
+        identifier: &str,
+        position: Position,
+        input: &[TokenTree],
+        depth: usize,
+    ) -> Result<Vec<TokenTree>, Error> {
+        if depth >= RECURSION_LIMIT {
+            return Err(macro_error(
+                MacroError::RecursionLimit,
+                format!("recursion limit reached while expanding `{}!`", identifier),
+                position,
+                vec![],
+            ));
+        }
+
+        let Some(macro_rules) = self.macros.get(identifier).cloned() else {
+            return Err(macro_error(
+                MacroError::UndefinedMacro,
+                format!("cannot find macro `{}!` in this scope", identifier),
+                position,
+                vec![],
+            ));
+        };
+        self.expansion_count += 1;
+
+        for rule in macro_rules.rules.iter() {
+            let Some(bindings) = match_trees(&rule.matchers, input) else {
+                continue;
+            };
+
+            let transcription = Transcription {
+                bindings: &bindings,
+                bound_names: &rule.bound_names,
+                expansion: self.expansion_count,
+                position,
+            };
+            let mut output = vec![];
+            transcription
+                .transcribe(&rule.transcribers, &mut vec![], &mut output)
+                .map_err(|text| {
+                    macro_error(MacroError::InvalidTranscriber, text, position, vec![])
+                })?;
+
+            return Ok(output);
+        }
+
+        Err(macro_error(
+            MacroError::NoRuleMatched,
+            format!(
+                "no rules of macro `{}!` matched this invocation",
+                identifier
+            ),
+            position,
+            vec![ErrorNote {
+                position: source_position(macro_rules.position),
+                note_text: format!("macro `{}!` is defined here", identifier),
+            }],
+        ))
+    }
+}
+
+//
+// 組み込みマクロ
+//
+
+impl MacroExpander {
+    // assert!系は条件付きのpanic!に、include_str!とinclude_bytes!はリテラルに置き換える
+    // それ以外の組み込みマクロは展開しないのでNone
+    fn expand_builtin_macro(
+        &mut self,
+        builtin_macro: BuiltinMacro,
+        position: Position,
+        input: &[TokenTree],
+    ) -> Result<Option<Vec<TokenTree>>, Error> {
+        let name = builtin_macro.name();
+        let arguments = split_arguments(input).map_err(|text| {
+            macro_error(
+                MacroError::InvalidBuiltinArguments,
+                format!("invalid arguments to `{}!`: {}", name, text),
+                position,
+                vec![],
+            )
+        })?;
+        let argument_error = |required: &str| {
+            macro_error(
+                MacroError::InvalidBuiltinArguments,
+                format!("`{}!` takes {}", name, required),
+                position,
+                vec![],
+            )
+        };
+
+        let expanded = match builtin_macro {
+            BuiltinMacro::Assert => {
+                let Some((condition, message)) = arguments.split_first() else {
+                    return Err(argument_error("a condition"));
+                };
+                let message = match message {
+                    [] => default_message(
+                        &format!("assertion failed: {}", stringify(condition)),
+                        position,
+                    ),
+                    message => join_arguments(message),
+                };
+                conditional_panic(condition.clone(), message, position)
+            }
+            BuiltinMacro::AssertEq | BuiltinMacro::AssertNe => {
+                let [left, right, message @ ..] = arguments.as_slice() else {
+                    return Err(argument_error("two expressions"));
+                };
+                let (operator, text) = match builtin_macro {
+                    BuiltinMacro::AssertEq => (Token::Equal, "=="),
+                    _ => (Token::Not, "!="),
+                };
+                let message = match message {
+                    [] => default_message(
+                        &format!("assertion `left {} right` failed", text),
+                        position,
+                    ),
+                    message => join_arguments(message),
+                };
+
+                // (left) == (right)
+                let condition = vec![
+                    parenthesize(left.clone(), position),
+                    TokenTree::Token(MacroToken::new(operator, position)),
+                    TokenTree::Token(MacroToken::new(Token::Equal, position)),
+                    parenthesize(right.clone(), position),
+                ];
+                conditional_panic(condition, message, position)
+            }
+            BuiltinMacro::IncludeStr | BuiltinMacro::IncludeBytes => {
+                let [argument] = arguments.as_slice() else {
+                    return Err(argument_error("a file path"));
+                };
+                let [TokenTree::Token(MacroToken {
+                    token: Token::Literal(literal),
+                    ..
+                })] = argument.as_slice()
+                else {
+                    return Err(argument_error("a string literal"));
+                };
+                let Some(file_path) = string_content(literal) else {
+                    return Err(argument_error("a string literal"));
+                };
+
+                let literal = self
+                    .include_file(builtin_macro, &file_path)
+                    .map_err(|text| {
+                        macro_error(MacroError::IncludeFailed, text, position, vec![])
+                    })?;
+                vec![TokenTree::Token(MacroToken::new(
+                    Token::Literal(literal),
+                    position,
+                ))]
+            }
+            _ => return Ok(None),
+        };
+        self.expansion_count += 1;
+
+        Ok(Some(expanded))
+    }
+
+    // ファイルの中身を文字列かバイト文字列のリテラルにする
+    fn include_file(
+        &self,
+        builtin_macro: BuiltinMacro,
+        file_path: &str,
+    ) -> Result<Literal, String> {
+        let path = match &self.source_directory {
+            Some(directory) => directory.join(file_path),
+            None => PathBuf::from(file_path),
+        };
+        let bytes = fs::read(&path)
+            .map_err(|error| format!("couldn't read `{}`: {}", path.display(), error))?;
+
+        if builtin_macro == BuiltinMacro::IncludeBytes {
+            let symbol: String = bytes.iter().map(|byte| escape_byte(*byte)).collect();
+            return Ok(Literal::new(
+                LiteralKind::ByteStr,
+                &format!("b\"{}\"", symbol),
+            ));
+        }
+
+        let text = String::from_utf8(bytes)
+            .map_err(|_| format!("`{}` is not valid UTF-8", path.display()))?;
+        Ok(Literal::new(
+            LiteralKind::Str,
+            &format!("\"{}\"", escape_string(&text)),
+        ))
+    }
+}
+
+// 一番外側の`,`で引数を分ける 最後の`,`は無くてもよい
+fn split_arguments(input: &[TokenTree]) -> Result<Vec<Vec<TokenTree>>, String> {
+    let mut arguments = vec![];
+    let mut argument = vec![];
+    for tree in input {
+        if tree.token() == Some(&Token::Comma) {
+            if argument.is_empty() {
+                return Err("expected an expression before `,`".to_string());
+            }
+            arguments.push(std::mem::take(&mut argument));
+        } else {
+            argument.push(tree.clone());
+        }
+    }
+    if !argument.is_empty() {
+        arguments.push(argument);
+    }
+
+    Ok(arguments)
+}
+
+fn join_arguments(arguments: &[Vec<TokenTree>]) -> Vec<TokenTree> {
+    let mut trees = vec![];
+    for (count, argument) in arguments.iter().enumerate() {
+        if count > 0 {
+            // 区切りの`,`は引数の先頭の位置に置く
+            let position = first_position(argument);
+            trees.push(TokenTree::Token(MacroToken::new(Token::Comma, position)));
+        }
+        trees.extend(argument.iter().cloned());
+    }
+
+    trees
+}
+
+fn first_position(trees: &[TokenTree]) -> Position {
+    match trees.first() {
+        Some(TokenTree::Token(token)) => token.position,
+        Some(TokenTree::Delimited { open, .. }) => open.position,
+        None => (0, 0),
+    }
+}
+
+// `if !(condition) { panic!(message) }`
+fn conditional_panic(
+    condition: Vec<TokenTree>,
+    message: Vec<TokenTree>,
+    position: Position,
+) -> Vec<TokenTree> {
+    let token = |token: Token| TokenTree::Token(MacroToken::new(token, position));
+    let panic = vec![
+        token(Token::Identifier("panic".to_string())),
+        token(Token::Not),
+        parenthesize(message, position),
+    ];
+
+    vec![
+        token(Token::Keyword(Keyword::If)),
+        token(Token::Not),
+        parenthesize(condition, position),
+        TokenTree::Delimited {
+            open: MacroToken::new(Token::LeftParenthesis(LeftParenthesis::Brace), position),
+            trees: panic,
+            close: MacroToken::new(Token::RightParenthesis(RightParenthesis::Brace), position),
+        },
+    ]
+}
+
+// 書式文字列として使うので括弧もエスケープする
+fn default_message(message: &str, position: Position) -> Vec<TokenTree> {
+    let message = escape_string(message).replace('{', "{{").replace('}', "}}");
+    vec![TokenTree::Token(MacroToken::new(
+        Token::Literal(Literal::new(LiteralKind::Str, &format!("\"{}\"", message))),
+        position,
+    ))]
+}
+
+fn stringify(trees: &[TokenTree]) -> String {
+    let mut tokens = vec![];
+    for tree in trees {
+        tree.flatten(&mut tokens);
+    }
+
+    // ソースコードで隣り合っていたトークンの間には空白を入れない
+    let mut text = String::new();
+    let mut end = None;
+    for token in tokens {
+        let symbol = token.token.to_string();
+        if end.is_some_and(|end| end != token.position) {
+            text.push(' ');
+        }
+        end = Some((token.position.0, token.position.1 + symbol.chars().count()));
+        text.push_str(&symbol);
+    }
+
+    text
+}
+
+// 文字列リテラルの引用符の中身 エスケープは今のところそのまま
+fn string_content(literal: &Literal) -> Option<String> {
+    if literal.literal_kind != LiteralKind::Str {
+        return None;
+    }
+
+    literal
+        .symbol
+        .strip_prefix('"')
+        .and_then(|symbol| symbol.strip_suffix('"'))
+        .map(str::to_string)
+}
+
+fn escape_string(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn escape_byte(byte: u8) -> String {
+    match byte {
+        b'\\' => "\\\\".to_string(),
+        b'"' => "\\\"".to_string(),
+        b' '..=b'~' => (byte as char).to_string(),
+        _ => format!("\\x{:02x}", byte),
+    }
+}
+
+// 前がブロックの先頭か`;`か`}`なら文の位置
+fn is_statement_position(output: &[TokenTree]) -> bool {
+    match output.last() {
+        None => true,
+        Some(TokenTree::Token(token)) => matches!(token.token, Token::Semicolon),
+        Some(tree) => tree.delimiter() == Some(&LeftParenthesis::Brace),
+    }
+}
+
+fn parenthesize(trees: Vec<TokenTree>, position: Position) -> TokenTree {
+    TokenTree::Delimited {
+        open: MacroToken::new(
+            Token::LeftParenthesis(LeftParenthesis::Parenthesis),
+            position,
+        ),
+        trees,
+        close: MacroToken::new(
+            Token::RightParenthesis(RightParenthesis::Parenthesis),
+            position,
+        ),
+    }
+}
+
+fn make_token_trees(tokens: Vec<(Token, Position)>) -> Vec<TokenTree> {
+    // 開き括弧と、その中のそれまでの木
+    let mut stack = Vec::<(MacroToken, Vec<TokenTree>)>::new();
+    let mut trees = vec![];
+    for (token, position) in tokens {
+        let token = MacroToken::new(token, position);
+        match &token.token {
+            Token::LeftParenthesis(_) => stack.push((token, std::mem::take(&mut trees))),
+            Token::RightParenthesis(_) => match stack.pop() {
+                Some((open, outer)) => {
+                    let inner = std::mem::replace(&mut trees, outer);
+                    trees.push(TokenTree::Delimited {
+                        open,
+                        trees: inner,
+                        close: token,
+                    });
+                }
+                // 対応する開き括弧が無い
+                None => trees.push(TokenTree::Token(token)),
+            },
+            _ => trees.push(TokenTree::Token(token)),
+        }
+    }
+
+    // 閉じられていない括弧はトークンのまま戻す
+    while let Some((open, outer)) = stack.pop() {
+        let inner = std::mem::replace(&mut trees, outer);
+        trees.push(TokenTree::Token(open));
+        trees.extend(inner);
+    }
+
+    trees
+}
+
+//
+// 定義の解析
+//
+
+// MacroRules ::= MacroRule ( `;` MacroRule )* `;`?
+fn parse_macro_rules(trees: &[TokenTree]) -> Result<Vec<MacroRule>, String> {
+    let mut rules = vec![];
+    let mut index = 0;
+    while index < trees.len() {
+        // MacroMatcher `=>` MacroTranscriber
+        let Some(TokenTree::Delimited { trees: matcher, .. }) = trees.get(index) else {
+            return Err("expected macro matcher".to_string());
+        };
+        let (Some(Token::Equal), Some(Token::GreaterThan)) = (
+            trees.get(index + 1).and_then(TokenTree::token),
+            trees.get(index + 2).and_then(TokenTree::token),
+        ) else {
+            return Err("expected `=>`".to_string());
+        };
+        let Some(TokenTree::Delimited {
+            trees: transcriber, ..
+        }) = trees.get(index + 3)
+        else {
+            return Err("expected macro transcriber".to_string());
+        };
+
+        let transcribers = parse_transcribers(transcriber)?;
+        let mut bound_names = HashSet::new();
+        collect_bound_names(&transcribers, &mut bound_names);
+        rules.push(MacroRule {
+            matchers: parse_matchers(matcher)?,
+            transcribers,
+            bound_names,
+        });
+        index += 4;
+
+        match trees.get(index).and_then(TokenTree::token) {
+            Some(Token::Semicolon) => index += 1,
+            None if index >= trees.len() => (),
+            _ => return Err("expected `;`".to_string()),
+        }
+    }
+
+    if rules.is_empty() {
+        return Err("no rules".to_string());
+    }
+
+    Ok(rules)
+}
+
+fn parse_matchers(trees: &[TokenTree]) -> Result<Vec<Matcher>, String> {
+    let mut matchers = vec![];
+    let mut index = 0;
+    while index < trees.len() {
+        match &trees[index] {
+            TokenTree::Token(MacroToken {
+                token: Token::Dollar,
+                ..
+            }) => match trees.get(index + 1) {
+                // `$` Identifier `:` MacroFragSpec
+                Some(TokenTree::Token(MacroToken {
+                    token: Token::Identifier(name),
+                    ..
+                })) => {
+                    let (Some(Token::Colon), Some(Token::Identifier(fragment))) = (
+                        trees.get(index + 2).and_then(TokenTree::token),
+                        trees.get(index + 3).and_then(TokenTree::token),
+                    ) else {
+                        return Err(format!("missing fragment specifier for `${}`", name));
+                    };
+                    let Some(fragment) = FragmentSpecifier::from_str(fragment) else {
+                        return Err(format!("invalid fragment specifier `{}`", fragment));
+                    };
+                    matchers.push(Matcher::Fragment(name.clone(), fragment));
+                    index += 4;
+                }
+                // `$` `(` MacroMatch+ `)` MacroRepSep? MacroRepOp
+                Some(
+                    tree @ TokenTree::Delimited {
+                        trees: repeated, ..
+                    },
+                ) if tree.delimiter() == Some(&LeftParenthesis::Parenthesis) => {
+                    let (separator, repeat_kind, length) =
+                        parse_repeat_operator(&trees[index + 2..])?;
+                    matchers.push(Matcher::Repeat {
+                        matchers: parse_matchers(repeated)?,
+                        separator,
+                        repeat_kind,
+                    });
+                    index += 2 + length;
+                }
+                _ => return Err("expected identifier or `(` after `$`".to_string()),
+            },
+            TokenTree::Token(token) => {
+                matchers.push(Matcher::Token(token.token.clone()));
+                index += 1;
+            }
+            tree @ TokenTree::Delimited { trees: inner, .. } => {
+                let delimiter = tree.delimiter().cloned().unwrap();
+                matchers.push(Matcher::Delimited(delimiter, parse_matchers(inner)?));
+                index += 1;
+            }
+        }
+    }
+
+    Ok(matchers)
+}
+
+fn parse_transcribers(trees: &[TokenTree]) -> Result<Vec<Transcriber>, String> {
+    let mut transcribers = vec![];
+    let mut index = 0;
+    while index < trees.len() {
+        match &trees[index] {
+            TokenTree::Token(MacroToken {
+                token: Token::Dollar,
+                ..
+            }) => match trees.get(index + 1) {
+                // `$` Identifier
+                Some(TokenTree::Token(MacroToken {
+                    token: Token::Identifier(name),
+                    ..
+                })) => {
+                    transcribers.push(Transcriber::Variable(name.clone()));
+                    index += 2;
+                }
+                // `$` `(` ... `)` MacroRepSep? MacroRepOp
+                Some(
+                    tree @ TokenTree::Delimited {
+                        trees: repeated, ..
+                    },
+                ) if tree.delimiter() == Some(&LeftParenthesis::Parenthesis) => {
+                    let (separator, _, length) = parse_repeat_operator(&trees[index + 2..])?;
+                    transcribers.push(Transcriber::Repeat {
+                        transcribers: parse_transcribers(repeated)?,
+                        separator,
+                    });
+                    index += 2 + length;
+                }
+                _ => return Err("expected identifier or `(` after `$`".to_string()),
+            },
+            TokenTree::Token(token) => {
+                transcribers.push(Transcriber::Token(token.token.clone()));
+                index += 1;
+            }
+            tree @ TokenTree::Delimited { trees: inner, .. } => {
+                let delimiter = tree.delimiter().cloned().unwrap();
+                transcribers.push(Transcriber::Delimited(
+                    delimiter,
+                    parse_transcribers(inner)?,
+                ));
+                index += 1;
+            }
+        }
+    }
+
+    Ok(transcribers)
+}
+
+// MacroRepSep? MacroRepOp 読んだ木の数も返す
+fn parse_repeat_operator(
+    trees: &[TokenTree],
+) -> Result<(Option<Token>, RepeatKind, usize), String> {
+    let repeat_kind = |tree: Option<&TokenTree>| match tree.and_then(TokenTree::token) {
+        Some(Token::Star) => Some(RepeatKind::ZeroOrMore),
+        Some(Token::Plus) => Some(RepeatKind::OneOrMore),
+        Some(Token::Question) => Some(RepeatKind::ZeroOrOne),
+        _ => None,
+    };
+
+    if let Some(kind) = repeat_kind(trees.first()) {
+        return Ok((None, kind, 1));
+    }
+    let Some(separator) = trees.first().and_then(TokenTree::token) else {
+        return Err("expected one of `*`, `+`, or `?`".to_string());
+    };
+    let Some(kind) = repeat_kind(trees.get(1)) else {
+        return Err("expected one of `*`, `+`, or `?`".to_string());
+    };
+
+    Ok((Some(separator.clone()), kind, 2))
+}
+
+// 本体の`let` (`ur` `sr` `nr`も) `mut`? Identifier で束縛される名前
+fn collect_bound_names(transcribers: &[Transcriber], names: &mut HashSet<String>) {
+    for (index, transcriber) in transcribers.iter().enumerate() {
+        match transcriber {
+            Transcriber::Token(Token::Keyword(
+                Keyword::Let | Keyword::Ur | Keyword::Sr | Keyword::Nr,
+            )) => {
+                let mut next = transcribers.get(index + 1);
+                if let Some(Transcriber::Token(Token::Keyword(Keyword::Mut))) = next {
+                    next = transcribers.get(index + 2);
+                }
+                if let Some(Transcriber::Token(Token::Identifier(name))) = next {
+                    names.insert(name.clone());
+                }
+            }
+            Transcriber::Delimited(_, inner)
+            | Transcriber::Repeat {
+                transcribers: inner,
+                ..
+            } => collect_bound_names(inner, names),
+            _ => (),
+        }
+    }
+}
+
+//
+// マッチ
+//
+
+// inputのすべてがmatchersにマッチした時のメタ変数
+fn match_trees(matchers: &[Matcher], input: &[TokenTree]) -> Option<Bindings> {
+    let Some((first, rest)) = matchers.split_first() else {
+        return input.is_empty().then(Bindings::new);
+    };
+
+    match first {
+        Matcher::Token(token) => {
+            let (tree, input) = input.split_first()?;
+            if tree.token() != Some(token) {
+                return None;
+            }
+            match_trees(rest, input)
+        }
+        Matcher::Delimited(delimiter, inner) => {
+            let (tree, input) = input.split_first()?;
+            let TokenTree::Delimited { trees, .. } = tree else {
+                return None;
+            };
+            if tree.delimiter() != Some(delimiter) {
+                return None;
+            }
+
+            let mut bindings = match_trees(inner, trees)?;
+            bindings.extend(match_trees(rest, input)?);
+            Some(bindings)
+        }
+        Matcher::Fragment(name, fragment) => {
+            let length = fragment_length(*fragment, input)?;
+            let mut bindings = match_trees(rest, &input[length..])?;
+            bindings.insert(
+                name.clone(),
+                Binding::Fragment(input[..length].to_vec(), *fragment),
+            );
+            Some(bindings)
+        }
+        Matcher::Repeat {
+            matchers: inner,
+            separator,
+            repeat_kind,
+        } => {
+            // 繰り返せるだけ繰り返してから、回数を減らしながら残りがマッチするか試す
+            let mut iterations = Vec::<Bindings>::new();
+            let mut ends = vec![0];
+            loop {
+                if *repeat_kind == RepeatKind::ZeroOrOne && iterations.len() == 1 {
+                    break;
+                }
+
+                let mut start = *ends.last().unwrap();
+                if let (false, Some(separator)) = (iterations.is_empty(), separator) {
+                    if input.get(start).and_then(TokenTree::token) != Some(separator) {
+                        break;
+                    }
+                    start += 1;
+                }
+
+                let Some((bindings, length)) = match_prefix(inner, &input[start..]) else {
+                    break;
+                };
+                if length == 0 {
+                    break;
+                }
+                iterations.push(bindings);
+                ends.push(start + length);
+            }
+
+            let min = if *repeat_kind == RepeatKind::OneOrMore {
+                1
+            } else {
+                0
+            };
+            let mut names = vec![];
+            collect_matcher_names(inner, &mut names);
+            for count in (min..=iterations.len()).rev() {
+                let Some(mut bindings) = match_trees(rest, &input[ends[count]..]) else {
+                    continue;
+                };
+                for name in names.iter() {
+                    let repeat = iterations[..count]
+                        .iter()
+                        .filter_map(|iteration| iteration.get(name).cloned())
+                        .collect();
+                    bindings.insert(name.clone(), Binding::Repeat(repeat));
+                }
+
+                return Some(bindings);
+            }
+
+            None
+        }
+    }
+}
+
+// inputの先頭のできるだけ長い部分がマッチした時のメタ変数と木の数
+fn match_prefix(matchers: &[Matcher], input: &[TokenTree]) -> Option<(Bindings, usize)> {
+    (0..=input.len())
+        .rev()
+        .find_map(|end| match_trees(matchers, &input[..end]).map(|bindings| (bindings, end)))
+}
+
+fn collect_matcher_names(matchers: &[Matcher], names: &mut Vec<String>) {
+    for matcher in matchers {
+        match matcher {
+            Matcher::Token(_) => (),
+            Matcher::Fragment(name, _) => names.push(name.clone()),
+            Matcher::Delimited(_, inner)
+            | Matcher::Repeat {
+                matchers: inner, ..
+            } => collect_matcher_names(inner, names),
+        }
+    }
+}
+
+// フラグメントにマッチする先頭の木の数
+fn fragment_length(fragment: FragmentSpecifier, input: &[TokenTree]) -> Option<usize> {
+    let first = input.first()?;
+    match fragment {
+        FragmentSpecifier::Tt => return Some(1),
+        FragmentSpecifier::Ident => {
+            return matches!(first.token(), Some(Token::Identifier(_))).then_some(1)
+        }
+        FragmentSpecifier::Expr
+        | FragmentSpecifier::Ty
+        | FragmentSpecifier::Pat
+        | FragmentSpecifier::Block => (),
+    }
+
+    // 構文解析器で読めたトークン数を木の数に直す
+    let mut tokens = vec![];
+    for tree in input {
+        tree.flatten(&mut tokens);
+    }
+    let eof_position = tokens.last().map(|token| token.position).unwrap_or((0, 0));
+    let tokens = tokens
+        .into_iter()
+        .map(|token| (token.token, token.position))
+        .collect();
+    let length = CSTParser::from_lexer(Lexer::from_tokens(tokens, eof_position))
+        .fragment_length(fragment)?;
+
+    let mut count = 0;
+    for (index, tree) in input.iter().enumerate() {
+        if count == length {
+            return Some(index);
+        }
+        count += tree.token_count();
+    }
+
+    (count == length).then_some(input.len())
+}
+
+//
+// 展開
+//
+
+struct Transcription<'a> {
+    bindings: &'a Bindings,
+    bound_names: &'a HashSet<String>,
+    expansion: usize,   // 何番目の展開か 衛生的な名前に付ける
+    position: Position, // マクロを呼び出した位置
+}
+
+impl Transcription<'_> {
+    // indicesは外側から順に今何回目の繰り返しか
+    fn transcribe(
+        &self,
+        transcribers: &[Transcriber],
+        indices: &mut Vec<usize>,
+        output: &mut Vec<TokenTree>,
+    ) -> Result<(), String> {
+        for transcriber in transcribers {
+            match transcriber {
+                Transcriber::Token(token) => {
+                    output.push(TokenTree::Token(self.make_token(token)));
+                }
+                Transcriber::Delimited(delimiter, inner) => {
+                    let mut trees = vec![];
+                    self.transcribe(inner, indices, &mut trees)?;
+                    output.push(TokenTree::Delimited {
+                        open: self.make_token(&Token::LeftParenthesis(delimiter.clone())),
+                        trees,
+                        close: self.make_token(&Token::RightParenthesis(delimiter.closing())),
+                    });
+                }
+                Transcriber::Variable(name) => {
+                    let Some(binding) = self.lookup(name, indices) else {
+                        return Err(format!("unknown macro variable `${}`", name));
+                    };
+                    match binding {
+                        // 式はまとまりを保つため括弧で囲む
+                        Binding::Fragment(trees, FragmentSpecifier::Expr) => {
+                            let position = trees
+                                .first()
+                                .map(|tree| match tree {
+                                    TokenTree::Token(token) => token.position,
+                                    TokenTree::Delimited { open, .. } => open.position,
+                                })
+                                .unwrap_or(self.position);
+                            output.push(parenthesize(trees.clone(), position));
+                        }
+                        Binding::Fragment(trees, _) => output.extend(trees.iter().cloned()),
+                        Binding::Repeat(_) => {
+                            return Err(format!(
+                                "variable `${}` is still repeating at this depth",
+                                name
+                            ))
+                        }
+                    }
+                }
+                Transcriber::Repeat {
+                    transcribers: inner,
+                    separator,
+                } => {
+                    let count = self.repeat_count(inner, indices)?;
+                    for index in 0..count {
+                        if let (true, Some(separator)) = (index != 0, separator) {
+                            output.push(TokenTree::Token(self.make_token(separator)));
+                        }
+                        indices.push(index);
+                        self.transcribe(inner, indices, output)?;
+                        indices.pop();
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn make_token(&self, token: &Token) -> MacroToken {
+        let token = match token {
+            Token::Identifier(name) if self.bound_names.contains(name) => {
+                Token::Identifier(format!("{}#{}", name, self.expansion))
+            }
+            _ => token.clone(),
+        };
+
+        MacroToken::new(token, self.position)
+    }
+
+    // 繰り返しの外で束縛したメタ変数は繰り返しの中でも同じものを使う
+    fn lookup(&self, name: &str, indices: &[usize]) -> Option<&Binding> {
+        let mut binding = self.bindings.get(name)?;
+        for index in indices {
+            let Binding::Repeat(repeat) = binding else {
+                break;
+            };
+            binding = repeat.get(*index)?;
+        }
+
+        Some(binding)
+    }
+
+    // 中で使うメタ変数の繰り返し回数 すべて同じでなければならない
+    fn repeat_count(
+        &self,
+        transcribers: &[Transcriber],
+        indices: &[usize],
+    ) -> Result<usize, String> {
+        let mut names = vec![];
+        collect_variable_names(transcribers, &mut names);
+
+        let mut count: Option<(usize, &str)> = None;
+        for name in names.iter() {
+            let Some(Binding::Repeat(repeat)) = self.lookup(name, indices) else {
+                continue;
+            };
+            match count {
+                Some((other, other_name)) if other != repeat.len() => {
+                    return Err(format!(
+                        "meta-variable `${}` repeats {} times, but `${}` repeats {} times",
+                        other_name,
+                        other,
+                        name,
+                        repeat.len()
+                    ))
+                }
+                _ => count = Some((repeat.len(), name)),
+            }
+        }
+
+        count.map(|(count, _)| count).ok_or_else(|| {
+            "attempted to repeat an expression containing no syntax variables matched as repeating at this depth".to_string()
+        })
+    }
+}
+
+fn collect_variable_names(transcribers: &[Transcriber], names: &mut Vec<String>) {
+    for transcriber in transcribers {
+        match transcriber {
+            Transcriber::Token(_) => (),
+            Transcriber::Variable(name) => names.push(name.clone()),
+            Transcriber::Delimited(_, inner)
+            | Transcriber::Repeat {
+                transcribers: inner,
+                ..
+            } => collect_variable_names(inner, names),
+        }
+    }
+}
+
+fn source_position(position: Position) -> SourcePosition {
+    SourcePosition {
+        row: position.0,
+        column: position.1,
+    }
+}
+
+fn macro_error(
+    error: MacroError,
+    text: String,
+    position: Position,
+    notes: Vec<ErrorNote>,
+) -> Error {
+    Error {
+        error_kind: ErrorKind::Macro(error),
+        error_text: text,
+        position: Some(source_position(position)),
+        notes,
+    }
+}
@@ -0,0 +1,307 @@
+#[cfg(test)]
+mod test {
+    use crate::ast_parse::ast_parser::ASTParser;
+    use crate::lexer;
+    use crate::macro_expand::macro_expander::expand;
+    use crate::SyntaxTree;
+    use nagi_command_option::{CompileCommandOption, ParseFrontEnd, ParseLimits, TreeFormat};
+    use nagi_errors::{Error, ErrorKind, MacroError, SourcePosition};
+    use nagi_lexer::lexer::Lexer;
+    use nagi_syntax_tree::token::{Literal, LiteralKind, Token};
+
+    fn token_lexer(code: &str) -> lexer::Lexer {
+        let mut lexer = Lexer::new(code);
+        lexer::Lexer::new(&lexer.tokenize())
+    }
+
+    // 展開したトークンを空白区切りで並べる
+    fn expanded(code: &str) -> String {
+        let lexer = expand(&token_lexer(code), None).unwrap().unwrap();
+        lexer
+            .tokens()
+            .iter()
+            .map(|(token, _)| token.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn expand_error(code: &str) -> Error {
+        expand(&token_lexer(code), None).unwrap_err()
+    }
+
+    fn make_option(parser: ParseFrontEnd) -> CompileCommandOption {
+        CompileCommandOption {
+            is_debug: false,
+            is_compiler_debug: false,
+            is_output_ast: false,
+            tree_format: TreeFormat::Json,
+            parse_trace: None,
+            graph: None,
+            parser,
+            parse_limits: ParseLimits::default(),
+            target_list: vec![],
+        }
+    }
+
+    #[test]
+    fn no_macro() {
+        assert!(expand(&token_lexer("fn f() { a != b; !c }"), None)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn fragments() {
+        // 式は括弧で囲んで優先順位を保つ
+        assert_eq!(
+            expanded("macro_rules! add { ($a:expr, $b:expr) => { $a + $b }; } fn f() { let x = add!(1, 2 * 3) * 4; }"),
+            "fn f ( ) { let x = ( ( 1 ) + ( 2 * 3 ) ) * 4 ; }"
+        );
+        assert_eq!(
+            expanded("macro_rules! make ( ($name:ident, $body:block) => { fn $name() $body } ); make!(one, { 1 });"),
+            "fn one ( ) { 1 }"
+        );
+        assert_eq!(
+            expanded("macro_rules! bind { ($p:pat = $e:expr) => { let $p = $e; } } fn f() { bind!(a | b = c); }"),
+            "fn f ( ) { let a | b = ( c ) ; }"
+        );
+        assert_eq!(
+            expanded(
+                "macro_rules! first { ($x:tt $($rest:tt)*) => { $x } } fn f() { first![(a) b c] }"
+            ),
+            "fn f ( ) { ( a ) }"
+        );
+    }
+
+    #[test]
+    fn repetition() {
+        let sum = "macro_rules! sum { ($($x:expr),* $(,)?) => { 0 $(+ $x)* }; }";
+        assert_eq!(
+            expanded(&format!("{} fn f() {{ sum!(1, a.b, 3,) }}", sum)),
+            "fn f ( ) { 0 + ( 1 ) + ( a . b ) + ( 3 ) }"
+        );
+        assert_eq!(
+            expanded(&format!("{} fn f() {{ sum!() }}", sum)),
+            "fn f ( ) { 0 }"
+        );
+
+        // 入れ子の繰り返しと、繰り返しの外のメタ変数
+        assert_eq!(
+            expanded(
+                "macro_rules! calls { ($f:ident; $($a:ident: $($b:expr),*);+) => { $($f($a, $($b),*);)+ } }
+                 fn f() { calls!(g; x: 1, 2; y:); }"
+            ),
+            "fn f ( ) { g ( x , ( 1 ) , ( 2 ) ) ; g ( y , ) ; }"
+        );
+    }
+
+    #[test]
+    fn recursive_macro() {
+        assert_eq!(
+            expanded(
+                "macro_rules! count { () => { 0 }; ($x:tt $($rest:tt)*) => { 1 + count!($($rest)*) }; }
+                 fn f() { count!(a b) }"
+            ),
+            "fn f ( ) { 1 + ( 1 + ( 0 ) ) }"
+        );
+    }
+
+    #[test]
+    fn hygiene() {
+        // 本体で束縛した名前は呼び出し側の名前と衝突しない
+        assert_eq!(
+            expanded(
+                "macro_rules! double { ($e:expr) => {{ let x = $e; x * 2 }}; }
+                 fn f() { let x = 1; let y = double!(x); }"
+            ),
+            "fn f ( ) { let x = 1 ; let y = ( { let x#1 = ( x ) ; x#1 * 2 } ) ; }"
+        );
+        assert_eq!(
+            expanded(
+                "macro_rules! swap { ($a:ident, $b:ident) => { let t = $a; $a = $b; $b = t; }; }
+                 fn f() { swap!(t, u); swap!(u, t); }"
+            ),
+            "fn f ( ) { let t#1 = t ; t = u ; u = t#1 ; let t#2 = u ; u = t ; t = t#2 ; }"
+        );
+    }
+
+    #[test]
+    fn macro_scope() {
+        // ブロックの中で定義したマクロはブロックの外で使えない
+        let error = expand_error("fn f() { macro_rules! m { () => { 1 } } m!() } fn g() { m!() }");
+        assert_eq!(
+            error.error_kind,
+            ErrorKind::Macro(MacroError::UndefinedMacro)
+        );
+        assert_eq!(error.position, Some(SourcePosition { row: 1, column: 57 }));
+
+        // 定義より前では使えない
+        let error = expand_error("fn f() { m!() } macro_rules! m { () => { 1 } }");
+        assert_eq!(
+            error.error_kind,
+            ErrorKind::Macro(MacroError::UndefinedMacro)
+        );
+    }
+
+    #[test]
+    fn expansion_errors_point_at_call() {
+        let error = expand_error("macro_rules! m { (a) => { 1 } }\nfn f() { m!(b) }");
+        assert_eq!(
+            error.error_kind,
+            ErrorKind::Macro(MacroError::NoRuleMatched)
+        );
+        assert_eq!(error.position, Some(SourcePosition { row: 2, column: 10 }));
+        assert_eq!(
+            error.notes[0].position,
+            SourcePosition { row: 1, column: 14 }
+        );
+
+        let error = expand_error("macro_rules! r { () => { r!() } }\nfn f() { r!(); }");
+        assert_eq!(
+            error.error_kind,
+            ErrorKind::Macro(MacroError::RecursionLimit)
+        );
+        assert_eq!(error.position, Some(SourcePosition { row: 2, column: 10 }));
+
+        let error = expand_error(
+            "macro_rules! zip { ($($a:ident)*; $($b:ident)*) => { $($a + $b)* } } fn f() { zip!(a b; c) }",
+        );
+        assert_eq!(
+            error.error_kind,
+            ErrorKind::Macro(MacroError::InvalidTranscriber)
+        );
+
+        for code in [
+            "macro_rules! bad { ($x) => {} }",
+            "macro_rules! bad { ($x:number) => {} }",
+            "macro_rules! bad { () {} }",
+            "macro_rules! bad {}",
+        ] {
+            let error = expand_error(code);
+            assert_eq!(
+                error.error_kind,
+                ErrorKind::Macro(MacroError::InvalidDefinition),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn parse_expanded_code() {
+        let code = "macro_rules! add { ($a:expr, $b:expr) => { $a + $b }; }
+                    macro_rules! make { ($name:ident) => { fn $name() { add!(1, 2) * 3; } }; }
+                    make!(f);";
+        let expected = "fn f() { ((1) + (2)) * 3; }";
+
+        let expected = ASTParser::from_lexer(token_lexer(expected)).parse().tree;
+        let result = crate::parse(code, &make_option(ParseFrontEnd::Ast));
+        assert!(result.is_ok(), "{:?}", result.errors);
+        assert_eq!(result.tree, SyntaxTree::Ast(expected.clone()));
+
+        let result = crate::parse(code, &make_option(ParseFrontEnd::Cst));
+        let SyntaxTree::Cst(cst) = result.tree else {
+            panic!();
+        };
+        assert_eq!(nagi_checker::check(&cst), Ok(expected));
+    }
+
+    #[test]
+    fn parse_errors_in_expansion_point_at_call() {
+        let code = "macro_rules! broken { () => { 1 + } }\nfn f() { let a = broken!(); }";
+        for parser in [ParseFrontEnd::Cst, ParseFrontEnd::Ast] {
+            let result = crate::parse(code, &make_option(parser));
+            assert_eq!(result.errors.len(), 1);
+            assert_eq!(
+                result.errors[0].position,
+                Some(SourcePosition { row: 2, column: 18 })
+            );
+        }
+
+        // 展開のエラー
+        let result = crate::parse("fn f() { m!() }", &make_option(ParseFrontEnd::Cst));
+        assert_eq!(
+            result.errors[0].error_kind,
+            ErrorKind::Macro(MacroError::UndefinedMacro)
+        );
+    }
+
+    #[test]
+    fn builtin_assert_becomes_conditional_panic() {
+        assert_eq!(
+            expanded("fn f() { assert!(a == b); }"),
+            "fn f ( ) { if ! ( a = = b ) { panic ! ( \"assertion failed: a == b\" ) } }"
+        );
+        assert_eq!(
+            expanded("fn f() { assert_ne!(a, b + 1, \"{}\", a); }"),
+            "fn f ( ) { if ! ( ( a ) ! = ( b + 1 ) ) { panic ! ( \"{}\" , a ) } }"
+        );
+        // 式の位置では括弧で囲む
+        assert_eq!(
+            expanded("fn f() { let x = assert_eq!(a, b); }"),
+            "fn f ( ) { let x = ( if ! ( ( a ) = = ( b ) ) { panic ! ( \"assertion `left == right` failed\" ) } ) ; }"
+        );
+        // 展開しない組み込みマクロの引数の中は展開する
+        assert_eq!(
+            expanded("macro_rules! one { () => { 1 } } fn f() { println!(\"{}\", one!()); }"),
+            "fn f ( ) { println ! ( \"{}\" , ( 1 ) ) ; }"
+        );
+        // 同じ名前のマクロを定義すると組み込みマクロは隠れる
+        assert_eq!(
+            expanded("macro_rules! assert { ($e:expr) => { $e } } fn f() { assert!(a) }"),
+            "fn f ( ) { ( a ) }"
+        );
+
+        for code in [
+            "fn f() { assert!(); }",
+            "fn f() { assert_eq!(a); }",
+            "fn f() { assert!(a,, b); }",
+        ] {
+            assert_eq!(
+                expand_error(code).error_kind,
+                ErrorKind::Macro(MacroError::InvalidBuiltinArguments),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn builtin_include_reads_relative_to_source() {
+        let directory = std::env::temp_dir().join(format!("nagi_include_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("data.txt"), "a \"b\"\n\u{1}").unwrap();
+        let source_path = directory.join("main.nag");
+
+        let expand_file = |code: &str| expand(&token_lexer(code), Some(&source_path));
+        let lexer = expand_file(
+            "fn f() { let s = include_str!(\"data.txt\"); let b = include_bytes!(\"data.txt\"); }",
+        )
+        .unwrap()
+        .unwrap();
+        let literals: Vec<_> = lexer
+            .tokens()
+            .iter()
+            .filter_map(|(token, _)| match token {
+                Token::Literal(literal) => Some(literal.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            literals,
+            vec![
+                Literal::new(LiteralKind::Str, "\"a \\\"b\\\"\\n\u{1}\""),
+                Literal::new(LiteralKind::ByteStr, "b\"a \\\"b\\\"\\x0a\\x01\""),
+            ]
+        );
+
+        let error = expand_file("fn f() {\n    include_str!(\"missing.txt\")\n}").unwrap_err();
+        assert_eq!(
+            error.error_kind,
+            ErrorKind::Macro(MacroError::IncludeFailed)
+        );
+        assert_eq!(error.position, Some(SourcePosition { row: 2, column: 5 }));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
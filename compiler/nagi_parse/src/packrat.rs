@@ -0,0 +1,647 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::lexer::Lexer;
+use crate::parser::*;
+use crate::trace::*;
+
+use nagi_command_option::ParseLimits;
+use nagi_errors::{Error, ErrorKind, ErrorNote, SourcePosition, SyntaxError};
+use nagi_syntax_tree::keywords::Keyword;
+use nagi_syntax_tree::token::*;
+
+// CSTParserとASTParserで共有する解析の状態
+pub struct PackratState<T> {
+    pub trace: Option<Tracer>, // トレースを出力する時だけ記録する
+    pub memo: MemoTable<T>,
+    pub left_recursion_stack: Vec<LeftRecursion<T>>,
+    pub heads: HashMap<usize, Head>, // トークン位置 -> 種を成長させている起点
+    pub evaluating: Option<ParseMemoKey>,
+    pub errors: BTreeMap<usize, Error>, // 復帰を開始したトークン位置 -> エラー
+    pub furthest: FurthestFailure,
+    pub limits: ParseLimits,
+    pub depth: usize,           // 評価中の規則の入れ子の深さ
+    pub aborted: Option<Error>, // 制限を超えたか中断された時のエラー
+}
+
+impl<T> PackratState<T> {
+    pub fn new(token_count: usize) -> Self {
+        Self {
+            trace: None,
+            memo: MemoTable::new(token_count + 1),
+            left_recursion_stack: Vec::new(),
+            heads: HashMap::new(),
+            evaluating: None,
+            errors: BTreeMap::new(),
+            furthest: FurthestFailure::default(),
+            limits: ParseLimits::default(),
+            depth: 0,
+            aborted: None,
+        }
+    }
+}
+
+// メモ化、左再帰、エラー報告、トレース
+// 各規則は先頭でget_memoを呼び、MemoResult::Noneの時だけ本体を評価する
+pub trait PackratParser: Sized {
+    type Node: Clone;
+
+    fn lexer(&self) -> &Lexer;
+    fn lexer_mut(&mut self) -> &mut Lexer;
+    fn state(&mut self) -> &mut PackratState<Self::Node>;
+
+    // 規則が読んだstartからendの手前までのトークンの範囲をノードに記録する
+    fn set_span(&self, node: Self::Node, _start: usize, _end: usize) -> Self::Node {
+        node
+    }
+
+    fn make_key(&self, rule: ParseRule) -> ParseMemoKey {
+        ParseMemoKey {
+            position: self.lexer().get_token_position(),
+            rule,
+        }
+    }
+
+    //
+    // Memo
+    //
+
+    fn write_memo(&mut self, key: &ParseMemoKey, memo: &Option<ParseMemoValue<Self::Node>>) {
+        let entry = match memo {
+            Some(value) => MemoEntry::Success(value.clone()),
+            None => MemoEntry::Fail,
+        };
+        self.state().memo.insert(*key, entry);
+    }
+
+    fn get_memo(
+        &mut self,
+        key: &ParseMemoKey,
+        rule: fn(&mut Self) -> Result<Self::Node, Error>,
+    ) -> MemoResult<Self::Node> {
+        // evaluateから呼ばれた場合は本体を評価する
+        if self.state().evaluating.as_ref() == Some(key) {
+            self.state().evaluating = None;
+            return MemoResult::None;
+        }
+
+        // 制限を超えた後は何も解析しない
+        if self.state().aborted.is_some() {
+            return MemoResult::Fail;
+        }
+
+        // 種を成長させている位置では左再帰に関わる規則だけを一度ずつ評価し直す
+        let state = self.state();
+        if let Some(head) = state.heads.get_mut(&key.position) {
+            let is_involved = head.rule == key.rule || head.involved.contains(&key.rule);
+            if !is_involved && !state.memo.contains_key(key) {
+                self.trace_memo(key, key.position, TraceResult::Skipped, false);
+                return MemoResult::Fail;
+            }
+
+            if head.eval.remove(&key.rule) {
+                let result = self.evaluate(key, rule);
+                self.write_memo(key, &result);
+                return self.memo_result(key, result);
+            }
+        }
+
+        match self.state().memo.get(key) {
+            Some(MemoEntry::Success(value)) => {
+                let value = value.clone();
+                self.trace_memo(key, value.next_position, TraceResult::Success, true);
+
+                // メモがあった場合解析が進んだ場所まで移動
+                self.lexer_mut().set_postion(value.next_position);
+                return MemoResult::Some(value.node);
+            }
+            Some(MemoEntry::Fail) => {
+                self.trace_memo(key, key.position, TraceResult::Fail, true);
+                return MemoResult::Fail;
+            }
+            Some(MemoEntry::LeftRecursion(index)) => {
+                let index = *index;
+
+                self.setup_left_recursion(index);
+                return match self.state().left_recursion_stack[index].seed.clone() {
+                    Some(seed) => {
+                        self.trace_memo(key, seed.next_position, TraceResult::Success, true);
+                        self.memo_result(key, Some(seed))
+                    }
+                    None => {
+                        self.trace_memo(key, key.position, TraceResult::Recursive, true);
+                        MemoResult::Recursive
+                    }
+                };
+            }
+            None => (),
+        }
+
+        let state = self.state();
+        let index = state.left_recursion_stack.len();
+        state.left_recursion_stack.push(LeftRecursion {
+            key: *key,
+            seed: None,
+            head: None,
+            involved: HashSet::new(),
+        });
+        state.memo.insert(*key, MemoEntry::LeftRecursion(index));
+
+        let result = self.evaluate(key, rule);
+        let left_recursion = self.state().left_recursion_stack.pop().unwrap();
+
+        // 自身が左再帰の起点なら種を成長させる
+        let result = if left_recursion.head == Some(index) {
+            self.grow_seed(key, rule, result, left_recursion.involved)
+        } else {
+            self.write_memo(key, &result);
+            result
+        };
+
+        self.memo_result(key, result)
+    }
+
+    // 左再帰を検出したのでスタック上の起点までの規則を左再帰に関わるものとして記録する
+    fn setup_left_recursion(&mut self, index: usize) {
+        let stack = &mut self.state().left_recursion_stack;
+        let head = *stack[index].head.get_or_insert(index);
+
+        for i in (0..stack.len()).rev() {
+            if stack[i].head == Some(head) {
+                break;
+            }
+            stack[i].head = Some(head);
+
+            let rule = stack[i].key.rule;
+            stack[head].involved.insert(rule);
+        }
+    }
+
+    // 解析が進まなくなるまで種を使って本体を評価し直す
+    fn grow_seed(
+        &mut self,
+        key: &ParseMemoKey,
+        rule: fn(&mut Self) -> Result<Self::Node, Error>,
+        seed: Option<ParseMemoValue<Self::Node>>,
+        involved: HashSet<ParseRule>,
+    ) -> Option<ParseMemoValue<Self::Node>> {
+        let Some(mut seed) = seed else {
+            self.write_memo(key, &None);
+            return None;
+        };
+
+        let previous = self.state().heads.insert(
+            key.position,
+            Head {
+                rule: key.rule,
+                involved: involved.clone(),
+                eval: HashSet::new(),
+            },
+        );
+
+        // 成長させるたびに木が一段深くなるので入れ子の深さとして数える
+        let depth = self.state().depth;
+        let mut grow = 0;
+        loop {
+            grow += 1;
+            let state = self.state();
+            state.memo.insert(*key, MemoEntry::Success(seed.clone()));
+            if let Some(head) = state.heads.get_mut(&key.position) {
+                head.eval = involved.clone();
+            }
+
+            let Some(result) = self.evaluate_with_grow(key, rule, Some(grow)) else {
+                break;
+            };
+            if result.next_position <= seed.next_position {
+                break;
+            }
+            seed = result;
+            self.state().depth += 1;
+        }
+        self.state().depth = depth;
+
+        let heads = &mut self.state().heads;
+        match previous {
+            Some(head) => heads.insert(key.position, head),
+            None => heads.remove(&key.position),
+        };
+
+        let result = Some(seed);
+        self.write_memo(key, &result);
+        result
+    }
+
+    // 規則の本体を評価する
+    fn evaluate(
+        &mut self,
+        key: &ParseMemoKey,
+        rule: fn(&mut Self) -> Result<Self::Node, Error>,
+    ) -> Option<ParseMemoValue<Self::Node>> {
+        self.evaluate_with_grow(key, rule, None)
+    }
+
+    fn evaluate_with_grow(
+        &mut self,
+        key: &ParseMemoKey,
+        rule: fn(&mut Self) -> Result<Self::Node, Error>,
+        grow: Option<usize>,
+    ) -> Option<ParseMemoValue<Self::Node>> {
+        self.backtrack(key.position);
+        if !self.check_limits() {
+            return None;
+        }
+        self.state().evaluating = Some(*key);
+
+        if self.state().trace.is_some() {
+            let mut node = self.trace_node(key.rule.to_string(), key.position);
+            node.grow = grow;
+            self.state().trace.as_mut().unwrap().enter(node);
+        }
+
+        let result = self.nested(rule);
+
+        let end = self.lexer().get_token_position();
+        if let Some(tracer) = &mut self.state().trace {
+            match &result {
+                Ok(_) => tracer.exit(end, TraceResult::Success, None),
+                Err(error) => tracer.exit(
+                    end,
+                    TraceResult::Fail,
+                    Some(format!("{:?}", error.error_kind)),
+                ),
+            }
+        }
+
+        let node = self.set_span(result.ok()?, key.position, end);
+        Some(ParseMemoValue {
+            node,
+            next_position: end,
+        })
+    }
+
+    fn memo_result(
+        &mut self,
+        key: &ParseMemoKey,
+        result: Option<ParseMemoValue<Self::Node>>,
+    ) -> MemoResult<Self::Node> {
+        match result {
+            Some(value) => {
+                self.lexer_mut().set_postion(value.next_position);
+                MemoResult::Some(value.node)
+            }
+            None => {
+                self.backtrack(key.position);
+                MemoResult::Fail
+            }
+        }
+    }
+
+    fn backtrack(&mut self, position: usize) {
+        self.lexer_mut().set_postion(position);
+    }
+
+    //
+    // Limits
+    //
+
+    // 入れ子を一段深くして評価する
+    // メモを通らずに再帰する規則もこれで深さを数える
+    fn nested<R>(&mut self, rule: impl FnOnce(&mut Self) -> R) -> R {
+        self.state().depth += 1;
+        let result = rule(self);
+        self.state().depth -= 1;
+        result
+    }
+
+    // 制限を超えていれば中断する理由を記録してfalseを返す
+    fn check_limits(&mut self) -> bool {
+        if self.state().aborted.is_some() {
+            return false;
+        }
+
+        let token_count = self.lexer().token_count();
+        let state = self.state();
+        let limits = &state.limits;
+        let (error_kind, error_text, position) = if limits.cancellation.is_cancelled() {
+            (
+                SyntaxError::Cancelled,
+                "parsing was cancelled".to_string(),
+                None,
+            )
+        } else if token_count > limits.max_tokens {
+            (
+                SyntaxError::LimitExceeded,
+                format!("too many tokens (limit is {})", limits.max_tokens),
+                Some(limits.max_tokens),
+            )
+        } else if state.depth >= limits.max_depth {
+            (
+                SyntaxError::LimitExceeded,
+                format!("nesting is too deep (limit is {})", limits.max_depth),
+                None,
+            )
+        } else if state.memo.entry_count() > limits.max_memo_entries {
+            (
+                SyntaxError::LimitExceeded,
+                format!(
+                    "too many memo entries (limit is {})",
+                    limits.max_memo_entries
+                ),
+                None,
+            )
+        } else {
+            return true;
+        };
+
+        let position = position.unwrap_or_else(|| self.lexer().get_token_position());
+        let (row, column) = self.lexer().get_sorce_position_at(position);
+        self.state().aborted = Some(Error {
+            error_kind: ErrorKind::Syntax(error_kind),
+            error_text,
+            position: Some(SourcePosition { row, column }),
+            notes: vec![],
+        });
+        false
+    }
+
+    // まともなエラー出力用のプロジェクトができるまで仮で
+    fn error(&mut self, error_type: SyntaxError, key: &ParseMemoKey) -> Result<Self::Node, Error> {
+        self.backtrack(key.position);
+
+        Err(Error {
+            error_kind: ErrorKind::Syntax(error_type),
+            error_text: "".to_string(),
+            position: None,
+            notes: vec![],
+        })
+    }
+
+    //
+    // Error recovery
+    //
+
+    // パニックモードでの復帰
+    // `;` `}` アイテムのキーワードまで読み飛ばし、読み飛ばし始めた位置を返す
+    // ブロック内(in_block)の場合、ブロックを閉じる`}`は読み飛ばさない
+    fn skip_to_sync(&mut self, in_block: bool) -> usize {
+        let position = self.lexer().get_token_position();
+
+        // 中断した後は残りを全て読み飛ばし、エラーは中断の理由だけにする
+        if self.state().aborted.is_some() {
+            self.state().errors.split_off(&position);
+            while self.lexer().peek() != Token::Eof {
+                self.lexer_mut().next();
+            }
+            return position;
+        }
+
+        // 読み飛ばす範囲の中で見つかったエラーは失敗した解析のものなので捨てる
+        self.state().errors.split_off(&position);
+
+        let mut skipped = 0;
+        let mut depth = 0;
+        loop {
+            match self.lexer().peek() {
+                Token::Eof => break,
+
+                Token::Semicolon if depth == 0 => {
+                    self.lexer_mut().next();
+                    break;
+                }
+
+                Token::LeftParenthesis(LeftParenthesis::Brace) => depth += 1,
+
+                Token::RightParenthesis(RightParenthesis::Brace) => {
+                    if depth == 0 && in_block {
+                        break;
+                    }
+
+                    // 対応する`{`が無い`}`もしくはブロックの終わり
+                    if depth <= 1 {
+                        self.lexer_mut().next();
+                        break;
+                    }
+                    depth -= 1;
+                }
+
+                token if depth == 0 && skipped != 0 && is_item_start(&token) => break,
+
+                _ => (),
+            }
+
+            self.lexer_mut().next();
+            skipped += 1;
+        }
+
+        if self.state().trace.is_some() {
+            let mut node = self.trace_node("Recover".to_string(), position);
+            node.end = self.lexer().get_token_position();
+            node.result = TraceResult::Recovered;
+            self.trace_leaf(node);
+        }
+
+        let error = self.syntax_error(position, if in_block { "statement" } else { "item" });
+        self.state().errors.entry(position).or_insert(error);
+
+        position
+    }
+
+    //
+    // Diagnostics
+    //
+
+    // 次のトークンが`token`か確認し、違えば期待したトークンとして記録する
+    fn is_token(&mut self, token: Token) -> bool {
+        if self.lexer().peek() == token {
+            return true;
+        }
+
+        self.expected(&format!("`{}`", token));
+        false
+    }
+
+    fn expected(&mut self, expected: &str) {
+        let position = self.lexer().get_token_position();
+        self.state().furthest.record(position, expected);
+    }
+
+    // 閉じ括弧が無かった
+    // 対応する開き括弧の位置も記録する
+    fn expected_closing(&mut self, token: Token, opener: Token, opener_position: (usize, usize)) {
+        let position = self.lexer().get_token_position();
+        self.state().furthest.record_unclosed(
+            position,
+            &format!("`{}`", token),
+            opener,
+            opener_position,
+        );
+    }
+
+    // 最も解析が進んだ位置で期待されていたトークンからエラーを作る
+    // start以降で何も記録されていなければ`context`が期待されていたとする
+    fn syntax_error(&mut self, start: usize, context: &str) -> Error {
+        let furthest = std::mem::take(&mut self.state().furthest);
+        let (position, expected, unclosed) =
+            if furthest.position < start || furthest.expected.is_empty() {
+                (start, vec![context.to_string()], vec![])
+            } else {
+                (furthest.position, furthest.expected, furthest.unclosed)
+            };
+
+        let lexer = self.lexer();
+        let found = match lexer.get_token_at(position) {
+            Token::Eof => "end of file".to_string(),
+            token => format!("`{}`", token),
+        };
+        let error_text = if expected.len() == 1 {
+            format!("expected {}, found {}", expected[0], found)
+        } else {
+            format!("expected one of {}, found {}", expected.join(", "), found)
+        };
+
+        let (row, column) = lexer.get_sorce_position_at(position);
+        let position = Some(SourcePosition { row, column });
+
+        // 閉じ括弧が見つからないまま他の閉じ括弧、文の終わりの`;`、ファイルの終わりに達した
+        let is_unclosed = !unclosed.is_empty()
+            && matches!(
+                lexer.get_token_at(furthest.position),
+                Token::Eof | Token::RightParenthesis(_) | Token::Semicolon
+            );
+        if is_unclosed {
+            let notes = unclosed
+                .iter()
+                .map(|(opener, (row, column))| ErrorNote {
+                    position: SourcePosition {
+                        row: *row,
+                        column: *column,
+                    },
+                    note_text: format!("unclosed delimiter `{}`", opener),
+                })
+                .collect();
+
+            return Error {
+                error_kind: ErrorKind::Syntax(SyntaxError::ParenthesesNotClosed),
+                error_text,
+                position,
+                notes,
+            };
+        }
+
+        let error_kind = if expected.len() == 1 && expected[0] == "`;`" {
+            SyntaxError::MissingSemicolon
+        } else {
+            SyntaxError::ExpectedToken
+        };
+
+        Error {
+            error_kind: ErrorKind::Syntax(error_kind),
+            error_text,
+            position,
+            notes: vec![],
+        }
+    }
+
+    // 解析の最後に見つかった全てのエラーとトレースを取り出す
+    // 字句のエラーは構文のエラーより先に並べる
+    fn finish(&mut self) -> (Vec<Error>, Option<ParseTrace>) {
+        let mut errors = self.lexer_mut().take_errors();
+        errors.extend(std::mem::take(&mut self.state().errors).into_values());
+        errors.extend(self.state().aborted.take());
+
+        let end = self.lexer().get_token_position();
+        let trace = self.state().trace.take().map(|tracer| {
+            let result = if errors.is_empty() {
+                TraceResult::Success
+            } else {
+                TraceResult::Fail
+            };
+            tracer.finish(end, result)
+        });
+
+        (errors, trace)
+    }
+
+    //
+    // Trace
+    //
+
+    fn start_trace(&mut self) {
+        let mut root = self.trace_node("Crate".to_string(), 0);
+        root.result = TraceResult::Success;
+        self.state().trace = Some(Tracer::new(root));
+    }
+
+    // トレースが無効な時はトークンも取得しない
+    fn trace_node(&self, rule: String, position: usize) -> TraceNode {
+        let (row, column) = self.lexer().get_sorce_position_at(position);
+        TraceNode {
+            rule,
+            start: position,
+            end: position,
+            row,
+            column,
+            token: self.lexer().get_token_at(position).to_string(),
+            result: TraceResult::Fail,
+            memo: false,
+            grow: None,
+            error: None,
+            children: vec![],
+        }
+    }
+
+    fn trace_leaf(&mut self, node: TraceNode) {
+        if let Some(tracer) = &mut self.state().trace {
+            tracer.leaf(node);
+        }
+    }
+
+    // 本体を評価せずに結果を返した呼び出し
+    fn trace_memo(&mut self, key: &ParseMemoKey, end: usize, result: TraceResult, memo: bool) {
+        if self.state().trace.is_none() {
+            return;
+        }
+
+        let mut node = self.trace_node(key.rule.to_string(), key.position);
+        node.end = end;
+        node.result = result;
+        node.memo = memo;
+        self.trace_leaf(node);
+    }
+}
+
+// パニックモードでの復帰時の同期点になるアイテムの先頭
+pub fn is_item_start(token: &Token) -> bool {
+    match token {
+        Token::Pound => true,
+        Token::Keyword(keyword) => matches!(
+            keyword,
+            Keyword::Fn
+                | Keyword::Pub
+                | Keyword::Struct
+                | Keyword::Enum
+                | Keyword::Union
+                | Keyword::Trait
+                | Keyword::Impl
+                | Keyword::Mod
+                | Keyword::Use
+                | Keyword::Extern
+                | Keyword::Type
+                | Keyword::Const
+                | Keyword::Static
+                | Keyword::Async
+                | Keyword::Unsafe
+                | Keyword::MacroRules
+        ),
+        _ => false,
+    }
+}
+
+// SimplePathSegment ::= Identifier | `super` | `self` | `crate` | `$crate`
+// TODO `$crate`
+pub fn is_simple_path_segment(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Identifier(_) | Token::Keyword(Keyword::Super | Keyword::SelfValue | Keyword::Crate)
+    )
+}
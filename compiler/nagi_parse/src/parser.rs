@@ -1,7 +1,110 @@
-#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+use std::collections::HashSet;
+use std::fmt;
+
+use nagi_syntax_tree::token::Token;
+
+use crate::trace::ParseTrace;
+
+// 構文規則
+// メモ表のキーに使う
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum ParseRule {
+    InnerAttribute,
+    OuterAttribute,
+    Attribute,
+    AttributeInput,
+    SimplePath,
+    DelimTokenTree,
+    Visibility,
+    Item,
+    MacroItem,
+    MacroInvocation,
+    MacroInvocationSemi,
+    BuiltinMacroInvocation,
+    MacroRulesDefinition,
+    VisItem,
+    Function,
+    FunctionQualifiers,
+    ItemSafety,
+    Abi,
+    GenericParams,
+    FunctionParameters,
+    SelfParam,
+    ShorthandSelf,
+    TypedSelf,
+    FunctionParam,
+    FunctionParamPattern,
+    FunctionReturnType,
+    TypeAlias,
+    Struct,
+    StructFields,
+    StructField,
+    Implementation,
+    AssociatedItem,
+    Type,
+    TypePath,
+    Expression,
+    ExpressionWithoutBlock,
+    Operand,
+    LiteralExpression,
+    PathExpression,
+    PathInExpression,
+    PathExprSegment,
+    PathIdentSegment,
+    QualifiedPathInExpression,
+    QualifiedPathType,
+    QualifiedPathInType,
+    OperatorExpression,
+    PrattExpression,
+    BorrowExpression,
+    GroupedExpression,
+    StructExpression,
+    StructExprStruct,
+    StructExprFields,
+    StructExprField,
+    StructBase,
+    CallExpression,
+    MethodCallExpression,
+    FieldExpression,
+    AwaitExpression,
+    IndexExpression,
+    ReturnExpression,
+    Scrutinee,
+    ExpressionWithBlock,
+    BlockExpression,
+    Statements,
+    ConstBlockExpression,
+    UnsafeBlockExpression,
+    AsyncBlockExpression,
+    IfExpression,
+    IfLetExpression,
+    LetChain,
+    LetChainCondition,
+    ConditionExpression,
+    MatchExpression,
+    Statement,
+    LetStatement,
+    ExpressionStatement,
+    Pattern,
+    PatternNoTopAlt,
+    PatternWithoutRange,
+    LiteralPattern,
+    IdentifierPattern,
+    WildcardPattern,
+    RestPattern,
+    ReferencePattern,
+}
+
+impl fmt::Display for ParseRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
 pub struct ParseMemoKey {
     pub position: usize,
-    pub rule: String,
+    pub rule: ParseRule,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -12,7 +115,173 @@ pub struct ParseMemoValue<T> {
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum MemoResult<T> {
-    None,
-    Recursive,
+    None,      // 規則の本体を評価する
+    Recursive, // 左再帰を検出したがまだ種が無い
+    Fail,
     Some(T),
 }
+
+// メモ表の値
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MemoEntry<T> {
+    LeftRecursion(usize), // 解析中 左再帰スタックの位置
+    Success(ParseMemoValue<T>),
+    Fail,
+}
+
+// トークン位置ごとのメモ表
+// 一つの位置で試される規則は少ないので線形探索する
+#[derive(Debug)]
+pub struct MemoTable<T> {
+    rows: Vec<Vec<(ParseRule, MemoEntry<T>)>>,
+    entry_count: usize,
+}
+
+impl<T> MemoTable<T> {
+    pub fn new(token_count: usize) -> Self {
+        let mut rows = Vec::new();
+        rows.resize_with(token_count, Vec::new);
+        Self {
+            rows,
+            entry_count: 0,
+        }
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entry_count
+    }
+
+    pub fn get(&self, key: &ParseMemoKey) -> Option<&MemoEntry<T>> {
+        self.rows
+            .get(key.position)?
+            .iter()
+            .find(|(rule, _)| *rule == key.rule)
+            .map(|(_, entry)| entry)
+    }
+
+    pub fn contains_key(&self, key: &ParseMemoKey) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: ParseMemoKey, entry: MemoEntry<T>) {
+        if self.rows.len() <= key.position {
+            self.rows.resize_with(key.position + 1, Vec::new);
+        }
+
+        let row = &mut self.rows[key.position];
+        match row.iter_mut().find(|(rule, _)| *rule == key.rule) {
+            Some((_, old)) => *old = entry,
+            None => {
+                row.push((key.rule, entry));
+                self.entry_count += 1;
+            }
+        }
+    }
+}
+
+// 解析中の規則
+// 左再帰の種を成長させる (Warth et al. "Packrat Parsers Can Support Left Recursion")
+#[derive(Debug)]
+pub struct LeftRecursion<T> {
+    pub key: ParseMemoKey,
+    pub seed: Option<ParseMemoValue<T>>,
+    pub head: Option<usize>, // 左再帰の起点となる規則の左再帰スタックの位置
+    pub involved: HashSet<ParseRule>, // 起点のみ 左再帰に関わる規則
+}
+
+// 種を成長させている途中の起点
+#[derive(Debug)]
+pub struct Head {
+    pub rule: ParseRule,
+    pub involved: HashSet<ParseRule>,
+    pub eval: HashSet<ParseRule>, // この繰り返しでまだ評価し直していない規則
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseResult<T> {
+    pub tree: T,
+    pub errors: Vec<nagi_errors::Error>,
+    pub trace: Option<ParseTrace>, // トレースを有効にした時だけ
+}
+
+impl<T> ParseResult<T> {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> ParseResult<U> {
+        ParseResult {
+            tree: f(self.tree),
+            errors: self.errors,
+            trace: self.trace,
+        }
+    }
+}
+
+// 最も解析が進んだ位置で期待されていたトークン
+// エラー報告用
+#[derive(Debug, Default)]
+pub struct FurthestFailure {
+    pub position: usize,
+    pub expected: Vec<String>,
+    pub unclosed: Vec<(Token, (usize, usize))>, // 閉じられていない開き括弧とその位置
+}
+
+impl FurthestFailure {
+    pub fn record(&mut self, position: usize, expected: &str) {
+        if !self.advance(position) {
+            return;
+        }
+
+        if !self.expected.iter().any(|e| e == expected) {
+            self.expected.push(expected.to_string());
+        }
+    }
+
+    pub fn record_unclosed(
+        &mut self,
+        position: usize,
+        expected: &str,
+        opener: Token,
+        opener_position: (usize, usize),
+    ) {
+        self.record(position, expected);
+        if position != self.position {
+            return;
+        }
+
+        if !self.unclosed.iter().any(|(_, pos)| *pos == opener_position) {
+            self.unclosed.push((opener, opener_position));
+        }
+    }
+
+    pub fn checkpoint(&self) -> (usize, usize) {
+        (self.position, self.expected.len())
+    }
+
+    // startから始まる規則の中で記録されたものを`expected`一つにまとめる
+    pub fn summarize(&mut self, checkpoint: (usize, usize), start: usize, expected: &str) {
+        if self.position == start {
+            let (position, len) = checkpoint;
+            self.expected
+                .truncate(if position == start { len } else { 0 });
+        }
+
+        self.record(start, expected);
+    }
+
+    // より進んだ位置であれば記録し直す
+    fn advance(&mut self, position: usize) -> bool {
+        if position < self.position {
+            return false;
+        }
+        if position > self.position {
+            *self = Self {
+                position,
+                ..Default::default()
+            };
+        }
+
+        true
+    }
+}
@@ -0,0 +1,140 @@
+// スナップショットテスト
+// snapshots/<name>.nagを2つの構文解析器で解析し、木をS式にしたものをsnapshots/<name>.snapと比べる
+// CSTはトークンの位置を除き、ASTは範囲を含めて書く
+//
+// 文法を変えて木が変わったときは、次のように.snapを今の結果で書き換えてから差分を確かめる
+//   NAGI_BLESS=1 cargo test -p nagi_parse snapshot
+#[cfg(test)]
+mod test {
+    use crate::{parse, SyntaxTree};
+    use nagi_command_option::{CompileCommandOption, ParseFrontEnd, ParseLimits, TreeFormat};
+    use nagi_syntax_tree::sexpr::to_sexpr;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    const SNAPSHOT_DIRECTORY: &str = "snapshots";
+    const BLESS_VARIABLE: &str = "NAGI_BLESS";
+
+    fn make_option(parser: ParseFrontEnd) -> CompileCommandOption {
+        CompileCommandOption {
+            is_debug: false,
+            is_compiler_debug: false,
+            is_output_ast: false,
+            tree_format: TreeFormat::Json,
+            parse_trace: None,
+            graph: None,
+            parser,
+            parse_limits: ParseLimits::default(),
+            target_list: vec![],
+        }
+    }
+
+    // 構文エラーがあれば、木の後ろにエラーを1行ずつ書く
+    fn render(code: &str) -> String {
+        let mut snapshot = String::new();
+        for parser in [ParseFrontEnd::Cst, ParseFrontEnd::Ast] {
+            let result = parse(code, &make_option(parser));
+            let tree = match &result.tree {
+                SyntaxTree::Cst(cst) => {
+                    snapshot.push_str("-- cst\n");
+                    to_sexpr(cst, false)
+                }
+                SyntaxTree::Ast(ast) => {
+                    snapshot.push_str("-- ast\n");
+                    to_sexpr(ast, true)
+                }
+            };
+            snapshot.push_str(&tree);
+            for error in result.errors.iter() {
+                snapshot.push_str(&format!("error {}\n", error));
+            }
+        }
+        snapshot
+    }
+
+    // 最初に違う行を示す
+    fn first_difference(expected: &str, actual: &str) -> String {
+        let mut expected_lines = expected.lines();
+        let mut actual_lines = actual.lines();
+        for row in 1.. {
+            match (expected_lines.next(), actual_lines.next()) {
+                (Some(expected), Some(actual)) if expected == actual => continue,
+                (None, None) => break,
+                (expected, actual) => {
+                    return format!(
+                        "line {}\n    expected: {}\n    actual:   {}",
+                        row,
+                        expected.unwrap_or("(end)"),
+                        actual.unwrap_or("(end)")
+                    )
+                }
+            }
+        }
+        "trailing newline".to_string()
+    }
+
+    fn fixtures(directory: &Path) -> Vec<PathBuf> {
+        let mut fixtures: Vec<PathBuf> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "nag"))
+            .collect();
+        fixtures.sort();
+        fixtures
+    }
+
+    #[test]
+    fn snapshot() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join(SNAPSHOT_DIRECTORY);
+        let is_bless = std::env::var(BLESS_VARIABLE).is_ok_and(|value| value == "1");
+
+        let fixtures = fixtures(&directory);
+        assert!(!fixtures.is_empty());
+
+        let mut failures = vec![];
+        for fixture in fixtures.iter() {
+            let code = fs::read_to_string(fixture).unwrap();
+            let actual = render(&code);
+            let snap = fixture.with_extension("snap");
+            if is_bless {
+                fs::write(&snap, actual).unwrap();
+                continue;
+            }
+
+            let name = fixture.file_name().unwrap().to_string_lossy();
+            match fs::read_to_string(&snap) {
+                Ok(expected) if expected == actual => (),
+                Ok(expected) => failures.push(format!(
+                    "{}: {}",
+                    name,
+                    first_difference(&expected, &actual)
+                )),
+                Err(_) => failures.push(format!("{}: missing .snap", name)),
+            }
+        }
+
+        // 対応する.nagの無い.snapは消し忘れ
+        for entry in fs::read_dir(&directory).unwrap() {
+            let snap = entry.unwrap().path();
+            if snap
+                .extension()
+                .is_some_and(|extension| extension == "snap")
+                && !snap.with_extension("nag").exists()
+            {
+                if is_bless {
+                    fs::remove_file(&snap).unwrap();
+                } else {
+                    failures.push(format!("{}: no .nag fixture", snap.display()));
+                }
+            }
+        }
+
+        assert!(
+            failures.is_empty(),
+            "{} snapshots differ (run with {}=1 to update them)\n{}",
+            failures.len(),
+            BLESS_VARIABLE,
+            failures.join("\n")
+        );
+    }
+}
@@ -1,56 +1,148 @@
 #[cfg(test)]
 mod test {
     use crate::cst_parse::cst_parser::CSTParser;
+    use crate::format_source;
+    use crate::parser::ParseResult;
+    use crate::trace::TraceResult;
+    use nagi_command_option::{read_format_style, FormatStyle};
+    use nagi_errors::{ErrorKind, SourcePosition, SyntaxError};
     use nagi_lexer::lexer::Lexer;
     use nagi_syntax_tree::cst::{CSTNode, CSTNodeKind};
     use nagi_syntax_tree::token;
+    use std::rc::Rc;
 
-    fn parse_cst(code: &str, tree: CSTNode) {
+    // 構文エラーを含むコードを解析し、途中までのCSTとエラーを返す
+    fn parse_with_errors(code: &str) -> ParseResult<CSTNode> {
         let mut lexer = Lexer::new(code);
         let mut parser = CSTParser::new(&lexer.tokenize());
 
-        match parser.parse() {
-            Ok(cst) => {
-                if cst != tree {
-                    cst.write_cst("tree.json");
+        parser.parse()
+    }
+
+    fn items(cst: &CSTNode) -> &Vec<CSTNode> {
+        let CSTNodeKind::Crate { items, .. } = &cst.node_kind else {
+            panic!("{:?}", cst.node_kind);
+        };
+        items
+    }
+
+    fn function_statements(function: &CSTNode) -> &Vec<CSTNode> {
+        let CSTNodeKind::Function {
+            block_expression_or_semicolon,
+            ..
+        } = &function.node_kind
+        else {
+            panic!("{:?}", function.node_kind);
+        };
+        let CSTNodeKind::BlockExpression {
+            statements: Some(statements),
+            ..
+        } = &block_expression_or_semicolon.node_kind
+        else {
+            panic!("{:?}", block_expression_or_semicolon.node_kind);
+        };
+        &statements.children
+    }
+
+    fn is_error(cst: &CSTNode) -> bool {
+        matches!(cst.node_kind, CSTNodeKind::Error { .. })
+    }
+
+    // `fn f() { code }` の末尾の式をS式風の文字列にする
+    fn parse_expression(code: &str) -> String {
+        let result = parse_with_errors(&format!("fn f() {{ {} }}", code));
+        assert!(result.is_ok(), "{:?}", result.errors);
+
+        let statements = function_statements(&items(&result.tree)[0]);
+        shape(statements.last().unwrap())
+    }
+
+    fn shape(cst: &CSTNode) -> String {
+        match &cst.node_kind {
+            CSTNodeKind::ExpressionWithoutBlock { expression, .. }
+            | CSTNodeKind::LiteralExpression {
+                literal: expression,
+            }
+            | CSTNodeKind::GroupedExpression { expression, .. }
+            | CSTNodeKind::PathExpression {
+                path_in_expression: expression,
+            }
+            | CSTNodeKind::PathInExpression {
+                path_expr_segment: expression,
+                ..
+            }
+            | CSTNodeKind::PathExprSegment {
+                path_ident_segment: expression,
+                ..
+            } => shape(expression),
+            CSTNodeKind::Literal { literal, .. } => {
+                token::Token::Literal(literal.clone()).to_string()
+            }
+            CSTNodeKind::Factor { token, .. } => token.to_string(),
+            CSTNodeKind::Operator { token, .. } => {
+                let mut res = format!("({}", token);
+                for child in cst.children.iter() {
+                    res.push_str(&format!(" {}", shape(child)));
                 }
-                assert_eq!(cst, tree);
+                res + ")"
             }
-            Err(_) => {
-                parser.output_log_file("log.txt");
-                assert!(false);
+            CSTNodeKind::CallExpression {
+                expression,
+                call_params,
+                ..
+            } => format!(
+                "(call {}{})",
+                shape(expression),
+                call_params_shape(call_params)
+            ),
+            CSTNodeKind::MethodCallExpression {
+                expression,
+                path_expr_segment,
+                call_params,
+                ..
+            } => format!(
+                "(method {} {}{})",
+                shape(expression),
+                shape(path_expr_segment),
+                call_params_shape(call_params)
+            ),
+            CSTNodeKind::FieldExpression {
+                expression,
+                identifier,
+                ..
+            } => format!("(field {} {})", shape(expression), shape(identifier)),
+            CSTNodeKind::IndexExpression {
+                expression, index, ..
+            } => format!("(index {} {})", shape(expression), shape(index)),
+            CSTNodeKind::AwaitExpression { expression, .. } => {
+                format!("(await {})", shape(expression))
             }
+            CSTNodeKind::AsyncBlockExpression { move_keyword, .. } => match move_keyword {
+                Some(_) => "(async move)".to_string(),
+                None => "(async)".to_string(),
+            },
+            _ => panic!("{:?}", cst.node_kind),
         }
     }
 
-    fn make_literal_expression(num: i128, row: usize, column: usize) -> CSTNode {
-        CSTNode::new(
-            CSTNodeKind::ExpressionWithoutBlock {
-                outer_attribute: vec![],
-                expression: Box::new(CSTNode::new(
-                    CSTNodeKind::Literal {
-                        literal: token::Literal::new(
-                            token::LiteralKind::Integer,
-                            num.to_string().as_str(),
-                        ),
-                        row,
-                        column,
-                    },
-                    vec![],
-                )),
-            },
-            vec![],
-        )
-    }
+    fn call_params_shape(call_params: &Option<Rc<CSTNode>>) -> String {
+        let Some(call_params) = call_params else {
+            return "".to_string();
+        };
+        let CSTNodeKind::CallParams {
+            expression,
+            comma_and_expression,
+            ..
+        } = &call_params.node_kind
+        else {
+            panic!("{:?}", call_params.node_kind);
+        };
 
-    fn make(cst: CSTNode) -> CSTNode {
-        CSTNode::new(
-            CSTNodeKind::Crate {
-                inner_attributes: vec![],
-                items: vec![],
-            },
-            vec![],
-        )
+        let mut res = format!(" {}", shape(expression));
+        for (_, expr) in comma_and_expression {
+            res.push_str(&format!(" {}", shape(expr)));
+        }
+        res
     }
 
     #[test]
@@ -62,107 +154,157 @@ mod test {
     // 四則演算など
     #[test]
     fn check_operator_expression() {
-        // TODO
-        return;
-
-        parse_cst(
-            "1 + 2",
-            CSTNode::new(
-                CSTNodeKind::ExpressionWithoutBlock {
-                    outer_attribute: vec![],
-                    expression: Box::new(CSTNode::new(
-                        CSTNodeKind::Factor {
-                            token: token::Token::Plus,
-                            row: 1,
-                            column: 3,
-                        },
-                        vec![
-                            make_literal_expression(1, 1, 1),
-                            make_literal_expression(2, 1, 5),
-                        ],
-                    )),
-                },
-                vec![],
-            ),
+        assert_eq!(parse_expression("1 + 2"), "(+ 1 2)");
+        assert_eq!(parse_expression("3 - 1"), "(- 3 1)");
+        assert_eq!(parse_expression("6 / 2"), "(/ 6 2)");
+        assert_eq!(parse_expression("5 * 6"), "(* 5 6)");
+        assert_eq!(
+            parse_expression("12 * 23 - 32 / 16 + 90"),
+            "(+ (- (* 12 23) (/ 32 16)) 90)"
         );
+    }
 
-        parse_cst(
-            "3 - 1",
-            CSTNode::new(
-                CSTNodeKind::ExpressionWithoutBlock {
-                    outer_attribute: vec![],
-                    expression: Box::new(CSTNode::new(
-                        CSTNodeKind::Factor {
-                            token: token::Token::Minus,
-                            row: 1,
-                            column: 3,
-                        },
-                        vec![
-                            make_literal_expression(3, 1, 1),
-                            make_literal_expression(1, 1, 5),
-                        ],
-                    )),
-                },
-                vec![],
-            ),
-        );
+    // 優先順位の低いものから順に並べた二項演算子
+    // 同じ段の演算子は優先順位が等しい
+    // `<-`は字句解析器が`<`と`-`に分けるので書けない
+    const BINARY_OPERATOR_LEVELS: &[&[&str]] = &[
+        &["=", "+=", "-=", "*=", "/=", "%=", "^=", "&=", "|="],
+        &["..", "..="],
+        &["||"],
+        &["&&"],
+        &["==", "!=", "<", ">", "<=", ">="],
+        &["|"],
+        &["^"],
+        &["&"],
+        &["<<", ">>"],
+        &["+", "-"],
+        &["*", "/", "%"],
+    ];
 
-        parse_cst(
-            "6 / 2",
-            CSTNode::new(
-                CSTNodeKind::ExpressionWithoutBlock {
-                    outer_attribute: vec![],
-                    expression: Box::new(CSTNode::new(
-                        CSTNodeKind::Factor {
-                            token: token::Token::Slash,
-                            row: 1,
-                            column: 3,
-                        },
-                        vec![
-                            make_literal_expression(6, 1, 1),
-                            make_literal_expression(2, 1, 5),
-                        ],
-                    )),
-                },
-                vec![],
-            ),
-        );
+    // 右結合の演算子 それ以外は左結合
+    const RIGHT_ASSOCIATIVE: &[&str] = &["=", "+=", "-=", "*=", "/=", "%=", "^=", "&=", "|="];
 
-        parse_cst(
-            "5 * 6",
-            CSTNode::new(
-                CSTNodeKind::ExpressionWithoutBlock {
-                    outer_attribute: vec![],
-                    expression: Box::new(CSTNode::new(
-                        CSTNodeKind::Factor {
-                            token: token::Token::Star,
-                            row: 1,
-                            column: 3,
-                        },
-                        vec![
-                            make_literal_expression(5, 1, 1),
-                            make_literal_expression(6, 1, 5),
-                        ],
-                    )),
-                },
-                vec![],
-            ),
+    #[test]
+    fn operator_precedence_and_associativity() {
+        // `a x b y c`を左から結び付けたものと右から結び付けたもの
+        let group = |x: &str, y: &str, is_left: bool| {
+            if is_left {
+                format!("({} ({} a b) c)", y, x)
+            } else {
+                format!("({} a ({} b c))", x, y)
+            }
+        };
+
+        // 段が違えば高い方が先に結び付き、同じ段なら結合性に従う
+        for (low_level, lows) in BINARY_OPERATOR_LEVELS.iter().enumerate() {
+            for (high_level, highs) in BINARY_OPERATOR_LEVELS.iter().enumerate().skip(low_level) {
+                for low in lows.iter() {
+                    for high in highs.iter() {
+                        let is_same_level = low_level == high_level;
+                        let is_left = !RIGHT_ASSOCIATIVE.contains(low);
+                        assert_eq!(
+                            parse_expression(&format!("a {} b {} c", low, high)),
+                            group(low, high, is_same_level && is_left)
+                        );
+                        assert_eq!(
+                            parse_expression(&format!("a {} b {} c", high, low)),
+                            group(high, low, !is_same_level || is_left)
+                        );
+                    }
+                }
+            }
+        }
+
+        // 前置演算子は二項演算子より先に結び付く
+        assert_eq!(parse_expression("-a * b"), "(* (- a) b)");
+        assert_eq!(parse_expression("!a == b"), "(== (! a) b)");
+        assert_eq!(parse_expression("a - -b"), "(- a (- b))");
+    }
+
+    #[test]
+    fn check_call_expression() {
+        assert_eq!(parse_expression("f()"), "(call f)");
+        assert_eq!(parse_expression("f(1, a)"), "(call f 1 a)");
+        assert_eq!(parse_expression("f(1,)"), "(call f 1)");
+        assert_eq!(parse_expression("f(1)(2)"), "(call (call f 1) 2)");
+        assert_eq!(parse_expression("f(1 + 2)"), "(call f (+ 1 2))");
+    }
+
+    #[test]
+    fn check_method_call_expression() {
+        assert_eq!(parse_expression("a.b()"), "(method a b)");
+        assert_eq!(
+            parse_expression("a.b(1).c(2, 3)"),
+            "(method (method a b 1) c 2 3)"
         );
+        assert_eq!(parse_expression("f().b()"), "(method (call f) b)");
+    }
 
-        //parse_cst("12 * 23 - 32 / 16 + 90");
+    #[test]
+    fn check_field_expression() {
+        assert_eq!(parse_expression("a.b"), "(field a b)");
+        assert_eq!(parse_expression("a.b.c"), "(field (field a b) c)");
+        assert_eq!(parse_expression("a.b(1).c"), "(field (method a b 1) c)");
+        assert_eq!(parse_expression("(a).b"), "(field a b)");
     }
 
     #[test]
-    fn check_call_expression() {}
+    fn check_await_expression() {
+        assert_eq!(parse_expression("a.await"), "(await a)");
+        assert_eq!(parse_expression("a.await.await"), "(await (await a))");
+        assert_eq!(
+            parse_expression("a.b().await.c"),
+            "(field (await (method a b)) c)"
+        );
+        assert_eq!(parse_expression("f(a.await)"), "(call f (await a))");
+
+        // `await`はメソッド名にならず、awaitした値の呼び出しになる
+        assert_eq!(parse_expression("a.await(1)"), "(call (await a) 1)");
+        assert!(!parse_with_errors("fn f() { a.; }").is_ok());
+    }
 
     #[test]
-    fn check_method_call_expression() {}
+    fn check_index_expression() {
+        assert_eq!(parse_expression("a[1]"), "(index a 1)");
+        assert_eq!(
+            parse_expression("a[1][b.c]"),
+            "(index (index a 1) (field b c))"
+        );
+        assert_eq!(
+            parse_expression("a.b[2](1)"),
+            "(call (index (field a b) 2) 1)"
+        );
+    }
 
     #[test]
-    fn check_field_expression() {}
+    fn left_recursion_with_operator() {
+        assert_eq!(parse_expression("a + b(1)"), "(+ a (call b 1))");
+        assert_eq!(
+            parse_expression("f(1) * g(2) + 3"),
+            "(+ (* (call f 1) (call g 2)) 3)"
+        );
+        assert_eq!(parse_expression("-a.b"), "(- (field a b))");
+        assert_eq!(parse_expression("1 + 2 * 3 - 4"), "(- (+ 1 (* 2 3)) 4)");
+        assert_eq!(parse_expression("(a + b).c"), "(field (+ a b) c)");
+    }
 
     #[test]
-    fn check_async_block_expression() {}
+    fn check_async_block_expression() {
+        assert_eq!(parse_expression("async { 1 }"), "(async)");
+        assert_eq!(parse_expression("async move { a }"), "(async move)");
+        assert_eq!(parse_expression("async { 1 }.await"), "(await (async))");
+
+        // `async fn`はアイテム、`async {`は式
+        let result = parse_with_errors("fn f() { async fn g() {} let a = async {}; }");
+        assert!(result.is_ok(), "{:?}", result.errors);
+        let statements = function_statements(&items(&result.tree)[0]);
+        let CSTNodeKind::Statement { statement } = &statements[0].node_kind else {
+            panic!("{:?}", statements[0].node_kind);
+        };
+        assert!(matches!(statement.node_kind, CSTNodeKind::Function { .. }));
+
+        assert!(!parse_with_errors("fn f() { async move; }").is_ok());
+    }
 
     #[test]
     fn check_continue_expression() {}
@@ -179,19 +321,78 @@ mod test {
     #[test]
     fn check_underscore_expression() {}
 
-    #[test]
-    fn check_block_expression() {
-        parse_cst(
-            "fn add() { let hoge = 100 * 10; sr a = 1; {ur b = 2;} {nr c = 3;}  } fn sub() { let a = 100; }",
-            make_literal_expression(1, 1, 1),
-        );
+    // `fn f() { code }` の最初の文
+    fn first_statement(code: &str) -> CSTNode {
+        let result = parse_with_errors(&format!("fn f() {{ {} }}", code));
+        assert!(result.is_ok(), "{:?}", result.errors);
+
+        let statements = function_statements(&items(&result.tree)[0]);
+        let CSTNodeKind::Statement { statement } = &statements[0].node_kind else {
+            panic!("{:?}", statements[0].node_kind);
+        };
+        statement.as_ref().clone()
     }
 
     #[test]
-    fn check_const_block_expression() {}
+    fn check_const_block_expression() {
+        let statement = first_statement("const { 1 } const fn g() {}");
+        let CSTNodeKind::ConstBlockExpression {
+            block_expression, ..
+        } = &statement.node_kind
+        else {
+            panic!("{:?}", statement.node_kind);
+        };
+        assert!(matches!(
+            block_expression.node_kind,
+            CSTNodeKind::BlockExpression {
+                statements: Some(_),
+                ..
+            }
+        ));
+
+        let statement = first_statement("const fn g() {} const { 1 }");
+        assert!(matches!(statement.node_kind, CSTNodeKind::Function { .. }));
+
+        assert!(!parse_with_errors("fn f() { const 1; }").is_ok());
+    }
 
     #[test]
-    fn check_unsafe_block_expression() {}
+    fn check_unsafe_block_expression() {
+        let statement = first_statement("unsafe { g() } unsafe fn g() {}");
+        let CSTNodeKind::UnsafeBlockExpression {
+            block_expression, ..
+        } = &statement.node_kind
+        else {
+            panic!("{:?}", statement.node_kind);
+        };
+        assert!(matches!(
+            block_expression.node_kind,
+            CSTNodeKind::BlockExpression {
+                statements: Some(_),
+                ..
+            }
+        ));
+
+        let statement = first_statement("unsafe extern \"C\" fn g() {}");
+        let CSTNodeKind::Function {
+            function_qualifiers,
+            ..
+        } = &statement.node_kind
+        else {
+            panic!("{:?}", statement.node_kind);
+        };
+        let CSTNodeKind::FunctionQualifiers {
+            item_safety: Some(_),
+            extern_keyword: Some(_),
+            abi: Some(_),
+            ..
+        } = &function_qualifiers.node_kind
+        else {
+            panic!("{:?}", function_qualifiers.node_kind);
+        };
+
+        assert!(!parse_with_errors("fn f() { unsafe 1; }").is_ok());
+    }
 
     #[test]
     fn check_loop_expression() {}
@@ -200,8 +401,510 @@ mod test {
     fn check_if_expression() {}
 
     #[test]
-    fn check_if_let_expression() {}
+    fn check_if_let_expression() {
+        let statement = first_statement("if let a | b = c { 1 } else { 2 }");
+        let CSTNodeKind::IfLetExpression {
+            pattern,
+            else_expression: Some(_),
+            ..
+        } = &statement.node_kind
+        else {
+            panic!("{:?}", statement.node_kind);
+        };
+        let CSTNodeKind::Pattern {
+            repeat_pattern_no_top_alt,
+            ..
+        } = &pattern.node_kind
+        else {
+            panic!("{:?}", pattern.node_kind);
+        };
+        assert_eq!(repeat_pattern_no_top_alt.len(), 1);
+
+        // `||`はScrutineeに含めない
+        assert!(!parse_with_errors("fn f() { if let a = b || c { 1 } }").is_ok());
+    }
+
+    #[test]
+    fn check_let_chain() {
+        let statement = first_statement("if let a = b && c == d && let e = a { 1 }");
+        let CSTNodeKind::IfExpression { expression, .. } = &statement.node_kind else {
+            panic!("{:?}", statement.node_kind);
+        };
+        let CSTNodeKind::LetChain {
+            let_chain_condition,
+            repeat_let_chain_condition,
+        } = &expression.node_kind
+        else {
+            panic!("{:?}", expression.node_kind);
+        };
+        assert!(matches!(
+            let_chain_condition.node_kind,
+            CSTNodeKind::LetChainCondition { .. }
+        ));
+        assert_eq!(repeat_let_chain_condition.len(), 2);
+        assert!(matches!(
+            repeat_let_chain_condition[0].1.node_kind,
+            CSTNodeKind::ExpressionWithoutBlock { .. }
+        ));
+        assert!(matches!(
+            repeat_let_chain_condition[1].1.node_kind,
+            CSTNodeKind::LetChainCondition { .. }
+        ));
+
+        // `let`が無ければ普通の`&&`の式
+        let statement = first_statement("if a && b { 1 }");
+        let CSTNodeKind::IfExpression { expression, .. } = &statement.node_kind else {
+            panic!("{:?}", statement.node_kind);
+        };
+        assert!(matches!(
+            expression.node_kind,
+            CSTNodeKind::ExpressionWithoutBlock { .. }
+        ));
+
+        assert!(!parse_with_errors("fn f() { if a || let b = c { 1 } }").is_ok());
+        assert!(!parse_with_errors("fn f() { if let a = b && { 1 } }").is_ok());
+    }
 
     #[test]
     fn check_match_expression() {}
+
+    #[test]
+    fn check_attribute() {
+        let result = parse_with_errors("#![a] #[b::c = 1] #[unsafe(d(e, [f]))] fn g() { #![h] }");
+        assert!(result.is_ok(), "{:?}", result.errors);
+
+        let CSTNodeKind::Crate {
+            inner_attributes,
+            items,
+        } = &result.tree.node_kind
+        else {
+            panic!("{:?}", result.tree.node_kind);
+        };
+        assert_eq!(inner_attributes.len(), 1);
+        assert!(matches!(
+            inner_attributes[0].node_kind,
+            CSTNodeKind::InnerAttribute { .. }
+        ));
+
+        // OuterAttributeはアイテムが持つ
+        let CSTNodeKind::Function {
+            outer_attribute,
+            block_expression_or_semicolon,
+            ..
+        } = &items[0].node_kind
+        else {
+            panic!("{:?}", items[0].node_kind);
+        };
+        assert_eq!(outer_attribute.len(), 2);
+
+        // `b::c = 1`
+        let CSTNodeKind::OuterAttribute { attribute, .. } = &outer_attribute[0].node_kind else {
+            panic!("{:?}", outer_attribute[0].node_kind);
+        };
+        let CSTNodeKind::Attribute {
+            unsafe_keyword: None,
+            simple_path,
+            attribute_input: Some(attribute_input),
+            ..
+        } = &attribute.node_kind
+        else {
+            panic!("{:?}", attribute.node_kind);
+        };
+        let CSTNodeKind::SimplePath {
+            repeat_simple_path_segment,
+            ..
+        } = &simple_path.node_kind
+        else {
+            panic!("{:?}", simple_path.node_kind);
+        };
+        assert_eq!(repeat_simple_path_segment.len(), 1);
+        assert!(matches!(
+            attribute_input.node_kind,
+            CSTNodeKind::AttributeInput { equal: Some(_), .. }
+        ));
+
+        // `unsafe(d(e, [f]))`
+        let CSTNodeKind::OuterAttribute { attribute, .. } = &outer_attribute[1].node_kind else {
+            panic!("{:?}", outer_attribute[1].node_kind);
+        };
+        let CSTNodeKind::Attribute {
+            unsafe_keyword: Some(_),
+            attribute_input: Some(attribute_input),
+            ..
+        } = &attribute.node_kind
+        else {
+            panic!("{:?}", attribute.node_kind);
+        };
+        let CSTNodeKind::AttributeInput {
+            equal: None,
+            expression,
+        } = &attribute_input.node_kind
+        else {
+            panic!("{:?}", attribute_input.node_kind);
+        };
+        let CSTNodeKind::DelimTokenTree { token_tree, .. } = &expression.node_kind else {
+            panic!("{:?}", expression.node_kind);
+        };
+        assert_eq!(token_tree.len(), 3);
+        assert!(matches!(
+            token_tree[2].node_kind,
+            CSTNodeKind::DelimTokenTree { .. }
+        ));
+
+        let CSTNodeKind::BlockExpression {
+            inner_attribute, ..
+        } = &block_expression_or_semicolon.node_kind
+        else {
+            panic!("{:?}", block_expression_or_semicolon.node_kind);
+        };
+        assert_eq!(inner_attribute.len(), 1);
+    }
+
+    #[test]
+    fn recover_from_broken_item() {
+        let result = parse_with_errors("fn a() { let x = 1; } = = ; fn b() { let y = 2; }");
+
+        assert_eq!(result.errors.len(), 1);
+        let items = items(&result.tree);
+        assert_eq!(items.len(), 3);
+        assert!(!is_error(&items[0]));
+        assert!(is_error(&items[1]));
+        assert!(!is_error(&items[2]));
+    }
+
+    #[test]
+    fn recover_from_broken_statement() {
+        let result = parse_with_errors("fn a() { let x = 1; let = ; let y = 2; } fn b() { }");
+
+        assert_eq!(result.errors.len(), 1);
+        let items = items(&result.tree);
+        assert_eq!(items.len(), 2);
+
+        let statements = function_statements(&items[0]);
+        assert_eq!(statements.len(), 3);
+        assert!(!is_error(&statements[0]));
+        assert!(is_error(&statements[1]));
+        assert!(!is_error(&statements[2]));
+    }
+
+    #[test]
+    fn recover_skips_nested_block() {
+        // 関数の先頭で失敗した場合は本体のブロックごと読み飛ばす
+        let result = parse_with_errors("fn a( { { let x = 1; } } fn b() { let y = 2; }");
+
+        assert_eq!(result.errors.len(), 1);
+        let items = items(&result.tree);
+        assert_eq!(items.len(), 2);
+        assert!(is_error(&items[0]));
+        assert!(!is_error(&items[1]));
+    }
+
+    #[test]
+    fn report_every_error() {
+        let result = parse_with_errors("fn a() { let = 1; } ) fn b() { ur = 2; }");
+
+        assert_eq!(result.errors.len(), 3);
+        let items = items(&result.tree);
+        assert_eq!(items.len(), 3);
+        assert!(is_error(&items[1]));
+        assert!(is_error(&function_statements(&items[0])[0]));
+        assert!(is_error(&function_statements(&items[2])[0]));
+    }
+
+    #[test]
+    fn report_missing_semicolon() {
+        let result = parse_with_errors("fn a() { let x = 1 let y = 2; }");
+
+        assert_eq!(result.errors.len(), 1);
+        let error = &result.errors[0];
+        assert_eq!(
+            error.error_kind,
+            ErrorKind::Syntax(SyntaxError::MissingSemicolon)
+        );
+        assert_eq!(error.error_text, "expected `;`, found `let`");
+        assert_eq!(error.position, Some(SourcePosition { row: 1, column: 20 }));
+    }
+
+    #[test]
+    fn report_expected_token() {
+        let result = parse_with_errors("fn 3() {}");
+
+        assert_eq!(result.errors.len(), 1);
+        let error = &result.errors[0];
+        assert_eq!(
+            error.error_kind,
+            ErrorKind::Syntax(SyntaxError::ExpectedToken)
+        );
+        assert_eq!(error.error_text, "expected identifier, found `3`");
+        assert_eq!(error.position, Some(SourcePosition { row: 1, column: 4 }));
+
+        let result = parse_with_errors("fn a() { sr b = (1 + 2; }");
+        assert_eq!(result.errors[0].error_text, "expected `)`, found `;`");
+
+        let result = parse_with_errors("fn a() { let = 3; }");
+        assert_eq!(result.errors[0].error_text, "expected pattern, found `=`");
+
+        let result = parse_with_errors("#[a::] fn b() {}");
+        assert_eq!(
+            result.errors[0].error_text,
+            "expected identifier, found `]`"
+        );
+
+        let result = parse_with_errors("#[a(b] fn c() {}");
+        assert_eq!(result.errors[0].error_text, "expected `)`, found `]`");
+    }
+
+    #[test]
+    fn report_unclosed_delimiter() {
+        let result = parse_with_errors("fn a() {\n    let x = 1;\n");
+
+        assert_eq!(result.errors.len(), 1);
+        let error = &result.errors[0];
+        assert_eq!(
+            error.error_kind,
+            ErrorKind::Syntax(SyntaxError::ParenthesesNotClosed)
+        );
+        assert!(error.error_text.ends_with("found end of file"));
+        assert_eq!(error.position, Some(SourcePosition { row: 3, column: 1 }));
+
+        // 開き括弧の位置
+        assert_eq!(error.notes.len(), 1);
+        assert_eq!(
+            error.notes[0].position,
+            SourcePosition { row: 1, column: 8 }
+        );
+        assert_eq!(error.notes[0].note_text, "unclosed delimiter `{`");
+
+        // 括弧の中で文が終わった
+        let result = parse_with_errors("fn a() {\n    let x = (1 + 2;\n}");
+
+        assert_eq!(result.errors.len(), 1);
+        let error = &result.errors[0];
+        assert_eq!(
+            error.error_kind,
+            ErrorKind::Syntax(SyntaxError::ParenthesesNotClosed)
+        );
+        assert_eq!(error.error_text, "expected `)`, found `;`");
+        assert_eq!(error.position, Some(SourcePosition { row: 2, column: 19 }));
+        assert_eq!(error.notes.len(), 1);
+        assert_eq!(
+            error.notes[0].position,
+            SourcePosition { row: 2, column: 13 }
+        );
+        assert_eq!(error.notes[0].note_text, "unclosed delimiter `(`");
+    }
+
+    fn parse_with_trace(code: &str) -> ParseResult<CSTNode> {
+        let mut lexer = Lexer::new(code);
+        let mut parser = CSTParser::new(&lexer.tokenize());
+        parser.enable_trace();
+
+        parser.parse()
+    }
+
+    #[test]
+    fn trace_rule_invocations() {
+        assert!(parse_with_errors("fn a() {}").trace.is_none());
+
+        let result = parse_with_trace("fn a() { b(1)(2); }");
+        let root = result.trace.unwrap().root;
+        assert_eq!(root.rule, "Crate");
+        assert_eq!(root.result, TraceResult::Success);
+        assert_eq!(root.end, 14);
+
+        let function = root.find(&|node| node.rule == "Function").unwrap();
+        assert_eq!(function.result, TraceResult::Success);
+        assert_eq!((function.start, function.end), (0, 14));
+        assert_eq!((function.row, function.column), (1, 1));
+        assert!(!function.memo);
+
+        // Item -> VisItem -> Function の順に呼び出される
+        let item = root
+            .children
+            .iter()
+            .find(|node| node.rule == "Item")
+            .unwrap();
+        assert_eq!(item.result, TraceResult::Success);
+        assert!(item.find(&|node| node.rule == "VisItem").is_some());
+
+        // メモから返した呼び出しと左再帰の種の成長
+        assert!(root
+            .find(&|node| node.memo && node.result == TraceResult::Success)
+            .is_some());
+        assert!(root
+            .find(&|node| node.memo && node.result == TraceResult::Recursive)
+            .is_some());
+        let grown = root
+            .find(&|node| node.rule == "Operand" && node.grow == Some(2))
+            .unwrap();
+        assert_eq!(grown.result, TraceResult::Success);
+    }
+
+    #[test]
+    fn trace_failure_and_recovery() {
+        let result = parse_with_trace("fn 3() {}");
+        let root = result.trace.unwrap().root;
+        assert_eq!(root.result, TraceResult::Fail);
+
+        let function = root.find(&|node| node.rule == "Function").unwrap();
+        assert_eq!(function.result, TraceResult::Fail);
+        assert_eq!(function.end, function.start);
+        assert_eq!(function.error.as_deref(), Some("Syntax(ExpectedToken)"));
+
+        let recover = root.find(&|node| node.rule == "Recover").unwrap();
+        assert_eq!(recover.result, TraceResult::Recovered);
+        assert_eq!((recover.start, recover.end), (0, 6));
+    }
+
+    #[test]
+    fn trace_output() {
+        let trace = parse_with_trace("fn a() {}").trace.unwrap();
+
+        let json = trace.to_json();
+        assert!(json.contains("\"rule\": \"Function\""));
+
+        let html = trace.to_html();
+        assert!(!html.contains("/*TRACE_JSON*/"));
+        assert!(html.contains("\"rule\":\"Function\""));
+    }
+
+    // nagi_macrosの表から作った字句解析器、構文解析器、演算子の表が食い違っていないか
+    #[test]
+    fn token_tables_are_consistent() {
+        use crate::expression::{infix_binding_power, prefix_binding_power};
+        use nagi_syntax_tree::keywords::Keyword;
+        use nagi_syntax_tree::token::{BinaryOperator, Token, UnaryOperator};
+
+        let token_lexer = |code: &str| {
+            let mut lexer = Lexer::new(code);
+            crate::lexer::Lexer::new(&lexer.tokenize())
+        };
+
+        for keyword in Keyword::ALL {
+            assert_eq!(Keyword::from_str(keyword.as_str()).as_ref(), Some(keyword));
+            if !keyword.as_str().starts_with('\'') {
+                let lexer = token_lexer(keyword.as_str());
+                assert_eq!(lexer.peek(), Token::Keyword(keyword.clone()));
+            }
+        }
+
+        for punctuation in Token::PUNCTUATIONS {
+            let spelling = punctuation.punctuation_str().unwrap();
+            assert_eq!(
+                Token::from_punctuation_str(spelling).as_ref(),
+                Some(punctuation)
+            );
+            assert_eq!(punctuation.to_string(), spelling);
+
+            // 字句解析器は1文字ずつ切り出し、構文解析器がつなげる
+            let lexer = token_lexer(spelling);
+            assert_eq!(
+                lexer.token_count(),
+                spelling.chars().count(),
+                "{}",
+                spelling
+            );
+            let glued = lexer.glue();
+            if punctuation.is_joint_punctuation() {
+                assert_eq!(glued, Some((punctuation.clone(), spelling.len())));
+            } else if spelling.len() == 1 {
+                assert_eq!(lexer.peek(), *punctuation);
+                assert!(glued.is_none() || glued == Some((punctuation.clone(), 1)));
+            } else {
+                assert_ne!(glued.map(|(token, _)| token).as_ref(), Some(punctuation));
+            }
+
+            assert_eq!(
+                BinaryOperator::from_token(punctuation).is_some(),
+                infix_binding_power(punctuation).is_some(),
+                "{}",
+                spelling
+            );
+            assert_eq!(
+                UnaryOperator::from_token(punctuation).is_some(),
+                prefix_binding_power(punctuation).is_some(),
+                "{}",
+                spelling
+            );
+            if let Some(operator) = BinaryOperator::from_token(punctuation) {
+                assert_eq!(operator.as_str(), spelling);
+                assert_eq!(
+                    Some(operator.binding_power()),
+                    infix_binding_power(punctuation)
+                );
+            }
+            if let Some(operator) = UnaryOperator::from_token(punctuation) {
+                assert_eq!(operator.as_str(), spelling);
+                assert_eq!(
+                    Some(((), operator.binding_power())),
+                    prefix_binding_power(punctuation)
+                );
+            }
+            // 左結合か右結合のどちらか
+            if let Some((left, right)) = infix_binding_power(punctuation) {
+                assert_eq!(left.abs_diff(right), 1, "{}", spelling);
+            }
+        }
+    }
+
+    #[test]
+    fn format_source_code() {
+        let code = "// a\nfn a(x: i32) -> i32 { // b\n  ur aa = 1;\n    sr b: i32 = p(x, 2,); /* c */\nnr ccc = 3;\n\n\n\n  let d = 4;\n    f(aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbbbbbbbbbb, cccccccccccccccccccccccccccccc);\n    g(1, // e\n 2,);\n    // f\n}\nfn h() { /* g */ }";
+        let formatted = "\
+// a
+fn a(x: i32) -> i32 { // b
+    ur aa     = 1;
+    sr b: i32 = p(x, 2); /* c */
+    nr ccc    = 3;
+
+    let d = 4;
+    f(
+        aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa,
+        bbbbbbbbbbbbbbbbbbbbbbbbbbbbbb,
+        cccccccccccccccccccccccccccccc,
+    );
+    g(
+        1, // e
+        2,
+    );
+    // f
+}
+
+fn h() { /* g */
+}
+";
+        let style = FormatStyle::default();
+        assert_eq!(format_source(code, &style).unwrap(), formatted);
+        assert_eq!(format_source(formatted, &style).unwrap(), formatted);
+
+        let config = "\
+[package]
+name = \"a\" # fmt以外の表は読み飛ばす
+
+[fmt]
+indent_width = 2
+brace_style = \"next_line\"
+trailing_comma = \"always\"
+align_let_rarity = false
+";
+        let style = read_format_style(config).unwrap();
+        let formatted = "\
+struct A
+{
+  x: i32,
+}
+
+fn a()
+{
+  ur aa = f(1,);
+  sr b = 2;
+}
+";
+        let code = "struct A { x: i32, } fn a() { ur aa = f(1); sr b = 2; }";
+        assert_eq!(format_source(code, &style).unwrap(), formatted);
+
+        assert!(format_source("fn a( {}", &style).is_err());
+        assert!(read_format_style("[fmt]\nwidth = 1").is_err());
+        assert!(read_format_style("[fmt]\nbrace_style = next_line").is_err());
+    }
 }
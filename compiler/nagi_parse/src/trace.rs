@@ -0,0 +1,103 @@
+use serde::Serialize;
+
+// 構文解析のトレース
+// 規則の呼び出しを木にして、どこで失敗して後戻りしたかを追えるようにする
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TraceResult {
+    Success,
+    Fail,
+    Recursive, // 左再帰を検出したがまだ種が無い
+    Skipped,   // 種を成長させている位置で左再帰に関わらない規則を呼び出した
+    Recovered, // パニックモードで読み飛ばした
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceNode {
+    pub rule: String,
+    pub start: usize, // トークン位置
+    pub end: usize,
+    pub row: usize, // 開始トークンのソース上の位置
+    pub column: usize,
+    pub token: String,
+    pub result: TraceResult,
+    pub memo: bool, // メモから結果を返した
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grow: Option<usize>, // 左再帰の種を成長させた回数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub children: Vec<TraceNode>,
+}
+
+impl TraceNode {
+    // 結果を含めた全ての子孫を数える
+    pub fn count(&self) -> usize {
+        1 + self.children.iter().map(TraceNode::count).sum::<usize>()
+    }
+
+    pub fn find(&self, predicate: &dyn Fn(&TraceNode) -> bool) -> Option<&TraceNode> {
+        if predicate(self) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(predicate))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ParseTrace {
+    pub root: TraceNode,
+}
+
+const HTML_TEMPLATE: &str = include_str!("trace.html");
+
+impl ParseTrace {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    // JSONを埋め込んだ単体で開けるHTML
+    pub fn to_html(&self) -> String {
+        // `</script>`でスクリプトが終わらないようにする
+        let json = serde_json::to_string(self).unwrap().replace("</", "<\\/");
+        HTML_TEMPLATE.replace("/*TRACE_JSON*/null", &json)
+    }
+}
+
+// 呼び出し中の規則をスタックに積み、終わったら親の子にする
+pub(crate) struct Tracer {
+    stack: Vec<TraceNode>,
+}
+
+impl Tracer {
+    pub fn new(root: TraceNode) -> Self {
+        Self { stack: vec![root] }
+    }
+
+    pub fn enter(&mut self, node: TraceNode) {
+        self.stack.push(node);
+    }
+
+    pub fn exit(&mut self, end: usize, result: TraceResult, error: Option<String>) {
+        let mut node = self.stack.pop().unwrap();
+        node.end = end;
+        node.result = result;
+        node.error = error;
+        self.leaf(node);
+    }
+
+    pub fn leaf(&mut self, node: TraceNode) {
+        self.stack.last_mut().unwrap().children.push(node);
+    }
+
+    pub fn finish(mut self, end: usize, result: TraceResult) -> ParseTrace {
+        // 途中で終わった呼び出しを閉じる
+        while self.stack.len() > 1 {
+            self.exit(end, TraceResult::Fail, None);
+        }
+
+        let mut root = self.stack.pop().unwrap();
+        root.end = end;
+        root.result = result;
+        ParseTrace { root }
+    }
+}
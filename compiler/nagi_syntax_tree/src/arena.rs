@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+
+// 木のノードを番号で持つ配列
+// 各ノードは子を取り外した形で持ち、親子は番号でたどる
+// from_treeで木から作り、to_treeで元の形の木に戻す
+//
+// 番号は根を0とし、子ノードをfor_each_childの順にたどった前順の番号
+// ある番号のノードの子孫は、その番号の直後から連続した番号になる
+// Rcで共有している部分木も、現れた場所ごとに別の番号のノードになる
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    pub(crate) fn new(index: usize) -> Self {
+        Self(index)
+    }
+
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+// define_syntax_tree!で定義したノードに実装される
+pub trait ArenaNode: Sized {
+    fn for_each_child<'a>(&'a self, f: &mut dyn FnMut(&'a Self));
+    fn map_children(self, f: &mut dyn FnMut(Self) -> Self) -> Self;
+}
+
+pub struct Arena<T> {
+    entries: Vec<ArenaEntry<T>>,
+}
+
+struct ArenaEntry<T> {
+    node: T, // 子を取り外したノード 子の場所には木の葉の複製を置いておく
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    end: usize, // 子孫の最後の番号の次
+}
+
+impl<T: ArenaNode + Clone> Arena<T> {
+    // 深い木でもスタックを使わないよう、再帰せずにたどる
+    pub fn from_tree(root: T) -> Self {
+        let placeholder = first_leaf(&root).clone();
+        let mut entries: Vec<ArenaEntry<T>> = vec![];
+
+        let mut stack = vec![(root, None)];
+        while let Some((node, parent)) = stack.pop() {
+            let id = NodeId(entries.len());
+            if let Some(NodeId(parent)) = parent {
+                entries[parent].children.push(id);
+            }
+
+            let mut children = vec![];
+            let node = node.map_children(&mut |child| {
+                children.push(child);
+                placeholder.clone()
+            });
+            entries.push(ArenaEntry {
+                node,
+                parent,
+                children: vec![],
+                end: 0,
+            });
+
+            // 最初の子から取り出されるように逆順に積む
+            stack.extend(children.into_iter().rev().map(|child| (child, Some(id))));
+        }
+
+        for index in (0..entries.len()).rev() {
+            entries[index].end = match entries[index].children.last() {
+                Some(NodeId(last)) => entries[*last].end,
+                None => index + 1,
+            };
+        }
+
+        Self { entries }
+    }
+
+    // 子を付け直した木
+    pub fn to_tree(&self) -> T {
+        // 子の番号は親より大きいので、後ろから組み立てる
+        let mut trees: Vec<Option<T>> = (0..self.entries.len()).map(|_| None).collect();
+        for index in (0..self.entries.len()).rev() {
+            let entry = &self.entries[index];
+            let mut children = entry
+                .children
+                .iter()
+                .map(|NodeId(child)| trees[*child].take().unwrap())
+                .collect::<Vec<T>>()
+                .into_iter();
+            trees[index] = Some(
+                entry
+                    .node
+                    .clone()
+                    .map_children(&mut |_| children.next().unwrap()),
+            );
+        }
+
+        trees[0].take().unwrap()
+    }
+}
+
+impl<T> Arena<T> {
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // ノードの子は取り外してあるので、子はchildrenでたどる
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.entries.get(id.0).map(|entry| &entry.node)
+    }
+
+    pub fn node(&self, id: NodeId) -> &T {
+        &self.entries[id.0].node
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.entries[id.0].parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.entries[id.0].children
+    }
+
+    // 親から根までを近い順に返す
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.parent(id), |id| self.parent(*id))
+    }
+
+    // 子孫を前順に返す
+    pub fn descendants(&self, id: NodeId) -> impl Iterator<Item = NodeId> {
+        (id.0 + 1..self.entries[id.0].end).map(NodeId)
+    }
+
+    pub fn is_ancestor(&self, ancestor: NodeId, id: NodeId) -> bool {
+        ancestor.0 < id.0 && id.0 < self.entries[ancestor.0].end
+    }
+
+    pub fn depth(&self, id: NodeId) -> usize {
+        self.ancestors(id).count()
+    }
+
+    // 全てのノードを前順に返す
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &T)> + '_ {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (NodeId(index), &entry.node))
+    }
+}
+
+// 元の木のノードに、from_treeで付く番号を付けて前順に並べる
+pub fn preorder<T: ArenaNode>(root: &T) -> Vec<(NodeId, &T)> {
+    let mut nodes = vec![];
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        nodes.push((NodeId(nodes.len()), node));
+        let mut children = vec![];
+        node.for_each_child(&mut |child| children.push(child));
+        stack.extend(children.into_iter().rev());
+    }
+    nodes
+}
+
+fn first_leaf<T: ArenaNode>(root: &T) -> &T {
+    let mut node = root;
+    loop {
+        let mut first_child = None;
+        node.for_each_child(&mut |child| {
+            first_child.get_or_insert(child);
+        });
+        match first_child {
+            Some(child) => node = child,
+            None => return node,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{ASTNode, ASTNodeKind};
+    use crate::keywords::Keyword;
+    use crate::test_trees::{ast, cst};
+    use crate::token::*;
+
+    // fn f(x: i32) { let a = 1 + x; ur b = a * 2 + 3; }
+    fn function() -> ASTNode {
+        let multiply = ast::binary(BinaryOperator::Mul, ast::path("a"), ast::integer("2"));
+        ast::with_spans(ast::crate_node(vec![ast::function(
+            "f",
+            &[("x", "i32")],
+            vec![
+                ast::let_statement(
+                    Rarity::Let,
+                    "a",
+                    ast::expression(ast::binary(
+                        BinaryOperator::Add,
+                        ast::integer("1"),
+                        ast::path("x"),
+                    )),
+                ),
+                ast::let_statement(
+                    Rarity::Ur,
+                    "b",
+                    ast::expression(ast::binary(
+                        BinaryOperator::Add,
+                        multiply,
+                        ast::integer("3"),
+                    )),
+                ),
+            ],
+        )]))
+    }
+
+    fn kind_names<'a>(nodes: impl Iterator<Item = &'a ASTNode>) -> Vec<&'static str> {
+        nodes.map(|node| node.kind_name()).collect()
+    }
+
+    // ノードの番号は前順に付く
+    #[test]
+    fn ids_in_preorder() {
+        let ast = function();
+        let arena = Arena::from_tree(ast.clone());
+        assert_eq!(arena.root().index(), 0);
+        assert_eq!(arena.len(), preorder(&ast).len());
+        assert_eq!(
+            kind_names(arena.iter().map(|(_, node)| node)),
+            kind_names(preorder(&ast).into_iter().map(|(_, node)| node))
+        );
+
+        // a * 2 + 3の左辺の演算子式は右辺の整数より前に番号が付く
+        let (add, _) = arena
+            .iter()
+            .filter(|(_, node)| matches!(node.node_kind, ASTNodeKind::BinaryOperator { .. }))
+            .nth(1)
+            .unwrap();
+        let children = arena.children(add);
+        assert_eq!(
+            kind_names(children.iter().map(|id| arena.node(*id))),
+            ["BinaryOperator", "ExpressionWithoutBlock"]
+        );
+        assert_eq!(children[0].index(), add.index() + 1);
+        assert_eq!(
+            arena.descendants(add).last(),
+            arena.descendants(children[1]).last()
+        );
+    }
+
+    // 子から親をたどれる
+    #[test]
+    fn parent_links() {
+        let arena = Arena::from_tree(function());
+        assert_eq!(arena.parent(arena.root()), None);
+
+        for (id, _) in arena.iter() {
+            for child in arena.children(id) {
+                assert_eq!(arena.parent(*child), Some(id));
+            }
+            for descendant in arena.descendants(id) {
+                assert!(arena.ancestors(descendant).any(|ancestor| ancestor == id));
+                assert!(arena.is_ancestor(id, descendant));
+            }
+            assert_eq!(arena.depth(id), arena.ancestors(id).count());
+        }
+    }
+
+    // 範囲も含めて元の木に戻る
+    #[test]
+    fn back_to_tree() {
+        let ast = function();
+        let tree = Arena::from_tree(ast.clone()).to_tree();
+        assert_eq!(tree, ast);
+        assert_eq!(
+            serde_json::to_string(&tree).unwrap(),
+            serde_json::to_string(&ast).unwrap()
+        );
+
+        // let x = 1 + 2;
+        let cst = cst::let_statement(
+            Keyword::Let,
+            "x",
+            cst::binary(
+                Token::Plus,
+                (1, 11),
+                cst::integer("1", (1, 9)),
+                cst::integer("2", (1, 13)),
+            ),
+            (1, 1),
+        );
+        let arena = Arena::from_tree(cst.clone());
+        assert_eq!(
+            serde_json::to_string(&arena.to_tree()).unwrap(),
+            serde_json::to_string(&cst).unwrap()
+        );
+    }
+
+    // Rcで共有した部分木も、現れた場所ごとに別の番号になる
+    #[test]
+    fn shared_subtree_per_occurrence() {
+        let operand = ast::binary(BinaryOperator::Add, ast::integer("1"), ast::integer("2"));
+        let ASTNodeKind::BinaryOperator { left, .. } = &operand.node_kind else {
+            unreachable!();
+        };
+        let ast = ASTNode::new(ASTNodeKind::BinaryOperator {
+            operator: BinaryOperator::Mul,
+            left: left.clone(),
+            right: left.clone(),
+        });
+        let arena = Arena::from_tree(ast.clone());
+        let [left, right] = arena.children(arena.root()) else {
+            panic!("{:?}", arena.children(arena.root()));
+        };
+        assert_ne!(left, right);
+        assert_eq!(
+            arena.descendants(*left).count(),
+            arena.descendants(*right).count()
+        );
+        assert_eq!(arena.to_tree(), ast);
+    }
+}
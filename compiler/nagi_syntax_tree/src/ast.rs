@@ -1,16 +1,61 @@
+use crate::span::Span;
 use crate::token::*;
+use crate::walk::{define_syntax_tree, SyntaxNode};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::prelude::*;
+use std::rc::Rc;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ASTNode {
-    pub node_kind: ASTNodeKind,
+// 木の形だけを比べ、範囲は比べない
+// マクロ展開した木と展開後のソースコードを解析した木などは範囲だけが違う
+impl PartialEq for ASTNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.node_kind == other.node_kind
+    }
 }
 
 impl ASTNode {
     pub fn new(node_kind: ASTNodeKind) -> Self {
-        Self { node_kind }
+        Self {
+            node_kind,
+            span: None,
+        }
+    }
+
+    // 範囲が決まっていなければspanにする
+    // 括弧などを読み飛ばして子をそのまま返した規則では子の範囲を残す
+    pub fn with_span(mut self, span: Option<Span>) -> Self {
+        if self.span.is_none() {
+            self.span = span;
+        }
+        self
+    }
+
+    // 演算子トークンと被演算子から演算子式を作る
+    // 被演算子が1つなら前置演算子、2つなら中置演算子
+    pub fn operator(token: &Token, operands: Vec<ASTNode>) -> Option<Self> {
+        let mut operands = operands.into_iter();
+        let node_kind = match (operands.next(), operands.next(), operands.next()) {
+            (Some(expression), None, None) => ASTNodeKind::UnaryOperator {
+                operator: UnaryOperator::from_token(token)?,
+                expression: Rc::new(expression),
+            },
+            (Some(left), Some(right), None) => ASTNodeKind::BinaryOperator {
+                operator: BinaryOperator::from_token(token)?,
+                left: Rc::new(left),
+                right: Rc::new(right),
+            },
+            _ => return None,
+        };
+
+        Some(Self::new(node_kind))
+    }
+
+    // 子ノードをフィールドの順に返す
+    pub fn children(&self) -> Vec<&ASTNode> {
+        let mut children = vec![];
+        self.for_each_child(&mut |child| children.push(child));
+        children
     }
 
     pub fn write_ast(&self, file_name: &str) {
@@ -25,140 +70,395 @@ impl ASTNode {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum ASTNodeKind {
-    Crate {
-        inner_attribute: Vec<ASTNode>,
-        item: Vec<ASTNode>,
-    },
-
-    Factor {
-        token: Token,
-    },
-
-    Literal {
-        literal: Literal,
-    },
-
-    BinaryOperator {
-        operator: BinaryOperator,
-        left: Box<ASTNode>,
-        right: Box<ASTNode>,
-    },
-
-    InnerAttribute {
-        attribute: Box<ASTNode>,
-    },
-
-    OuterAttribute {
-        attribute: Box<ASTNode>,
-    },
-
-    Visibility {},
-
-    Function {
-        function_qualifiers: Box<ASTNode>,
-        identifier: String,
-        generic_params: Option<Box<ASTNode>>,
-        function_parameters: Option<Box<ASTNode>>,
-        function_return_type: Option<Box<ASTNode>>,
-        where_clause: Option<Box<ASTNode>>,
-        block_expression: Option<Box<ASTNode>>, // When None is ;
-    },
-
-    FunctionQualifiers {
-        const_keyword: bool,
-        async_keyword: bool,
-        item_safety: Option<Box<ASTNode>>,
-        extern_keyword: bool,
-        abi: Option<Box<ASTNode>>,
-    },
-
-    Expression {
-        expression: Box<ASTNode>,
-    },
-
-    ExpressionWithoutBlock {
-        outer_attribute: Vec<ASTNode>,
-        expression: Box<ASTNode>,
-    },
-
-    ExpressionWithBlock {
-        outer_attribute: Vec<ASTNode>,
-        expression_with_block: Box<ASTNode>,
-    },
-
-    PathExpression {
-        expression: Box<ASTNode>,
-    },
-
-    // PathInExpression ::= `::`? PathExprSegment (`::` PathExprSegment)*
-    PathInExpression {
-        path_expr_segment: Box<ASTNode>,
-        repeat_path_expr_segment: Vec<ASTNode>,
-    },
-
-    // PathExprSegment ::= PathIdentSegment (`::` GenericArgs)?
-    PathExprSegment {
-        path_ident_segment: Box<ASTNode>,
-        generic_args: Option<Box<ASTNode>>,
-    },
-
-    // ReturnExpression ::= return (Expression)?
-    ReturnExpression {
-        expression: Option<Box<ASTNode>>,
-    },
-
-    // IfExpression ::= `if` Expression BlockExpression (`else` ( BlockExpression | IfExpression | IfLetExpression ) )?
-    IfExpression {
-        expression: Box<ASTNode>,
-        block_expression: Box<ASTNode>,
-        else_expression: Option<Box<ASTNode>>,
-    },
-
-    // IfLetExpression ::= `if` `let` Pattern `=` Scrutinee BlockExpression (`else` ( BlockExpression | IfExpression | IfLetExpression ) )?
-    IfLetExpression {
-        pattern: Box<ASTNode>,
-        scrutinee: Box<ASTNode>,
-        block_expression: Box<ASTNode>,
-        else_expression: Option<Box<ASTNode>>,
-    },
-
-    BlockExpression {
-        inner_attribute: Vec<ASTNode>,
-        statements: Option<Box<ASTNode>>,
-    },
-
-    //
-    IdentifierPattern {
-        ref_keyword: bool,
-        mut_keyword: bool,
-        identifier: String,
-        pattern_no_top_alt: Option<Box<ASTNode>>,
-    },
-
-    WildcardPattern {
-        wildcard: Box<ASTNode>,
-    },
-
-    RestPattern {
-        rest: Box<ASTNode>,
-    },
-
-    Statements {
-        statements: Vec<ASTNode>,
-    },
-
-    Statement {
-        statement: Option<Box<ASTNode>>,
-    },
-
-    LetStatement {
-        outer_attribute: Vec<ASTNode>,
-        rarity: Rarity,
-        pattern_no_top_alt: Box<ASTNode>,
-        type_expression: Option<Box<ASTNode>>,
-        expression: Option<Box<ASTNode>>,
-        block_expression: Option<Box<ASTNode>>,
-    },
+impl SyntaxNode for ASTNode {
+    fn kind_name(&self) -> &'static str {
+        ASTNode::kind_name(self)
+    }
+
+    fn leaf_fields(&self) -> Vec<(&'static str, String)> {
+        ASTNode::leaf_fields(self)
+    }
+
+    fn start_position(&self) -> Option<(usize, usize)> {
+        self.span.map(|span| span.start)
+    }
+}
+
+define_syntax_tree! {
+    #[derive(Debug, Clone, Eq, Serialize, Deserialize)]
+    pub struct ASTNode {
+        pub node_kind: ASTNodeKind,
+        pub span: Option<Span>, // 構文解析器が作ったノードだけが持つ
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum ASTNodeKind {
+        #[visit(visit_crate, fold_crate)]
+        Crate {
+            inner_attribute: Vec<ASTNode>,
+            item: Vec<ASTNode>,
+        },
+
+        #[visit(visit_factor, fold_factor)]
+        Factor {
+            token: Token,
+        },
+
+        #[visit(visit_literal, fold_literal)]
+        Literal {
+            literal: Literal,
+        },
+
+        #[visit(visit_binary_operator, fold_binary_operator)]
+        BinaryOperator {
+            operator: BinaryOperator,
+            left: Rc<ASTNode>,
+            right: Rc<ASTNode>,
+        },
+
+        #[visit(visit_unary_operator, fold_unary_operator)]
+        UnaryOperator {
+            operator: UnaryOperator,
+            expression: Rc<ASTNode>,
+        },
+
+        #[visit(visit_inner_attribute, fold_inner_attribute)]
+        InnerAttribute {
+            attribute: Rc<ASTNode>,
+        },
+
+        #[visit(visit_outer_attribute, fold_outer_attribute)]
+        OuterAttribute {
+            attribute: Rc<ASTNode>,
+        },
+
+        // Attribute ::= SimplePath AttributeInput? | `unsafe` `(` SimplePath AttributeInput? `)`
+        #[visit(visit_attribute, fold_attribute)]
+        Attribute {
+            is_unsafe: bool,
+            simple_path: Rc<ASTNode>,
+            attribute_input: Option<Rc<ASTNode>>, // DelimTokenTree | `=`の右辺のExpression
+        },
+
+        // SimplePath ::= `::`? SimplePathSegment (`::` SimplePathSegment)*
+        #[visit(visit_simple_path, fold_simple_path)]
+        SimplePath {
+            simple_path_segment: Rc<ASTNode>,
+            repeat_simple_path_segment: Vec<ASTNode>,
+        },
+
+        // DelimTokenTree ::= `(` TokenTree* `)` | `[` TokenTree* `]` | `{` TokenTree* `}`
+        #[visit(visit_delim_token_tree, fold_delim_token_tree)]
+        DelimTokenTree {
+            delimiter: LeftParenthesis,
+            token_tree: Vec<ASTNode>, // Factor | DelimTokenTree
+        },
+
+        #[visit(visit_visibility, fold_visibility)]
+        Visibility {},
+
+        // MacroInvocationとMacroInvocationSemiの両方
+        #[visit(visit_macro_invocation, fold_macro_invocation)]
+        MacroInvocation {
+            simple_path: Rc<ASTNode>,
+            delim_token_tree: Rc<ASTNode>,
+        },
+
+        // BuiltinMacroInvocation ::= BuiltinMacroName `!` `(` CallParams? `)`
+        #[visit(visit_builtin_macro_invocation, fold_builtin_macro_invocation)]
+        BuiltinMacroInvocation {
+            builtin_macro: BuiltinMacro,
+            call_params: Vec<ASTNode>,
+        },
+
+        #[visit(visit_macro_rules_definition, fold_macro_rules_definition)]
+        MacroRulesDefinition {
+            identifier: String,
+            delim_token_tree: Rc<ASTNode>,
+        },
+
+        #[visit(visit_function, fold_function)]
+        Function {
+            outer_attribute: Vec<ASTNode>,
+            function_qualifiers: Rc<ASTNode>,
+            identifier: String,
+            generic_params: Option<Rc<ASTNode>>,
+            function_parameters: Option<Rc<ASTNode>>,
+            function_return_type: Option<Rc<ASTNode>>,
+            where_clause: Option<Rc<ASTNode>>,
+            block_expression: Option<Rc<ASTNode>>, // When None is ;
+        },
+
+        // FunctionParameters ::= SelfParam `,`? | (SelfParam `,`)? FunctionParam (`,` FunctionParam)* `,`?
+        #[visit(visit_function_parameters, fold_function_parameters)]
+        FunctionParameters {
+            self_param: Option<Rc<ASTNode>>,
+            function_params: Vec<ASTNode>,
+        },
+
+        // SelfParam ::= OuterAttribute* ( ShorthandSelf | TypedSelf )
+        // ShorthandSelf ::= `&`? `mut`? `self`
+        // TypedSelf ::= `mut`? `self` `:` Type
+        #[visit(visit_self_param, fold_self_param)]
+        SelfParam {
+            outer_attribute: Vec<ASTNode>,
+            reference: bool,
+            mut_keyword: bool,
+            type_expression: Option<Rc<ASTNode>>,
+        },
+
+        // FunctionParam ::= OuterAttribute* PatternNoTopAlt `:` Type
+        #[visit(visit_function_param, fold_function_param)]
+        FunctionParam {
+            outer_attribute: Vec<ASTNode>,
+            pattern_no_top_alt: Rc<ASTNode>,
+            type_expression: Rc<ASTNode>,
+        },
+
+        // TypeAlias ::= `type` Identifier `=` Type `;`
+        #[visit(visit_type_alias, fold_type_alias)]
+        TypeAlias {
+            outer_attribute: Vec<ASTNode>,
+            identifier: String,
+            type_expression: Rc<ASTNode>,
+        },
+
+        // Struct ::= `struct` Identifier ( `{` StructFields? `}` | `;` )
+        #[visit(visit_struct, fold_struct)]
+        Struct {
+            outer_attribute: Vec<ASTNode>,
+            identifier: String,
+            struct_fields: Vec<ASTNode>,
+        },
+
+        // StructField ::= OuterAttribute* Visibility? Identifier `:` Type
+        #[visit(visit_struct_field, fold_struct_field)]
+        StructField {
+            outer_attribute: Vec<ASTNode>,
+            identifier: String,
+            type_expression: Rc<ASTNode>,
+        },
+
+        // Implementation ::= `impl` Type `{` AssociatedItem* `}`
+        //                  | `impl` TypePath `for` Type `{` AssociatedItem* `}`
+        #[visit(visit_implementation, fold_implementation)]
+        Implementation {
+            outer_attribute: Vec<ASTNode>,
+            trait_path: Option<Rc<ASTNode>>,
+            type_expression: Rc<ASTNode>,
+            associated_items: Vec<ASTNode>,
+        },
+
+        // TypePath ::= `::`? PathIdentSegment (`::` PathIdentSegment)*
+        #[visit(visit_type_path, fold_type_path)]
+        TypePath {
+            path_ident_segments: Vec<ASTNode>,
+        },
+
+        #[visit(visit_function_qualifiers, fold_function_qualifiers)]
+        FunctionQualifiers {
+            const_keyword: bool,
+            async_keyword: bool,
+            item_safety: Option<Rc<ASTNode>>,
+            extern_keyword: bool,
+            abi: Option<Rc<ASTNode>>,
+        },
+
+        #[visit(visit_expression, fold_expression)]
+        Expression {
+            expression: Rc<ASTNode>,
+        },
+
+        #[visit(visit_expression_without_block, fold_expression_without_block)]
+        ExpressionWithoutBlock {
+            outer_attribute: Vec<ASTNode>,
+            expression: Rc<ASTNode>,
+        },
+
+        #[visit(visit_expression_with_block, fold_expression_with_block)]
+        ExpressionWithBlock {
+            outer_attribute: Vec<ASTNode>,
+            expression_with_block: Rc<ASTNode>,
+        },
+
+        #[visit(visit_path_expression, fold_path_expression)]
+        PathExpression {
+            expression: Rc<ASTNode>,
+        },
+
+        // PathInExpression ::= `::`? PathExprSegment (`::` PathExprSegment)*
+        #[visit(visit_path_in_expression, fold_path_in_expression)]
+        PathInExpression {
+            path_expr_segment: Rc<ASTNode>,
+            repeat_path_expr_segment: Vec<ASTNode>,
+        },
+
+        // PathExprSegment ::= PathIdentSegment (`::` GenericArgs)?
+        #[visit(visit_path_expr_segment, fold_path_expr_segment)]
+        PathExprSegment {
+            path_ident_segment: Rc<ASTNode>,
+            generic_args: Option<Rc<ASTNode>>,
+        },
+
+        // StructExprStruct ::= PathInExpression `{` (StructExprFields | StructBase)? `}`
+        #[visit(visit_struct_expression, fold_struct_expression)]
+        StructExpression {
+            path_in_expression: Rc<ASTNode>,
+            struct_expr_fields: Vec<ASTNode>,
+            struct_base: Option<Rc<ASTNode>>, // `..` Expression
+        },
+
+        // StructExprField ::= OuterAttribute* ( Identifier | (Identifier | TUPLE_INDEX) `:` Expression )
+        #[visit(visit_struct_expr_field, fold_struct_expr_field)]
+        StructExprField {
+            outer_attribute: Vec<ASTNode>,
+            identifier: String,
+            expression: Option<Rc<ASTNode>>, // 省略した場合はNone
+        },
+
+        // CallExpression ::= Expression `(` CallParams? `)`
+        #[visit(visit_call_expression, fold_call_expression)]
+        CallExpression {
+            expression: Rc<ASTNode>,
+            call_params: Vec<ASTNode>,
+        },
+
+        // MethodCallExpression ::= Expression `.` PathExprSegment `(` CallParams? `)`
+        #[visit(visit_method_call_expression, fold_method_call_expression)]
+        MethodCallExpression {
+            expression: Rc<ASTNode>,
+            path_expr_segment: Rc<ASTNode>,
+            call_params: Vec<ASTNode>,
+        },
+
+        // FieldExpression ::= Expression `.` Identifier
+        #[visit(visit_field_expression, fold_field_expression)]
+        FieldExpression {
+            expression: Rc<ASTNode>,
+            identifier: String,
+        },
+
+        // AwaitExpression ::= Expression `.` `await`
+        #[visit(visit_await_expression, fold_await_expression)]
+        AwaitExpression {
+            expression: Rc<ASTNode>,
+        },
+
+        // IndexExpression ::= Expression `[` Expression `]`
+        #[visit(visit_index_expression, fold_index_expression)]
+        IndexExpression {
+            expression: Rc<ASTNode>,
+            index: Rc<ASTNode>,
+        },
+
+        // ReturnExpression ::= return (Expression)?
+        #[visit(visit_return_expression, fold_return_expression)]
+        ReturnExpression {
+            expression: Option<Rc<ASTNode>>,
+        },
+
+        // IfExpression ::= `if` (Expression | LetChain) BlockExpression (`else` ( BlockExpression | IfExpression | IfLetExpression ) )?
+        #[visit(visit_if_expression, fold_if_expression)]
+        IfExpression {
+            expression: Rc<ASTNode>,
+            block_expression: Rc<ASTNode>,
+            else_expression: Option<Rc<ASTNode>>,
+        },
+
+        // LetChain ::= LetChainCondition ( `&&` LetChainCondition )*
+        #[visit(visit_let_chain, fold_let_chain)]
+        LetChain {
+            let_chain_condition: Vec<ASTNode>,
+        },
+
+        // LetChainCondition ::= Expression | `let` Pattern `=` Scrutinee
+        #[visit(visit_let_chain_condition, fold_let_chain_condition)]
+        LetChainCondition {
+            pattern: Rc<ASTNode>,
+            scrutinee: Rc<ASTNode>,
+        },
+
+        // IfLetExpression ::= `if` `let` Pattern `=` Scrutinee BlockExpression (`else` ( BlockExpression | IfExpression | IfLetExpression ) )?
+        #[visit(visit_if_let_expression, fold_if_let_expression)]
+        IfLetExpression {
+            pattern: Rc<ASTNode>,
+            scrutinee: Rc<ASTNode>,
+            block_expression: Rc<ASTNode>,
+            else_expression: Option<Rc<ASTNode>>,
+        },
+
+        #[visit(visit_block_expression, fold_block_expression)]
+        BlockExpression {
+            inner_attribute: Vec<ASTNode>,
+            statements: Option<Rc<ASTNode>>,
+        },
+
+        // ConstBlockExpression ::= `const` BlockExpression
+        #[visit(visit_const_block_expression, fold_const_block_expression)]
+        ConstBlockExpression {
+            block_expression: Rc<ASTNode>,
+        },
+
+        // UnsafeBlockExpression ::= `unsafe` BlockExpression
+        #[visit(visit_unsafe_block_expression, fold_unsafe_block_expression)]
+        UnsafeBlockExpression {
+            block_expression: Rc<ASTNode>,
+        },
+
+        // AsyncBlockExpression ::= `async` `move`? BlockExpression
+        #[visit(visit_async_block_expression, fold_async_block_expression)]
+        AsyncBlockExpression {
+            move_keyword: bool,
+            block_expression: Rc<ASTNode>,
+        },
+
+        // Pattern ::= `|`? PatternNoTopAlt ( `|` PatternNoTopAlt )*
+        #[visit(visit_pattern, fold_pattern)]
+        Pattern {
+            pattern_no_top_alt: Vec<ASTNode>,
+        },
+
+        #[visit(visit_literal_pattern, fold_literal_pattern)]
+        LiteralPattern {
+            literal: Literal,
+        },
+
+        #[visit(visit_identifier_pattern, fold_identifier_pattern)]
+        IdentifierPattern {
+            ref_keyword: bool,
+            mut_keyword: bool,
+            identifier: String,
+            pattern_no_top_alt: Option<Rc<ASTNode>>,
+        },
+
+        #[visit(visit_wildcard_pattern, fold_wildcard_pattern)]
+        WildcardPattern {
+            wildcard: Rc<ASTNode>,
+        },
+
+        #[visit(visit_rest_pattern, fold_rest_pattern)]
+        RestPattern {
+            rest: Rc<ASTNode>,
+        },
+
+        #[visit(visit_statements, fold_statements)]
+        Statements {
+            statements: Vec<ASTNode>,
+        },
+
+        #[visit(visit_statement, fold_statement)]
+        Statement {
+            statement: Option<Rc<ASTNode>>,
+        },
+
+        #[visit(visit_let_statement, fold_let_statement)]
+        LetStatement {
+            outer_attribute: Vec<ASTNode>,
+            rarity: Rarity,
+            pattern_no_top_alt: Rc<ASTNode>,
+            type_expression: Option<Rc<ASTNode>>,
+            expression: Option<Rc<ASTNode>>,
+            block_expression: Option<Rc<ASTNode>>,
+        },
+    }
 }
@@ -1,20 +1,46 @@
+use crate::span::Span;
 use crate::token::*;
+use crate::walk::{define_syntax_tree, SyntaxNode};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::prelude::*;
+use std::rc::Rc;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub struct CSTNode {
-    pub node_kind: CSTNodeKind,
-    pub children: Vec<CSTNode>,
+// 木の形だけを比べ、範囲は比べない
+// 範囲は作るときに子から決まるので、位置を書き換えた木とも比べられるようにする
+impl PartialEq for CSTNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.node_kind == other.node_kind && self.children == other.children
+    }
 }
 
+impl Eq for CSTNode {}
+
 impl CSTNode {
+    // 範囲は子の範囲から決める トークンのノードはトークンの表記の長さとする
+    // 構文解析器は字句解析器が読んだ範囲をwith_spanで付け直す
     pub fn new(node_kind: CSTNodeKind, children: Vec<CSTNode>) -> Self {
-        Self {
+        let mut node = Self {
             node_kind,
             children,
+            span: None,
+        };
+        node.span = node.compute_span();
+        node
+    }
+
+    // トークンのノードの範囲を置き換える
+    pub fn with_span(mut self, span: Option<Span>) -> Self {
+        if span.is_some() {
+            self.span = span;
         }
+        self
+    }
+
+    // 作ったあとで子やnode_kindの中のノードを変えたときに範囲を決め直す
+    // トークンのノードには使わない
+    pub fn update_span(&mut self) {
+        self.span = self.compute_span();
     }
 
     pub fn write_cst(&self, file_name: &str) {
@@ -27,307 +53,1316 @@ impl CSTNode {
 
         file.write_all(data.as_bytes()).unwrap();
     }
+
+    // ソースコード上の順に並べたトークン
+    pub fn tokens(&self) -> Vec<Token> {
+        let mut tokens = vec![];
+        self.push_tokens(&mut tokens);
+        tokens
+    }
+
+    fn push_tokens(&self, tokens: &mut Vec<Token>) {
+        match &self.node_kind {
+            CSTNodeKind::Factor { token, .. } => tokens.push(token.clone()),
+            CSTNodeKind::Literal { literal, .. } => tokens.push(Token::Literal(literal.clone())),
+            CSTNodeKind::LiteralPattern { minus, literal, .. } => {
+                if let Some(minus) = minus {
+                    minus.push_tokens(tokens);
+                }
+                tokens.push(Token::Literal(literal.clone()));
+            }
+            // 二項演算子は左辺と右辺の間、単項演算子は被演算子の前
+            CSTNodeKind::Operator { token, .. } => match self.children.as_slice() {
+                [left, right] => {
+                    left.push_tokens(tokens);
+                    tokens.push(token.clone());
+                    right.push_tokens(tokens);
+                }
+                children => {
+                    tokens.push(token.clone());
+                    for child in children {
+                        child.push_tokens(tokens);
+                    }
+                }
+            },
+            _ => {
+                for child in self.nodes_in_source_order() {
+                    child.push_tokens(tokens);
+                }
+            }
+        }
+    }
+
+    // 最初のトークンから最後のトークンまでの範囲
+    // トークンを含まないノードはNone
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    // 子はもう範囲を持っているので、最初と最後の子の範囲をつなぐだけでよい
+    fn compute_span(&self) -> Option<Span> {
+        let token_span = |token: &Token, row: usize, column: usize| {
+            Some(Span::of_text((row, column), &token.to_string()))
+        };
+        match &self.node_kind {
+            CSTNodeKind::Factor { token, row, column }
+            | CSTNodeKind::Operator { token, row, column }
+                if self.children.is_empty() =>
+            {
+                token_span(token, *row, *column)
+            }
+            CSTNodeKind::Literal {
+                literal,
+                row,
+                column,
+            } => token_span(&Token::Literal(literal.clone()), *row, *column),
+            CSTNodeKind::LiteralPattern {
+                minus,
+                literal,
+                row,
+                column,
+            } => {
+                let span = token_span(&Token::Literal(literal.clone()), *row, *column)?;
+                match minus.as_ref().and_then(|minus| minus.span) {
+                    Some(minus) => Some(minus.to(span)),
+                    None => Some(span),
+                }
+            }
+            // 単項演算子は被演算子の前にある
+            CSTNodeKind::Operator { token, row, column } => {
+                let last = self.children.last()?.span?;
+                let first = if self.children.len() == 1 {
+                    token_span(token, *row, *column)?
+                } else {
+                    self.children.first()?.span?
+                };
+                Some(first.to(last))
+            }
+            _ => cover(&self.nodes_in_source_order()),
+        }
+    }
+
+    // node_kindの中のノードとchildrenをソースコード上の順に並べる
+    // Factor、Literal、Operatorなどトークンそのもののノードは含めない
+    pub(crate) fn nodes_in_source_order(&self) -> Vec<&CSTNode> {
+        let mut nodes = vec![];
+        match &self.node_kind {
+            CSTNodeKind::Crate {
+                inner_attributes,
+                items,
+            } => {
+                nodes.extend(inner_attributes);
+                nodes.extend(items);
+            }
+            CSTNodeKind::Factor { .. }
+            | CSTNodeKind::Operator { .. }
+            | CSTNodeKind::Literal { .. }
+            | CSTNodeKind::LiteralPattern { .. }
+            | CSTNodeKind::Item
+            | CSTNodeKind::Statements => (),
+            CSTNodeKind::Error { tokens } => nodes.extend(tokens),
+            CSTNodeKind::InnerAttribute {
+                pound,
+                exclamation,
+                left_brackets,
+                attribute,
+                right_brackets,
+            } => nodes.extend(
+                [pound, exclamation, left_brackets, attribute, right_brackets].map(Rc::as_ref),
+            ),
+            CSTNodeKind::OuterAttribute {
+                pound,
+                left_brackets,
+                attribute,
+                right_brackets,
+            } => nodes.extend([pound, left_brackets, attribute, right_brackets].map(Rc::as_ref)),
+            CSTNodeKind::Attribute {
+                unsafe_keyword,
+                left_parenthesis,
+                simple_path,
+                attribute_input,
+                right_parenthesis,
+            } => {
+                nodes.extend(unsafe_keyword.as_deref());
+                nodes.extend(left_parenthesis.as_deref());
+                nodes.push(simple_path);
+                nodes.extend(attribute_input.as_deref());
+                nodes.extend(right_parenthesis.as_deref());
+            }
+            CSTNodeKind::AttributeInput { equal, expression } => {
+                nodes.extend(equal.as_deref());
+                nodes.push(expression);
+            }
+            CSTNodeKind::SimplePath {
+                path_separater,
+                simple_path_segment,
+                repeat_simple_path_segment,
+            } => {
+                nodes.extend(path_separater.as_deref());
+                nodes.push(simple_path_segment);
+                extend_pairs(&mut nodes, repeat_simple_path_segment);
+            }
+            CSTNodeKind::DelimTokenTree {
+                left_parenthesis,
+                token_tree,
+                right_parenthesis,
+            } => {
+                nodes.push(left_parenthesis);
+                nodes.extend(token_tree);
+                nodes.push(right_parenthesis);
+            }
+            CSTNodeKind::Visibility { pub_keyword } => nodes.push(pub_keyword),
+            CSTNodeKind::MacroInvocation {
+                simple_path,
+                not,
+                delim_token_tree,
+            } => nodes.extend([simple_path, not, delim_token_tree].map(Rc::as_ref)),
+            CSTNodeKind::MacroInvocationSemi {
+                simple_path,
+                not,
+                delim_token_tree,
+                semicolon,
+            } => {
+                nodes.extend([simple_path, not, delim_token_tree].map(Rc::as_ref));
+                nodes.extend(semicolon.as_deref());
+            }
+            CSTNodeKind::BuiltinMacroInvocation {
+                identifier,
+                not,
+                left_parenthesis,
+                call_params,
+                right_parenthesis,
+            } => {
+                nodes.extend([identifier, not, left_parenthesis].map(Rc::as_ref));
+                nodes.extend(call_params.as_deref());
+                nodes.push(right_parenthesis);
+            }
+            CSTNodeKind::MacroRulesDefinition {
+                macro_rules_keyword,
+                not,
+                identifier,
+                delim_token_tree,
+                semicolon,
+            } => {
+                nodes.extend(
+                    [macro_rules_keyword, not, identifier, delim_token_tree].map(Rc::as_ref),
+                );
+                nodes.extend(semicolon.as_deref());
+            }
+            CSTNodeKind::Function {
+                outer_attribute,
+                visibility,
+                function_qualifiers,
+                fn_keyword,
+                identifier,
+                generic_params,
+                left_parenthesis,
+                function_parameters,
+                right_parenthesis,
+                function_return_type,
+                where_clause,
+                block_expression_or_semicolon,
+            } => {
+                nodes.extend(outer_attribute);
+                nodes.extend(visibility.as_deref());
+                nodes.extend([function_qualifiers, fn_keyword, identifier].map(Rc::as_ref));
+                nodes.extend(generic_params.as_deref());
+                nodes.push(left_parenthesis);
+                nodes.extend(function_parameters.as_deref());
+                nodes.push(right_parenthesis);
+                nodes.extend(function_return_type.as_deref());
+                nodes.extend(where_clause.as_deref());
+                nodes.push(block_expression_or_semicolon);
+            }
+            CSTNodeKind::GenericParams {
+                less_than,
+                greater_than,
+            } => nodes.extend([less_than, greater_than].map(Rc::as_ref)),
+            CSTNodeKind::FunctionQualifiers {
+                const_keyword,
+                async_keyword,
+                item_safety,
+                extern_keyword,
+                abi,
+            } => {
+                for node in [
+                    const_keyword,
+                    async_keyword,
+                    item_safety,
+                    extern_keyword,
+                    abi,
+                ] {
+                    nodes.extend(node.as_deref());
+                }
+            }
+            CSTNodeKind::FunctionParam1 { self_param, comma } => {
+                nodes.push(self_param);
+                nodes.extend(comma.as_deref());
+            }
+            CSTNodeKind::FunctionParam2 {
+                self_param,
+                function_param,
+                function_param_repeat,
+                comma,
+            } => {
+                if let Some((self_param, comma)) = self_param {
+                    nodes.extend([self_param, comma].map(Rc::as_ref));
+                }
+                nodes.push(function_param);
+                extend_pairs(&mut nodes, function_param_repeat);
+                nodes.extend(comma.as_deref());
+            }
+            CSTNodeKind::ShorthandSelf {
+                outer_attribute,
+                and,
+                mut_keyword,
+                self_keyword,
+            } => {
+                nodes.extend(outer_attribute);
+                nodes.extend(and.as_deref());
+                nodes.extend(mut_keyword.as_deref());
+                nodes.push(self_keyword);
+            }
+            CSTNodeKind::TypedSelf {
+                outer_attribute,
+                mut_keyword,
+                self_keyword,
+                colon,
+                type_expression,
+            } => {
+                nodes.extend(outer_attribute);
+                nodes.extend(mut_keyword.as_deref());
+                nodes.extend([self_keyword, colon, type_expression].map(Rc::as_ref));
+            }
+            CSTNodeKind::FunctionParam {
+                outer_attribute,
+                pattern_no_top_alt,
+                colon,
+                type_expression,
+            } => {
+                nodes.extend(outer_attribute);
+                nodes.extend([pattern_no_top_alt, colon, type_expression].map(Rc::as_ref));
+            }
+            CSTNodeKind::FunctionReturnType {
+                right_allow,
+                type_expression,
+            } => nodes.extend([right_allow, type_expression].map(Rc::as_ref)),
+            CSTNodeKind::TypeAlias {
+                outer_attribute,
+                visibility,
+                type_keyword,
+                identifier,
+                equal,
+                type_expression,
+                semicolon,
+            } => {
+                nodes.extend(outer_attribute);
+                nodes.extend(visibility.as_deref());
+                nodes.extend(
+                    [type_keyword, identifier, equal, type_expression, semicolon].map(Rc::as_ref),
+                );
+            }
+            CSTNodeKind::Struct {
+                outer_attribute,
+                visibility,
+                struct_keyword,
+                identifier,
+                left_brace,
+                struct_fields,
+                right_brace_or_semicolon,
+            } => {
+                nodes.extend(outer_attribute);
+                nodes.extend(visibility.as_deref());
+                nodes.extend([struct_keyword, identifier].map(Rc::as_ref));
+                nodes.extend(left_brace.as_deref());
+                nodes.extend(struct_fields.as_deref());
+                nodes.push(right_brace_or_semicolon);
+            }
+            CSTNodeKind::StructFields {
+                struct_field,
+                repeat_struct_field,
+                comma,
+            } => {
+                nodes.push(struct_field);
+                extend_pairs(&mut nodes, repeat_struct_field);
+                nodes.extend(comma.as_deref());
+            }
+            CSTNodeKind::StructField {
+                outer_attribute,
+                visibility,
+                identifier,
+                colon,
+                type_expression,
+            } => {
+                nodes.extend(outer_attribute);
+                nodes.extend(visibility.as_deref());
+                nodes.extend([identifier, colon, type_expression].map(Rc::as_ref));
+            }
+            CSTNodeKind::Implementation {
+                outer_attribute,
+                visibility,
+                impl_keyword,
+                trait_path,
+                type_expression,
+                left_brace,
+                associated_items,
+                right_brace,
+            } => {
+                nodes.extend(outer_attribute);
+                nodes.extend(visibility.as_deref());
+                nodes.push(impl_keyword);
+                if let Some((trait_path, for_keyword)) = trait_path {
+                    nodes.extend([trait_path, for_keyword].map(Rc::as_ref));
+                }
+                nodes.extend([type_expression, left_brace].map(Rc::as_ref));
+                nodes.extend(associated_items);
+                nodes.push(right_brace);
+            }
+            CSTNodeKind::TypePath {
+                path_separater,
+                path_ident_segment,
+                repeat_path_ident_segment,
+            } => {
+                nodes.extend(path_separater.as_deref());
+                nodes.push(path_ident_segment);
+                extend_pairs(&mut nodes, repeat_path_ident_segment);
+            }
+            CSTNodeKind::Expression { expression }
+            | CSTNodeKind::LiteralExpression {
+                literal: expression,
+            }
+            | CSTNodeKind::PathExpression {
+                path_in_expression: expression,
+            }
+            | CSTNodeKind::StructExpression { expression }
+            | CSTNodeKind::Statement {
+                statement: expression,
+            }
+            | CSTNodeKind::WildcardPattern {
+                wildcard: expression,
+            }
+            | CSTNodeKind::RestPattern { rest: expression } => nodes.push(expression),
+            CSTNodeKind::ExpressionWithoutBlock {
+                outer_attribute,
+                expression,
+            }
+            | CSTNodeKind::ExpressionWithBlock {
+                outer_attribute,
+                expression_with_block: expression,
+            } => {
+                nodes.extend(outer_attribute);
+                nodes.push(expression);
+            }
+            CSTNodeKind::PathInExpression {
+                path_separater,
+                path_expr_segment,
+                repeat_path_expr_segment,
+            } => {
+                nodes.extend(path_separater.as_deref());
+                nodes.push(path_expr_segment);
+                extend_pairs(&mut nodes, repeat_path_expr_segment);
+            }
+            CSTNodeKind::PathExprSegment {
+                path_ident_segment,
+                generic_args,
+            } => {
+                nodes.push(path_ident_segment);
+                if let Some((path_separater, generic_args)) = generic_args {
+                    nodes.extend([path_separater, generic_args].map(Rc::as_ref));
+                }
+            }
+            CSTNodeKind::GroupedExpression {
+                left_parenthesis,
+                expression,
+                right_parenthesis,
+            } => nodes.extend([left_parenthesis, expression, right_parenthesis].map(Rc::as_ref)),
+            CSTNodeKind::StructExprStruct {
+                path_in_expression,
+                left_brace,
+                expression,
+                right_brace,
+            } => {
+                nodes.extend([path_in_expression, left_brace].map(Rc::as_ref));
+                nodes.extend(expression.as_deref());
+                nodes.push(right_brace);
+            }
+            CSTNodeKind::StructExprFields {
+                struct_expr_filed,
+                struct_expr_filed_repeat,
+                comma,
+                struct_base,
+            } => {
+                nodes.push(struct_expr_filed);
+                extend_pairs(&mut nodes, struct_expr_filed_repeat);
+                nodes.extend(comma.as_deref());
+                nodes.extend(struct_base.as_deref());
+            }
+            CSTNodeKind::StructExprField1 {
+                outer_attribute,
+                identifier,
+            } => {
+                nodes.extend(outer_attribute);
+                nodes.push(identifier);
+            }
+            CSTNodeKind::StructExprField2 {
+                outer_attribute,
+                identifier_or_tuple,
+                colon,
+                expression,
+            } => {
+                nodes.extend(outer_attribute);
+                nodes.extend([identifier_or_tuple, colon, expression].map(Rc::as_ref));
+            }
+            CSTNodeKind::StructBase { dotdot, expression } => {
+                nodes.extend([dotdot, expression].map(Rc::as_ref))
+            }
+            CSTNodeKind::CallExpression {
+                expression,
+                left_parenthesis,
+                call_params,
+                right_parenthesis,
+            } => {
+                nodes.extend([expression, left_parenthesis].map(Rc::as_ref));
+                nodes.extend(call_params.as_deref());
+                nodes.push(right_parenthesis);
+            }
+            CSTNodeKind::CallParams {
+                expression,
+                comma_and_expression,
+                comma,
+            } => {
+                nodes.push(expression);
+                extend_pairs(&mut nodes, comma_and_expression);
+                nodes.extend(comma.as_deref());
+            }
+            CSTNodeKind::MethodCallExpression {
+                expression,
+                dot,
+                path_expr_segment,
+                left_parenthesis,
+                call_params,
+                right_parenthesis,
+            } => {
+                nodes
+                    .extend([expression, dot, path_expr_segment, left_parenthesis].map(Rc::as_ref));
+                nodes.extend(call_params.as_deref());
+                nodes.push(right_parenthesis);
+            }
+            CSTNodeKind::FieldExpression {
+                expression,
+                dot,
+                identifier,
+            } => nodes.extend([expression, dot, identifier].map(Rc::as_ref)),
+            CSTNodeKind::AwaitExpression {
+                expression,
+                dot,
+                await_keyword,
+            } => nodes.extend([expression, dot, await_keyword].map(Rc::as_ref)),
+            CSTNodeKind::IndexExpression {
+                expression,
+                left_brackets,
+                index,
+                right_brackets,
+            } => nodes.extend([expression, left_brackets, index, right_brackets].map(Rc::as_ref)),
+            CSTNodeKind::ReturnExpression {
+                return_keyword,
+                expression,
+            } => {
+                nodes.push(return_keyword);
+                nodes.extend(expression.as_deref());
+            }
+            CSTNodeKind::IfExpression {
+                if_keyword,
+                expression,
+                block_expression,
+                else_keyword,
+                else_expression,
+            } => {
+                nodes.extend([if_keyword, expression, block_expression].map(Rc::as_ref));
+                nodes.extend(else_keyword.as_deref());
+                nodes.extend(else_expression.as_deref());
+            }
+            CSTNodeKind::LetChain {
+                let_chain_condition,
+                repeat_let_chain_condition,
+            } => {
+                nodes.push(let_chain_condition);
+                extend_pairs(&mut nodes, repeat_let_chain_condition);
+            }
+            CSTNodeKind::LetChainCondition {
+                let_keyword,
+                pattern,
+                equal,
+                scrutinee,
+            } => nodes.extend([let_keyword, pattern, equal, scrutinee].map(Rc::as_ref)),
+            CSTNodeKind::IfLetExpression {
+                if_keyword,
+                let_keyword,
+                pattern,
+                equal,
+                scrutinee,
+                block_expression,
+                else_keyword,
+                else_expression,
+            } => {
+                nodes.extend(
+                    [
+                        if_keyword,
+                        let_keyword,
+                        pattern,
+                        equal,
+                        scrutinee,
+                        block_expression,
+                    ]
+                    .map(Rc::as_ref),
+                );
+                nodes.extend(else_keyword.as_deref());
+                nodes.extend(else_expression.as_deref());
+            }
+            CSTNodeKind::BlockExpression {
+                left_brace,
+                inner_attribute,
+                statements,
+                right_brace,
+            } => {
+                nodes.push(left_brace);
+                nodes.extend(inner_attribute);
+                nodes.extend(statements.as_deref());
+                nodes.push(right_brace);
+            }
+            CSTNodeKind::ConstBlockExpression {
+                const_keyword: keyword,
+                block_expression,
+            }
+            | CSTNodeKind::UnsafeBlockExpression {
+                unsafe_keyword: keyword,
+                block_expression,
+            } => nodes.extend([keyword, block_expression].map(Rc::as_ref)),
+            CSTNodeKind::AsyncBlockExpression {
+                async_keyword,
+                move_keyword,
+                block_expression,
+            } => {
+                nodes.push(async_keyword);
+                nodes.extend(move_keyword.as_deref());
+                nodes.push(block_expression);
+            }
+            CSTNodeKind::LetStatement {
+                outer_attribute,
+                rarity,
+                pattern_no_top_alt,
+                colon,
+                type_expression,
+                equal,
+                expression,
+                else_keyword,
+                block_expression,
+                semicolon,
+            } => {
+                nodes.extend(outer_attribute);
+                nodes.extend([rarity, pattern_no_top_alt].map(Rc::as_ref));
+                for node in [
+                    colon,
+                    type_expression,
+                    equal,
+                    expression,
+                    else_keyword,
+                    block_expression,
+                ] {
+                    nodes.extend(node.as_deref());
+                }
+                nodes.push(semicolon);
+            }
+            CSTNodeKind::Pattern {
+                or_token,
+                pattern_no_top_alt,
+                repeat_pattern_no_top_alt,
+            } => {
+                nodes.extend(or_token.as_deref());
+                nodes.push(pattern_no_top_alt);
+                extend_pairs(&mut nodes, repeat_pattern_no_top_alt);
+            }
+            CSTNodeKind::IdentifierPattern {
+                ref_keyword,
+                mut_keyword,
+                identifier,
+                at_symbol,
+                pattern_no_top_alt,
+            } => {
+                nodes.extend(ref_keyword.as_deref());
+                nodes.extend(mut_keyword.as_deref());
+                nodes.push(identifier);
+                nodes.extend(at_symbol.as_deref());
+                nodes.extend(pattern_no_top_alt.as_deref());
+            }
+        }
+
+        // StatementsとExpressionStatementの`;`はchildrenにある
+        nodes.extend(&self.children);
+        nodes
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub enum CSTNodeKind {
-    Crate {
-        inner_attributes: Vec<CSTNode>,
-        items: Vec<CSTNode>,
-    },
-
-    Factor {
-        token: Token,
-        row: usize,
-        column: usize,
-    },
-
-    Operator {
-        token: Token,
-        row: usize,
-        column: usize,
-    },
-
-    Literal {
-        literal: Literal,
-        row: usize,
-        column: usize,
-    },
-
-    // InnerAttribute ::= `#` `!` `[` Attribute `]`
-    InnerAttribute {
-        pound: Box<CSTNode>,
-        exclamation: Box<CSTNode>,
-        left_brackets: Box<CSTNode>,
-        attribute: Box<CSTNode>,
-        right_brackets: Box<CSTNode>,
-    },
-
-    // OuterAttribute ::= `#` `[` Attribute `]`
-    OuterAttribute {
-        pound: Box<CSTNode>,
-        left_brackets: Box<CSTNode>,
-        attribute: Box<CSTNode>,
-        right_brackets: Box<CSTNode>,
-    },
-
-    // Attribute ::= SimplePath AttributeInput?  | `unsafe` `(` SimplePath AttributeInput? `)`
-    Attribute,
-
-    // Visibility ::= `pub`
-    //              | `pub` `(` `crate` `)`
-    //              | `pub` `(` `self` `)`
-    //              | `pub` `(` `super` `)`
-    //              | `pub` `(` `in` SimplePath `)`
-    Visibility {
-        pub_keyword: Box<CSTNode>,
-    },
-
-    // Item ::= OuterAttribute* VisItem | MacroItem
-    Item,
-
-    // Function ::= FunctionQualifiers `fn` Identifier GenericParams?
-    //             `(` FunctionParameters? `)`
-    //             FunctionReturnType? WhereClause?
-    //             ( BlockExpression | `;` )
-    Function {
-        function_qualifiers: Box<CSTNode>,
-        fn_keyword: Box<CSTNode>,
-        identifier: Box<CSTNode>,
-        generic_params: Option<Box<CSTNode>>,
-        left_parenthesis: Box<CSTNode>,
-        function_parameters: Option<Box<CSTNode>>,
-        right_parenthesis: Box<CSTNode>,
-        function_return_type: Option<Box<CSTNode>>,
-        where_clause: Option<Box<CSTNode>>,
-        block_expression_or_semicolon: Box<CSTNode>,
-    },
-
-    // FunctionQualifiers ::= `const`? `async`? ItemSafety? (`extern` Abi?)?
-    FunctionQualifiers {
-        const_keyword: Option<Box<CSTNode>>,
-        async_keyword: Option<Box<CSTNode>>,
-        item_safety: Option<Box<CSTNode>>,
-        extern_keyword: Option<Box<CSTNode>>,
-        abi: Option<Box<CSTNode>>,
-    },
-
-    // FunctionParameters ::= SelfParam `,`?
-    FunctionParam1 {
-        self_param: Box<CSTNode>,
-        comma: Option<Box<CSTNode>>,
-    },
-
-    // FunctionParameters ::= (SelfParam `,`)? FunctionParam (`,` FunctionParam)* `,`?
-    FunctionParam2 {
-        self_param: Option<(Box<CSTNode>, Box<CSTNode>)>,
-        function_param: Box<CSTNode>,
-        function_param_repeat: Vec<(CSTNode, CSTNode)>,
-        comma: Option<Box<CSTNode>>,
-    },
-
-    // Expression ::= ExpressionWithoutBlock | ExpressionWithBlock
-    Expression {
-        expression: Box<CSTNode>,
-    },
-
-    // ExpressionWithoutBlock ::= OuterAttribute*
-    //                            (
-    //                              LiteralExpression | PathExpression | OperatorExpression | GroupedExpression
-    //                            | ArrayExpression | AwaitExpression | IndexExpression | TupleExpression
-    //                            | TupleIndexingExpression | StructExpression | CallExpression | MethodCallExpression
-    //                            | FieldExpression | ClosureExpression | AsyncBlockExpression | ContinueExpression
-    //                            | BreakExpression | RangeExpression | ReturnExpression | UnderscoreExpression | MacroInvocation
-    //                            )
-    ExpressionWithoutBlock {
-        outer_attribute: Vec<CSTNode>,
-        expression: Box<CSTNode>,
-    },
-
-    // ExpressionWithoutBlock ::= OuterAttribute*
-    //                           (
-    //                                LiteralExpression | PathExpression | OperatorExpression | GroupedExpression | ArrayExpression
-    //                              | AwaitExpression | IndexExpression | TupleExpression | TupleIndexingExpression | StructExpression
-    //                              | CallExpression | MethodCallExpression | FieldExpression | ClosureExpression | AsyncBlockExpression
-    //                              | ContinueExpression | BreakExpression | RangeExpression | ReturnExpression | UnderscoreExpression | MacroInvocation
-    //                           )
-    ExpressionWithBlock {
-        outer_attribute: Vec<CSTNode>,
-        expression_with_block: Box<CSTNode>,
-    },
-
-    // LiteralExpression ::=  CharacterLiteral
-    //                      | StringLiteral
-    //                      | RawStringLiteral
-    //                      | ByteLiteral
-    //                      | ByteStringLiteral
-    //                      | RawByteStringLiteral
-    //                      | CStringLiteral
-    //                      | RawCStringLiteral
-    //                      | IntgerLiteral
-    //                      | FloatLiteral
-    //                      | true
-    //                      | false
-    LiteralExpression {
-        literal: Box<CSTNode>,
-    },
-
-    // PathExpression ::= PathInExpression | QualifiedPathInExpression
-    PathExpression {
-        path_in_expression: Box<CSTNode>,
-    },
-
-    // PathInExpression ::= `::`? PathExprSegment (`::` PathExprSegment)*
-    PathInExpression {
-        path_separater: Option<Box<CSTNode>>,
-        path_expr_segment: Box<CSTNode>,
-        repeat_path_expr_segment: Vec<(CSTNode, CSTNode)>,
-    },
-
-    // PathExprSegment ::= PathIdentSegment (`::` GenericArgs)?
-    PathExprSegment {
-        path_ident_segment: Box<CSTNode>,
-        generic_args: Option<(Box<CSTNode>, Box<CSTNode>)>,
-    },
-
-    // GroupedExpression ::= `(` Expression `)`
-    GroupedExpression {
-        left_parenthesis: Box<CSTNode>,
-        expression: Box<CSTNode>,
-        right_parenthesis: Box<CSTNode>,
-    },
-
-    // StructExpression ::= StructExprStruct | StructExprTuple | StructExprUnit
-    StructExpression {
-        expression: Box<CSTNode>,
-    },
-
-    // StructExprStruct ::= PathInExpression `{` (StructExprFields | StructBase)? `}`
-    StructExprStruct {
-        path_in_expression: Box<CSTNode>,
-        left_brace: Box<CSTNode>,
-        expression: Option<Box<CSTNode>>,
-        right_brace: Box<CSTNode>,
-    },
-
-    // StructExprFields
-    StructExprFields {
-        struct_expr_filed: Box<CSTNode>,
-        struct_expr_filed_repeat: Vec<(CSTNode, CSTNode)>,
-        comma: Option<Box<CSTNode>>,
-        struct_base: Option<Box<CSTNode>>,
-    },
-
-    // StructExprField  ::= OuterAttribute* ( Identifier | (Identifier |TUPLE_INDEX) `:` Expression )
-    StructExprField1 {
-        outer_attribute: Vec<CSTNode>,
-        identifier: Box<CSTNode>,
-    },
-    StructExprField2 {
-        outer_attribute: Vec<CSTNode>,
-        identifier_or_tuple: Box<CSTNode>,
-        colon: Box<CSTNode>,
-        expression: Box<CSTNode>,
-    },
-
-    // StructBase ::= `..` Expression
-    StructBase {
-        dotdot: Box<CSTNode>,
-        expression: Box<CSTNode>,
-    },
-
-    //  CallExpression ::= Expression `(` CallParams? `)`
-    CallExpression {
-        expression: Box<CSTNode>,
-        left_parenthesis: Box<CSTNode>,
-        call_params: Option<Box<CSTNode>>,
-        right_parenthesis: Box<CSTNode>,
-    },
-
-    // CallParams     ::= Expression ( `,` Expression )* `,`?
-    CallParams {
-        expression: Box<CSTNode>,
-        comma_and_expression: Vec<(CSTNode, CSTNode)>,
-        comma: Option<Box<CSTNode>>,
-    },
-
-    // ReturnExpression ::= return (Expression)?
-    ReturnExpression {
-        return_keyword: Box<CSTNode>,
-        expression: Option<Box<CSTNode>>,
-    },
-
-    // IfExpression ::= `if` Expression BlockExpression (`else` ( BlockExpression | IfExpression | IfLetExpression ) )?
-    IfExpression {
-        if_keyword: Box<CSTNode>,
-        expression: Box<CSTNode>,
-        block_expression: Box<CSTNode>,
-        else_keyword: Option<Box<CSTNode>>,
-        else_expression: Option<Box<CSTNode>>,
-    },
-
-    // IfLetExpression ::= `if` `let` Pattern `=` Scrutinee BlockExpression (`else` ( BlockExpression | IfExpression | IfLetExpression ) )?
-    IfLetExpression {
-        if_keyword: Box<CSTNode>,
-        let_keyword: Box<CSTNode>,
-        pattern: Box<CSTNode>,
-        equal: Box<CSTNode>,
-        scrutinee: Box<CSTNode>,
-        block_expression: Box<CSTNode>,
-        else_keyword: Option<Box<CSTNode>>,
-        else_expression: Option<Box<CSTNode>>,
-    },
-
-    // Statements ::= Statement+ | Statement+ ExpressionWithoutBlock | ExpressionWithoutBlock
-    Statements,
-
-    // Statement ::= `;` | Item | LetStatement | ExpressionStatement | MacroInvocationSemi
-    Statement {
-        statement: Box<CSTNode>,
-    },
-
-    BlockExpression {
-        left_brace: Box<CSTNode>,
-        inner_attribute: Vec<CSTNode>,
-        statements: Option<Box<CSTNode>>,
-        right_brace: Box<CSTNode>,
-    },
-
-    // LetStatement ::= OuterAttribute* (`ur` | `sr` | `nr` | `let`)
-    //                  PatternNoTopAlt ( `:` Type )?
-    //                  (`=` Expression ( `else` BlockExpression)? )? `;`
-    LetStatement {
-        outer_attribute: Vec<CSTNode>,
-        rarity: Box<CSTNode>,
-        pattern_no_top_alt: Box<CSTNode>,
-        colon: Option<Box<CSTNode>>,
-        type_expression: Option<Box<CSTNode>>,
-        equal: Option<Box<CSTNode>>,
-        expression: Option<Box<CSTNode>>,
-        else_keyword: Option<Box<CSTNode>>,
-        block_expression: Option<Box<CSTNode>>,
-        semicolon: Box<CSTNode>,
-    },
-
-    // Pattern
-    LiteralPattern {
-        literal: Literal,
-    },
-
-    //
-    IdentifierPattern {
-        ref_keyword: Option<Box<CSTNode>>,
-        mut_keyword: Option<Box<CSTNode>>,
-        identifier: Box<CSTNode>,
-        at_symbol: Option<Box<CSTNode>>,
-        pattern_no_top_alt: Option<Box<CSTNode>>,
-    },
-
-    WildcardPattern {
-        wildcard: Box<CSTNode>,
-    },
-
-    RestPattern {
-        rest: Box<CSTNode>,
-    },
+impl SyntaxNode for CSTNode {
+    fn kind_name(&self) -> &'static str {
+        CSTNode::kind_name(self)
+    }
+
+    fn leaf_fields(&self) -> Vec<(&'static str, String)> {
+        CSTNode::leaf_fields(self)
+    }
+
+    // トークンを持たないノードの位置は決めず、使う側で最初の子孫のトークンの位置にする
+    fn start_position(&self) -> Option<(usize, usize)> {
+        match &self.node_kind {
+            CSTNodeKind::Factor { row, column, .. }
+            | CSTNodeKind::Operator { row, column, .. }
+            | CSTNodeKind::Literal { row, column, .. } => Some((*row, *column)),
+            _ => None,
+        }
+    }
+}
+
+// 最初に範囲を持つノードから最後に範囲を持つノードまで
+pub(crate) fn cover(nodes: &[&CSTNode]) -> Option<Span> {
+    let first = nodes.iter().find_map(|node| node.span)?;
+    let last = nodes.iter().rev().find_map(|node| node.span)?;
+    Some(first.to(last))
+}
+
+fn extend_pairs<'a>(nodes: &mut Vec<&'a CSTNode>, pairs: &'a [(CSTNode, CSTNode)]) {
+    for (first, second) in pairs {
+        nodes.push(first);
+        nodes.push(second);
+    }
+}
+
+define_syntax_tree! {
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct CSTNode {
+        pub node_kind: CSTNodeKind,
+        pub children: Vec<CSTNode>,
+        pub span: Option<Span>, // 最初のトークンから最後のトークンまで
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+    pub enum CSTNodeKind {
+        #[visit(visit_crate, fold_crate)]
+        Crate {
+            inner_attributes: Vec<CSTNode>,
+            items: Vec<CSTNode>,
+        },
+
+        #[visit(visit_factor, fold_factor)]
+        Factor {
+            token: Token,
+            row: usize,
+            column: usize,
+        },
+
+        #[visit(visit_operator, fold_operator)]
+        Operator {
+            token: Token,
+            row: usize,
+            column: usize,
+        },
+
+        #[visit(visit_literal, fold_literal)]
+        Literal {
+            literal: Literal,
+            row: usize,
+            column: usize,
+        },
+
+        // 構文エラーから復帰する際に読み飛ばしたトークン
+        #[visit(visit_error, fold_error)]
+        Error {
+            tokens: Vec<CSTNode>,
+        },
+
+        // InnerAttribute ::= `#` `!` `[` Attribute `]`
+        #[visit(visit_inner_attribute, fold_inner_attribute)]
+        InnerAttribute {
+            pound: Rc<CSTNode>,
+            exclamation: Rc<CSTNode>,
+            left_brackets: Rc<CSTNode>,
+            attribute: Rc<CSTNode>,
+            right_brackets: Rc<CSTNode>,
+        },
+
+        // OuterAttribute ::= `#` `[` Attribute `]`
+        #[visit(visit_outer_attribute, fold_outer_attribute)]
+        OuterAttribute {
+            pound: Rc<CSTNode>,
+            left_brackets: Rc<CSTNode>,
+            attribute: Rc<CSTNode>,
+            right_brackets: Rc<CSTNode>,
+        },
+
+        // Attribute ::= SimplePath AttributeInput?  | `unsafe` `(` SimplePath AttributeInput? `)`
+        #[visit(visit_attribute, fold_attribute)]
+        Attribute {
+            unsafe_keyword: Option<Rc<CSTNode>>,
+            left_parenthesis: Option<Rc<CSTNode>>,
+            simple_path: Rc<CSTNode>,
+            attribute_input: Option<Rc<CSTNode>>,
+            right_parenthesis: Option<Rc<CSTNode>>,
+        },
+
+        // AttributeInput ::= DelimTokenTree | `=` Expression
+        #[visit(visit_attribute_input, fold_attribute_input)]
+        AttributeInput {
+            equal: Option<Rc<CSTNode>>, // DelimTokenTreeの場合はNone
+            expression: Rc<CSTNode>,
+        },
+
+        // SimplePath ::= `::`? SimplePathSegment (`::` SimplePathSegment)*
+        // SimplePathSegment ::= Identifier | `super` | `self` | `crate` | `$crate`
+        #[visit(visit_simple_path, fold_simple_path)]
+        SimplePath {
+            path_separater: Option<Rc<CSTNode>>,
+            simple_path_segment: Rc<CSTNode>,
+            repeat_simple_path_segment: Vec<(CSTNode, CSTNode)>,
+        },
+
+        // DelimTokenTree ::= `(` TokenTree* `)` | `[` TokenTree* `]` | `{` TokenTree* `}`
+        // TokenTree ::= 括弧以外のトークン | DelimTokenTree
+        #[visit(visit_delim_token_tree, fold_delim_token_tree)]
+        DelimTokenTree {
+            left_parenthesis: Rc<CSTNode>,
+            token_tree: Vec<CSTNode>,
+            right_parenthesis: Rc<CSTNode>,
+        },
+
+        // Visibility ::= `pub`
+        //              | `pub` `(` `crate` `)`
+        //              | `pub` `(` `self` `)`
+        //              | `pub` `(` `super` `)`
+        //              | `pub` `(` `in` SimplePath `)`
+        #[visit(visit_visibility, fold_visibility)]
+        Visibility {
+            pub_keyword: Rc<CSTNode>,
+        },
+
+        // Item ::= OuterAttribute* VisItem | MacroItem
+        #[visit(visit_item, fold_item)]
+        Item,
+
+        // MacroInvocation ::= SimplePath `!` DelimTokenTree
+        #[visit(visit_macro_invocation, fold_macro_invocation)]
+        MacroInvocation {
+            simple_path: Rc<CSTNode>,
+            not: Rc<CSTNode>,
+            delim_token_tree: Rc<CSTNode>,
+        },
+
+        // MacroInvocationSemi ::= SimplePath `!` `(` TokenTree* `)` `;`
+        //                       | SimplePath `!` `[` TokenTree* `]` `;`
+        //                       | SimplePath `!` `{` TokenTree* `}`
+        #[visit(visit_macro_invocation_semi, fold_macro_invocation_semi)]
+        MacroInvocationSemi {
+            simple_path: Rc<CSTNode>,
+            not: Rc<CSTNode>,
+            delim_token_tree: Rc<CSTNode>,
+            semicolon: Option<Rc<CSTNode>>,
+        },
+
+        // BuiltinMacroInvocation ::= BuiltinMacroName `!` `(` CallParams? `)`
+        #[visit(visit_builtin_macro_invocation, fold_builtin_macro_invocation)]
+        BuiltinMacroInvocation {
+            identifier: Rc<CSTNode>,
+            not: Rc<CSTNode>,
+            left_parenthesis: Rc<CSTNode>,
+            call_params: Option<Rc<CSTNode>>,
+            right_parenthesis: Rc<CSTNode>,
+        },
+
+        // MacroRulesDefinition ::= `macro_rules` `!` Identifier MacroRulesDef
+        // MacroRulesDef ::= `(` MacroRules `)` `;` | `[` MacroRules `]` `;` | `{` MacroRules `}`
+        // MacroRulesは展開する時に解析するので、ここではDelimTokenTreeのまま持つ
+        #[visit(visit_macro_rules_definition, fold_macro_rules_definition)]
+        MacroRulesDefinition {
+            macro_rules_keyword: Rc<CSTNode>,
+            not: Rc<CSTNode>,
+            identifier: Rc<CSTNode>,
+            delim_token_tree: Rc<CSTNode>,
+            semicolon: Option<Rc<CSTNode>>,
+        },
+
+        // Function ::= FunctionQualifiers `fn` Identifier GenericParams?
+        //             `(` FunctionParameters? `)`
+        //             FunctionReturnType? WhereClause?
+        //             ( BlockExpression | `;` )
+        // outer_attributeはItemのOuterAttribute*
+        #[visit(visit_function, fold_function)]
+        Function {
+            outer_attribute: Vec<CSTNode>,
+            visibility: Option<Rc<CSTNode>>,
+            function_qualifiers: Rc<CSTNode>,
+            fn_keyword: Rc<CSTNode>,
+            identifier: Rc<CSTNode>,
+            generic_params: Option<Rc<CSTNode>>,
+            left_parenthesis: Rc<CSTNode>,
+            function_parameters: Option<Rc<CSTNode>>,
+            right_parenthesis: Rc<CSTNode>,
+            function_return_type: Option<Rc<CSTNode>>,
+            where_clause: Option<Rc<CSTNode>>,
+            block_expression_or_semicolon: Rc<CSTNode>,
+        },
+
+        // GenericParams ::= `<` `>`
+        #[visit(visit_generic_params, fold_generic_params)]
+        GenericParams {
+            less_than: Rc<CSTNode>,
+            greater_than: Rc<CSTNode>,
+        },
+
+        // FunctionQualifiers ::= `const`? `async`? ItemSafety? (`extern` Abi?)?
+        #[visit(visit_function_qualifiers, fold_function_qualifiers)]
+        FunctionQualifiers {
+            const_keyword: Option<Rc<CSTNode>>,
+            async_keyword: Option<Rc<CSTNode>>,
+            item_safety: Option<Rc<CSTNode>>,
+            extern_keyword: Option<Rc<CSTNode>>,
+            abi: Option<Rc<CSTNode>>,
+        },
+
+        // FunctionParameters ::= SelfParam `,`?
+        #[visit(visit_function_param1, fold_function_param1)]
+        FunctionParam1 {
+            self_param: Rc<CSTNode>,
+            comma: Option<Rc<CSTNode>>,
+        },
+
+        // FunctionParameters ::= (SelfParam `,`)? FunctionParam (`,` FunctionParam)* `,`?
+        #[visit(visit_function_param2, fold_function_param2)]
+        FunctionParam2 {
+            self_param: Option<(Rc<CSTNode>, Rc<CSTNode>)>,
+            function_param: Rc<CSTNode>,
+            function_param_repeat: Vec<(CSTNode, CSTNode)>,
+            comma: Option<Rc<CSTNode>>,
+        },
+
+        // ShorthandSelf ::= OuterAttribute* `&`? `mut`? `self`
+        // outer_attributeはSelfParamのOuterAttribute*
+        #[visit(visit_shorthand_self, fold_shorthand_self)]
+        ShorthandSelf {
+            outer_attribute: Vec<CSTNode>,
+            and: Option<Rc<CSTNode>>,
+            mut_keyword: Option<Rc<CSTNode>>,
+            self_keyword: Rc<CSTNode>,
+        },
+
+        // TypedSelf ::= OuterAttribute* `mut`? `self` `:` Type
+        #[visit(visit_typed_self, fold_typed_self)]
+        TypedSelf {
+            outer_attribute: Vec<CSTNode>,
+            mut_keyword: Option<Rc<CSTNode>>,
+            self_keyword: Rc<CSTNode>,
+            colon: Rc<CSTNode>,
+            type_expression: Rc<CSTNode>,
+        },
+
+        // FunctionParam ::= OuterAttribute* PatternNoTopAlt `:` Type
+        #[visit(visit_function_param, fold_function_param)]
+        FunctionParam {
+            outer_attribute: Vec<CSTNode>,
+            pattern_no_top_alt: Rc<CSTNode>,
+            colon: Rc<CSTNode>,
+            type_expression: Rc<CSTNode>,
+        },
+
+        // FunctionReturnType ::= `->` Type
+        #[visit(visit_function_return_type, fold_function_return_type)]
+        FunctionReturnType {
+            right_allow: Rc<CSTNode>,
+            type_expression: Rc<CSTNode>,
+        },
+
+        // TypeAlias ::= `type` Identifier `=` Type `;`
+        #[visit(visit_type_alias, fold_type_alias)]
+        TypeAlias {
+            outer_attribute: Vec<CSTNode>,
+            visibility: Option<Rc<CSTNode>>,
+            type_keyword: Rc<CSTNode>,
+            identifier: Rc<CSTNode>,
+            equal: Rc<CSTNode>,
+            type_expression: Rc<CSTNode>,
+            semicolon: Rc<CSTNode>,
+        },
+
+        // Struct ::= `struct` Identifier ( `{` StructFields? `}` | `;` )
+        #[visit(visit_struct, fold_struct)]
+        Struct {
+            outer_attribute: Vec<CSTNode>,
+            visibility: Option<Rc<CSTNode>>,
+            struct_keyword: Rc<CSTNode>,
+            identifier: Rc<CSTNode>,
+            left_brace: Option<Rc<CSTNode>>,
+            struct_fields: Option<Rc<CSTNode>>,
+            right_brace_or_semicolon: Rc<CSTNode>,
+        },
+
+        // StructFields ::= StructField (`,` StructField)* `,`?
+        #[visit(visit_struct_fields, fold_struct_fields)]
+        StructFields {
+            struct_field: Rc<CSTNode>,
+            repeat_struct_field: Vec<(CSTNode, CSTNode)>,
+            comma: Option<Rc<CSTNode>>,
+        },
+
+        // StructField ::= OuterAttribute* Visibility? Identifier `:` Type
+        #[visit(visit_struct_field, fold_struct_field)]
+        StructField {
+            outer_attribute: Vec<CSTNode>,
+            visibility: Option<Rc<CSTNode>>,
+            identifier: Rc<CSTNode>,
+            colon: Rc<CSTNode>,
+            type_expression: Rc<CSTNode>,
+        },
+
+        // Implementation ::= `impl` Type `{` AssociatedItem* `}`
+        //                  | `impl` TypePath `for` Type `{` AssociatedItem* `}`
+        // AssociatedItem ::= OuterAttribute* ( TypeAlias | Function )
+        // trait_pathは(TypePath, `for`)
+        #[visit(visit_implementation, fold_implementation)]
+        Implementation {
+            outer_attribute: Vec<CSTNode>,
+            visibility: Option<Rc<CSTNode>>,
+            impl_keyword: Rc<CSTNode>,
+            trait_path: Option<(Rc<CSTNode>, Rc<CSTNode>)>,
+            type_expression: Rc<CSTNode>,
+            left_brace: Rc<CSTNode>,
+            associated_items: Vec<CSTNode>,
+            right_brace: Rc<CSTNode>,
+        },
+
+        // Type ::= TypePath
+        // TypePath ::= `::`? PathIdentSegment (`::` PathIdentSegment)*
+        #[visit(visit_type_path, fold_type_path)]
+        TypePath {
+            path_separater: Option<Rc<CSTNode>>,
+            path_ident_segment: Rc<CSTNode>,
+            repeat_path_ident_segment: Vec<(CSTNode, CSTNode)>,
+        },
+
+        // Expression ::= ExpressionWithoutBlock | ExpressionWithBlock
+        #[visit(visit_expression, fold_expression)]
+        Expression {
+            expression: Rc<CSTNode>,
+        },
+
+        // ExpressionWithoutBlock ::= OuterAttribute*
+        //                            (
+        //                              LiteralExpression | PathExpression | OperatorExpression | GroupedExpression
+        //                            | ArrayExpression | AwaitExpression | IndexExpression | TupleExpression
+        //                            | TupleIndexingExpression | StructExpression | CallExpression | MethodCallExpression
+        //                            | FieldExpression | ClosureExpression | AsyncBlockExpression | ContinueExpression
+        //                            | BreakExpression | RangeExpression | ReturnExpression | UnderscoreExpression | MacroInvocation
+        //                            )
+        #[visit(visit_expression_without_block, fold_expression_without_block)]
+        ExpressionWithoutBlock {
+            outer_attribute: Vec<CSTNode>,
+            expression: Rc<CSTNode>,
+        },
+
+        // ExpressionWithoutBlock ::= OuterAttribute*
+        //                           (
+        //                                LiteralExpression | PathExpression | OperatorExpression | GroupedExpression | ArrayExpression
+        //                              | AwaitExpression | IndexExpression | TupleExpression | TupleIndexingExpression | StructExpression
+        //                              | CallExpression | MethodCallExpression | FieldExpression | ClosureExpression | AsyncBlockExpression
+        //                              | ContinueExpression | BreakExpression | RangeExpression | ReturnExpression | UnderscoreExpression | MacroInvocation
+        //                           )
+        #[visit(visit_expression_with_block, fold_expression_with_block)]
+        ExpressionWithBlock {
+            outer_attribute: Vec<CSTNode>,
+            expression_with_block: Rc<CSTNode>,
+        },
+
+        // LiteralExpression ::=  CharacterLiteral
+        //                      | StringLiteral
+        //                      | RawStringLiteral
+        //                      | ByteLiteral
+        //                      | ByteStringLiteral
+        //                      | RawByteStringLiteral
+        //                      | CStringLiteral
+        //                      | RawCStringLiteral
+        //                      | IntgerLiteral
+        //                      | FloatLiteral
+        //                      | true
+        //                      | false
+        #[visit(visit_literal_expression, fold_literal_expression)]
+        LiteralExpression {
+            literal: Rc<CSTNode>,
+        },
+
+        // PathExpression ::= PathInExpression | QualifiedPathInExpression
+        #[visit(visit_path_expression, fold_path_expression)]
+        PathExpression {
+            path_in_expression: Rc<CSTNode>,
+        },
+
+        // PathInExpression ::= `::`? PathExprSegment (`::` PathExprSegment)*
+        #[visit(visit_path_in_expression, fold_path_in_expression)]
+        PathInExpression {
+            path_separater: Option<Rc<CSTNode>>,
+            path_expr_segment: Rc<CSTNode>,
+            repeat_path_expr_segment: Vec<(CSTNode, CSTNode)>,
+        },
+
+        // PathExprSegment ::= PathIdentSegment (`::` GenericArgs)?
+        #[visit(visit_path_expr_segment, fold_path_expr_segment)]
+        PathExprSegment {
+            path_ident_segment: Rc<CSTNode>,
+            generic_args: Option<(Rc<CSTNode>, Rc<CSTNode>)>,
+        },
+
+        // GroupedExpression ::= `(` Expression `)`
+        #[visit(visit_grouped_expression, fold_grouped_expression)]
+        GroupedExpression {
+            left_parenthesis: Rc<CSTNode>,
+            expression: Rc<CSTNode>,
+            right_parenthesis: Rc<CSTNode>,
+        },
+
+        // StructExpression ::= StructExprStruct | StructExprTuple | StructExprUnit
+        #[visit(visit_struct_expression, fold_struct_expression)]
+        StructExpression {
+            expression: Rc<CSTNode>,
+        },
+
+        // StructExprStruct ::= PathInExpression `{` (StructExprFields | StructBase)? `}`
+        #[visit(visit_struct_expr_struct, fold_struct_expr_struct)]
+        StructExprStruct {
+            path_in_expression: Rc<CSTNode>,
+            left_brace: Rc<CSTNode>,
+            expression: Option<Rc<CSTNode>>,
+            right_brace: Rc<CSTNode>,
+        },
+
+        // StructExprFields
+        #[visit(visit_struct_expr_fields, fold_struct_expr_fields)]
+        StructExprFields {
+            struct_expr_filed: Rc<CSTNode>,
+            struct_expr_filed_repeat: Vec<(CSTNode, CSTNode)>,
+            comma: Option<Rc<CSTNode>>,
+            struct_base: Option<Rc<CSTNode>>,
+        },
+
+        // StructExprField  ::= OuterAttribute* ( Identifier | (Identifier |TUPLE_INDEX) `:` Expression )
+        #[visit(visit_struct_expr_field1, fold_struct_expr_field1)]
+        StructExprField1 {
+            outer_attribute: Vec<CSTNode>,
+            identifier: Rc<CSTNode>,
+        },
+        #[visit(visit_struct_expr_field2, fold_struct_expr_field2)]
+        StructExprField2 {
+            outer_attribute: Vec<CSTNode>,
+            identifier_or_tuple: Rc<CSTNode>,
+            colon: Rc<CSTNode>,
+            expression: Rc<CSTNode>,
+        },
+
+        // StructBase ::= `..` Expression
+        #[visit(visit_struct_base, fold_struct_base)]
+        StructBase {
+            dotdot: Rc<CSTNode>,
+            expression: Rc<CSTNode>,
+        },
+
+        //  CallExpression ::= Expression `(` CallParams? `)`
+        #[visit(visit_call_expression, fold_call_expression)]
+        CallExpression {
+            expression: Rc<CSTNode>,
+            left_parenthesis: Rc<CSTNode>,
+            call_params: Option<Rc<CSTNode>>,
+            right_parenthesis: Rc<CSTNode>,
+        },
+
+        // CallParams     ::= Expression ( `,` Expression )* `,`?
+        #[visit(visit_call_params, fold_call_params)]
+        CallParams {
+            expression: Rc<CSTNode>,
+            comma_and_expression: Vec<(CSTNode, CSTNode)>,
+            comma: Option<Rc<CSTNode>>,
+        },
+
+        // MethodCallExpression ::= Expression `.` PathExprSegment `(` CallParams? `)`
+        #[visit(visit_method_call_expression, fold_method_call_expression)]
+        MethodCallExpression {
+            expression: Rc<CSTNode>,
+            dot: Rc<CSTNode>,
+            path_expr_segment: Rc<CSTNode>,
+            left_parenthesis: Rc<CSTNode>,
+            call_params: Option<Rc<CSTNode>>,
+            right_parenthesis: Rc<CSTNode>,
+        },
+
+        // FieldExpression ::= Expression `.` Identifier
+        #[visit(visit_field_expression, fold_field_expression)]
+        FieldExpression {
+            expression: Rc<CSTNode>,
+            dot: Rc<CSTNode>,
+            identifier: Rc<CSTNode>,
+        },
+
+        // AwaitExpression ::= Expression `.` `await`
+        #[visit(visit_await_expression, fold_await_expression)]
+        AwaitExpression {
+            expression: Rc<CSTNode>,
+            dot: Rc<CSTNode>,
+            await_keyword: Rc<CSTNode>,
+        },
+
+        // IndexExpression ::= Expression `[` Expression `]`
+        #[visit(visit_index_expression, fold_index_expression)]
+        IndexExpression {
+            expression: Rc<CSTNode>,
+            left_brackets: Rc<CSTNode>,
+            index: Rc<CSTNode>,
+            right_brackets: Rc<CSTNode>,
+        },
+
+        // ReturnExpression ::= return (Expression)?
+        #[visit(visit_return_expression, fold_return_expression)]
+        ReturnExpression {
+            return_keyword: Rc<CSTNode>,
+            expression: Option<Rc<CSTNode>>,
+        },
+
+        // IfExpression ::= `if` (Expression | LetChain) BlockExpression (`else` ( BlockExpression | IfExpression | IfLetExpression ) )?
+        #[visit(visit_if_expression, fold_if_expression)]
+        IfExpression {
+            if_keyword: Rc<CSTNode>,
+            expression: Rc<CSTNode>,
+            block_expression: Rc<CSTNode>,
+            else_keyword: Option<Rc<CSTNode>>,
+            else_expression: Option<Rc<CSTNode>>,
+        },
+
+        // LetChain ::= LetChainCondition ( `&&` LetChainCondition )*
+        // LetChainConditionが二つ以上あり、そのうち一つ以上が`let`のもの
+        #[visit(visit_let_chain, fold_let_chain)]
+        LetChain {
+            let_chain_condition: Rc<CSTNode>,
+            repeat_let_chain_condition: Vec<(CSTNode, CSTNode)>,
+        },
+
+        // LetChainCondition ::= Expression | `let` Pattern `=` Scrutinee
+        // Expressionの場合はExpressionのノードをそのまま使う
+        #[visit(visit_let_chain_condition, fold_let_chain_condition)]
+        LetChainCondition {
+            let_keyword: Rc<CSTNode>,
+            pattern: Rc<CSTNode>,
+            equal: Rc<CSTNode>,
+            scrutinee: Rc<CSTNode>,
+        },
+
+        // IfLetExpression ::= `if` `let` Pattern `=` Scrutinee BlockExpression (`else` ( BlockExpression | IfExpression | IfLetExpression ) )?
+        #[visit(visit_if_let_expression, fold_if_let_expression)]
+        IfLetExpression {
+            if_keyword: Rc<CSTNode>,
+            let_keyword: Rc<CSTNode>,
+            pattern: Rc<CSTNode>,
+            equal: Rc<CSTNode>,
+            scrutinee: Rc<CSTNode>,
+            block_expression: Rc<CSTNode>,
+            else_keyword: Option<Rc<CSTNode>>,
+            else_expression: Option<Rc<CSTNode>>,
+        },
+
+        // Statements ::= Statement+ | Statement+ ExpressionWithoutBlock | ExpressionWithoutBlock
+        #[visit(visit_statements, fold_statements)]
+        Statements,
+
+        // Statement ::= `;` | Item | LetStatement | ExpressionStatement | MacroInvocationSemi
+        #[visit(visit_statement, fold_statement)]
+        Statement {
+            statement: Rc<CSTNode>,
+        },
+
+        #[visit(visit_block_expression, fold_block_expression)]
+        BlockExpression {
+            left_brace: Rc<CSTNode>,
+            inner_attribute: Vec<CSTNode>,
+            statements: Option<Rc<CSTNode>>,
+            right_brace: Rc<CSTNode>,
+        },
+
+        // ConstBlockExpression ::= `const` BlockExpression
+        #[visit(visit_const_block_expression, fold_const_block_expression)]
+        ConstBlockExpression {
+            const_keyword: Rc<CSTNode>,
+            block_expression: Rc<CSTNode>,
+        },
+
+        // UnsafeBlockExpression ::= `unsafe` BlockExpression
+        #[visit(visit_unsafe_block_expression, fold_unsafe_block_expression)]
+        UnsafeBlockExpression {
+            unsafe_keyword: Rc<CSTNode>,
+            block_expression: Rc<CSTNode>,
+        },
+
+        // AsyncBlockExpression ::= `async` `move`? BlockExpression
+        #[visit(visit_async_block_expression, fold_async_block_expression)]
+        AsyncBlockExpression {
+            async_keyword: Rc<CSTNode>,
+            move_keyword: Option<Rc<CSTNode>>,
+            block_expression: Rc<CSTNode>,
+        },
+
+        // LetStatement ::= OuterAttribute* (`ur` | `sr` | `nr` | `let`)
+        //                  PatternNoTopAlt ( `:` Type )?
+        //                  (`=` Expression ( `else` BlockExpression)? )? `;`
+        #[visit(visit_let_statement, fold_let_statement)]
+        LetStatement {
+            outer_attribute: Vec<CSTNode>,
+            rarity: Rc<CSTNode>,
+            pattern_no_top_alt: Rc<CSTNode>,
+            colon: Option<Rc<CSTNode>>,
+            type_expression: Option<Rc<CSTNode>>,
+            equal: Option<Rc<CSTNode>>,
+            expression: Option<Rc<CSTNode>>,
+            else_keyword: Option<Rc<CSTNode>>,
+            block_expression: Option<Rc<CSTNode>>,
+            semicolon: Rc<CSTNode>,
+        },
+
+        // Pattern ::= `|`? PatternNoTopAlt ( `|` PatternNoTopAlt )*
+        #[visit(visit_pattern, fold_pattern)]
+        Pattern {
+            or_token: Option<Rc<CSTNode>>,
+            pattern_no_top_alt: Rc<CSTNode>,
+            repeat_pattern_no_top_alt: Vec<(CSTNode, CSTNode)>,
+        },
+
+        // 位置はリテラルの位置
+        #[visit(visit_literal_pattern, fold_literal_pattern)]
+        LiteralPattern {
+            minus: Option<Rc<CSTNode>>,
+            literal: Literal,
+            row: usize,
+            column: usize,
+        },
+
+        //
+        #[visit(visit_identifier_pattern, fold_identifier_pattern)]
+        IdentifierPattern {
+            ref_keyword: Option<Rc<CSTNode>>,
+            mut_keyword: Option<Rc<CSTNode>>,
+            identifier: Rc<CSTNode>,
+            at_symbol: Option<Rc<CSTNode>>,
+            pattern_no_top_alt: Option<Rc<CSTNode>>,
+        },
+
+        #[visit(visit_wildcard_pattern, fold_wildcard_pattern)]
+        WildcardPattern {
+            wildcard: Rc<CSTNode>,
+        },
+
+        #[visit(visit_rest_pattern, fold_rest_pattern)]
+        RestPattern {
+            rest: Rc<CSTNode>,
+        },
+    }
 }
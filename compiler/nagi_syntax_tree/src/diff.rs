@@ -0,0 +1,680 @@
+use crate::arena::NodeId;
+use crate::walk::{SyntaxNode, SPAN_FIELDS};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+// 2つの構文木の差分
+// 先に両方の木のノードを対応付け、対応の無いノードを挿入と削除、ラベルの違うものを更新、
+// 親が変わったものと兄弟の中で順番が変わったものを移動とする
+//
+// ノードの対応付けは3段階で行う
+//   1. 同じ形の部分木を大きいものから対応付ける
+//   2. 子孫の多くが対応している同じ種類のノードを対応付ける
+//   3. 対応したノードの子で、まだ対応していないものを順番に対応付ける
+//
+// NodeIdはarena::Arena::from_treeで付く番号と同じ
+
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    pub ignore_spans: bool, // ASTの範囲とCSTのトークンの行と列を比べない
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKind {
+    Insert,
+    Delete,
+    Update,
+    Move,
+}
+
+// 挿入と削除は部分木の根だけを示す
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub kind: EditKind,
+    pub old: Option<NodeId>, // 挿入ではNone
+    pub new: Option<NodeId>, // 削除ではNone
+}
+
+// 1段階目で、木の中に同じ形が複数ある部分木を対応付ける最小のノード数
+// 小さい部分木は`;`のようにどこにでも現れるので、ほかの段階で親から決める
+const MIN_SHARED_SUBTREE: usize = 2;
+
+// 2段階目で、子孫のうち対応しているものがこの割合以上なら対応付ける
+const MIN_DICE: f64 = 0.5;
+
+pub struct TreeDiff {
+    pub edits: Vec<Edit>,
+    old: Vec<DiffNode>,
+    new: Vec<DiffNode>,
+}
+
+struct DiffNode {
+    kind: &'static str,
+    label: String,      // 範囲を除いたラベル 対応付けにはこれを使う
+    full_label: String, // 範囲を比べるときは範囲も含めたラベル
+    position: Option<(usize, usize)>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    end: usize, // 子孫の最後の番号の次
+    hash: u64,  // 部分木の形のハッシュ
+}
+
+impl DiffNode {
+    fn size(&self, index: usize) -> usize {
+        self.end - index
+    }
+}
+
+pub fn diff_trees<T: SyntaxNode>(old: &T, new: &T, options: &DiffOptions) -> TreeDiff {
+    let old = flatten(old, options);
+    let new = flatten(new, options);
+    let matching = Matching::new(&old, &new);
+    let edits = matching.edits();
+
+    TreeDiff { edits, old, new }
+}
+
+fn flatten<T: SyntaxNode>(tree: &T, options: &DiffOptions) -> Vec<DiffNode> {
+    let mut nodes = vec![];
+    push_node(&mut nodes, tree, None, options);
+
+    // ハッシュと子孫から決める位置は子から順に決める
+    for index in (0..nodes.len()).rev() {
+        let mut hasher = DefaultHasher::new();
+        nodes[index].label.hash(&mut hasher);
+        for child in nodes[index].children.iter() {
+            nodes[*child].hash.hash(&mut hasher);
+        }
+        nodes[index].hash = hasher.finish();
+
+        if nodes[index].position.is_none() {
+            nodes[index].position = nodes[index]
+                .children
+                .iter()
+                .find_map(|child| nodes[*child].position);
+        }
+    }
+
+    nodes
+}
+
+fn push_node<T: SyntaxNode>(
+    nodes: &mut Vec<DiffNode>,
+    node: &T,
+    parent: Option<usize>,
+    options: &DiffOptions,
+) -> usize {
+    let fields = node.leaf_fields();
+    let label = |with_spans: bool| {
+        let fields: Vec<String> = fields
+            .iter()
+            .filter(|(name, _)| with_spans || !SPAN_FIELDS.contains(name))
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        if fields.is_empty() {
+            node.kind_name().to_string()
+        } else {
+            format!("{}({})", node.kind_name(), fields.join(", "))
+        }
+    };
+
+    let index = nodes.len();
+    nodes.push(DiffNode {
+        kind: node.kind_name(),
+        label: label(false),
+        full_label: label(!options.ignore_spans),
+        position: node.start_position(),
+        parent,
+        children: vec![],
+        end: 0,
+        hash: 0,
+    });
+
+    let mut children = vec![];
+    node.for_each_child(&mut |child| children.push(child));
+    for child in children {
+        let child_index = push_node(nodes, child, Some(index), options);
+        nodes[index].children.push(child_index);
+    }
+
+    nodes[index].end = nodes.len();
+    index
+}
+
+// 古い木と新しい木のノードの対応
+struct Matching<'a> {
+    old: &'a [DiffNode],
+    new: &'a [DiffNode],
+    old_to_new: Vec<Option<usize>>,
+    new_to_old: Vec<Option<usize>>,
+}
+
+impl<'a> Matching<'a> {
+    fn new(old: &'a [DiffNode], new: &'a [DiffNode]) -> Self {
+        let mut matching = Self {
+            old,
+            new,
+            old_to_new: vec![None; old.len()],
+            new_to_old: vec![None; new.len()],
+        };
+        if old.is_empty() || new.is_empty() {
+            return matching;
+        }
+
+        matching.match_subtrees();
+        matching.match_containers();
+        if matching.old_to_new[0].is_none()
+            && matching.new_to_old[0].is_none()
+            && old[0].kind == new[0].kind
+        {
+            matching.link(0, 0);
+        }
+        matching.match_children();
+        matching
+    }
+
+    fn link(&mut self, old: usize, new: usize) {
+        self.old_to_new[old] = Some(new);
+        self.new_to_old[new] = Some(old);
+    }
+
+    // 1段階目 同じ形の部分木を大きいものから対応付け、その子孫もまとめて対応付ける
+    fn match_subtrees(&mut self) {
+        let mut new_by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (index, node) in self.new.iter().enumerate() {
+            new_by_hash.entry(node.hash).or_default().push(index);
+        }
+        let mut old_count: HashMap<u64, usize> = HashMap::new();
+        for node in self.old.iter() {
+            *old_count.entry(node.hash).or_default() += 1;
+        }
+
+        let mut order: Vec<usize> = (0..self.old.len()).collect();
+        order.sort_by_key(|index| std::cmp::Reverse(self.old[*index].size(*index)));
+        for old in order {
+            if self.old_to_new[old].is_some() {
+                continue;
+            }
+            let hash = self.old[old].hash;
+            let Some(candidates) = new_by_hash.get(&hash) else {
+                continue;
+            };
+            let is_unique = candidates.len() == 1 && old_count[&hash] == 1;
+            if !is_unique && self.old[old].size(old) < MIN_SHARED_SUBTREE {
+                continue;
+            }
+
+            // 同じ形が複数あれば、親のラベルが同じものを優先する
+            let available: Vec<usize> = candidates
+                .iter()
+                .copied()
+                .filter(|new| self.new_to_old[*new].is_none())
+                .collect();
+            let Some(new) = available
+                .iter()
+                .copied()
+                .find(|new| parent_label(self.new, *new) == parent_label(self.old, old))
+                .or(available.first().copied())
+            else {
+                continue;
+            };
+
+            // 同じ形なので前順に並べれば1つずつ対応する
+            for offset in 0..self.old[old].size(old) {
+                self.link(old + offset, new + offset);
+            }
+        }
+    }
+
+    // 2段階目 子孫の対応が多い同じ種類のノードを、子から順に対応付ける
+    fn match_containers(&mut self) {
+        for old in (0..self.old.len()).rev() {
+            if self.old_to_new[old].is_some() || self.old[old].size(old) == 1 {
+                continue;
+            }
+
+            // 対応している子孫の、対応先の祖先を数える
+            let mut common: HashMap<usize, usize> = HashMap::new();
+            for descendant in old + 1..self.old[old].end {
+                let Some(mut new) = self.old_to_new[descendant] else {
+                    continue;
+                };
+                while let Some(parent) = self.new[new].parent {
+                    *common.entry(parent).or_default() += 1;
+                    new = parent;
+                }
+            }
+
+            let old_descendants = self.old[old].size(old) - 1;
+            let best = common
+                .into_iter()
+                .filter(|(new, _)| {
+                    self.new_to_old[*new].is_none() && self.new[*new].kind == self.old[old].kind
+                })
+                .map(|(new, count)| {
+                    let new_descendants = self.new[new].size(new) - 1;
+                    let dice = 2.0 * count as f64 / (old_descendants + new_descendants) as f64;
+                    (new, dice)
+                })
+                .filter(|(_, dice)| *dice >= MIN_DICE)
+                .max_by(|(left, left_dice), (right, right_dice)| {
+                    left_dice
+                        .total_cmp(right_dice)
+                        .then_with(|| right.cmp(left))
+                });
+            if let Some((new, _)) = best {
+                self.link(old, new);
+            }
+        }
+    }
+
+    // 3段階目 対応したノードの子のうち残ったものを、同じラベル、同じ種類の順に対応付ける
+    // 前順にたどるので、ここで対応付けた子の子もたどる
+    fn match_children(&mut self) {
+        for old in 0..self.old.len() {
+            let Some(new) = self.old_to_new[old] else {
+                continue;
+            };
+
+            let old_children: Vec<usize> = self.old[old]
+                .children
+                .iter()
+                .copied()
+                .filter(|child| self.old_to_new[*child].is_none())
+                .collect();
+            let new_children: Vec<usize> = self.new[new]
+                .children
+                .iter()
+                .copied()
+                .filter(|child| self.new_to_old[*child].is_none())
+                .collect();
+
+            for (old_child, new_child) in lcs(&old_children, &new_children, |old, new| {
+                self.old[old].label == self.new[new].label
+            }) {
+                self.link(old_child, new_child);
+            }
+            for old_child in old_children {
+                if self.old_to_new[old_child].is_some() {
+                    continue;
+                }
+                if let Some(new_child) = new_children.iter().copied().find(|new_child| {
+                    self.new_to_old[*new_child].is_none()
+                        && self.new[*new_child].kind == self.old[old_child].kind
+                }) {
+                    self.link(old_child, new_child);
+                }
+            }
+        }
+    }
+
+    fn edits(&self) -> Vec<Edit> {
+        let mut edits = vec![];
+        for (old, node) in self.old.iter().enumerate() {
+            let Some(new) = self.old_to_new[old] else {
+                // 親も削除されていれば親の削除に含める
+                if node
+                    .parent
+                    .is_none_or(|parent| self.old_to_new[parent].is_some())
+                {
+                    edits.push(Edit {
+                        kind: EditKind::Delete,
+                        old: Some(NodeId::new(old)),
+                        new: None,
+                    });
+                }
+                continue;
+            };
+
+            if node.full_label != self.new[new].full_label {
+                edits.push(Edit {
+                    kind: EditKind::Update,
+                    old: Some(NodeId::new(old)),
+                    new: Some(NodeId::new(new)),
+                });
+            }
+            if self.is_moved(old, new) {
+                edits.push(Edit {
+                    kind: EditKind::Move,
+                    old: Some(NodeId::new(old)),
+                    new: Some(NodeId::new(new)),
+                });
+            }
+        }
+
+        for (new, node) in self.new.iter().enumerate() {
+            if self.new_to_old[new].is_none()
+                && node
+                    .parent
+                    .is_none_or(|parent| self.new_to_old[parent].is_some())
+            {
+                edits.push(Edit {
+                    kind: EditKind::Insert,
+                    old: None,
+                    new: Some(NodeId::new(new)),
+                });
+            }
+        }
+
+        edits
+    }
+
+    // 親の対応先が変わったか、同じ親の子の中で順番が入れ替わった
+    fn is_moved(&self, old: usize, new: usize) -> bool {
+        let (Some(old_parent), Some(new_parent)) = (self.old[old].parent, self.new[new].parent)
+        else {
+            return false;
+        };
+        if self.old_to_new[old_parent] != Some(new_parent) {
+            return true;
+        }
+
+        // 両方の親に残っている子の並びの最長共通部分列に入らなければ移動したとする
+        let old_children: Vec<usize> = self.old[old_parent]
+            .children
+            .iter()
+            .copied()
+            .filter(|child| {
+                self.old_to_new[*child]
+                    .is_some_and(|new_child| self.new[new_child].parent == Some(new_parent))
+            })
+            .collect();
+        let new_children: Vec<usize> = self.new[new_parent]
+            .children
+            .iter()
+            .copied()
+            .filter(|child| {
+                self.new_to_old[*child]
+                    .is_some_and(|old_child| self.old[old_child].parent == Some(old_parent))
+            })
+            .collect();
+        !lcs(&old_children, &new_children, |old_child, new_child| {
+            self.old_to_new[old_child] == Some(new_child)
+        })
+        .contains(&(old, new))
+    }
+}
+
+fn parent_label(nodes: &[DiffNode], index: usize) -> Option<&str> {
+    nodes[index]
+        .parent
+        .map(|parent| nodes[parent].label.as_str())
+}
+
+// 2つの並びの最長共通部分列を、対応する要素の組で返す
+fn lcs(
+    old: &[usize],
+    new: &[usize],
+    is_same: impl Fn(usize, usize) -> bool,
+) -> Vec<(usize, usize)> {
+    // table[i][j]はold[i..]とnew[j..]の最長共通部分列の長さ
+    let mut table = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i][j] = if is_same(old[i], new[j]) {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if is_same(old[i], new[j]) {
+            pairs.push((old[i], new[j]));
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+impl TreeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    pub fn count(&self, kind: EditKind) -> usize {
+        self.edits.iter().filter(|edit| edit.kind == kind).count()
+    }
+
+    pub fn old_label(&self, id: NodeId) -> &str {
+        &self.old[id.index()].full_label
+    }
+
+    pub fn new_label(&self, id: NodeId) -> &str {
+        &self.new[id.index()].full_label
+    }
+
+    // 部分木のノードの数
+    pub fn old_size(&self, id: NodeId) -> usize {
+        self.old[id.index()].size(id.index())
+    }
+
+    pub fn new_size(&self, id: NodeId) -> usize {
+        self.new[id.index()].size(id.index())
+    }
+
+    // 人が読むための差分
+    // 1行に1つの変更を書き、最後に数をまとめる
+    pub fn report(&self) -> String {
+        self.to_string()
+    }
+
+    fn write_edit(&self, f: &mut fmt::Formatter, edit: &Edit) -> fmt::Result {
+        let position = |nodes: &[DiffNode], id: Option<NodeId>| {
+            id.and_then(|id| nodes[id.index()].position)
+                .map_or("?".to_string(), |(row, column)| {
+                    format!("{}:{}", row, column)
+                })
+        };
+        let old_position = position(&self.old, edit.old);
+        let new_position = position(&self.new, edit.new);
+
+        match (edit.kind, edit.old, edit.new) {
+            (EditKind::Insert, _, Some(new)) => write!(
+                f,
+                "insert {}: {}{}",
+                new_position,
+                self.new_label(new),
+                subtree_size(self.new_size(new))
+            ),
+            (EditKind::Delete, Some(old), _) => write!(
+                f,
+                "delete {}: {}{}",
+                old_position,
+                self.old_label(old),
+                subtree_size(self.old_size(old))
+            ),
+            (EditKind::Update, Some(old), Some(new)) => write!(
+                f,
+                "update {}: {} -> {}",
+                old_position,
+                self.old_label(old),
+                self.new_label(new)
+            ),
+            (EditKind::Move, Some(old), _) => write!(
+                f,
+                "move {} -> {}: {}{}",
+                old_position,
+                new_position,
+                self.old_label(old),
+                subtree_size(self.old_size(old))
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn subtree_size(size: usize) -> String {
+    if size > 1 {
+        format!(" ({} nodes)", size)
+    } else {
+        String::new()
+    }
+}
+
+impl fmt::Display for TreeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no differences");
+        }
+
+        for edit in self.edits.iter() {
+            self.write_edit(f, edit)?;
+            writeln!(f)?;
+        }
+        writeln!(
+            f,
+            "{} changes: {} inserted, {} deleted, {} updated, {} moved",
+            self.edits.len(),
+            self.count(EditKind::Insert),
+            self.count(EditKind::Delete),
+            self.count(EditKind::Update),
+            self.count(EditKind::Move)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::Arena;
+    use crate::ast::ASTNode;
+    use crate::keywords::Keyword;
+    use crate::span::Span;
+    use crate::test_trees::{ast, cst};
+    use crate::token::*;
+
+    // fn name() { let a = literal + x; ... }
+    fn function(name: &str, literals: &[&str]) -> ASTNode {
+        let statements = literals
+            .iter()
+            .map(|literal| {
+                ast::let_statement(
+                    Rarity::Let,
+                    "a",
+                    ast::expression(ast::binary(
+                        BinaryOperator::Add,
+                        ast::integer(literal),
+                        ast::path("x"),
+                    )),
+                )
+            })
+            .collect();
+        ast::function(name, &[], statements)
+    }
+
+    // 範囲の無い木なので位置は`?`になる
+    fn report(old: Vec<ASTNode>, new: Vec<ASTNode>) -> String {
+        let old = ast::crate_node(old);
+        let new = ast::crate_node(new);
+        diff_trees(&old, &new, &DiffOptions::default()).report()
+    }
+
+    #[test]
+    fn same_tree_has_no_edits() {
+        let tree = ast::crate_node(vec![function("a", &["1", "2"]), function("b", &[])]);
+        let diff = diff_trees(&tree, &tree.clone(), &DiffOptions::default());
+        assert!(diff.is_empty());
+        assert_eq!(diff.report(), "no differences\n");
+    }
+
+    #[test]
+    fn update_leaf() {
+        assert_eq!(
+            report(vec![function("a", &["1"])], vec![function("a", &["2"])]),
+            "update ?: Literal(literal=1) -> Literal(literal=2)\n\
+             1 changes: 0 inserted, 0 deleted, 1 updated, 0 moved\n"
+        );
+    }
+
+    #[test]
+    fn move_subtree() {
+        assert_eq!(
+            report(
+                vec![function("a", &["1"]), function("b", &["2"])],
+                vec![function("b", &["2"]), function("a", &["1"])]
+            ),
+            "move ? -> ?: Function(identifier=\"a\") (15 nodes)\n\
+             1 changes: 0 inserted, 0 deleted, 0 updated, 1 moved\n"
+        );
+    }
+
+    #[test]
+    fn insert_and_delete_subtree() {
+        assert_eq!(
+            report(
+                vec![function("a", &["1"])],
+                vec![function("a", &["2", "1"])]
+            ),
+            "insert ?: Statement (11 nodes)\n\
+             1 changes: 1 inserted, 0 deleted, 0 updated, 0 moved\n"
+        );
+        assert_eq!(
+            report(
+                vec![function("a", &["1", "2"])],
+                vec![function("a", &["2"])]
+            ),
+            "delete ?: Statement (11 nodes)\n\
+             1 changes: 0 inserted, 1 deleted, 0 updated, 0 moved\n"
+        );
+    }
+
+    // 範囲を比べれば、ずれたノードは更新になる
+    #[test]
+    fn compare_spans_unless_ignored() {
+        let tree = ast::crate_node(vec![function("a", &["1"])]);
+        let mut shifted = tree.clone();
+        shifted.span = Some(Span::new((1, 1), (1, 11)));
+        let tree = tree.with_span(Some(Span::new((1, 1), (1, 10))));
+        assert_eq!(
+            diff_trees(&tree, &shifted, &DiffOptions::default())
+                .report()
+                .lines()
+                .next(),
+            Some("update 1:1: Crate(span=1:1-1:10) -> Crate(span=1:1-1:11)")
+        );
+        assert!(diff_trees(&tree, &shifted, &DiffOptions { ignore_spans: true }).is_empty());
+    }
+
+    // 番号はArenaの番号と同じ
+    #[test]
+    fn edit_ids_are_arena_ids() {
+        // {
+        //     let a = 1;
+        //     let b = 2;
+        // }
+        let statement = |row, identifier, literal| {
+            cst::let_statement(
+                Keyword::Let,
+                identifier,
+                cst::integer(literal, (row, 13)),
+                (row, 5),
+            )
+        };
+        let old = cst::block((1, 1), vec![statement(2, "a", "1")], (3, 1));
+        let new = cst::block(
+            (1, 1),
+            vec![statement(2, "a", "1"), statement(3, "b", "2")],
+            (4, 1),
+        );
+        let diff = diff_trees(&old, &new, &DiffOptions { ignore_spans: true });
+        let [edit] = &diff.edits[..] else {
+            panic!("{}", diff.report());
+        };
+        assert_eq!(edit.kind, EditKind::Insert);
+        let arena = Arena::from_tree(new.clone());
+        let inserted = arena.node(edit.new.unwrap());
+        assert_eq!(diff.new_label(edit.new.unwrap()), inserted.kind_name());
+        assert_eq!(inserted.span.map(|span| span.start), Some((3, 5)));
+    }
+}
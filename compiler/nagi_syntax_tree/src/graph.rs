@@ -0,0 +1,237 @@
+use crate::walk::{SyntaxNode, SPAN_FIELDS};
+
+// 木をGraphvizのDOTかMermaidの図にする
+// ノードには種類と子ノード以外のフィールドを1行ずつ書き、レアリティを持つノードはレアリティで色を分ける
+// 記号表のスコープのように構文木でない木は、add_nodeとadd_edgeで組み立てる
+
+const MAX_VALUE_WIDTH: usize = 40; // これより長い値は省略する
+
+// レアリティごとの色
+const RARITY_COLORS: [(&str, &str); 3] = [("Ur", "#f9d976"), ("Sr", "#c9d6df"), ("Nr", "#e8e8e8")];
+
+// 構文木と記号表のスコープを図にする形式
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl GraphFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::Mermaid => "mmd",
+        }
+    }
+}
+
+pub struct Graph {
+    name: String,
+    nodes: Vec<GraphNode>,
+    edges: Vec<(usize, usize)>,
+}
+
+struct GraphNode {
+    lines: Vec<String>,
+    rarity: Option<String>,
+}
+
+impl Graph {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            nodes: vec![],
+            edges: vec![],
+        }
+    }
+
+    // 1行目を見出しとし、追加した順に番号を返す
+    pub fn add_node(&mut self, lines: Vec<String>, rarity: Option<&str>) -> usize {
+        self.nodes.push(GraphNode {
+            lines,
+            rarity: rarity.map(str::to_string),
+        });
+        self.nodes.len() - 1
+    }
+
+    pub fn add_edge(&mut self, parent: usize, child: usize) {
+        self.edges.push((parent, child));
+    }
+
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Mermaid => self.to_mermaid(),
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let mut output = format!("digraph \"{}\" {{\n", escape_dot(&self.name));
+        output.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        for (index, node) in self.nodes.iter().enumerate() {
+            let label: Vec<String> = node.lines.iter().map(|line| escape_dot(line)).collect();
+            output.push_str(&format!("    n{} [label=\"{}\"", index, label.join("\\n")));
+            if let Some(color) = rarity_color(&node.rarity) {
+                output.push_str(&format!(", style=filled, fillcolor=\"{}\"", color));
+            }
+            output.push_str("];\n");
+        }
+        for (parent, child) in self.edges.iter() {
+            output.push_str(&format!("    n{} -> n{};\n", parent, child));
+        }
+        output.push_str("}\n");
+        output
+    }
+
+    fn to_mermaid(&self) -> String {
+        let mut output = format!("%% {}\nflowchart TD\n", self.name);
+        for (index, node) in self.nodes.iter().enumerate() {
+            let label: Vec<String> = node.lines.iter().map(|line| escape_mermaid(line)).collect();
+            output.push_str(&format!("    n{}[\"{}\"]\n", index, label.join("<br/>")));
+        }
+        for (parent, child) in self.edges.iter() {
+            output.push_str(&format!("    n{} --> n{}\n", parent, child));
+        }
+
+        // 使ったレアリティだけクラスを定義する
+        for (rarity, color) in RARITY_COLORS {
+            let nodes: Vec<String> = self
+                .nodes
+                .iter()
+                .enumerate()
+                .filter(|(_, node)| node.rarity.as_deref() == Some(rarity))
+                .map(|(index, _)| format!("n{}", index))
+                .collect();
+            if nodes.is_empty() {
+                continue;
+            }
+            let class = rarity.to_lowercase();
+            output.push_str(&format!("    classDef {} fill:{}\n", class, color));
+            output.push_str(&format!("    class {} {}\n", nodes.join(","), class));
+        }
+        output
+    }
+}
+
+pub fn tree_graph<T: SyntaxNode>(name: &str, tree: &T) -> Graph {
+    let mut graph = Graph::new(name);
+    add_tree(&mut graph, tree, None);
+    graph
+}
+
+// 範囲とfalseのフィールドは図を読みにくくするだけなので書かない
+fn add_tree<T: SyntaxNode>(graph: &mut Graph, node: &T, parent: Option<usize>) {
+    let mut lines = vec![node.kind_name().to_string()];
+    let mut rarity = None;
+    for (name, value) in node.leaf_fields() {
+        if SPAN_FIELDS.contains(&name) || value == "false" {
+            continue;
+        }
+        if name == "rarity" {
+            rarity = Some(value.clone());
+        }
+        lines.push(format!("{}: {}", name, shorten(&value)));
+    }
+
+    let index = graph.add_node(lines, rarity.as_deref());
+    if let Some(parent) = parent {
+        graph.add_edge(parent, index);
+    }
+    node.for_each_child(&mut |child| add_tree(graph, child, Some(index)));
+}
+
+fn shorten(value: &str) -> String {
+    if value.chars().count() <= MAX_VALUE_WIDTH {
+        return value.to_string();
+    }
+    let value: String = value.chars().take(MAX_VALUE_WIDTH - 3).collect();
+    format!("{}...", value)
+}
+
+fn rarity_color(rarity: &Option<String>) -> Option<&'static str> {
+    RARITY_COLORS
+        .iter()
+        .find(|(name, _)| rarity.as_deref() == Some(*name))
+        .map(|(_, color)| *color)
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Mermaidのラベルでは記号を実体参照で書く
+fn escape_mermaid(text: &str) -> String {
+    text.replace('#', "#35;")
+        .replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::Arena;
+    use crate::ast::ASTNode;
+    use crate::keywords::Keyword;
+    use crate::test_trees::{ast, cst};
+    use crate::token::*;
+
+    // fn a() { ur x = 1 + 2; }
+    fn function() -> ASTNode {
+        ast::crate_node(vec![ast::function(
+            "a",
+            &[],
+            vec![ast::let_statement(
+                Rarity::Ur,
+                "x",
+                ast::expression(ast::binary(
+                    BinaryOperator::Add,
+                    ast::integer("1"),
+                    ast::integer("2"),
+                )),
+            )],
+        )])
+    }
+
+    #[test]
+    fn dot_export() {
+        let tree = function();
+        let dot = tree_graph("ast", &tree).render(GraphFormat::Dot);
+        assert!(dot.starts_with("digraph \"ast\" {\n"));
+        assert!(dot.contains(
+            "[label=\"LetStatement\\nrarity: Ur\", style=filled, fillcolor=\"#f9d976\"];"
+        ));
+        assert!(dot.contains("[label=\"BinaryOperator\\noperator: Add\"];"));
+        assert_eq!(
+            dot.matches(" -> ").count(),
+            Arena::from_tree(tree.clone()).len() - 1
+        );
+    }
+
+    #[test]
+    fn mermaid_export() {
+        let mermaid = tree_graph("ast", &function()).render(GraphFormat::Mermaid);
+        assert!(mermaid.starts_with("%% ast\nflowchart TD\n    n0[\"Crate\"]\n"));
+        assert!(mermaid.contains("[\"IdentifierPattern<br/>identifier: #quot;x#quot;\"]"));
+        assert!(mermaid.ends_with("    classDef ur fill:#f9d976\n    class n6 ur\n"));
+    }
+
+    // CSTのノードはトークンを表示する
+    #[test]
+    fn cst_tokens_in_labels() {
+        // ur x = 1 + 2;
+        let tree = cst::let_statement(
+            Keyword::Ur,
+            "x",
+            cst::binary(
+                Token::Plus,
+                (1, 10),
+                cst::integer("1", (1, 8)),
+                cst::integer("2", (1, 12)),
+            ),
+            (1, 1),
+        );
+        let dot = tree_graph("cst", &tree).render(GraphFormat::Dot);
+        assert!(dot.contains("[label=\"Operator\\ntoken: +\"];"));
+    }
+}
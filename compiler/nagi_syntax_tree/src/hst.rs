@@ -1,15 +1,31 @@
 use crate::token::*;
+use crate::walk::{define_syntax_tree, SyntaxNode};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::prelude::*;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct HSTNode {
-    pub node_kind: HSTNodeKind,
-    pub children: Vec<HSTNode>,
+impl SyntaxNode for HSTNode {
+    fn kind_name(&self) -> &'static str {
+        HSTNode::kind_name(self)
+    }
+
+    fn leaf_fields(&self) -> Vec<(&'static str, String)> {
+        HSTNode::leaf_fields(self)
+    }
+
+    fn start_position(&self) -> Option<(usize, usize)> {
+        None
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum HSTNodeKind {
-    Factor { token: Token },
+define_syntax_tree! {
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct HSTNode {
+        pub node_kind: HSTNodeKind,
+        pub children: Vec<HSTNode>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub enum HSTNodeKind {
+        #[visit(visit_factor, fold_factor)]
+        Factor { token: Token },
+    }
 }
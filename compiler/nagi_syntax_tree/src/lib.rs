@@ -1,8 +1,21 @@
+pub mod arena;
 pub mod ast;
 pub mod cst;
+pub mod diff;
+pub mod graph;
 pub mod hst;
 pub mod keywords;
+pub mod pretty;
+pub mod query;
+pub mod serialize;
+pub mod sexpr;
+pub mod span;
 pub mod token;
+pub mod trivia;
+pub mod walk;
+
+#[cfg(test)]
+mod test_trees;
 
 pub enum SyntaxTree {
     AST(ast::ASTNode),
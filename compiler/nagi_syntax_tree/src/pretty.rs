@@ -0,0 +1,489 @@
+mod ast;
+mod cst;
+
+use std::collections::VecDeque;
+
+use crate::keywords::Keyword;
+use crate::token::*;
+use crate::trivia::Comment;
+use serde::Deserialize;
+
+pub use ast::print_ast;
+pub use cst::{format_cst, print_cst};
+
+const INDENT_WIDTH: usize = 4;
+
+// ブロックの`{`を置く場所
+// NextLineは関数、構造体、implの`{`だけを次の行に置く
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BraceStyle {
+    SameLine,
+    NextLine,
+}
+
+// CallParamsとStructExprFieldsの最後の要素の後ろに`,`を置くか
+// Verticalは要素を1行に1つ書いたときだけ置く
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrailingComma {
+    Vertical,
+    Always,
+    Never,
+}
+
+// 整形の設定
+// nagi.tomlの[fmt]で変えられる 書かなかった項目は既定の値になる
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatStyle {
+    pub indent_width: usize,
+    pub max_width: usize, // これを超える呼び出しの引数と構造体式のフィールドは1行に1つ書く
+    pub brace_style: BraceStyle,
+    pub trailing_comma: TrailingComma,
+    pub align_let_rarity: bool, // 続けて並んだur/sr/nrのlet文の`=`を揃える
+}
+
+impl Default for FormatStyle {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            max_width: 100,
+            brace_style: BraceStyle::SameLine,
+            trailing_comma: TrailingComma::Vertical,
+            align_let_rarity: true,
+        }
+    }
+}
+
+// トークンを並べてソースコードを組み立てる
+// 空白は前後のトークンから決め、改行と字下げは呼び出し側が決める
+struct Printer {
+    output: String,
+    line: String,
+    indent: usize,
+    previous: Option<Token>,
+    space: Option<bool>, // 次のトークンの前に空白を入れるかを前後のトークンによらず決める
+
+    // 整形するときだけ使う
+    format: Option<FormatStyle>,
+    comments: VecDeque<(Comment, Option<(usize, usize)>)>, // まだ書いていないコメントと、その直前のトークンの位置
+    last_position: Option<(usize, usize)>, // 最後に書いたソースコード上のトークンの位置
+    last_row: usize,                       // 最後に書いたトークンかコメントが終わる行
+    is_after_comment: bool,
+    align: Option<usize>, // 次のlet文の`=`を、let文の先頭からこの幅の位置に揃える
+    is_measuring: bool,   // 幅を測るために書いている
+}
+
+impl Printer {
+    fn new() -> Self {
+        Self {
+            output: String::new(),
+            line: String::new(),
+            indent: 0,
+            previous: None,
+            space: None,
+            format: None,
+            comments: VecDeque::new(),
+            last_position: None,
+            last_row: 0,
+            is_after_comment: false,
+            align: None,
+            is_measuring: false,
+        }
+    }
+
+    fn with_format(format: &FormatStyle) -> Self {
+        Self {
+            format: Some(format.clone()),
+            ..Self::new()
+        }
+    }
+
+    fn token(&mut self, token: &Token) {
+        if self.line.is_empty() {
+            self.push_indent();
+        } else if self.space.unwrap_or_else(|| {
+            self.previous
+                .as_ref()
+                .is_some_and(|previous| space_between(previous, token))
+        }) {
+            self.line.push(' ');
+        }
+
+        self.line.push_str(&token.to_string());
+        self.previous = Some(token.clone());
+        self.space = None;
+        self.is_after_comment = false;
+    }
+
+    // ソースコード上のトークン
+    // 整形するときは、前にあるコメントと空行を書いてから書く
+    fn source_token(&mut self, token: &Token, position: (usize, usize)) {
+        if self.format.is_some() {
+            self.comments_before(position);
+            if self.is_after_comment && position.0 > self.last_row {
+                self.newline();
+            }
+            // 空行は1つにまとめて残す ただし`{`の直後と`}`の直前は詰める
+            if self.line.is_empty()
+                && self.last_position.is_some()
+                && position.0 > self.last_row + 1
+                && !matches!(
+                    self.previous,
+                    Some(Token::LeftParenthesis(LeftParenthesis::Brace))
+                )
+                && !matches!(token, Token::RightParenthesis(RightParenthesis::Brace))
+            {
+                self.blank_line();
+            }
+        }
+
+        self.token(token);
+        self.last_position = Some(position);
+        self.last_row = position.0 + token.to_string().matches('\n').count();
+    }
+
+    // 書かずに読み飛ばすトークン
+    fn skip_source_token(&mut self, position: (usize, usize)) {
+        self.comments_before(position);
+        self.last_position = Some(position);
+    }
+
+    fn comment(&mut self, comment: &Comment) {
+        // 前のトークンと別の行にあるコメントは行の先頭から書く
+        if comment.position.0 > self.last_row {
+            self.newline();
+            if self.last_position.is_some()
+                && comment.position.0 > self.last_row + 1
+                && !matches!(
+                    self.previous,
+                    Some(Token::LeftParenthesis(LeftParenthesis::Brace))
+                )
+            {
+                self.blank_line();
+            }
+        }
+
+        if self.line.is_empty() {
+            self.push_indent();
+        } else {
+            self.line.push(' ');
+        }
+        self.line.push_str(&comment.text);
+        self.last_row = comment.end_row();
+        self.is_after_comment = true;
+        self.space = Some(true);
+
+        if comment.is_line_comment() {
+            self.newline();
+        }
+    }
+
+    fn comments_before(&mut self, position: (usize, usize)) {
+        while let Some((comment, _)) = self
+            .comments
+            .pop_front_if(|(comment, _)| comment.position < position)
+        {
+            self.comment(&comment);
+        }
+    }
+
+    // 最後に書いたトークンの直後にあるコメント
+    // row_onlyならそのトークンと同じ行にあるものだけ
+    fn comments_after_last_token(&mut self, row_only: bool) {
+        let last_position = self.last_position;
+        let last_row = self.last_row;
+        while let Some((comment, _)) = self.comments.pop_front_if(|(comment, previous)| {
+            *previous == last_position && (!row_only || comment.position.0 == last_row)
+        }) {
+            self.comment(&comment);
+        }
+    }
+
+    fn has_comments_after_last_token(&self) -> bool {
+        self.comments
+            .front()
+            .is_some_and(|(_, previous)| *previous == self.last_position)
+    }
+
+    fn push_indent(&mut self) {
+        let indent_width = self
+            .format
+            .as_ref()
+            .map_or(INDENT_WIDTH, |format| format.indent_width);
+        self.line.push_str(&" ".repeat(indent_width * self.indent));
+    }
+
+    fn line_width(&self) -> usize {
+        self.line.chars().count()
+    }
+
+    // 行の幅がwidthになるまで空白を入れる
+    fn pad_to(&mut self, width: usize) {
+        while self.line_width() < width {
+            self.line.push(' ');
+        }
+    }
+
+    fn keyword(&mut self, keyword: Keyword) {
+        self.token(&Token::Keyword(keyword));
+    }
+
+    fn identifier(&mut self, identifier: &str) {
+        self.token(&Token::Identifier(identifier.to_string()));
+    }
+
+    fn punctuation(&mut self, punctuation: &str) {
+        self.token(&Token::from_punctuation_str(punctuation).unwrap());
+    }
+
+    fn open(&mut self, delimiter: &LeftParenthesis) {
+        self.token(&Token::LeftParenthesis(delimiter.clone()));
+    }
+
+    fn close(&mut self, delimiter: &LeftParenthesis) {
+        self.token(&Token::RightParenthesis(delimiter.closing()));
+    }
+
+    // 二項演算子は前後を空け、単項演算子は被演算子に付ける
+    fn binary_operator(&mut self, token: &Token) {
+        self.space = Some(true);
+        self.token(token);
+        self.space = Some(true);
+    }
+
+    fn unary_operator(&mut self, token: &Token) {
+        self.token(token);
+        self.space = Some(false);
+    }
+
+    fn newline(&mut self) {
+        // 行末のコメントは同じ行に残す
+        self.comments_after_last_token(true);
+        if !self.line.is_empty() {
+            self.output.push_str(&self.line);
+            self.output.push('\n');
+            self.line.clear();
+        }
+    }
+
+    // 項目の間に1行空ける
+    fn blank_line(&mut self) {
+        self.newline();
+        if !self.output.is_empty() && !self.output.ends_with("\n\n") {
+            self.output.push('\n');
+        }
+    }
+
+    fn indent(&mut self) {
+        self.indent += 1;
+    }
+
+    fn dedent(&mut self) {
+        self.indent -= 1;
+    }
+
+    fn finish(mut self) -> String {
+        while let Some((comment, _)) = self.comments.pop_front() {
+            self.comment(&comment);
+        }
+        self.newline();
+        self.output
+    }
+}
+
+// 前のトークンとの間に空白を入れるか
+// 空白を入れても入れなくても、字句解析し直したトークン列は変わらない
+fn space_between(previous: &Token, next: &Token) -> bool {
+    match (previous, next) {
+        (Token::LeftParenthesis(LeftParenthesis::Parenthesis | LeftParenthesis::Brackets), _)
+        | (
+            _,
+            Token::RightParenthesis(RightParenthesis::Parenthesis | RightParenthesis::Brackets),
+        )
+        | (
+            Token::LeftParenthesis(LeftParenthesis::Brace),
+            Token::RightParenthesis(RightParenthesis::Brace),
+        ) => false,
+        // `1.a`は浮動小数点数と区別できるように空ける
+        (
+            Token::Literal(Literal {
+                literal_kind: LiteralKind::Integer,
+                ..
+            }),
+            Token::Dot,
+        ) => true,
+        // `a.b` `a::b` `#[a]` `$a` `&self` `..base` `<>`
+        (
+            Token::Dot
+            | Token::PathSeparater
+            | Token::Pound
+            | Token::Dollar
+            | Token::And
+            | Token::DotDot
+            | Token::LessThan,
+            _,
+        ) => false,
+        (
+            _,
+            Token::Comma
+            | Token::Semicolon
+            | Token::Colon
+            | Token::Dot
+            | Token::PathSeparater
+            | Token::Question,
+        ) => false,
+        // トークン木の中の`=>`と`->`
+        (Token::Equal | Token::Minus, Token::GreaterThan) => false,
+        // `m!(a)` `#![a]` `macro_rules!`
+        (
+            Token::Not,
+            Token::LeftParenthesis(LeftParenthesis::Parenthesis | LeftParenthesis::Brackets),
+        ) => false,
+        (Token::Identifier(_) | Token::Keyword(Keyword::MacroRules), Token::Not) => false,
+        // `f(a)` `a[0]` `f<>`
+        (
+            _,
+            Token::LeftParenthesis(LeftParenthesis::Parenthesis | LeftParenthesis::Brackets)
+            | Token::LessThan,
+        ) => !is_operand_end(previous),
+        _ => true,
+    }
+}
+
+// 直後の括弧が呼び出しや添字になるトークン
+fn is_operand_end(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Identifier(_)
+            | Token::Literal(_)
+            | Token::RightParenthesis(_)
+            | Token::GreaterThan
+            | Token::Question
+            | Token::Keyword(
+                Keyword::SelfValue
+                    | Keyword::SelfType
+                    | Keyword::Super
+                    | Keyword::Crate
+                    | Keyword::Await
+                    | Keyword::Unsafe
+                    | Keyword::True
+                    | Keyword::False
+            )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ASTNode;
+    use crate::test_trees::{ast, cst};
+
+    // `fn f() { let a = expression; }`の文の行
+    fn print_statement(expression: ASTNode) -> String {
+        let tree = ast::function(
+            "f",
+            &[],
+            vec![ast::let_statement(Rarity::Let, "a", expression)],
+        );
+        let printed = print_ast(&tree);
+        printed.lines().nth(1).unwrap().trim().to_string()
+    }
+
+    // 演算子式の左辺は括弧を持たないので、括弧は束縛力から付ける
+    #[test]
+    fn parenthesize_by_binding_power() {
+        let add = ast::binary(BinaryOperator::Add, ast::integer("1"), ast::path("x"));
+        let subtract = ast::binary(BinaryOperator::Sub, ast::integer("2"), ast::path("a"));
+
+        assert_eq!(
+            print_statement(ast::expression(ast::binary(
+                BinaryOperator::Mul,
+                add,
+                ast::integer("2")
+            ))),
+            "let a = (1 + x) * 2;"
+        );
+        assert_eq!(
+            print_statement(ast::expression(ast::binary(
+                BinaryOperator::Sub,
+                ast::integer("1"),
+                ast::expression(subtract.clone()),
+            ))),
+            "let a = 1 - (2 - a);"
+        );
+        assert_eq!(
+            print_statement(ast::expression(ast::binary(
+                BinaryOperator::Sub,
+                subtract,
+                ast::integer("1"),
+            ))),
+            "let a = 2 - a - 1;"
+        );
+    }
+
+    // 式が直接式を持つのは括弧で囲んだ式
+    #[test]
+    fn keep_grouped_expression() {
+        assert_eq!(
+            print_statement(ast::expression(ast::path("a"))),
+            "let a = (a);"
+        );
+    }
+
+    #[test]
+    fn print_items_and_parameters() {
+        let tree = ast::crate_node(vec![
+            ast::function(
+                "f",
+                &[("x", "i32"), ("y", "f32")],
+                vec![
+                    ast::let_statement(Rarity::Ur, "b", ast::integer("1")),
+                    ast::let_statement(Rarity::Sr, "c", ast::integer("2")),
+                    ast::let_statement(Rarity::Nr, "d", ast::integer("3")),
+                ],
+            ),
+            ast::function("g", &[], vec![]),
+        ]);
+        let expected = "fn f(x: i32, y: f32) {
+    ur b = 1;
+    sr c = 2;
+    nr d = 3;
+}
+
+fn g() {}
+";
+        assert_eq!(print_ast(&tree), expected);
+    }
+
+    #[test]
+    fn print_cst_block() {
+        // {
+        //     let a = 1 + 2;
+        //     ur b = 3;
+        // }
+        let tree = cst::block(
+            (1, 1),
+            vec![
+                cst::let_statement(
+                    Keyword::Let,
+                    "a",
+                    cst::binary(
+                        Token::Plus,
+                        (2, 15),
+                        cst::integer("1", (2, 13)),
+                        cst::integer("2", (2, 17)),
+                    ),
+                    (2, 5),
+                ),
+                cst::let_statement(Keyword::Ur, "b", cst::integer("3", (3, 12)), (3, 5)),
+            ],
+            (4, 1),
+        );
+        assert_eq!(
+            print_cst(&tree),
+            "{\n    let a = 1 + 2;\n    ur b = 3;\n}\n"
+        );
+    }
+}
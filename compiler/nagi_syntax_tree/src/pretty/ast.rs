@@ -0,0 +1,706 @@
+use super::Printer;
+use crate::ast::{ASTNode, ASTNodeKind};
+use crate::keywords::Keyword;
+use crate::token::*;
+
+// ASTからソースコードを作る
+// 括弧は元のソースコードにあったものと、演算子の束縛力から必要になるものだけを書く
+pub fn print_ast(node: &ASTNode) -> String {
+    let mut printer = Printer::new();
+    printer.ast_node(node);
+    printer.finish()
+}
+
+// 式を書く位置
+// 演算子式を括弧で囲む必要があるかを決める
+#[derive(Clone, Copy)]
+enum Position {
+    Top,
+    Left(u16),   // 二項演算子の左辺(演算子の左の束縛力)
+    Right(u16),  // 二項演算子の右辺(演算子の右の束縛力)
+    Prefix(u16), // 単項演算子の被演算子(演算子の束縛力)
+    Receiver,    // 呼び出し、フィールド、添字、awaitの左
+}
+
+impl Printer {
+    fn ast_node(&mut self, node: &ASTNode) {
+        match &node.node_kind {
+            ASTNodeKind::Crate {
+                inner_attribute,
+                item,
+            } => {
+                for attribute in inner_attribute {
+                    self.ast_node(attribute);
+                    self.newline();
+                }
+                for item in item {
+                    self.blank_line();
+                    self.statement(item);
+                }
+            }
+            ASTNodeKind::Factor { token } => self.token(token),
+            ASTNodeKind::Literal { literal } | ASTNodeKind::LiteralPattern { literal } => {
+                self.token(&Token::Literal(literal.clone()))
+            }
+            ASTNodeKind::BinaryOperator { .. }
+            | ASTNodeKind::UnaryOperator { .. }
+            | ASTNodeKind::Expression { .. }
+            | ASTNodeKind::ExpressionWithoutBlock { .. } => self.expression(node, Position::Top),
+            ASTNodeKind::InnerAttribute { attribute } => {
+                self.punctuation("#");
+                self.punctuation("!");
+                self.open(&LeftParenthesis::Brackets);
+                self.ast_node(attribute);
+                self.close(&LeftParenthesis::Brackets);
+            }
+            ASTNodeKind::OuterAttribute { attribute } => {
+                self.punctuation("#");
+                self.open(&LeftParenthesis::Brackets);
+                self.ast_node(attribute);
+                self.close(&LeftParenthesis::Brackets);
+            }
+            ASTNodeKind::Attribute {
+                is_unsafe,
+                simple_path,
+                attribute_input,
+            } => {
+                if *is_unsafe {
+                    self.keyword(Keyword::Unsafe);
+                    self.open(&LeftParenthesis::Parenthesis);
+                }
+                self.ast_node(simple_path);
+                if let Some(attribute_input) = attribute_input {
+                    if !matches!(
+                        attribute_input.node_kind,
+                        ASTNodeKind::DelimTokenTree { .. }
+                    ) {
+                        self.punctuation("=");
+                    }
+                    self.ast_node(attribute_input);
+                }
+                if *is_unsafe {
+                    self.close(&LeftParenthesis::Parenthesis);
+                }
+            }
+            ASTNodeKind::SimplePath {
+                simple_path_segment,
+                repeat_simple_path_segment,
+            } => {
+                self.ast_node(simple_path_segment);
+                for segment in repeat_simple_path_segment {
+                    self.punctuation("::");
+                    self.ast_node(segment);
+                }
+            }
+            ASTNodeKind::DelimTokenTree {
+                delimiter,
+                token_tree,
+            } => {
+                self.open(delimiter);
+                for token_tree in token_tree {
+                    self.ast_node(token_tree);
+                }
+                self.close(delimiter);
+            }
+            ASTNodeKind::Visibility {} => self.keyword(Keyword::Pub),
+            ASTNodeKind::MacroInvocation {
+                simple_path,
+                delim_token_tree,
+            } => {
+                self.ast_node(simple_path);
+                self.punctuation("!");
+                self.ast_node(delim_token_tree);
+            }
+            ASTNodeKind::BuiltinMacroInvocation {
+                builtin_macro,
+                call_params,
+            } => {
+                self.identifier(builtin_macro.name());
+                self.punctuation("!");
+                self.call_params(call_params);
+            }
+            ASTNodeKind::MacroRulesDefinition {
+                identifier,
+                delim_token_tree,
+            } => {
+                self.keyword(Keyword::MacroRules);
+                self.punctuation("!");
+                self.identifier(identifier);
+                self.ast_node(delim_token_tree);
+                if !is_brace_token_tree(delim_token_tree) {
+                    self.punctuation(";");
+                }
+            }
+            ASTNodeKind::Function {
+                outer_attribute,
+                function_qualifiers,
+                identifier,
+                generic_params,
+                function_parameters,
+                function_return_type,
+                where_clause: _,
+                block_expression,
+            } => {
+                self.outer_attributes(outer_attribute);
+                self.ast_node(function_qualifiers);
+                self.keyword(Keyword::Fn);
+                self.identifier(identifier);
+                if generic_params.is_some() {
+                    self.punctuation("<");
+                    self.punctuation(">");
+                }
+                self.open(&LeftParenthesis::Parenthesis);
+                if let Some(function_parameters) = function_parameters {
+                    self.ast_node(function_parameters);
+                }
+                self.close(&LeftParenthesis::Parenthesis);
+                if let Some(function_return_type) = function_return_type {
+                    self.punctuation("->");
+                    self.ast_node(function_return_type);
+                }
+                match block_expression {
+                    Some(block_expression) => self.ast_node(block_expression),
+                    None => self.punctuation(";"),
+                }
+            }
+            ASTNodeKind::FunctionParameters {
+                self_param,
+                function_params,
+            } => {
+                let parameters = self_param
+                    .iter()
+                    .map(|self_param| self_param.as_ref())
+                    .chain(function_params);
+                for (index, parameter) in parameters.enumerate() {
+                    if index > 0 {
+                        self.punctuation(",");
+                    }
+                    self.ast_node(parameter);
+                }
+            }
+            ASTNodeKind::SelfParam {
+                outer_attribute,
+                reference,
+                mut_keyword,
+                type_expression,
+            } => {
+                self.inline_outer_attributes(outer_attribute);
+                if *reference {
+                    self.punctuation("&");
+                }
+                if *mut_keyword {
+                    self.keyword(Keyword::Mut);
+                }
+                self.keyword(Keyword::SelfValue);
+                if let Some(type_expression) = type_expression {
+                    self.punctuation(":");
+                    self.ast_node(type_expression);
+                }
+            }
+            ASTNodeKind::FunctionParam {
+                outer_attribute,
+                pattern_no_top_alt,
+                type_expression,
+            } => {
+                self.inline_outer_attributes(outer_attribute);
+                self.ast_node(pattern_no_top_alt);
+                self.punctuation(":");
+                self.ast_node(type_expression);
+            }
+            ASTNodeKind::TypeAlias {
+                outer_attribute,
+                identifier,
+                type_expression,
+            } => {
+                self.outer_attributes(outer_attribute);
+                self.keyword(Keyword::Type);
+                self.identifier(identifier);
+                self.punctuation("=");
+                self.ast_node(type_expression);
+                self.punctuation(";");
+            }
+            ASTNodeKind::Struct {
+                outer_attribute,
+                identifier,
+                struct_fields,
+            } => {
+                self.outer_attributes(outer_attribute);
+                self.keyword(Keyword::Struct);
+                self.identifier(identifier);
+                if struct_fields.is_empty() {
+                    self.punctuation(";");
+                    return;
+                }
+
+                self.open(&LeftParenthesis::Brace);
+                self.indent();
+                for struct_field in struct_fields {
+                    self.newline();
+                    self.ast_node(struct_field);
+                    self.punctuation(",");
+                }
+                self.dedent();
+                self.newline();
+                self.close(&LeftParenthesis::Brace);
+            }
+            ASTNodeKind::StructField {
+                outer_attribute,
+                identifier,
+                type_expression,
+            } => {
+                self.outer_attributes(outer_attribute);
+                self.identifier(identifier);
+                self.punctuation(":");
+                self.ast_node(type_expression);
+            }
+            ASTNodeKind::Implementation {
+                outer_attribute,
+                trait_path,
+                type_expression,
+                associated_items,
+            } => {
+                self.outer_attributes(outer_attribute);
+                self.keyword(Keyword::Impl);
+                if let Some(trait_path) = trait_path {
+                    self.ast_node(trait_path);
+                    self.keyword(Keyword::For);
+                }
+                self.ast_node(type_expression);
+                self.open(&LeftParenthesis::Brace);
+                if !associated_items.is_empty() {
+                    self.indent();
+                    for (index, associated_item) in associated_items.iter().enumerate() {
+                        if index > 0 {
+                            self.blank_line();
+                        }
+                        self.newline();
+                        self.ast_node(associated_item);
+                    }
+                    self.dedent();
+                    self.newline();
+                }
+                self.close(&LeftParenthesis::Brace);
+            }
+            ASTNodeKind::TypePath {
+                path_ident_segments,
+            } => self.separated(path_ident_segments, "::"),
+            ASTNodeKind::FunctionQualifiers {
+                const_keyword,
+                async_keyword,
+                item_safety,
+                extern_keyword,
+                abi,
+            } => {
+                if *const_keyword {
+                    self.keyword(Keyword::Const);
+                }
+                if *async_keyword {
+                    self.keyword(Keyword::Async);
+                }
+                if let Some(item_safety) = item_safety {
+                    self.ast_node(item_safety);
+                }
+                if *extern_keyword {
+                    self.keyword(Keyword::Extern);
+                }
+                if let Some(abi) = abi {
+                    self.ast_node(abi);
+                }
+            }
+            ASTNodeKind::ExpressionWithBlock {
+                outer_attribute,
+                expression_with_block,
+            } => {
+                self.inline_outer_attributes(outer_attribute);
+                self.ast_node(expression_with_block);
+            }
+            ASTNodeKind::PathExpression { expression } => self.ast_node(expression),
+            ASTNodeKind::PathInExpression {
+                path_expr_segment,
+                repeat_path_expr_segment,
+            } => {
+                self.ast_node(path_expr_segment);
+                for segment in repeat_path_expr_segment {
+                    self.punctuation("::");
+                    self.ast_node(segment);
+                }
+            }
+            ASTNodeKind::PathExprSegment {
+                path_ident_segment,
+                generic_args,
+            } => {
+                self.ast_node(path_ident_segment);
+                if let Some(generic_args) = generic_args {
+                    self.punctuation("::");
+                    self.ast_node(generic_args);
+                }
+            }
+            ASTNodeKind::StructExpression {
+                path_in_expression,
+                struct_expr_fields,
+                struct_base,
+            } => {
+                self.ast_node(path_in_expression);
+                self.open(&LeftParenthesis::Brace);
+                self.separated(struct_expr_fields, ",");
+                if let Some(struct_base) = struct_base {
+                    if !struct_expr_fields.is_empty() {
+                        self.punctuation(",");
+                    }
+                    self.punctuation("..");
+                    self.ast_node(struct_base);
+                }
+                self.close(&LeftParenthesis::Brace);
+            }
+            ASTNodeKind::StructExprField {
+                outer_attribute,
+                identifier,
+                expression,
+            } => {
+                self.inline_outer_attributes(outer_attribute);
+                self.identifier(identifier);
+                if let Some(expression) = expression {
+                    self.punctuation(":");
+                    self.ast_node(expression);
+                }
+            }
+            ASTNodeKind::CallExpression {
+                expression,
+                call_params,
+            } => {
+                self.expression(expression, Position::Receiver);
+                self.call_params(call_params);
+            }
+            ASTNodeKind::MethodCallExpression {
+                expression,
+                path_expr_segment,
+                call_params,
+            } => {
+                self.expression(expression, Position::Receiver);
+                self.punctuation(".");
+                self.ast_node(path_expr_segment);
+                self.call_params(call_params);
+            }
+            ASTNodeKind::FieldExpression {
+                expression,
+                identifier,
+            } => {
+                self.expression(expression, Position::Receiver);
+                self.punctuation(".");
+                self.identifier(identifier);
+            }
+            ASTNodeKind::AwaitExpression { expression } => {
+                self.expression(expression, Position::Receiver);
+                self.punctuation(".");
+                self.keyword(Keyword::Await);
+            }
+            ASTNodeKind::IndexExpression { expression, index } => {
+                self.expression(expression, Position::Receiver);
+                self.open(&LeftParenthesis::Brackets);
+                self.ast_node(index);
+                self.close(&LeftParenthesis::Brackets);
+            }
+            ASTNodeKind::ReturnExpression { expression } => {
+                self.keyword(Keyword::Return);
+                if let Some(expression) = expression {
+                    self.ast_node(expression);
+                }
+            }
+            ASTNodeKind::IfExpression {
+                expression,
+                block_expression,
+                else_expression,
+            } => {
+                self.keyword(Keyword::If);
+                self.ast_node(expression);
+                self.ast_node(block_expression);
+                self.else_expression(else_expression.as_deref());
+            }
+            ASTNodeKind::LetChain {
+                let_chain_condition,
+            } => {
+                for (index, condition) in let_chain_condition.iter().enumerate() {
+                    if index > 0 {
+                        self.binary_operator(&Token::AndAnd);
+                    }
+                    self.ast_node(condition);
+                }
+            }
+            ASTNodeKind::LetChainCondition { pattern, scrutinee } => {
+                self.keyword(Keyword::Let);
+                self.ast_node(pattern);
+                self.punctuation("=");
+                self.ast_node(scrutinee);
+            }
+            ASTNodeKind::IfLetExpression {
+                pattern,
+                scrutinee,
+                block_expression,
+                else_expression,
+            } => {
+                self.keyword(Keyword::If);
+                self.keyword(Keyword::Let);
+                self.ast_node(pattern);
+                self.punctuation("=");
+                self.ast_node(scrutinee);
+                self.ast_node(block_expression);
+                self.else_expression(else_expression.as_deref());
+            }
+            ASTNodeKind::BlockExpression {
+                inner_attribute,
+                statements,
+            } => {
+                let is_empty = statements.as_ref().is_none_or(|statements| {
+                    matches!(&statements.node_kind, ASTNodeKind::Statements { statements } if statements.is_empty())
+                });
+
+                self.open(&LeftParenthesis::Brace);
+                if !inner_attribute.is_empty() || !is_empty {
+                    self.indent();
+                    for attribute in inner_attribute {
+                        self.newline();
+                        self.ast_node(attribute);
+                    }
+                    if let Some(statements) = statements {
+                        self.ast_node(statements);
+                    }
+                    self.dedent();
+                    self.newline();
+                }
+                self.close(&LeftParenthesis::Brace);
+            }
+            ASTNodeKind::ConstBlockExpression { block_expression } => {
+                self.keyword(Keyword::Const);
+                self.ast_node(block_expression);
+            }
+            ASTNodeKind::UnsafeBlockExpression { block_expression } => {
+                self.keyword(Keyword::Unsafe);
+                self.ast_node(block_expression);
+            }
+            ASTNodeKind::AsyncBlockExpression {
+                move_keyword,
+                block_expression,
+            } => {
+                self.keyword(Keyword::Async);
+                if *move_keyword {
+                    self.keyword(Keyword::Move);
+                }
+                self.ast_node(block_expression);
+            }
+            ASTNodeKind::Pattern { pattern_no_top_alt } => {
+                for (index, pattern) in pattern_no_top_alt.iter().enumerate() {
+                    if index > 0 {
+                        self.binary_operator(&Token::Or);
+                    }
+                    self.ast_node(pattern);
+                }
+            }
+            ASTNodeKind::IdentifierPattern {
+                ref_keyword,
+                mut_keyword,
+                identifier,
+                pattern_no_top_alt,
+            } => {
+                if *ref_keyword {
+                    self.keyword(Keyword::Ref);
+                }
+                if *mut_keyword {
+                    self.keyword(Keyword::Mut);
+                }
+                self.identifier(identifier);
+                if let Some(pattern_no_top_alt) = pattern_no_top_alt {
+                    self.punctuation("@");
+                    self.ast_node(pattern_no_top_alt);
+                }
+            }
+            ASTNodeKind::WildcardPattern { wildcard: token }
+            | ASTNodeKind::RestPattern { rest: token } => self.ast_node(token),
+            ASTNodeKind::Statements { statements } => {
+                for statement in statements {
+                    self.newline();
+                    self.ast_node(statement);
+                }
+            }
+            ASTNodeKind::Statement { statement } => match statement {
+                Some(statement) => self.statement(statement),
+                None => self.punctuation(";"),
+            },
+            ASTNodeKind::LetStatement {
+                outer_attribute,
+                rarity,
+                pattern_no_top_alt,
+                type_expression,
+                expression,
+                block_expression,
+            } => {
+                self.outer_attributes(outer_attribute);
+                self.keyword(rarity_keyword(rarity));
+                self.ast_node(pattern_no_top_alt);
+                if let Some(type_expression) = type_expression {
+                    self.punctuation(":");
+                    self.ast_node(type_expression);
+                }
+                if let Some(expression) = expression {
+                    self.punctuation("=");
+                    self.ast_node(expression);
+                }
+                if let Some(block_expression) = block_expression {
+                    self.keyword(Keyword::Else);
+                    self.ast_node(block_expression);
+                }
+                self.punctuation(";");
+            }
+        }
+    }
+
+    // 文として書き、必要なら`;`を付ける
+    fn statement(&mut self, node: &ASTNode) {
+        self.ast_node(node);
+        let needs_semicolon = match &node.node_kind {
+            ASTNodeKind::Expression { .. }
+            | ASTNodeKind::ExpressionWithoutBlock { .. }
+            | ASTNodeKind::BuiltinMacroInvocation { .. } => true,
+            ASTNodeKind::MacroInvocation {
+                delim_token_tree, ..
+            } => !is_brace_token_tree(delim_token_tree),
+            _ => false,
+        };
+        if needs_semicolon {
+            self.punctuation(";");
+        }
+    }
+
+    // 位置に合わせて括弧を付けて式を書く
+    fn expression(&mut self, node: &ASTNode, position: Position) {
+        match &node.node_kind {
+            ASTNodeKind::ExpressionWithoutBlock {
+                outer_attribute,
+                expression,
+            } => {
+                self.inline_outer_attributes(outer_attribute);
+
+                // ExpressionWithoutBlockが直接ExpressionWithoutBlockやブロックを持つ式を持つのは
+                // 括弧で囲んだ式
+                if matches!(
+                    expression.node_kind,
+                    ASTNodeKind::ExpressionWithoutBlock { .. }
+                        | ASTNodeKind::ExpressionWithBlock { .. }
+                        | ASTNodeKind::BlockExpression { .. }
+                        | ASTNodeKind::ConstBlockExpression { .. }
+                        | ASTNodeKind::UnsafeBlockExpression { .. }
+                        | ASTNodeKind::IfExpression { .. }
+                        | ASTNodeKind::IfLetExpression { .. }
+                ) {
+                    self.parenthesized(expression);
+                } else {
+                    self.expression(expression, position);
+                }
+            }
+            ASTNodeKind::Expression { expression } => self.expression(expression, position),
+            _ if needs_parentheses(node, position) => self.parenthesized(node),
+            ASTNodeKind::BinaryOperator {
+                operator,
+                left,
+                right,
+            } => {
+                let (left_binding_power, right_binding_power) = operator.binding_power();
+                self.expression(left, Position::Left(left_binding_power));
+                self.binary_operator(&Token::from_punctuation_str(operator.as_str()).unwrap());
+                self.expression(right, Position::Right(right_binding_power));
+            }
+            ASTNodeKind::UnaryOperator {
+                operator,
+                expression,
+            } => {
+                self.unary_operator(&Token::from_punctuation_str(operator.as_str()).unwrap());
+                self.expression(expression, Position::Prefix(operator.binding_power()));
+            }
+            _ => self.ast_node(node),
+        }
+    }
+
+    fn parenthesized(&mut self, node: &ASTNode) {
+        self.open(&LeftParenthesis::Parenthesis);
+        self.expression(node, Position::Top);
+        self.close(&LeftParenthesis::Parenthesis);
+    }
+
+    fn else_expression(&mut self, else_expression: Option<&ASTNode>) {
+        if let Some(else_expression) = else_expression {
+            self.keyword(Keyword::Else);
+            self.ast_node(else_expression);
+        }
+    }
+
+    fn call_params(&mut self, call_params: &[ASTNode]) {
+        self.open(&LeftParenthesis::Parenthesis);
+        self.separated(call_params, ",");
+        self.close(&LeftParenthesis::Parenthesis);
+    }
+
+    fn separated(&mut self, nodes: &[ASTNode], separator: &str) {
+        for (index, node) in nodes.iter().enumerate() {
+            if index > 0 {
+                self.punctuation(separator);
+            }
+            self.ast_node(node);
+        }
+    }
+
+    // 項目の属性は1行に1つ、式や引数の属性は同じ行に書く
+    fn outer_attributes(&mut self, outer_attribute: &[ASTNode]) {
+        for attribute in outer_attribute {
+            self.ast_node(attribute);
+            self.newline();
+        }
+    }
+
+    fn inline_outer_attributes(&mut self, outer_attribute: &[ASTNode]) {
+        for attribute in outer_attribute {
+            self.ast_node(attribute);
+        }
+    }
+}
+
+// 束縛力が足りず、括弧で囲まないと別の木になる式か
+fn needs_parentheses(node: &ASTNode, position: Position) -> bool {
+    match (&node.node_kind, position) {
+        (_, Position::Top) => false,
+        (ASTNodeKind::BinaryOperator { operator, .. }, Position::Left(left_binding_power)) => {
+            left_binding_power >= operator.binding_power().1
+        }
+        (ASTNodeKind::UnaryOperator { operator, .. }, Position::Left(left_binding_power)) => {
+            left_binding_power >= operator.binding_power()
+        }
+        (
+            ASTNodeKind::BinaryOperator { operator, .. },
+            Position::Right(binding_power) | Position::Prefix(binding_power),
+        ) => operator.binding_power().0 < binding_power,
+        (
+            ASTNodeKind::BinaryOperator { .. } | ASTNodeKind::UnaryOperator { .. },
+            Position::Receiver,
+        ) => true,
+        // returnは右側を全部読むので、後ろに演算子が続くなら囲む
+        (ASTNodeKind::ReturnExpression { .. }, Position::Left(_) | Position::Receiver) => true,
+        _ => false,
+    }
+}
+
+fn is_brace_token_tree(node: &ASTNode) -> bool {
+    matches!(
+        node.node_kind,
+        ASTNodeKind::DelimTokenTree {
+            delimiter: LeftParenthesis::Brace,
+            ..
+        }
+    )
+}
+
+fn rarity_keyword(rarity: &Rarity) -> Keyword {
+    match rarity {
+        Rarity::Ur => Keyword::Ur,
+        Rarity::Sr => Keyword::Sr,
+        Rarity::Nr => Keyword::Nr,
+        Rarity::Let => Keyword::Let,
+    }
+}
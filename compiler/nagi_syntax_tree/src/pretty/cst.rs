@@ -0,0 +1,448 @@
+use std::ptr;
+
+use super::{BraceStyle, FormatStyle, Printer, TrailingComma};
+use crate::cst::{CSTNode, CSTNodeKind};
+use crate::keywords::Keyword;
+use crate::token::*;
+use crate::trivia::Comment;
+
+// CSTからソースコードを作る
+// トークンは木にあるものをそのまま書き、空白と改行だけを整える
+pub fn print_cst(node: &CSTNode) -> String {
+    let mut printer = Printer::new();
+    printer.cst_node(node);
+    printer.finish()
+}
+
+// CSTとコメントから整形したソースコードを作る
+// print_cstと違い、空行を1つまで残し、呼び出しの引数などの`,`はformatに従って付け直す
+pub fn format_cst(node: &CSTNode, comments: &[Comment], format: &FormatStyle) -> String {
+    let mut positions = vec![];
+    token_positions(node, &mut positions);
+    positions.sort();
+
+    let mut printer = Printer::with_format(format);
+    printer.comments = comments
+        .iter()
+        .map(|comment| {
+            let index = positions.partition_point(|position| *position < comment.position);
+            let previous = index.checked_sub(1).map(|index| positions[index]);
+            (comment.clone(), previous)
+        })
+        .collect();
+    printer.cst_node(node);
+    printer.finish()
+}
+
+fn token_positions(node: &CSTNode, positions: &mut Vec<(usize, usize)>) {
+    match &node.node_kind {
+        CSTNodeKind::Factor { row, column, .. }
+        | CSTNodeKind::Literal { row, column, .. }
+        | CSTNodeKind::Operator { row, column, .. } => positions.push((*row, *column)),
+        CSTNodeKind::LiteralPattern {
+            minus, row, column, ..
+        } => {
+            if let Some(minus) = minus {
+                token_positions(minus, positions);
+            }
+            positions.push((*row, *column));
+        }
+        _ => {}
+    }
+    for child in node.nodes_in_source_order() {
+        token_positions(child, positions);
+    }
+}
+
+impl Printer {
+    fn cst_node(&mut self, node: &CSTNode) {
+        match &node.node_kind {
+            CSTNodeKind::Crate {
+                inner_attributes,
+                items,
+            } => {
+                for attribute in inner_attributes {
+                    self.cst_node(attribute);
+                    self.newline();
+                }
+                for item in items {
+                    self.blank_line();
+                    self.cst_node(item);
+                }
+            }
+            CSTNodeKind::Factor { token, row, column } => self.source_token(token, (*row, *column)),
+            CSTNodeKind::Literal {
+                literal,
+                row,
+                column,
+            } => self.source_token(&Token::Literal(literal.clone()), (*row, *column)),
+            CSTNodeKind::LiteralPattern {
+                minus,
+                literal,
+                row,
+                column,
+            } => {
+                if let Some(minus) = minus {
+                    self.cst_node(minus);
+                    self.space = Some(false);
+                }
+                self.source_token(&Token::Literal(literal.clone()), (*row, *column));
+            }
+            // 二項演算子は左辺と右辺の間、単項演算子は被演算子の前
+            CSTNodeKind::Operator { token, row, column } => match node.children.as_slice() {
+                [left, right] => {
+                    self.cst_node(left);
+                    self.space = Some(true);
+                    self.source_token(token, (*row, *column));
+                    self.space = Some(true);
+                    self.cst_node(right);
+                }
+                children => {
+                    self.source_token(token, (*row, *column));
+                    self.space = Some(false);
+                    for child in children {
+                        self.cst_node(child);
+                    }
+                }
+            },
+            CSTNodeKind::Function { .. }
+            | CSTNodeKind::TypeAlias { .. }
+            | CSTNodeKind::Struct { .. }
+            | CSTNodeKind::StructField { .. } => {
+                // 属性は1行に1つ書く
+                for child in node.nodes_in_source_order() {
+                    if self.is_next_line_brace(node, child) {
+                        self.newline();
+                    }
+                    self.cst_node(child);
+                    if matches!(child.node_kind, CSTNodeKind::OuterAttribute { .. }) {
+                        self.newline();
+                    }
+                }
+            }
+            // 揃える時は`=`の前を空白で埋める
+            CSTNodeKind::LetStatement { rarity, equal, .. } => {
+                let align = self.align.take();
+                let mut start = 0;
+                for child in node.nodes_in_source_order() {
+                    if let (Some(width), Some(equal)) = (align, equal) {
+                        if ptr::eq(child, equal.as_ref()) {
+                            self.pad_to(start + width);
+                        }
+                    }
+                    self.cst_node(child);
+                    if ptr::eq(child, rarity.as_ref()) {
+                        start = self.line_width()
+                            - Printer::measure(&self.format, &[child]).chars().count();
+                    }
+                    if matches!(child.node_kind, CSTNodeKind::OuterAttribute { .. }) {
+                        self.newline();
+                    }
+                }
+            }
+            CSTNodeKind::StructFields { .. } => {
+                self.indent();
+                for child in node.nodes_in_source_order() {
+                    if matches!(child.node_kind, CSTNodeKind::StructField { .. }) {
+                        self.newline();
+                    }
+                    self.cst_node(child);
+                }
+                self.comments_after_last_token(false);
+                self.dedent();
+                self.newline();
+            }
+            CSTNodeKind::Implementation {
+                outer_attribute,
+                visibility,
+                impl_keyword,
+                trait_path,
+                type_expression,
+                left_brace,
+                associated_items,
+                right_brace,
+            } => {
+                for attribute in outer_attribute {
+                    self.cst_node(attribute);
+                    self.newline();
+                }
+                if let Some(visibility) = visibility {
+                    self.cst_node(visibility);
+                }
+                self.cst_node(impl_keyword);
+                if let Some((trait_path, for_keyword)) = trait_path {
+                    self.cst_node(trait_path);
+                    self.cst_node(for_keyword);
+                }
+                self.cst_node(type_expression);
+                if self.is_next_line_brace(node, left_brace) {
+                    self.newline();
+                }
+                self.cst_node(left_brace);
+                if !associated_items.is_empty() || self.has_comments_after_last_token() {
+                    self.indent();
+                    for (index, associated_item) in associated_items.iter().enumerate() {
+                        if index > 0 {
+                            self.blank_line();
+                        }
+                        self.newline();
+                        self.cst_node(associated_item);
+                    }
+                    self.comments_after_last_token(false);
+                    self.dedent();
+                    self.newline();
+                }
+                self.cst_node(right_brace);
+            }
+            // 文は1行に1つ書く
+            CSTNodeKind::BlockExpression {
+                left_brace,
+                inner_attribute,
+                statements,
+                right_brace,
+            } => {
+                self.cst_node(left_brace);
+                if !inner_attribute.is_empty()
+                    || statements.is_some()
+                    || self.has_comments_after_last_token()
+                {
+                    self.indent();
+                    for attribute in inner_attribute {
+                        self.newline();
+                        self.cst_node(attribute);
+                    }
+                    if let Some(statements) = statements {
+                        let aligns = self.let_aligns(&statements.children);
+                        for (statement, align) in statements.children.iter().zip(aligns) {
+                            self.newline();
+                            self.align = align;
+                            self.cst_node(statement);
+                        }
+                    }
+                    self.comments_after_last_token(false);
+                    self.dedent();
+                    self.newline();
+                }
+                self.cst_node(right_brace);
+            }
+            CSTNodeKind::CallParams {
+                expression,
+                comma_and_expression,
+                comma,
+            } if self.format.is_some() => {
+                let mut items = vec![(expression.as_ref(), None)];
+                for (separator, expression) in comma_and_expression {
+                    items.last_mut().unwrap().1 = Some(separator);
+                    items.push((expression, None));
+                }
+                items.last_mut().unwrap().1 = comma.as_deref();
+                self.list(&items, true);
+            }
+            CSTNodeKind::StructExprFields {
+                struct_expr_filed,
+                struct_expr_filed_repeat,
+                comma,
+                struct_base,
+            } if self.format.is_some() => {
+                let mut items = vec![(struct_expr_filed.as_ref(), None)];
+                for (separator, field) in struct_expr_filed_repeat {
+                    items.last_mut().unwrap().1 = Some(separator);
+                    items.push((field, None));
+                }
+                items.last_mut().unwrap().1 = comma.as_deref();
+                // `..base`の後ろには`,`を置けない
+                if let Some(struct_base) = struct_base {
+                    items.push((struct_base, None));
+                }
+                self.list(&items, struct_base.is_none());
+            }
+            _ => {
+                for child in node.nodes_in_source_order() {
+                    self.cst_node(child);
+                }
+            }
+        }
+    }
+
+    // 呼び出しの引数と構造体式のフィールドを`,`で区切って書く
+    // 1行に収まらない時やコメントを含む時は1行に1つ書く
+    // 要素の後ろの`,`はソースコード上のものを使い、無ければ足す
+    fn list(&mut self, items: &[(&CSTNode, Option<&CSTNode>)], can_trail: bool) {
+        let format = self.format.clone().unwrap();
+        let is_vertical = !self.is_measuring && {
+            let printed: Vec<String> = items
+                .iter()
+                .map(|(item, _)| Printer::measure(&self.format, &[item]))
+                .collect();
+            let width = printed
+                .iter()
+                .map(|printed| printed.chars().count() + 2)
+                .sum::<usize>();
+            let is_multiline = printed.iter().any(|printed| printed.contains('\n'));
+            let start = items.first().and_then(|(item, _)| item.span());
+            let end = items.last().and_then(|(item, _)| item.span());
+            let has_comment = start.zip(end).is_some_and(|(start, end)| {
+                self.comments.iter().any(|(comment, _)| {
+                    start.start < comment.position && comment.position < end.end
+                })
+            });
+
+            has_comment
+                || (items.len() > 1 && is_multiline)
+                || self.line_width() + width > format.max_width
+        };
+        let has_trailing_comma = can_trail
+            && match format.trailing_comma {
+                TrailingComma::Vertical => is_vertical,
+                TrailingComma::Always => true,
+                TrailingComma::Never => false,
+            };
+
+        if is_vertical {
+            self.indent();
+        }
+        for (index, (item, comma)) in items.iter().enumerate() {
+            if is_vertical {
+                self.newline();
+            }
+            self.cst_node(item);
+            let has_comma = index + 1 < items.len() || has_trailing_comma;
+            match comma {
+                Some(comma) if has_comma => self.cst_node(comma),
+                Some(comma) => {
+                    if let Some(span) = comma.span() {
+                        self.skip_source_token(span.start);
+                    }
+                }
+                None if has_comma => self.punctuation(","),
+                None => {}
+            }
+        }
+        if is_vertical {
+            self.comments_after_last_token(false);
+            self.dedent();
+            self.newline();
+        }
+    }
+
+    // 続けて並んだur/sr/nrのlet文ごとに、`=`を揃える幅
+    // 空行を挟むと別の並びにする
+    fn let_aligns(&self, statements: &[CSTNode]) -> Vec<Option<usize>> {
+        let mut aligns = vec![None; statements.len()];
+        if !self
+            .format
+            .as_ref()
+            .is_some_and(|format| format.align_let_rarity)
+        {
+            return aligns;
+        }
+
+        let widths: Vec<Option<usize>> = statements
+            .iter()
+            .map(|statement| self.let_prefix_width(statement))
+            .collect();
+        let mut start = 0;
+        for index in 0..=statements.len() {
+            let continues = index < statements.len()
+                && widths[index].is_some()
+                && index > start
+                && !has_blank_line(&statements[index - 1], &statements[index]);
+            if continues {
+                continue;
+            }
+
+            if let Some(width) = widths[start..index].iter().flatten().max() {
+                for align in &mut aligns[start..index] {
+                    *align = Some(*width);
+                }
+            }
+            start = index;
+            if index < statements.len() && widths[index].is_none() {
+                start = index + 1;
+            }
+        }
+
+        aligns
+    }
+
+    // `ur a: T = b;`の`ur a: T`の幅
+    // 属性があるもの、`let`、`=`が無いものは揃えない
+    fn let_prefix_width(&self, statement: &CSTNode) -> Option<usize> {
+        let CSTNodeKind::Statement { statement } = &statement.node_kind else {
+            return None;
+        };
+        let CSTNodeKind::LetStatement {
+            outer_attribute,
+            rarity,
+            pattern_no_top_alt,
+            colon,
+            type_expression,
+            equal: Some(_),
+            ..
+        } = &statement.node_kind
+        else {
+            return None;
+        };
+        let is_rarity = matches!(
+            rarity.node_kind,
+            CSTNodeKind::Factor {
+                token: Token::Keyword(Keyword::Ur | Keyword::Sr | Keyword::Nr),
+                ..
+            }
+        );
+        if !outer_attribute.is_empty() || !is_rarity {
+            return None;
+        }
+
+        let mut nodes = vec![rarity.as_ref(), pattern_no_top_alt.as_ref()];
+        nodes.extend(colon.as_deref());
+        nodes.extend(type_expression.as_deref());
+        let printed = Printer::measure(&self.format, &nodes);
+        (!printed.contains('\n')).then(|| printed.chars().count())
+    }
+
+    // brace_styleがNextLineなら、関数、構造体、implの`{`の前で改行する
+    fn is_next_line_brace(&self, node: &CSTNode, child: &CSTNode) -> bool {
+        if !self
+            .format
+            .as_ref()
+            .is_some_and(|format| format.brace_style == BraceStyle::NextLine)
+        {
+            return false;
+        }
+
+        match &node.node_kind {
+            CSTNodeKind::Function {
+                block_expression_or_semicolon,
+                ..
+            } => {
+                ptr::eq(child, block_expression_or_semicolon.as_ref())
+                    && matches!(child.node_kind, CSTNodeKind::BlockExpression { .. })
+            }
+            CSTNodeKind::Struct {
+                left_brace: Some(left_brace),
+                ..
+            } => ptr::eq(child, left_brace.as_ref()),
+            CSTNodeKind::Implementation { left_brace, .. } => ptr::eq(child, left_brace.as_ref()),
+            _ => false,
+        }
+    }
+
+    // コメントを除いて1行から書き始めたときの表記
+    fn measure(format: &Option<FormatStyle>, nodes: &[&CSTNode]) -> String {
+        let mut printer = Printer::new();
+        printer.format = format.clone();
+        printer.is_measuring = true;
+        for node in nodes {
+            printer.cst_node(node);
+        }
+        printer.finish().trim_end().to_string()
+    }
+}
+
+fn has_blank_line(previous: &CSTNode, next: &CSTNode) -> bool {
+    previous
+        .span()
+        .zip(next.span())
+        .is_some_and(|(previous, next)| next.start.0 > previous.end.0 + 1)
+}
@@ -0,0 +1,501 @@
+use crate::arena::{self, Arena, NodeId};
+use crate::walk::{SyntaxNode, SPAN_FIELDS};
+use std::fmt;
+
+// 木からノードを探すためのセレクタ
+// CSSのセレクタに倣い、ノードの種類とフィールドの条件を組み合わせて書く
+//
+// LetStatement[rarity=Ur] > ExpressionWithoutBlock > CallExpression
+//
+//   Kind            種類がKindのノード `*`ならどの種類でもよい
+//   [field]         fieldがあり、falseでない
+//   [field=value]   fieldの値がvalueと等しい
+//   [field!=value]  fieldの値がvalueと異なる fieldが無いものも含む
+//   [field*=value]  fieldの値がvalueを含む
+//   A > B           Aの子であるB
+//   A B             Aの子孫であるB
+//   A, B            AかBに当てはまるノード
+//
+// フィールドの値はS式やグラフと同じ表示で比べる ただし文字列は`"`を外して比べる
+// 空白や記号を含む値は`"`で囲んで書く
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    selectors: Vec<Selector>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Selector {
+    steps: Vec<Step>, // 最初のステップのcombinatorは使わない
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    combinator: Combinator, // 1つ前のステップとの関係
+    kind: Option<String>,   // Noneなら`*`
+    conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    field: String,
+    comparison: Option<(Comparison, String)>, // Noneならフィールドがあるかだけを見る
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryError {
+    pub column: usize, // 1から数えた文字の位置
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+// 見つかったノード
+// positionは開始位置を持たないノードなら子孫の最初の位置を使う
+pub struct QueryMatch<'a, T> {
+    pub id: NodeId,
+    pub node: &'a T,
+    pub position: Option<(usize, usize)>,
+    pub span: Option<String>, // ノードの範囲
+}
+
+impl<T: SyntaxNode> QueryMatch<'_, T> {
+    // 種類と範囲以外のフィールドを1行で書く
+    pub fn describe(&self) -> String {
+        let mut text = self.node.kind_name().to_string();
+        for (name, value) in self.node.leaf_fields() {
+            if !SPAN_FIELDS.contains(&name) && value != "false" {
+                text.push_str(&format!(" {}={}", name, value));
+            }
+        }
+        text
+    }
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Self, QueryError> {
+        QueryParser::new(text).parse()
+    }
+
+    // 当てはまるノードを前順に返す
+    pub fn find<'a, T: SyntaxNode + Clone>(&self, tree: &'a T) -> Vec<QueryMatch<'a, T>> {
+        let arena = Arena::from_tree(tree.clone());
+        let nodes = arena::preorder(tree);
+        self.find_in(&arena)
+            .into_iter()
+            .map(|id| QueryMatch {
+                id,
+                node: nodes[id.index()].1,
+                position: std::iter::once(id)
+                    .chain(arena.descendants(id))
+                    .find_map(|id| arena.node(id).start_position()),
+                span: arena
+                    .node(id)
+                    .leaf_fields()
+                    .into_iter()
+                    .find(|(name, _)| *name == "span")
+                    .map(|(_, span)| span),
+            })
+            .collect()
+    }
+
+    pub fn find_in<T: SyntaxNode>(&self, arena: &Arena<T>) -> Vec<NodeId> {
+        arena
+            .iter()
+            .map(|(id, _)| id)
+            .filter(|id| {
+                self.selectors
+                    .iter()
+                    .any(|selector| selector.matches(arena, *id))
+            })
+            .collect()
+    }
+}
+
+impl Selector {
+    fn matches<T: SyntaxNode>(&self, arena: &Arena<T>, id: NodeId) -> bool {
+        self.matches_from(arena, self.steps.len() - 1, id)
+    }
+
+    // 右のステップから順に、親や先祖へさかのぼって確かめる
+    fn matches_from<T: SyntaxNode>(&self, arena: &Arena<T>, index: usize, id: NodeId) -> bool {
+        let step = &self.steps[index];
+        if !step.matches(arena.node(id)) {
+            return false;
+        }
+        if index == 0 {
+            return true;
+        }
+
+        match step.combinator {
+            Combinator::Child => arena
+                .parent(id)
+                .is_some_and(|parent| self.matches_from(arena, index - 1, parent)),
+            Combinator::Descendant => arena
+                .ancestors(id)
+                .any(|ancestor| self.matches_from(arena, index - 1, ancestor)),
+        }
+    }
+}
+
+impl Step {
+    fn matches<T: SyntaxNode>(&self, node: &T) -> bool {
+        if self
+            .kind
+            .as_ref()
+            .is_some_and(|kind| kind != node.kind_name())
+        {
+            return false;
+        }
+
+        let fields = node.leaf_fields();
+        self.conditions.iter().all(|condition| {
+            let value = fields
+                .iter()
+                .find(|(name, _)| *name == condition.field)
+                .map(|(_, value)| unquote(value));
+            match (&condition.comparison, value) {
+                (None, Some(value)) => value != "false",
+                (Some((Comparison::Equal, expected)), Some(value)) => value == expected,
+                (Some((Comparison::Contains, expected)), Some(value)) => value.contains(expected),
+                (Some((Comparison::NotEqual, expected)), value) => value != Some(expected),
+                (_, None) => false,
+            }
+        })
+    }
+}
+
+// 文字列のフィールドはDebugで`"`に囲まれている
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+struct QueryParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl QueryParser {
+    fn new(text: &str) -> Self {
+        Self {
+            chars: text.chars().collect(),
+            position: 0,
+        }
+    }
+
+    fn parse(&mut self) -> Result<Query, QueryError> {
+        let mut selectors = vec![self.parse_selector()?];
+        while self.eat(',') {
+            selectors.push(self.parse_selector()?);
+        }
+        Ok(Query { selectors })
+    }
+
+    fn parse_selector(&mut self) -> Result<Selector, QueryError> {
+        self.skip_whitespace();
+        let mut steps = vec![self.parse_step(Combinator::Descendant)?];
+        loop {
+            let has_whitespace = self.skip_whitespace();
+            let combinator = match self.peek() {
+                None | Some(',') => break,
+                Some('>') => {
+                    self.position += 1;
+                    self.skip_whitespace();
+                    Combinator::Child
+                }
+                Some(_) if has_whitespace => Combinator::Descendant,
+                Some(c) => return Err(self.error(&format!("unexpected '{}'", c))),
+            };
+            steps.push(self.parse_step(combinator)?);
+        }
+        Ok(Selector { steps })
+    }
+
+    fn parse_step(&mut self, combinator: Combinator) -> Result<Step, QueryError> {
+        let kind = if self.eat('*') {
+            None
+        } else {
+            let kind = self.parse_name();
+            if kind.is_empty() {
+                return Err(self.error("expected a node kind or '*'"));
+            }
+            Some(kind)
+        };
+
+        let mut conditions = vec![];
+        while self.eat('[') {
+            conditions.push(self.parse_condition()?);
+        }
+        Ok(Step {
+            combinator,
+            kind,
+            conditions,
+        })
+    }
+
+    fn parse_condition(&mut self) -> Result<Condition, QueryError> {
+        self.skip_whitespace();
+        let field = self.parse_name();
+        if field.is_empty() {
+            return Err(self.error("expected a field name"));
+        }
+        self.skip_whitespace();
+
+        let comparison = if self.eat('=') {
+            Some(Comparison::Equal)
+        } else if self.eat_str("!=") {
+            Some(Comparison::NotEqual)
+        } else if self.eat_str("*=") {
+            Some(Comparison::Contains)
+        } else {
+            None
+        };
+        let comparison = match comparison {
+            Some(comparison) => {
+                self.skip_whitespace();
+                let value = self.parse_value()?;
+                self.skip_whitespace();
+                Some((comparison, value))
+            }
+            None => None,
+        };
+
+        if !self.eat(']') {
+            return Err(self.error("expected ']'"));
+        }
+        Ok(Condition { field, comparison })
+    }
+
+    fn parse_value(&mut self) -> Result<String, QueryError> {
+        if !self.eat('"') {
+            let value: String = self.take_while(|c| !c.is_whitespace() && !matches!(c, ']' | '"'));
+            if value.is_empty() {
+                return Err(self.error("expected a value"));
+            }
+            return Ok(value);
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.next() {
+                    Some(c) => value.push(c),
+                    None => return Err(self.error("unterminated string")),
+                },
+                Some(c) => value.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn parse_name(&mut self) -> String {
+        self.take_while(|c| c.is_alphanumeric() || c == '_')
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let start = self.position;
+        while self.peek().is_some_and(&f) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    // 空白を読み飛ばしたかを返す
+    fn skip_whitespace(&mut self) -> bool {
+        !self.take_while(char::is_whitespace).is_empty()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.eat_str(&c.to_string())
+    }
+
+    fn eat_str(&mut self, text: &str) -> bool {
+        let length = text.chars().count();
+        let is_match = self.chars.len() >= self.position + length
+            && text
+                .chars()
+                .zip(&self.chars[self.position..])
+                .all(|(a, b)| a == *b);
+        if is_match {
+            self.position += length;
+        }
+        is_match
+    }
+
+    fn error(&self, message: &str) -> QueryError {
+        QueryError {
+            column: self.position + 1,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ASTNode;
+    use crate::keywords::Keyword;
+    use crate::test_trees::{ast, cst};
+    use crate::token::*;
+
+    // fn f(x: i32) { ur a = 1 + x; sr b = a * 2; ur c = 3; }
+    fn tree() -> ASTNode {
+        ast::crate_node(vec![ast::function(
+            "f",
+            &[("x", "i32")],
+            vec![
+                ast::let_statement(
+                    Rarity::Ur,
+                    "a",
+                    ast::expression(ast::binary(
+                        BinaryOperator::Add,
+                        ast::integer("1"),
+                        ast::path("x"),
+                    )),
+                ),
+                ast::let_statement(
+                    Rarity::Sr,
+                    "b",
+                    ast::expression(ast::binary(
+                        BinaryOperator::Mul,
+                        ast::path("a"),
+                        ast::integer("2"),
+                    )),
+                ),
+                ast::let_statement(Rarity::Ur, "c", ast::integer("3")),
+            ],
+        )])
+    }
+
+    // 見つかったノードを前順に、種類とフィールドで並べる
+    fn find(selector: &str) -> Vec<String> {
+        let tree = tree();
+        Query::parse(selector)
+            .unwrap()
+            .find(&tree)
+            .iter()
+            .map(|found| found.describe())
+            .collect()
+    }
+
+    #[test]
+    fn child_and_descendant_combinators() {
+        assert_eq!(
+            find("LetStatement > ExpressionWithoutBlock > BinaryOperator"),
+            ["BinaryOperator operator=Add", "BinaryOperator operator=Mul"]
+        );
+        // 子孫は何段下でもよいが、子は直下だけ
+        assert_eq!(find("LetStatement > Literal"), Vec::<String>::new());
+        assert_eq!(
+            find("LetStatement Literal"),
+            [
+                "Literal literal=1",
+                "Literal literal=2",
+                "Literal literal=3"
+            ]
+        );
+        assert_eq!(find("* > *").len(), Arena::from_tree(tree()).len() - 1);
+    }
+
+    #[test]
+    fn field_conditions() {
+        assert_eq!(
+            find("LetStatement[rarity=Ur] Literal"),
+            ["Literal literal=1", "Literal literal=3"]
+        );
+        assert_eq!(
+            find("LetStatement[rarity!=Ur] IdentifierPattern"),
+            ["IdentifierPattern identifier=\"b\""]
+        );
+        assert_eq!(
+            find("IdentifierPattern[identifier*=c]"),
+            ["IdentifierPattern identifier=\"c\""]
+        );
+        // 無いフィールドと、falseのフィールド
+        assert_eq!(find("Literal[operator]"), Vec::<String>::new());
+        assert_eq!(find("IdentifierPattern[mut_keyword]"), Vec::<String>::new());
+    }
+
+    // どれかのセレクタに当てはまるノードを、重複させずに前順で返す
+    #[test]
+    fn selector_list() {
+        assert_eq!(
+            find(
+                "Literal[literal=3], LetStatement[rarity=Sr] IdentifierPattern, Literal[literal=3]"
+            ),
+            ["IdentifierPattern identifier=\"b\"", "Literal literal=3"]
+        );
+    }
+
+    // CSTのノードは最初のトークンから最後のトークンまでの範囲を持つ
+    #[test]
+    fn cst_match_position() {
+        // fn f() {
+        //     ur a = 1;
+        // }
+        let tree = cst::block(
+            (1, 8),
+            vec![cst::let_statement(
+                Keyword::Ur,
+                "a",
+                cst::integer("1", (2, 12)),
+                (2, 5),
+            )],
+            (3, 1),
+        );
+        let matches = Query::parse("Statement > LetStatement")
+            .unwrap()
+            .find(&tree);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            (matches[0].position, &matches[0].span),
+            (Some((2, 5)), &Some("2:5-2:14".to_string()))
+        );
+    }
+
+    #[test]
+    fn selector_syntax_errors() {
+        for (selector, column, message) in [
+            ("", 1, "expected a node kind or '*'"),
+            ("LetStatement[", 14, "expected a field name"),
+            ("LetStatement[rarity=Ur", 23, "expected ']'"),
+            ("Literal[literal=\"1]", 20, "unterminated string"),
+            ("A >", 4, "expected a node kind or '*'"),
+            ("A ) B", 3, "expected a node kind or '*'"),
+        ] {
+            let error = Query::parse(selector).unwrap_err();
+            assert_eq!((error.column, error.message.as_str()), (column, message));
+        }
+    }
+}
@@ -0,0 +1,373 @@
+mod binary;
+
+use crate::ast::ASTNode;
+use crate::cst::CSTNode;
+use crate::hst::HSTNode;
+use nagi_errors::{Error, ErrorKind, TreeFileError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// バイナリ形式のファイルの先頭
+// MAGIC、形式のバージョン(u16)、木の種類(u8)、コンパイラのバージョン(長さu8と文字列)、
+// ソースコードのハッシュ(u64)、本体の長さ(u64)、本体のハッシュ(u64)の順に書き、その後に本体を書く
+// 数値はすべてリトルエンディアン
+const MAGIC: &[u8; 4] = b"NAGT";
+pub const FORMAT_VERSION: u16 = 2; // 2: CSTのノードに範囲を持たせた
+pub const COMPILER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// 構文木を書き出す形式
+// Jsonはほかのツールからもそのまま読めるように、Binaryは大きな木を小さく速く読み書きできるようにする
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TreeFormat {
+    Json,
+    Binary,
+}
+
+impl TreeFormat {
+    // ASTを書き出すファイルの拡張子
+    pub fn ast_extension(&self) -> &'static str {
+        match self {
+            TreeFormat::Json => "ast.json",
+            TreeFormat::Binary => "ast",
+        }
+    }
+
+    // 書き出したASTのファイルならその形式 ソースコードならNone
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        [TreeFormat::Json, TreeFormat::Binary]
+            .into_iter()
+            .find(|format| file_name.ends_with(&format!(".{}", format.ast_extension())))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TreeKind {
+    Ast,
+    Cst,
+    Hst,
+}
+
+impl TreeKind {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(TreeKind::Ast),
+            1 => Some(TreeKind::Cst),
+            2 => Some(TreeKind::Hst),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            TreeKind::Ast => "AST",
+            TreeKind::Cst => "CST",
+            TreeKind::Hst => "HST",
+        }
+    }
+}
+
+// ファイルに書き出せる木
+pub trait TreeFile: Serialize + DeserializeOwned {
+    const TREE_KIND: TreeKind;
+}
+
+impl TreeFile for ASTNode {
+    const TREE_KIND: TreeKind = TreeKind::Ast;
+}
+
+impl TreeFile for CSTNode {
+    const TREE_KIND: TreeKind = TreeKind::Cst;
+}
+
+impl TreeFile for HSTNode {
+    const TREE_KIND: TreeKind = TreeKind::Hst;
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TreeHeader {
+    pub format_version: u16,
+    pub tree_kind: TreeKind,
+    pub compiler_version: String,
+    pub source_hash: u64, // 木を作ったソースコードのsource_hash
+}
+
+// 書き出した木が古くなっていないかを比べるためのハッシュ(FNV-1a)
+pub fn source_hash(sorce_code: &str) -> u64 {
+    fnv1a(sorce_code.as_bytes())
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn encode_tree<T: TreeFile>(tree: &T, source_hash: u64) -> Vec<u8> {
+    // 木の型はどれも長さの分かる列だけを持つので失敗しない
+    let body = binary::to_bytes(tree).unwrap();
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.push(T::TREE_KIND as u8);
+    bytes.push(COMPILER_VERSION.len() as u8);
+    bytes.extend_from_slice(COMPILER_VERSION.as_bytes());
+    bytes.extend_from_slice(&source_hash.to_le_bytes());
+    bytes.extend_from_slice(&(body.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&fnv1a(&body).to_le_bytes());
+    bytes.extend_from_slice(&body);
+    bytes
+}
+
+// ヘッダーを確かめてから本体を読む
+pub fn decode_tree<T: TreeFile>(bytes: &[u8]) -> Result<(TreeHeader, T), Error> {
+    let mut reader = HeaderReader { bytes };
+    let invalid_format = || tree_file_error(TreeFileError::InvalidFormat, "not a nagi tree file");
+    if reader.take(MAGIC.len()) != Some(MAGIC) {
+        return Err(invalid_format());
+    }
+
+    let format_version = reader.u16().ok_or_else(invalid_format)?;
+    if format_version != FORMAT_VERSION {
+        return Err(tree_file_error(
+            TreeFileError::VersionMismatch,
+            &format!(
+                "tree format version {} is not supported (expected {})",
+                format_version, FORMAT_VERSION
+            ),
+        ));
+    }
+
+    let tree_kind = reader
+        .take(1)
+        .and_then(|byte| TreeKind::from_byte(byte[0]))
+        .ok_or_else(invalid_format)?;
+    if tree_kind != T::TREE_KIND {
+        return Err(tree_file_error(
+            TreeFileError::InvalidFormat,
+            &format!(
+                "expected {} file, found {} file",
+                T::TREE_KIND.as_str(),
+                tree_kind.as_str()
+            ),
+        ));
+    }
+
+    let compiler_version = reader
+        .take(1)
+        .and_then(|len| reader.take(len[0].into()))
+        .and_then(|version| String::from_utf8(version.to_vec()).ok())
+        .ok_or_else(invalid_format)?;
+    if compiler_version != COMPILER_VERSION {
+        return Err(tree_file_error(
+            TreeFileError::VersionMismatch,
+            &format!(
+                "tree was written by compiler version {} (current version is {})",
+                compiler_version, COMPILER_VERSION
+            ),
+        ));
+    }
+
+    let corrupted = |text: &str| tree_file_error(TreeFileError::Corrupted, text);
+    let (Some(source_hash), Some(body_len), Some(body_hash)) =
+        (reader.u64(), reader.u64(), reader.u64())
+    else {
+        return Err(corrupted("tree file header is truncated"));
+    };
+    let body = reader.bytes;
+    if body.len() as u64 != body_len {
+        return Err(corrupted(&format!(
+            "tree file body has {} bytes (expected {})",
+            body.len(),
+            body_len
+        )));
+    }
+    if fnv1a(body) != body_hash {
+        return Err(corrupted("tree file checksum mismatch"));
+    }
+
+    let tree = binary::from_bytes(body)
+        .map_err(|error| corrupted(&format!("invalid tree file body: {}", error)))?;
+    let header = TreeHeader {
+        format_version,
+        tree_kind,
+        compiler_version,
+        source_hash,
+    };
+    Ok((header, tree))
+}
+
+struct HeaderReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> HeaderReader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(..len)?;
+        self.bytes = &self.bytes[len..];
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+}
+
+// JSONにはヘッダーを付けないので、source_hashはバイナリ形式だけで使う
+pub fn write_tree<T: TreeFile>(
+    file_name: &str,
+    tree: &T,
+    format: TreeFormat,
+    source_hash: u64,
+) -> Result<(), Error> {
+    let bytes = match format {
+        TreeFormat::Json => serde_json::to_vec(tree).map_err(|error| {
+            tree_file_error(
+                TreeFileError::InvalidFormat,
+                &format!("cannot write the tree as JSON: {}", error),
+            )
+        })?,
+        TreeFormat::Binary => encode_tree(tree, source_hash),
+    };
+
+    std::fs::write(file_name, bytes).map_err(|error| {
+        tree_file_error(
+            TreeFileError::Io,
+            &format!("cannot write `{}`: {}", file_name, error),
+        )
+    })
+}
+
+pub fn read_tree<T: TreeFile>(file_name: &str, format: TreeFormat) -> Result<T, Error> {
+    let bytes = std::fs::read(file_name).map_err(|error| {
+        tree_file_error(
+            TreeFileError::Io,
+            &format!("cannot read `{}`: {}", file_name, error),
+        )
+    })?;
+
+    match format {
+        TreeFormat::Json => serde_json::from_slice(&bytes).map_err(|error| {
+            tree_file_error(
+                TreeFileError::Corrupted,
+                &format!("invalid JSON tree: {}", error),
+            )
+        }),
+        TreeFormat::Binary => decode_tree(&bytes).map(|(_, tree)| tree),
+    }
+}
+
+fn tree_file_error(error: TreeFileError, text: &str) -> Error {
+    Error {
+        error_kind: ErrorKind::TreeFile(error),
+        error_text: text.to_string(),
+        position: None,
+        notes: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keywords::Keyword;
+    use crate::test_trees::{ast, cst};
+    use crate::token::*;
+
+    fn function(statement_count: usize) -> ASTNode {
+        let statements = (0..statement_count)
+            .map(|index| {
+                ast::let_statement(
+                    Rarity::Let,
+                    &format!("a{}", index),
+                    ast::expression(ast::binary(
+                        BinaryOperator::Add,
+                        ast::integer(&index.to_string()),
+                        ast::path("x"),
+                    )),
+                )
+            })
+            .collect();
+        ast::with_spans(ast::crate_node(vec![ast::function(
+            "f",
+            &[("x", "i32")],
+            statements,
+        )]))
+    }
+
+    // ASTNodeの比較は範囲を見ないので、JSONにして範囲まで比べる
+    #[test]
+    fn ast_round_trip() {
+        let ast = function(50);
+        let bytes = encode_tree(&ast, source_hash("fn f"));
+        let (header, decoded) = decode_tree::<ASTNode>(&bytes).unwrap();
+        assert_eq!(header.source_hash, source_hash("fn f"));
+        assert_eq!(header.tree_kind, TreeKind::Ast);
+        assert_eq!(
+            serde_json::to_string(&decoded).unwrap(),
+            serde_json::to_string(&ast).unwrap()
+        );
+    }
+
+    #[test]
+    fn cst_round_trip() {
+        // ur b = 3;
+        let cst = cst::let_statement(Keyword::Ur, "b", cst::integer("3", (1, 8)), (1, 1));
+        let (header, decoded) = decode_tree::<CSTNode>(&encode_tree(&cst, 0)).unwrap();
+        assert_eq!(header.tree_kind, TreeKind::Cst);
+        assert_eq!(decoded, cst);
+        assert_eq!(decoded.span, cst.span);
+    }
+
+    #[test]
+    fn smaller_than_json() {
+        let ast = function(50);
+        let bytes = encode_tree(&ast, 0);
+        assert!(bytes.len() * 4 < serde_json::to_vec(&ast).unwrap().len());
+    }
+
+    fn decode_error(bytes: &[u8]) -> ErrorKind {
+        decode_tree::<ASTNode>(bytes).unwrap_err().error_kind
+    }
+
+    // 違う種類の木や違うバージョンのファイルは読まずにエラーにする
+    #[test]
+    fn reject_other_trees_and_versions() {
+        let bytes = encode_tree(&function(1), 0);
+        let mut other_version = bytes.clone();
+        other_version[4] += 1;
+        let cst = cst::integer("1", (1, 1));
+
+        assert_eq!(
+            decode_error(b"{}"),
+            ErrorKind::TreeFile(TreeFileError::InvalidFormat)
+        );
+        assert_eq!(
+            decode_error(&encode_tree(&cst, 0)),
+            ErrorKind::TreeFile(TreeFileError::InvalidFormat)
+        );
+        assert_eq!(
+            decode_error(&other_version),
+            ErrorKind::TreeFile(TreeFileError::VersionMismatch)
+        );
+    }
+
+    // 途中で切れたファイルや中身の壊れたファイル
+    #[test]
+    fn reject_corrupted_files() {
+        let bytes = encode_tree(&function(1), 0);
+        let mut flipped = bytes.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+
+        assert_eq!(
+            decode_error(&bytes[..bytes.len() - 1]),
+            ErrorKind::TreeFile(TreeFileError::Corrupted)
+        );
+        assert_eq!(
+            decode_error(&flipped),
+            ErrorKind::TreeFile(TreeFileError::Corrupted)
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use crate::walk::{SyntaxNode, SPAN_FIELDS};
+
+// 木をS式で書く
+// 1行に1つのノードを書き、子ノードは字下げして続ける
+//
+// (BinaryOperator operator=Add @1:10-1:15
+//   (Literal literal=1 @1:10-1:11)
+//   (Literal literal=2 @1:14-1:15))
+//
+// with_spansなら範囲を`@`に続けて書く CSTはトークンの位置を`@行:列`と書く
+// falseのフィールドは書かない
+
+const INDENT: &str = "  ";
+
+pub fn to_sexpr<T: SyntaxNode>(tree: &T, with_spans: bool) -> String {
+    let mut output = String::new();
+    write_node(&mut output, tree, with_spans, 0);
+    output.push('\n');
+    output
+}
+
+fn write_node<T: SyntaxNode>(output: &mut String, node: &T, with_spans: bool, depth: usize) {
+    output.push('(');
+    output.push_str(node.kind_name());
+
+    let fields = node.leaf_fields();
+    for (name, value) in fields.iter() {
+        if !SPAN_FIELDS.contains(name) && value != "false" {
+            output.push_str(&format!(" {}={}", name, atom(value)));
+        }
+    }
+    if with_spans {
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(field, _)| *field == name)
+                .map(|(_, value)| value.as_str())
+        };
+        match (field("span"), field("row"), field("column")) {
+            (Some(span), _, _) => output.push_str(&format!(" @{}", span)),
+            (None, Some(row), Some(column)) => output.push_str(&format!(" @{}:{}", row, column)),
+            _ => (),
+        }
+    }
+
+    node.for_each_child(&mut |child| {
+        output.push('\n');
+        output.push_str(&INDENT.repeat(depth + 1));
+        write_node(output, child, with_spans, depth + 1);
+    });
+    output.push(')');
+}
+
+// 空白や括弧を含む値は、1つの値と分かるように文字列にする
+// 文字列のフィールドは既に`"`で囲まれている
+fn atom(value: &str) -> String {
+    let is_plain = !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '(' | ')' | '"' | '@'));
+    if value.starts_with('"') || is_plain {
+        value.to_string()
+    } else {
+        format!("{:?}", value)
+    }
+}
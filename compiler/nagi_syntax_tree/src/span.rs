@@ -0,0 +1,54 @@
+use nagi_errors::SourcePosition;
+use serde::{Deserialize, Serialize};
+
+// ソースコード上の範囲
+// startは最初のトークンの位置、endは最後のトークンの直後の位置で、どちらも(行, 列)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl Span {
+    pub fn new(start: (usize, usize), end: (usize, usize)) -> Self {
+        Self { start, end }
+    }
+
+    // startから書かれたtextの範囲
+    // 字句解析器と同じく、改行があれば次の行の1列目に進める
+    pub fn of_text(start: (usize, usize), text: &str) -> Self {
+        Self {
+            start,
+            end: advance(start, text),
+        }
+    }
+
+    // エラーを示す位置 範囲の始まりにする
+    pub fn start_position(&self) -> SourcePosition {
+        SourcePosition {
+            row: self.start.0,
+            column: self.start.1,
+        }
+    }
+
+    // selfの始まりからlastの終わりまで
+    pub fn to(self, last: Span) -> Self {
+        Self {
+            start: self.start,
+            end: last.end,
+        }
+    }
+}
+
+// (行, 列)からtextを読み進めた位置
+pub fn advance(position: (usize, usize), text: &str) -> (usize, usize) {
+    let (mut row, mut column) = position;
+    for c in text.chars() {
+        column += 1;
+        if c == '\n' {
+            row += 1;
+            column = 1;
+        }
+    }
+    (row, column)
+}
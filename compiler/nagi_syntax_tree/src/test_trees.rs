@@ -0,0 +1,257 @@
+// テストで使う小さな木を組み立てる
+// 構文解析器が作る木と同じ形にする
+
+pub mod ast {
+    use crate::ast::{ASTNode, ASTNodeKind};
+    use crate::span::Span;
+    use crate::token::*;
+    use std::rc::Rc;
+
+    fn node(node_kind: ASTNodeKind) -> ASTNode {
+        ASTNode::new(node_kind)
+    }
+
+    pub fn expression(expression: ASTNode) -> ASTNode {
+        node(ASTNodeKind::ExpressionWithoutBlock {
+            outer_attribute: vec![],
+            expression: Rc::new(expression),
+        })
+    }
+
+    // 式としての整数リテラル
+    pub fn integer(symbol: &str) -> ASTNode {
+        expression(node(ASTNodeKind::Literal {
+            literal: Literal::new(LiteralKind::Integer, symbol),
+        }))
+    }
+
+    // 式としての変数
+    pub fn path(identifier: &str) -> ASTNode {
+        expression(node(ASTNodeKind::PathInExpression {
+            path_expr_segment: Rc::new(node(ASTNodeKind::PathExprSegment {
+                path_ident_segment: Rc::new(node(ASTNodeKind::Factor {
+                    token: Token::Identifier(identifier.to_string()),
+                })),
+                generic_args: None,
+            })),
+            repeat_path_expr_segment: vec![],
+        }))
+    }
+
+    // 左辺が演算子式なら括弧で囲まずにそのまま持つ
+    pub fn binary(operator: BinaryOperator, left: ASTNode, right: ASTNode) -> ASTNode {
+        node(ASTNodeKind::BinaryOperator {
+            operator,
+            left: Rc::new(left),
+            right: Rc::new(right),
+        })
+    }
+
+    pub fn identifier_pattern(identifier: &str) -> ASTNode {
+        node(ASTNodeKind::IdentifierPattern {
+            ref_keyword: false,
+            mut_keyword: false,
+            identifier: identifier.to_string(),
+            pattern_no_top_alt: None,
+        })
+    }
+
+    pub fn let_statement(rarity: Rarity, identifier: &str, expression: ASTNode) -> ASTNode {
+        node(ASTNodeKind::Statement {
+            statement: Some(Rc::new(node(ASTNodeKind::LetStatement {
+                outer_attribute: vec![],
+                rarity,
+                pattern_no_top_alt: Rc::new(identifier_pattern(identifier)),
+                type_expression: None,
+                expression: Some(Rc::new(expression)),
+                block_expression: None,
+            }))),
+        })
+    }
+
+    // 引数は(名前, 型の名前)
+    pub fn function(
+        identifier: &str,
+        params: &[(&str, &str)],
+        statements: Vec<ASTNode>,
+    ) -> ASTNode {
+        let function_params = params
+            .iter()
+            .map(|(name, type_name)| {
+                node(ASTNodeKind::FunctionParam {
+                    outer_attribute: vec![],
+                    pattern_no_top_alt: Rc::new(identifier_pattern(name)),
+                    type_expression: Rc::new(node(ASTNodeKind::TypePath {
+                        path_ident_segments: vec![node(ASTNodeKind::Factor {
+                            token: Token::Identifier(type_name.to_string()),
+                        })],
+                    })),
+                })
+            })
+            .collect::<Vec<_>>();
+        let block_expression = node(ASTNodeKind::BlockExpression {
+            inner_attribute: vec![],
+            statements: (!statements.is_empty())
+                .then(|| Rc::new(node(ASTNodeKind::Statements { statements }))),
+        });
+
+        node(ASTNodeKind::Function {
+            outer_attribute: vec![],
+            function_qualifiers: Rc::new(node(ASTNodeKind::FunctionQualifiers {
+                const_keyword: false,
+                async_keyword: false,
+                item_safety: None,
+                extern_keyword: false,
+                abi: None,
+            })),
+            identifier: identifier.to_string(),
+            generic_params: None,
+            function_parameters: (!function_params.is_empty()).then(|| {
+                Rc::new(node(ASTNodeKind::FunctionParameters {
+                    self_param: None,
+                    function_params,
+                }))
+            }),
+            function_return_type: None,
+            where_clause: None,
+            block_expression: Some(Rc::new(block_expression)),
+        })
+    }
+
+    pub fn crate_node(item: Vec<ASTNode>) -> ASTNode {
+        node(ASTNodeKind::Crate {
+            inner_attribute: vec![],
+            item,
+        })
+    }
+
+    // 前順に1行ずつずらした範囲を全てのノードに付ける
+    pub fn with_spans(mut tree: ASTNode) -> ASTNode {
+        fn set_spans(node: &mut ASTNode, row: &mut usize) {
+            *row += 1;
+            node.span = Some(Span::new((*row, 1), (*row, 2)));
+            node.for_each_child_mut(&mut |child| set_spans(child, row));
+        }
+
+        set_spans(&mut tree, &mut 0);
+        tree
+    }
+}
+
+pub mod cst {
+    use crate::cst::{CSTNode, CSTNodeKind};
+    use crate::keywords::Keyword;
+    use crate::token::*;
+    use std::rc::Rc;
+
+    pub fn factor(token: Token, (row, column): (usize, usize)) -> CSTNode {
+        CSTNode::new(CSTNodeKind::Factor { token, row, column }, vec![])
+    }
+
+    pub fn expression(expression: CSTNode) -> CSTNode {
+        CSTNode::new(
+            CSTNodeKind::ExpressionWithoutBlock {
+                outer_attribute: vec![],
+                expression: Rc::new(expression),
+            },
+            vec![],
+        )
+    }
+
+    // 式としての整数リテラル
+    pub fn integer(symbol: &str, (row, column): (usize, usize)) -> CSTNode {
+        expression(CSTNode::new(
+            CSTNodeKind::Literal {
+                literal: Literal::new(LiteralKind::Integer, symbol),
+                row,
+                column,
+            },
+            vec![],
+        ))
+    }
+
+    pub fn binary(
+        token: Token,
+        (row, column): (usize, usize),
+        left: CSTNode,
+        right: CSTNode,
+    ) -> CSTNode {
+        expression(CSTNode::new(
+            CSTNodeKind::Operator { token, row, column },
+            vec![left, right],
+        ))
+    }
+
+    // `rarity identifier = expression;`
+    // 位置はrarityの位置 間の空白は1文字とする
+    pub fn let_statement(
+        rarity: Keyword,
+        identifier: &str,
+        expression: CSTNode,
+        (row, column): (usize, usize),
+    ) -> CSTNode {
+        let identifier_column = column + rarity.as_str().len() + 1;
+        let equal_column = identifier_column + identifier.len() + 1;
+        let semicolon = expression.span.unwrap().end;
+
+        let let_statement = CSTNode::new(
+            CSTNodeKind::LetStatement {
+                outer_attribute: vec![],
+                rarity: Rc::new(factor(Token::Keyword(rarity), (row, column))),
+                pattern_no_top_alt: Rc::new(CSTNode::new(
+                    CSTNodeKind::IdentifierPattern {
+                        ref_keyword: None,
+                        mut_keyword: None,
+                        identifier: Rc::new(factor(
+                            Token::Identifier(identifier.to_string()),
+                            (row, identifier_column),
+                        )),
+                        at_symbol: None,
+                        pattern_no_top_alt: None,
+                    },
+                    vec![],
+                )),
+                colon: None,
+                type_expression: None,
+                equal: Some(Rc::new(factor(Token::Equal, (row, equal_column)))),
+                expression: Some(Rc::new(expression)),
+                else_keyword: None,
+                block_expression: None,
+                semicolon: Rc::new(factor(Token::Semicolon, semicolon)),
+            },
+            vec![],
+        );
+        CSTNode::new(
+            CSTNodeKind::Statement {
+                statement: Rc::new(let_statement),
+            },
+            vec![],
+        )
+    }
+
+    pub fn statements(statements: Vec<CSTNode>) -> CSTNode {
+        CSTNode::new(CSTNodeKind::Statements, statements)
+    }
+    // 波括弧の位置と、その間の文
+    pub fn block(
+        left_brace: (usize, usize),
+        statements: Vec<CSTNode>,
+        right_brace: (usize, usize),
+    ) -> CSTNode {
+        CSTNode::new(
+            CSTNodeKind::BlockExpression {
+                left_brace: Rc::new(factor(
+                    Token::LeftParenthesis(LeftParenthesis::Brace),
+                    left_brace,
+                )),
+                inner_attribute: vec![],
+                statements: Some(Rc::new(self::statements(statements))),
+                right_brace: Rc::new(factor(
+                    Token::RightParenthesis(RightParenthesis::Brace),
+                    right_brace,
+                )),
+            },
+            vec![],
+        )
+    }
+}
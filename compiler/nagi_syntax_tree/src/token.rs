@@ -1,19 +1,81 @@
 use crate::keywords::Keyword;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
-pub enum BinaryOperator {
-    Equal,
-    Add,
-    Sub,
-    Mul,
-    Div,
-    Mod,
-    Xor,
-    Or,
-    And,
-    LeftShift,
-    RightShiht,
+// 演算子と優先順位はnagi_macrosの表にある
+nagi_macros::define_operators! {
+    Token;
+
+    #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+    pub enum UnaryOperator {}
+
+    #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+    pub enum BinaryOperator {}
+}
+
+// コンパイラに組み込まれたマクロ
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum BuiltinMacro {
+    Print,
+    Println,
+    Eprint,
+    Eprintln,
+    Format,
+    Panic,
+    Assert,
+    AssertEq,
+    AssertNe,
+    IncludeStr,
+    IncludeBytes,
+}
+
+impl BuiltinMacro {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let builtin_macro = match name {
+            "print" => Self::Print,
+            "println" => Self::Println,
+            "eprint" => Self::Eprint,
+            "eprintln" => Self::Eprintln,
+            "format" => Self::Format,
+            "panic" => Self::Panic,
+            "assert" => Self::Assert,
+            "assert_eq" => Self::AssertEq,
+            "assert_ne" => Self::AssertNe,
+            "include_str" => Self::IncludeStr,
+            "include_bytes" => Self::IncludeBytes,
+            _ => return None,
+        };
+
+        Some(builtin_macro)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Print => "print",
+            Self::Println => "println",
+            Self::Eprint => "eprint",
+            Self::Eprintln => "eprintln",
+            Self::Format => "format",
+            Self::Panic => "panic",
+            Self::Assert => "assert",
+            Self::AssertEq => "assert_eq",
+            Self::AssertNe => "assert_ne",
+            Self::IncludeStr => "include_str",
+            Self::IncludeBytes => "include_bytes",
+        }
+    }
+
+    // 書式文字列が何番目の引数か
+    // assert!系は条件の後に書式文字列を書ける
+    pub fn format_string_index(&self) -> Option<usize> {
+        match self {
+            Self::Print | Self::Println | Self::Eprint | Self::Eprintln | Self::Format => Some(0),
+            Self::Panic => Some(0),
+            Self::Assert => Some(1),
+            Self::AssertEq | Self::AssertNe => Some(2),
+            Self::IncludeStr | Self::IncludeBytes => None,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -39,6 +101,17 @@ pub enum LeftParenthesis {
     Brace,       // {
 }
 
+impl LeftParenthesis {
+    // 対応する閉じ括弧
+    pub fn closing(&self) -> RightParenthesis {
+        match self {
+            LeftParenthesis::Parenthesis => RightParenthesis::Parenthesis,
+            LeftParenthesis::Brackets => RightParenthesis::Brackets,
+            LeftParenthesis::Brace => RightParenthesis::Brace,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum RightParenthesis {
     Parenthesis, // )
@@ -65,67 +138,50 @@ impl Literal {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
-pub enum Token {
-    // literal
-    Literal(Literal),
-    Identifier(String),
-    Keyword(Keyword),
-
-    //
-    LeftParenthesis(LeftParenthesis),
-    RightParenthesis(RightParenthesis),
-
-    // operator
-    Plus,               // +
-    Minus,              // -
-    Star,               // *
-    Slash,              // /
-    Percent,            // %
-    Caret,              // ^
-    Not,                // !
-    And,                // &
-    Or,                 // |
-    AndAnd,             // &&
-    OrOr,               // ||
-    LeftShift,          // <<
-    RightShift,         // >>
-    PlusEqual,          // +=
-    MinusEqual,         // -=
-    StarEqual,          // *=
-    SlashEqual,         // /=
-    PercentEqual,       // %=
-    CaretEqual,         // ^=
-    AndEqual,           // &=
-    OrEqual,            // |=
-    LeftShiftEqual,     // <<=
-    RightShiftEqual,    // >>=
-    Equal,              // =
-    EqualEqual,         // ==
-    NotEqual,           // !=
-    GreaterThan,        // >
-    LessThan,           // <
-    GreaterThanOrEqual, // >=
-    LessThanOrEqual,    // <=
-    At,                 // @
-    Underscore,         // _
-    Dot,                // .
-    DotDot,             // ..
-    DotDotDot,          // ...
-    DotDotEqual,        // ..=
-    Comma,              // ,
-    Semicolon,          // ;
-    Colon,              // :
-    PathSeparater,      // ::
-    RightAllow,         // ->
-    FatAllow,           // =>
-    LeftAllow,          // <-
-    Pound,              // #
-    Dollar,             // $
-    Question,           // ?
-    Tilde,              // ~
-
-    Eof,
+// 記号はnagi_macrosの表から付け足す
+nagi_macros::define_tokens! {
+    #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+    pub enum Token {
+        // literal
+        Literal(Literal),
+        Identifier(String),
+        Keyword(Keyword),
+
+        //
+        LeftParenthesis(LeftParenthesis),
+        RightParenthesis(RightParenthesis),
+
+        Eof,
+    }
+}
+
+// ソースコード上での表記
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Token::Literal(literal) => {
+                return match literal.literal_kind {
+                    LiteralKind::Bool(value) => write!(f, "{}", value),
+                    _ => write!(f, "{}{}{}", literal.prefix, literal.symbol, literal.suffix),
+                };
+            }
+            Token::Identifier(identifier) => identifier.as_str(),
+            Token::Keyword(keyword) => keyword.as_str(),
+
+            Token::LeftParenthesis(LeftParenthesis::Parenthesis) => "(",
+            Token::LeftParenthesis(LeftParenthesis::Brackets) => "[",
+            Token::LeftParenthesis(LeftParenthesis::Brace) => "{",
+            Token::RightParenthesis(RightParenthesis::Parenthesis) => ")",
+            Token::RightParenthesis(RightParenthesis::Brackets) => "]",
+            Token::RightParenthesis(RightParenthesis::Brace) => "}",
+
+            Token::Eof => "",
+
+            punctuation => punctuation.punctuation_str().unwrap(),
+        };
+
+        write!(f, "{}", symbol)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, Hash)]
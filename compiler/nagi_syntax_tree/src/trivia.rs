@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+// 構文木に入らないコメント
+// 整形するときに、位置を手がかりにトークンの間へ戻す
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Comment {
+    pub text: String,
+    pub position: (usize, usize), // 先頭の(行, 列)
+}
+
+impl Comment {
+    pub fn new(text: &str, position: (usize, usize)) -> Self {
+        Self {
+            text: text.to_string(),
+            position,
+        }
+    }
+
+    // `//`のコメントは行末まで続くので、後ろで必ず改行する
+    pub fn is_line_comment(&self) -> bool {
+        self.text.starts_with("//")
+    }
+
+    // 最後の文字がある行
+    pub fn end_row(&self) -> usize {
+        self.position.0 + self.text.matches('\n').count()
+    }
+}
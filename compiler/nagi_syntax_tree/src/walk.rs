@@ -0,0 +1,524 @@
+use crate::arena::ArenaNode;
+use crate::span::Span;
+use crate::token::*;
+use std::rc::Rc;
+
+// 値の中にあるN型のノードをフィールドの順にたどる
+// ノードの定義をdefine_syntax_tree!で囲むと、ノードとその種類の実装が作られる
+pub trait Walk<N> {
+    fn walk<'a>(&'a self, f: &mut dyn FnMut(&'a N));
+    fn walk_mut(&mut self, f: &mut dyn FnMut(&mut N));
+    fn map(self, f: &mut dyn FnMut(N) -> N) -> Self;
+
+    // ノード以外の部分を表示する ノードしか含まなければNone
+    fn leaf(&self) -> Option<String>;
+}
+
+// 共有されているRcは書き換える前に複製する
+impl<N, T: Walk<N> + Clone> Walk<N> for Rc<T> {
+    fn walk<'a>(&'a self, f: &mut dyn FnMut(&'a N)) {
+        self.as_ref().walk(f);
+    }
+
+    fn walk_mut(&mut self, f: &mut dyn FnMut(&mut N)) {
+        Rc::make_mut(self).walk_mut(f);
+    }
+
+    fn map(self, f: &mut dyn FnMut(N) -> N) -> Self {
+        Rc::new(Walk::map(Rc::unwrap_or_clone(self), f))
+    }
+
+    fn leaf(&self) -> Option<String> {
+        self.as_ref().leaf()
+    }
+}
+
+impl<N, T: Walk<N>> Walk<N> for Option<T> {
+    fn walk<'a>(&'a self, f: &mut dyn FnMut(&'a N)) {
+        if let Some(value) = self {
+            value.walk(f);
+        }
+    }
+
+    fn walk_mut(&mut self, f: &mut dyn FnMut(&mut N)) {
+        if let Some(value) = self {
+            value.walk_mut(f);
+        }
+    }
+
+    fn map(self, f: &mut dyn FnMut(N) -> N) -> Self {
+        self.map(|value| Walk::map(value, f))
+    }
+
+    fn leaf(&self) -> Option<String> {
+        self.as_ref().and_then(|value| value.leaf())
+    }
+}
+
+impl<N, T: Walk<N>> Walk<N> for Vec<T> {
+    fn walk<'a>(&'a self, f: &mut dyn FnMut(&'a N)) {
+        for value in self {
+            value.walk(f);
+        }
+    }
+
+    fn walk_mut(&mut self, f: &mut dyn FnMut(&mut N)) {
+        for value in self {
+            value.walk_mut(f);
+        }
+    }
+
+    fn map(self, f: &mut dyn FnMut(N) -> N) -> Self {
+        self.into_iter().map(|value| Walk::map(value, f)).collect()
+    }
+
+    fn leaf(&self) -> Option<String> {
+        let values: Vec<String> = self.iter().filter_map(|value| value.leaf()).collect();
+        (!values.is_empty()).then(|| format!("[{}]", values.join(", ")))
+    }
+}
+
+impl<N, A: Walk<N>, B: Walk<N>> Walk<N> for (A, B) {
+    fn walk<'a>(&'a self, f: &mut dyn FnMut(&'a N)) {
+        self.0.walk(f);
+        self.1.walk(f);
+    }
+
+    fn walk_mut(&mut self, f: &mut dyn FnMut(&mut N)) {
+        self.0.walk_mut(f);
+        self.1.walk_mut(f);
+    }
+
+    fn map(self, f: &mut dyn FnMut(N) -> N) -> Self {
+        (Walk::map(self.0, f), Walk::map(self.1, f))
+    }
+
+    fn leaf(&self) -> Option<String> {
+        match (self.0.leaf(), self.1.leaf()) {
+            (Some(first), Some(second)) => Some(format!("({}, {})", first, second)),
+            (first, second) => first.or(second),
+        }
+    }
+}
+
+// 差分やグラフで使う、ノードの種類と子ノード以外のフィールド
+// kind_nameとleaf_fieldsはdefine_syntax_tree!で作ったものを返す
+pub trait SyntaxNode: ArenaNode {
+    fn kind_name(&self) -> &'static str;
+    fn leaf_fields(&self) -> Vec<(&'static str, String)>;
+    fn start_position(&self) -> Option<(usize, usize)>; // ソースコード上の(行, 列)
+}
+
+// 範囲を表すフィールド
+pub const SPAN_FIELDS: [&str; 3] = ["span", "row", "column"];
+
+// ノードを含まない型
+// 値の表示の仕方を書かなければDebugで表示する
+macro_rules! impl_walk_for_leaf {
+    ($($leaf:ty => |$value:ident| $text:expr),* $(,)?) => {
+        $(
+            impl<N> Walk<N> for $leaf {
+                fn walk<'a>(&'a self, _: &mut dyn FnMut(&'a N)) {}
+                fn walk_mut(&mut self, _: &mut dyn FnMut(&mut N)) {}
+                fn map(self, _: &mut dyn FnMut(N) -> N) -> Self {
+                    self
+                }
+
+                fn leaf(&self) -> Option<String> {
+                    let $value = self;
+                    Some($text)
+                }
+            }
+        )*
+    };
+    ($($leaf:ty),* $(,)?) => {
+        impl_walk_for_leaf!($($leaf => |value| format!("{:?}", value)),*);
+    };
+}
+
+impl_walk_for_leaf!(
+    bool,
+    usize,
+    String,
+    BinaryOperator,
+    UnaryOperator,
+    LeftParenthesis,
+    BuiltinMacro,
+    Rarity
+);
+
+// トークンはソースコード上の表記で、範囲は`行:列-行:列`で表示する
+impl_walk_for_leaf!(
+    Span => |span| format!(
+        "{}:{}-{}:{}",
+        span.start.0, span.start.1, span.end.0, span.end.1
+    ),
+    Token => |token| token.to_string(),
+    Literal => |literal| Token::Literal(literal.clone()).to_string(),
+);
+
+// ノードの構造体と種類の列挙型を定義し、子ノードをたどる関数とvisitモジュールを作る
+// 列挙型の各バリアントには`#[visit(訪問するメソッド名, 畳み込むメソッド名)]`を付ける
+//
+// visitモジュール
+//   Visitor    : &Nodeでたどる
+//   VisitorMut : &mut Nodeでたどり、その場で書き換える
+//   Folder     : Nodeを受け取り、新しいNodeを返す
+// visit_nodeとfold_nodeは種類ごとのメソッドに振り分け、種類ごとのメソッドは子ノードをたどる
+// 必要なノードのメソッドだけを上書きし、子ノードもたどるならwalk_*を呼ぶ
+macro_rules! define_syntax_tree {
+    (
+        $(#[$node_meta:meta])*
+        pub struct $node:ident {
+            $(pub $node_field:ident: $node_type:ty,)*
+        }
+
+        $(#[$kind_meta:meta])*
+        pub enum $kind:ident {
+            $(
+                #[visit($visit:ident, $fold:ident)]
+                $variant:ident $({
+                    $($field:ident: $type:ty),* $(,)?
+                })?,
+            )*
+        }
+    ) => {
+        $(#[$node_meta])*
+        pub struct $node {
+            $(pub $node_field: $node_type,)*
+        }
+
+        $(#[$kind_meta])*
+        pub enum $kind {
+            $(
+                $variant $({
+                    $($field: $type,)*
+                })?,
+            )*
+        }
+
+        impl $node {
+            // 子ノードをフィールドの順にたどる
+            pub fn for_each_child<'a>(&'a self, f: &mut dyn FnMut(&'a $node)) {
+                $($crate::walk::Walk::<$node>::walk(&self.$node_field, f);)*
+            }
+
+            pub fn for_each_child_mut(&mut self, f: &mut dyn FnMut(&mut $node)) {
+                $($crate::walk::Walk::<$node>::walk_mut(&mut self.$node_field, f);)*
+            }
+
+            pub fn map_children(self, f: &mut dyn FnMut($node) -> $node) -> Self {
+                Self {
+                    $($node_field: $crate::walk::Walk::<$node>::map(self.$node_field, f),)*
+                }
+            }
+
+            pub fn kind_name(&self) -> &'static str {
+                match &self.node_kind {
+                    $($kind::$variant { .. } => stringify!($variant),)*
+                }
+            }
+
+            // 子ノード以外のフィールドの名前と値
+            pub fn leaf_fields(&self) -> Vec<(&'static str, String)> {
+                let mut fields = vec![];
+                match &self.node_kind {
+                    $($kind::$variant $({ $($field,)* })? => {
+                        $($(
+                            if let Some(value) = $crate::walk::Walk::<$node>::leaf($field) {
+                                fields.push((stringify!($field), value));
+                            }
+                        )*)?
+                    })*
+                }
+                $(
+                    if let Some(value) = $crate::walk::Walk::<$node>::leaf(&self.$node_field) {
+                        fields.push((stringify!($node_field), value));
+                    }
+                )*
+                fields
+            }
+        }
+
+        impl $crate::arena::ArenaNode for $node {
+            fn for_each_child<'a>(&'a self, f: &mut dyn FnMut(&'a $node)) {
+                $node::for_each_child(self, f);
+            }
+
+            fn map_children(self, f: &mut dyn FnMut($node) -> $node) -> $node {
+                $node::map_children(self, f)
+            }
+        }
+
+        // フィールドにあるノードはそれ自身が子ノード
+        impl $crate::walk::Walk<$node> for $node {
+            fn walk<'a>(&'a self, f: &mut dyn FnMut(&'a $node)) {
+                f(self);
+            }
+
+            fn walk_mut(&mut self, f: &mut dyn FnMut(&mut $node)) {
+                f(self);
+            }
+
+            fn map(self, f: &mut dyn FnMut($node) -> $node) -> Self {
+                f(self)
+            }
+
+            fn leaf(&self) -> Option<String> {
+                None
+            }
+        }
+
+        impl $crate::walk::Walk<$node> for $kind {
+            fn walk<'a>(&'a self, f: &mut dyn FnMut(&'a $node)) {
+                match self {
+                    $($kind::$variant $({ $($field,)* })? => {
+                        $($($crate::walk::Walk::<$node>::walk($field, f);)*)?
+                    })*
+                }
+            }
+
+            fn walk_mut(&mut self, f: &mut dyn FnMut(&mut $node)) {
+                match self {
+                    $($kind::$variant $({ $($field,)* })? => {
+                        $($($crate::walk::Walk::<$node>::walk_mut($field, f);)*)?
+                    })*
+                }
+            }
+
+            fn map(self, f: &mut dyn FnMut($node) -> $node) -> Self {
+                match self {
+                    $($kind::$variant $({ $($field,)* })? => $kind::$variant $({
+                        $($field: $crate::walk::Walk::<$node>::map($field, f),)*
+                    })?,)*
+                }
+            }
+
+            // 種類のフィールドはleaf_fieldsで1つずつ表示する
+            fn leaf(&self) -> Option<String> {
+                None
+            }
+        }
+
+        pub mod visit {
+            use super::{$kind, $node};
+
+            pub trait Visitor<'a> {
+                fn visit_node(&mut self, node: &'a $node) {
+                    walk_node(self, node);
+                }
+
+                $(fn $visit(&mut self, node: &'a $node) {
+                    walk_children(self, node);
+                })*
+            }
+
+            // 種類ごとのメソッドに振り分ける
+            pub fn walk_node<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, node: &'a $node) {
+                match &node.node_kind {
+                    $($kind::$variant { .. } => visitor.$visit(node),)*
+                }
+            }
+
+            pub fn walk_children<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, node: &'a $node) {
+                node.for_each_child(&mut |child| visitor.visit_node(child));
+            }
+
+            pub trait VisitorMut {
+                fn visit_node(&mut self, node: &mut $node) {
+                    walk_node_mut(self, node);
+                }
+
+                $(fn $visit(&mut self, node: &mut $node) {
+                    walk_children_mut(self, node);
+                })*
+            }
+
+            pub fn walk_node_mut<V: VisitorMut + ?Sized>(visitor: &mut V, node: &mut $node) {
+                match &node.node_kind {
+                    $($kind::$variant { .. } => visitor.$visit(node),)*
+                }
+            }
+
+            pub fn walk_children_mut<V: VisitorMut + ?Sized>(visitor: &mut V, node: &mut $node) {
+                node.for_each_child_mut(&mut |child| visitor.visit_node(child));
+            }
+
+            pub trait Folder {
+                fn fold_node(&mut self, node: $node) -> $node {
+                    walk_node_fold(self, node)
+                }
+
+                $(fn $fold(&mut self, node: $node) -> $node {
+                    walk_children_fold(self, node)
+                })*
+            }
+
+            pub fn walk_node_fold<F: Folder + ?Sized>(folder: &mut F, node: $node) -> $node {
+                match &node.node_kind {
+                    $($kind::$variant { .. } => folder.$fold(node),)*
+                }
+            }
+
+            pub fn walk_children_fold<F: Folder + ?Sized>(folder: &mut F, node: $node) -> $node {
+                node.map_children(&mut |child| folder.fold_node(child))
+            }
+        }
+    };
+}
+
+pub(crate) use define_syntax_tree;
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::visit::{walk_children, walk_node, Folder, Visitor, VisitorMut};
+    use crate::ast::{ASTNode, ASTNodeKind};
+    use crate::cst::{self, CSTNode};
+    use crate::keywords::Keyword;
+    use crate::test_trees::{ast, cst as cst_tree};
+    use crate::token::*;
+
+    // 識別子パターンの名前と、たどったノードの数
+    #[derive(Default)]
+    struct PatternCollector {
+        identifiers: Vec<String>,
+        node_count: usize,
+    }
+
+    impl Visitor<'_> for PatternCollector {
+        fn visit_node(&mut self, node: &ASTNode) {
+            self.node_count += 1;
+            walk_node(self, node);
+        }
+
+        fn visit_identifier_pattern(&mut self, node: &ASTNode) {
+            if let ASTNodeKind::IdentifierPattern { identifier, .. } = &node.node_kind {
+                self.identifiers.push(identifier.clone());
+            }
+            walk_children(self, node);
+        }
+    }
+
+    struct PatternRenamer;
+
+    impl VisitorMut for PatternRenamer {
+        fn visit_identifier_pattern(&mut self, node: &mut ASTNode) {
+            if let ASTNodeKind::IdentifierPattern { identifier, .. } = &mut node.node_kind {
+                identifier.push('_');
+            }
+        }
+    }
+
+    struct LiteralReplacer(ASTNode);
+
+    impl Folder for LiteralReplacer {
+        fn fold_literal(&mut self, _: ASTNode) -> ASTNode {
+            self.0.clone()
+        }
+    }
+
+    #[derive(Default)]
+    struct TokenCounter(usize);
+
+    impl cst::visit::Visitor<'_> for TokenCounter {
+        fn visit_factor(&mut self, _: &CSTNode) {
+            self.0 += 1;
+        }
+
+        fn visit_literal(&mut self, _: &CSTNode) {
+            self.0 += 1;
+        }
+
+        fn visit_operator(&mut self, node: &CSTNode) {
+            self.0 += 1;
+            cst::visit::walk_children(self, node);
+        }
+    }
+
+    // fn f(x: i32) { let a = 1 + x; ur b = a * 2; }
+    fn function(one: &str, two: &str) -> ASTNode {
+        ast::crate_node(vec![ast::function(
+            "f",
+            &[("x", "i32")],
+            vec![
+                ast::let_statement(
+                    Rarity::Let,
+                    "a",
+                    ast::expression(ast::binary(
+                        BinaryOperator::Add,
+                        ast::integer(one),
+                        ast::path("x"),
+                    )),
+                ),
+                ast::let_statement(
+                    Rarity::Ur,
+                    "b",
+                    ast::expression(ast::binary(
+                        BinaryOperator::Mul,
+                        ast::path("a"),
+                        ast::integer(two),
+                    )),
+                ),
+            ],
+        )])
+    }
+
+    fn node_count(node: &ASTNode) -> usize {
+        1 + node.children().into_iter().map(node_count).sum::<usize>()
+    }
+
+    #[test]
+    fn visitor_visits_every_node() {
+        let mut collector = PatternCollector::default();
+        let ast = function("1", "2");
+        collector.visit_node(&ast);
+        assert_eq!(collector.identifiers, ["x", "a", "b"]);
+        assert_eq!(collector.node_count, node_count(&ast));
+    }
+
+    #[test]
+    fn visitor_mut_edits_in_place() {
+        let mut ast = function("1", "2");
+        PatternRenamer.visit_node(&mut ast);
+
+        let mut collector = PatternCollector::default();
+        collector.visit_node(&ast);
+        assert_eq!(collector.identifiers, ["x_", "a_", "b_"]);
+    }
+
+    // 全てのリテラルを0に置き換える
+    #[test]
+    fn folder_rebuilds_tree() {
+        let ASTNodeKind::ExpressionWithoutBlock { expression, .. } = ast::integer("0").node_kind
+        else {
+            unreachable!();
+        };
+        let folded = LiteralReplacer((*expression).clone()).fold_node(function("1", "2"));
+        assert_eq!(folded, function("0", "0"));
+    }
+
+    // CSTはnode_kindの中のノードとchildrenの両方をたどる
+    #[test]
+    fn cst_visitor_walks_node_kind_and_children() {
+        // let a = 1 + 2;
+        // ur b = 3;
+        let cst = cst_tree::statements(vec![
+            cst_tree::let_statement(
+                Keyword::Let,
+                "a",
+                cst_tree::binary(
+                    Token::Plus,
+                    (1, 11),
+                    cst_tree::integer("1", (1, 9)),
+                    cst_tree::integer("2", (1, 13)),
+                ),
+                (1, 1),
+            ),
+            cst_tree::let_statement(Keyword::Ur, "b", cst_tree::integer("3", (2, 8)), (2, 1)),
+        ]);
+        let mut counter = TokenCounter::default();
+        cst::visit::Visitor::visit_node(&mut counter, &cst);
+        assert_eq!(counter.0, cst.tokens().len());
+        assert_eq!(counter.0, 12);
+    }
+}